@@ -206,6 +206,8 @@ pub struct ContainerSpec {
     /// Key-Map of environment variables
     /// Starts with RUST_LOG=debug,h2=info
     env: HashMap<String, String>,
+    /// Host paths mounted into the container, as host:container[:ro|rw]
+    binds: Vec<String>,
 }
 
 impl ContainerSpec {
@@ -260,6 +262,43 @@ impl ContainerSpec {
         self
     }
 
+    /// Mount a host path into the container, given as
+    /// host_path:container_path with an optional :ro or :rw suffix, eg to
+    /// back an aio bdev with a file of the host. Can be used more than
+    /// once. The specs are checked when the containers are built.
+    pub fn with_bind(mut self, bind: &str) -> Self {
+        self.binds.push(bind.into());
+        self
+    }
+
+    /// check that each bind is well formed and that its host path exists
+    fn check_binds(&self) -> Result<(), String> {
+        for bind in &self.binds {
+            let parts = bind.split(':').collect::<Vec<_>>();
+            let well_formed = match parts.as_slice() {
+                [host, container] | [host, container, "ro"]
+                | [host, container, "rw"] => {
+                    host.starts_with('/') && container.starts_with('/')
+                }
+                _ => false,
+            };
+            if !well_formed {
+                return Err(format!(
+                    "invalid bind {} of container {}, expected \
+                     host_path:container_path[:ro|rw] with absolute paths",
+                    bind, self.name
+                ));
+            }
+            if !std::path::Path::new(parts[0]).exists() {
+                return Err(format!(
+                    "host path {} of bind {} of container {} does not exist",
+                    parts[0], bind, self.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Environment variables as a vector with each element as:
     /// "{key}={value}"
     fn environment(&self) -> Vec<String> {
@@ -432,6 +471,9 @@ impl Builder {
         self,
     ) -> Result<ComposeTest, Box<dyn std::error::Error>> {
         let net: Ipv4Network = self.network.parse()?;
+        for spec in &self.containers {
+            spec.check_binds()?;
+        }
 
         let path = std::path::PathBuf::from(std::env!("CARGO_MANIFEST_DIR"));
         let srcdir = path.parent().unwrap().to_string_lossy().into();
@@ -742,12 +784,15 @@ impl ComposeTest {
                 .await;
         }
 
+        let mut binds = vec![
+            format!("{}:{}", self.srcdir, self.srcdir),
+            "/nix:/nix:ro".into(),
+            "/dev/hugepages:/dev/hugepages:rw".into(),
+        ];
+        binds.extend(spec.binds.iter().cloned());
+
         let host_config = HostConfig {
-            binds: Some(vec![
-                format!("{}:{}", self.srcdir, self.srcdir),
-                "/nix:/nix:ro".into(),
-                "/dev/hugepages:/dev/hugepages:rw".into(),
-            ]),
+            binds: Some(binds),
             mounts: Some(vec![
                 // DPDK needs to have a /tmp
                 Mount {
@@ -1098,6 +1143,28 @@ mod tests {
     use super::*;
     use rpc::mayastor::Null;
 
+    #[test]
+    fn binds() {
+        let spec = |bind: &str| {
+            ContainerSpec::from_binary("ms", Binary::from_dbg("mayastor"))
+                .with_bind(bind)
+        };
+        for bind in &["/tmp:/host", "/tmp:/host:ro", "/tmp:/host:rw"] {
+            assert!(spec(bind).check_binds().is_ok(), "{} is refused", bind);
+        }
+        for bind in &[
+            "",
+            "/tmp",
+            "tmp:/host",
+            "/tmp:host",
+            "/tmp:/host:rx",
+            "/tmp:/host:ro:rw",
+            "/no/such/path:/host",
+        ] {
+            assert!(spec(bind).check_binds().is_err(), "{} is accepted", bind);
+        }
+    }
+
     #[tokio::test]
     async fn compose() {
         let test = Builder::new()
@@ -90,6 +90,20 @@ function createK8sVolumeObject (volume) {
   return obj;
 }
 
+// Create k8s snapshot object as returned by CSI snapshot methods.
+//
+// @param   {object} snapshot   Snapshot object.
+// @returns {object} K8s CSI snapshot object.
+function createK8sSnapshotObject (snapshot) {
+  return {
+    snapshotId: snapshot.name,
+    sourceVolumeId: snapshot.volumeUuid,
+    sizeBytes: snapshot.size,
+    creationTime: { seconds: snapshot.timestamp, nanos: 0 },
+    readyToUse: true
+  };
+}
+
 // CSI Controller implementation.
 //
 // It implements Identity and Controller grpc services from csi proto file.
@@ -121,7 +135,7 @@ class CsiServer {
     // and request/response logging to avoid repeating code.
     const self = this;
     const controllerMethods = {};
-    let methodNames = [
+    const methodNames = [
       'createVolume',
       'deleteVolume',
       'controllerPublishVolume',
@@ -129,7 +143,11 @@ class CsiServer {
       'validateVolumeCapabilities',
       'listVolumes',
       'getCapacity',
-      'controllerGetCapabilities'
+      'controllerGetCapabilities',
+      'controllerExpandVolume',
+      'createSnapshot',
+      'deleteSnapshot',
+      'listSnapshots'
     ];
     methodNames.forEach((name) => {
       controllerMethods[name] = function checkReady (args, cb) {
@@ -159,20 +177,6 @@ class CsiServer {
         });
       };
     });
-    // unimplemented methods
-    methodNames = [
-      'createSnapshot',
-      'deleteSnapshot',
-      'listSnapshots',
-      'controllerExpandVolume'
-    ];
-    methodNames.forEach((name) => {
-      controllerMethods[name] = function notImplemented (_, cb) {
-        const msg = `CSI method ${name} not implemented`;
-        log.error(msg);
-        cb(new GrpcError(grpc.status.UNIMPLEMENTED, msg));
-      };
-    });
     this.server.addService(csi.Controller.service, controllerMethods);
   }
 
@@ -242,9 +246,12 @@ class CsiServer {
     const caps = ['CONTROLLER_SERVICE', 'VOLUME_ACCESSIBILITY_CONSTRAINTS'];
     log.debug('getPluginCapabilities request: ' + caps.join(', '));
     cb(null, {
-      capabilities: caps.map((c) => {
-        return { service: { type: c } };
-      })
+      capabilities: caps
+        .map((c) => {
+          return { service: { type: c } };
+        })
+        // volumes can be expanded while they are published
+        .concat([{ volumeExpansion: { type: 'ONLINE' } }])
     });
   }
 
@@ -262,7 +269,11 @@ class CsiServer {
       'CREATE_DELETE_VOLUME',
       'PUBLISH_UNPUBLISH_VOLUME',
       'LIST_VOLUMES',
-      'GET_CAPACITY'
+      'GET_CAPACITY',
+      'EXPAND_VOLUME',
+      'CREATE_DELETE_SNAPSHOT',
+      'LIST_SNAPSHOTS',
+      'CLONE_VOLUME'
     ];
     log.debug('get capabilities request: ' + caps.join(', '));
     cb(null, {
@@ -281,13 +292,22 @@ class CsiServer {
         ` (limit ${args.capacityRange.limitBytes})`
     );
 
+    // the volume can be restored from a snapshot or cloned from a volume
+    let snapshot;
+    let sourceVolume;
     if (args.volumeContentSource) {
-      return cb(
-        new GrpcError(
-          grpc.status.INVALID_ARGUMENT,
-          'Source for create volume is not supported'
-        )
-      );
+      if (args.volumeContentSource.snapshot) {
+        snapshot = args.volumeContentSource.snapshot.snapshotId;
+      } else if (args.volumeContentSource.volume) {
+        sourceVolume = args.volumeContentSource.volume.volumeId;
+      } else {
+        return cb(
+          new GrpcError(
+            grpc.status.INVALID_ARGUMENT,
+            'Source for create volume other than snapshot or volume is not supported'
+          )
+        );
+      }
     }
     // k8s uses names pvc-{uuid} and we use uuid further as ID in SPDK so we
     // must require it.
@@ -325,20 +345,20 @@ class CsiServer {
         i++
       ) {
         const reqs = args.accessibilityRequirements.requisite[i];
-        for (const key in reqs.segments) {
-          // We are not able to evaluate any other topology requirements than
-          // the hostname req. Reject all others.
-          if (key !== 'kubernetes.io/hostname') {
-            return cb(
-              new GrpcError(
-                grpc.status.INVALID_ARGUMENT,
-                'Volume topology other than hostname not supported'
-              )
-            );
-          } else {
-            mustNodes.push(reqs.segments[key]);
-          }
+        // Nodes advertise their labels as topology segments along with the
+        // hostname. We are not able to evaluate the other segments on our own
+        // but the hostname identifies the node, which satisfies all of them.
+        // Reject requirements without the hostname.
+        const nodeName = reqs.segments['kubernetes.io/hostname'];
+        if (!nodeName) {
+          return cb(
+            new GrpcError(
+              grpc.status.INVALID_ARGUMENT,
+              'Volume topology other than hostname not supported'
+            )
+          );
         }
+        mustNodes.push(nodeName);
       }
       for (
         let i = 0;
@@ -355,6 +375,17 @@ class CsiServer {
       }
     }
 
+    // Filesystem created on the volume by the node plugin on first use
+    const fsType = args.parameters.fsType;
+    if (fsType && !['ext4', 'xfs'].includes(fsType)) {
+      return cb(
+        new GrpcError(
+          grpc.status.INVALID_ARGUMENT,
+          `Unsupported filesystem type "${fsType}"`
+        )
+      );
+    }
+
     let count = args.parameters.repl;
     if (count) {
       count = parseInt(count);
@@ -367,17 +398,25 @@ class CsiServer {
       count = 1;
     }
 
+    const spec = {
+      replicaCount: count,
+      preferredNodes: shouldNodes,
+      requiredNodes: mustNodes,
+      requiredBytes: args.capacityRange.requiredBytes,
+      limitBytes: args.capacityRange.limitBytes,
+      protocol: protocol
+    };
+    if (snapshot) {
+      spec.snapshot = snapshot;
+    }
+    if (sourceVolume) {
+      spec.sourceVolume = sourceVolume;
+    }
+
     // create the volume
     let volume;
     try {
-      volume = await this.volumes.createVolume(uuid, {
-        replicaCount: count,
-        preferredNodes: shouldNodes,
-        requiredNodes: mustNodes,
-        requiredBytes: args.capacityRange.requiredBytes,
-        limitBytes: args.capacityRange.limitBytes,
-        protocol: protocol
-      });
+      volume = await this.volumes.createVolume(uuid, spec);
     } catch (err) {
       return cb(err);
     }
@@ -394,6 +433,7 @@ class CsiServer {
         capacityBytes: volume.getSize(),
         volumeId: uuid,
         accessibleTopology,
+        contentSource: args.volumeContentSource,
         // parameters defined in the storage class are only presented
         // to the CSI driver createVolume method.
         // Propagate them to other CSI driver methods involved in
@@ -463,6 +503,113 @@ class CsiServer {
     }
   }
 
+  async createSnapshot (call, cb) {
+    const args = call.request;
+
+    log.debug(
+      `Request to create snapshot "${args.name}" of volume "${args.sourceVolumeId}"`
+    );
+
+    if (!args.name || !args.sourceVolumeId) {
+      return cb(
+        new GrpcError(
+          grpc.status.INVALID_ARGUMENT,
+          'Snapshot name and source volume ID are required'
+        )
+      );
+    }
+
+    let snapshot;
+    try {
+      snapshot = await this.volumes.createSnapshot(
+        args.sourceVolumeId,
+        args.name
+      );
+    } catch (err) {
+      return cb(err);
+    }
+    log.info(`Snapshot "${snapshot}" of volume "${args.sourceVolumeId}" created`);
+    cb(null, { snapshot: createK8sSnapshotObject(snapshot) });
+  }
+
+  // A snapshot with volumes restored from it cannot be deleted and the call
+  // fails with FAILED_PRECONDITION until those volumes have been deleted.
+  async deleteSnapshot (call, cb) {
+    const args = call.request;
+
+    log.debug(`Request to delete snapshot "${args.snapshotId}"`);
+
+    if (!args.snapshotId) {
+      return cb(
+        new GrpcError(grpc.status.INVALID_ARGUMENT, 'Missing snapshot ID')
+      );
+    }
+
+    try {
+      await this.volumes.destroySnapshot(args.snapshotId);
+    } catch (err) {
+      return cb(err);
+    }
+    log.info(`Snapshot "${args.snapshotId}" deleted`);
+    cb();
+  }
+
+  async listSnapshots (call, cb) {
+    const args = call.request;
+    let ctx = {};
+
+    if (args.startingToken) {
+      ctx = this.listContexts[args.startingToken];
+      delete this.listContexts[args.startingToken];
+      if (!ctx) {
+        return cb(
+          new GrpcError(
+            grpc.status.ABORTED,
+            'Paging context for list snapshots is gone'
+          )
+        );
+      }
+    } else {
+      log.debug('Request to list snapshots');
+      let snapshots;
+      try {
+        snapshots = await this.volumes.listSnapshots(
+          args.snapshotId || undefined
+        );
+      } catch (err) {
+        return cb(err);
+      }
+      ctx = {
+        snapshots: snapshots
+          .filter(
+            (s) => !args.sourceVolumeId || s.volumeUuid === args.sourceVolumeId
+          )
+          .sort((a, b) => a.name.localeCompare(b.name))
+          .map(createK8sSnapshotObject)
+          .map((s) => {
+            return { snapshot: s };
+          })
+      };
+    }
+    // default max entries
+    if (!args.maxEntries) {
+      args.maxEntries = 1000;
+    }
+
+    const entries = ctx.snapshots.splice(0, args.maxEntries);
+
+    if (ctx.snapshots.length > 0) {
+      const ctxId = this.nextListContextId++;
+      this.listContexts[ctxId] = ctx;
+      cb(null, {
+        entries: entries,
+        nextToken: ctxId.toString()
+      });
+    } else {
+      cb(null, { entries: entries });
+    }
+  }
+
   async controllerPublishVolume (call, cb) {
     const args = call.request;
 
@@ -595,6 +742,47 @@ class CsiServer {
     cb(null, resp);
   }
 
+  async controllerExpandVolume (call, cb) {
+    const args = call.request;
+
+    if (!args.capacityRange) {
+      return cb(
+        new GrpcError(grpc.status.INVALID_ARGUMENT, 'Missing capacity range')
+      );
+    }
+    log.debug(
+      `Request to expand volume "${args.volumeId}" to ` +
+        args.capacityRange.requiredBytes +
+        ` (limit ${args.capacityRange.limitBytes})`
+    );
+
+    const volume = this.volumes.get(args.volumeId);
+    if (!volume) {
+      return cb(
+        new GrpcError(
+          grpc.status.NOT_FOUND,
+          `Volume "${args.volumeId}" does not exist`
+        )
+      );
+    }
+    let size;
+    try {
+      size = await volume.resize(
+        args.capacityRange.requiredBytes,
+        args.capacityRange.limitBytes
+      );
+    } catch (err) {
+      return cb(err);
+    }
+    log.info(`Expanded volume "${args.volumeId}" to ${size} bytes`);
+    // a filesystem on the volume must be grown by the node plugin
+    const block = !!(args.volumeCapability && args.volumeCapability.block);
+    cb(null, {
+      capacityBytes: size,
+      nodeExpansionRequired: !block
+    });
+  }
+
   // We understand just one topology segment type and that is hostname.
   // So if it is specified we return capacity of storage pools on the node
   // or capacity of all pools in the cluster.
@@ -79,6 +79,13 @@ async function main () {
         default: '127.0.0.1:4222',
         string: true
       },
+      o: {
+        alias: 'overcommit',
+        describe:
+          'Ratio by which replicas may overcommit the capacity of a pool (replicas are thin if > 1)',
+        default: 1,
+        number: true
+      },
       p: {
         alias: 'port',
         describe: 'Port the REST API server should listen on',
@@ -151,7 +158,7 @@ async function main () {
   // serve csi.identity() calls while getting ready.
   const csiServer = new CsiServer(opts.csiAddress);
   await csiServer.start();
-  const registry = new Registry();
+  const registry = new Registry({ overcommit: opts.overcommit });
 
   // Listen to register and deregister messages from mayastor nodes
   const messageBus = new MessageBus(registry);
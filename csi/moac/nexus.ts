@@ -6,6 +6,7 @@ const { GrpcCode, GrpcError, mayastor } = require('./grpc_client');
 const log = require('./logger').Logger('nexus');
 
 import { Replica } from './replica';
+import { snapshotName } from './snapshot';
 
 // Protocol used to export nexus (volume)
 export enum Protocol {
@@ -298,6 +299,58 @@ export class Nexus {
     this._emitMod();
   }
 
+  // Grow the nexus to the given size. All children must have been grown to at
+  // least the new size beforehand.
+  //
+  // @param {number} size   New size of the nexus in bytes.
+  //
+  async resize(size: number) {
+    var res;
+
+    log.debug(`Resizing nexus "${this}" to ${size} bytes ...`);
+    try {
+      res = await this.node.call('resizeNexus', {
+        uuid: this.uuid,
+        size
+      });
+    } catch (err) {
+      throw new GrpcError(
+        GrpcCode.INTERNAL,
+        `Failed to resize nexus "${this}": ${err}`
+      );
+    }
+    log.info(`Nexus "${this}" resized to ${res.size} bytes`);
+    this.size = res.size;
+    this._emitMod();
+  }
+
+  // Take a snapshot of all children of the nexus.
+  //
+  // @returns {string} Name of the snapshot on the replicas.
+  //
+  async createSnapshot(): Promise<string> {
+    var res;
+
+    log.debug(`Creating snapshot of nexus "${this}" ...`);
+    try {
+      res = await this.node.call('createSnapshot', { uuid: this.uuid });
+    } catch (err) {
+      throw new GrpcError(
+        GrpcCode.INTERNAL,
+        `Failed to create snapshot of nexus "${this}": ${err}`
+      );
+    }
+    const name = snapshotName(this.uuid, res.name);
+    if (!name) {
+      throw new GrpcError(
+        GrpcCode.INTERNAL,
+        `Invalid snapshot name "${res.name}" returned by nexus "${this}"`
+      );
+    }
+    log.info(`Created snapshot "${name}" of nexus "${this}"`);
+    return name;
+  }
+
   // Destroy nexus on storage node.
   async destroy() {
     log.debug(`Destroying nexus "${this}" ...`);
@@ -213,12 +213,12 @@ export class Pool {
 
   // Create replica in this storage pool.
   //
-  // @param {string} uuid   ID of the new replica.
-  // @param {number} size   Size of the replica in bytes.
+  // @param {string}  uuid     ID of the new replica.
+  // @param {number}  size     Size of the replica in bytes.
+  // @param {boolean} [thin]   Create thin provisioned replica.
   //
-  async createReplica(uuid: string, size: number) {
+  async createReplica(uuid: string, size: number, thin = false) {
     const pool = this.name;
-    const thin = false;
     const share = 'REPLICA_NONE';
 
     log.debug(`Creating replica "${uuid}" on the pool "${this}" ...`);
@@ -230,4 +230,25 @@ export class Pool {
     this.registerReplica(newReplica);
     return newReplica;
   }
+
+  // Create replica in this storage pool as a clone of a snapshot stored in
+  // the pool.
+  //
+  // @param {string} snapshot   Name of the snapshot to clone.
+  // @param {string} uuid       ID of the new replica.
+  //
+  async cloneSnapshot(snapshot: string, uuid: string) {
+    const share = 'REPLICA_NONE';
+
+    log.debug(
+      `Cloning snapshot "${snapshot}" to replica "${uuid}" on the pool "${this}" ...`
+    );
+
+    var replicaInfo = await this.node.call('cloneSnapshot', { snapshot, uuid, share });
+    log.info(`Cloned snapshot "${snapshot}" to replica "${uuid}" on the pool "${this}"`);
+
+    const newReplica = new Replica(replicaInfo);
+    this.registerReplica(newReplica);
+    return newReplica;
+  }
 }
\ No newline at end of file
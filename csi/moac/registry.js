@@ -22,9 +22,17 @@ const { Node } = require('./node');
 const eventObjects = ['node', 'nexus', 'pool', 'replica'];
 
 class Registry extends EventEmitter {
-  constructor () {
+  // Create the registry.
+  //
+  // @param {object} [opts]             Registry options.
+  // @param {number} [opts.overcommit]  Ratio by which the sum of replica sizes
+  //                                    may exceed the capacity of a pool.
+  constructor (opts) {
     super();
     this.nodes = {}; // node objects indexed by name
+    // Overcommit policy: pools are not overcommitted by default, replicas are
+    // created thin only if they are.
+    this.overcommit = Math.max((opts && opts.overcommit) || 1, 1);
     // This gives a chance to override Node class used for creating new
     // node objects, which is useful for testing of the registry.
     this.Node = Node;
@@ -171,8 +179,29 @@ class Registry extends EventEmitter {
     }
   }
 
-  // Return total capacity of all pools summed together or capacity of pools on
-  // a single node if node name is specified.
+  // Return true if replicas should be created thin because pools are
+  // overcommitted.
+  thinReplicas () {
+    return this.overcommit > 1;
+  }
+
+  // Return space in the pool available for new replicas. Without overcommit
+  // it is the free space in the pool. With overcommit it is what remains of
+  // the overcommitted capacity after subtracting the sizes of the replicas.
+  //
+  // @param {object}   pool   Pool object.
+  // @returns {number} Available space in bytes.
+  //
+  availableBytes (pool) {
+    if (!this.thinReplicas()) {
+      return pool.freeBytes();
+    }
+    const allocated = pool.replicas.reduce((acc, r) => acc + r.size, 0);
+    return Math.max(Math.floor(pool.capacity * this.overcommit) - allocated, 0);
+  }
+
+  // Return total available capacity of all pools summed together or capacity
+  // of pools on a single node if node name is specified.
   //
   // @param {string}   [nodeName]  Name of the node to get the capacity for.
   // @returns {number} Total capacity in bytes.
@@ -187,7 +216,7 @@ class Registry extends EventEmitter {
     }
     return pools
       .filter((p) => p.isAccessible())
-      .reduce((acc, p) => acc + (p.capacity - p.used), 0);
+      .reduce((acc, p) => acc + this.availableBytes(p), 0);
   }
 
   // Return ordered list of storage pools suitable for new volume creation
@@ -202,7 +231,7 @@ class Registry extends EventEmitter {
     let pools = this.getPool().filter((p) => {
       return (
         p.isAccessible() &&
-        p.capacity - p.used >= requiredBytes &&
+        this.availableBytes(p) >= requiredBytes &&
         (mustNodes.length === 0 || mustNodes.indexOf(p.node.name) >= 0)
       );
     });
@@ -238,9 +267,7 @@ class Registry extends EventEmitter {
       }
 
       // Rule #4: Pools with more free space take precedence
-      const aFree = a.capacity - a.used;
-      const bFree = b.capacity - b.used;
-      return bFree - aFree;
+      return this.availableBytes(b) - this.availableBytes(a);
     });
 
     // only one pool from each node
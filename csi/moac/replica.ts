@@ -128,6 +128,37 @@ export class Replica {
     return res.uri;
   }
 
+  // Grow the replica to the given size. Shrinking is not supported.
+  //
+  // @param {number} size     New size of the replica in bytes.
+  //
+  async resize(size: number) {
+    var res;
+
+    if (!this.pool) {
+      throw new Error('Cannot resize a replica that has not been bound');
+    }
+    log.debug(`Resizing replica "${this}" to ${size} bytes ...`);
+
+    try {
+      res = await this.pool.node.call('resizeReplica', {
+        uuid: this.uuid,
+        size
+      });
+    } catch (err) {
+      throw new GrpcError(
+        GrpcCode.INTERNAL,
+        `Failed to resize replica "${this}": ` + err
+      );
+    }
+    log.info(`Replica "${this}" resized to ${res.size} bytes`);
+    this.size = res.size;
+    this.pool.node.emit('replica', {
+      eventType: 'mod',
+      object: this
+    });
+  }
+
   // Destroy replica on storage node.
   //
   // This must be called after the replica is removed from nexus.
@@ -0,0 +1,76 @@
+// Snapshot object implementation.
+//
+// A snapshot of a volume is taken by the nexus on all replicas of the volume
+// at once. The copies of the snapshot on the replicas share the same name
+// "<volume uuid>-snap-<timestamp>", which is used as the ID of the snapshot.
+
+import { Pool } from './pool';
+
+const SNAPSHOT_RE = /^(.+)-snap-(\d+)$/;
+
+// Copy of the snapshot in a storage pool.
+export interface SnapshotCopy {
+  pool: Pool;
+  // uuids of replicas (volumes) cloned from the copy
+  clones: string[];
+}
+
+export class Snapshot {
+  name: string;
+  volumeUuid: string;
+  // creation time in seconds since the epoch
+  timestamp: number;
+  size: number;
+  copies: SnapshotCopy[];
+
+  // Create snapshot object.
+  //
+  // @param {object} props  Snapshot properties obtained from storage node.
+  constructor(props: any) {
+    this.name = props.name;
+    this.volumeUuid = props.source;
+    this.timestamp = parseInt(props.timestamp);
+    this.size = parseInt(props.size);
+    this.copies = [];
+  }
+
+  // Stringify snapshot.
+  toString() {
+    return this.name;
+  }
+
+  // Add copy of the snapshot found in a storage pool.
+  //
+  // @param {object}   pool     Pool holding the copy.
+  // @param {string[]} clones   Replicas cloned from the copy.
+  addCopy(pool: Pool, clones: string[]) {
+    this.copies.push({ pool, clones: clones || [] });
+  }
+
+  // Return uuids of volumes restored from the snapshot.
+  getClones(): string[] {
+    const clones = this.copies.reduce(
+      (acc: string[], copy) => acc.concat(copy.clones),
+      []
+    );
+    return [...new Set(clones)].sort();
+  }
+}
+
+// Compose the name of the snapshot from the name of a snapshot reported by
+// nexus. The nexus reports the snapshot under its own name, while the copies
+// on the replicas are named after the volume.
+//
+// @param {string} volumeUuid   ID of the volume.
+// @param {string} nexusName    Name of the snapshot reported by the nexus.
+// @returns Name of the snapshot or undefined if not a snapshot name.
+export function snapshotName(
+  volumeUuid: string,
+  nexusName: string
+): string | undefined {
+  const m = nexusName.match(SNAPSHOT_RE);
+  if (!m) {
+    return undefined;
+  }
+  return `${volumeUuid}-snap-${m[2]}`;
+}
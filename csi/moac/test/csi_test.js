@@ -10,6 +10,7 @@ const sinon = require('sinon');
 const { CsiServer, csi } = require('../csi');
 const { GrpcError, GrpcCode } = require('../grpc_client');
 const Registry = require('../registry');
+const { Snapshot } = require('../snapshot');
 const { Volume } = require('../volume');
 const { Volumes } = require('../volumes');
 const { shouldFailWith } = require('./utils');
@@ -75,11 +76,12 @@ module.exports = function () {
       const res = await client.getPluginCapabilities().sendMessage({});
       // If you need to change any capabilities below, you will
       // need to change source code of csi node server too!
-      expect(res.capabilities).to.have.lengthOf(2);
+      expect(res.capabilities).to.have.lengthOf(3);
       expect(res.capabilities[0].service.type).to.equal('CONTROLLER_SERVICE');
       expect(res.capabilities[1].service.type).to.equal(
         'VOLUME_ACCESSIBILITY_CONSTRAINTS'
       );
+      expect(res.capabilities[2].volumeExpansion.type).to.equal('ONLINE');
     });
 
     it('probe not ready', async () => {
@@ -98,6 +100,7 @@ module.exports = function () {
     let client;
     let registry, volumes;
     let getCapacityStub, createVolumeStub, listVolumesStub, getVolumesStub, destroyVolumeStub;
+    let createSnapshotStub, destroySnapshotStub, listSnapshotsStub;
 
     async function mockedServer (pools, replicas, nexus) {
       const server = new CsiServer(SOCKPATH);
@@ -110,6 +113,9 @@ module.exports = function () {
       listVolumesStub = sinon.stub(volumes, 'list');
       getVolumesStub = sinon.stub(volumes, 'get');
       destroyVolumeStub = sinon.stub(volumes, 'destroyVolume');
+      createSnapshotStub = sinon.stub(volumes, 'createSnapshot');
+      destroySnapshotStub = sinon.stub(volumes, 'destroySnapshot');
+      listSnapshotsStub = sinon.stub(volumes, 'listSnapshots');
       return server;
     }
 
@@ -139,11 +145,15 @@ module.exports = function () {
         server = await mockedServer();
         const res = await client.controllerGetCapabilities().sendMessage({});
         const caps = res.capabilities;
-        expect(caps).to.have.lengthOf(4);
+        expect(caps).to.have.lengthOf(8);
         expect(caps[0].rpc.type).to.equal('CREATE_DELETE_VOLUME');
         expect(caps[1].rpc.type).to.equal('PUBLISH_UNPUBLISH_VOLUME');
         expect(caps[2].rpc.type).to.equal('LIST_VOLUMES');
         expect(caps[3].rpc.type).to.equal('GET_CAPACITY');
+        expect(caps[4].rpc.type).to.equal('EXPAND_VOLUME');
+        expect(caps[5].rpc.type).to.equal('CREATE_DELETE_SNAPSHOT');
+        expect(caps[6].rpc.type).to.equal('LIST_SNAPSHOTS');
+        expect(caps[7].rpc.type).to.equal('CLONE_VOLUME');
       });
 
       it('should not get controller capabilities if not ready', async () => {
@@ -153,43 +163,6 @@ module.exports = function () {
           client.controllerGetCapabilities().sendMessage({})
         );
       });
-
-      it('should return unimlemented error for CreateSnapshot', async () => {
-        server = await mockedServer();
-        await shouldFailWith(GrpcCode.UNIMPLEMENTED, () =>
-          client.createSnapshot().sendMessage({
-            sourceVolumeId: 'd01b8bfb-0116-47b0-a03a-447fcbdc0e99',
-            name: 'blabla2'
-          })
-        );
-      });
-
-      it('should return unimlemented error for DeleteSnapshot', async () => {
-        server = await mockedServer();
-        await shouldFailWith(GrpcCode.UNIMPLEMENTED, () =>
-          client.deleteSnapshot().sendMessage({ snapshotId: 'blabla' })
-        );
-      });
-
-      it('should return unimlemented error for ListSnapshots', async () => {
-        server = await mockedServer();
-        await shouldFailWith(GrpcCode.UNIMPLEMENTED, () =>
-          client.listSnapshots().sendMessage({})
-        );
-      });
-
-      it('should return unimlemented error for ControllerExpandVolume', async () => {
-        server = await mockedServer();
-        await shouldFailWith(GrpcCode.UNIMPLEMENTED, () =>
-          client.controllerExpandVolume().sendMessage({
-            volumeId: UUID,
-            capacityRange: {
-              requiredBytes: 200,
-              limitBytes: 500
-            }
-          })
-        );
-      });
     });
 
     describe('CreateVolume', function () {
@@ -271,7 +244,29 @@ module.exports = function () {
         });
       });
 
-      it('should fail if topology requirement other than hostname', async () => {
+      it('should pass filesystem type to the node in volume context', async () => {
+        createVolumeStub.resolves(returnedVolume);
+        const result = await client.createVolume().sendMessage({
+          name: 'pvc-' + UUID,
+          capacityRange: {
+            requiredBytes: 10,
+            limitBytes: 20
+          },
+          volumeCapabilities: [
+            {
+              accessMode: { mode: 'SINGLE_NODE_WRITER' },
+              mount: {}
+            }
+          ],
+          parameters: { protocol: 'nvmf', fsType: 'ext4' }
+        });
+        expect(result.volume.volumeContext).to.eql({
+          protocol: 'nvmf',
+          fsType: 'ext4'
+        });
+      });
+
+      it('should fail if filesystem type is not supported', async () => {
         createVolumeStub.resolves(returnedVolume);
         await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
           client.createVolume().sendMessage({
@@ -283,24 +278,20 @@ module.exports = function () {
             volumeCapabilities: [
               {
                 accessMode: { mode: 'SINGLE_NODE_WRITER' },
-                block: {}
+                mount: {}
               }
             ],
-            accessibilityRequirements: {
-              requisite: [{ segments: { rack: 'some-rack-info' } }],
-              preferred: []
-            },
-            parameters: { protocol: 'nbd' }
+            parameters: { protocol: 'nvmf', fsType: 'btrfs' }
           })
         );
+        sinon.assert.notCalled(createVolumeStub);
       });
 
-      it('should fail if volume source', async () => {
+      it('should fail if topology requirement other than hostname', async () => {
         createVolumeStub.resolves(returnedVolume);
         await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
           client.createVolume().sendMessage({
             name: 'pvc-' + UUID,
-            volumeContentSource: { volume: { volumeId: UUID } },
             capacityRange: {
               requiredBytes: 10,
               limitBytes: 20
@@ -311,11 +302,45 @@ module.exports = function () {
                 block: {}
               }
             ],
+            accessibilityRequirements: {
+              requisite: [{ segments: { rack: 'some-rack-info' } }],
+              preferred: []
+            },
             parameters: { protocol: 'nbd' }
           })
         );
       });
 
+      it('should clone a volume from another volume', async () => {
+        const SOURCE = 'a01b8bfb-0116-47b0-a03a-447fcbdc0e98';
+        createVolumeStub.resolves(returnedVolume);
+        const res = await client.createVolume().sendMessage({
+          name: 'pvc-' + UUID,
+          volumeContentSource: { volume: { volumeId: SOURCE } },
+          capacityRange: {
+            requiredBytes: 10,
+            limitBytes: 20
+          },
+          volumeCapabilities: [
+            {
+              accessMode: { mode: 'SINGLE_NODE_WRITER' },
+              block: {}
+            }
+          ],
+          parameters: { protocol: 'iscsi' }
+        });
+        sinon.assert.calledWith(createVolumeStub, UUID, {
+          replicaCount: 1,
+          preferredNodes: [],
+          requiredNodes: [],
+          requiredBytes: 10,
+          limitBytes: 20,
+          protocol: 'iscsi',
+          sourceVolume: SOURCE
+        });
+        expect(res.volume.contentSource.volume.volumeId).to.equal(SOURCE);
+      });
+
       it('should fail if capability other than SINGLE_NODE_WRITER', async () => {
         createVolumeStub.resolves(returnedVolume);
         await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
@@ -407,6 +432,42 @@ module.exports = function () {
         });
       });
 
+      it('should create volume on node matching labels and hostname', async () => {
+        createVolumeStub.resolves(returnedVolume);
+        await client.createVolume().sendMessage({
+          name: 'pvc-' + UUID,
+          capacityRange: {
+            requiredBytes: 50,
+            limitBytes: 50
+          },
+          volumeCapabilities: [
+            {
+              accessMode: { mode: 'SINGLE_NODE_WRITER' },
+              block: {}
+            }
+          ],
+          accessibilityRequirements: {
+            requisite: [
+              {
+                segments: {
+                  'openebs.io/zone': 'zone-a',
+                  'kubernetes.io/hostname': 'node'
+                }
+              }
+            ]
+          },
+          parameters: { protocol: 'nbd' }
+        });
+        sinon.assert.calledWith(createVolumeStub, UUID, {
+          replicaCount: 1,
+          preferredNodes: [],
+          requiredNodes: ['node'],
+          requiredBytes: 50,
+          limitBytes: 50,
+          protocol: 'nbd'
+        });
+      });
+
       it('should create volume on preferred node', async () => {
         createVolumeStub.resolves(returnedVolume);
         await client.createVolume().sendMessage({
@@ -491,6 +552,142 @@ module.exports = function () {
       });
     });
 
+    describe('snapshots', function () {
+      const SNAPSHOT = `${UUID}-snap-1600000000`;
+      const UUID2 = 'a01b8bfb-0116-47b0-a03a-447fcbdc0e98';
+      let server;
+      let snapshot;
+
+      beforeEach(async () => {
+        server = await mockedServer();
+        snapshot = new Snapshot({
+          name: SNAPSHOT,
+          source: UUID,
+          size: 100,
+          timestamp: 1600000000
+        });
+      });
+
+      afterEach(async () => {
+        if (server) {
+          await server.stop();
+          server = null;
+        }
+      });
+
+      it('should create a snapshot', async () => {
+        createSnapshotStub.resolves(snapshot);
+
+        const res = await client.createSnapshot().sendMessage({
+          sourceVolumeId: UUID,
+          name: 'snapshot-1'
+        });
+
+        sinon.assert.calledOnce(createSnapshotStub);
+        sinon.assert.calledWith(createSnapshotStub, UUID, 'snapshot-1');
+        expect(res.snapshot.snapshotId).to.equal(SNAPSHOT);
+        expect(res.snapshot.sourceVolumeId).to.equal(UUID);
+        expect(res.snapshot.sizeBytes).to.equal(100);
+        expect(res.snapshot.creationTime.seconds).to.equal(1600000000);
+        expect(res.snapshot.readyToUse).to.be.true();
+      });
+
+      it('should fail to create a snapshot without name', async () => {
+        await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
+          client.createSnapshot().sendMessage({ sourceVolumeId: UUID })
+        );
+        sinon.assert.notCalled(createSnapshotStub);
+      });
+
+      it('should restore a volume from the snapshot', async () => {
+        const restored = new Volume(UUID2, registry, () => {}, {
+          replicaCount: 1,
+          requiredBytes: 100,
+          protocol: 'nvmf'
+        });
+        sinon.stub(restored, 'getSize').returns(100);
+        createVolumeStub.resolves(restored);
+
+        const res = await client.createVolume().sendMessage({
+          name: 'pvc-' + UUID2,
+          volumeContentSource: { snapshot: { snapshotId: SNAPSHOT } },
+          capacityRange: {
+            requiredBytes: 100,
+            limitBytes: 0
+          },
+          volumeCapabilities: [
+            {
+              accessMode: { mode: 'SINGLE_NODE_WRITER' },
+              block: {}
+            }
+          ],
+          parameters: { protocol: 'nvmf' }
+        });
+
+        sinon.assert.calledWith(createVolumeStub, UUID2, {
+          replicaCount: 1,
+          preferredNodes: [],
+          requiredNodes: [],
+          requiredBytes: 100,
+          limitBytes: 0,
+          protocol: 'nvmf',
+          snapshot: SNAPSHOT
+        });
+        expect(res.volume.volumeId).to.equal(UUID2);
+        expect(res.volume.contentSource.snapshot.snapshotId).to.equal(SNAPSHOT);
+      });
+
+      it('should delete a snapshot', async () => {
+        destroySnapshotStub.resolves();
+
+        await client.deleteSnapshot().sendMessage({ snapshotId: SNAPSHOT });
+
+        sinon.assert.calledOnce(destroySnapshotStub);
+        sinon.assert.calledWith(destroySnapshotStub, SNAPSHOT);
+      });
+
+      it('should fail to delete a snapshot with restored volumes', async () => {
+        destroySnapshotStub.rejects(
+          new GrpcError(GrpcCode.FAILED_PRECONDITION, 'Snapshot is used')
+        );
+
+        await shouldFailWith(GrpcCode.FAILED_PRECONDITION, () =>
+          client.deleteSnapshot().sendMessage({ snapshotId: SNAPSHOT })
+        );
+      });
+
+      it('should list snapshots of the source volume', async () => {
+        const other = new Snapshot({
+          name: `${UUID2}-snap-1600000001`,
+          source: UUID2,
+          size: 100,
+          timestamp: 1600000001
+        });
+        listSnapshotsStub.resolves([other, snapshot]);
+
+        let res = await client.listSnapshots().sendMessage({});
+        expect(res.entries).to.have.lengthOf(2);
+
+        res = await client.listSnapshots().sendMessage({
+          sourceVolumeId: UUID
+        });
+        expect(res.entries).to.have.lengthOf(1);
+        expect(res.entries[0].snapshot.snapshotId).to.equal(SNAPSHOT);
+      });
+
+      it('should list snapshot with given ID', async () => {
+        listSnapshotsStub.resolves([snapshot]);
+
+        const res = await client.listSnapshots().sendMessage({
+          snapshotId: SNAPSHOT
+        });
+
+        sinon.assert.calledWith(listSnapshotsStub, SNAPSHOT);
+        expect(res.entries).to.have.lengthOf(1);
+        expect(res.entries[0].snapshot.snapshotId).to.equal(SNAPSHOT);
+      });
+    });
+
     describe('DeleteVolume', function () {
       let server;
 
@@ -885,6 +1082,101 @@ module.exports = function () {
       });
     });
 
+    describe('ControllerExpandVolume', function () {
+      const GiB = 1024 * 1024 * 1024;
+      let server;
+
+      before(async () => {
+        server = await mockedServer();
+      });
+
+      after(async () => {
+        if (server) {
+          await server.stop();
+          server = null;
+        }
+      });
+
+      afterEach(() => {
+        getVolumesStub.reset();
+      });
+
+      it('should expand volume from 1GiB to 2GiB', async () => {
+        const volume = new Volume(UUID, registry, () => {}, {});
+        const resizeStub = sinon.stub(volume, 'resize');
+        resizeStub.resolves(2 * GiB);
+        getVolumesStub.returns(volume);
+
+        const res = await client.controllerExpandVolume().sendMessage({
+          volumeId: UUID,
+          capacityRange: {
+            requiredBytes: 2 * GiB,
+            limitBytes: 0
+          },
+          volumeCapability: {
+            accessMode: { mode: 'SINGLE_NODE_WRITER' },
+            mount: { fsType: 'xfs', mountFlags: [] }
+          }
+        });
+
+        expect(res.capacityBytes).to.equal(2 * GiB);
+        expect(res.nodeExpansionRequired).to.be.true();
+        sinon.assert.calledOnce(resizeStub);
+        sinon.assert.calledWith(resizeStub, 2 * GiB, 0);
+      });
+
+      it('should not require node expansion of a block volume', async () => {
+        const volume = new Volume(UUID, registry, () => {}, {});
+        const resizeStub = sinon.stub(volume, 'resize');
+        resizeStub.resolves(2 * GiB);
+        getVolumesStub.returns(volume);
+
+        const res = await client.controllerExpandVolume().sendMessage({
+          volumeId: UUID,
+          capacityRange: {
+            requiredBytes: 2 * GiB,
+            limitBytes: 0
+          },
+          volumeCapability: {
+            accessMode: { mode: 'SINGLE_NODE_WRITER' },
+            block: {}
+          }
+        });
+
+        expect(res.capacityBytes).to.equal(2 * GiB);
+        expect(res.nodeExpansionRequired).to.be.false();
+      });
+
+      it('should not expand volume if it does not exist', async () => {
+        getVolumesStub.returns(null);
+
+        await shouldFailWith(GrpcCode.NOT_FOUND, () =>
+          client.controllerExpandVolume().sendMessage({
+            volumeId: UUID,
+            capacityRange: {
+              requiredBytes: 2 * GiB,
+              limitBytes: 0
+            }
+          })
+        );
+      });
+
+      it('should not shrink volume', async () => {
+        const volume = new Volume(UUID, registry, () => {}, {}, 'healthy', 2 * GiB);
+        getVolumesStub.returns(volume);
+
+        await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
+          client.controllerExpandVolume().sendMessage({
+            volumeId: UUID,
+            capacityRange: {
+              requiredBytes: GiB,
+              limitBytes: 0
+            }
+          })
+        );
+      });
+    });
+
     describe('ValidateVolumeCapabilities', function () {
       let server;
 
@@ -258,6 +258,51 @@ module.exports = function () {
     expect(cap).to.equal(75);
   });
 
+  it('should drop capacity as replicas are created and raise it when destroyed', () => {
+    const pool = new Pool({
+      name: 'pool',
+      disks: [],
+      state: 'POOL_ONLINE',
+      capacity: 100,
+      used: 10
+    });
+    const node = new Node('node', {}, [pool]);
+    const registry = new Registry();
+    registry.nodes.node = node;
+    expect(registry.getCapacity('node')).to.equal(90);
+
+    // used space of the pool is updated by the storage node
+    pool.used = 50;
+    expect(registry.getCapacity('node')).to.equal(50);
+    pool.used = 10;
+    expect(registry.getCapacity('node')).to.equal(90);
+  });
+
+  it('should account for overcommit of pools in capacity', () => {
+    const pool = new Pool({
+      name: 'pool',
+      disks: [],
+      state: 'POOL_ONLINE',
+      capacity: 100,
+      used: 10
+    });
+    const node = new Node('node', {}, [pool]);
+    const registry = new Registry({ overcommit: 2 });
+    registry.nodes.node = node;
+    expect(registry.thinReplicas()).to.be.true();
+    expect(registry.getCapacity('node')).to.equal(200);
+
+    // thin replicas count with their full size
+    const replica = new Replica({ uuid: 'replica', size: 80 });
+    pool.registerReplica(replica);
+    expect(registry.getCapacity('node')).to.equal(120);
+    expect(registry.choosePools(150, [], [])).to.have.lengthOf(0);
+    expect(registry.choosePools(120, [], [])).to.have.lengthOf(1);
+
+    pool.unregisterReplica(replica);
+    expect(registry.getCapacity('node')).to.equal(200);
+  });
+
   describe('pool selection', function () {
     it('should prefer ONLINE pool', () => {
       // has more free space but is degraded
@@ -199,4 +199,101 @@ module.exports = function () {
     expect(volume.getNodeName()).to.be.undefined();
     sinon.assert.notCalled(stub);
   });
+
+  describe('resize', function () {
+    const GiB = 1024 * 1024 * 1024;
+
+    // Create a healthy 1GiB volume with a replica on each of the two nodes
+    // and a nexus on the first node.
+    function createTwoReplicaVolume (freeBytes) {
+      const registry = new Registry();
+      const volume = new Volume(UUID, registry, () => {}, {
+        replicaCount: 2,
+        preferredNodes: [],
+        requiredNodes: [],
+        requiredBytes: GiB,
+        limitBytes: 0
+      }, 'healthy', GiB);
+      const fsaStub = sinon.stub(volume, 'fsa');
+      fsaStub.returns();
+      const nodes = [new Node('node1'), new Node('node2')];
+      nodes.forEach((node, i) => {
+        const pool = new Pool({
+          name: `pool${i}`,
+          disks: [],
+          capacity: GiB + freeBytes[i],
+          used: GiB
+        });
+        pool.bind(node);
+        const replica = new Replica({
+          uuid: UUID,
+          size: GiB,
+          share: i === 0 ? 'REPLICA_NONE' : 'REPLICA_NVMF',
+          uri: i === 0 ? `bdev:///${UUID}` : `nvmf://node2/${UUID}`
+        });
+        replica.bind(pool);
+        volume.newReplica(replica);
+      });
+      const nexus = new Nexus({ uuid: UUID, size: GiB, children: [] });
+      nexus.bind(nodes[0]);
+      volume.newNexus(nexus);
+      return [volume, nodes, nexus];
+    }
+
+    it('should expand a volume from 1GiB to 2GiB', async () => {
+      const [volume, nodes, nexus] = createTwoReplicaVolume([2 * GiB, 2 * GiB]);
+      const stub1 = sinon.stub(nodes[0], 'call');
+      stub1.withArgs('resizeReplica').resolves({ uuid: UUID, size: 2 * GiB });
+      stub1.withArgs('resizeNexus').resolves({ uuid: UUID, size: 2 * GiB });
+      const stub2 = sinon.stub(nodes[1], 'call');
+      stub2.withArgs('resizeReplica').resolves({ uuid: UUID, size: 2 * GiB });
+
+      const size = await volume.resize(2 * GiB);
+
+      expect(size).to.equal(2 * GiB);
+      expect(volume.getSize()).to.equal(2 * GiB);
+      expect(nexus.size).to.equal(2 * GiB);
+      Object.values(volume.replicas).forEach((r) => {
+        expect(r.size).to.equal(2 * GiB);
+      });
+      sinon.assert.calledTwice(stub1);
+      sinon.assert.calledWithMatch(stub1.firstCall, 'resizeReplica', {
+        uuid: UUID,
+        size: 2 * GiB
+      });
+      sinon.assert.calledWithMatch(stub1.secondCall, 'resizeNexus', {
+        uuid: UUID,
+        size: 2 * GiB
+      });
+      sinon.assert.calledOnce(stub2);
+      sinon.assert.calledWithMatch(stub2, 'resizeReplica', {
+        uuid: UUID,
+        size: 2 * GiB
+      });
+    });
+
+    it('should not expand any replica if one of the pools is full', async () => {
+      const [volume, nodes] = createTwoReplicaVolume([2 * GiB, GiB / 2]);
+      const stub1 = sinon.stub(nodes[0], 'call');
+      const stub2 = sinon.stub(nodes[1], 'call');
+
+      await shouldFailWith(GrpcCode.RESOURCE_EXHAUSTED, () =>
+        volume.resize(2 * GiB)
+      );
+      expect(volume.getSize()).to.equal(GiB);
+      sinon.assert.notCalled(stub1);
+      sinon.assert.notCalled(stub2);
+    });
+
+    it('should not shrink a volume', async () => {
+      const [volume, nodes] = createTwoReplicaVolume([2 * GiB, 2 * GiB]);
+      const stub1 = sinon.stub(nodes[0], 'call');
+
+      await shouldFailWith(GrpcCode.INVALID_ARGUMENT, () =>
+        volume.resize(GiB / 2)
+      );
+      expect(volume.getSize()).to.equal(GiB);
+      sinon.assert.notCalled(stub1);
+    });
+  });
 };
@@ -1318,6 +1318,245 @@ module.exports = function () {
 
   // Volume is created once in the first test and then all tests use it.
   // This tests the typical life-cycle of a volume from create to destroy.
+  describe('snapshots', function () {
+    const SNAPSHOT = `${UUID}-snap-1600000000`;
+
+    // listSnapshots reply of a node with a copy of the snapshot in the pool
+    function snapshotCopy (pool, clones) {
+      return {
+        snapshots: [
+          {
+            name: SNAPSHOT,
+            uuid: pool + '-lvol-uuid',
+            pool,
+            size: 95,
+            source: UUID,
+            timestamp: 1600000000,
+            clones: clones || []
+          }
+        ]
+      };
+    }
+
+    beforeEach(async () => {
+      await setUpReferenceEnv(true);
+      stub1.withArgs('listSnapshots').resolves(snapshotCopy('pool1'));
+      stub2.withArgs('listSnapshots').resolves(snapshotCopy('pool2'));
+      stub3.withArgs('listSnapshots').resolves({ snapshots: [] });
+    });
+
+    afterEach(() => {
+      volumes.stop();
+    });
+
+    it('should create a snapshot of all replicas', async () => {
+      stub1.withArgs('createSnapshot').resolves({
+        name: `nexus-${UUID}-snap-1600000000`
+      });
+
+      const snapshot = await volumes.createSnapshot(UUID, 'snapshot-1');
+
+      sinon.assert.calledWithMatch(stub1, 'createSnapshot', { uuid: UUID });
+      expect(snapshot.name).to.equal(SNAPSHOT);
+      expect(snapshot.volumeUuid).to.equal(UUID);
+      expect(snapshot.timestamp).to.equal(1600000000);
+      expect(snapshot.size).to.equal(95);
+      expect(snapshot.copies.map((c) => c.pool)).to.have.members([
+        pool1,
+        pool2
+      ]);
+
+      // the same name gives the same snapshot
+      const again = await volumes.createSnapshot(UUID, 'snapshot-1');
+      expect(again.name).to.equal(SNAPSHOT);
+      sinon.assert.calledOnce(stub1.withArgs('createSnapshot'));
+    });
+
+    it('should fail to create a snapshot of unpublished volume', async () => {
+      await volume.unpublish();
+
+      await shouldFailWith(GrpcCode.FAILED_PRECONDITION, () =>
+        volumes.createSnapshot(UUID, 'snapshot-1')
+      );
+      sinon.assert.notCalled(stub1.withArgs('createSnapshot'));
+    });
+
+    it('should restore a volume from the snapshot', async () => {
+      stub1.withArgs('cloneSnapshot').resolves({
+        uuid: UUID2,
+        pool: 'pool1',
+        size: 95,
+        thin: false,
+        share: 'REPLICA_NONE',
+        uri: 'bdev:///' + UUID2
+      });
+      stub2.withArgs('cloneSnapshot').resolves({
+        uuid: UUID2,
+        pool: 'pool2',
+        size: 95,
+        thin: false,
+        share: 'REPLICA_NONE',
+        uri: 'bdev:///' + UUID2
+      });
+
+      const restored = await volumes.createVolume(UUID2, {
+        replicaCount: 2,
+        preferredNodes: [],
+        requiredNodes: [],
+        requiredBytes: 90,
+        limitBytes: 110,
+        protocol: 'nbd',
+        snapshot: SNAPSHOT
+      });
+
+      sinon.assert.calledWithMatch(stub1, 'cloneSnapshot', {
+        snapshot: SNAPSHOT,
+        uuid: UUID2,
+        share: 'REPLICA_NONE'
+      });
+      sinon.assert.calledWithMatch(stub2, 'cloneSnapshot', {
+        snapshot: SNAPSHOT,
+        uuid: UUID2,
+        share: 'REPLICA_NONE'
+      });
+      sinon.assert.neverCalledWith(stub1, 'createReplica');
+      sinon.assert.neverCalledWith(stub2, 'createReplica');
+      sinon.assert.neverCalledWith(stub3, 'createReplica');
+      expect(restored.getSize()).to.equal(95);
+      expect(restored.state).to.equal('healthy');
+    });
+
+    it('should fail to restore a volume from unknown snapshot', async () => {
+      await shouldFailWith(GrpcCode.NOT_FOUND, () =>
+        volumes.createVolume(UUID2, {
+          replicaCount: 1,
+          preferredNodes: [],
+          requiredNodes: [],
+          requiredBytes: 90,
+          limitBytes: 110,
+          protocol: 'nbd',
+          snapshot: `${UUID}-snap-1`
+        })
+      );
+      expect(volumes.get(UUID2)).to.be.undefined();
+    });
+
+    it('should clone a volume from another volume and grow it', async () => {
+      stub1.withArgs('createSnapshot').resolves({
+        name: `nexus-${UUID}-snap-1600000000`
+      });
+      stub1.withArgs('cloneSnapshot').resolves({
+        uuid: UUID2,
+        pool: 'pool1',
+        size: 95,
+        thin: false,
+        share: 'REPLICA_NONE',
+        uri: 'bdev:///' + UUID2
+      });
+      stub2.withArgs('cloneSnapshot').resolves({
+        uuid: UUID2,
+        pool: 'pool2',
+        size: 95,
+        thin: false,
+        share: 'REPLICA_NONE',
+        uri: 'bdev:///' + UUID2
+      });
+      stub1.withArgs('resizeReplica').resolves({ size: 200 });
+      stub2.withArgs('resizeReplica').resolves({ size: 200 });
+
+      const clone = await volumes.createVolume(UUID2, {
+        replicaCount: 2,
+        preferredNodes: [],
+        requiredNodes: [],
+        requiredBytes: 200,
+        limitBytes: 0,
+        protocol: 'nbd',
+        sourceVolume: UUID
+      });
+
+      sinon.assert.calledWithMatch(stub1, 'createSnapshot', { uuid: UUID });
+      sinon.assert.calledWithMatch(stub1, 'cloneSnapshot', {
+        snapshot: SNAPSHOT,
+        uuid: UUID2
+      });
+      sinon.assert.calledWithMatch(stub2, 'cloneSnapshot', {
+        snapshot: SNAPSHOT,
+        uuid: UUID2
+      });
+      sinon.assert.calledWithMatch(stub1, 'resizeReplica', {
+        uuid: UUID2,
+        size: 200
+      });
+      sinon.assert.calledWithMatch(stub2, 'resizeReplica', {
+        uuid: UUID2,
+        size: 200
+      });
+      expect(clone.getSize()).to.equal(200);
+      expect(clone.state).to.equal('healthy');
+      // the source volume is left as it was
+      sinon.assert.neverCalledWithMatch(stub1, 'resizeReplica', { uuid: UUID });
+      sinon.assert.neverCalledWithMatch(stub1, 'destroyReplica', { uuid: UUID });
+      expect(volume.getSize()).to.equal(95);
+      expect(volume.state).to.equal('healthy');
+    });
+
+    it('should destroy the snapshot taken to clone a volume with the clone', async () => {
+      stub1.withArgs('createSnapshot').resolves({
+        name: `nexus-${UUID}-snap-1600000000`
+      });
+      stub1.withArgs('cloneSnapshot').resolves({
+        uuid: UUID2,
+        pool: 'pool1',
+        size: 95,
+        thin: false,
+        share: 'REPLICA_NONE',
+        uri: 'bdev:///' + UUID2
+      });
+      stub1.withArgs('destroyReplica').resolves({});
+      stub1.withArgs('destroySnapshot').resolves({});
+      stub2.withArgs('destroySnapshot').resolves({});
+
+      await volumes.createVolume(UUID2, {
+        replicaCount: 1,
+        preferredNodes: [],
+        requiredNodes: ['node1'],
+        requiredBytes: 90,
+        limitBytes: 110,
+        protocol: 'nbd',
+        sourceVolume: UUID
+      });
+      sinon.assert.neverCalledWith(stub1, 'destroySnapshot');
+
+      await volumes.destroyVolume(UUID2);
+
+      sinon.assert.calledWithMatch(stub1, 'destroyReplica', { uuid: UUID2 });
+      sinon.assert.calledWithMatch(stub1, 'destroySnapshot', { name: SNAPSHOT });
+      sinon.assert.calledWithMatch(stub2, 'destroySnapshot', { name: SNAPSHOT });
+      expect(volumes.get(UUID2)).to.be.undefined();
+    });
+
+    it('should not destroy a snapshot with restored volumes', async () => {
+      stub2.withArgs('listSnapshots').resolves(snapshotCopy('pool2', [UUID2]));
+
+      await shouldFailWith(GrpcCode.FAILED_PRECONDITION, () =>
+        volumes.destroySnapshot(SNAPSHOT)
+      );
+      sinon.assert.neverCalledWith(stub1, 'destroySnapshot');
+      sinon.assert.neverCalledWith(stub2, 'destroySnapshot');
+    });
+
+    it('should destroy all copies of the snapshot', async () => {
+      stub1.withArgs('destroySnapshot').resolves({});
+      stub2.withArgs('destroySnapshot').resolves({});
+
+      await volumes.destroySnapshot(SNAPSHOT);
+
+      sinon.assert.calledWithMatch(stub1, 'destroySnapshot', { name: SNAPSHOT });
+      sinon.assert.calledWithMatch(stub2, 'destroySnapshot', { name: SNAPSHOT });
+      sinon.assert.neverCalledWith(stub3, 'destroySnapshot');
+    });
+  });
+
   describe('misc', function () {
     before(createTestEnv);
 
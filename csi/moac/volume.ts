@@ -7,6 +7,7 @@ import { Replica } from './replica';
 import { Child, Nexus, Protocol } from './nexus';
 import { Pool } from './pool';
 import { Node } from './node';
+import { Snapshot } from './snapshot';
 
 const log = require('./logger').Logger('volume');
 const { GrpcCode, GrpcError } = require('./grpc_client');
@@ -195,6 +196,79 @@ export class Volume {
     }
   }
 
+  // Grow the volume to the required size. The replicas are grown first and
+  // then the nexus, which notifies the initiator about the new size. If any
+  // of the replicas does not have room to grow in its pool, the expansion
+  // fails before any replica is touched to keep the replicas consistent.
+  //
+  // @params requiredBytes   The volume must have at least this size.
+  // @params [limitBytes]    The volume should not be bigger than this.
+  // @returns New size of the volume.
+  async resize(requiredBytes: number, limitBytes?: number): Promise<number> {
+    if (!requiredBytes || requiredBytes < 0) {
+      throw new GrpcError(
+        GrpcCode.INVALID_ARGUMENT,
+        'Required bytes must be greater than zero'
+      );
+    }
+    if (limitBytes && requiredBytes > limitBytes) {
+      throw new GrpcError(
+        GrpcCode.INVALID_ARGUMENT,
+        `Required bytes ${requiredBytes} are greater than limit ${limitBytes}`
+      );
+    }
+    if (requiredBytes < this.size) {
+      throw new GrpcError(
+        GrpcCode.INVALID_ARGUMENT,
+        `Shrinking the volume "${this}" is not supported`
+      );
+    }
+    if (requiredBytes === this.size) {
+      return this.size;
+    }
+    if (this.state !== VolumeState.Healthy) {
+      throw new GrpcError(
+        GrpcCode.FAILED_PRECONDITION,
+        `Cannot expand the volume "${this}" in ${this.state} state`
+      );
+    }
+
+    // check that all replicas can grow before growing any of them
+    const replicas = Object.values(this.replicas);
+    for (const replica of replicas) {
+      if (replica.isOffline()) {
+        throw new GrpcError(
+          GrpcCode.UNAVAILABLE,
+          `Cannot expand the volume "${this}" with offline replica "${replica}"`
+        );
+      }
+      const growth = requiredBytes - replica.size;
+      if (growth > 0 && this.registry.availableBytes(replica.pool) < growth) {
+        throw new GrpcError(
+          GrpcCode.RESOURCE_EXHAUSTED,
+          `Not enough free space in pool "${replica.pool}" to expand the ` +
+            `replica "${replica}" to ${requiredBytes} bytes`
+        );
+      }
+    }
+
+    log.debug(`Expanding the volume "${this}" to ${requiredBytes} bytes ...`);
+    for (const replica of replicas) {
+      if (replica.size < requiredBytes) {
+        await replica.resize(requiredBytes);
+      }
+    }
+    if (this.nexus) {
+      await this.nexus.resize(requiredBytes);
+    }
+    this.size = requiredBytes;
+    this.requiredBytes = requiredBytes;
+    this.limitBytes = limitBytes || 0;
+    log.info(`Volume "${this}" expanded to ${this.size} bytes`);
+    this.emitEvent('mod');
+    return this.size;
+  }
+
   // Delete nexus and destroy all replicas of the volume.
   async destroy() {
     this.publishedOn = undefined;
@@ -487,19 +561,46 @@ export class Volume {
   // NOTE: Until we switch state from "pending" at the end, the volume is not
   // acted upon by FSA. That's exactly what we want, because the async events
   // produced by this function do not interfere with execution of the "create".
-  async create() {
+  //
+  // A volume restored from a snapshot gets only as many replicas as there are
+  // copies of the snapshot. Remaining replicas are created and rebuilt when
+  // the nexus is created, as an empty replica must not become a child of a
+  // new nexus without the rebuild.
+  //
+  // @param snapshot   Snapshot to restore the volume from.
+  //
+  async create(snapshot?: Snapshot) {
     log.debug(`Creating the volume "${this}"`);
 
-    // Ensure there is sufficient number of replicas for the volume.
-    const newReplicaCount = this.replicaCount - Object.keys(this.replicas).length;
-    if (newReplicaCount > 0) {
-      // create more replicas if higher replication factor is desired
-      await this._createReplicas(newReplicaCount);
+    if (snapshot) {
+      await this._cloneReplicas(snapshot);
+    } else {
+      // Ensure there is sufficient number of replicas for the volume.
+      const newReplicaCount = this.replicaCount - Object.keys(this.replicas).length;
+      if (newReplicaCount > 0) {
+        // create more replicas if higher replication factor is desired
+        await this._createReplicas(newReplicaCount);
+      }
     }
     this._setState(VolumeState.Healthy);
     log.info(`Volume "${this}" with ${this.replicaCount} replica(s) and size ${this.size} was created`);
   }
 
+  // Take a snapshot of the volume. The volume must be published, because the
+  // snapshot is taken by the nexus on all replicas at once.
+  //
+  // @returns Name of the snapshot.
+  //
+  async createSnapshot(): Promise<string> {
+    if (!this.nexus) {
+      throw new GrpcError(
+        GrpcCode.FAILED_PRECONDITION,
+        `Cannot create snapshot of volume "${this}" which is not published`
+      );
+    }
+    return this.nexus.createSnapshot();
+  }
+
   // Update child devices of existing nexus or create a new nexus if it does not
   // exist.
   //
@@ -545,7 +646,7 @@ export class Volume {
     if (!this.size) {
       this.size = Math.min(
         pools.reduce(
-          (acc, pool) => Math.min(acc, pool.freeBytes()),
+          (acc, pool) => Math.min(acc, this.registry.availableBytes(pool)),
           Number.MAX_SAFE_INTEGER
         ),
         this.limitBytes || this.requiredBytes
@@ -561,7 +662,11 @@ export class Volume {
 
       try {
         // this will add the replica to the cache if successful
-        await pool.createReplica(this.uuid, this.size);
+        await pool.createReplica(
+          this.uuid,
+          this.size,
+          this.registry.thinReplicas()
+        );
       } catch (err) {
         log.error(err.message);
         errors.push(err.message);
@@ -577,6 +682,63 @@ export class Volume {
     }
   }
 
+  // Create replicas of the volume as clones of the snapshot. A clone can only
+  // be created in the pool holding the copy of the snapshot, so the copies
+  // decide where the replicas go. Clones are grown if the volume should be
+  // bigger than the snapshot.
+  //
+  // @param snapshot   Snapshot to clone the replicas from.
+  //
+  async _cloneReplicas(snapshot: Snapshot) {
+    if (this.limitBytes && snapshot.size > this.limitBytes) {
+      throw new GrpcError(
+        GrpcCode.OUT_OF_RANGE,
+        `Snapshot "${snapshot}" of size ${snapshot.size} does not fit ` +
+        `into the volume limit ${this.limitBytes}`
+      );
+    }
+    const usedNodes = Object.keys(this.replicas);
+    const copies = snapshot.copies.filter((c) => {
+      return (
+        c.pool.isAccessible() &&
+        usedNodes.indexOf(c.pool.node.name) < 0 &&
+        (this.requiredNodes.length === 0 ||
+          this.requiredNodes.indexOf(c.pool.node.name) >= 0)
+      );
+    });
+    if (copies.length === 0) {
+      throw new GrpcError(
+        GrpcCode.RESOURCE_EXHAUSTED,
+        `No accessible copy of snapshot "${snapshot}" for volume "${this}"`
+      );
+    }
+    this.size = Math.max(snapshot.size, this.requiredBytes);
+
+    let count = this.replicaCount - usedNodes.length;
+    let created = 0;
+    const errors = [];
+    for (let i = 0; i < copies.length && count > 0; i++) {
+      const pool = copies[i].pool;
+      try {
+        const replica = await pool.cloneSnapshot(snapshot.name, this.uuid);
+        if (replica.size < this.size) {
+          await replica.resize(this.size);
+        }
+      } catch (err) {
+        log.error(err.message);
+        errors.push(err.message);
+        continue;
+      }
+      count--;
+      created++;
+    }
+    if (created === 0) {
+      let msg = `Failed to restore volume "${this}" from snapshot "${snapshot}": `;
+      msg += errors.join('. ');
+      throw new GrpcError(GrpcCode.INTERNAL, msg);
+    }
+  }
+
   // Get list of replicas for this volume sorted from the most to the
   // least preferred.
   //
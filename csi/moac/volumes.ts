@@ -2,7 +2,9 @@
 
 import assert from 'assert';
 import { Nexus } from './nexus';
+import { Pool } from './pool';
 import { Replica } from './replica';
+import { Snapshot } from './snapshot';
 import { Volume, VolumeState } from './volume';
 
 const EventEmitter = require('events');
@@ -15,12 +17,20 @@ export class Volumes extends EventEmitter {
   private registry: any;
   private events: any; // stream of events from registry
   private volumes: Record<string, Volume>; // volumes indexed by uuid
+  // Names of snapshots indexed by the name given to them by the user. This is
+  // what makes create snapshot idempotent and it does not survive restart.
+  private snapshotNames: Record<string, string>;
+  // Names of snapshots taken to clone a volume from another volume indexed
+  // by uuid of the clone. The snapshot is destroyed with the clone.
+  private cloneSnapshots: Record<string, string>;
 
   constructor (registry: any) {
     super();
     this.registry = registry;
     this.events = null;
     this.volumes = {};
+    this.snapshotNames = {};
+    this.cloneSnapshots = {};
   }
 
   start() {
@@ -100,6 +110,8 @@ export class Volumes extends EventEmitter {
   // @params  {number}   spec.requiredBytes   The volume must have at least this size.
   // @params  {number}   spec.limitBytes      The volume should not be bigger than this.
   // @params  {string}   spec.protocol        The share protocol for the nexus.
+  // @params  {string}   [spec.snapshot]      Snapshot to restore the volume from.
+  // @params  {string}   [spec.sourceVolume]  Volume to clone the volume from.
   // @returns {object}   New volume object.
   //
   async createVolume(uuid: string, spec: any): Promise<Volume> {
@@ -109,6 +121,21 @@ export class Volumes extends EventEmitter {
         'Required bytes must be greater than zero'
       );
     }
+    let snapshot: Snapshot | undefined;
+    if (spec.sourceVolume) {
+      // The clone is restored from a snapshot of the source volume taken just
+      // for that purpose. The source volume stays in use as it is.
+      snapshot = await this.createSnapshot(spec.sourceVolume, `clone-${uuid}`);
+      this.cloneSnapshots[uuid] = snapshot.name;
+    } else if (spec.snapshot) {
+      snapshot = (await this.listSnapshots(spec.snapshot))[0];
+      if (!snapshot) {
+        throw new GrpcError(
+          GrpcCode.NOT_FOUND,
+          `Snapshot "${spec.snapshot}" does not exist`
+        );
+      }
+    }
     let volume = this.volumes[uuid];
     if (volume) {
       volume.update(spec);
@@ -135,7 +162,7 @@ export class Volumes extends EventEmitter {
       }
 
       try {
-        await volume.create();
+        await volume.create(snapshot);
       } catch (err) {
         // undo the pending state
         delete this.volumes[uuid];
@@ -144,6 +171,7 @@ export class Volumes extends EventEmitter {
         } catch (err) {
           log.error(`Failed to destroy "${volume}": ${err}`);
         }
+        await this._destroyCloneSnapshot(uuid);
         throw err;
       }
       volume.fsa();
@@ -164,6 +192,126 @@ export class Volumes extends EventEmitter {
 
     await volume.destroy();
     delete this.volumes[uuid];
+    await this._destroyCloneSnapshot(uuid);
+  }
+
+  // Destroy the snapshot that the volume was cloned from if it was taken
+  // only to clone the volume from another volume.
+  //
+  // @param   uuid            ID of the clone.
+  //
+  async _destroyCloneSnapshot(uuid: string) {
+    const snapshot = this.cloneSnapshots[uuid];
+    if (!snapshot) return;
+
+    try {
+      await this.destroySnapshot(snapshot);
+    } catch (err) {
+      log.warn(`Failed to destroy snapshot "${snapshot}" of clone "${uuid}": ${err}`);
+    }
+    delete this.cloneSnapshots[uuid];
+  }
+
+  // Take a snapshot of the volume. The method is idempotent - if a snapshot
+  // with the same name has been created before, then it is returned.
+  //
+  // @param   uuid   ID of the volume.
+  // @param   name   Name of the snapshot given by the user.
+  // @returns Created snapshot.
+  //
+  async createSnapshot(uuid: string, name: string): Promise<Snapshot> {
+    const existing = this.snapshotNames[name];
+    if (existing) {
+      const snapshot = (await this.listSnapshots(existing))[0];
+      if (snapshot && snapshot.volumeUuid !== uuid) {
+        throw new GrpcError(
+          GrpcCode.ALREADY_EXISTS,
+          `Snapshot "${name}" already exists for volume "${snapshot.volumeUuid}"`
+        );
+      }
+      if (snapshot) return snapshot;
+    }
+    const volume = this.volumes[uuid];
+    if (!volume) {
+      throw new GrpcError(
+        GrpcCode.NOT_FOUND,
+        `Volume "${uuid}" does not exist`
+      );
+    }
+    const snapshotName = await volume.createSnapshot();
+    const snapshot = (await this.listSnapshots(snapshotName))[0];
+    if (!snapshot) {
+      throw new GrpcError(
+        GrpcCode.INTERNAL,
+        `Snapshot "${snapshotName}" of volume "${uuid}" was not found`
+      );
+    }
+    this.snapshotNames[name] = snapshotName;
+    return snapshot;
+  }
+
+  // Destroy all copies of the snapshot. A snapshot that volumes have been
+  // restored from cannot be destroyed until those volumes are destroyed.
+  //
+  // The method is idempotent - if the snapshot does not exist it does not
+  // return an error.
+  //
+  // @param   name   Name (ID) of the snapshot.
+  //
+  async destroySnapshot(name: string) {
+    const snapshot = (await this.listSnapshots(name))[0];
+    if (snapshot) {
+      const clones = snapshot.getClones();
+      if (clones.length > 0) {
+        throw new GrpcError(
+          GrpcCode.FAILED_PRECONDITION,
+          `Snapshot "${name}" is used by volume(s): ${clones.join(', ')}`
+        );
+      }
+      for (const copy of snapshot.copies) {
+        await copy.pool.node.call('destroySnapshot', { name });
+      }
+      log.info(`Destroyed snapshot "${name}"`);
+    }
+    for (const userName in this.snapshotNames) {
+      if (this.snapshotNames[userName] === name) {
+        delete this.snapshotNames[userName];
+      }
+    }
+  }
+
+  // Get snapshots from storage nodes. The copies of the snapshot found on the
+  // replicas of a volume are merged into a single snapshot object.
+  //
+  // @param   [name]   Name (ID) of the snapshot to return.
+  // @returns Array of snapshots.
+  //
+  async listSnapshots(name?: string): Promise<Snapshot[]> {
+    const snapshots: Record<string, Snapshot> = {};
+    const nodes = this.registry.getNode().filter((n: any) => n.isSynced());
+
+    for (const node of nodes) {
+      let reply;
+      try {
+        reply = await node.call('listSnapshots', {});
+      } catch (err) {
+        log.warn(`Failed to list snapshots on node "${node}": ${err}`);
+        continue;
+      }
+      reply.snapshots
+        .filter((s: any) => !name || s.name === name)
+        .forEach((s: any) => {
+          const pool = node.pools.find((p: Pool) => p.name === s.pool);
+          if (!pool) return;
+          let snapshot = snapshots[s.name];
+          if (!snapshot) {
+            snapshot = new Snapshot(s);
+            snapshots[s.name] = snapshot;
+          }
+          snapshot.addCopy(pool, s.clones);
+        });
+    }
+    return Object.values(snapshots);
   }
 
   // Import the volume object (just the object) and add it to the internal list
@@ -53,10 +53,19 @@ pub async fn publish_block_volume(
         )
     })? {
         let path_target = Path::new(target_path);
-        if path_target.exists()
-            && !path_target.is_file()
-            && !path_target.is_dir()
-        {
+
+        // The device node is bind mounted onto a file, so that the workload
+        // sees a block special file rather than a mounted filesystem.
+        if path_target.is_dir() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to publish volume {}: target path {} is a directory",
+                volume_id,
+                target_path
+            ));
+        }
+
+        if path_target.exists() && !path_target.is_file() {
             //target exists and is a special file
 
             // Idempotency, if we have done this already just return success.
@@ -93,7 +102,8 @@ pub async fn publish_block_volume(
             }
         }
 
-        if !path_target.exists() {
+        let created = !path_target.exists();
+        if created {
             std::fs::File::create(&target_path)?;
         }
 
@@ -102,6 +112,14 @@ pub async fn publish_block_volume(
             target_path.as_str(),
             msg.readonly,
         ) {
+            if created {
+                if let Err(error) = std::fs::remove_file(target_path) {
+                    warn!(
+                        "Failed to remove block file {}: {}",
+                        target_path, error
+                    );
+                }
+            }
             return Err(failure!(
                 Code::Internal,
                 "Failed to publish volume {}: {}",
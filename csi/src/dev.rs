@@ -116,6 +116,26 @@ impl Device {
         Ok(None)
     }
 
+    /// Make the kernel re-read the capacity of an attached device after the
+    /// volume has been expanded. NVMe namespaces are updated by the kernel
+    /// when the target signals the change, SCSI devices must be rescanned.
+    /// Returns the resulting capacity of the device in bytes.
+    pub fn rescan(devname: &str) -> Result<u64, DeviceError> {
+        let name = devname.trim_start_matches("/dev/");
+
+        let rescan = format!("/sys/class/block/{}/device/rescan", name);
+        if std::path::Path::new(&rescan).exists() {
+            std::fs::write(&rescan, "1")?;
+        }
+
+        // the size is always reported in 512 byte sectors
+        let size = std::fs::read_to_string(format!(
+            "/sys/class/block/{}/size",
+            name
+        ))?;
+        Ok(size.trim().parse::<u64>()? * 512)
+    }
+
     /// Wait for a device to show up in udev
     /// once attach() has been called.
     pub async fn wait_for_device(
@@ -1,5 +1,6 @@
 use std::convert::TryFrom;
 
+use nvmeadm::nvmf_subsystem::NvmeSubsystems;
 use udev::Enumerator;
 use url::Url;
 use uuid::Uuid;
@@ -73,6 +74,16 @@ impl TryFrom<&Url> for NvmfAttach {
 #[tonic::async_trait]
 impl Attach for NvmfAttach {
     async fn attach(&self) -> Result<(), DeviceError> {
+        // connecting again would create a second controller for the same
+        // subsystem, so leave an existing connection alone
+        if NvmeSubsystems::new()?
+            .filter_map(Result::ok)
+            .any(|subsystem| subsystem.nqn == self.nqn)
+        {
+            debug!("nvmf target {} is already connected", self.nqn);
+            return Ok(());
+        }
+
         if let Err(error) =
             nvmeadm::nvmf_discovery::connect(&self.host, self.port, &self.nqn)
         {
@@ -123,10 +134,7 @@ impl NvmfDetach {
 impl Detach for NvmfDetach {
     async fn detach(&self) -> Result<(), DeviceError> {
         if nvmeadm::nvmf_discovery::disconnect(&self.nqn)? == 0 {
-            return Err(DeviceError::from(format!(
-                "nvmf disconnect {} failed: no device found",
-                self.nqn
-            )));
+            debug!("nvmf target {} is already disconnected", self.nqn);
         }
 
         Ok(())
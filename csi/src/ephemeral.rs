@@ -0,0 +1,330 @@
+//! Functions for CSI publish and unpublish of ephemeral (inline) volumes.
+//!
+//! An ephemeral volume has no persistent volume claim: it is created by the
+//! volume service when it is published and destroyed when it is unpublished.
+//! The uuid of the volume is derived from the volume id chosen by kubelet,
+//! so that the volume can be found again on unpublish and after a restart.
+
+use std::{fs, io::ErrorKind, path::PathBuf, time::Duration};
+
+use mbus_api::{
+    message_bus::v0::{MessageBus, MessageBusTrait},
+    v0::{
+        CreateVolume,
+        DestroyVolume,
+        Filter,
+        NodeId,
+        PublishVolume,
+        UnpublishVolume,
+        Volume,
+        VolumeId,
+    },
+};
+use tonic::{Code, Status};
+use uuid::Uuid;
+
+macro_rules! failure {
+    (Code::$code:ident, $msg:literal) => {{ error!($msg); Status::new(Code::$code, $msg) }};
+    (Code::$code:ident, $fmt:literal $(,$args:expr)+) => {{ let message = format!($fmt $(,$args)+); error!("{}", message); Status::new(Code::$code, message) }};
+}
+
+use crate::{
+    csi::{volume_capability::MountVolume, *},
+    dev::Device,
+    format::prepare_device,
+    mount,
+};
+
+/// Volume context key set by kubelet for ephemeral volumes
+const EPHEMERAL_KEY: &str = "csi.storage.k8s.io/ephemeral";
+/// Size of an ephemeral volume without the "size" attribute
+const DEFAULT_SIZE: u64 = 1024 * 1024 * 1024;
+
+const ATTACH_TIMEOUT_INTERVAL: Duration = Duration::from_millis(100);
+const ATTACH_RETRIES: u32 = 100;
+
+/// Return true if the volume to publish is an ephemeral volume.
+pub fn is_ephemeral(msg: &NodePublishVolumeRequest) -> bool {
+    msg.volume_context
+        .get(EPHEMERAL_KEY)
+        .map_or(false, |value| value == "true")
+}
+
+/// Uuid of the ephemeral volume with the given volume id. Name based (v5)
+/// uuids are used for ephemeral volumes only, which tells them apart from
+/// other volumes when reclaiming them.
+fn ephemeral_uuid(volume_id: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, volume_id.as_bytes())
+}
+
+/// Find the volume among the volumes known to the volume service.
+async fn find_volume(uuid: &VolumeId) -> Result<Option<Volume>, String> {
+    let volumes = MessageBus::get_volumes(Filter::Volume(uuid.clone()))
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok(volumes.into_iter().find(|volume| &volume.uuid == uuid))
+}
+
+/// Create the volume, unless it exists already, publish it on the node and
+/// attach it. Returns the path of the device.
+async fn create_and_attach(
+    uuid: &VolumeId,
+    size: u64,
+    node: &NodeId,
+) -> Result<String, String> {
+    if find_volume(uuid).await?.is_none() {
+        debug!("Creating ephemeral volume {} of size {}", uuid, size);
+        MessageBus::create_volume(CreateVolume {
+            uuid: uuid.clone(),
+            size,
+            nexuses: 1,
+            replicas: 1,
+            allowed_nodes: vec![node.clone()],
+            ..Default::default()
+        })
+        .await
+        .map_err(|error| format!("failed to create volume: {}", error))?;
+    }
+
+    let uri = MessageBus::publish_volume(PublishVolume {
+        uuid: uuid.clone(),
+        node: node.clone(),
+        force: false,
+    })
+    .await
+    .map_err(|error| format!("failed to publish volume: {}", error))?;
+
+    let device = Device::parse(&uri).map_err(|error| {
+        format!("error parsing URI {}: {}", uri, error)
+    })?;
+    if let Some(device_path) = device.find().await.map_err(|error| {
+        format!("error locating device for URI {}: {}", uri, error)
+    })? {
+        return Ok(device_path);
+    }
+
+    debug!("Attaching ephemeral volume {}", uuid);
+    device
+        .attach()
+        .await
+        .map_err(|error| format!("attach failed: {}", error))?;
+    Device::wait_for_device(device, ATTACH_TIMEOUT_INTERVAL, ATTACH_RETRIES)
+        .await
+        .map_err(|error| error.to_string())
+}
+
+/// Detach the device of the volume, if attached, and destroy the volume.
+async fn detach_and_destroy(uuid: &Uuid) -> Result<(), String> {
+    if let Some(device) = Device::lookup(uuid)
+        .await
+        .map_err(|error| format!("error locating device: {}", error))?
+    {
+        debug!("Detaching device {}", device.devname());
+        device.detach().await.map_err(|error| {
+            format!("failed to detach device {}: {}", device.devname(), error)
+        })?;
+    }
+
+    let id = VolumeId::from(uuid.to_string());
+    if find_volume(&id).await?.is_some() {
+        MessageBus::unpublish_volume(UnpublishVolume {
+            uuid: id.clone(),
+        })
+        .await
+        .map_err(|error| format!("failed to unpublish volume: {}", error))?;
+        MessageBus::delete_volume(DestroyVolume {
+            uuid: id.clone(),
+        })
+        .await
+        .map_err(|error| format!("failed to destroy volume: {}", error))?;
+        info!("Ephemeral volume {} destroyed", id);
+    }
+    Ok(())
+}
+
+/// Create an ephemeral volume on the node and mount it at the target path
+pub async fn publish_ephemeral_volume(
+    msg: &NodePublishVolumeRequest,
+    mnt: &MountVolume,
+    filesystems: &[String],
+    node_name: &str,
+) -> Result<(), Status> {
+    let target_path = &msg.target_path;
+    let volume_id = &msg.volume_id;
+
+    if mount::find_mount(None, Some(target_path)).is_some() {
+        info!(
+            "Ephemeral volume {} is already published to {}",
+            volume_id, target_path
+        );
+        return Ok(());
+    }
+
+    if let Err(error) = mount::check_options(&mnt.mount_flags) {
+        return Err(failure!(
+            Code::InvalidArgument,
+            "Failed to publish ephemeral volume {}: {}",
+            volume_id,
+            error
+        ));
+    }
+
+    let requested = if mnt.fs_type.is_empty() {
+        msg.volume_context
+            .get("fsType")
+            .map(String::as_str)
+            .unwrap_or_default()
+    } else {
+        mnt.fs_type.as_str()
+    };
+    let fstype = if requested.is_empty() {
+        filesystems[0].as_str()
+    } else {
+        match filesystems.iter().find(|&entry| entry == requested) {
+            Some(fstype) => fstype.as_str(),
+            None => {
+                return Err(failure!(
+                    Code::InvalidArgument,
+                    "Failed to publish ephemeral volume {}: unsupported filesystem type: {}",
+                    volume_id,
+                    requested
+                ));
+            }
+        }
+    };
+
+    let size = match msg.volume_context.get("size") {
+        Some(size) => size.parse::<u64>().map_err(|error| {
+            failure!(
+                Code::InvalidArgument,
+                "Failed to publish ephemeral volume {}: invalid size {}: {}",
+                volume_id,
+                size,
+                error
+            )
+        })?,
+        None => DEFAULT_SIZE,
+    };
+
+    let uuid = ephemeral_uuid(volume_id);
+    let id = VolumeId::from(uuid.to_string());
+    let node = NodeId::from(node_name);
+
+    let result: Result<(), String> = async {
+        let device_path = create_and_attach(&id, size, &node).await?;
+
+        if let Err(error) = fs::create_dir_all(PathBuf::from(target_path)) {
+            if error.kind() != ErrorKind::AlreadyExists {
+                return Err(format!(
+                    "failed to create directory {}: {}",
+                    target_path, error
+                ));
+            }
+        }
+
+        prepare_device(&device_path, fstype).await.map_err(|error| {
+            format!("error preparing device {}: {}", device_path, error)
+        })?;
+
+        let mut options = mnt.mount_flags.clone();
+        if msg.readonly {
+            options.push(String::from("ro"));
+        }
+        mount::filesystem_mount(&device_path, target_path, fstype, &options)
+            .map_err(|error| {
+                format!(
+                    "failed to mount device {} onto {}: {}",
+                    device_path, target_path, error
+                )
+            })?;
+        Ok::<(), String>(())
+    }
+    .await;
+
+    if let Err(error) = result {
+        // do not leave a half created volume behind
+        if let Err(error) = detach_and_destroy(&uuid).await {
+            error!(
+                "Failed to clean up ephemeral volume {} ({}): {}",
+                volume_id, uuid, error
+            );
+        }
+        return Err(failure!(
+            Code::Internal,
+            "Failed to publish ephemeral volume {}: {}",
+            volume_id,
+            error
+        ));
+    }
+
+    info!(
+        "Ephemeral volume {} ({}) published to {}",
+        volume_id, uuid, target_path
+    );
+    Ok(())
+}
+
+/// Destroy the ephemeral volume with the given volume id, once it has been
+/// unmounted. Does nothing for volumes which are not ephemeral.
+pub async fn unpublish_ephemeral_volume(volume_id: &str) -> Result<(), Status> {
+    let uuid = ephemeral_uuid(volume_id);
+
+    // only ephemeral volumes are attached under their derived uuid
+    let attached = Device::lookup(&uuid).await.map_err(|error| {
+        failure!(
+            Code::Internal,
+            "Failed to unpublish volume {}: error locating device: {}",
+            volume_id,
+            error
+        )
+    })?;
+    if attached.is_none() {
+        return Ok(());
+    }
+
+    detach_and_destroy(&uuid).await.map_err(|error| {
+        failure!(
+            Code::Internal,
+            "Failed to unpublish ephemeral volume {}: {}",
+            volume_id,
+            error
+        )
+    })
+}
+
+/// Destroy the ephemeral volumes published on the node which are no longer
+/// mounted, eg: because the node plugin crashed before their pod was deleted.
+pub async fn reclaim_ephemeral_volumes(node_name: &str) -> Result<(), String> {
+    let node = NodeId::from(node_name);
+    let volumes = MessageBus::get_volumes(Filter::Node(node.clone()))
+        .await
+        .map_err(|error| error.to_string())?;
+
+    for volume in volumes {
+        let uuid = match Uuid::parse_str(volume.uuid.as_str()) {
+            Ok(uuid) if uuid.get_version_num() == 5 => uuid,
+            _ => continue,
+        };
+        if !volume.children.iter().any(|nexus| nexus.node == node) {
+            continue;
+        }
+
+        if let Some(device) = Device::lookup(&uuid)
+            .await
+            .map_err(|error| error.to_string())?
+        {
+            if mount::find_mount(Some(&device.devname()), None).is_some() {
+                continue;
+            }
+        }
+
+        info!("Reclaiming ephemeral volume {}", uuid);
+        if let Err(error) = detach_and_destroy(&uuid).await {
+            error!(
+                "Failed to reclaim ephemeral volume {}: {}",
+                uuid, error
+            );
+        }
+    }
+    Ok(())
+}
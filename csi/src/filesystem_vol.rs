@@ -1,4 +1,5 @@
-//! Functions for CSI stage, unstage, publish and unpublish filesystem volumes.
+//! Functions for CSI stage, unstage, publish, unpublish and expand filesystem
+//! volumes.
 
 use std::{fs, io::ErrorKind, path::PathBuf};
 
@@ -11,7 +12,7 @@ macro_rules! failure {
 
 use crate::{
     csi::{volume_capability::MountVolume, *},
-    format::prepare_device,
+    format::{grow_filesystem, prepare_device},
     mount::{self, subset, ReadOnly},
 };
 
@@ -39,17 +40,37 @@ pub async fn stage_fs_volume(
 
     debug!("Staging volume {} to {}", volume_id, fs_staging_path);
 
-    let fstype = if mnt.fs_type.is_empty() {
+    if let Err(error) = mount::check_options(&mnt.mount_flags) {
+        return Err(failure!(
+            Code::InvalidArgument,
+            "Failed to stage volume {}: {}",
+            volume_id,
+            error
+        ));
+    }
+
+    // The filesystem type of the volume capability takes precedence over
+    // the "fsType" parameter of the storage class.
+    let requested = if mnt.fs_type.is_empty() {
+        msg.volume_context
+            .get("fsType")
+            .map(String::as_str)
+            .unwrap_or_default()
+    } else {
+        mnt.fs_type.as_str()
+    };
+
+    let fstype = if requested.is_empty() {
         String::from(&filesystems[0])
     } else {
-        match filesystems.iter().find(|&entry| entry == &mnt.fs_type) {
+        match filesystems.iter().find(|&entry| entry == requested) {
             Some(fstype) => String::from(fstype),
             None => {
                 return Err(failure!(
                         Code::InvalidArgument,
                         "Failed to stage volume {}: unsupported filesystem type: {}",
                         volume_id,
-                        mnt.fs_type
+                        requested
                     ));
             }
         }
@@ -162,6 +183,15 @@ pub fn publish_fs_volume(
         volume_id, fs_staging_path, target_path
     );
 
+    if let Err(error) = mount::check_options(&mnt.mount_flags) {
+        return Err(failure!(
+            Code::InvalidArgument,
+            "Failed to publish volume {}: {}",
+            volume_id,
+            error
+        ));
+    }
+
     let staged =
         mount::find_mount(None, Some(&fs_staging_path)).ok_or_else(|| {
             failure!(
@@ -265,18 +295,22 @@ pub fn publish_fs_volume(
         ));
     }
 
-    if msg.readonly && !readonly {
+    // The options of a bind mount can only be changed by remounting it.
+    if msg.readonly || !mnt.mount_flags.is_empty() {
         let mut options = mnt.mount_flags.clone();
-        options.push(String::from("ro"));
+        if msg.readonly {
+            options.push(String::from("ro"));
+        }
 
-        debug!("Remounting {} as readonly", target_path);
+        debug!("Remounting {} with options {:?}", target_path, options);
 
         if let Err(error) = mount::bind_remount(&target_path, &options) {
             let message = format!(
-                    "Failed to publish volume {}: failed to mount {} to {} as readonly: {}",
+                    "Failed to publish volume {}: failed to mount {} to {} with options {:?}: {}",
                     volume_id,
                     fs_staging_path,
                     target_path,
+                    options,
                     error
                 );
 
@@ -346,3 +380,36 @@ pub fn unpublish_fs_volume(
     info!("Volume {} unpublished from {}", volume_id, target_path);
     Ok(())
 }
+
+pub async fn expand_fs_volume(
+    msg: &NodeExpandVolumeRequest,
+    device_path: String,
+) -> Result<(), Status> {
+    let volume_id = &msg.volume_id;
+    let volume_path = &msg.volume_path;
+
+    let mount = mount::find_mount(None, Some(volume_path)).ok_or_else(|| {
+        failure!(
+            Code::NotFound,
+            "Failed to expand volume {}: no filesystem mounted at {}",
+            volume_id,
+            volume_path
+        )
+    })?;
+
+    debug!("Expanding volume {} mounted at {}", volume_id, volume_path);
+
+    if let Err(error) =
+        grow_filesystem(&device_path, &mount.dest, &mount.fstype).await
+    {
+        return Err(failure!(
+            Code::Internal,
+            "Failed to expand volume {}: {}",
+            volume_id,
+            error
+        ));
+    }
+
+    info!("Volume {} filesystem grown at {}", volume_id, volume_path);
+    Ok(())
+}
@@ -1,4 +1,5 @@
-//! Utility function for formatting a device with filesystem
+//! Utility functions for formatting a device with filesystem and for growing
+//! the filesystem after the device has been expanded
 
 use std::process::Command;
 
@@ -17,8 +18,16 @@ pub(crate) async fn prepare_device(
         return Err(format!("probe failed: {}", error));
     }
 
+    // never reformat a device, even if the filesystem on it is not the
+    // one asked for
     if let Ok(fs) = probe.lookup_value("TYPE") {
         debug!("Found existing filesystem ({}) on device {}", fs, device);
+        if fs != fstype {
+            return Err(format!(
+                "existing filesystem ({}) does not match requested type ({})",
+                fs, fstype
+            ));
+        }
         return Ok(());
     }
 
@@ -46,3 +55,43 @@ pub(crate) async fn prepare_device(
         String::from_utf8(output.stderr).unwrap()
     ))
 }
+
+pub(crate) async fn grow_filesystem(
+    device: &str,
+    mountpoint: &str,
+    fstype: &str,
+) -> Result<(), String> {
+    debug!(
+        "Growing filesystem ({}) on device {} mounted at {}",
+        fstype, device, mountpoint
+    );
+
+    // xfs can only be grown while mounted and takes the mountpoint
+    let (binary, target) = match fstype {
+        "ext4" => ("resize2fs", device),
+        "xfs" => ("xfs_growfs", mountpoint),
+        _ => {
+            return Err(format!("unsupported filesystem type: {}", fstype));
+        }
+    };
+    let output = Command::new(binary)
+        .arg(target)
+        .output()
+        .map_err(|error| format!("failed to execute {}: {}", binary, error))?;
+
+    trace!(
+        "Output from {} command: {}",
+        binary,
+        String::from_utf8(output.stdout.clone()).unwrap()
+    );
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{} command failed: {}",
+        binary,
+        String::from_utf8(output.stderr).unwrap()
+    ))
+}
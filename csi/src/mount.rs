@@ -99,26 +99,57 @@ pub fn probe_filesystems() -> Vec<String> {
     vec![String::from("xfs"), String::from("ext4")]
 }
 
+/// Check the mount options supplied by the CO (e.g. from the mountOptions of
+/// a storage class). The options are passed through to the mount syscall,
+/// but they may not change the kind of mount performed.
+pub fn check_options(options: &[String]) -> Result<(), String> {
+    for entry in options {
+        if entry.is_empty()
+            || entry.contains(|c: char| c == ',' || c.is_whitespace())
+        {
+            return Err(format!("invalid mount option \"{}\"", entry));
+        }
+        if ["bind", "rbind", "remount", "move"].contains(&entry.as_str()) {
+            return Err(format!("mount option \"{}\" not allowed", entry));
+        }
+    }
+    Ok(())
+}
+
+// Utility function to map an option onto the corresponding flag,
+// for those options that are not specific to a filesystem.
+fn flag(option: &str) -> Option<MountFlags> {
+    match option {
+        "ro" => Some(MountFlags::RDONLY),
+        "rw" => Some(MountFlags::empty()),
+        "noatime" => Some(MountFlags::NOATIME),
+        "nodiratime" => Some(MountFlags::NODIRATIME),
+        "relatime" => Some(MountFlags::RELATIME),
+        "strictatime" => Some(MountFlags::STRICTATIME),
+        "nodev" => Some(MountFlags::NODEV),
+        "noexec" => Some(MountFlags::NOEXEC),
+        "nosuid" => Some(MountFlags::NOSUID),
+        "sync" => Some(MountFlags::SYNCHRONOUS),
+        "dirsync" => Some(MountFlags::DIRSYNC),
+        _ => None,
+    }
+}
+
 // Utility function to transform a vector of options
-// to the format required by sys_mount::Mount::new()
-fn parse(options: &[String]) -> (bool, String) {
+// to the format required by sys_mount::Mount::new():
+// the flags and the filesystem specific options (e.g. discard).
+fn parse(options: &[String]) -> (MountFlags, String) {
     let mut list: Vec<&str> = Vec::new();
-    let mut readonly: bool = false;
+    let mut flags = MountFlags::empty();
 
     for entry in options {
-        if entry == "ro" {
-            readonly = true;
-            continue;
+        match flag(entry) {
+            Some(value) => flags.insert(value),
+            None => list.push(entry),
         }
-
-        if entry == "rw" {
-            continue;
-        }
-
-        list.push(entry);
     }
 
-    (readonly, list.join(","))
+    (flags, list.join(","))
 }
 
 // Utility function to wrap a string in an Option.
@@ -153,13 +184,7 @@ pub fn filesystem_mount(
     fstype: &str,
     options: &[String],
 ) -> Result<Mount, Error> {
-    let mut flags = MountFlags::empty();
-
-    let (readonly, value) = parse(options);
-
-    if readonly {
-        flags.insert(MountFlags::RDONLY);
-    }
+    let (flags, value) = parse(options);
 
     let mount = Mount::new(
         device,
@@ -225,16 +250,9 @@ pub fn bind_mount(
 /// Bind remount a path to modify mount options.
 /// Assumes that target has already been bind mounted.
 pub fn bind_remount(target: &str, options: &[String]) -> Result<Mount, Error> {
-    let mut flags = MountFlags::empty();
-
-    let (readonly, value) = parse(options);
+    let (mut flags, value) = parse(options);
 
     flags.insert(MountFlags::BIND);
-
-    if readonly {
-        flags.insert(MountFlags::RDONLY);
-    }
-
     flags.insert(MountFlags::REMOUNT);
 
     let mount = Mount::new(
@@ -1,4 +1,10 @@
-use std::{boxed::Box, path::Path, time::Duration, vec::Vec};
+use std::{
+    boxed::Box,
+    collections::HashMap,
+    path::Path,
+    time::Duration,
+    vec::Vec,
+};
 
 use tonic::{Code, Request, Response, Status};
 
@@ -8,6 +14,10 @@ macro_rules! failure {
 }
 
 use glob::glob;
+use mbus_api::{
+    message_bus::v0::{MessageBus, MessageBusTrait},
+    v0::NodeId,
+};
 use uuid::Uuid;
 
 use crate::{
@@ -17,7 +27,13 @@ use crate::{
         *,
     },
     dev::Device,
+    ephemeral::{
+        is_ephemeral,
+        publish_ephemeral_volume,
+        unpublish_ephemeral_volume,
+    },
     filesystem_vol::{
+        expand_fs_volume,
         publish_fs_volume,
         stage_fs_volume,
         unpublish_fs_volume,
@@ -29,10 +45,19 @@ use crate::{
 pub struct Node {
     pub node_name: String,
     pub filesystems: Vec<String>,
+    /// the message bus is used to get the labels of the node and to create
+    /// ephemeral volumes
+    pub message_bus: bool,
 }
 
 const ATTACH_TIMEOUT_INTERVAL: Duration = Duration::from_millis(100);
 const ATTACH_RETRIES: u32 = 100;
+const LABELS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Topology key identifying the node, as already used by the controller
+const TOPOLOGY_KEY_NODE: &str = "kubernetes.io/hostname";
+/// Prefix of the topology keys of node labels without a prefix of their own
+const TOPOLOGY_KEY_PREFIX: &str = "openebs.io";
 
 // Determine if given access mode in conjunction with ro mount flag makes
 // sense or not. If access mode is not supported or the combination does
@@ -122,7 +147,53 @@ async fn detach(uuid: &Uuid, errheader: String) -> Result<(), Status> {
     Ok(())
 }
 
-impl Node {}
+impl Node {
+    /// Get the labels of the node from the node service. An empty set of
+    /// labels is returned when the message bus is not in use.
+    async fn get_labels(&self) -> Result<HashMap<String, String>, Status> {
+        if !self.message_bus {
+            return Ok(HashMap::new());
+        }
+
+        let id = NodeId::from(self.node_name.as_str());
+        match tokio::time::timeout(LABELS_TIMEOUT, MessageBus::get_node(&id))
+            .await
+        {
+            Ok(Ok(node)) => Ok(node.labels),
+            Ok(Err(error)) => Err(failure!(
+                Code::Unavailable,
+                "Failed to get the labels of node {}: {}",
+                id,
+                error
+            )),
+            Err(_) => Err(failure!(
+                Code::Unavailable,
+                "Failed to get the labels of node {}: timed out",
+                id
+            )),
+        }
+    }
+}
+
+/// Topology segments of the node: the node name and its labels, which are
+/// prefixed unless they already have a prefix like "topology.kubernetes.io/".
+fn topology_segments(
+    node_name: &str,
+    labels: HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut segments = labels
+        .into_iter()
+        .map(|(key, value)| {
+            if key.contains('/') {
+                (key, value)
+            } else {
+                (format!("{}/{}", TOPOLOGY_KEY_PREFIX, key), value)
+            }
+        })
+        .collect::<HashMap<_, _>>();
+    segments.insert(TOPOLOGY_KEY_NODE.to_string(), node_name.to_string());
+    segments
+}
 #[tonic::async_trait]
 impl node_server::Node for Node {
     async fn node_get_info(
@@ -132,16 +203,20 @@ impl node_server::Node for Node {
         let node_id = format!("mayastor://{}", &self.node_name);
         let max_volumes_per_node =
             glob("/dev/nbd*").expect("Invalid glob pattern").count() as i64;
+        let segments =
+            topology_segments(&self.node_name, self.get_labels().await?);
 
         debug!(
-            "NodeGetInfo request: ID={}, max volumes={}",
-            node_id, max_volumes_per_node,
+            "NodeGetInfo request: ID={}, max volumes={}, topology={:?}",
+            node_id, max_volumes_per_node, segments,
         );
 
         Ok(Response::new(NodeGetInfoResponse {
             node_id,
             max_volumes_per_node,
-            accessible_topology: None,
+            accessible_topology: Some(Topology {
+                segments,
+            }),
         }))
     }
 
@@ -149,11 +224,13 @@ impl node_server::Node for Node {
         &self,
         _request: Request<NodeGetCapabilitiesRequest>,
     ) -> Result<Response<NodeGetCapabilitiesResponse>, Status> {
-        let caps = vec![node_service_capability::rpc::Type::StageUnstageVolume];
+        let caps = vec![
+            node_service_capability::rpc::Type::StageUnstageVolume,
+            node_service_capability::rpc::Type::ExpandVolume,
+        ];
 
         debug!("NodeGetCapabilities request: {:?}", caps);
 
-        // We don't support stage/unstage and expand volume rpcs
         Ok(Response::new(NodeGetCapabilitiesResponse {
             capabilities: caps
                 .into_iter()
@@ -219,6 +296,43 @@ impl node_server::Node for Node {
             ));
         }
 
+        // Ephemeral volumes are not staged, they are created right here.
+        if is_ephemeral(&msg) {
+            if !self.message_bus {
+                return Err(failure!(
+                    Code::FailedPrecondition,
+                    "Failed to publish ephemeral volume {}: message bus is not configured",
+                    &msg.volume_id
+                ));
+            }
+            match get_access_type(&msg.volume_capability).map_err(|error| {
+                failure!(
+                    Code::InvalidArgument,
+                    "Failed to publish volume {}: {}",
+                    &msg.volume_id,
+                    error
+                )
+            })? {
+                AccessType::Mount(mnt) => {
+                    publish_ephemeral_volume(
+                        &msg,
+                        &mnt,
+                        &self.filesystems,
+                        &self.node_name,
+                    )
+                    .await?;
+                }
+                AccessType::Block(_) => {
+                    return Err(failure!(
+                        Code::InvalidArgument,
+                        "Failed to publish ephemeral volume {}: block volumes are not supported",
+                        &msg.volume_id
+                    ));
+                }
+            }
+            return Ok(Response::new(NodePublishVolumeResponse {}));
+        }
+
         // Note that the staging path is NOT optional,
         // as we advertise StageUnstageVolume.
         if msg.staging_target_path.is_empty() {
@@ -316,6 +430,11 @@ impl node_server::Node for Node {
                 unpublish_block_volume(&msg)?;
             }
         }
+
+        // An ephemeral volume is destroyed once it has been unmounted.
+        if self.message_bus {
+            unpublish_ephemeral_volume(&msg.volume_id).await?;
+        }
         Ok(Response::new(NodeUnpublishVolumeResponse {}))
     }
 
@@ -345,13 +464,86 @@ impl node_server::Node for Node {
         Err(Status::new(Code::Unimplemented, "Method not implemented"))
     }
 
+    /// This RPC is called by the CO after the volume has been expanded by
+    /// the controller. The device of the volume is rescanned to pick up its
+    /// new size and the filesystem on it, if any, is grown. Block volumes
+    /// only need the rescan.
     async fn node_expand_volume(
         &self,
         request: Request<NodeExpandVolumeRequest>,
     ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
         let msg = request.into_inner();
-        error!("Unimplemented {:?}", msg);
-        Err(Status::new(Code::Unimplemented, "Method not implemented"))
+
+        trace!("node_expand_volume {:?}", msg);
+
+        if msg.volume_id.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume: missing volume id"
+            ));
+        }
+
+        if msg.volume_path.is_empty() {
+            return Err(failure!(
+                Code::InvalidArgument,
+                "Failed to expand volume {}: missing volume path",
+                &msg.volume_id
+            ));
+        }
+
+        let uuid = Uuid::parse_str(&msg.volume_id).map_err(|error| {
+            failure!(
+                Code::Internal,
+                "Failed to expand volume {}: not a valid UUID: {}",
+                &msg.volume_id,
+                error
+            )
+        })?;
+
+        let device = Device::lookup(&uuid)
+            .await
+            .map_err(|error| {
+                failure!(
+                    Code::Internal,
+                    "Failed to expand volume {}: error locating device: {}",
+                    &msg.volume_id,
+                    error
+                )
+            })?
+            .ok_or_else(|| {
+                failure!(
+                    Code::NotFound,
+                    "Failed to expand volume {}: device not found",
+                    &msg.volume_id
+                )
+            })?;
+        let device_path = device.devname();
+
+        let capacity_bytes = Device::rescan(&device_path).map_err(|error| {
+            failure!(
+                Code::Internal,
+                "Failed to expand volume {}: error rescanning device {}: {}",
+                &msg.volume_id,
+                device_path,
+                error
+            )
+        })?;
+
+        let block = matches!(
+            get_access_type(&msg.volume_capability),
+            Ok(AccessType::Block(_))
+        );
+        if !block {
+            expand_fs_volume(&msg, device_path).await?;
+        }
+
+        info!(
+            "Volume {} expanded to {} bytes",
+            &msg.volume_id, capacity_bytes
+        );
+        Ok(Response::new(NodeExpandVolumeResponse {
+            capacity_bytes: capacity_bytes as i64,
+        }))
     }
 
     async fn node_stage_volume(
@@ -435,6 +627,9 @@ impl node_server::Node for Node {
             )
         })?;
 
+        // Remember whether the device was attached by this request, so that
+        // a failed stage does not detach a device already staged before.
+        let mut attached = false;
         let device_path = match device.find().await.map_err(|error| {
             failure!(
             Code::Internal,
@@ -457,6 +652,7 @@ impl node_server::Node for Node {
                         error
                     ));
                 }
+                attached = true;
 
                 Device::wait_for_device(
                     device,
@@ -482,14 +678,16 @@ impl node_server::Node for Node {
                     stage_fs_volume(&msg, device_path, &mnt, &self.filesystems)
                         .await
                 {
-                    detach(
-                        &uuid,
-                        format!(
-                            "Failed to stage volume {}: {};",
-                            &msg.volume_id, fsmount_error
-                        ),
-                    )
-                    .await?;
+                    if attached {
+                        detach(
+                            &uuid,
+                            format!(
+                                "Failed to stage volume {}: {};",
+                                &msg.volume_id, fsmount_error
+                            ),
+                        )
+                        .await?;
+                    }
                     return Err(fsmount_error);
                 }
             }
@@ -41,6 +41,7 @@ pub mod csi {
 
 mod block_vol;
 mod dev;
+mod ephemeral;
 mod error;
 mod filesystem_vol;
 mod findmnt;
@@ -92,6 +93,7 @@ impl AsyncWrite for UnixStream {
 }
 
 const GRPC_PORT: u16 = 10199;
+const NATS_PORT: u16 = 4222;
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
@@ -128,6 +130,14 @@ async fn main() -> Result<(), String> {
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("nats")
+                .short("b")
+                .long("nats")
+                .value_name("ENDPOINT")
+                .help("NATS server used to get the labels (topology) of the node and to create ephemeral volumes")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("v")
                 .short("v")
@@ -185,6 +195,25 @@ async fn main() -> Result<(), String> {
         }
     }
 
+    let message_bus = match matches.value_of("nats") {
+        Some(nats) => {
+            let nats = if nats.contains(':') {
+                nats.to_string()
+            } else {
+                format!("{}:{}", nats, NATS_PORT)
+            };
+            info!("Connecting to the message bus at {}", nats);
+            mbus_api::message_bus_init(nats).await;
+            if let Err(error) =
+                ephemeral::reclaim_ephemeral_volumes(node_name).await
+            {
+                error!("Failed to reclaim ephemeral volumes: {}", error);
+            }
+            true
+        }
+        None => false,
+    };
+
     let sock_addr = if endpoint.contains(':') {
         endpoint.to_string()
     } else {
@@ -192,7 +221,7 @@ async fn main() -> Result<(), String> {
     };
 
     let _ = tokio::join!(
-        CSIServer::run(csi_socket, node_name),
+        CSIServer::run(csi_socket, node_name, message_bus),
         MayastorNodePluginGrpcServer::run(
             sock_addr.parse().expect("Invalid gRPC endpoint")
         ),
@@ -204,7 +233,11 @@ async fn main() -> Result<(), String> {
 struct CSIServer {}
 
 impl CSIServer {
-    pub async fn run(csi_socket: &str, node_name: &str) -> Result<(), ()> {
+    pub async fn run(
+        csi_socket: &str,
+        node_name: &str,
+        message_bus: bool,
+    ) -> Result<(), ()> {
         let mut uds_sock = UnixListener::bind(csi_socket).unwrap();
         info!("CSI plugin bound to {}", csi_socket);
 
@@ -212,6 +245,7 @@ impl CSIServer {
             .add_service(NodeServer::new(Node {
                 node_name: node_name.into(),
                 filesystems: probe_filesystems(),
+                message_bus,
             }))
             .add_service(IdentityServer::new(Identity {}))
             .serve_with_incoming(uds_sock.incoming().map_ok(UnixStream))
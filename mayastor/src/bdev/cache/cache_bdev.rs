@@ -0,0 +1,457 @@
+//!
+//! The cache bdev is a virtual bdev on top of a backing bdev which keeps
+//! recently read blocks in memory. Reads of which all blocks are cached are
+//! completed from memory, any other read goes to the backing bdev and the
+//! blocks read are added to the cache. Writes, unmaps and write zeroes go
+//! to the backing bdev and drop the blocks they cover from the cache, both
+//! when they are submitted and when they complete, so that a read racing
+//! with them does not leave stale data behind.
+
+use std::{
+    convert::TryFrom,
+    ffi::c_void,
+    fmt::{Debug, Formatter},
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+};
+
+use crate::{
+    bdev::{
+        cache::{
+            cache_fn_table::CacheFnTable,
+            cache_lru::{BlockCache, CacheStats, EvictionPolicy},
+            cache_module::{CacheModule, CACHE_MODULE},
+        },
+        nexus::nexus_io::{Bio, IoStatus},
+    },
+    core::{Bdev, BdevHandle, CoreError, Descriptor},
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const CACHE_PRODUCT_ID: &str = "Cache Bdev";
+
+/// context of an IO submitted to the cache bdev
+#[derive(Debug)]
+pub struct CacheIoCtx {
+    /// generation of the cache when a read was sent to the backing bdev
+    generation: u64,
+}
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct CacheChannel {
+    handle: *mut BdevHandle,
+}
+
+impl CacheChannel {
+    /// allocates a handle to the backing bdev for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let cache = unsafe { CacheBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut CacheChannel) };
+
+        match cache.desc.as_ref().map(|d| BdevHandle::try_from(d.clone())) {
+            Some(Ok(handle)) => {
+                ch.handle = Box::into_raw(Box::new(handle));
+                0
+            }
+            _ => {
+                error!("{}: failed to create IO channel", cache.name);
+                ch.handle = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut CacheChannel) };
+        if !ch.handle.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handle) };
+            ch.handle = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handle to the backing bdev of the given channel
+    pub(crate) fn handle<'a>(channel: *mut spdk_io_channel) -> &'a BdevHandle {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut CacheChannel;
+            &*(*ctx).handle
+        }
+    }
+}
+
+pub struct CacheBdev {
+    /// name of the cache bdev
+    pub name: String,
+    /// name of the bdev the cache is on top of
+    pub backing: String,
+    /// the cache bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    /// descriptor of the backing bdev
+    desc: Option<Arc<Descriptor>>,
+    policy: EvictionPolicy,
+    cache: Mutex<BlockCache>,
+}
+
+impl Debug for CacheBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (backing: {}, policy: {})",
+            self.name, self.backing, self.policy
+        )
+    }
+}
+
+impl Drop for CacheBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl CacheBdev {
+    /// Create a cache bdev of the given size in bytes on top of the backing
+    /// bdev and register it with SPDK.
+    pub(crate) async fn create(
+        name: &str,
+        backing: &str,
+        size: u64,
+        policy: EvictionPolicy,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+
+        let desc = base.open(true).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?;
+
+        let cache =
+            BlockCache::new(size, base.block_len(), base.alignment(), policy)
+                .map_err(|_| NexusBdevError::CreateBdev {
+                    source: Errno::ENOMEM,
+                    name: name.to_string(),
+                })?;
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = CACHE_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = CacheFnTable::table();
+        b.module = CACHE_MODULE.as_ptr();
+        b.blocklen = base.block_len();
+        b.blockcnt = base.num_blocks();
+        b.required_alignment = unsafe { (*base.as_ptr()).required_alignment };
+
+        let mut c = Box::new(CacheBdev {
+            name: name.to_string(),
+            backing: backing.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            desc: Some(Arc::new(desc)),
+            policy,
+            cache: Mutex::new(cache),
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*c.bdev.as_ptr()).ctxt = c.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                c.as_ptr(),
+                Some(CacheChannel::create),
+                Some(CacheChannel::destroy),
+                std::mem::size_of::<CacheChannel>() as u32,
+                (*c.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(c.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(c.as_ptr(), None);
+            }
+            c.desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        info!("{}: created {:?}", name, c);
+        CacheModule::get_instances().push(c);
+        Ok(name.to_string())
+    }
+
+    /// Unregister the cache bdev, which closes the backing bdev.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match cache_lookup(name) {
+            Some(cache) => cache.bdev.clone(),
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the cache bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the backing bdev
+        self.desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// the backing bdev of the cache
+    pub(crate) fn backing_bdev(&self) -> Option<Bdev> {
+        self.desc.as_ref().map(|d| d.get_bdev())
+    }
+
+    /// the eviction policy of the cache
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// counters of the cache
+    pub fn stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut CacheBdev)
+    }
+
+    /// obtain the CacheBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), CACHE_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    fn ctx<'a>(io: &Bio) -> &'a mut CacheIoCtx {
+        unsafe {
+            &mut *((*io.as_ptr()).driver_ctx.as_mut_ptr() as *mut CacheIoCtx)
+        }
+    }
+
+    fn iovs<'a>(io: &Bio) -> &'a [spdk_sys::iovec] {
+        unsafe {
+            std::slice::from_raw_parts(io.iovs(), io.iov_count() as usize)
+        }
+    }
+
+    /// complete an IO submitted to the cache bdev
+    pub(crate) fn complete(io: &Bio, success: bool) {
+        let status = if success {
+            IoStatus::Success
+        } else {
+            IoStatus::Failed
+        };
+        unsafe { spdk_bdev_io_complete(io.as_ptr(), status.into()) }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let cache = Self::from_io(&bio);
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", cache.name, bio);
+            bio.fail();
+            return;
+        }
+        cache.readv(&bio, CacheChannel::handle(ch));
+    }
+
+    /// read from the cache if all blocks are cached, from the backing bdev
+    /// otherwise
+    pub(crate) fn readv(&self, io: &Bio, handle: &BdevHandle) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * io.block_len(),
+                )
+            }
+            return;
+        }
+
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.read(io.offset(), io.num_blocks(), Self::iovs(io)) {
+                drop(cache);
+                Self::complete(io, true);
+                return;
+            }
+            Self::ctx(io).generation = cache.generation();
+        }
+
+        let (desc, ch) = handle.io_tuple();
+        let rc = unsafe {
+            spdk_sys::spdk_bdev_readv_blocks(
+                desc,
+                ch,
+                io.iovs(),
+                io.iov_count(),
+                io.offset(),
+                io.num_blocks(),
+                Some(Self::read_done),
+                io.as_ptr() as *mut c_void,
+            )
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit read {:?}", self.name, io);
+            io.fail();
+        }
+    }
+
+    /// completion of a read of the backing bdev
+    extern "C" fn read_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let pio = Bio::from(parent_io);
+        if success {
+            let cache = Self::from_io(&pio);
+            let generation = Self::ctx(&pio).generation;
+            cache.cache.lock().unwrap().insert(
+                pio.offset(),
+                pio.num_blocks(),
+                Self::iovs(&pio),
+                generation,
+            );
+        }
+        Bio::from(child_io).free();
+        Self::complete(&pio, success);
+    }
+
+    /// drop the blocks of the given IO from the cache as they are about to
+    /// change
+    pub(crate) fn invalidate(&self, io: &Bio) {
+        self.cache
+            .lock()
+            .unwrap()
+            .invalidate(io.offset(), io.num_blocks());
+    }
+
+    /// completion of an IO changing the data of the backing bdev
+    pub(crate) extern "C" fn write_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let pio = Bio::from(parent_io);
+        Self::from_io(&pio).invalidate(&pio);
+        Bio::from(child_io).free();
+        Self::complete(&pio, success);
+    }
+
+    /// completion of any other IO passed on to the backing bdev
+    pub(crate) extern "C" fn io_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        Bio::from(child_io).free();
+        Self::complete(&Bio::from(parent_io), success);
+    }
+}
+
+/// Lookup a cache bdev by its name.
+pub fn cache_lookup(name: &str) -> Option<&mut CacheBdev> {
+    CacheModule::get_instances()
+        .iter_mut()
+        .find(|c| c.name == name)
+        .map(|c| c.as_mut())
+}
+
+/// Unregister the cache bdevs on top of the given bdev which is being
+/// removed.
+pub(crate) fn backing_removed(backing: &str) {
+    for cache in CacheModule::get_instances()
+        .iter()
+        .filter(|c| c.backing == backing)
+    {
+        info!("{}: backing bdev {} removed", cache.name, backing);
+        unsafe {
+            spdk_bdev_unregister(
+                cache.bdev.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
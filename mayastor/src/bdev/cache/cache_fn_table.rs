@@ -0,0 +1,207 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_flush_blocks,
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_bdev_reset,
+    spdk_bdev_unmap_blocks,
+    spdk_bdev_write_zeroes_blocks,
+    spdk_bdev_writev_blocks,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    cache::{
+        cache_bdev::{CacheBdev, CacheChannel},
+        cache_module::CacheModule,
+    },
+    nexus::nexus_io::{Bio, IoType},
+};
+
+static CACHE_FN_TBL: Lazy<CacheFnTable> = Lazy::new(CacheFnTable::new);
+
+pub struct CacheFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for CacheFnTable {}
+unsafe impl Send for CacheFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl CacheFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        CacheFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &CACHE_FN_TBL.f_tbl
+    }
+
+    /// reads and writes are always supported, other IO types are supported
+    /// if the backing bdev supports them
+    extern "C" fn io_supported(
+        ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        let cache = unsafe { CacheBdev::from_raw(ctx) };
+        let io_type = IoType::from(io_type);
+        match io_type {
+            IoType::Read | IoType::Write => true,
+            IoType::Flush
+            | IoType::Reset
+            | IoType::Unmap
+            | IoType::WriteZeros => cache
+                .backing_bdev()
+                .map_or(false, |b| b.io_type_supported(io_type)),
+            _ => false,
+        }
+    }
+
+    /// Submit an IO to the cache, anything that cannot be served from the
+    /// cache is passed on to the backing bdev.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let cache = CacheBdev::from_io(&bio);
+        let handle = CacheChannel::handle(channel);
+        let (desc, ch) = handle.io_tuple();
+        let arg = io as *mut c_void;
+
+        let rc = match bio.io_type() {
+            IoType::Read => {
+                cache.readv(&bio, handle);
+                return;
+            }
+            IoType::Write => {
+                cache.invalidate(&bio);
+                unsafe {
+                    spdk_bdev_writev_blocks(
+                        desc,
+                        ch,
+                        bio.iovs(),
+                        bio.iov_count(),
+                        bio.offset(),
+                        bio.num_blocks(),
+                        Some(CacheBdev::write_done),
+                        arg,
+                    )
+                }
+            }
+            IoType::Unmap => {
+                cache.invalidate(&bio);
+                unsafe {
+                    spdk_bdev_unmap_blocks(
+                        desc,
+                        ch,
+                        bio.offset(),
+                        bio.num_blocks(),
+                        Some(CacheBdev::write_done),
+                        arg,
+                    )
+                }
+            }
+            IoType::WriteZeros => {
+                cache.invalidate(&bio);
+                unsafe {
+                    spdk_bdev_write_zeroes_blocks(
+                        desc,
+                        ch,
+                        bio.offset(),
+                        bio.num_blocks(),
+                        Some(CacheBdev::write_done),
+                        arg,
+                    )
+                }
+            }
+            IoType::Flush => unsafe {
+                spdk_bdev_flush_blocks(
+                    desc,
+                    ch,
+                    bio.offset(),
+                    bio.num_blocks(),
+                    Some(CacheBdev::io_done),
+                    arg,
+                )
+            },
+            IoType::Reset => unsafe {
+                spdk_bdev_reset(desc, ch, Some(CacheBdev::io_done), arg)
+            },
+            io_type => {
+                error!("{}: unsupported IO type {:?}", cache.name, io_type);
+                bio.fail();
+                return;
+            }
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit IO {:?}", cache.name, bio);
+            bio.fail();
+        }
+    }
+
+    /// called per core to create IO channels per cache instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the cache bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let cache = unsafe { CacheBdev::from_raw(ctx) };
+        cache.destruct();
+        let name = cache.name.clone();
+        // removing the cache from the list should cause a drop
+        CacheModule::get_instances().retain(|c| c.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let cache = unsafe { CacheBdev::from_raw(ctx) };
+        let json = serde_json::json!({
+            "backing": cache.backing,
+            "policy": cache.policy().to_string(),
+            "stats": cache.stats(),
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "cache\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
@@ -0,0 +1,312 @@
+//!
+//! The block store of the cache bdev. Cached blocks are kept in slots of a
+//! single DMA buffer which is allocated when the cache is created, the
+//! order in which blocks are evicted is kept separately.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+use spdk_sys::iovec;
+
+use crate::core::{DmaBuf, DmaError};
+
+/// Determines which block is evicted when the cache is full.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum EvictionPolicy {
+    /// evict the block which has not been read for the longest time
+    Lru,
+    /// evict the block which has been cached for the longest time
+    Fifo,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lru" => Ok(Self::Lru),
+            "fifo" => Ok(Self::Fifo),
+            _ => Err(format!("unknown eviction policy {}", s)),
+        }
+    }
+}
+
+impl Display for EvictionPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lru => write!(f, "lru"),
+            Self::Fifo => write!(f, "fifo"),
+        }
+    }
+}
+
+/// Counters of the cache, hits and misses are counted per read IO.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct CacheStats {
+    /// reads served from the cache
+    pub hits: u64,
+    /// reads which went to the backing bdev
+    pub misses: u64,
+    /// blocks evicted to make room for other blocks
+    pub evictions: u64,
+    /// blocks dropped because they have been written to
+    pub invalidations: u64,
+    /// number of blocks currently cached
+    pub cached_blocks: u64,
+    /// maximum number of blocks the cache can hold
+    pub capacity: u64,
+}
+
+#[derive(Debug)]
+struct CachedBlock {
+    /// slot within the buffer holding the data of the block
+    slot: usize,
+    /// key of the block in the eviction order
+    tick: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct BlockCache {
+    /// holds the data of all cached blocks
+    buf: DmaBuf,
+    block_len: usize,
+    policy: EvictionPolicy,
+    /// cached blocks by block number
+    blocks: HashMap<u64, CachedBlock>,
+    /// block numbers in the order they are evicted in
+    order: BTreeMap<u64, u64>,
+    /// slots of the buffer not holding any block
+    free: Vec<usize>,
+    tick: u64,
+    /// incremented by every invalidation, reads which started before an
+    /// invalidation do not populate the cache as their data may be stale
+    generation: u64,
+    stats: CacheStats,
+}
+
+impl BlockCache {
+    /// Create a cache of the given size in bytes for blocks of block_len.
+    pub(crate) fn new(
+        size: u64,
+        block_len: u32,
+        alignment: u64,
+        policy: EvictionPolicy,
+    ) -> Result<Self, DmaError> {
+        let capacity = size / u64::from(block_len);
+        let buf = DmaBuf::new(capacity * u64::from(block_len), alignment)?;
+        let capacity = capacity as usize;
+
+        Ok(Self {
+            buf,
+            block_len: block_len as usize,
+            policy,
+            blocks: HashMap::with_capacity(capacity),
+            order: BTreeMap::new(),
+            free: (0 .. capacity).rev().collect(),
+            tick: 0,
+            generation: 0,
+            stats: CacheStats {
+                capacity: capacity as u64,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            cached_blocks: self.blocks.len() as u64,
+            ..self.stats
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn slot(&self, slot: usize) -> &[u8] {
+        let start = slot * self.block_len;
+        &self.buf.as_slice()[start .. start + self.block_len]
+    }
+
+    fn slot_mut(&mut self, slot: usize) -> &mut [u8] {
+        let start = slot * self.block_len;
+        &mut self.buf.as_mut_slice()[start .. start + self.block_len]
+    }
+
+    /// Copy the given blocks into the iovs if all of them are cached.
+    /// Returns false, without touching the iovs, if any block is missing.
+    pub(crate) fn read(
+        &mut self,
+        offset: u64,
+        num_blocks: u64,
+        iovs: &[iovec],
+    ) -> bool {
+        let end = offset + num_blocks;
+        if !(offset .. end).all(|b| self.blocks.contains_key(&b)) {
+            self.stats.misses += 1;
+            return false;
+        }
+
+        let mut iov = IovCursor::new(iovs);
+        for block in offset .. end {
+            let slot = self.blocks[&block].slot;
+            iov.copy_from(self.slot(slot));
+            if self.policy == EvictionPolicy::Lru {
+                let tick = self.next_tick();
+                let cached = self.blocks.get_mut(&block).unwrap();
+                self.order.remove(&cached.tick);
+                cached.tick = tick;
+                self.order.insert(tick, block);
+            }
+        }
+
+        self.stats.hits += 1;
+        true
+    }
+
+    /// Cache the given blocks read from the backing bdev into the iovs,
+    /// unless they have been invalidated since the read was started.
+    pub(crate) fn insert(
+        &mut self,
+        offset: u64,
+        num_blocks: u64,
+        iovs: &[iovec],
+        generation: u64,
+    ) {
+        if generation != self.generation || self.stats.capacity == 0 {
+            return;
+        }
+
+        let mut iov = IovCursor::new(iovs);
+        for block in offset .. offset + num_blocks {
+            let slot = match self.blocks.get(&block) {
+                Some(cached) => cached.slot,
+                None => self.allocate(block),
+            };
+            iov.copy_to(self.slot_mut(slot));
+        }
+    }
+
+    /// find a slot for a block which is not cached yet, evicting the first
+    /// block in order if the cache is full
+    fn allocate(&mut self, block: u64) -> usize {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                let (tick, victim) =
+                    self.order.iter().next().map(|(t, b)| (*t, *b)).unwrap();
+                self.order.remove(&tick);
+                self.stats.evictions += 1;
+                self.blocks.remove(&victim).unwrap().slot
+            }
+        };
+
+        let tick = self.next_tick();
+        self.order.insert(tick, block);
+        self.blocks.insert(
+            block,
+            CachedBlock {
+                slot,
+                tick,
+            },
+        );
+        slot
+    }
+
+    /// Drop the given blocks from the cache as their data has changed.
+    pub(crate) fn invalidate(&mut self, offset: u64, num_blocks: u64) {
+        self.generation += 1;
+
+        let end = offset.saturating_add(num_blocks);
+        let victims: Vec<u64> = if num_blocks > self.blocks.len() as u64 {
+            self.blocks
+                .keys()
+                .filter(|b| **b >= offset && **b < end)
+                .copied()
+                .collect()
+        } else {
+            (offset .. end)
+                .filter(|b| self.blocks.contains_key(b))
+                .collect()
+        };
+
+        for block in victims {
+            let cached = self.blocks.remove(&block).unwrap();
+            self.order.remove(&cached.tick);
+            self.free.push(cached.slot);
+            self.stats.invalidations += 1;
+        }
+    }
+}
+
+/// Sequential access to the buffers of an IO vector.
+struct IovCursor<'a> {
+    iovs: &'a [iovec],
+    /// index of the current iov
+    index: usize,
+    /// offset within the current iov
+    offset: usize,
+}
+
+impl<'a> IovCursor<'a> {
+    fn new(iovs: &'a [iovec]) -> Self {
+        Self {
+            iovs,
+            index: 0,
+            offset: 0,
+        }
+    }
+
+    /// copy the next bytes of the iovs into dst
+    fn copy_to(&mut self, dst: &mut [u8]) {
+        let mut done = 0;
+        while done < dst.len() {
+            let (src, len) = self.next(dst.len() - done);
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    src,
+                    dst[done ..].as_mut_ptr(),
+                    len,
+                );
+            }
+            done += len;
+        }
+    }
+
+    /// copy src into the next bytes of the iovs
+    fn copy_from(&mut self, src: &[u8]) {
+        let mut done = 0;
+        while done < src.len() {
+            let (dst, len) = self.next(src.len() - done);
+            unsafe {
+                std::ptr::copy_nonoverlapping(src[done ..].as_ptr(), dst, len);
+            }
+            done += len;
+        }
+    }
+
+    /// returns the position within the iovs and the number of bytes, at
+    /// most max, which can be accessed there and moves past those bytes
+    fn next(&mut self, max: usize) -> (*mut u8, usize) {
+        while self.offset == self.iovs[self.index].iov_len as usize {
+            self.index += 1;
+            self.offset = 0;
+        }
+
+        let iov = &self.iovs[self.index];
+        let len = std::cmp::min(max, iov.iov_len as usize - self.offset);
+        let ptr = unsafe { (iov.iov_base as *mut u8).add(self.offset) };
+        self.offset += len;
+        (ptr, len)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{cell::UnsafeCell, ffi::CString};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{
+    bdev::cache::cache_bdev::{CacheBdev, CacheIoCtx},
+    ffihelper::IntoCString,
+};
+
+pub const CACHE_MODULE_NAME: &str = "cache";
+
+pub static CACHE_MODULE: Lazy<CacheModule> = Lazy::new(CacheModule::new);
+
+#[derive(Default, Debug)]
+pub struct CacheInstances {
+    inner: UnsafeCell<Vec<Box<CacheBdev>>>,
+}
+
+#[derive(Debug)]
+pub struct CacheModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for CacheModule {}
+unsafe impl Sync for CacheInstances {}
+
+unsafe impl Send for CacheModule {}
+unsafe impl Send for CacheInstances {}
+
+impl CacheModule {
+    /// construct a new CacheModule instance and setup the main properties,
+    /// cache bdevs are only created explicitly so there is nothing to examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = CACHE_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::cache_mod_init);
+        module.module_fini = Some(Self::cache_mod_fini);
+        module.get_ctx_size = Some(Self::cache_ctx_size);
+        module.examine_config = None;
+        module.examine_disk = None;
+        CacheModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<CacheBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static CACHE_INSTANCES: OnceCell<CacheInstances> = OnceCell::new();
+
+        let global_instances = CACHE_INSTANCES.get_or_init(|| CacheInstances {
+            inner: UnsafeCell::new(Vec::new()),
+        });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    extern "C" fn cache_mod_init() -> i32 {
+        info!("Initializing Cache Module");
+        0
+    }
+
+    extern "C" fn cache_mod_fini() {
+        info!("Unloading Cache Module");
+        let _ = unsafe { CString::from_raw((*(CACHE_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+    }
+
+    extern "C" fn cache_ctx_size() -> i32 {
+        std::mem::size_of::<CacheIoCtx>() as i32
+    }
+}
+
+impl Default for CacheModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((CACHE_MODULE.0) as *const _ as *mut _);
+    }
+}
@@ -0,0 +1,15 @@
+//!
+//! Read cache bdev, see [cache_bdev] for how it works.
+
+pub use cache_bdev::{cache_lookup, CacheBdev};
+pub use cache_lru::{CacheStats, EvictionPolicy};
+
+pub(crate) mod cache_bdev;
+mod cache_fn_table;
+mod cache_lru;
+pub(crate) mod cache_module;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    cache_module::register_module()
+}
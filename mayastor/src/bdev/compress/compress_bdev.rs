@@ -0,0 +1,1082 @@
+//!
+//! The compress bdev is a virtual bdev on top of a thin provisioned backing
+//! bdev which compresses the data written to it, so that compressible data
+//! takes less of the space of the backing bdev. Its blocks are chunks of
+//! CHUNK_LEN bytes, which are compressed one by one with lz4 or zstd. A
+//! chunk which does not compress into fewer blocks of the backing bdev is
+//! stored as it is, so that reading it does not cost a decompression
+//! either, and a chunk of zeroes is not stored at all.
+//!
+//! The backing bdev starts with a header block, followed by a table with an
+//! entry of ENTRY_LEN bytes for every chunk, and then the blocks the chunks
+//! are stored in. An entry holds the length of the stored chunk and the
+//! blocks it is stored in, which need not be contiguous, so the blocks of
+//! overwritten chunks can always be reused. Blocks are allocated lowest
+//! first, which packs the chunks into as few clusters of a thin provisioned
+//! lvol as possible. An entry of zeroes is a chunk which has never been
+//! written, so a new backing bdev needs nothing but its header. The data
+//! area has room for SLACK_CHUNKS more chunks than the compress bdev has,
+//! as a chunk which is overwritten is stored in new blocks before its old
+//! ones are freed, even when none of the chunks compresses.
+//!
+//! The table is kept in memory as well. A write stores the chunk in newly
+//! allocated blocks and flushes them before it writes the block of the table
+//! with its entry, which is flushed as well before the blocks the chunk was
+//! stored in before are freed, so a crash leaves either the old or the new
+//! chunk behind. Freed blocks are not reused while a read which started
+//! before they were freed is in flight, as it may still be reading them.
+
+use std::{
+    cmp::min,
+    convert::{TryFrom, TryInto},
+    ffi::c_void,
+    fmt::{Debug, Display, Formatter},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use futures::{channel::oneshot, lock::Mutex as AsyncMutex};
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+};
+
+use crate::{
+    bdev::{
+        compress::{
+            compress_fn_table::CompressFnTable,
+            compress_module::{CompressModule, COMPRESS_MODULE},
+        },
+        nexus::nexus_io::{Bio, IoStatus},
+        util::{
+            block::{decode_block, encode_block, BlockAllocator},
+            iov::{gather, scatter},
+        },
+    },
+    core::{Bdev, BdevHandle, CoreError, DmaBuf, Descriptor, Reactors},
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const COMPRESS_PRODUCT_ID: &str = "Compress Bdev";
+
+/// length of a chunk, the unit of compression and the block length of the
+/// compress bdev
+pub const CHUNK_LEN: u64 = 4096;
+
+/// length of the entry of a chunk in the table
+const ENTRY_LEN: u64 = 64;
+
+/// the most blocks of the backing bdev a chunk can be stored in, as many as
+/// an entry has room for after the length and the number of blocks
+const MAX_CHUNK_BLOCKS: u64 = (ENTRY_LEN - 8) / 4;
+
+/// the number of chunks of the data area beyond the chunks of the compress
+/// bdev
+const SLACK_CHUNKS: u64 = 1;
+
+/// the header is in the first block, the table follows it
+const TABLE_START: u64 = 1;
+
+/// the table is read in pieces of at most this many bytes
+const TABLE_READ_LEN: u64 = 1 << 20;
+
+/// magic of the header, "MCMP"
+const COMPRESS_MAGIC: u32 = 0x4d43_4d50;
+
+/// level of zstd, fast enough to compress inline
+const ZSTD_LEVEL: i32 = 1;
+
+/// the algorithm the chunks are compressed with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompressionAlgorithm {
+    Lz4,
+    Zstd,
+}
+
+impl Default for CompressionAlgorithm {
+    fn default() -> Self {
+        Self::Lz4
+    }
+}
+
+impl CompressionAlgorithm {
+    /// compress a chunk, an empty result means it could not be
+    fn compress(self, chunk: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Lz4 => lz4_flex::compress(chunk),
+            Self::Zstd => {
+                zstd::block::compress(chunk, ZSTD_LEVEL).unwrap_or_default()
+            }
+        }
+    }
+
+    /// decompress a chunk, None unless it decompresses into a whole chunk
+    fn decompress(self, data: &[u8]) -> Option<Vec<u8>> {
+        let chunk = match self {
+            Self::Lz4 => lz4_flex::decompress(data, CHUNK_LEN as usize).ok(),
+            Self::Zstd => {
+                zstd::block::decompress(data, CHUNK_LEN as usize).ok()
+            }
+        }?;
+        Some(chunk).filter(|c| c.len() as u64 == CHUNK_LEN)
+    }
+}
+
+impl FromStr for CompressionAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lz4" => Ok(Self::Lz4),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(format!("unknown compression algorithm {}", s)),
+        }
+    }
+}
+
+impl Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lz4 => write!(f, "lz4"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// how well the data written to a compress bdev compresses
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct CompressStats {
+    /// number of chunks stored
+    pub chunks: u64,
+    /// number of chunks stored uncompressed as they did not compress
+    pub incompressible: u64,
+    /// bytes of data in the chunks stored
+    pub logical_bytes: u64,
+    /// bytes of the backing bdev the chunks are stored in
+    pub stored_bytes: u64,
+}
+
+impl CompressStats {
+    /// the ratio of the data in the chunks to the space they take, 1 when
+    /// nothing is stored
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.logical_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// the header of the backing bdev, in its first block
+#[derive(Debug, Serialize, Deserialize)]
+struct CompressHeader {
+    magic: u32,
+    algorithm: CompressionAlgorithm,
+    chunk_len: u32,
+    chunks: u64,
+}
+
+/// the entry of a chunk in the table
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Entry {
+    /// length of the stored chunk, 0 if it has never been written and
+    /// CHUNK_LEN if it is stored uncompressed
+    len: u32,
+    /// the blocks of the data area the chunk is stored in
+    blocks: Vec<u32>,
+}
+
+impl Entry {
+    fn decode(slot: &[u8]) -> Self {
+        let word = |i: usize| {
+            u32::from_le_bytes(slot[i * 4 .. i * 4 + 4].try_into().unwrap())
+        };
+        let count = min(u64::from(word(1)), MAX_CHUNK_BLOCKS) as usize;
+        Entry {
+            len: word(0),
+            blocks: (0 .. count).map(|i| word(2 + i)).collect(),
+        }
+    }
+
+    fn encode(&self, slot: &mut [u8]) {
+        slot.iter_mut().for_each(|b| *b = 0);
+        slot[0 .. 4].copy_from_slice(&self.len.to_le_bytes());
+        slot[4 .. 8]
+            .copy_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for (i, block) in self.blocks.iter().enumerate() {
+            slot[8 + i * 4 .. 12 + i * 4].copy_from_slice(&block.to_le_bytes());
+        }
+    }
+
+    /// the chunk is stored uncompressed
+    fn is_raw(&self) -> bool {
+        u64::from(self.len) == CHUNK_LEN
+    }
+
+    /// the length and the blocks of the entry agree with each other
+    fn is_valid(&self, block_len: u64) -> bool {
+        let len = u64::from(self.len);
+        len <= CHUNK_LEN
+            && self.blocks.len() as u64 == (len + block_len - 1) / block_len
+    }
+}
+
+/// the table of the chunks and the blocks of the data area in use
+struct ChunkMap {
+    /// the table as it is stored on the backing bdev
+    table: Vec<u8>,
+    /// the blocks of the data area in use
+    allocator: BlockAllocator,
+    block_len: u64,
+    stats: CompressStats,
+}
+
+impl ChunkMap {
+    /// Build the map from the table read from the backing bdev, None if an
+    /// entry is not valid or shares a block with another one.
+    fn load(
+        table: Vec<u8>,
+        chunks: u64,
+        data_blocks: u64,
+        block_len: u64,
+    ) -> Option<Self> {
+        let mut map = ChunkMap {
+            table,
+            allocator: BlockAllocator::new(data_blocks),
+            block_len,
+            stats: CompressStats::default(),
+        };
+        for chunk in 0 .. chunks {
+            let entry = map.entry(chunk);
+            if !entry.is_valid(block_len)
+                || entry.blocks.iter().any(|b| {
+                    u64::from(*b) >= data_blocks || map.allocator.is_used(*b)
+                })
+            {
+                error!("invalid entry of chunk {}: {:?}", chunk, entry);
+                return None;
+            }
+            entry
+                .blocks
+                .iter()
+                .for_each(|b| map.allocator.set_used(*b, true));
+            map.account(&entry, true);
+        }
+        Some(map)
+    }
+
+    fn entry(&self, chunk: u64) -> Entry {
+        let start = (chunk * ENTRY_LEN) as usize;
+        Entry::decode(&self.table[start .. start + ENTRY_LEN as usize])
+    }
+
+    /// replace the entry of the chunk, returns the entry it had
+    fn set_entry(&mut self, chunk: u64, entry: &Entry) -> Entry {
+        let old = self.entry(chunk);
+        self.account(&old, false);
+        let start = (chunk * ENTRY_LEN) as usize;
+        entry.encode(&mut self.table[start .. start + ENTRY_LEN as usize]);
+        self.account(entry, true);
+        old
+    }
+
+    /// the offset in the table of the block with the entry of the chunk,
+    /// and its contents
+    fn table_block(&self, chunk: u64) -> (u64, &[u8]) {
+        let offset = chunk * ENTRY_LEN / self.block_len * self.block_len;
+        let start = offset as usize;
+        (offset, &self.table[start .. start + self.block_len as usize])
+    }
+
+    fn account(&mut self, entry: &Entry, add: bool) {
+        if entry.len == 0 {
+            return;
+        }
+        let stored = entry.blocks.len() as u64 * self.block_len;
+        let raw = entry.is_raw() as u64;
+        let stats = &mut self.stats;
+        if add {
+            stats.chunks += 1;
+            stats.incompressible += raw;
+            stats.logical_bytes += CHUNK_LEN;
+            stats.stored_bytes += stored;
+        } else {
+            stats.chunks -= 1;
+            stats.incompressible -= raw;
+            stats.logical_bytes -= CHUNK_LEN;
+            stats.stored_bytes -= stored;
+        }
+    }
+}
+
+/// why an IO submitted to the compress bdev failed
+#[derive(Debug)]
+enum CompressError {
+    Io(CoreError),
+    NoMemory,
+    NoSpace,
+    Corrupt { chunk: u64 },
+}
+
+impl Display for CompressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::NoMemory => write!(f, "failed to allocate a buffer"),
+            Self::NoSpace => write!(f, "no free blocks"),
+            Self::Corrupt {
+                chunk,
+            } => write!(f, "chunk {} does not decompress", chunk),
+        }
+    }
+}
+
+impl From<CoreError> for CompressError {
+    fn from(error: CoreError) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// the runs of consecutive blocks, as their first block and length
+fn runs(blocks: &[u32]) -> Vec<(u32, u64)> {
+    let mut runs: Vec<(u32, u64)> = Vec::new();
+    for &block in blocks {
+        match runs.last_mut() {
+            Some((first, count))
+                if u64::from(*first) + *count == u64::from(block) =>
+            {
+                *count += 1
+            }
+            _ => runs.push((block, 1)),
+        }
+    }
+    runs
+}
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct CompressChannel {
+    handle: *mut BdevHandle,
+}
+
+impl CompressChannel {
+    /// allocates a handle to the backing bdev for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let compress = unsafe { CompressBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut CompressChannel) };
+
+        match compress
+            .desc
+            .as_ref()
+            .map(|d| BdevHandle::try_from(d.clone()))
+        {
+            Some(Ok(handle)) => {
+                ch.handle = Box::into_raw(Box::new(handle));
+                0
+            }
+            _ => {
+                error!("{}: failed to create IO channel", compress.name);
+                ch.handle = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut CompressChannel) };
+        if !ch.handle.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handle) };
+            ch.handle = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handle to the backing bdev of the given channel
+    pub(crate) fn handle<'a>(channel: *mut spdk_io_channel) -> &'a BdevHandle {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut CompressChannel;
+            &*(*ctx).handle
+        }
+    }
+}
+
+pub struct CompressBdev {
+    /// name of the compress bdev
+    pub name: String,
+    /// name of the bdev the chunks are stored on
+    pub backing: String,
+    /// the compress bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    /// descriptor of the backing bdev
+    desc: Option<Arc<Descriptor>>,
+    algorithm: CompressionAlgorithm,
+    /// block length of the backing bdev
+    block_len: u64,
+    /// the first block of the data area
+    data_start: u64,
+    map: Mutex<ChunkMap>,
+    /// held while the table is updated, so that the blocks of the table
+    /// are written in the order their entries change
+    table_lock: AsyncMutex<()>,
+}
+
+impl Debug for CompressBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (backing: {}, algorithm: {})",
+            self.name, self.backing, self.algorithm
+        )
+    }
+}
+
+impl Drop for CompressBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl CompressBdev {
+    /// the number of blocks of the table for the given number of chunks
+    fn table_blocks(chunks: u64, block_len: u64) -> u64 {
+        (chunks * ENTRY_LEN + block_len - 1) / block_len
+    }
+
+    /// the number of blocks of a backing bdev holding the given number of
+    /// chunks, none of which compresses, and the slack
+    fn blocks_needed(chunks: u64, block_len: u64) -> u64 {
+        TABLE_START
+            + Self::table_blocks(chunks, block_len)
+            + (chunks + SLACK_CHUNKS) * (CHUNK_LEN / block_len)
+    }
+
+    /// the number of chunks a backing bdev of the given number of blocks
+    /// holds
+    fn chunks(backing_blocks: u64, block_len: u64) -> u64 {
+        let mut chunks = backing_blocks.saturating_sub(TABLE_START)
+            * block_len
+            / (ENTRY_LEN + CHUNK_LEN);
+        // the blocks of the data area are numbered with 32 bits
+        chunks = min(
+            chunks,
+            u64::from(u32::MAX) / (CHUNK_LEN / block_len) - SLACK_CHUNKS,
+        );
+        while chunks > 0
+            && Self::blocks_needed(chunks, block_len) > backing_blocks
+        {
+            chunks -= 1;
+        }
+        chunks
+    }
+
+    /// The size in bytes of a backing bdev with the given block length for
+    /// a compress bdev of at least the given size.
+    pub fn backing_size(size: u64, block_len: u32) -> u64 {
+        let block_len = u64::from(block_len);
+        let chunks = (size + CHUNK_LEN - 1) / CHUNK_LEN;
+        Self::blocks_needed(chunks, block_len) * block_len
+    }
+
+    /// Create a compress bdev on top of the backing bdev and register it
+    /// with SPDK. A backing bdev of zeroes is set up for the given
+    /// algorithm, otherwise the chunks are compressed with the algorithm
+    /// they have been compressed with all along.
+    pub(crate) async fn create(
+        name: &str,
+        backing: &str,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+
+        let create_error = |source: Errno, msg: String| {
+            error!("{}: {}", name, msg);
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        };
+
+        let block_len = u64::from(base.block_len());
+        if CHUNK_LEN % block_len != 0
+            || !(2 ..= MAX_CHUNK_BLOCKS).contains(&(CHUNK_LEN / block_len))
+        {
+            return Err(create_error(
+                Errno::EINVAL,
+                format!(
+                    "blocks of {} bytes can not hold chunks of {} bytes",
+                    block_len, CHUNK_LEN
+                ),
+            ));
+        }
+
+        let desc = Arc::new(base.open(true).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?);
+        let io_error = |e: CoreError| create_error(Errno::EIO, e.to_string());
+        let nomem_error = |_| {
+            create_error(Errno::ENOMEM, "failed to allocate a buffer".into())
+        };
+
+        let handle = BdevHandle::try_from(Arc::clone(&desc)).map_err(io_error)?;
+        let mut buf = handle.dma_malloc(block_len).map_err(nomem_error)?;
+        handle.read_at(0, &mut buf).await.map_err(io_error)?;
+
+        let header = if buf.as_slice().iter().all(|b| *b == 0) {
+            let header = CompressHeader {
+                magic: COMPRESS_MAGIC,
+                algorithm,
+                chunk_len: CHUNK_LEN as u32,
+                chunks: Self::chunks(base.num_blocks(), block_len),
+            };
+            if header.chunks == 0 {
+                return Err(create_error(
+                    Errno::EINVAL,
+                    format!("{} is too small", backing),
+                ));
+            }
+            encode_block(&header, buf.as_mut_slice());
+            handle.write_at(0, &buf).await.map_err(io_error)?;
+            info!("{}: set up {} for {:?}", name, backing, header);
+            header
+        } else {
+            match decode_block::<CompressHeader>(buf.as_slice())
+                .filter(|h| h.magic == COMPRESS_MAGIC)
+            {
+                Some(header)
+                    if u64::from(header.chunk_len) == CHUNK_LEN
+                        && Self::blocks_needed(header.chunks, block_len)
+                            <= base.num_blocks() =>
+                {
+                    header
+                }
+                _ => {
+                    return Err(create_error(
+                        Errno::EINVAL,
+                        format!("{} does not hold compressed chunks", backing),
+                    ))
+                }
+            }
+        };
+
+        if header.algorithm != algorithm {
+            warn!(
+                "{}: the chunks of {} are compressed with {}, not {}",
+                name, backing, header.algorithm, algorithm
+            );
+        }
+
+        let table_blocks = Self::table_blocks(header.chunks, block_len);
+        let mut table = Vec::with_capacity((table_blocks * block_len) as usize);
+        let mut block = TABLE_START;
+        while block < TABLE_START + table_blocks {
+            let count = min(
+                TABLE_START + table_blocks - block,
+                TABLE_READ_LEN / block_len,
+            );
+            let mut buf =
+                handle.dma_malloc(count * block_len).map_err(nomem_error)?;
+            handle
+                .read_at(block * block_len, &mut buf)
+                .await
+                .map_err(io_error)?;
+            table.extend_from_slice(buf.as_slice());
+            block += count;
+        }
+        drop(handle);
+
+        let data_start = TABLE_START + table_blocks;
+        let data_blocks =
+            min(base.num_blocks() - data_start, u64::from(u32::MAX));
+        let map =
+            ChunkMap::load(table, header.chunks, data_blocks, block_len)
+                .ok_or_else(|| {
+                    create_error(
+                        Errno::EINVAL,
+                        format!("the table of {} is corrupt", backing),
+                    )
+                })?;
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = COMPRESS_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = CompressFnTable::table();
+        b.module = COMPRESS_MODULE.as_ptr();
+        b.blocklen = CHUNK_LEN as u32;
+        b.blockcnt = header.chunks;
+        b.required_alignment = unsafe { (*base.as_ptr()).required_alignment };
+
+        let mut c = Box::new(CompressBdev {
+            name: name.to_string(),
+            backing: backing.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            desc: Some(desc),
+            algorithm: header.algorithm,
+            block_len,
+            data_start,
+            map: Mutex::new(map),
+            table_lock: AsyncMutex::new(()),
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*c.bdev.as_ptr()).ctxt = c.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                c.as_ptr(),
+                Some(CompressChannel::create),
+                Some(CompressChannel::destroy),
+                std::mem::size_of::<CompressChannel>() as u32,
+                (*c.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(c.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(c.as_ptr(), None);
+            }
+            c.desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        info!("{}: created {:?}", name, c);
+        CompressModule::get_instances().push(c);
+        Ok(name.to_string())
+    }
+
+    /// Unregister the compress bdev, which closes the backing bdev.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match compress_lookup(name) {
+            Some(compress) => compress.bdev.clone(),
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the compress bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the backing bdev
+        self.desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// the backing bdev of the compress bdev
+    pub(crate) fn backing_bdev(&self) -> Option<Bdev> {
+        self.desc.as_ref().map(|d| d.get_bdev())
+    }
+
+    /// the algorithm the chunks are compressed with
+    pub fn algorithm(&self) -> CompressionAlgorithm {
+        self.algorithm
+    }
+
+    /// how well the chunks stored so far have compressed
+    pub fn stats(&self) -> CompressStats {
+        self.map.lock().unwrap().stats
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut CompressBdev)
+    }
+
+    /// obtain the CompressBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), COMPRESS_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    /// complete an IO submitted to the compress bdev
+    fn complete(&self, io: &Bio, result: Result<(), CompressError>) {
+        let status = match result {
+            Ok(()) => IoStatus::Success,
+            Err(error) => {
+                error!("{}: IO {:?} failed: {}", self.name, io, error);
+                IoStatus::Failed
+            }
+        };
+        unsafe { spdk_bdev_io_complete(io.as_ptr(), status.into()) }
+    }
+
+    /// completion of an IO passed on to the backing bdev
+    pub(crate) extern "C" fn io_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let pio = Bio::from(parent_io);
+        Bio::from(child_io).free();
+        let status = if success {
+            IoStatus::Success
+        } else {
+            IoStatus::Failed
+        };
+        unsafe { spdk_bdev_io_complete(pio.as_ptr(), status.into()) }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let compress = Self::from_io(&bio);
+        if !success {
+            warn!(
+                "{}: Failed to get io buffer for io {:?}",
+                compress.name, bio
+            );
+            bio.fail();
+            return;
+        }
+        compress.readv(&bio, CompressChannel::handle(ch));
+    }
+
+    /// read the chunks of the IO
+    pub(crate) fn readv(&'static self, io: &Bio, handle: &'static BdevHandle) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * CHUNK_LEN,
+                )
+            }
+            return;
+        }
+
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.read(&io, handle).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// compress and write the chunks of the IO
+    pub(crate) fn writev(&'static self, io: &Bio, handle: &'static BdevHandle) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.write(&io, handle).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// drop the chunks of the IO, which read as zeroes from then on
+    pub(crate) fn discard(
+        &'static self,
+        io: &Bio,
+        handle: &'static BdevHandle,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let mut result = Ok(());
+            for chunk in io.offset() .. io.offset() + io.num_blocks() {
+                result = self.update(handle, chunk, Entry::default()).await;
+                if result.is_err() {
+                    break;
+                }
+            }
+            self.complete(&io, result);
+        });
+    }
+
+    async fn read(
+        &self,
+        io: &Bio,
+        handle: &BdevHandle,
+    ) -> Result<(), CompressError> {
+        let (epoch, entries) = {
+            let mut map = self.map.lock().unwrap();
+            let epoch = map.allocator.read_start();
+            let entries = (io.offset() .. io.offset() + io.num_blocks())
+                .map(|chunk| map.entry(chunk))
+                .collect::<Vec<_>>();
+            (epoch, entries)
+        };
+
+        let mut data =
+            Vec::with_capacity((io.num_blocks() * CHUNK_LEN) as usize);
+        let mut result = Ok(());
+        for (chunk, entry) in (io.offset() ..).zip(&entries) {
+            match self.read_chunk(handle, chunk, entry).await {
+                Ok(chunk) => data.extend_from_slice(&chunk),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        self.map.lock().unwrap().allocator.read_done(epoch);
+
+        result.map(|_| scatter(io, &data))
+    }
+
+    async fn read_chunk(
+        &self,
+        handle: &BdevHandle,
+        chunk: u64,
+        entry: &Entry,
+    ) -> Result<Vec<u8>, CompressError> {
+        if entry.len == 0 {
+            return Ok(vec![0; CHUNK_LEN as usize]);
+        }
+        let mut stored = self.read_blocks(handle, &entry.blocks).await?;
+        if entry.is_raw() {
+            stored.truncate(CHUNK_LEN as usize);
+            return Ok(stored);
+        }
+        self.algorithm
+            .decompress(&stored[.. entry.len as usize])
+            .ok_or(CompressError::Corrupt {
+                chunk,
+            })
+    }
+
+    async fn write(
+        &self,
+        io: &Bio,
+        handle: &BdevHandle,
+    ) -> Result<(), CompressError> {
+        let data = gather(io, (io.num_blocks() * CHUNK_LEN) as usize);
+        let chunks = data.chunks(CHUNK_LEN as usize);
+        for (chunk, data) in (io.offset() ..).zip(chunks) {
+            self.write_chunk(handle, chunk, data).await?;
+        }
+        Ok(())
+    }
+
+    /// the chunk as it is stored, compressed unless that saves no blocks
+    fn compress(&self, chunk: &[u8]) -> Vec<u8> {
+        let compressed = self.algorithm.compress(chunk);
+        let blocks =
+            |len: usize| (len as u64 + self.block_len - 1) / self.block_len;
+        if !compressed.is_empty()
+            && blocks(compressed.len()) < blocks(chunk.len())
+        {
+            compressed
+        } else {
+            chunk.to_vec()
+        }
+    }
+
+    async fn write_chunk(
+        &self,
+        handle: &BdevHandle,
+        chunk: u64,
+        data: &[u8],
+    ) -> Result<(), CompressError> {
+        if data.iter().all(|b| *b == 0) {
+            return self.update(handle, chunk, Entry::default()).await;
+        }
+
+        let stored = self.compress(data);
+        let count = (stored.len() as u64 + self.block_len - 1) / self.block_len;
+        let blocks = self
+            .map
+            .lock()
+            .unwrap()
+            .allocator
+            .alloc(count as usize)
+            .ok_or(CompressError::NoSpace)?;
+        if let Err(error) = self.write_blocks(handle, &blocks, &stored).await {
+            self.map.lock().unwrap().allocator.free(&blocks);
+            return Err(error);
+        }
+
+        let entry = Entry {
+            len: stored.len() as u32,
+            blocks,
+        };
+        self.update(handle, chunk, entry).await
+    }
+
+    /// Point the entry of the chunk at where it is stored now, and write it
+    /// to the table. The blocks it was stored in before are freed once the
+    /// entry has been flushed, the new ones if it could not be written. An
+    /// entry written but not flushed leaks the old blocks until the table is
+    /// loaded again, as it may or may not point at them after a crash.
+    async fn update(
+        &self,
+        handle: &BdevHandle,
+        chunk: u64,
+        entry: Entry,
+    ) -> Result<(), CompressError> {
+        if !entry.blocks.is_empty() {
+            if let Err(error) = handle.flush().await {
+                self.map.lock().unwrap().allocator.free(&entry.blocks);
+                return Err(error.into());
+            }
+        }
+        let _table = self.table_lock.lock().await;
+        if self.map.lock().unwrap().entry(chunk) == entry {
+            return Ok(());
+        }
+        let mut buf = self.dma_malloc(handle, 1)?;
+        let (old, offset) = {
+            let mut map = self.map.lock().unwrap();
+            let old = map.set_entry(chunk, &entry);
+            let (offset, block) = map.table_block(chunk);
+            buf.as_mut_slice().copy_from_slice(block);
+            (old, offset)
+        };
+
+        let offset = TABLE_START * self.block_len + offset;
+        if let Err(error) = handle.write_at(offset, &buf).await {
+            let mut map = self.map.lock().unwrap();
+            map.set_entry(chunk, &old);
+            map.allocator.free(&entry.blocks);
+            return Err(error.into());
+        }
+        handle.flush().await?;
+        self.map.lock().unwrap().allocator.free(&old.blocks);
+        Ok(())
+    }
+
+    fn dma_malloc(
+        &self,
+        handle: &BdevHandle,
+        blocks: u64,
+    ) -> Result<DmaBuf, CompressError> {
+        handle
+            .dma_malloc(blocks * self.block_len)
+            .map_err(|_| CompressError::NoMemory)
+    }
+
+    /// the offset in bytes of a block of the data area
+    fn data_offset(&self, block: u32) -> u64 {
+        (self.data_start + u64::from(block)) * self.block_len
+    }
+
+    async fn read_blocks(
+        &self,
+        handle: &BdevHandle,
+        blocks: &[u32],
+    ) -> Result<Vec<u8>, CompressError> {
+        let mut data =
+            Vec::with_capacity(blocks.len() * self.block_len as usize);
+        for (first, count) in runs(blocks) {
+            let mut buf = self.dma_malloc(handle, count)?;
+            handle.read_at(self.data_offset(first), &mut buf).await?;
+            data.extend_from_slice(buf.as_slice());
+        }
+        Ok(data)
+    }
+
+    async fn write_blocks(
+        &self,
+        handle: &BdevHandle,
+        blocks: &[u32],
+        mut data: &[u8],
+    ) -> Result<(), CompressError> {
+        for (first, count) in runs(blocks) {
+            let mut buf = self.dma_malloc(handle, count)?;
+            buf.fill(0);
+            let len = min(data.len(), buf.len() as usize);
+            buf.as_mut_slice()[.. len].copy_from_slice(&data[.. len]);
+            data = &data[len ..];
+            handle.write_at(self.data_offset(first), &buf).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Lookup a compress bdev by its name.
+pub fn compress_lookup(name: &str) -> Option<&mut CompressBdev> {
+    CompressModule::get_instances()
+        .iter_mut()
+        .find(|c| c.name == name)
+        .map(|c| c.as_mut())
+}
+
+/// Unregister the compress bdevs on top of the given bdev which is being
+/// removed.
+pub(crate) fn backing_removed(backing: &str) {
+    for compress in CompressModule::get_instances()
+        .iter()
+        .filter(|c| c.backing == backing)
+    {
+        info!("{}: backing bdev {} removed", compress.name, backing);
+        unsafe {
+            spdk_bdev_unregister(
+                compress.bdev.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
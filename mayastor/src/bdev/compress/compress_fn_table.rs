@@ -0,0 +1,175 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_flush_blocks,
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_bdev_reset,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    compress::{
+        compress_bdev::{CompressBdev, CompressChannel},
+        compress_module::CompressModule,
+    },
+    nexus::nexus_io::{Bio, IoType},
+};
+
+static COMPRESS_FN_TBL: Lazy<CompressFnTable> =
+    Lazy::new(CompressFnTable::new);
+
+pub struct CompressFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for CompressFnTable {}
+unsafe impl Send for CompressFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl CompressFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        CompressFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &COMPRESS_FN_TBL.f_tbl
+    }
+
+    /// reads, writes, unmaps and write zeroes are always supported, as the
+    /// latter two only drop chunks from the table. Flushes and resets are
+    /// supported if the backing bdev supports them.
+    extern "C" fn io_supported(
+        ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        let compress = unsafe { CompressBdev::from_raw(ctx) };
+        let io_type = IoType::from(io_type);
+        match io_type {
+            IoType::Read
+            | IoType::Write
+            | IoType::Unmap
+            | IoType::WriteZeros => true,
+            IoType::Flush | IoType::Reset => compress
+                .backing_bdev()
+                .map_or(false, |b| b.io_type_supported(io_type)),
+            _ => false,
+        }
+    }
+
+    /// Submit an IO to the compress bdev, the chunks it covers are looked
+    /// up in the table and read or written one by one.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let compress = CompressBdev::from_io(&bio);
+        let handle = CompressChannel::handle(channel);
+        let (desc, ch) = handle.io_tuple();
+        let arg = io as *mut c_void;
+
+        let rc = match bio.io_type() {
+            IoType::Read => {
+                compress.readv(&bio, handle);
+                return;
+            }
+            IoType::Write => {
+                compress.writev(&bio, handle);
+                return;
+            }
+            IoType::Unmap | IoType::WriteZeros => {
+                compress.discard(&bio, handle);
+                return;
+            }
+            IoType::Flush => unsafe {
+                spdk_bdev_flush_blocks(
+                    desc,
+                    ch,
+                    0,
+                    compress.backing_bdev().map_or(0, |b| b.num_blocks()),
+                    Some(CompressBdev::io_done),
+                    arg,
+                )
+            },
+            IoType::Reset => unsafe {
+                spdk_bdev_reset(desc, ch, Some(CompressBdev::io_done), arg)
+            },
+            io_type => {
+                error!("{}: unsupported IO type {:?}", compress.name, io_type);
+                bio.fail();
+                return;
+            }
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit IO {:?}", compress.name, bio);
+            bio.fail();
+        }
+    }
+
+    /// called per core to create IO channels per compress instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the compress bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let compress = unsafe { CompressBdev::from_raw(ctx) };
+        compress.destruct();
+        let name = compress.name.clone();
+        // removing the compress bdev from the list should cause a drop
+        CompressModule::get_instances().retain(|c| c.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let compress = unsafe { CompressBdev::from_raw(ctx) };
+        let stats = compress.stats();
+        let json = serde_json::json!({
+            "backing": compress.backing,
+            "algorithm": compress.algorithm().to_string(),
+            "stats": stats,
+            "ratio": stats.ratio(),
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "compress\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
@@ -0,0 +1,99 @@
+use std::{cell::UnsafeCell, ffi::CString};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{
+    bdev::compress::compress_bdev::CompressBdev,
+    ffihelper::IntoCString,
+};
+
+pub const COMPRESS_MODULE_NAME: &str = "compress";
+
+pub static COMPRESS_MODULE: Lazy<CompressModule> =
+    Lazy::new(CompressModule::new);
+
+#[derive(Default, Debug)]
+pub struct CompressInstances {
+    inner: UnsafeCell<Vec<Box<CompressBdev>>>,
+}
+
+#[derive(Debug)]
+pub struct CompressModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for CompressModule {}
+unsafe impl Sync for CompressInstances {}
+
+unsafe impl Send for CompressModule {}
+unsafe impl Send for CompressInstances {}
+
+impl CompressModule {
+    /// construct a new CompressModule instance and setup the main properties,
+    /// compress bdevs are only created explicitly so there is nothing to
+    /// examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = COMPRESS_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::compress_mod_init);
+        module.module_fini = Some(Self::compress_mod_fini);
+        module.get_ctx_size = Some(Self::compress_ctx_size);
+        module.examine_config = None;
+        module.examine_disk = None;
+        CompressModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<CompressBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static COMPRESS_INSTANCES: OnceCell<CompressInstances> =
+            OnceCell::new();
+
+        let global_instances =
+            COMPRESS_INSTANCES.get_or_init(|| CompressInstances {
+                inner: UnsafeCell::new(Vec::new()),
+            });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    extern "C" fn compress_mod_init() -> i32 {
+        info!("Initializing Compress Module");
+        0
+    }
+
+    extern "C" fn compress_mod_fini() {
+        info!("Unloading Compress Module");
+        let _ = unsafe { CString::from_raw((*(COMPRESS_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+    }
+
+    /// the IOs are carried out by futures, which keep their own state
+    extern "C" fn compress_ctx_size() -> i32 {
+        0
+    }
+}
+
+impl Default for CompressModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((COMPRESS_MODULE.0) as *const _ as *mut _);
+    }
+}
@@ -0,0 +1,18 @@
+//!
+//! Inline compression bdev, see [compress_bdev] for how it works.
+
+pub use compress_bdev::{
+    compress_lookup,
+    CompressBdev,
+    CompressStats,
+    CompressionAlgorithm,
+};
+
+pub(crate) mod compress_bdev;
+mod compress_fn_table;
+pub(crate) mod compress_module;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    compress_module::register_module()
+}
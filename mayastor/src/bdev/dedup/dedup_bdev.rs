@@ -0,0 +1,817 @@
+//!
+//! The dedup bdev is a virtual bdev on top of an lvol of a pool with a dedup
+//! store, see [dedup_store](super::dedup_store). The data written to it is
+//! kept in the store of the pool, where a block with the same data as one
+//! stored before, by the same or any other dedup bdev of the pool, is shared
+//! with it rather than stored again. A block of zeroes is not stored at all.
+//!
+//! The lvol only holds the map of the blocks of the dedup bdev to the blocks
+//! of the store: a header block, followed by an entry of MAP_ENTRY_LEN bytes
+//! for every block, which is the block of the store plus one, or zero if the
+//! block has never been written. An lvol of zeroes is a new map.
+//!
+//! The map is kept in memory as well. A write takes a reference to the block
+//! of the store with its data before it writes the block of the map with
+//! its entry, and only then drops the reference to the block it was mapped
+//! to before, so a crash leaves either the old or the new data behind.
+
+use std::{
+    cmp::min,
+    convert::{TryFrom, TryInto},
+    ffi::c_void,
+    fmt::{Debug, Formatter},
+    sync::{Arc, Mutex},
+};
+
+use futures::{channel::oneshot, lock::Mutex as AsyncMutex};
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+};
+
+use crate::{
+    bdev::{
+        dedup::{
+            dedup_fn_table::DedupFnTable,
+            dedup_module::{DedupModule, DEDUP_MODULE},
+            dedup_store::{DedupError, DedupStore, DEDUP_BLOCK_LEN},
+        },
+        nexus::nexus_io::{Bio, IoStatus},
+        util::{
+            block::{decode_block, encode_block},
+            iov::{gather, scatter},
+        },
+    },
+    core::{Bdev, BdevHandle, CoreError, DmaBuf, Descriptor, Reactors},
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const DEDUP_PRODUCT_ID: &str = "Dedup Bdev";
+
+/// length of the entry of a block in the map
+const MAP_ENTRY_LEN: u64 = 8;
+
+/// the header is in the first block, the map follows it
+const MAP_START: u64 = 1;
+
+/// the map is read in pieces of at most this many bytes
+const MAP_READ_LEN: u64 = 1 << 20;
+
+/// magic of the header, "MDDM"
+const MAP_MAGIC: u32 = 0x4d44_444d;
+
+/// the header of the map, in the first block of the lvol
+#[derive(Debug, Serialize, Deserialize)]
+struct MapHeader {
+    magic: u32,
+    block_len: u32,
+    blocks: u64,
+}
+
+/// the map of the blocks of a dedup bdev to the blocks of the store
+struct BlockMap {
+    /// the map as it is stored on the lvol
+    table: Vec<u8>,
+    /// the number of blocks mapped
+    mapped: u64,
+    /// block length of the lvol
+    block_len: u64,
+}
+
+impl BlockMap {
+    fn new(table: Vec<u8>, blocks: u64, block_len: u64) -> Self {
+        let mut map = BlockMap {
+            table,
+            mapped: 0,
+            block_len,
+        };
+        map.mapped = (0 .. blocks).filter(|b| map.get(*b).is_some()).count()
+            as u64;
+        map
+    }
+
+    fn raw(&self, block: u64) -> u64 {
+        let start = (block * MAP_ENTRY_LEN) as usize;
+        u64::from_le_bytes(
+            self.table[start .. start + MAP_ENTRY_LEN as usize]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// the block of the store the block is mapped to, if it is mapped
+    fn get(&self, block: u64) -> Option<u32> {
+        match self.raw(block) {
+            0 => None,
+            entry => Some((entry - 1) as u32),
+        }
+    }
+
+    /// map the block to a block of the store, returns what it was mapped to
+    fn set(&mut self, block: u64, stored: Option<u32>) -> Option<u32> {
+        let old = self.get(block);
+        let entry = stored.map_or(0, |s| u64::from(s) + 1);
+        let start = (block * MAP_ENTRY_LEN) as usize;
+        self.table[start .. start + MAP_ENTRY_LEN as usize]
+            .copy_from_slice(&entry.to_le_bytes());
+        self.mapped = self.mapped + stored.is_some() as u64
+            - old.is_some() as u64;
+        old
+    }
+
+    /// the offset in the map of the block with the entry of the block, and
+    /// its contents
+    fn map_block(&self, block: u64) -> (u64, &[u8]) {
+        let offset = block * MAP_ENTRY_LEN / self.block_len * self.block_len;
+        let start = offset as usize;
+        (offset, &self.table[start .. start + self.block_len as usize])
+    }
+}
+
+/// handles to the lvol with the map and to the dedup store, per core
+pub(crate) struct DedupHandles {
+    map: BdevHandle,
+    store: BdevHandle,
+}
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct DedupChannel {
+    handles: *mut DedupHandles,
+}
+
+impl DedupChannel {
+    /// allocates the handles to the lvol and the store for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let dedup = unsafe { DedupBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut DedupChannel) };
+
+        let map = dedup
+            .desc
+            .as_ref()
+            .map(|d| BdevHandle::try_from(d.clone()));
+        match (map, dedup.store.handle()) {
+            (Some(Ok(map)), Ok(store)) => {
+                ch.handles = Box::into_raw(Box::new(DedupHandles {
+                    map,
+                    store,
+                }));
+                0
+            }
+            _ => {
+                error!("{}: failed to create IO channel", dedup.name);
+                ch.handles = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut DedupChannel) };
+        if !ch.handles.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handles) };
+            ch.handles = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handles of the given channel
+    pub(crate) fn handles<'a>(
+        channel: *mut spdk_io_channel,
+    ) -> &'a DedupHandles {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut DedupChannel;
+            &*(*ctx).handles
+        }
+    }
+}
+
+pub struct DedupBdev {
+    /// name of the dedup bdev
+    pub name: String,
+    /// name of the lvol holding the map
+    pub backing: String,
+    /// the dedup bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    /// descriptor of the lvol holding the map
+    desc: Option<Arc<Descriptor>>,
+    /// the dedup store of the pool of the lvol
+    pub(crate) store: Arc<DedupStore>,
+    /// block length of the lvol
+    block_len: u64,
+    map: Mutex<BlockMap>,
+    /// held while the map is updated, so that the blocks of the map are
+    /// written in the order their entries change
+    map_lock: AsyncMutex<()>,
+}
+
+impl Debug for DedupBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (backing: {}, store: {})",
+            self.name, self.backing, self.store.name
+        )
+    }
+}
+
+impl Drop for DedupBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl DedupBdev {
+    /// the number of blocks of the map for the given number of blocks
+    fn map_blocks(blocks: u64, block_len: u64) -> u64 {
+        (blocks * MAP_ENTRY_LEN + block_len - 1) / block_len
+    }
+
+    /// the number of blocks of an lvol with the map of the given number of
+    /// blocks, and room for their data
+    fn blocks_needed(blocks: u64, block_len: u64) -> u64 {
+        MAP_START
+            + Self::map_blocks(blocks, block_len)
+            + blocks * (DEDUP_BLOCK_LEN / block_len)
+    }
+
+    /// the number of blocks of a dedup bdev on an lvol of the given number
+    /// of blocks
+    fn capacity(lvol_blocks: u64, block_len: u64) -> u64 {
+        let mut blocks = lvol_blocks.saturating_sub(MAP_START) * block_len
+            / (MAP_ENTRY_LEN + DEDUP_BLOCK_LEN);
+        while blocks > 0
+            && Self::blocks_needed(blocks, block_len) > lvol_blocks
+        {
+            blocks -= 1;
+        }
+        blocks
+    }
+
+    /// The size in bytes of an lvol with the given block length for a dedup
+    /// bdev of at least the given size. The lvol has room for the data as
+    /// well, although that is kept in the dedup store, so that it is sized
+    /// like any other lvol of the same size.
+    pub fn backing_size(size: u64, block_len: u32) -> u64 {
+        let block_len = u64::from(block_len);
+        let blocks = (size + DEDUP_BLOCK_LEN - 1) / DEDUP_BLOCK_LEN;
+        Self::blocks_needed(blocks, block_len) * block_len
+    }
+
+    /// Read the header and the map of the lvol, None if it has never been
+    /// set up.
+    async fn read_map(
+        handle: &BdevHandle,
+        block_len: u64,
+        lvol_blocks: u64,
+    ) -> Result<Option<(MapHeader, Vec<u8>)>, String> {
+        let nomem = |_| "failed to allocate a buffer".to_string();
+        let mut buf = handle.dma_malloc(block_len).map_err(nomem)?;
+        handle
+            .read_at(0, &mut buf)
+            .await
+            .map_err(|e| e.to_string())?;
+        if buf.as_slice().iter().all(|b| *b == 0) {
+            return Ok(None);
+        }
+
+        let header = match decode_block::<MapHeader>(buf.as_slice()) {
+            Some(header)
+                if header.magic == MAP_MAGIC
+                    && u64::from(header.block_len) == DEDUP_BLOCK_LEN
+                    && Self::blocks_needed(header.blocks, block_len)
+                        <= lvol_blocks =>
+            {
+                header
+            }
+            _ => return Err("the lvol does not hold a dedup map".into()),
+        };
+
+        let map_blocks = Self::map_blocks(header.blocks, block_len);
+        let mut table = Vec::with_capacity((map_blocks * block_len) as usize);
+        let mut block = MAP_START;
+        while block < MAP_START + map_blocks {
+            let count =
+                min(MAP_START + map_blocks - block, MAP_READ_LEN / block_len);
+            let mut buf =
+                handle.dma_malloc(count * block_len).map_err(nomem)?;
+            handle
+                .read_at(block * block_len, &mut buf)
+                .await
+                .map_err(|e| e.to_string())?;
+            table.extend_from_slice(buf.as_slice());
+            block += count;
+        }
+        Ok(Some((header, table)))
+    }
+
+    /// Create a dedup bdev on top of the lvol and register it with SPDK,
+    /// its data is kept in the given store. An lvol of zeroes is set up as
+    /// a new map.
+    pub(crate) async fn create(
+        name: &str,
+        backing: &str,
+        store: Arc<DedupStore>,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+
+        let create_error = |source: Errno, msg: String| {
+            error!("{}: {}", name, msg);
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        };
+
+        let block_len = u64::from(base.block_len());
+        if DEDUP_BLOCK_LEN % block_len != 0 || MAP_ENTRY_LEN > block_len {
+            return Err(create_error(
+                Errno::EINVAL,
+                format!(
+                    "blocks of {} bytes can not hold a dedup map",
+                    block_len
+                ),
+            ));
+        }
+
+        let desc = Arc::new(base.open(true).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?);
+        let io_error = |e: CoreError| create_error(Errno::EIO, e.to_string());
+
+        let handle = BdevHandle::try_from(Arc::clone(&desc)).map_err(io_error)?;
+        let (header, table) =
+            match Self::read_map(&handle, block_len, base.num_blocks()).await {
+                Ok(Some(map)) => map,
+                Ok(None) => {
+                    let header = MapHeader {
+                        magic: MAP_MAGIC,
+                        block_len: DEDUP_BLOCK_LEN as u32,
+                        blocks: Self::capacity(base.num_blocks(), block_len),
+                    };
+                    if header.blocks == 0 {
+                        return Err(create_error(
+                            Errno::EINVAL,
+                            format!("{} is too small", backing),
+                        ));
+                    }
+                    let mut buf = handle.dma_malloc(block_len).map_err(|_| {
+                        create_error(
+                            Errno::ENOMEM,
+                            "failed to allocate a buffer".into(),
+                        )
+                    })?;
+                    encode_block(&header, buf.as_mut_slice());
+                    handle.write_at(0, &buf).await.map_err(io_error)?;
+                    info!("{}: set up {} for {:?}", name, backing, header);
+                    let len = Self::map_blocks(header.blocks, block_len)
+                        * block_len;
+                    (header, vec![0; len as usize])
+                }
+                Err(msg) => {
+                    return Err(create_error(
+                        Errno::EINVAL,
+                        format!("{}: {}", backing, msg),
+                    ))
+                }
+            };
+        drop(handle);
+
+        let map = BlockMap::new(table, header.blocks, block_len);
+        let beyond = |stored: u32| u64::from(stored) >= store.blocks();
+        if let Some(block) = (0 .. header.blocks)
+            .find(|b| map.get(*b).map_or(false, beyond))
+        {
+            return Err(create_error(
+                Errno::EINVAL,
+                format!(
+                    "block {} of {} is mapped beyond the end of {}",
+                    block, backing, store.name
+                ),
+            ));
+        }
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = DEDUP_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = DedupFnTable::table();
+        b.module = DEDUP_MODULE.as_ptr();
+        b.blocklen = DEDUP_BLOCK_LEN as u32;
+        b.blockcnt = header.blocks;
+        b.required_alignment = unsafe { (*base.as_ptr()).required_alignment };
+
+        let mut d = Box::new(DedupBdev {
+            name: name.to_string(),
+            backing: backing.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            desc: Some(desc),
+            store,
+            block_len,
+            map: Mutex::new(map),
+            map_lock: AsyncMutex::new(()),
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*d.bdev.as_ptr()).ctxt = d.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                d.as_ptr(),
+                Some(DedupChannel::create),
+                Some(DedupChannel::destroy),
+                std::mem::size_of::<DedupChannel>() as u32,
+                (*d.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(d.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(d.as_ptr(), None);
+            }
+            d.desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        info!("{}: created {:?}", name, d);
+        DedupModule::get_instances().push(d);
+        Ok(name.to_string())
+    }
+
+    /// The blocks of the store the map on the lvol refers to, once for
+    /// every block mapped to them. The lvol must not be written to while
+    /// its map is read, so there must be no dedup bdev on top of it.
+    pub(crate) async fn references(
+        backing: &str,
+    ) -> Result<Vec<u32>, NexusBdevError> {
+        let error = |source: Errno, msg: String| {
+            error!("{}: {}", backing, msg);
+            NexusBdevError::OpenBdev {
+                source,
+                name: backing.to_string(),
+            }
+        };
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+        let block_len = u64::from(base.block_len());
+        let handle = BdevHandle::open(backing, false, false)
+            .map_err(|e| error(Errno::EIO, e.to_string()))?;
+        match Self::read_map(&handle, block_len, base.num_blocks()).await {
+            Ok(Some((header, table))) => {
+                let map = BlockMap::new(table, header.blocks, block_len);
+                Ok((0 .. header.blocks).filter_map(|b| map.get(b)).collect())
+            }
+            Ok(None) => Ok(Vec::new()),
+            Err(msg) => Err(error(Errno::EINVAL, msg)),
+        }
+    }
+
+    /// Unregister the dedup bdev, which closes the lvol.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match dedup_lookup(name) {
+            Some(dedup) => dedup.bdev.clone(),
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the dedup bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the lvol
+        self.desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// the number of blocks of the dedup bdev which are mapped to the store
+    pub fn mapped(&self) -> u64 {
+        self.map.lock().unwrap().mapped
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut DedupBdev)
+    }
+
+    /// obtain the DedupBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), DEDUP_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    /// complete an IO submitted to the dedup bdev
+    fn complete(&self, io: &Bio, result: Result<(), DedupError>) {
+        let status = match result {
+            Ok(()) => IoStatus::Success,
+            Err(error) => {
+                error!("{}: IO {:?} failed: {}", self.name, io, error);
+                IoStatus::Failed
+            }
+        };
+        unsafe { spdk_bdev_io_complete(io.as_ptr(), status.into()) }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let dedup = Self::from_io(&bio);
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", dedup.name, bio);
+            bio.fail();
+            return;
+        }
+        dedup.readv(&bio, DedupChannel::handles(ch));
+    }
+
+    /// read the blocks of the IO
+    pub(crate) fn readv(
+        &'static self,
+        io: &Bio,
+        handles: &'static DedupHandles,
+    ) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * DEDUP_BLOCK_LEN,
+                )
+            }
+            return;
+        }
+
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.read(&io, handles).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// store the blocks of the IO
+    pub(crate) fn writev(
+        &'static self,
+        io: &Bio,
+        handles: &'static DedupHandles,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.write(&io, handles).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// unmap the blocks of the IO, which read as zeroes from then on
+    pub(crate) fn discard(
+        &'static self,
+        io: &Bio,
+        handles: &'static DedupHandles,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let mut result = Ok(());
+            for block in io.offset() .. io.offset() + io.num_blocks() {
+                result = self.update(handles, block, None).await;
+                if result.is_err() {
+                    break;
+                }
+            }
+            self.complete(&io, result);
+        });
+    }
+
+    /// flush the lvol with the map and the store
+    pub(crate) fn flush(
+        &'static self,
+        io: &Bio,
+        handles: &'static DedupHandles,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = match handles.store.flush().await {
+                Ok(_) => handles.map.flush().await,
+                Err(error) => Err(error),
+            };
+            self.complete(&io, result.map_err(DedupError::from));
+        });
+    }
+
+    async fn read(
+        &self,
+        io: &Bio,
+        handles: &DedupHandles,
+    ) -> Result<(), DedupError> {
+        // no block of the store is freed until the read is done
+        let epoch = self.store.read_start();
+        let entries = {
+            let map = self.map.lock().unwrap();
+            (io.offset() .. io.offset() + io.num_blocks())
+                .map(|block| map.get(block))
+                .collect::<Vec<_>>()
+        };
+
+        let mut data =
+            Vec::with_capacity((io.num_blocks() * DEDUP_BLOCK_LEN) as usize);
+        let mut result = Ok(());
+        for entry in entries {
+            match entry {
+                None => data.resize(data.len() + DEDUP_BLOCK_LEN as usize, 0),
+                Some(stored) => {
+                    match self.store.read_data(&handles.store, stored).await {
+                        Ok(block) => data.extend_from_slice(&block),
+                        Err(error) => {
+                            result = Err(error);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.store.read_done(epoch);
+
+        result.map(|_| scatter(io, &data))
+    }
+
+    async fn write(
+        &self,
+        io: &Bio,
+        handles: &DedupHandles,
+    ) -> Result<(), DedupError> {
+        let data = gather(io, (io.num_blocks() * DEDUP_BLOCK_LEN) as usize);
+        let blocks = data.chunks(DEDUP_BLOCK_LEN as usize);
+        for (block, data) in (io.offset() ..).zip(blocks) {
+            self.write_block(handles, block, data).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_block(
+        &self,
+        handles: &DedupHandles,
+        block: u64,
+        data: &[u8],
+    ) -> Result<(), DedupError> {
+        if data.iter().all(|b| *b == 0) {
+            return self.update(handles, block, None).await;
+        }
+        let stored = self.store.insert(&handles.store, data).await?;
+        self.update(handles, block, Some(stored)).await
+    }
+
+    /// Map the block to the given block of the store, whose reference it
+    /// takes over, and write its entry to the map. The reference to the
+    /// block of the store it was mapped to before is dropped once the entry
+    /// has been flushed, the new one if it could not be written.
+    async fn update(
+        &self,
+        handles: &DedupHandles,
+        block: u64,
+        stored: Option<u32>,
+    ) -> Result<(), DedupError> {
+        let _map = self.map_lock.lock().await;
+        if self.map.lock().unwrap().get(block) == stored {
+            // the block keeps the reference it has
+            if let Some(stored) = stored {
+                self.store.release(&handles.store, stored).await?;
+            }
+            return Ok(());
+        }
+        let mut buf = self.dma_malloc(&handles.map)?;
+        let (old, offset) = {
+            let mut map = self.map.lock().unwrap();
+            let old = map.set(block, stored);
+            let (offset, map_block) = map.map_block(block);
+            buf.as_mut_slice().copy_from_slice(map_block);
+            (old, offset)
+        };
+
+        let offset = MAP_START * self.block_len + offset;
+        if let Err(error) = handles.map.write_at(offset, &buf).await {
+            self.map.lock().unwrap().set(block, old);
+            if let Some(stored) = stored {
+                if let Err(error) =
+                    self.store.release(&handles.store, stored).await
+                {
+                    error!("{}: {}", self.name, error);
+                }
+            }
+            return Err(error.into());
+        }
+        handles.map.flush().await?;
+        match old {
+            Some(old) => self.store.release(&handles.store, old).await,
+            None => Ok(()),
+        }
+    }
+
+    fn dma_malloc(&self, handle: &BdevHandle) -> Result<DmaBuf, DedupError> {
+        handle
+            .dma_malloc(self.block_len)
+            .map_err(|_| DedupError::NoMemory)
+    }
+}
+
+/// Lookup a dedup bdev by its name.
+pub fn dedup_lookup(name: &str) -> Option<&mut DedupBdev> {
+    DedupModule::get_instances()
+        .iter_mut()
+        .find(|d| d.name == name)
+        .map(|d| d.as_mut())
+}
+
+/// Unregister the dedup bdevs on top of the given bdev which is being
+/// removed, or all of them if it holds their dedup store, which is closed.
+pub(crate) fn backing_removed(backing: &str) {
+    DedupStore::close(backing);
+    for dedup in DedupModule::get_instances()
+        .iter()
+        .filter(|d| d.backing == backing || d.store.name == backing)
+    {
+        info!("{}: backing bdev {} removed", dedup.name, backing);
+        unsafe {
+            spdk_bdev_unregister(
+                dedup.bdev.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    dedup::{
+        dedup_bdev::{DedupBdev, DedupChannel},
+        dedup_module::DedupModule,
+    },
+    nexus::nexus_io::{Bio, IoType},
+};
+
+static DEDUP_FN_TBL: Lazy<DedupFnTable> = Lazy::new(DedupFnTable::new);
+
+pub struct DedupFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for DedupFnTable {}
+unsafe impl Send for DedupFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl DedupFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        DedupFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &DEDUP_FN_TBL.f_tbl
+    }
+
+    /// reads, writes, unmaps, write zeroes and flushes are supported, the
+    /// latter three are carried out on the map and the dedup store rather
+    /// than passed on
+    extern "C" fn io_supported(
+        _ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        matches!(
+            IoType::from(io_type),
+            IoType::Read
+                | IoType::Write
+                | IoType::Unmap
+                | IoType::WriteZeros
+                | IoType::Flush
+        )
+    }
+
+    /// Submit an IO to the dedup bdev, the blocks it covers are looked up
+    /// in the map and read from or written to the dedup store one by one.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let dedup = DedupBdev::from_io(&bio);
+        let handles = DedupChannel::handles(channel);
+
+        match bio.io_type() {
+            IoType::Read => dedup.readv(&bio, handles),
+            IoType::Write => dedup.writev(&bio, handles),
+            IoType::Unmap | IoType::WriteZeros => {
+                dedup.discard(&bio, handles)
+            }
+            IoType::Flush => dedup.flush(&bio, handles),
+            io_type => {
+                error!("{}: unsupported IO type {:?}", dedup.name, io_type);
+                bio.fail();
+            }
+        }
+    }
+
+    /// called per core to create IO channels per dedup instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the dedup bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let dedup = unsafe { DedupBdev::from_raw(ctx) };
+        dedup.destruct();
+        let name = dedup.name.clone();
+        // removing the dedup bdev from the list should cause a drop
+        DedupModule::get_instances().retain(|d| d.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let dedup = unsafe { DedupBdev::from_raw(ctx) };
+        let json = serde_json::json!({
+            "backing": dedup.backing,
+            "store": dedup.store.name,
+            "hash": dedup.store.hash().to_string(),
+            "blocks": dedup.mapped(),
+            "store_stats": dedup.store.stats(),
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "dedup\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
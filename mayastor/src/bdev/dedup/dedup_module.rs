@@ -0,0 +1,122 @@
+use std::{cell::UnsafeCell, ffi::CString, sync::Arc};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{
+    bdev::dedup::{dedup_bdev::DedupBdev, dedup_store::DedupStore},
+    ffihelper::IntoCString,
+};
+
+pub const DEDUP_MODULE_NAME: &str = "dedup";
+
+pub static DEDUP_MODULE: Lazy<DedupModule> = Lazy::new(DedupModule::new);
+
+#[derive(Default, Debug)]
+pub struct DedupInstances {
+    inner: UnsafeCell<Vec<Box<DedupBdev>>>,
+}
+
+/// the dedup stores which are open, one for every pool with one
+#[derive(Default, Debug)]
+pub struct DedupStores {
+    inner: UnsafeCell<Vec<Arc<DedupStore>>>,
+}
+
+#[derive(Debug)]
+pub struct DedupModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for DedupModule {}
+unsafe impl Sync for DedupInstances {}
+unsafe impl Sync for DedupStores {}
+
+unsafe impl Send for DedupModule {}
+unsafe impl Send for DedupInstances {}
+unsafe impl Send for DedupStores {}
+
+impl DedupModule {
+    /// construct a new DedupModule instance and setup the main properties,
+    /// dedup bdevs are only created explicitly so there is nothing to
+    /// examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = DEDUP_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::dedup_mod_init);
+        module.module_fini = Some(Self::dedup_mod_fini);
+        module.get_ctx_size = Some(Self::dedup_ctx_size);
+        module.examine_config = None;
+        module.examine_disk = None;
+        DedupModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<DedupBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static DEDUP_INSTANCES: OnceCell<DedupInstances> = OnceCell::new();
+
+        let global_instances = DEDUP_INSTANCES.get_or_init(|| DedupInstances {
+            inner: UnsafeCell::new(Vec::new()),
+        });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    /// return the dedup stores which are open, like the instances this can
+    /// only ever be called on a properly allocated thread
+    pub fn get_stores() -> &'static mut Vec<Arc<DedupStore>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static DEDUP_STORES: OnceCell<DedupStores> = OnceCell::new();
+
+        let global_stores = DEDUP_STORES.get_or_init(|| DedupStores {
+            inner: UnsafeCell::new(Vec::new()),
+        });
+
+        unsafe { &mut *global_stores.inner.get() }
+    }
+
+    extern "C" fn dedup_mod_init() -> i32 {
+        info!("Initializing Dedup Module");
+        0
+    }
+
+    extern "C" fn dedup_mod_fini() {
+        info!("Unloading Dedup Module");
+        let _ = unsafe { CString::from_raw((*(DEDUP_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+        Self::get_stores().clear();
+    }
+
+    /// the IOs are carried out by futures, which keep their own state
+    extern "C" fn dedup_ctx_size() -> i32 {
+        0
+    }
+}
+
+impl Default for DedupModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((DEDUP_MODULE.0) as *const _ as *mut _);
+    }
+}
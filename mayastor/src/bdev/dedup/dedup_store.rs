@@ -0,0 +1,673 @@
+//!
+//! The dedup store of a pool holds the data of the lvols of the pool which
+//! deduplicate it, every distinct block of data once. It is kept in a thin
+//! provisioned lvol of the pool, which starts with a header block, followed
+//! by an index with an entry of INDEX_ENTRY_LEN bytes for every block of the
+//! store, and then the blocks themselves of DEDUP_BLOCK_LEN bytes each. An
+//! entry holds the number of references to its block and the digest of its
+//! data, and an entry of zeroes is a free block, so a new store needs
+//! nothing but its header.
+//!
+//! The index is kept in memory as well, along with the blocks stored for
+//! every digest. A block written to a dedup bdev whose digest is found takes
+//! another reference to the block stored with the same data rather than
+//! being stored again. With a hash other than SHA-256 different data may
+//! have the same digest, so the block found is read back and compared with
+//! the data before it is shared.
+//!
+//! A dedup bdev takes a reference to a block before it maps one of its own
+//! blocks to it, and drops it only once the block is no longer mapped, so a
+//! crash may leave a block with more references than it has but never with
+//! fewer. The references are counted again from the maps of the dedup bdevs
+//! when the pool is imported, which frees the blocks that leaked. Freed
+//! blocks are not reused while a read which started before they were freed
+//! is in flight, as it may still be reading them.
+
+use std::{
+    cmp::min,
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt::{Debug, Display, Formatter},
+    sync::{Arc, Mutex},
+};
+
+use futures::lock::Mutex as AsyncMutex;
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bdev::{
+        dedup::dedup_module::DedupModule,
+        util::block::{decode_block, encode_block, BlockAllocator, ReadEpoch},
+    },
+    core::{
+        Bdev,
+        BdevHandle,
+        ChecksumAlgorithm,
+        CoreError,
+        Descriptor,
+        DmaBuf,
+    },
+    nexus_uri::NexusBdevError,
+};
+
+/// length of a block of the store, and the block length of dedup bdevs
+pub const DEDUP_BLOCK_LEN: u64 = 4096;
+
+/// length of the entry of a block in the index, enough for the references
+/// and a digest of any of the hashes
+const INDEX_ENTRY_LEN: u64 = 64;
+
+/// the header is in the first block, the index follows it
+const INDEX_START: u64 = 1;
+
+/// the index is read in pieces of at most this many bytes
+const INDEX_READ_LEN: u64 = 1 << 20;
+
+/// magic of the header, "MDDS"
+const STORE_MAGIC: u32 = 0x4d44_4453;
+
+/// how well the data of the lvols of a pool deduplicates
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct DedupStats {
+    /// number of distinct blocks stored
+    pub blocks: u64,
+    /// number of blocks of the dedup bdevs mapped to the blocks stored
+    pub references: u64,
+}
+
+impl DedupStats {
+    /// the bytes of data the blocks shared by several references save
+    pub fn saved_bytes(&self) -> u64 {
+        self.references.saturating_sub(self.blocks) * DEDUP_BLOCK_LEN
+    }
+
+    /// the ratio of the blocks referenced to the blocks stored, 1 when
+    /// nothing is stored
+    pub fn ratio(&self) -> f64 {
+        if self.blocks == 0 {
+            1.0
+        } else {
+            self.references as f64 / self.blocks as f64
+        }
+    }
+}
+
+/// the header of the store, in its first block
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreHeader {
+    magic: u32,
+    /// the hash the digests of the blocks are computed with
+    hash: String,
+    block_len: u32,
+    blocks: u64,
+}
+
+/// the index of the blocks of the store
+struct Index {
+    /// the index as it is stored
+    table: Vec<u8>,
+    /// the blocks in use with the given digest
+    digests: HashMap<Vec<u8>, Vec<u32>>,
+    /// the blocks of the store in use
+    allocator: BlockAllocator,
+    digest_len: usize,
+    /// block length of the lvol the store is kept in
+    block_len: u64,
+    stats: DedupStats,
+}
+
+impl Index {
+    /// build the index from the table read from the store
+    fn load(
+        table: Vec<u8>,
+        blocks: u64,
+        digest_len: usize,
+        block_len: u64,
+    ) -> Self {
+        let mut index = Index {
+            table,
+            digests: HashMap::new(),
+            allocator: BlockAllocator::new(blocks),
+            digest_len,
+            block_len,
+            stats: DedupStats::default(),
+        };
+        for block in 0 .. blocks as u32 {
+            let refs = index.refs(block);
+            if refs > 0 {
+                let digest = index.digest(block).to_vec();
+                index.allocator.set_used(block, true);
+                index.account(block, &digest, refs, true);
+            }
+        }
+        index
+    }
+
+    fn slot(&self, block: u32) -> &[u8] {
+        let start = (u64::from(block) * INDEX_ENTRY_LEN) as usize;
+        &self.table[start .. start + INDEX_ENTRY_LEN as usize]
+    }
+
+    /// the number of references to the block
+    fn refs(&self, block: u32) -> u32 {
+        u32::from_le_bytes(self.slot(block)[0 .. 4].try_into().unwrap())
+    }
+
+    /// the digest of the data of the block
+    fn digest(&self, block: u32) -> &[u8] {
+        &self.slot(block)[4 .. 4 + self.digest_len]
+    }
+
+    /// Set the references to the block and the digest of its data, a block
+    /// without references has an entry of zeroes.
+    fn set_entry(&mut self, block: u32, refs: u32, digest: &[u8]) {
+        let old = self.refs(block);
+        let old_digest = self.digest(block).to_vec();
+        self.account(block, &old_digest, old, false);
+
+        let start = (u64::from(block) * INDEX_ENTRY_LEN) as usize;
+        let slot = &mut self.table[start .. start + INDEX_ENTRY_LEN as usize];
+        slot.iter_mut().for_each(|b| *b = 0);
+        if refs > 0 {
+            slot[0 .. 4].copy_from_slice(&refs.to_le_bytes());
+            slot[4 .. 4 + digest.len()].copy_from_slice(digest);
+        }
+        self.account(block, digest, refs, true);
+    }
+
+    /// the offset in the index of the block with the entry of the given
+    /// block, and its contents
+    fn index_block(&self, block: u32) -> (u64, &[u8]) {
+        let offset = u64::from(block) * INDEX_ENTRY_LEN / self.block_len
+            * self.block_len;
+        let start = offset as usize;
+        (offset, &self.table[start .. start + self.block_len as usize])
+    }
+
+    fn account(&mut self, block: u32, digest: &[u8], refs: u32, add: bool) {
+        if refs == 0 {
+            return;
+        }
+        let stats = &mut self.stats;
+        if add {
+            stats.blocks += 1;
+            stats.references += u64::from(refs);
+            self.digests.entry(digest.to_vec()).or_default().push(block);
+        } else {
+            stats.blocks -= 1;
+            stats.references -= u64::from(refs);
+            if let Some(blocks) = self.digests.get_mut(digest) {
+                blocks.retain(|b| *b != block);
+                if blocks.is_empty() {
+                    self.digests.remove(digest);
+                }
+            }
+        }
+    }
+}
+
+/// why an IO to a dedup bdev failed
+#[derive(Debug)]
+pub(crate) enum DedupError {
+    Io(CoreError),
+    NoMemory,
+    NoSpace,
+}
+
+impl Display for DedupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::NoMemory => write!(f, "failed to allocate a buffer"),
+            Self::NoSpace => write!(f, "no free blocks in the dedup store"),
+        }
+    }
+}
+
+impl From<CoreError> for DedupError {
+    fn from(error: CoreError) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// The dedup store of a pool, shared by the dedup bdevs of its lvols.
+pub struct DedupStore {
+    /// name of the lvol the store is kept in
+    pub name: String,
+    /// descriptor of the lvol the store is kept in
+    desc: Arc<Descriptor>,
+    hash: ChecksumAlgorithm,
+    /// block length of the lvol the store is kept in
+    block_len: u64,
+    /// the first block of the data area
+    data_start: u64,
+    index: Mutex<Index>,
+    /// held while blocks are looked up and their references change, so
+    /// that data is only stored once and the blocks of the index are
+    /// written in the order their entries change
+    lock: AsyncMutex<()>,
+}
+
+impl Debug for DedupStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (hash: {})", self.name, self.hash)
+    }
+}
+
+impl DedupStore {
+    /// the number of blocks of the index for the given number of blocks
+    fn index_blocks(blocks: u64, block_len: u64) -> u64 {
+        (blocks * INDEX_ENTRY_LEN + block_len - 1) / block_len
+    }
+
+    /// the number of blocks of the lvol to store the given number of blocks
+    fn blocks_needed(blocks: u64, block_len: u64) -> u64 {
+        INDEX_START
+            + Self::index_blocks(blocks, block_len)
+            + blocks * (DEDUP_BLOCK_LEN / block_len)
+    }
+
+    /// the number of blocks an lvol of the given number of blocks stores
+    fn capacity(lvol_blocks: u64, block_len: u64) -> u64 {
+        let mut blocks = lvol_blocks.saturating_sub(INDEX_START) * block_len
+            / (INDEX_ENTRY_LEN + DEDUP_BLOCK_LEN);
+        // the blocks of the store are numbered with 32 bits
+        blocks = min(blocks, u64::from(u32::MAX));
+        while blocks > 0
+            && Self::blocks_needed(blocks, block_len) > lvol_blocks
+        {
+            blocks -= 1;
+        }
+        blocks
+    }
+
+    /// Open the store kept in the named lvol, or return it if it is open
+    /// already. An lvol of zeroes is set up as a new store if a hash is
+    /// given, otherwise the store keeps the hash it was set up with.
+    pub(crate) async fn open(
+        name: &str,
+        hash: Option<ChecksumAlgorithm>,
+    ) -> Result<Arc<DedupStore>, NexusBdevError> {
+        if let Some(store) = Self::lookup(name) {
+            return Ok(store);
+        }
+
+        let base = Bdev::lookup_by_name(name).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: name.to_string(),
+            }
+        })?;
+
+        let open_error = |source: Errno, msg: String| {
+            error!("{}: {}", name, msg);
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        };
+
+        let block_len = u64::from(base.block_len());
+        if DEDUP_BLOCK_LEN % block_len != 0 || INDEX_ENTRY_LEN > block_len {
+            return Err(open_error(
+                Errno::EINVAL,
+                format!(
+                    "blocks of {} bytes can not hold a dedup store",
+                    block_len
+                ),
+            ));
+        }
+
+        let desc = Arc::new(base.open(true).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?);
+        let io_error = |e: CoreError| open_error(Errno::EIO, e.to_string());
+        let nomem_error = |_| {
+            open_error(Errno::ENOMEM, "failed to allocate a buffer".into())
+        };
+
+        let handle = BdevHandle::try_from(Arc::clone(&desc)).map_err(io_error)?;
+        let mut buf = handle.dma_malloc(block_len).map_err(nomem_error)?;
+        handle.read_at(0, &mut buf).await.map_err(io_error)?;
+
+        let zeroes = buf.as_slice().iter().all(|b| *b == 0);
+        let header = if let (true, Some(hash)) = (zeroes, hash) {
+            let header = StoreHeader {
+                magic: STORE_MAGIC,
+                hash: hash.to_string(),
+                block_len: DEDUP_BLOCK_LEN as u32,
+                blocks: Self::capacity(base.num_blocks(), block_len),
+            };
+            if header.blocks == 0 {
+                return Err(open_error(
+                    Errno::EINVAL,
+                    format!("{} is too small", name),
+                ));
+            }
+            encode_block(&header, buf.as_mut_slice());
+            handle.write_at(0, &buf).await.map_err(io_error)?;
+            info!("{}: set up the dedup store {:?}", name, header);
+            header
+        } else {
+            match decode_block::<StoreHeader>(buf.as_slice()) {
+                Some(header)
+                    if header.magic == STORE_MAGIC
+                        && u64::from(header.block_len) == DEDUP_BLOCK_LEN
+                        && Self::blocks_needed(header.blocks, block_len)
+                            <= base.num_blocks() =>
+                {
+                    header
+                }
+                _ => {
+                    return Err(open_error(
+                        Errno::EINVAL,
+                        format!("{} does not hold a dedup store", name),
+                    ))
+                }
+            }
+        };
+
+        let stored_hash = header.hash.parse::<ChecksumAlgorithm>().map_err(
+            |msg| open_error(Errno::EINVAL, format!("{}: {}", name, msg)),
+        )?;
+        match hash {
+            Some(hash) if hash != stored_hash => warn!(
+                "{}: the dedup store hashes with {}, not {}",
+                name, stored_hash, hash
+            ),
+            _ => (),
+        }
+
+        let index_blocks = Self::index_blocks(header.blocks, block_len);
+        let mut table = Vec::with_capacity((index_blocks * block_len) as usize);
+        let mut block = INDEX_START;
+        while block < INDEX_START + index_blocks {
+            let count = min(
+                INDEX_START + index_blocks - block,
+                INDEX_READ_LEN / block_len,
+            );
+            let mut buf =
+                handle.dma_malloc(count * block_len).map_err(nomem_error)?;
+            handle
+                .read_at(block * block_len, &mut buf)
+                .await
+                .map_err(io_error)?;
+            table.extend_from_slice(buf.as_slice());
+            block += count;
+        }
+        drop(handle);
+
+        let index =
+            Index::load(table, header.blocks, stored_hash.len(), block_len);
+        let store = Arc::new(DedupStore {
+            name: name.to_string(),
+            desc,
+            hash: stored_hash,
+            block_len,
+            data_start: INDEX_START + index_blocks,
+            index: Mutex::new(index),
+            lock: AsyncMutex::new(()),
+        });
+
+        info!("{}: opened {:?}, {:?}", name, store, store.stats());
+        DedupModule::get_stores().push(Arc::clone(&store));
+        Ok(store)
+    }
+
+    /// Close the store kept in the named lvol, which happens once the dedup
+    /// bdevs using it are gone as well.
+    pub(crate) fn close(name: &str) {
+        DedupModule::get_stores().retain(|s| s.name != name);
+    }
+
+    /// the store kept in the named lvol, if it is open
+    pub(crate) fn lookup(name: &str) -> Option<Arc<DedupStore>> {
+        DedupModule::get_stores()
+            .iter()
+            .find(|s| s.name == name)
+            .cloned()
+    }
+
+    /// the hash the digests of the blocks are computed with
+    pub fn hash(&self) -> ChecksumAlgorithm {
+        self.hash
+    }
+
+    /// how well the data stored so far has deduplicated
+    pub fn stats(&self) -> DedupStats {
+        self.index.lock().unwrap().stats
+    }
+
+    /// the number of blocks the store has room for
+    pub(crate) fn blocks(&self) -> u64 {
+        self.index.lock().unwrap().allocator.blocks()
+    }
+
+    /// a handle to the lvol the store is kept in, for the current core
+    pub(crate) fn handle(&self) -> Result<BdevHandle, CoreError> {
+        BdevHandle::try_from(Arc::clone(&self.desc))
+    }
+
+    /// Store the data, returns the block it is stored in with a reference
+    /// taken to it. The block is shared with any other with the same data.
+    pub(crate) async fn insert(
+        &self,
+        handle: &BdevHandle,
+        data: &[u8],
+    ) -> Result<u32, DedupError> {
+        let digest = self.hash.digest(data);
+        let _lock = self.lock.lock().await;
+
+        let found = self
+            .index
+            .lock()
+            .unwrap()
+            .digests
+            .get(&digest)
+            .cloned()
+            .unwrap_or_default();
+        for block in found {
+            // only SHA-256 makes a different block with the same digest
+            // practically impossible
+            if self.hash != ChecksumAlgorithm::Sha256
+                && self.read_data(handle, block).await? != data
+            {
+                continue;
+            }
+            self.reference(handle, block, &digest).await?;
+            return Ok(block);
+        }
+
+        let block = self
+            .index
+            .lock()
+            .unwrap()
+            .allocator
+            .alloc(1)
+            .ok_or(DedupError::NoSpace)?[0];
+        let stored = match self.write_data(handle, block, data).await {
+            Ok(_) => self.reference(handle, block, &digest).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = stored {
+            self.index.lock().unwrap().allocator.free(&[block]);
+            return Err(error);
+        }
+        Ok(block)
+    }
+
+    /// Take another reference to the block and write its entry. The store
+    /// is flushed, so its data and the reference are on disk before a map
+    /// refers to the block.
+    async fn reference(
+        &self,
+        handle: &BdevHandle,
+        block: u32,
+        digest: &[u8],
+    ) -> Result<(), DedupError> {
+        let refs = {
+            let mut index = self.index.lock().unwrap();
+            let refs = index.refs(block);
+            index.set_entry(block, refs + 1, digest);
+            refs
+        };
+        let written = match self.write_entry(handle, block).await {
+            Ok(_) => handle.flush().await.map_err(DedupError::from),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = written {
+            self.index.lock().unwrap().set_entry(block, refs, digest);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Drop a reference to the block, which is freed with the last one. A
+    /// reference dropped in memory but not on disk only leaks the block
+    /// until the pool is imported again.
+    pub(crate) async fn release(
+        &self,
+        handle: &BdevHandle,
+        block: u32,
+    ) -> Result<(), DedupError> {
+        let _lock = self.lock.lock().await;
+        let refs = {
+            let mut index = self.index.lock().unwrap();
+            let refs = index.refs(block);
+            if refs == 0 {
+                error!("{}: block {} has no references", self.name, block);
+                return Ok(());
+            }
+            let digest = index.digest(block).to_vec();
+            index.set_entry(block, refs - 1, &digest);
+            refs
+        };
+        let result = self.write_entry(handle, block).await;
+        if refs == 1 {
+            self.index.lock().unwrap().allocator.free(&[block]);
+        }
+        result
+    }
+
+    /// Set the references of the blocks to the given counts, blocks which
+    /// are not given have no references. Returns the number of blocks whose
+    /// references were wrong.
+    pub(crate) async fn reconcile(
+        &self,
+        refs: &HashMap<u32, u32>,
+    ) -> Result<u64, DedupError> {
+        let handle = self.handle()?;
+        let _lock = self.lock.lock().await;
+        let blocks = self.index.lock().unwrap().allocator.blocks() as u32;
+        let mut fixed = 0;
+        for block in 0 .. blocks {
+            let expected = refs.get(&block).copied().unwrap_or(0);
+            let (actual, mut digest) = {
+                let index = self.index.lock().unwrap();
+                (index.refs(block), index.digest(block).to_vec())
+            };
+            if actual == expected {
+                continue;
+            }
+            if actual == 0 {
+                // the block was freed but is still mapped, which the order
+                // references are taken and dropped in rules out
+                error!(
+                    "{}: block {} is mapped but was freed",
+                    self.name, block
+                );
+                let data = self.read_data(&handle, block).await?;
+                digest = self.hash.digest(&data);
+                self.index.lock().unwrap().allocator.set_used(block, true);
+            }
+            self.index
+                .lock()
+                .unwrap()
+                .set_entry(block, expected, &digest);
+            self.write_entry(&handle, block).await?;
+            if expected == 0 {
+                self.index.lock().unwrap().allocator.free(&[block]);
+            }
+            fixed += 1;
+        }
+        if fixed > 0 {
+            warn!("{}: fixed the references of {} blocks", self.name, fixed);
+        }
+        Ok(fixed)
+    }
+
+    /// a read of the blocks of the store starts, none is freed until it is
+    /// done
+    pub(crate) fn read_start(&self) -> ReadEpoch {
+        self.index.lock().unwrap().allocator.read_start()
+    }
+
+    /// a read of the blocks of the store is done
+    pub(crate) fn read_done(&self, epoch: ReadEpoch) {
+        self.index.lock().unwrap().allocator.read_done(epoch);
+    }
+
+    fn dma_malloc(
+        &self,
+        handle: &BdevHandle,
+        len: u64,
+    ) -> Result<DmaBuf, DedupError> {
+        handle.dma_malloc(len).map_err(|_| DedupError::NoMemory)
+    }
+
+    /// the offset in bytes of a block of the data area
+    fn data_offset(&self, block: u32) -> u64 {
+        self.data_start * self.block_len + u64::from(block) * DEDUP_BLOCK_LEN
+    }
+
+    /// read the data stored in the block
+    pub(crate) async fn read_data(
+        &self,
+        handle: &BdevHandle,
+        block: u32,
+    ) -> Result<Vec<u8>, DedupError> {
+        let mut buf = self.dma_malloc(handle, DEDUP_BLOCK_LEN)?;
+        handle.read_at(self.data_offset(block), &mut buf).await?;
+        Ok(buf.as_slice().to_vec())
+    }
+
+    async fn write_data(
+        &self,
+        handle: &BdevHandle,
+        block: u32,
+        data: &[u8],
+    ) -> Result<(), DedupError> {
+        let mut buf = self.dma_malloc(handle, DEDUP_BLOCK_LEN)?;
+        buf.as_mut_slice().copy_from_slice(data);
+        handle.write_at(self.data_offset(block), &buf).await?;
+        Ok(())
+    }
+
+    /// write the block of the index with the entry of the given block
+    async fn write_entry(
+        &self,
+        handle: &BdevHandle,
+        block: u32,
+    ) -> Result<(), DedupError> {
+        let mut buf = self.dma_malloc(handle, self.block_len)?;
+        let offset = {
+            let index = self.index.lock().unwrap();
+            let (offset, block) = index.index_block(block);
+            buf.as_mut_slice().copy_from_slice(block);
+            offset
+        };
+        handle
+            .write_at(INDEX_START * self.block_len + offset, &buf)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+//!
+//! Block-level deduplication of the lvols of a pool, see [dedup_store] for
+//! how the blocks are shared and [dedup_bdev] for the lvols on top of it.
+
+pub use dedup_bdev::{dedup_lookup, DedupBdev};
+pub use dedup_store::{DedupStats, DedupStore, DEDUP_BLOCK_LEN};
+
+pub(crate) mod dedup_bdev;
+mod dedup_fn_table;
+pub(crate) mod dedup_module;
+pub(crate) mod dedup_store;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    dedup_module::register_module()
+}
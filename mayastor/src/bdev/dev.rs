@@ -31,14 +31,20 @@ use crate::{
 };
 
 mod aio;
+mod cache;
+mod compress;
 mod iscsi;
 mod loopback;
 mod malloc;
 mod null;
 mod nvme;
 mod nvmf;
+mod pi;
+mod tier;
 mod uring;
 
+pub(crate) use nvmf::ReconnectPolicy;
+
 impl Uri {
     pub fn parse(
         uri: &str,
@@ -56,6 +62,15 @@ impl Uri {
             "malloc" => Ok(Box::new(malloc::Malloc::try_from(&url)?)),
             "null" => Ok(Box::new(null::Null::try_from(&url)?)),
 
+            // read cache on top of an existing bdev
+            "cache" => Ok(Box::new(cache::Cache::try_from(&url)?)),
+            // inline compression on top of an existing bdev
+            "compress" => Ok(Box::new(compress::Compress::try_from(&url)?)),
+            // T10 DIF protection information on top of an existing bdev
+            "pi" => Ok(Box::new(pi::Pi::try_from(&url)?)),
+            // tiered storage across a fast and a slow bdev
+            "tier" => Ok(Box::new(tier::Tier::try_from(&url)?)),
+
             // retain this for the time being for backwards compatibility
             "bdev" => Ok(Box::new(loopback::Loopback::try_from(&url)?)),
             // arbitrary bdev found in spdk (used for local replicas)
@@ -0,0 +1,131 @@
+//!
+//! The cache bdev keeps recently read blocks of an existing (backing) bdev
+//! in DMA memory, which helps read heavy workloads on top of slow backing
+//! bdevs. The URI path is the name of the backing bdev, for example:
+//! cache:///disk0?size_mb=64&policy=lru creates the bdev disk0-cache with a
+//! cache of 64MiB evicting the least recently read blocks first.
+use std::{collections::HashMap, convert::TryFrom};
+
+use async_trait::async_trait;
+use snafu::ResultExt;
+use url::Url;
+
+use crate::{
+    bdev::{
+        cache::{CacheBdev, EvictionPolicy},
+        util::uri,
+        CreateDestroy,
+        GetName,
+    },
+    core::Bdev,
+    nexus_uri::{self, NexusBdevError},
+};
+
+/// size of the cache if no size_mb is given
+const DEFAULT_SIZE_MB: u64 = 64;
+
+#[derive(Debug)]
+pub(super) struct Cache {
+    /// name of the cache bdev, the name of the backing bdev with a "-cache"
+    /// suffix
+    name: String,
+    /// alias which can be used to open the bdev
+    alias: String,
+    /// name of the bdev to cache
+    backing: String,
+    /// size of the cache in bytes
+    size: u64,
+    /// determines which blocks are evicted when the cache is full
+    policy: EvictionPolicy,
+}
+
+impl TryFrom<&Url> for Cache {
+    type Error = NexusBdevError;
+
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        let segments = uri::segments(url);
+
+        if segments.is_empty() {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no path segments"),
+            });
+        }
+
+        let mut parameters: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        let size: u64 = if let Some(value) = parameters.remove("size_mb") {
+            value.parse().context(nexus_uri::IntParamParseError {
+                uri: url.to_string(),
+                parameter: String::from("size_mb"),
+            })?
+        } else {
+            DEFAULT_SIZE_MB
+        };
+
+        if size == 0 {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("size_mb must be greater than 0"),
+            });
+        }
+
+        let policy = match parameters.remove("policy") {
+            Some(value) => value.parse().map_err(|message| {
+                NexusBdevError::UriInvalid {
+                    uri: url.to_string(),
+                    message,
+                }
+            })?,
+            None => EvictionPolicy::Lru,
+        };
+
+        if let Some(keys) = uri::keys(parameters) {
+            warn!("ignored parameters: {}", keys);
+        }
+
+        let backing = segments.join("/");
+
+        Ok(Cache {
+            name: format!("{}-cache", backing),
+            alias: url.to_string(),
+            backing,
+            size: size << 20,
+            policy,
+        })
+    }
+}
+
+impl GetName for Cache {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for Cache {
+    type Error = NexusBdevError;
+
+    async fn create(&self) -> Result<String, Self::Error> {
+        let name =
+            CacheBdev::create(&self.name, &self.backing, self.size, self.policy)
+                .await?;
+
+        if let Some(mut bdev) = Bdev::lookup_by_name(&name) {
+            if !bdev.add_alias(&self.alias) {
+                error!(
+                    "Failed to add alias {} to device {}",
+                    self.alias,
+                    self.get_name()
+                );
+            }
+        }
+
+        Ok(name)
+    }
+
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        CacheBdev::destroy(&self.name).await
+    }
+}
@@ -0,0 +1,110 @@
+//!
+//! The compress bdev compresses the chunks written to an existing (backing)
+//! bdev, which is best thin provisioned so that the space the compression
+//! saves is not allocated. The URI path is the name of the backing bdev, for
+//! example: compress:///lvol0?algorithm=zstd creates the bdev lvol0-compress
+//! with 4KiB blocks, which are compressed with zstd. The default algorithm
+//! is lz4, and a backing bdev which has been compressed before keeps the
+//! algorithm it was compressed with.
+use std::{collections::HashMap, convert::TryFrom};
+
+use async_trait::async_trait;
+use url::Url;
+
+use crate::{
+    bdev::{
+        compress::{CompressBdev, CompressionAlgorithm},
+        util::uri,
+        CreateDestroy,
+        GetName,
+    },
+    core::Bdev,
+    nexus_uri::NexusBdevError,
+};
+
+#[derive(Debug)]
+pub(super) struct Compress {
+    /// name of the compress bdev, the name of the backing bdev with a
+    /// "-compress" suffix
+    name: String,
+    /// alias which can be used to open the bdev
+    alias: String,
+    /// name of the bdev to store the compressed chunks on
+    backing: String,
+    /// algorithm to compress the chunks with
+    algorithm: CompressionAlgorithm,
+}
+
+impl TryFrom<&Url> for Compress {
+    type Error = NexusBdevError;
+
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        let segments = uri::segments(url);
+
+        if segments.is_empty() {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no path segments"),
+            });
+        }
+
+        let mut parameters: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        let algorithm = match parameters.remove("algorithm") {
+            Some(value) => value.parse().map_err(|message| {
+                NexusBdevError::UriInvalid {
+                    uri: url.to_string(),
+                    message,
+                }
+            })?,
+            None => CompressionAlgorithm::default(),
+        };
+
+        if let Some(keys) = uri::keys(parameters) {
+            warn!("ignored parameters: {}", keys);
+        }
+
+        let backing = segments.join("/");
+
+        Ok(Compress {
+            name: format!("{}-compress", backing),
+            alias: url.to_string(),
+            backing,
+            algorithm,
+        })
+    }
+}
+
+impl GetName for Compress {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for Compress {
+    type Error = NexusBdevError;
+
+    async fn create(&self) -> Result<String, Self::Error> {
+        let name =
+            CompressBdev::create(&self.name, &self.backing, self.algorithm)
+                .await?;
+
+        if let Some(mut bdev) = Bdev::lookup_by_name(&name) {
+            if !bdev.add_alias(&self.alias) {
+                error!(
+                    "Failed to add alias {} to device {}",
+                    self.alias,
+                    self.get_name()
+                );
+            }
+        }
+
+        Ok(name)
+    }
+
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        CompressBdev::destroy(&self.name).await
+    }
+}
@@ -4,6 +4,7 @@ use std::{
     ffi::{CStr, CString},
     os::raw::{c_char, c_int, c_ulong, c_void},
     ptr::copy_nonoverlapping,
+    time::Duration,
 };
 
 use async_trait::async_trait;
@@ -29,6 +30,56 @@ use crate::{
 
 const DEFAULT_NVMF_PORT: u16 = 4420;
 
+/// Defaults of the reconnect policy, which can be overridden by the
+/// reconnect_delay_ms, reconnect_max_delay_ms and reconnect_attempts
+/// parameters of the URI.
+const DEFAULT_RECONNECT_DELAY_MS: u64 = 500;
+const DEFAULT_RECONNECT_MAX_DELAY_MS: u64 = 30_000;
+const DEFAULT_RECONNECT_ATTEMPTS: u32 = 10;
+
+/// How to reconnect to the target after the connection has been lost.
+/// The delay between attempts doubles after every failed attempt, up to
+/// the maximum delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ReconnectPolicy {
+    /// delay before the first attempt
+    pub(crate) initial_delay: Duration,
+    /// upper limit of the delay between attempts
+    pub(crate) max_delay: Duration,
+    /// number of attempts before giving up, 0 disables reconnecting
+    pub(crate) max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(DEFAULT_RECONNECT_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_RECONNECT_MAX_DELAY_MS),
+            max_attempts: DEFAULT_RECONNECT_ATTEMPTS,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Get the reconnect policy of the bdev with the given URI, or None if
+    /// the URI is not an nvmf URI.
+    pub(crate) fn from_uri(uri: &str) -> Option<Self> {
+        let url = Url::parse(uri).ok()?;
+        if url.scheme() != "nvmf" {
+            return None;
+        }
+        Nvmf::try_from(&url).ok().map(|nvmf| nvmf.reconnect)
+    }
+
+    /// Delay before the given attempt, counting from 0.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct Nvmf {
     /// name of the nvme controller and base name of the bdev
@@ -46,6 +97,10 @@ pub(super) struct Nvmf {
     prchk_flags: u32,
     /// uuid of the spdk bdev
     uuid: Option<uuid::Uuid>,
+    /// how to reconnect after the connection has been lost
+    reconnect: ReconnectPolicy,
+    /// the NQN to connect to the target as, one is generated if not given
+    hostnqn: Option<String>,
 }
 
 /// Convert a URI to an Nvmf "object"
@@ -108,6 +163,51 @@ impl TryFrom<&Url> for Nvmf {
             },
         )?;
 
+        let mut reconnect = ReconnectPolicy::default();
+
+        if let Some(value) = parameters.remove("reconnect_delay_ms") {
+            reconnect.initial_delay = Duration::from_millis(
+                value.parse().context(nexus_uri::IntParamParseError {
+                    uri: url.to_string(),
+                    parameter: String::from("reconnect_delay_ms"),
+                })?,
+            );
+        }
+
+        if let Some(value) = parameters.remove("reconnect_max_delay_ms") {
+            reconnect.max_delay = Duration::from_millis(
+                value.parse().context(nexus_uri::IntParamParseError {
+                    uri: url.to_string(),
+                    parameter: String::from("reconnect_max_delay_ms"),
+                })?,
+            );
+        }
+
+        if let Some(value) = parameters.remove("reconnect_attempts") {
+            reconnect.max_attempts =
+                value.parse().context(nexus_uri::IntParamParseError {
+                    uri: url.to_string(),
+                    parameter: String::from("reconnect_attempts"),
+                })?;
+        }
+
+        let hostnqn = parameters.remove("hostnqn");
+        if matches!(&hostnqn, Some(nqn) if !nqn.starts_with("nqn.")) {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("hostnqn is not an NQN"),
+            });
+        }
+
+        if reconnect.max_delay < reconnect.initial_delay {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from(
+                    "reconnect_max_delay_ms is smaller than reconnect_delay_ms",
+                ),
+            });
+        }
+
         if let Some(keys) = uri::keys(parameters) {
             warn!("ignored parameters: {}", keys);
         }
@@ -121,6 +221,8 @@ impl TryFrom<&Url> for Nvmf {
             subnqn: segments[0].to_string(),
             prchk_flags,
             uuid,
+            reconnect,
+            hostnqn,
         })
     }
 }
@@ -161,6 +263,7 @@ impl CreateDestroy for Nvmf {
         }
 
         let cname = CString::new(self.name.clone()).unwrap();
+        let hostnqn = self.hostnqn.clone().map(|n| CString::new(n).unwrap());
         let mut context = NvmeCreateContext::new(self);
 
         let (sender, receiver) = oneshot::channel::<ErrnoResult<usize>>();
@@ -172,7 +275,7 @@ impl CreateDestroy for Nvmf {
                 cname.as_ptr(),
                 &mut context.names[0],
                 context.count,
-                std::ptr::null_mut(),
+                hostnqn.as_ref().map_or(std::ptr::null(), |n| n.as_ptr()),
                 context.prchk_flags,
                 Some(done_nvme_create_cb),
                 cb_arg(sender),
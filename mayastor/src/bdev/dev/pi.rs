@@ -0,0 +1,108 @@
+//!
+//! The PI bdev formats an existing (backing) bdev with T10 DIF Type 1
+//! protection information, which is checked on every read and write. The URI
+//! path is the name of the backing bdev, for example: pi:///lvol0 creates the
+//! bdev lvol0-pi with 520 byte blocks when lvol0 has 512 byte blocks. With
+//! pi:///lvol0?format=crc32c the blocks keep their size and the protection
+//! information is a checksum generated by the PI bdev, the other checksum
+//! algorithms are xxhash64 and sha256, and crc is an alias for crc32c.
+use std::{collections::HashMap, convert::TryFrom};
+
+use async_trait::async_trait;
+use url::Url;
+
+use crate::{
+    bdev::{
+        pi::{PiBdev, PiFormat},
+        util::uri,
+        CreateDestroy,
+        GetName,
+    },
+    core::Bdev,
+    nexus_uri::NexusBdevError,
+};
+
+#[derive(Debug)]
+pub(super) struct Pi {
+    /// name of the PI bdev, the name of the backing bdev with a "-pi" suffix
+    name: String,
+    /// alias which can be used to open the bdev
+    alias: String,
+    /// name of the bdev to protect
+    backing: String,
+    /// how the protection information is exposed
+    format: PiFormat,
+}
+
+impl TryFrom<&Url> for Pi {
+    type Error = NexusBdevError;
+
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        let segments = uri::segments(url);
+
+        if segments.is_empty() {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no path segments"),
+            });
+        }
+
+        let mut parameters: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        let format = match parameters.remove("format") {
+            Some(value) => value.parse().map_err(|message| {
+                NexusBdevError::UriInvalid {
+                    uri: url.to_string(),
+                    message,
+                }
+            })?,
+            None => PiFormat::Dif,
+        };
+
+        if let Some(keys) = uri::keys(parameters) {
+            warn!("ignored parameters: {}", keys);
+        }
+
+        let backing = segments.join("/");
+
+        Ok(Pi {
+            name: format!("{}-pi", backing),
+            alias: url.to_string(),
+            backing,
+            format,
+        })
+    }
+}
+
+impl GetName for Pi {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for Pi {
+    type Error = NexusBdevError;
+
+    async fn create(&self) -> Result<String, Self::Error> {
+        let name =
+            PiBdev::create(&self.name, &self.backing, self.format).await?;
+
+        if let Some(mut bdev) = Bdev::lookup_by_name(&name) {
+            if !bdev.add_alias(&self.alias) {
+                error!(
+                    "Failed to add alias {} to device {}",
+                    self.alias,
+                    self.get_name()
+                );
+            }
+        }
+
+        Ok(name)
+    }
+
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        PiBdev::destroy(&self.name).await
+    }
+}
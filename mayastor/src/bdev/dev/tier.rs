@@ -0,0 +1,160 @@
+//!
+//! The tier bdev keeps the extents of an existing (slow) bdev which are read
+//! often on a fast bdev, and migrates them back when the fast bdev fills up.
+//! The URI path is the name of the slow bdev, for example:
+//! tier:///hdd0?fast=nvme0n1&promote=2&demote=90&extent_kb=64 creates the
+//! bdev hdd0-tier, which promotes extents of 64KiB to nvme0n1 once they have
+//! been read twice and demotes the least recently used ones once more than
+//! 90% of nvme0n1 is in use.
+use std::{collections::HashMap, convert::TryFrom};
+
+use async_trait::async_trait;
+use snafu::ResultExt;
+use url::Url;
+
+use crate::{
+    bdev::{
+        tier::{TierBdev, TierPolicy},
+        util::uri,
+        CreateDestroy,
+        GetName,
+    },
+    core::Bdev,
+    nexus_uri::{self, NexusBdevError},
+};
+
+/// length of the extents if no extent_kb is given
+const DEFAULT_EXTENT_KB: u64 = 64;
+
+#[derive(Debug)]
+pub(super) struct Tier {
+    /// name of the tier bdev, the name of the slow bdev with a "-tier"
+    /// suffix
+    name: String,
+    /// alias which can be used to open the bdev
+    alias: String,
+    /// name of the bdev holding the extents read often
+    fast: String,
+    /// name of the bdev holding all other extents
+    slow: String,
+    /// length of the extents in bytes
+    extent_len: u64,
+    /// determines when extents are migrated
+    policy: TierPolicy,
+}
+
+impl TryFrom<&Url> for Tier {
+    type Error = NexusBdevError;
+
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
+        let segments = uri::segments(url);
+
+        if segments.is_empty() {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no path segments"),
+            });
+        }
+
+        let mut parameters: HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        let fast = parameters.remove("fast").ok_or_else(|| {
+            NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("no fast bdev"),
+            }
+        })?;
+
+        let mut int_param = |parameter: &str, default: u64| {
+            parameters.remove(parameter).map_or(Ok(default), |value| {
+                value.parse::<u64>().context(nexus_uri::IntParamParseError {
+                    uri: url.to_string(),
+                    parameter: parameter.to_string(),
+                })
+            })
+        };
+
+        let defaults = TierPolicy::default();
+        let promote = int_param("promote", defaults.promote.into())?;
+        let demote = int_param("demote", defaults.demote.into())?;
+        let extent_kb = int_param("extent_kb", DEFAULT_EXTENT_KB)?;
+
+        if promote == 0 || promote > u64::from(u32::MAX) {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("promote must be greater than 0"),
+            });
+        }
+
+        if demote == 0 || demote > 100 {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("demote must be between 1 and 100"),
+            });
+        }
+
+        if extent_kb == 0 {
+            return Err(NexusBdevError::UriInvalid {
+                uri: url.to_string(),
+                message: String::from("extent_kb must be greater than 0"),
+            });
+        }
+
+        if let Some(keys) = uri::keys(parameters) {
+            warn!("ignored parameters: {}", keys);
+        }
+
+        let slow = segments.join("/");
+
+        Ok(Tier {
+            name: format!("{}-tier", slow),
+            alias: url.to_string(),
+            fast,
+            slow,
+            extent_len: extent_kb << 10,
+            policy: TierPolicy {
+                promote: promote as u32,
+                demote: demote as u32,
+            },
+        })
+    }
+}
+
+impl GetName for Tier {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for Tier {
+    type Error = NexusBdevError;
+
+    async fn create(&self) -> Result<String, Self::Error> {
+        let name = TierBdev::create(
+            &self.name,
+            &self.fast,
+            &self.slow,
+            self.extent_len,
+            self.policy,
+        )
+        .await?;
+
+        if let Some(mut bdev) = Bdev::lookup_by_name(&name) {
+            if !bdev.add_alias(&self.alias) {
+                error!(
+                    "Failed to add alias {} to device {}",
+                    self.alias,
+                    self.get_name()
+                );
+            }
+        }
+
+        Ok(name)
+    }
+
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        TierBdev::destroy(&self.name).await
+    }
+}
@@ -1,5 +1,19 @@
 use async_trait::async_trait;
 
+pub use cache::{cache_lookup, CacheBdev, CacheStats, EvictionPolicy};
+pub use compress::{
+    compress_lookup,
+    CompressBdev,
+    CompressStats,
+    CompressionAlgorithm,
+};
+pub use dedup::{
+    dedup_lookup,
+    DedupBdev,
+    DedupStats,
+    DedupStore,
+    DEDUP_BLOCK_LEN,
+};
 pub use nexus::{
     nexus_bdev::{
         nexus_create,
@@ -9,10 +23,19 @@ pub use nexus::{
         NexusStatus,
         VerboseError,
     },
+    nexus_bdev_failover::nexus_failover_all,
+    nexus_bdev_rebuild::RebuildProgress,
+    nexus_bdev_scrub::{
+        ReplicaComparison,
+        ScrubMismatch,
+        ScrubReport,
+        ScrubSource,
+    },
+    nexus_bdev_vote::ReadVote,
     nexus_child::{lookup_child_from_bdev, ChildState, Reason},
     nexus_child_error_store::{ActionType, NexusErrStore, QueryType},
     nexus_child_status_config,
-    nexus_io::Bio,
+    nexus_io::{Bio, IoType},
     nexus_label::{GPTHeader, GptEntry},
     nexus_metadata_content::{
         NexusConfig,
@@ -21,6 +44,9 @@ pub use nexus::{
         NexusConfigVersion3,
     },
 };
+pub use pi::{pi_lookup, PiBdev, PiFormat};
+pub use readonly::{readonly_lookup, ReadOnlyBdev};
+pub use tier::{tier_lookup, TierBdev, TierPolicy, TierStats};
 
 pub trait BdevCreateDestroy: CreateDestroy + GetName + std::fmt::Debug {}
 
@@ -43,6 +69,12 @@ pub trait GetName {
 
 pub struct Uri;
 
+pub(crate) mod cache;
+pub(crate) mod compress;
+pub(crate) mod dedup;
 pub(crate) mod dev;
 pub(crate) mod nexus;
+pub(crate) mod pi;
+pub(crate) mod readonly;
+pub(crate) mod tier;
 pub mod util;
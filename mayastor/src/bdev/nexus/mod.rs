@@ -14,11 +14,16 @@ macro_rules! c_str {
 
 pub mod nexus_bdev;
 pub mod nexus_bdev_children;
+pub mod nexus_bdev_failover;
 pub mod nexus_bdev_rebuild;
+pub mod nexus_bdev_scrub;
 pub mod nexus_bdev_snapshot;
+pub mod nexus_bdev_vote;
 mod nexus_channel;
 pub(crate) mod nexus_child;
+pub(crate) mod nexus_child_breaker;
 pub(crate) mod nexus_child_error_store;
+pub(crate) mod nexus_child_reconnect;
 pub mod nexus_child_status_config;
 mod nexus_config;
 pub mod nexus_fn_table;
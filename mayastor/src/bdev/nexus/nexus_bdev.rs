@@ -8,8 +8,10 @@ use std::{
     convert::TryFrom,
     fmt::{Display, Formatter},
     os::raw::c_void,
+    time::Duration,
 };
 
+use crossbeam::atomic::AtomicCell;
 use futures::channel::oneshot;
 use nix::errno::Errno;
 use serde::Serialize;
@@ -21,6 +23,7 @@ use spdk_sys::{
     spdk_bdev_desc,
     spdk_bdev_io,
     spdk_bdev_io_get_buf,
+    spdk_bdev_notify_blockcnt_change,
     spdk_bdev_nvme_admin_passthru,
     spdk_bdev_readv_blocks,
     spdk_bdev_register,
@@ -39,6 +42,7 @@ use crate::{
         nexus,
         nexus::{
             instances,
+            nexus_bdev_vote::ReadVote,
             nexus_channel::{
                 DREvent,
                 NexusChannel,
@@ -51,13 +55,22 @@ use crate::{
             nexus_nbd::{NbdDisk, NbdError},
         },
     },
-    core::{Bdev, CoreError, DmaError, Protocol, Reactor, Share},
+    core::{
+        Bdev,
+        CoreError,
+        DmaError,
+        IoPool,
+        Protocol,
+        Reactor,
+        Share,
+    },
     ffihelper::errno_result_from_i32,
+    logger::RATE_LIMITED,
     lvs::Lvol,
     nexus_uri::{bdev_destroy, NexusBdevError},
     rebuild::RebuildError,
     subsys,
-    subsys::{Config, NvmfSubsystem},
+    subsys::{Config, NvmfError, NvmfSubsystem},
 };
 use std::ptr::NonNull;
 
@@ -113,6 +126,8 @@ pub enum Error {
     ShareNvmfNexus { source: CoreError, name: String },
     #[snafu(display("Failed to unshare nexus {}", name))]
     UnshareNexus { source: CoreError, name: String },
+    #[snafu(display("Failed to fail over the initiators of nexus {}", name))]
+    FailoverNexus { source: NvmfError, name: String },
     #[snafu(display("Failed to allocate label of nexus {}", name))]
     AllocLabel { source: DmaError, name: String },
     #[snafu(display("Failed to write label of nexus {}", name))]
@@ -140,6 +155,19 @@ pub enum Error {
         name
     ))]
     ChildGeometry { child: String, name: String },
+    #[snafu(display(
+        "Child {} of nexus {} is too small, child size {} nexus size {}",
+        child,
+        name,
+        child_size,
+        nexus_size
+    ))]
+    ChildTooSmall {
+        child: String,
+        name: String,
+        child_size: u64,
+        nexus_size: u64,
+    },
     #[snafu(display("Child {} of nexus {} cannot be found", child, name))]
     ChildMissing { child: String, name: String },
     #[snafu(display("Child {} of nexus {} has no error store", child, name))]
@@ -162,6 +190,12 @@ pub enum Error {
         name
     ))]
     DestroyLastChild { child: String, name: String },
+    #[snafu(display(
+        "Cannot delete the last healthy child {} of nexus {}",
+        child,
+        name
+    ))]
+    DestroyLastHealthyChild { child: String, name: String },
     #[snafu(display(
         "Cannot remove the last child {} of nexus {} from the IO path",
         child,
@@ -245,6 +279,68 @@ pub enum Error {
     FailedGetHandle,
     #[snafu(display("Failed to create snapshot on nexus {}", name))]
     FailedCreateSnapshot { name: String, source: CoreError },
+    #[snafu(display("Cannot shrink nexus {} to {} bytes", name, size))]
+    ShrinkNexus { name: String, size: u64 },
+    #[snafu(display(
+        "Children of nexus {} are too small to grow it to {} bytes",
+        name,
+        size
+    ))]
+    ChildrenTooSmall { name: String, size: u64 },
+    #[snafu(display("Failed to resize nexus {}", name))]
+    ResizeNexus { source: Errno, name: String },
+    #[snafu(display(
+        "Data partition of nexus {} would move from block {} to {}",
+        name,
+        expected,
+        offset
+    ))]
+    DataOffsetChanged { name: String, offset: u64, expected: u64 },
+    #[snafu(display(
+        "Child {} of nexus {} failed too often, it is not rebuilt for {:?}",
+        child,
+        name,
+        cooldown
+    ))]
+    ChildBreakerOpen {
+        child: String,
+        name: String,
+        cooldown: Duration,
+    },
+    #[snafu(display(
+        "Nexus {} needs at least two healthy children to scrub",
+        name
+    ))]
+    ScrubChildren { name: String },
+    #[snafu(display(
+        "Failed to lock the segment at offset {} of nexus {} for scrubbing",
+        offset,
+        name
+    ))]
+    ScrubSegment {
+        source: Errno,
+        name: String,
+        offset: u64,
+    },
+    #[snafu(display(
+        "Range at offset {} length {} is not within nexus {} or not aligned \
+         to its blocks",
+        offset,
+        len,
+        name
+    ))]
+    ScrubRange { name: String, offset: u64, len: u64 },
+    #[snafu(display("Failed to scrub child {} of nexus {}", child, name))]
+    ScrubChild {
+        source: CoreError,
+        child: String,
+        name: String,
+    },
+    #[snafu(display(
+        "Nexus {} needs at least three healthy children to vote on reads",
+        name
+    ))]
+    ReadVoteChildren { name: String },
 }
 
 impl From<Error> for tonic::Status {
@@ -274,15 +370,39 @@ impl From<Error> for tonic::Status {
             Error::ChildGeometry {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::ChildTooSmall {
+                ..
+            } => Status::invalid_argument(e.to_string()),
             Error::OpenChild {
                 ..
             } => Status::invalid_argument(e.to_string()),
             Error::DestroyLastChild {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::DestroyLastHealthyChild {
+                ..
+            } => Status::invalid_argument(e.to_string()),
             Error::ChildNotFound {
                 ..
             } => Status::not_found(e.to_string()),
+            Error::ShrinkNexus {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::ChildrenTooSmall {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::ChildBreakerOpen {
+                ..
+            } => Status::unavailable(e.to_string()),
+            Error::ScrubChildren {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::ScrubRange {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::ReadVoteChildren {
+                ..
+            } => Status::failed_precondition(e.to_string()),
             e => Status::new(Code::Internal, e.to_string()),
         }
     }
@@ -323,6 +443,8 @@ pub struct Nexus {
     pub nexus_target: Option<NexusTarget>,
     /// the maximum number of times to attempt to send an IO
     pub(crate) max_io_attempts: i32,
+    /// how reads are served, from one child or voted on by all of them
+    pub(crate) read_vote: AtomicCell<ReadVote>,
 }
 
 unsafe impl core::marker::Sync for Nexus {}
@@ -415,6 +537,7 @@ impl Nexus {
             size,
             nexus_target: None,
             max_io_attempts: cfg.err_store_opts.max_io_attempts,
+            read_vote: AtomicCell::new(ReadVote::default()),
         });
 
         n.bdev.set_uuid(match uuid {
@@ -447,6 +570,36 @@ impl Nexus {
         u64::from(self.bdev.block_len()) * self.bdev.num_blocks()
     }
 
+    /// returns the name of the nexus
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns an iterator over all nexus instances, as they are when it is
+    /// created. The nexus last returned may be destroyed while iterating,
+    /// any of those still to come must not be.
+    pub fn iter() -> impl Iterator<Item = &'static mut Nexus> {
+        instances()
+            .iter_mut()
+            .map(|n| n.as_mut() as *mut Nexus)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|n| unsafe { &mut *n })
+    }
+
+    /// lookup a nexus by its name
+    pub fn lookup(name: &str) -> Option<&'static mut Nexus> {
+        nexus_lookup(name)
+    }
+
+    /// returns the URI of each child of the nexus with its state
+    pub fn children(&self) -> Vec<(String, ChildState)> {
+        self.children
+            .iter()
+            .map(|c| (c.name.clone(), c.state()))
+            .collect()
+    }
+
     /// reconfigure the child event handler
     pub(crate) async fn reconfigure(&self, event: DREvent) {
         let (s, r) = oneshot::channel::<i32>();
@@ -500,6 +653,60 @@ impl Nexus {
         Ok(())
     }
 
+    /// Grow the nexus to `size` bytes once all of its children have been
+    /// grown. The labels of the children are rewritten so that the data
+    /// partition covers the new size, and the consumers of the nexus bdev
+    /// (i.e. the nvmf target and thus the initiator) are notified of the new
+    /// block count.
+    pub async fn resize(&mut self, size: u64) -> Result<(), Error> {
+        if size < self.size {
+            return Err(Error::ShrinkNexus {
+                name: self.name.clone(),
+                size,
+            });
+        }
+        let block_len = u64::from(self.bdev.block_len());
+        if self.min_num_blocks() * block_len < size {
+            return Err(Error::ChildrenTooSmall {
+                name: self.name.clone(),
+                size,
+            });
+        }
+        if size == self.size {
+            return Ok(());
+        }
+
+        // the data partition keeps its offset, only its end moves
+        let label = self.generate_label();
+        if label.offset() != self.data_ent_offset {
+            return Err(Error::DataOffsetChanged {
+                name: self.name.clone(),
+                offset: label.offset(),
+                expected: self.data_ent_offset,
+            });
+        }
+        self.write_all_labels(&label).await.context(WriteLabel {
+            name: self.name.clone(),
+        })?;
+
+        // the label might not cover all of the size due to the on disk
+        // metadata, the nexus is as large as the blocks it does cover
+        let blocks = std::cmp::min(size / block_len, label.get_block_count());
+        let rc = unsafe {
+            spdk_bdev_notify_blockcnt_change(self.bdev.as_ptr(), blocks)
+        };
+        if rc != 0 {
+            return Err(Error::ResizeNexus {
+                source: Errno::from_i32(rc.abs()),
+                name: self.name.clone(),
+            });
+        }
+        self.size = blocks * block_len;
+
+        info!("{}: resized to {} bytes", self.name, self.size());
+        Ok(())
+    }
+
     /// close the nexus and any children that are open
     pub(crate) fn destruct(&mut self) -> NexusState {
         // a closed operation might already be in progress calling unregister
@@ -678,14 +885,16 @@ impl Nexus {
         &mut *(n as *mut Nexus)
     }
 
-    /// determine if any of the children do not support the requested
-    /// io type. Break the loop on first occurrence.
+    /// determine if all of the children support the requested io type, as
+    /// the io is sent to all of them. An unmap in particular is only
+    /// advertised, and so passed down by the initiator, when every replica
+    /// can honour it. Break the loop on first occurrence.
     /// TODO: optionally add this check during nexus creation
     pub fn io_is_supported(&self, io_type: IoType) -> bool {
         self.children
             .iter()
             .filter_map(|e| e.bdev.as_ref())
-            .any(|b| b.io_type_supported(io_type))
+            .all(|b| b.io_type_supported(io_type))
     }
 
     /// main IO completion routine
@@ -754,7 +963,10 @@ impl Nexus {
         let ch = NexusChannel::inner_from_channel(ch);
         let (desc, ch) = ch.readers[ch.previous].io_tuple();
         let ret = Self::readv_impl(io, desc, ch);
-        if ret != 0 {
+        if ret == -libc::ENOMEM {
+            IoPool::exhausted();
+            Bio::from(io).no_mem();
+        } else if ret != 0 {
             let bio = Bio::from(io);
             let nexus = bio.nexus_as_ref();
             error!("{}: Failed to submit IO {:?}", nexus.name, bio);
@@ -763,13 +975,18 @@ impl Nexus {
 
     /// read vectored io from the underlying children.
     pub(crate) fn readv(&self, io: &Bio, channels: &mut NexusChannelInner) {
+        if self.votes(channels) {
+            self.readv_vote(io, channels);
+            return;
+        }
+
         // we use RR to read from the children.
         let child = channels.child_select();
         if child.is_none() {
             error!(
-                "{}: No child available to read from {:p}",
+                target: RATE_LIMITED,
+                "{}: No child available to read from",
                 io.nexus_as_ref().name,
-                io.as_ptr(),
             );
             io.fail();
             return;
@@ -792,11 +1009,15 @@ impl Nexus {
 
         let ret = Self::readv_impl(io.as_ptr(), desc, ch);
 
-        if ret != 0 {
+        if ret == -libc::ENOMEM {
+            // the bdev layer resubmits the IO once other IOs complete
+            IoPool::exhausted();
+            io.no_mem();
+        } else if ret != 0 {
             error!(
-                "{}: Failed to submit dispatched IO {:p}",
+                target: RATE_LIMITED,
+                "{}: Failed to submit dispatched IO",
                 io.nexus_as_ref().name,
-                io.as_ptr()
             );
 
             io.fail();
@@ -829,12 +1050,21 @@ impl Nexus {
     /// check results after submitting IO, failing if all failed to submit
     #[inline(always)]
     fn check_io_submission(&self, results: &[i32], io: &Bio) {
+        // the bdev IO pool is exhausted, nothing has been submitted yet
+        if !results.is_empty()
+            && results.iter().all(|r| *r == -libc::ENOMEM)
+        {
+            IoPool::exhausted();
+            io.no_mem();
+            return;
+        }
+
         // if any of the children failed to dispatch
         if results.iter().any(|r| *r != 0) {
             error!(
-                "{}: Failed to submit dispatched IO {:?}",
+                target: RATE_LIMITED,
+                "{}: Failed to submit dispatched IO",
                 io.nexus_as_ref().name,
-                io.as_ptr(),
             );
         }
 
@@ -1081,8 +1311,8 @@ pub async fn nexus_create(
     Ok(())
 }
 
-/// Lookup a nexus by its name (currently used only by test functions).
-pub fn nexus_lookup(name: &str) -> Option<&mut Nexus> {
+/// Lookup a nexus by its name.
+pub fn nexus_lookup(name: &str) -> Option<&'static mut Nexus> {
     if let Some(nexus) = instances().iter_mut().find(|n| n.name == name) {
         Some(nexus)
     } else {
@@ -140,6 +140,18 @@ impl Nexus {
         })?;
 
         let child_bdev = match Bdev::lookup_by_name(&name) {
+            Some(child) if child.size_in_bytes() < self.size => {
+                if let Err(err) = bdev_destroy(uri).await {
+                    error!("Failed to destroy child bdev too small: {}", err);
+                }
+
+                return Err(Error::ChildTooSmall {
+                    child: name,
+                    name: self.name.clone(),
+                    child_size: child.size_in_bytes(),
+                    nexus_size: self.size,
+                });
+            }
             Some(child) => {
                 if child.block_len() != self.bdev.block_len()
                     || self.min_num_blocks() > child.num_blocks()
@@ -214,7 +226,9 @@ impl Nexus {
     }
 
     /// Destroy child with given uri.
-    /// If the child does not exist the method returns success.
+    /// If the child does not exist the method returns success. The last
+    /// healthy child is never destroyed, as the nexus could no longer be read
+    /// from. A rebuild of the child is cancelled first.
     pub async fn remove_child(&mut self, uri: &str) -> Result<(), Error> {
         if self.child_count == 1 {
             return Err(Error::DestroyLastChild {
@@ -223,6 +237,19 @@ impl Nexus {
             });
         }
 
+        let healthy_children = self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .collect::<Vec<_>>();
+
+        if healthy_children.len() == 1 && healthy_children[0].name == uri {
+            return Err(Error::DestroyLastHealthyChild {
+                name: self.name.clone(),
+                child: uri.to_owned(),
+            });
+        }
+
         let cancelled_rebuilding_children =
             self.cancel_child_rebuild_jobs(uri).await;
 
@@ -340,6 +367,14 @@ impl Nexus {
         trace!("{} Online child request", self.name);
 
         if let Some(child) = self.children.iter_mut().find(|c| c.name == name) {
+            // a child failing repeatedly is left alone during its cooldown
+            if let Some(cooldown) = child.breaker.cooldown_left() {
+                return Err(Error::ChildBreakerOpen {
+                    child: name.to_owned(),
+                    name: self.name.clone(),
+                    cooldown,
+                });
+            }
             child.online(self.size).await.context(OpenChild {
                 child: name.to_owned(),
                 name: self.name.clone(),
@@ -565,6 +600,19 @@ impl Nexus {
         blockcnt
     }
 
+    /// returns the state of the child with the given uri, which tells why
+    /// the child is faulted should it be
+    pub fn child_state(&self, uri: &str) -> Result<ChildState, Error> {
+        self.children
+            .iter()
+            .find(|c| c.name == uri)
+            .map(|c| c.state())
+            .ok_or_else(|| Error::ChildNotFound {
+                child: uri.to_owned(),
+                name: self.name.clone(),
+            })
+    }
+
     /// lookup a child by its name
     pub fn child_lookup(&self, name: &str) -> Option<&NexusChild> {
         self.children
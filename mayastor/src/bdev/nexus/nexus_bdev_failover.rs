@@ -0,0 +1,83 @@
+//! Graceful failover of the nexuses shared to initiators when mayastor shuts
+//! down cleanly. Before the subsystem of a nexus goes away, the control plane
+//! publishes its volume on another node holding one of its replicas, under
+//! the same NQN, and the path through this node is then marked inaccessible.
+//! Multipath initiators switch over to the remaining path when notified of
+//! the ANA change, so tearing down the nexus does not fail their IO.
+
+use std::time::Duration;
+
+use futures_timer::Delay;
+use mbus_api::{v0::FailoverVolume, Message};
+use snafu::ResultExt;
+
+use crate::{
+    bdev::nexus::{
+        instances,
+        nexus_bdev::{Error, FailoverNexus, Nexus, NexusTarget},
+    },
+    core::MayastorEnvironment,
+    subsys::{AnaState, Config, NvmfSubsystem},
+};
+
+impl Nexus {
+    /// Move the initiators of the nexus to another path before it goes away,
+    /// returns whether the nexus is shared over nvmf and so had a path to
+    /// move them from. The control plane, if there is one, is asked to
+    /// publish the volume on another node first.
+    pub async fn failover(&self) -> Result<bool, Error> {
+        if !matches!(self.nexus_target, Some(NexusTarget::NexusNvmfTarget)) {
+            return Ok(false);
+        }
+
+        let args = MayastorEnvironment::global_or_default();
+        if args.mbus_endpoint.is_some() {
+            let request = FailoverVolume {
+                uuid: self.bdev.uuid_as_string().into(),
+                node: args.node_name.into(),
+            };
+            match request.request().await {
+                Ok(uri) => info!("{}: volume published at {}", self.name, uri),
+                // the initiators may still have another path
+                Err(error) => warn!(
+                    "{}: failed to publish the volume on another node: {:?}",
+                    self.name, error
+                ),
+            }
+        }
+
+        let ss = NvmfSubsystem::nqn_lookup(&self.bdev.name()).ok_or_else(
+            || Error::NotShared {
+                name: self.name.clone(),
+            },
+        )?;
+        ss.set_ana_state(AnaState::Inaccessible)
+            .await
+            .context(FailoverNexus {
+                name: self.name.clone(),
+            })?;
+        Ok(true)
+    }
+}
+
+/// Fail over all the nexuses shared over nvmf, and give their initiators
+/// time to switch paths before the subsystems are torn down.
+pub async fn nexus_failover_all() {
+    let mut count = 0;
+    for nexus in instances().iter() {
+        match nexus.failover().await {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(error) => error!("{}", error),
+        }
+    }
+
+    let grace = Config::get().nexus_opts.failover_grace_ms;
+    if count > 0 && grace > 0 {
+        info!(
+            "waiting {} ms for the initiators of {} nexuses to switch paths",
+            grace, count
+        );
+        Delay::new(Duration::from_millis(grace)).await;
+    }
+}
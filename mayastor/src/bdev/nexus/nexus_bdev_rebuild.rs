@@ -34,6 +34,18 @@ use crate::{
     },
 };
 
+/// Progress of the rebuild of a nexus child
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebuildProgress {
+    /// total number of blocks to rebuild
+    pub blocks_total: u64,
+    /// number of blocks rebuilt so far
+    pub blocks_rebuilt: u64,
+    /// rebuild progress in %
+    pub percentage: u64,
+    pub state: RebuildState,
+}
+
 impl Nexus {
     /// Starts a rebuild job and returns a receiver channel
     /// which can be used to await the rebuild completion
@@ -56,6 +68,13 @@ impl Nexus {
 
         let dst_child_name =
             match self.children.iter_mut().find(|c| c.name == name) {
+                Some(c) if c.breaker.is_open() => {
+                    Err(Error::ChildBreakerOpen {
+                        child: name.to_owned(),
+                        name: self.name.clone(),
+                        cooldown: c.breaker.cooldown_left().unwrap_or_default(),
+                    })
+                }
                 Some(c)
                     if c.state() == ChildState::Faulted(Reason::OutOfSync) =>
                 {
@@ -188,6 +207,48 @@ impl Nexus {
         })
     }
 
+    /// Returns the progress of the rebuild of child target `name`. Once its
+    /// job is gone, the rebuild has completed if the child is healthy and
+    /// failed if it is faulted because of it.
+    pub fn rebuild_progress(
+        &self,
+        name: &str,
+    ) -> Result<RebuildProgress, Error> {
+        let error = match self.get_rebuild_job(name) {
+            Ok(rj) => {
+                let stats = rj.as_client().stats();
+                return Ok(RebuildProgress {
+                    blocks_total: stats.blocks_total,
+                    blocks_rebuilt: stats.blocks_recovered,
+                    percentage: stats.progress,
+                    state: rj.state(),
+                });
+            }
+            Err(error) => error,
+        };
+
+        let blocks_total = self.bdev.num_blocks();
+        match self.children.iter().find(|c| c.name == name) {
+            Some(c) if c.state() == ChildState::Open => Ok(RebuildProgress {
+                blocks_total,
+                blocks_rebuilt: blocks_total,
+                percentage: 100,
+                state: RebuildState::Completed,
+            }),
+            Some(c)
+                if c.state() == ChildState::Faulted(Reason::RebuildFailed) =>
+            {
+                Ok(RebuildProgress {
+                    blocks_total,
+                    blocks_rebuilt: 0,
+                    percentage: 0,
+                    state: RebuildState::Failed,
+                })
+            }
+            _ => Err(error),
+        }
+    }
+
     /// Cancels all rebuilds jobs associated with the child.
     /// Returns a list of rebuilding children whose rebuild job was cancelled.
     pub async fn cancel_child_rebuild_jobs(&self, name: &str) -> Vec<String> {
@@ -0,0 +1,356 @@
+//! Implements scrubbing of a nexus: the data of all healthy children is read
+//! back segment by segment and compared. Segments which differ are reported
+//! and, when a source is given, the divergent children are repaired by
+//! rewriting them with the data of the authoritative child. Every segment is
+//! locked on the nexus while it is scrubbed, so that frontend writes can not
+//! race with the comparison or the repair, and scrubbing is paced by its
+//! share of the background IO budget.
+//!
+//! Operators who suspect a specific region of a nexus can compare just that
+//! range of the children, and repair it, the same way while the nexus stays
+//! online.
+
+use futures_timer::Delay;
+use serde::Serialize;
+
+use crate::{
+    bdev::nexus::{
+        nexus_bdev::{Error, Nexus},
+        nexus_child::ChildState,
+    },
+    core::{
+        BackgroundClass,
+        BackgroundScheduler,
+        Bdev,
+        BdevHandle,
+        DmaBuf,
+        RangeContext,
+    },
+};
+
+/// size of the segments compared at once in bytes
+const SCRUB_SEGMENT_SIZE: u64 = 64 * 1024;
+
+/// The child whose data is taken as correct when the children differ.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrubSource {
+    /// the preferred child to read from, given by its name
+    Child(String),
+    /// the data held by more than half of the children, segments without a
+    /// majority are reported but not repaired
+    Majority,
+}
+
+/// A segment of the nexus whose data differs across the children.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScrubMismatch {
+    /// offset of the segment in the nexus in bytes
+    pub offset: u64,
+    /// length of the segment in bytes
+    pub len: u64,
+    /// children whose data differs from the authoritative data, or all
+    /// children if there is none
+    pub children: Vec<String>,
+    /// whether the children have been rewritten with the authoritative data
+    pub repaired: bool,
+}
+
+/// How the children of a nexus compare over a range of it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ReplicaComparison {
+    /// offset of the range in the nexus in bytes
+    pub offset: u64,
+    /// length of the range in bytes
+    pub len: u64,
+    /// children holding the same data as the majority of them over the range
+    pub agree: Vec<String>,
+    /// children whose data differs from that of the majority somewhere in
+    /// the range, or all children where there is no majority
+    pub disagree: Vec<String>,
+    /// the segments of the range found to differ
+    pub mismatches: Vec<ScrubMismatch>,
+}
+
+/// Outcome of a scrub of a nexus.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ScrubReport {
+    /// number of bytes compared across the children
+    pub scrubbed: u64,
+    /// the segments found to differ
+    pub mismatches: Vec<ScrubMismatch>,
+}
+
+impl Nexus {
+    /// Scrub the nexus, comparing the data of all healthy children. If a
+    /// source is given, children which differ from it are repaired.
+    pub async fn scrub(
+        &self,
+        source: Option<ScrubSource>,
+    ) -> Result<ScrubReport, Error> {
+        let repair = source.is_some();
+        self.scrub_blocks(source.as_ref(), repair, 0, self.bdev.num_blocks())
+            .await
+    }
+
+    /// Compare the given range of the nexus, in bytes, across all healthy
+    /// children without taking the nexus offline. Children whose data
+    /// differs from that of the majority of them disagree, nothing is
+    /// repaired.
+    pub async fn compare_replicas(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<ReplicaComparison, Error> {
+        let (blk, num_blocks) = self.scrub_range(offset, len)?;
+        let report = self
+            .scrub_blocks(Some(&ScrubSource::Majority), false, blk, num_blocks)
+            .await?;
+
+        let (disagree, agree) = self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .map(|c| c.name.clone())
+            .partition(|name| {
+                report.mismatches.iter().any(|m| m.children.contains(name))
+            });
+        Ok(ReplicaComparison {
+            offset,
+            len,
+            agree,
+            disagree,
+            mismatches: report.mismatches,
+        })
+    }
+
+    /// Repair the given range of the nexus, in bytes, rewriting the children
+    /// whose data differs from that of the source, while the nexus stays
+    /// online.
+    pub async fn repair_range(
+        &self,
+        offset: u64,
+        len: u64,
+        source: ScrubSource,
+    ) -> Result<ScrubReport, Error> {
+        let (blk, num_blocks) = self.scrub_range(offset, len)?;
+        self.scrub_blocks(Some(&source), true, blk, num_blocks).await
+    }
+
+    /// The blocks of the nexus covered by the given range in bytes, which
+    /// must be aligned to the blocks and within the nexus.
+    fn scrub_range(&self, offset: u64, len: u64) -> Result<(u64, u64), Error> {
+        let block_len = u64::from(self.bdev.block_len());
+        if len == 0
+            || offset % block_len != 0
+            || len % block_len != 0
+            || offset + len > self.bdev.num_blocks() * block_len
+        {
+            return Err(Error::ScrubRange {
+                name: self.name.clone(),
+                offset,
+                len,
+            });
+        }
+        Ok((offset / block_len, len / block_len))
+    }
+
+    /// Compare the given blocks of the nexus across all healthy children,
+    /// and repair the children which differ from the source if asked to.
+    async fn scrub_blocks(
+        &self,
+        source: Option<&ScrubSource>,
+        repair: bool,
+        start: u64,
+        num_blocks: u64,
+    ) -> Result<ScrubReport, Error> {
+        let children = self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .map(|c| c.handle().map(|h| (c.name.clone(), h)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::FailedGetHandle)?;
+
+        if children.len() < 2 {
+            return Err(Error::ScrubChildren {
+                name: self.name.clone(),
+            });
+        }
+        if let Some(ScrubSource::Child(name)) = source {
+            if !children.iter().any(|(n, _)| n == name) {
+                return Err(Error::ChildNotFound {
+                    child: name.clone(),
+                    name: self.name.clone(),
+                });
+            }
+        }
+
+        let descriptor = Bdev::open_by_name(&self.name, false)
+            .map_err(|_| Error::FailedGetHandle)?;
+        let ch = descriptor.get_channel().ok_or(Error::FailedGetHandle)?;
+
+        let block_len = u64::from(self.bdev.block_len());
+        let end = start + num_blocks;
+        let segment_blocks = std::cmp::max(SCRUB_SEGMENT_SIZE / block_len, 1);
+
+        info!(
+            "{}: scrubbing {} children at offset {} length {}, repair: {}, \
+             source: {:?}",
+            self.name,
+            children.len(),
+            start * block_len,
+            num_blocks * block_len,
+            repair,
+            source
+        );
+
+        let mut report = ScrubReport::default();
+        let mut blk = start;
+        while blk < end {
+            // segments are aligned to the nexus also when the range is not
+            let len =
+                std::cmp::min(segment_blocks - blk % segment_blocks, end - blk);
+            if let Some(wait) = BackgroundScheduler::reserve(
+                BackgroundClass::Scrub,
+                len * block_len * children.len() as u64,
+            ) {
+                Delay::new(wait).await;
+            }
+
+            let mut ctx = RangeContext::new(blk, len);
+            descriptor.lock_lba_range(&mut ctx, &ch).await.map_err(|e| {
+                Error::ScrubSegment {
+                    source: e,
+                    name: self.name.clone(),
+                    offset: blk * block_len,
+                }
+            })?;
+
+            let result = self
+                .scrub_segment(&children, source, repair, blk, len)
+                .await;
+
+            descriptor.unlock_lba_range(&mut ctx, &ch).await.map_err(|e| {
+                Error::ScrubSegment {
+                    source: e,
+                    name: self.name.clone(),
+                    offset: blk * block_len,
+                }
+            })?;
+
+            if let Some(mismatch) = result? {
+                report.mismatches.push(mismatch);
+            }
+            report.scrubbed += len * block_len;
+            blk += len;
+        }
+
+        info!(
+            "{}: scrubbed {} bytes, {} segments differ, {} repaired",
+            self.name,
+            report.scrubbed,
+            report.mismatches.len(),
+            report.mismatches.iter().filter(|m| m.repaired).count()
+        );
+        Ok(report)
+    }
+
+    /// Compare one segment of the nexus across the children, and repair the
+    /// ones differing from the source if asked to.
+    async fn scrub_segment(
+        &self,
+        children: &[(String, BdevHandle)],
+        source: Option<&ScrubSource>,
+        repair: bool,
+        blk: u64,
+        len: u64,
+    ) -> Result<Option<ScrubMismatch>, Error> {
+        let block_len = u64::from(self.bdev.block_len());
+        let offset = (self.data_ent_offset + blk) * block_len;
+
+        let mut bufs: Vec<DmaBuf> = Vec::with_capacity(children.len());
+        for (name, hdl) in children {
+            let mut buf = hdl
+                .dma_malloc(len * block_len)
+                .map_err(|_| Error::FailedGetHandle)?;
+            hdl.read_at(offset, &mut buf).await.map_err(|e| {
+                Error::ScrubChild {
+                    source: e,
+                    child: name.clone(),
+                    name: self.name.clone(),
+                }
+            })?;
+            bufs.push(buf);
+        }
+
+        if bufs.iter().all(|b| b.as_slice() == bufs[0].as_slice()) {
+            return Ok(None);
+        }
+
+        // index of the child holding the authoritative data
+        let good = match source {
+            Some(ScrubSource::Child(name)) => {
+                children.iter().position(|(n, _)| n == name)
+            }
+            Some(ScrubSource::Majority) => (0 .. bufs.len()).find(|i| {
+                bufs.iter()
+                    .filter(|b| b.as_slice() == bufs[*i].as_slice())
+                    .count()
+                    * 2
+                    > bufs.len()
+            }),
+            None => None,
+        };
+
+        let mut mismatch = ScrubMismatch {
+            offset: blk * block_len,
+            len: len * block_len,
+            children: Vec::new(),
+            repaired: false,
+        };
+
+        let good = match good {
+            Some(good) => good,
+            None => {
+                mismatch.children =
+                    children.iter().map(|(n, _)| n.clone()).collect();
+                warn!(
+                    "{}: children {:?} differ at offset {} length {}",
+                    self.name, mismatch.children, mismatch.offset, mismatch.len
+                );
+                return Ok(Some(mismatch));
+            }
+        };
+
+        for (i, (name, hdl)) in children.iter().enumerate() {
+            if bufs[i].as_slice() == bufs[good].as_slice() {
+                continue;
+            }
+            mismatch.children.push(name.clone());
+            if !repair {
+                warn!(
+                    "{}: child {} differs from {} at offset {} length {}",
+                    self.name,
+                    name,
+                    children[good].0,
+                    mismatch.offset,
+                    mismatch.len
+                );
+                continue;
+            }
+            hdl.write_at(offset, &bufs[good]).await.map_err(|e| {
+                Error::ScrubChild {
+                    source: e,
+                    child: name.clone(),
+                    name: self.name.clone(),
+                }
+            })?;
+            info!(
+                "{}: repaired child {} at offset {} length {} from {}",
+                self.name, name, mismatch.offset, mismatch.len, children[good].0
+            );
+        }
+        mismatch.repaired = repair;
+        Ok(Some(mismatch))
+    }
+}
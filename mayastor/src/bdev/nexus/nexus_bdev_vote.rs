@@ -0,0 +1,328 @@
+//! Implements voting reads of a nexus, for volumes where correctness trumps
+//! latency. A voting read is sent to all healthy children, and completed
+//! with the data returned by the majority of them. Children whose read
+//! succeeded but returned data differing from the majority are flagged and,
+//! if asked to, repaired in the background the same way a range of the
+//! nexus is repaired by an operator. This masks a child returning wrong
+//! data, which a read from a single child would pass on.
+//!
+//! Voting is opt-in per nexus as every read is amplified by the number of
+//! children, and it needs at least three healthy children. With fewer of
+//! them, reads go to one child at a time again.
+
+use std::{ffi::c_void, ptr, sync::atomic::Ordering};
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_read_blocks,
+    spdk_io_channel,
+};
+
+use crate::{
+    bdev::nexus::{
+        nexus_bdev::{Error, Nexus},
+        nexus_bdev_scrub::ScrubSource,
+        nexus_channel::{NexusChannel, NexusChannelInner},
+        nexus_child::ChildState,
+        nexus_io::Bio,
+    },
+    bdev::nexus_lookup,
+    core::{DmaBuf, IoPool, Reactors},
+};
+
+/// the minimum number of healthy children for reads to be voted on
+pub const MIN_VOTERS: usize = 3;
+
+/// How the reads of a nexus are served.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadVote {
+    /// every read goes to one child, in turn
+    Off,
+    /// every read goes to all children, the minority is flagged
+    Flag,
+    /// every read goes to all children, the minority is flagged and
+    /// repaired
+    Repair,
+}
+
+impl Default for ReadVote {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+/// the voting read of one IO of the nexus
+struct VoteCtx {
+    io: Bio,
+    /// the children read from
+    bdevs: Vec<*mut spdk_bdev>,
+    /// the data read from each child
+    bufs: Vec<DmaBuf>,
+    /// whether the read of each child succeeded
+    ok: Vec<bool>,
+    /// the number of child reads not completed yet
+    pending: usize,
+}
+
+impl Nexus {
+    /// how the reads of the nexus are served
+    pub fn read_vote(&self) -> ReadVote {
+        self.read_vote.load()
+    }
+
+    /// Set how the reads of the nexus are served, voting needs at least
+    /// three healthy children.
+    pub fn set_read_vote(&self, vote: ReadVote) -> Result<(), Error> {
+        let healthy = self
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open)
+            .count();
+        if vote != ReadVote::Off && healthy < MIN_VOTERS {
+            return Err(Error::ReadVoteChildren {
+                name: self.name.clone(),
+            });
+        }
+        info!("{}: read voting {:?}", self.name, vote);
+        self.read_vote.store(vote);
+        Ok(())
+    }
+
+    /// whether the given read is voted on
+    pub(crate) fn votes(&self, channels: &NexusChannelInner) -> bool {
+        self.read_vote.load() != ReadVote::Off
+            && channels.readers.len() >= MIN_VOTERS
+    }
+
+    /// Read from all children, the majority of them decides the data the
+    /// read is completed with.
+    pub(crate) fn readv_vote(&self, io: &Bio, channels: &NexusChannelInner) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::vote_get_buf_cb),
+                    io.num_blocks() * io.block_len(),
+                )
+            }
+            return;
+        }
+
+        let len = io.num_blocks() * io.block_len();
+        let mut bufs = Vec::with_capacity(channels.readers.len());
+        for hdl in &channels.readers {
+            match hdl.dma_malloc(len) {
+                Ok(buf) => bufs.push(buf),
+                Err(_) => {
+                    error!("{}: failed to allocate vote buffers", self.name);
+                    io.fail();
+                    return;
+                }
+            }
+        }
+
+        let ctx = Box::into_raw(Box::new(VoteCtx {
+            io: io.clone(),
+            bdevs: channels
+                .readers
+                .iter()
+                .map(|h| h.get_bdev().as_ptr())
+                .collect(),
+            bufs,
+            ok: vec![false; channels.readers.len()],
+            pending: channels.readers.len(),
+        }));
+
+        let mut results = Vec::with_capacity(channels.readers.len());
+        for (i, hdl) in channels.readers.iter().enumerate() {
+            let (desc, ch) = hdl.io_tuple();
+            let rc = unsafe {
+                spdk_bdev_read_blocks(
+                    desc,
+                    ch,
+                    *(*ctx).bufs[i],
+                    io.offset() + self.data_ent_offset,
+                    io.num_blocks(),
+                    Some(Self::vote_done),
+                    ctx as *mut c_void,
+                )
+            };
+            results.push(rc);
+        }
+
+        // the reads which failed to submit will not complete
+        let failed = results.iter().filter(|rc| **rc != 0).count();
+        if failed == 0 {
+            return;
+        }
+        let mut ctx = unsafe { Box::from_raw(ctx) };
+        ctx.pending -= failed;
+        if ctx.pending > 0 {
+            error!("{}: failed to submit voting read {:?}", self.name, io);
+            Box::into_raw(ctx);
+        } else if results.iter().all(|rc| *rc == -libc::ENOMEM) {
+            // the bdev layer resubmits the IO once other IOs complete
+            IoPool::exhausted();
+            io.no_mem();
+        } else {
+            error!("{}: failed to submit voting read {:?}", self.name, io);
+            io.fail();
+        }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn vote_get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let nexus = bio.nexus_as_ref();
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", nexus.name, bio);
+            bio.fail();
+            return;
+        }
+        let ch = NexusChannel::inner_from_channel(ch);
+        nexus.readv_vote(&bio, ch);
+    }
+
+    /// completion of the read of one child
+    extern "C" fn vote_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        arg: *mut c_void,
+    ) {
+        let ctx = unsafe { &mut *(arg as *mut VoteCtx) };
+        let child_io = Bio::from(child_io);
+        let bdev = child_io.bdev_as_ref();
+        if let Some(i) = ctx.bdevs.iter().position(|b| *b == bdev.as_ptr()) {
+            ctx.ok[i] = success;
+        }
+        if !success {
+            Reactors::master().send_future(Bio::child_retire(
+                ctx.io.nexus_as_ref().name.clone(),
+                bdev,
+            ));
+        }
+        child_io.free();
+
+        ctx.pending -= 1;
+        if ctx.pending == 0 {
+            let ctx = unsafe { Box::from_raw(arg as *mut VoteCtx) };
+            ctx.nexus_vote();
+        }
+    }
+}
+
+impl VoteCtx {
+    /// Complete the IO with the data of the majority of the children read
+    /// from successfully, and flag the others.
+    fn nexus_vote(self) {
+        let nexus = self.io.nexus_as_ref();
+        let voters = (0 .. self.bufs.len())
+            .filter(|i| self.ok[*i])
+            .collect::<Vec<_>>();
+        let bufs = &self.bufs;
+        let same =
+            |i: usize, j: usize| bufs[i].as_slice() == bufs[j].as_slice();
+        let winner = voters.iter().copied().find(|i| {
+            voters.iter().filter(|j| same(*i, **j)).count() * 2 > voters.len()
+        });
+
+        let offset = self.io.offset() * self.io.block_len();
+        let len = self.io.num_blocks() * self.io.block_len();
+        let winner = match winner {
+            Some(winner) => winner,
+            None => {
+                error!(
+                    "{}: no majority among {} children for read at offset {} \
+                     length {}",
+                    nexus.name,
+                    voters.len(),
+                    offset,
+                    len
+                );
+                self.io.fail();
+                return;
+            }
+        };
+
+        let minority = voters
+            .iter()
+            .filter(|i| !same(winner, **i))
+            .map(|i| self.bdevs[*i])
+            .collect::<Vec<_>>();
+        for child in nexus.children.iter().filter(|c| {
+            c.bdev
+                .as_ref()
+                .map_or(false, |b| minority.contains(&b.as_ptr()))
+        }) {
+            child.vote_mismatches.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "{}: child {} outvoted for read at offset {} length {}",
+                nexus.name, child.name, offset, len
+            );
+        }
+
+        self.copy_to_io(winner);
+        let mut io = self.io.clone();
+        io.reset(0);
+        io.ok();
+
+        if !minority.is_empty() && nexus.read_vote() == ReadVote::Repair {
+            Reactors::master()
+                .send_future(repair(nexus.name.clone(), offset, len));
+        }
+    }
+
+    /// copy the data read from the given child into the buffers of the IO
+    fn copy_to_io(&self, child: usize) {
+        let data = self.bufs[child].as_slice();
+        let iovs = unsafe {
+            std::slice::from_raw_parts(
+                self.io.iovs(),
+                self.io.iov_count() as usize,
+            )
+        };
+        let mut copied = 0;
+        for iov in iovs {
+            let n = std::cmp::min(iov.iov_len as usize, data.len() - copied);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    data[copied ..].as_ptr(),
+                    iov.iov_base as *mut u8,
+                    n,
+                );
+            }
+            copied += n;
+        }
+    }
+}
+
+/// Repair the range of the nexus a voting read found children to differ in,
+/// the range is locked, read again and repaired from the majority.
+async fn repair(nexus: String, offset: u64, len: u64) {
+    let nexus = match nexus_lookup(&nexus) {
+        Some(nexus) => nexus,
+        None => return,
+    };
+    match nexus.repair_range(offset, len, ScrubSource::Majority).await {
+        Ok(report) => info!(
+            "{}: repaired {} segments at offset {} length {}",
+            nexus.name,
+            report.mismatches.iter().filter(|m| m.repaired).count(),
+            offset,
+            len
+        ),
+        Err(error) => error!(
+            "{}: failed to repair offset {} length {}: {}",
+            nexus.name, offset, len, error
+        ),
+    }
+}
+
@@ -135,7 +135,9 @@ impl NexusChannelInner {
                 }
             });
 
-        // then add write-only children
+        // then add write-only children, children being rebuilt are not read
+        // from so reads keep going to the healthy children whilst any number
+        // of rebuilds are running
         if !self.readers.is_empty() {
             nexus
                 .children
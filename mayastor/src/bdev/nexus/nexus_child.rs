@@ -1,4 +1,11 @@
-use std::{convert::TryFrom, fmt::Display, sync::Arc};
+use std::{
+    convert::TryFrom,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use nix::errno::Errno;
 use serde::{export::Formatter, Serialize};
@@ -10,6 +17,7 @@ use crate::{
             instances,
             nexus_channel::DREvent,
             nexus_child::ChildState::Faulted,
+            nexus_child_breaker::ChildBreaker,
             nexus_child_status_config::ChildStatusConfig,
         },
         nexus_lookup,
@@ -17,6 +25,7 @@ use crate::{
         VerboseError,
     },
     core::{Bdev, BdevHandle, CoreError, Descriptor, Reactor, Reactors},
+    events::{self, Event},
     nexus_uri::{bdev_create, bdev_destroy, NexusBdevError},
     rebuild::{ClientOperations, RebuildJob},
     subsys::Config,
@@ -135,6 +144,13 @@ pub struct NexusChild {
     /// record of most-recent IO errors
     #[serde(skip_serializing)]
     pub(crate) err_store: Option<NexusErrStore>,
+    /// circuit breaker which stops rebuilds of a child failing repeatedly
+    #[serde(skip_serializing)]
+    pub(crate) breaker: ChildBreaker,
+    /// number of voting reads the child returned data differing from the
+    /// majority for
+    #[serde(skip_serializing)]
+    pub(crate) vote_mismatches: AtomicU64,
     #[serde(skip_serializing)]
     remove_channel: (mpsc::Sender<()>, mpsc::Receiver<()>),
 }
@@ -159,15 +175,23 @@ impl Display for NexusChild {
 
 impl NexusChild {
     pub(crate) fn set_state(&self, state: ChildState) {
+        let old = self.state.swap(state);
         trace!(
             "{}: child {}: state change from {} to {}",
             self.parent,
             self.name,
-            self.state.load().to_string(),
+            old.to_string(),
             state.to_string(),
         );
 
-        self.state.store(state);
+        if old != state {
+            events::publish(Event::ChildStateChanged {
+                nexus: self.parent.clone(),
+                child: self.name.clone(),
+                old,
+                new: state,
+            });
+        }
     }
 
     /// Open the child in RW mode and claim the device to be ours. If the child
@@ -247,6 +271,9 @@ impl NexusChild {
     /// We do not close the child if it is out-of-sync because it will
     /// subsequently be rebuilt.
     pub(crate) async fn fault(&mut self, reason: Reason) {
+        if reason == Reason::IoError || reason == Reason::RebuildFailed {
+            self.record_failure();
+        }
         match reason {
             Reason::OutOfSync => {
                 self.set_state(ChildState::Faulted(reason));
@@ -266,6 +293,24 @@ impl NexusChild {
         NexusChild::save_state_change();
     }
 
+    /// Record a failure of the child with its circuit breaker.
+    pub(crate) fn record_failure(&mut self) {
+        if self.breaker.record_failure() {
+            warn!(
+                "{}: child {} failed too often, not rebuilding it for {:?}",
+                self.parent,
+                self.name,
+                self.breaker.cooldown_left().unwrap_or_default()
+            );
+        }
+    }
+
+    /// The number of voting reads the child has been outvoted in, as the
+    /// data it returned differed from that of the majority of the children.
+    pub fn vote_mismatches(&self) -> u64 {
+        self.vote_mismatches.load(Ordering::Relaxed)
+    }
+
     /// Set the child as temporarily offline
     pub(crate) async fn offline(&mut self) {
         if let Err(e) = self.close().await {
@@ -429,6 +474,8 @@ impl NexusChild {
             desc: None,
             state: AtomicCell::new(ChildState::Init),
             err_store: None,
+            breaker: ChildBreaker::default(),
+            vote_mismatches: AtomicU64::new(0),
             remove_channel: mpsc::channel(0),
         }
     }
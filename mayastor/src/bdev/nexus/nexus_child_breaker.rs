@@ -0,0 +1,61 @@
+//!
+//! Circuit breaker of a nexus child.
+//!
+//! A child which fails, is rebuilt and then fails again shortly after keeps
+//! the nexus busy with rebuilds that are bound to be wasted. The breaker
+//! counts the failures of the child and once there have been too many of
+//! them within the configured window, it trips: the child is then left
+//! faulted and is not rebuilt until the cooldown has passed.
+
+use std::time::{Duration, Instant};
+
+use crate::subsys::Config;
+
+#[derive(Debug, Default)]
+pub struct ChildBreaker {
+    /// times of the recent failures of the child
+    failures: Vec<Instant>,
+    /// the breaker is open (tripped) until this time
+    open_until: Option<Instant>,
+}
+
+impl ChildBreaker {
+    /// Record a failure of the child, returns true if it trips the breaker.
+    pub(crate) fn record_failure(&mut self) -> bool {
+        let cfg = &Config::get().err_store_opts;
+        if cfg.breaker_max_failures == 0 {
+            return false;
+        }
+
+        let now = Instant::now();
+        let window = Duration::from_nanos(cfg.breaker_window_ns);
+        self.failures.retain(|t| now.duration_since(*t) < window);
+        self.failures.push(now);
+
+        if self.failures.len() < cfg.breaker_max_failures as usize {
+            return false;
+        }
+
+        self.failures.clear();
+        self.open_until =
+            Some(now + Duration::from_nanos(cfg.breaker_cooldown_ns));
+        true
+    }
+
+    /// Time left until the breaker closes again, None if it is closed.
+    pub fn cooldown_left(&self) -> Option<Duration> {
+        let until = self.open_until?;
+        let now = Instant::now();
+        if until > now {
+            Some(until - now)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the breaker has tripped and the child must not be
+    /// rebuilt.
+    pub fn is_open(&self) -> bool {
+        self.cooldown_left().is_some()
+    }
+}
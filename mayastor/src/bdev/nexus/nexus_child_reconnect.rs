@@ -0,0 +1,123 @@
+//!
+//! Reconnect of nexus children backed by remote (nvmf) replicas.
+//!
+//! When a remote child is retired because of I/O errors, its bdev is
+//! destroyed which takes it out of the I/O path, so that I/O to it fails
+//! fast and the nexus carries on with its other children. The connection is
+//! then re-established in the background, with an exponential backoff
+//! between the attempts as described by the reconnect policy of the URI of
+//! the child. Once reconnected, the child is onlined and rebuilt like any
+//! other out-of-sync child.
+
+use futures_timer::Delay;
+
+use crate::{
+    bdev::{
+        dev::ReconnectPolicy,
+        nexus::nexus_child::{ChildState, Reason},
+        nexus_lookup,
+        VerboseError,
+    },
+    nexus_uri::bdev_destroy,
+};
+
+/// Reconnect the child with the given URI of the nexus after it has been
+/// faulted because of I/O errors. Nothing is done for children which are
+/// not nvmf children.
+pub(crate) async fn reconnect_child(nexus_name: String, uri: String) {
+    let policy = match ReconnectPolicy::from_uri(&uri) {
+        Some(policy) => policy,
+        None => return,
+    };
+
+    for attempt in 0 .. policy.max_attempts {
+        let delay = policy.delay(attempt);
+        debug!(
+            "{}: reconnecting child {} in {:?} (attempt {}/{})",
+            nexus_name,
+            uri,
+            delay,
+            attempt + 1,
+            policy.max_attempts
+        );
+        Delay::new(delay).await;
+
+        // a child failing repeatedly is not brought back before the cooldown
+        // of its circuit breaker has passed
+        if let Some(cooldown) = nexus_lookup(&nexus_name)
+            .and_then(|n| n.children.iter().find(|c| c.name == uri))
+            .and_then(|c| c.breaker.cooldown_left())
+        {
+            info!(
+                "{}: holding child {} faulted for {:?}",
+                nexus_name, uri, cooldown
+            );
+            Delay::new(cooldown).await;
+        }
+
+        let nexus = match nexus_lookup(&nexus_name) {
+            Some(nexus) => nexus,
+            None => {
+                debug!("{}: nexus gone, stop reconnecting {}", nexus_name, uri);
+                return;
+            }
+        };
+
+        // the child may have been removed, or onlined by an rpc call, while
+        // we were waiting
+        match nexus.children.iter().find(|c| c.name == uri) {
+            Some(child)
+                if child.state() == ChildState::Faulted(Reason::IoError) =>
+            {
+                child.set_state(ChildState::Closed);
+            }
+            _ => {
+                debug!(
+                    "{}: child {} no longer faulted, stop reconnecting",
+                    nexus_name, uri
+                );
+                return;
+            }
+        }
+
+        match nexus.online_child(&uri).await {
+            Ok(status) => {
+                info!(
+                    "{}: child {} reconnected, nexus is {:?}",
+                    nexus_name, uri, status
+                );
+                return;
+            }
+            Err(error) => {
+                warn!(
+                    "{}: failed to reconnect child {}: {}",
+                    nexus_name,
+                    uri,
+                    error.verbose()
+                );
+            }
+        }
+
+        // online_child leaves the child out-of-sync when the bdev could be
+        // opened but not rebuilt, e.g. because no healthy child is left to
+        // rebuild from. It is connected, so stop here in that case.
+        if let Some(child) =
+            nexus.children.iter_mut().find(|c| c.name == uri)
+        {
+            if child.desc.is_some() {
+                return;
+            }
+            if child.bdev.take().is_some() {
+                if let Err(error) = bdev_destroy(&uri).await {
+                    error!("{} destroying bdev {}", error, uri);
+                }
+            }
+            child.set_state(ChildState::Faulted(Reason::IoError));
+        }
+    }
+
+    error!(
+        "{}: giving up reconnecting child {} after {} attempts",
+        nexus_name, uri, policy.max_attempts
+    );
+}
@@ -11,11 +11,17 @@
 //! This will update the configuration file but WILL NOT update the in-memory
 //! ChildStatusConfig structure as this is only required on startup and not
 //! during runtime.
-
-use crate::bdev::nexus::{
-    instances,
-    nexus_channel::DREvent,
-    nexus_child::{ChildState, NexusChild},
+//!
+//! The progress of the rebuild of each child is saved alongside its status, so
+//! that a rebuild interrupted by a restart resumes where it left off.
+
+use crate::{
+    bdev::nexus::{
+        instances,
+        nexus_channel::DREvent,
+        nexus_child::{ChildState, NexusChild, Reason},
+    },
+    rebuild::RebuildJob,
 };
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
@@ -23,18 +29,26 @@ use std::{collections::HashMap, fs, fs::File, io::Write, sync::Once};
 
 type ChildName = String;
 static mut CONFIG_FILE: Option<String> = None;
+/// rebuild checkpoints loaded from the config file which have not been
+/// resumed from yet
+static mut PENDING_CHECKPOINTS: Option<HashMap<ChildName, u64>> = None;
 static INIT: Once = Once::new();
 pub static STATUS_CONFIG: OnceCell<ChildStatusConfig> = OnceCell::new();
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ChildStatusConfig {
     status: HashMap<ChildName, ChildState>,
+    /// number of blocks of the data partition of the child which have
+    /// durably been rebuilt
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    rebuild_checkpoints: HashMap<ChildName, u64>,
 }
 
 impl Default for ChildStatusConfig {
     fn default() -> Self {
         Self {
             status: Default::default(),
+            rebuild_checkpoints: Default::default(),
         }
     }
 }
@@ -82,7 +96,20 @@ impl ChildStatusConfig {
     /// Apply the status in the configuration to each child.
     pub(crate) async fn apply() {
         debug!("Applying child status");
-        let store = &ChildStatusConfig::get().status;
+        let config = ChildStatusConfig::get();
+        let store = &config.status;
+
+        // only children which are still out of sync can resume their rebuild
+        let out_of_sync = ChildState::Faulted(Reason::OutOfSync);
+        let pending = config
+            .rebuild_checkpoints
+            .iter()
+            .filter(|(name, _)| store.get(*name) == Some(&out_of_sync))
+            .map(|(name, blocks)| (name.clone(), *blocks))
+            .collect();
+        unsafe {
+            PENDING_CHECKPOINTS = Some(pending);
+        }
         for nexus in instances() {
             nexus.children.iter_mut().for_each(|child| {
                 if let Some(status) = store.get(&child.name) {
@@ -119,14 +146,17 @@ impl ChildStatusConfig {
         debug!("Saving child status");
         let mut status_cfg = match cfg {
             Some(cfg) => cfg,
-            None => ChildStatusConfig {
-                status: HashMap::new(),
-            },
+            None => ChildStatusConfig::default(),
         };
 
         instances().iter().for_each(|nexus| {
             nexus.children.iter().for_each(|child| {
                 status_cfg.status.insert(child.name.clone(), child.state());
+                if let Some(blocks) = ChildStatusConfig::checkpoint(child) {
+                    status_cfg
+                        .rebuild_checkpoints
+                        .insert(child.name.clone(), blocks);
+                }
             });
         });
 
@@ -149,13 +179,36 @@ impl ChildStatusConfig {
     /// Therefore, we have to explicitly add the child to the configuration
     /// here.
     pub(crate) fn add(child: &NexusChild) -> Result<(), std::io::Error> {
-        let mut cfg = ChildStatusConfig {
-            status: HashMap::new(),
-        };
+        let mut cfg = ChildStatusConfig::default();
         cfg.status.insert(child.name.clone(), child.state());
         ChildStatusConfig::do_save(Some(cfg))
     }
 
+    /// The rebuild checkpoint of the child, which is the progress of its
+    /// rebuild if it is running, or the checkpoint loaded from the config file
+    /// if the rebuild has yet to be resumed.
+    fn checkpoint(child: &NexusChild) -> Option<u64> {
+        if child.state() != ChildState::Faulted(Reason::OutOfSync) {
+            return None;
+        }
+
+        if let Ok(job) = RebuildJob::lookup(&child.name) {
+            return job.checkpoint();
+        }
+
+        unsafe {
+            PENDING_CHECKPOINTS
+                .as_ref()
+                .and_then(|pending| pending.get(&child.name).cloned())
+        }
+    }
+
+    /// Remove and return the rebuild checkpoint loaded from the config file
+    /// for the child, the number of blocks which need not be rebuilt again.
+    pub(crate) fn take_checkpoint(name: &str) -> Option<u64> {
+        unsafe { PENDING_CHECKPOINTS.as_mut()?.remove(name) }
+    }
+
     /// Initialise the config file location
     fn init_config_location(path: &str) {
         INIT.call_once(|| unsafe {
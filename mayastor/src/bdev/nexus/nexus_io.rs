@@ -19,6 +19,7 @@ use crate::{
         nexus::{
             nexus_bdev::{Nexus, NEXUS_PRODUCT_ID},
             nexus_channel::DREvent,
+            nexus_child_reconnect::reconnect_child,
             nexus_fn_table::NexusFnTable,
         },
         nexus_lookup,
@@ -27,6 +28,7 @@ use crate::{
         Reason,
     },
     core::{Bdev, Cores, GenericStatusCode, Mthread, NvmeStatus, Reactors},
+    logger::RATE_LIMITED,
     nexus_uri::bdev_destroy,
 };
 
@@ -270,6 +272,15 @@ impl Bio {
         }
     }
 
+    /// complete the IO as no IO structures are left in the bdev IO pool, the
+    /// bdev layer queues the IO and submits it again later
+    #[inline]
+    pub(crate) fn no_mem(&self) {
+        unsafe {
+            spdk_bdev_io_complete(self.0.as_ptr(), IoStatus::NoMemory.into())
+        }
+    }
+
     #[inline]
     pub(crate) fn complete(&mut self) {
         let pio_ctx = self.ctx_as_mut_ref();
@@ -315,8 +326,8 @@ impl Bio {
         self.complete();
     }
 
-    async fn child_retire(nexus: String, child: Bdev) {
-        error!("{:#?}", child);
+    pub(crate) async fn child_retire(nexus: String, child: Bdev) {
+        error!(target: RATE_LIMITED, "{}: IO error on child {}", nexus, child);
 
         if let Some(nexus) = nexus_lookup(&nexus) {
             if let Some(child) = nexus.child_lookup(&child.name()) {
@@ -334,6 +345,11 @@ impl Bio {
                     );
 
                     let uri = child.name.clone();
+                    if let Some(child) =
+                        nexus.children.iter_mut().find(|c| c.name == uri)
+                    {
+                        child.record_failure();
+                    }
                     nexus.pause().await.unwrap();
                     nexus.reconfigure(DREvent::ChildFault).await;
                     //nexus.remove_child(&uri).await.unwrap();
@@ -351,6 +367,10 @@ impl Bio {
                     if nexus.status() == NexusStatus::Faulted {
                         error!(":{} has no children left... ", nexus);
                     }
+
+                    // try to get remote children back in the background
+                    Reactors::master()
+                        .send_future(reconnect_child(nexus.name.clone(), uri));
                 }
             }
         } else {
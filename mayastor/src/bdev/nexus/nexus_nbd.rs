@@ -9,7 +9,7 @@ use std::{
     os::unix::io::AsRawFd,
     path::Path,
     sync::{atomic::AtomicBool, Arc},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures::channel::oneshot;
@@ -18,6 +18,9 @@ use snafu::{ResultExt, Snafu};
 
 use spdk_sys::{
     nbd_disk_find_by_nbd_path,
+    nbd_disk_first,
+    nbd_disk_get_bdev_name,
+    nbd_disk_next,
     spdk_nbd_disk,
     spdk_nbd_get_path,
     spdk_nbd_start,
@@ -34,7 +37,10 @@ const IOCTL_BLKGETSIZE: u32 = ior!(0x12, 114, std::mem::size_of::<u64>());
 const SET_TIMEOUT: u32 = io!(0xab, 9);
 const SET_SIZE: u32 = io!(0xab, 2);
 
-#[derive(Debug, Snafu)]
+/// how long to wait for SPDK to release a disconnected nbd device
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Snafu, Clone)]
 pub enum NbdError {
     #[snafu(display("No free NBD devices available (is NBD kmod loaded?)"))]
     Unavailable {},
@@ -268,9 +274,39 @@ impl NbdDisk {
             Reactors::current().poll_once();
         }
 
+        // the disk is only released by SPDK once the kernel has seen the
+        // disconnect, wait for it so the bdev is no longer open and the
+        // device can be reused when we return
+        let c_name = CString::new(name.clone()).unwrap();
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !unsafe { nbd_disk_find_by_nbd_path(c_name.as_ptr()) }.is_null()
+        {
+            if Instant::now() > deadline {
+                warn!("NBD device {} not released, continuing anyway", name);
+                break;
+            }
+            Reactors::current().poll_once();
+        }
+
         info!("NBD {} device stopped", name);
     }
 
+    /// Find the nbd disk the bdev is exported as, if any.
+    pub fn lookup(bdev_name: &str) -> Option<Self> {
+        let mut nbd_ptr = unsafe { nbd_disk_first() };
+        while !nbd_ptr.is_null() {
+            let name =
+                unsafe { CStr::from_ptr(nbd_disk_get_bdev_name(nbd_ptr)) };
+            if name.to_str() == Ok(bdev_name) {
+                return Some(Self {
+                    nbd_ptr,
+                });
+            }
+            nbd_ptr = unsafe { nbd_disk_next(nbd_ptr) };
+        }
+        None
+    }
+
     /// Get nbd device path (/dev/nbd...) for the nbd disk.
     pub fn get_path(&self) -> String {
         unsafe {
@@ -16,7 +16,8 @@ use crate::{
         },
         nexus_nbd::NbdDisk,
     },
-    core::{Protocol, Share},
+    core::{CoreError, Protocol, Share},
+    subsys::{NvmfSubsystem, Reservations},
 };
 
 #[async_trait(? Send)]
@@ -68,6 +69,24 @@ impl Share for Nexus {
         Ok(self.share_uri().unwrap())
     }
 
+    async fn share_nbd(&self) -> Result<Self::Output, Self::Error> {
+        match self.shared() {
+            Some(Protocol::Off) | None => {
+                NbdDisk::create(&self.name).await.context(ShareNbdNexus {
+                    name: self.name.clone(),
+                })?;
+            }
+            Some(Protocol::Nbd) => {}
+            Some(protocol) => {
+                warn!("nexus {} already shared as {}", self.name, protocol);
+                return Err(Error::AlreadyShared {
+                    name: self.name.clone(),
+                });
+            }
+        }
+        Ok(NbdDisk::lookup(&self.name).unwrap().get_path())
+    }
+
     async fn unshare(&self) -> Result<Self::Output, Self::Error> {
         self.bdev.unshare().await.context(UnshareNexus {
             name: self.name.clone(),
@@ -143,6 +162,87 @@ impl Nexus {
         }
     }
 
+    /// returns a boolean indicating if the nexus is shared with reservations
+    pub fn is_shared_with_reservations(&self) -> bool {
+        NvmfSubsystem::nqn_lookup(&self.bdev.name())
+            .map_or(false, |s| s.ptpl_file().is_some())
+    }
+
+    /// Share the nexus over nvmf like `share()`, with the reservations the
+    /// initiators make on it honored and persisted by the target.
+    pub async fn share_nvmf_with_reservations(
+        &mut self,
+    ) -> Result<String, Error> {
+        match self.nexus_target {
+            Some(NexusTarget::NexusNvmfTarget)
+                if self.is_shared_with_reservations() =>
+            {
+                return Ok(self.get_share_uri().unwrap());
+            }
+            Some(_) => {
+                return Err(Error::AlreadyShared {
+                    name: self.name.clone(),
+                });
+            }
+            None => {}
+        }
+
+        self.bdev.share_nvmf_with_reservations().await.context(
+            ShareNvmfNexus {
+                name: self.name.clone(),
+            },
+        )?;
+        self.nexus_target = Some(NexusTarget::NexusNvmfTarget);
+        Ok(self.get_share_uri().unwrap())
+    }
+
+    /// Share the nexus over nvmf like `share()`, under the given NQN prefix
+    /// rather than the default one.
+    pub async fn share_nvmf_with_prefix(
+        &mut self,
+        prefix: &str,
+    ) -> Result<String, Error> {
+        let nqn = format!("{}:{}", prefix, self.bdev.name());
+        match self.nexus_target {
+            Some(NexusTarget::NexusNvmfTarget)
+                if NvmfSubsystem::nqn_lookup(&self.bdev.name())
+                    .map_or(false, |s| s.get_nqn() == nqn) =>
+            {
+                return Ok(self.get_share_uri().unwrap());
+            }
+            Some(_) => {
+                return Err(Error::AlreadyShared {
+                    name: self.name.clone(),
+                });
+            }
+            None => {}
+        }
+
+        self.bdev.share_nvmf_with_prefix(prefix).await.context(
+            ShareNvmfNexus {
+                name: self.name.clone(),
+            },
+        )?;
+        self.nexus_target = Some(NexusTarget::NexusNvmfTarget);
+        Ok(self.get_share_uri().unwrap())
+    }
+
+    /// returns the reservation the initiators hold on the nexus shared with
+    /// reservations
+    pub fn reservations(&self) -> Result<Reservations, Error> {
+        let subsystem = NvmfSubsystem::nqn_lookup(&self.bdev.name())
+            .filter(|s| s.ptpl_file().is_some())
+            .ok_or_else(|| Error::NotShared {
+                name: self.name.clone(),
+            })?;
+        subsystem.reservations().map_err(|e| Error::ShareNvmfNexus {
+            source: CoreError::ShareNvmf {
+                source: e,
+            },
+            name: self.name.clone(),
+        })
+    }
+
     pub async fn unshare_nexus(&mut self) -> Result<(), Error> {
         match self.nexus_target.take() {
             Some(NexusTarget::NbdDisk(disk)) => {
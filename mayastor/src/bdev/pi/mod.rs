@@ -0,0 +1,14 @@
+//!
+//! T10 DIF protection information and software checksum bdev, see [pi_bdev]
+//! for how it works.
+
+pub use pi_bdev::{pi_lookup, PiBdev, PiFormat};
+
+pub(crate) mod pi_bdev;
+mod pi_fn_table;
+pub(crate) mod pi_module;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    pi_module::register_module()
+}
@@ -0,0 +1,1020 @@
+//!
+//! The PI bdev is a virtual bdev on top of a backing bdev, typically an lvol,
+//! which formats it with T10 DIF Type 1 protection information: every block
+//! carries 8 bytes of metadata interleaved with its data, holding a CRC16
+//! guard of the data and the LBA as reference tag. The backing bdev has no
+//! room for metadata, so the data of every block is stored in the block with
+//! the same LBA of the backing bdev, while the protection information of all
+//! blocks is packed into a region at its end.
+//!
+//! The protection information of a write is checked before anything is
+//! written, that of a read after the data has been read back, so corruption
+//! anywhere between the initiator and the backing device is detected. A
+//! mismatch fails the IO with the NVMe media error of the check that failed.
+//! Blocks that have never been written carry the escape application tag,
+//! which disables the checks. As the protection information of many blocks
+//! shares a block of the backing bdev, writes update it under a range lock.
+//!
+//! Where the initiator can not handle protection information, the checksum
+//! format keeps the block size of the backing bdev and generates the
+//! protection information in software instead: a checksum of the data and
+//! the LBA of every block are stored in the same region when it is written,
+//! and checked when it is read back. The checksum algorithm is selectable,
+//! the larger digests of the slower algorithms take more room in the region.
+
+use std::{
+    convert::TryFrom,
+    ffi::c_void,
+    fmt::{Debug, Display, Formatter},
+    ptr,
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use snafu::ResultExt;
+
+use spdk_sys::{
+    bdev_lock_lba_range,
+    bdev_unlock_lba_range,
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_complete_nvme_status,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_read_blocks,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_bdev_write_blocks,
+    spdk_dif_ctx,
+    spdk_dif_ctx_init,
+    spdk_dif_error,
+    spdk_dif_verify,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+    SPDK_DIF_APPTAG_ERROR,
+    SPDK_DIF_DISABLE,
+    SPDK_DIF_FLAGS_GUARD_CHECK,
+    SPDK_DIF_FLAGS_REFTAG_CHECK,
+    SPDK_DIF_GUARD_ERROR,
+    SPDK_DIF_TYPE1,
+    SPDK_NVME_SCT_MEDIA_ERROR,
+    SPDK_NVME_SC_APPLICATION_TAG_CHECK_ERROR,
+    SPDK_NVME_SC_GUARD_CHECK_ERROR,
+    SPDK_NVME_SC_REFERENCE_TAG_CHECK_ERROR,
+};
+
+use crate::{
+    bdev::{
+        nexus::nexus_io::{Bio, IoStatus},
+        pi::{
+            pi_fn_table::PiFnTable,
+            pi_module::{PiModule, PI_MODULE},
+        },
+        util::iov::{gather, scatter},
+    },
+    core::{
+        Bdev,
+        BdevHandle,
+        ChecksumAlgorithm,
+        CoreError,
+        Descriptor,
+        DmaBuf,
+    },
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const PI_PRODUCT_ID: &str = "PI Bdev";
+
+/// size of the T10 DIF protection information of a block in bytes
+pub const PI_SIZE: u32 = 8;
+
+/// size of the LBA stored with the checksum of a block in bytes
+const LBA_SIZE: usize = 4;
+
+/// protection information of a block which has never been written, the
+/// escape application tag disables its checks
+const UNWRITTEN_PI: [u8; PI_SIZE as usize] = [0, 0, 0xff, 0xff, 0, 0, 0, 0];
+
+/// how the protection information of the blocks is exposed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PiFormat {
+    /// T10 DIF Type 1 interleaved with the data, generated by the initiator
+    Dif,
+    /// checksum of the data and LBA of every block, generated by the PI bdev
+    Checksum(ChecksumAlgorithm),
+}
+
+impl PiFormat {
+    /// size of the protection information of a block in bytes, the size of
+    /// a block is a multiple of it
+    fn tuple_len(self) -> u32 {
+        match self {
+            Self::Dif => PI_SIZE,
+            Self::Checksum(ChecksumAlgorithm::Crc32c) => 8,
+            Self::Checksum(ChecksumAlgorithm::XxHash64) => 16,
+            // the digest is truncated to 28 bytes
+            Self::Checksum(ChecksumAlgorithm::Sha256) => 32,
+        }
+    }
+
+    /// the largest size of the protection information of a block of any
+    /// format
+    pub(crate) fn max_tuple_len() -> u32 {
+        Self::Checksum(ChecksumAlgorithm::Sha256).tuple_len()
+    }
+}
+
+impl FromStr for PiFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dif" => Ok(Self::Dif),
+            "crc" => Ok(Self::Checksum(ChecksumAlgorithm::Crc32c)),
+            _ => s.parse().map(Self::Checksum).map_err(|_| {
+                format!("unknown protection information format {}", s)
+            }),
+        }
+    }
+}
+
+impl Display for PiFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dif => write!(f, "dif"),
+            Self::Checksum(algorithm) => write!(f, "{}", algorithm),
+        }
+    }
+}
+
+/// outcome of an IO submitted to the PI bdev
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PiStatus {
+    Success,
+    Failed,
+    /// the protection information did not match, with the NVMe media error
+    /// status code of the check that failed
+    Mismatch(u32),
+}
+
+/// context of an IO submitted to the PI bdev
+#[derive(Debug)]
+pub struct PiIoCtx {
+    /// the data of the blocks without their protection information
+    data: Option<DmaBuf>,
+    /// the blocks of the backing bdev holding the protection information
+    pi: Option<DmaBuf>,
+    /// the protection information of the blocks written
+    tuples: Vec<u8>,
+    /// IOs to the backing bdev in flight
+    pending: u32,
+    status: PiStatus,
+}
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct PiChannel {
+    handle: *mut BdevHandle,
+}
+
+impl PiChannel {
+    /// allocates a handle to the backing bdev for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let pi = unsafe { PiBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut PiChannel) };
+
+        match pi.desc.as_ref().map(|d| BdevHandle::try_from(d.clone())) {
+            Some(Ok(handle)) => {
+                ch.handle = Box::into_raw(Box::new(handle));
+                0
+            }
+            _ => {
+                error!("{}: failed to create IO channel", pi.name);
+                ch.handle = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut PiChannel) };
+        if !ch.handle.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handle) };
+            ch.handle = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handle to the backing bdev of the given channel
+    pub(crate) fn handle<'a>(channel: *mut spdk_io_channel) -> &'a BdevHandle {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut PiChannel;
+            &*(*ctx).handle
+        }
+    }
+}
+
+pub struct PiBdev {
+    /// name of the PI bdev
+    pub name: String,
+    /// name of the bdev the PI bdev is on top of
+    pub backing: String,
+    /// the PI bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    /// descriptor of the backing bdev
+    desc: Option<Arc<Descriptor>>,
+    /// how the protection information is exposed
+    pub format: PiFormat,
+    /// size of the data of a block, the block size of the backing bdev
+    data_len: u32,
+    /// first block of the backing bdev holding protection information
+    pi_offset: u64,
+}
+
+impl Debug for PiBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (backing: {}, format: {}, blocks: {})",
+            self.name, self.backing, self.format, self.pi_offset
+        )
+    }
+}
+
+impl Drop for PiBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl PiBdev {
+    /// Create a PI bdev on top of the backing bdev and register it with
+    /// SPDK. It is somewhat smaller than the backing bdev, which also holds
+    /// the protection information.
+    pub(crate) async fn create(
+        name: &str,
+        backing: &str,
+        format: PiFormat,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+
+        let data_len = base.block_len();
+        let num_blocks =
+            Self::num_blocks(base.num_blocks(), data_len, format.tuple_len());
+        if num_blocks == 0 {
+            return Err(NexusBdevError::CreateBdev {
+                source: Errno::EINVAL,
+                name: name.to_string(),
+            });
+        }
+
+        let desc = base.open(true).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?;
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = PI_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = PiFnTable::table();
+        b.module = PI_MODULE.as_ptr();
+        b.blockcnt = num_blocks;
+        match format {
+            PiFormat::Dif => {
+                b.blocklen = data_len + PI_SIZE;
+                b.md_len = PI_SIZE;
+                b.md_interleave = true;
+                b.dif_type = SPDK_DIF_TYPE1;
+                b.dif_is_head_of_md = false;
+                b.dif_check_flags =
+                    SPDK_DIF_FLAGS_GUARD_CHECK | SPDK_DIF_FLAGS_REFTAG_CHECK;
+            }
+            PiFormat::Checksum(_) => {
+                b.blocklen = data_len;
+                b.dif_type = SPDK_DIF_DISABLE;
+            }
+        }
+        b.required_alignment = unsafe { (*base.as_ptr()).required_alignment };
+
+        let mut p = Box::new(PiBdev {
+            name: name.to_string(),
+            backing: backing.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            desc: Some(Arc::new(desc)),
+            format,
+            data_len,
+            pi_offset: num_blocks,
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*p.bdev.as_ptr()).ctxt = p.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                p.as_ptr(),
+                Some(PiChannel::create),
+                Some(PiChannel::destroy),
+                std::mem::size_of::<PiChannel>() as u32,
+                (*p.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(p.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(p.as_ptr(), None);
+            }
+            p.desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        info!("{}: created {:?}", name, p);
+        PiModule::get_instances().push(p);
+        Ok(name.to_string())
+    }
+
+    /// Unregister the PI bdev, which closes the backing bdev.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match pi_lookup(name) {
+            Some(pi) => pi.bdev.clone(),
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the PI bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the backing bdev
+        self.desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// the backing bdev of the PI bdev
+    pub(crate) fn backing_bdev(&self) -> Option<Bdev> {
+        self.desc.as_ref().map(|d| d.get_bdev())
+    }
+
+    /// The number of blocks of a PI bdev on top of a backing bdev with the
+    /// given number of blocks of data_len bytes. Every data_len / tuple_len
+    /// blocks of data need one block of protection information.
+    fn num_blocks(backing_blocks: u64, data_len: u32, tuple_len: u32) -> u64 {
+        let per_block = u64::from(data_len / tuple_len);
+        backing_blocks * per_block / (per_block + 1)
+    }
+
+    /// the number of blocks whose protection information fits in a block
+    fn tuples_per_block(&self) -> u64 {
+        u64::from(self.data_len / self.format.tuple_len())
+    }
+
+    /// the first and the number of blocks of the backing bdev holding the
+    /// protection information of the blocks of the IO
+    fn pi_blocks(&self, io: &Bio) -> (u64, u64) {
+        let per_block = self.tuples_per_block();
+        let first = io.offset() / per_block;
+        let last = (io.offset() + io.num_blocks() - 1) / per_block;
+        (self.pi_offset + first, last - first + 1)
+    }
+
+    /// byte offset of the protection information of the first block of the
+    /// IO within the blocks returned by pi_blocks()
+    fn pi_start(&self, io: &Bio) -> usize {
+        let per_block = self.tuples_per_block();
+        ((io.offset() % per_block) * u64::from(self.format.tuple_len()))
+            as usize
+    }
+
+    /// Check the protection information of the blocks of the IO against
+    /// their data and LBAs.
+    fn verify(&self, io: &Bio) -> Result<(), PiStatus> {
+        let mut ctx = spdk_dif_ctx::default();
+        let rc = unsafe {
+            spdk_dif_ctx_init(
+                &mut ctx,
+                self.data_len + PI_SIZE,
+                PI_SIZE,
+                true,
+                false,
+                SPDK_DIF_TYPE1,
+                SPDK_DIF_FLAGS_GUARD_CHECK | SPDK_DIF_FLAGS_REFTAG_CHECK,
+                io.offset() as u32,
+                0,
+                0,
+                0,
+                0,
+            )
+        };
+        if rc != 0 {
+            error!("{}: failed to initialize DIF context: {}", self.name, rc);
+            return Err(PiStatus::Failed);
+        }
+
+        let mut error = spdk_dif_error::default();
+        let rc = unsafe {
+            spdk_dif_verify(
+                io.iovs(),
+                io.iov_count(),
+                io.num_blocks() as u32,
+                &ctx,
+                &mut error,
+            )
+        };
+        if rc == 0 {
+            return Ok(());
+        }
+
+        warn!(
+            "{}: protection information check of {:?} failed at offset {}: \
+             expected {:x}, actual {:x}",
+            self.name,
+            io,
+            u64::from(error.err_offset) + io.offset(),
+            error.expected,
+            error.actual
+        );
+        Err(PiStatus::Mismatch(match u32::from(error.err_type) {
+            SPDK_DIF_GUARD_ERROR => SPDK_NVME_SC_GUARD_CHECK_ERROR,
+            SPDK_DIF_APPTAG_ERROR => SPDK_NVME_SC_APPLICATION_TAG_CHECK_ERROR,
+            _ => SPDK_NVME_SC_REFERENCE_TAG_CHECK_ERROR,
+        }))
+    }
+
+    /// The protection information of a block in the checksum format: the
+    /// digest of the data, truncated to what fits, followed by the LBA.
+    fn checksum_tuple(&self, block: &[u8], lba: u64) -> Vec<u8> {
+        let algorithm = match self.format {
+            PiFormat::Checksum(algorithm) => algorithm,
+            PiFormat::Dif => unreachable!("DIF is generated by the initiator"),
+        };
+        let tuple_len = self.format.tuple_len() as usize;
+        let mut tuple = algorithm.digest(block);
+        tuple.resize(tuple_len - LBA_SIZE, 0);
+        tuple.extend_from_slice(&(lba as u32).to_be_bytes());
+        tuple
+    }
+
+    /// Check the data of the blocks of the IO read from the backing bdev
+    /// against their protection information in the checksum format.
+    fn verify_checksum(
+        &self,
+        io: &Bio,
+        data: &[u8],
+        pi: &[u8],
+    ) -> Result<(), PiStatus> {
+        let tuple_len = self.format.tuple_len() as usize;
+        let blocks = data
+            .chunks(self.data_len as usize)
+            .zip(pi.chunks(tuple_len));
+        for (lba, (block, tuple)) in (io.offset() ..).zip(blocks) {
+            if tuple.iter().all(|b| *b == 0) {
+                continue;
+            }
+            let expected = self.checksum_tuple(block, lba);
+            if tuple == expected.as_slice() {
+                continue;
+            }
+            warn!(
+                "{}: {} checksum of {:?} does not match at offset {}: \
+                 expected {:x?}, actual {:x?}",
+                self.name, self.format, io, lba, expected, tuple
+            );
+            let digest_len = tuple_len - LBA_SIZE;
+            return Err(PiStatus::Mismatch(
+                if tuple[.. digest_len] != expected[.. digest_len] {
+                    SPDK_NVME_SC_GUARD_CHECK_ERROR
+                } else {
+                    SPDK_NVME_SC_REFERENCE_TAG_CHECK_ERROR
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut PiBdev)
+    }
+
+    /// obtain the PiBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), PI_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    fn ctx<'a>(io: &Bio) -> &'a mut PiIoCtx {
+        unsafe {
+            &mut *((*io.as_ptr()).driver_ctx.as_mut_ptr() as *mut PiIoCtx)
+        }
+    }
+
+    /// initialize the context of a newly submitted IO, the memory of the
+    /// context is not initialized by SPDK
+    fn ctx_init<'a>(io: &Bio) -> &'a mut PiIoCtx {
+        unsafe {
+            ptr::write(
+                (*io.as_ptr()).driver_ctx.as_mut_ptr() as *mut PiIoCtx,
+                PiIoCtx {
+                    data: None,
+                    pi: None,
+                    tuples: Vec::new(),
+                    pending: 0,
+                    status: PiStatus::Success,
+                },
+            );
+        }
+        Self::ctx(io)
+    }
+
+    /// allocate the buffers for the data and the protection information of
+    /// the IO
+    fn alloc_bufs(&self, io: &Bio) -> bool {
+        let ctx = Self::ctx(io);
+        let align = self.bdev.alignment();
+        let (_, pi_blocks) = self.pi_blocks(io);
+        match (
+            DmaBuf::new(io.num_blocks() * u64::from(self.data_len), align),
+            DmaBuf::new(pi_blocks * u64::from(self.data_len), align),
+        ) {
+            (Ok(data), Ok(pi)) => {
+                ctx.data = Some(data);
+                ctx.pi = Some(pi);
+                true
+            }
+            _ => {
+                error!(
+                    "{}: failed to allocate buffers for {:?}",
+                    self.name, io
+                );
+                false
+            }
+        }
+    }
+
+    /// complete an IO submitted to the PI bdev, freeing its buffers
+    fn complete(io: &Bio) {
+        let ctx = Self::ctx(io);
+        ctx.data.take();
+        ctx.pi.take();
+        ctx.tuples = Vec::new();
+
+        unsafe {
+            match ctx.status {
+                PiStatus::Success => spdk_bdev_io_complete(
+                    io.as_ptr(),
+                    IoStatus::Success.into(),
+                ),
+                PiStatus::Failed => io.fail(),
+                PiStatus::Mismatch(sc) => spdk_bdev_io_complete_nvme_status(
+                    io.as_ptr(),
+                    0,
+                    SPDK_NVME_SCT_MEDIA_ERROR as i32,
+                    sc as i32,
+                ),
+            }
+        }
+    }
+
+    /// account for an IO to the backing bdev which has been submitted,
+    /// returns false if it could not be
+    fn submitted(io: &Bio, rc: i32) -> bool {
+        let ctx = Self::ctx(io);
+        if rc == 0 {
+            ctx.pending += 1;
+            true
+        } else {
+            error!("Failed to submit IO to backing bdev for {:?}", io);
+            ctx.status = PiStatus::Failed;
+            false
+        }
+    }
+
+    /// account for a completed IO to the backing bdev, returns true if it
+    /// was the last one in flight
+    fn done(child_io: *mut spdk_bdev_io, success: bool, io: &Bio) -> bool {
+        Bio::from(child_io).free();
+        let ctx = Self::ctx(io);
+        if !success {
+            ctx.status = PiStatus::Failed;
+        }
+        ctx.pending -= 1;
+        ctx.pending == 0
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let pi = Self::from_io(&bio);
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", pi.name, bio);
+            bio.fail();
+            return;
+        }
+        pi.readv(&bio, PiChannel::handle(ch));
+    }
+
+    /// read the data and the protection information of the blocks from the
+    /// backing bdev
+    pub(crate) fn readv(&self, io: &Bio, handle: &BdevHandle) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * io.block_len(),
+                )
+            }
+            return;
+        }
+
+        let ctx = Self::ctx_init(io);
+        if !self.alloc_bufs(io) {
+            ctx.status = PiStatus::Failed;
+            Self::complete(io);
+            return;
+        }
+
+        let (desc, ch) = handle.io_tuple();
+        let (pi_first, pi_blocks) = self.pi_blocks(io);
+        let arg = io.as_ptr() as *mut c_void;
+        let rc = unsafe {
+            spdk_bdev_read_blocks(
+                desc,
+                ch,
+                **ctx.data.as_ref().unwrap(),
+                io.offset(),
+                io.num_blocks(),
+                Some(Self::read_done),
+                arg,
+            )
+        };
+        if Self::submitted(io, rc) {
+            let rc = unsafe {
+                spdk_bdev_read_blocks(
+                    desc,
+                    ch,
+                    **ctx.pi.as_ref().unwrap(),
+                    pi_first,
+                    pi_blocks,
+                    Some(Self::read_done),
+                    arg,
+                )
+            };
+            Self::submitted(io, rc);
+        }
+
+        if ctx.pending == 0 {
+            Self::complete(io);
+        }
+    }
+
+    /// completion of a read of the backing bdev
+    extern "C" fn read_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let io = Bio::from(parent_io);
+        if !Self::done(child_io, success, &io) {
+            return;
+        }
+
+        let pi = Self::from_io(&io);
+        let ctx = Self::ctx(&io);
+        if ctx.status == PiStatus::Success {
+            let result = match pi.format {
+                PiFormat::Dif => {
+                    pi.interleave(&io);
+                    pi.verify(&io)
+                }
+                PiFormat::Checksum(_) => {
+                    let data = ctx.data.as_ref().unwrap().as_slice();
+                    let tuples = &ctx.pi.as_ref().unwrap().as_slice()
+                        [pi.pi_start(&io) ..];
+                    pi.verify_checksum(&io, data, tuples)
+                        .map(|_| scatter(&io, data))
+                }
+            };
+            if let Err(status) = result {
+                ctx.status = status;
+            }
+        }
+        Self::complete(&io);
+    }
+
+    /// copy the data and the protection information read into the buffers
+    /// of the IO
+    fn interleave(&self, io: &Bio) {
+        let ctx = Self::ctx(io);
+        let data = ctx.data.as_ref().unwrap().as_slice();
+        let pi = &ctx.pi.as_ref().unwrap().as_slice()[self.pi_start(io) ..];
+        let data_len = self.data_len as usize;
+        let pi_size = PI_SIZE as usize;
+
+        let mut buf = Vec::with_capacity(
+            io.num_blocks() as usize * (data_len + pi_size),
+        );
+        for (block, tuple) in data.chunks(data_len).zip(pi.chunks(pi_size)) {
+            buf.extend_from_slice(block);
+            if tuple.iter().all(|b| *b == 0) {
+                buf.extend_from_slice(&UNWRITTEN_PI);
+            } else {
+                buf.extend_from_slice(tuple);
+            }
+        }
+        scatter(io, &buf);
+    }
+
+    /// Write the data and the protection information of the blocks, the
+    /// blocks holding the protection information are locked and read first
+    /// as they are shared with other blocks.
+    pub(crate) fn writev(&self, io: &Bio, handle: &BdevHandle) {
+        let ctx = Self::ctx_init(io);
+        if self.format == PiFormat::Dif {
+            if let Err(status) = self.verify(io) {
+                ctx.status = status;
+                Self::complete(io);
+                return;
+            }
+        }
+
+        if !self.alloc_bufs(io) {
+            ctx.status = PiStatus::Failed;
+            Self::complete(io);
+            return;
+        }
+
+        // split the blocks into their data and protection information, which
+        // is generated here for the checksum format
+        let data_len = self.data_len as usize;
+        let block_len = match self.format {
+            PiFormat::Dif => data_len + PI_SIZE as usize,
+            PiFormat::Checksum(_) => data_len,
+        };
+        let buf = gather(io, io.num_blocks() as usize * block_len);
+        let data = ctx.data.as_mut().unwrap().as_mut_slice();
+        ctx.tuples = Vec::with_capacity(
+            io.num_blocks() as usize * self.format.tuple_len() as usize,
+        );
+        for (i, block) in buf.chunks(block_len).enumerate() {
+            data[i * data_len .. (i + 1) * data_len]
+                .copy_from_slice(&block[.. data_len]);
+            match self.format {
+                PiFormat::Dif => {
+                    ctx.tuples.extend_from_slice(&block[data_len ..])
+                }
+                PiFormat::Checksum(_) => ctx.tuples.extend_from_slice(
+                    &self.checksum_tuple(block, io.offset() + i as u64),
+                ),
+            }
+        }
+
+        let (desc, ch) = handle.io_tuple();
+        let (pi_first, pi_blocks) = self.pi_blocks(io);
+        let rc = unsafe {
+            bdev_lock_lba_range(
+                desc,
+                ch,
+                pi_first,
+                pi_blocks,
+                Some(Self::write_locked),
+                io.as_ptr() as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            error!("{}: failed to lock range for {:?}", self.name, io);
+            ctx.status = PiStatus::Failed;
+            Self::complete(io);
+        }
+    }
+
+    /// the blocks with the protection information of a write are locked,
+    /// read them so that they can be updated
+    extern "C" fn write_locked(arg: *mut c_void, status: i32) {
+        let io = Bio::from(arg);
+        let pi = Self::from_io(&io);
+        let ctx = Self::ctx(&io);
+        if status != 0 {
+            error!("{}: failed to lock range for {:?}", pi.name, io);
+            ctx.status = PiStatus::Failed;
+            Self::complete(&io);
+            return;
+        }
+
+        let (desc, ch) = PiChannel::handle(io.io_channel()).io_tuple();
+        let (pi_first, pi_blocks) = pi.pi_blocks(&io);
+        // the IO must be submitted with the context of the lock to pass it
+        let rc = unsafe {
+            spdk_bdev_read_blocks(
+                desc,
+                ch,
+                **ctx.pi.as_ref().unwrap(),
+                pi_first,
+                pi_blocks,
+                Some(Self::write_pi_read),
+                arg,
+            )
+        };
+        if !Self::submitted(&io, rc) {
+            pi.unlock(&io);
+        }
+    }
+
+    /// update the protection information read and write it along with the
+    /// data
+    extern "C" fn write_pi_read(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let io = Bio::from(parent_io);
+        let pi = Self::from_io(&io);
+        let ctx = Self::ctx(&io);
+        Self::done(child_io, success, &io);
+        if ctx.status != PiStatus::Success {
+            pi.unlock(&io);
+            return;
+        }
+
+        let start = pi.pi_start(&io);
+        let end = start + ctx.tuples.len();
+        ctx.pi.as_mut().unwrap().as_mut_slice()[start .. end]
+            .copy_from_slice(&ctx.tuples);
+
+        let (desc, ch) = PiChannel::handle(io.io_channel()).io_tuple();
+        let (pi_first, pi_blocks) = pi.pi_blocks(&io);
+        let rc = unsafe {
+            spdk_bdev_write_blocks(
+                desc,
+                ch,
+                **ctx.data.as_ref().unwrap(),
+                io.offset(),
+                io.num_blocks(),
+                Some(Self::write_done),
+                parent_io,
+            )
+        };
+        if Self::submitted(&io, rc) {
+            let rc = unsafe {
+                spdk_bdev_write_blocks(
+                    desc,
+                    ch,
+                    **ctx.pi.as_ref().unwrap(),
+                    pi_first,
+                    pi_blocks,
+                    Some(Self::write_done),
+                    parent_io,
+                )
+            };
+            Self::submitted(&io, rc);
+        }
+
+        if ctx.pending == 0 {
+            pi.unlock(&io);
+        }
+    }
+
+    /// completion of a write of the backing bdev
+    extern "C" fn write_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        let io = Bio::from(parent_io);
+        if Self::done(child_io, success, &io) {
+            Self::from_io(&io).unlock(&io);
+        }
+    }
+
+    /// release the lock of the blocks holding the protection information of
+    /// a write
+    fn unlock(&self, io: &Bio) {
+        let (desc, ch) = PiChannel::handle(io.io_channel()).io_tuple();
+        let (pi_first, pi_blocks) = self.pi_blocks(io);
+        let rc = unsafe {
+            bdev_unlock_lba_range(
+                desc,
+                ch,
+                pi_first,
+                pi_blocks,
+                Some(Self::write_unlocked),
+                io.as_ptr() as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            error!("{}: failed to unlock range for {:?}", self.name, io);
+            Self::complete(io);
+        }
+    }
+
+    extern "C" fn write_unlocked(arg: *mut c_void, status: i32) {
+        let io = Bio::from(arg);
+        if status != 0 {
+            error!("failed to unlock range for {:?}", io);
+        }
+        Self::complete(&io);
+    }
+
+    /// completion of any other IO passed on to the backing bdev
+    pub(crate) extern "C" fn io_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        Bio::from(child_io).free();
+        let status = if success {
+            IoStatus::Success
+        } else {
+            IoStatus::Failed
+        };
+        unsafe { spdk_bdev_io_complete(parent_io as *mut _, status.into()) }
+    }
+}
+
+/// Lookup a PI bdev by its name.
+pub fn pi_lookup(name: &str) -> Option<&mut PiBdev> {
+    PiModule::get_instances()
+        .iter_mut()
+        .find(|p| p.name == name)
+        .map(|p| p.as_mut())
+}
+
+/// Unregister the PI bdevs on top of the given bdev which is being removed.
+pub(crate) fn backing_removed(backing: &str) {
+    for pi in PiModule::get_instances()
+        .iter()
+        .filter(|p| p.backing == backing)
+    {
+        info!("{}: backing bdev {} removed", pi.name, backing);
+        unsafe {
+            spdk_bdev_unregister(pi.bdev.as_ptr(), None, std::ptr::null_mut());
+        }
+    }
+}
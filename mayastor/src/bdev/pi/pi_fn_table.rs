@@ -0,0 +1,164 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_flush_blocks,
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_bdev_reset,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    nexus::nexus_io::{Bio, IoType},
+    pi::{
+        pi_bdev::{PiBdev, PiChannel},
+        pi_module::PiModule,
+    },
+};
+
+static PI_FN_TBL: Lazy<PiFnTable> = Lazy::new(PiFnTable::new);
+
+pub struct PiFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for PiFnTable {}
+unsafe impl Send for PiFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl PiFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        PiFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &PI_FN_TBL.f_tbl
+    }
+
+    /// reads and writes are always supported, flushes and resets if the
+    /// backing bdev supports them. Unmaps and write zeroes are not supported
+    /// as they would leave the protection information behind.
+    extern "C" fn io_supported(
+        ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        let pi = unsafe { PiBdev::from_raw(ctx) };
+        let io_type = IoType::from(io_type);
+        match io_type {
+            IoType::Read | IoType::Write => true,
+            IoType::Flush | IoType::Reset => pi
+                .backing_bdev()
+                .map_or(false, |b| b.io_type_supported(io_type)),
+            _ => false,
+        }
+    }
+
+    /// Submit an IO to the PI bdev, reads and writes are split into their
+    /// data and protection information.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let pi = PiBdev::from_io(&bio);
+        let handle = PiChannel::handle(channel);
+        let (desc, ch) = handle.io_tuple();
+        let arg = io as *mut c_void;
+
+        let rc = match bio.io_type() {
+            IoType::Read => {
+                pi.readv(&bio, handle);
+                return;
+            }
+            IoType::Write => {
+                pi.writev(&bio, handle);
+                return;
+            }
+            IoType::Flush => unsafe {
+                spdk_bdev_flush_blocks(
+                    desc,
+                    ch,
+                    0,
+                    pi.backing_bdev().map_or(0, |b| b.num_blocks()),
+                    Some(PiBdev::io_done),
+                    arg,
+                )
+            },
+            IoType::Reset => unsafe {
+                spdk_bdev_reset(desc, ch, Some(PiBdev::io_done), arg)
+            },
+            io_type => {
+                error!("{}: unsupported IO type {:?}", pi.name, io_type);
+                bio.fail();
+                return;
+            }
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit IO {:?}", pi.name, bio);
+            bio.fail();
+        }
+    }
+
+    /// called per core to create IO channels per PI instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the PI bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let pi = unsafe { PiBdev::from_raw(ctx) };
+        pi.destruct();
+        let name = pi.name.clone();
+        // removing the PI bdev from the list should cause a drop
+        PiModule::get_instances().retain(|p| p.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let pi = unsafe { PiBdev::from_raw(ctx) };
+        let json = serde_json::json!({
+            "backing": pi.backing,
+            "format": pi.format.to_string(),
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "pi\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
@@ -0,0 +1,94 @@
+use std::{cell::UnsafeCell, ffi::CString};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{
+    bdev::pi::pi_bdev::{PiBdev, PiIoCtx},
+    ffihelper::IntoCString,
+};
+
+pub const PI_MODULE_NAME: &str = "pi";
+
+pub static PI_MODULE: Lazy<PiModule> = Lazy::new(PiModule::new);
+
+#[derive(Default, Debug)]
+pub struct PiInstances {
+    inner: UnsafeCell<Vec<Box<PiBdev>>>,
+}
+
+#[derive(Debug)]
+pub struct PiModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for PiModule {}
+unsafe impl Sync for PiInstances {}
+
+unsafe impl Send for PiModule {}
+unsafe impl Send for PiInstances {}
+
+impl PiModule {
+    /// construct a new PiModule instance and setup the main properties,
+    /// PI bdevs are only created explicitly so there is nothing to examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = PI_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::pi_mod_init);
+        module.module_fini = Some(Self::pi_mod_fini);
+        module.get_ctx_size = Some(Self::pi_ctx_size);
+        module.examine_config = None;
+        module.examine_disk = None;
+        PiModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<PiBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static PI_INSTANCES: OnceCell<PiInstances> = OnceCell::new();
+
+        let global_instances = PI_INSTANCES.get_or_init(|| PiInstances {
+            inner: UnsafeCell::new(Vec::new()),
+        });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    extern "C" fn pi_mod_init() -> i32 {
+        info!("Initializing PI Module");
+        0
+    }
+
+    extern "C" fn pi_mod_fini() {
+        info!("Unloading PI Module");
+        let _ = unsafe { CString::from_raw((*(PI_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+    }
+
+    extern "C" fn pi_ctx_size() -> i32 {
+        std::mem::size_of::<PiIoCtx>() as i32
+    }
+}
+
+impl Default for PiModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((PI_MODULE.0) as *const _ as *mut _);
+    }
+}
@@ -0,0 +1,13 @@
+//!
+//! Read-only bdev, see [readonly_bdev] for how it works.
+
+pub use readonly_bdev::{readonly_lookup, ReadOnlyBdev};
+
+pub(crate) mod readonly_bdev;
+mod readonly_fn_table;
+pub(crate) mod readonly_module;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    readonly_module::register_module()
+}
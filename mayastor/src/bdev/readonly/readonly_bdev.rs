@@ -0,0 +1,361 @@
+//!
+//! The read-only bdev is a virtual bdev on top of a backing bdev which it
+//! opens read-only. Reads, flushes and resets are passed on to the backing
+//! bdev, writes are failed with the Namespace is Write Protected status, so
+//! that initiators of a read-only share can not change the data behind it.
+//! Unmaps and write zeroes are not supported at all.
+
+use std::{
+    convert::TryFrom,
+    ffi::c_void,
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_complete_nvme_status,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+    SPDK_NVME_SCT_GENERIC,
+    SPDK_NVME_SC_NAMESPACE_IS_WRITE_PROTECTED,
+};
+
+use crate::{
+    bdev::{
+        nexus::nexus_io::{Bio, IoStatus},
+        readonly::{
+            readonly_fn_table::ReadOnlyFnTable,
+            readonly_module::{ReadOnlyModule, READONLY_MODULE},
+        },
+    },
+    core::{Bdev, BdevHandle, CoreError, Descriptor},
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const READONLY_PRODUCT_ID: &str = "Read-only Bdev";
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct ReadOnlyChannel {
+    handle: *mut BdevHandle,
+}
+
+impl ReadOnlyChannel {
+    /// allocates a handle to the backing bdev for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let ro = unsafe { ReadOnlyBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut ReadOnlyChannel) };
+
+        match ro.desc.as_ref().map(|d| BdevHandle::try_from(d.clone())) {
+            Some(Ok(handle)) => {
+                ch.handle = Box::into_raw(Box::new(handle));
+                0
+            }
+            _ => {
+                error!("{}: failed to create IO channel", ro.name);
+                ch.handle = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut ReadOnlyChannel) };
+        if !ch.handle.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handle) };
+            ch.handle = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handle to the backing bdev of the given channel
+    pub(crate) fn handle<'a>(channel: *mut spdk_io_channel) -> &'a BdevHandle {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut ReadOnlyChannel;
+            &*(*ctx).handle
+        }
+    }
+}
+
+pub struct ReadOnlyBdev {
+    /// name of the read-only bdev
+    pub name: String,
+    /// name of the bdev the read-only bdev is on top of
+    pub backing: String,
+    /// the read-only bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    /// read-only descriptor of the backing bdev
+    desc: Option<Arc<Descriptor>>,
+}
+
+impl Debug for ReadOnlyBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (backing: {})", self.name, self.backing)
+    }
+}
+
+impl Drop for ReadOnlyBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl ReadOnlyBdev {
+    /// Create a read-only bdev on top of the backing bdev and register it
+    /// with SPDK.
+    pub(crate) async fn create(
+        name: &str,
+        backing: &str,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let base = Bdev::lookup_by_name(backing).ok_or_else(|| {
+            NexusBdevError::BdevNotFound {
+                name: backing.to_string(),
+            }
+        })?;
+
+        let desc = base.open(false).map_err(|error| {
+            let source = match error {
+                CoreError::OpenBdev {
+                    source,
+                } => source,
+                _ => Errno::EINVAL,
+            };
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        })?;
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = READONLY_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = ReadOnlyFnTable::table();
+        b.module = READONLY_MODULE.as_ptr();
+        b.blocklen = base.block_len();
+        b.blockcnt = base.num_blocks();
+        b.required_alignment = unsafe { (*base.as_ptr()).required_alignment };
+
+        let mut ro = Box::new(ReadOnlyBdev {
+            name: name.to_string(),
+            backing: backing.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            desc: Some(Arc::new(desc)),
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*ro.bdev.as_ptr()).ctxt = ro.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                ro.as_ptr(),
+                Some(ReadOnlyChannel::create),
+                Some(ReadOnlyChannel::destroy),
+                std::mem::size_of::<ReadOnlyChannel>() as u32,
+                (*ro.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(ro.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(ro.as_ptr(), None);
+            }
+            ro.desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        info!("{}: created {:?}", name, ro);
+        ReadOnlyModule::get_instances().push(ro);
+        Ok(name.to_string())
+    }
+
+    /// Unregister the read-only bdev, which closes the backing bdev.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match readonly_lookup(name) {
+            Some(ro) => ro.bdev.clone(),
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the read-only bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the backing bdev
+        self.desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// the backing bdev of the read-only bdev
+    pub(crate) fn backing_bdev(&self) -> Option<Bdev> {
+        self.desc.as_ref().map(|d| d.get_bdev())
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut ReadOnlyBdev)
+    }
+
+    /// obtain the ReadOnlyBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), READONLY_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    /// fail an IO which would change the data of the backing bdev
+    pub(crate) fn write_protected(io: &Bio) {
+        unsafe {
+            spdk_bdev_io_complete_nvme_status(
+                io.as_ptr(),
+                0,
+                SPDK_NVME_SCT_GENERIC as i32,
+                SPDK_NVME_SC_NAMESPACE_IS_WRITE_PROTECTED as i32,
+            )
+        }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let ro = Self::from_io(&bio);
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", ro.name, bio);
+            bio.fail();
+            return;
+        }
+        ro.readv(&bio, ReadOnlyChannel::handle(ch));
+    }
+
+    /// pass a read on to the backing bdev
+    pub(crate) fn readv(&self, io: &Bio, handle: &BdevHandle) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * io.block_len(),
+                )
+            }
+            return;
+        }
+
+        let (desc, ch) = handle.io_tuple();
+        let rc = unsafe {
+            spdk_sys::spdk_bdev_readv_blocks(
+                desc,
+                ch,
+                io.iovs(),
+                io.iov_count(),
+                io.offset(),
+                io.num_blocks(),
+                Some(Self::io_done),
+                io.as_ptr() as *mut c_void,
+            )
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit read {:?}", self.name, io);
+            io.fail();
+        }
+    }
+
+    /// completion of an IO passed on to the backing bdev
+    pub(crate) extern "C" fn io_done(
+        child_io: *mut spdk_bdev_io,
+        success: bool,
+        parent_io: *mut c_void,
+    ) {
+        Bio::from(child_io).free();
+        let status = if success {
+            IoStatus::Success
+        } else {
+            IoStatus::Failed
+        };
+        unsafe { spdk_bdev_io_complete(parent_io as *mut _, status.into()) }
+    }
+}
+
+/// Lookup a read-only bdev by its name.
+pub fn readonly_lookup(name: &str) -> Option<&mut ReadOnlyBdev> {
+    ReadOnlyModule::get_instances()
+        .iter_mut()
+        .find(|r| r.name == name)
+        .map(|r| r.as_mut())
+}
+
+/// Unregister the read-only bdevs on top of the given bdev which is being
+/// removed.
+pub(crate) fn backing_removed(backing: &str) {
+    for ro in ReadOnlyModule::get_instances()
+        .iter()
+        .filter(|r| r.backing == backing)
+    {
+        info!("{}: backing bdev {} removed", ro.name, backing);
+        unsafe {
+            spdk_bdev_unregister(ro.bdev.as_ptr(), None, std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_flush_blocks,
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_bdev_reset,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    nexus::nexus_io::{Bio, IoType},
+    readonly::{
+        readonly_bdev::{ReadOnlyBdev, ReadOnlyChannel},
+        readonly_module::ReadOnlyModule,
+    },
+};
+
+static READONLY_FN_TBL: Lazy<ReadOnlyFnTable> =
+    Lazy::new(ReadOnlyFnTable::new);
+
+pub struct ReadOnlyFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for ReadOnlyFnTable {}
+unsafe impl Send for ReadOnlyFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl ReadOnlyFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        ReadOnlyFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &READONLY_FN_TBL.f_tbl
+    }
+
+    /// Reads are always supported and so are writes, which are failed as
+    /// the namespace being write protected. Unmaps and write zeroes are not
+    /// supported, so they are not advertised to the initiators. Flushes and
+    /// resets are supported if the backing bdev supports them.
+    extern "C" fn io_supported(
+        ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        let ro = unsafe { ReadOnlyBdev::from_raw(ctx) };
+        let io_type = IoType::from(io_type);
+        match io_type {
+            IoType::Read | IoType::Write => true,
+            IoType::Flush | IoType::Reset => ro
+                .backing_bdev()
+                .map_or(false, |b| b.io_type_supported(io_type)),
+            _ => false,
+        }
+    }
+
+    /// Submit an IO to the read-only bdev, reads are passed on to the
+    /// backing bdev and writes are failed.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let ro = ReadOnlyBdev::from_io(&bio);
+        let handle = ReadOnlyChannel::handle(channel);
+        let (desc, ch) = handle.io_tuple();
+        let arg = io as *mut c_void;
+
+        let rc = match bio.io_type() {
+            IoType::Read => {
+                ro.readv(&bio, handle);
+                return;
+            }
+            IoType::Write => {
+                ReadOnlyBdev::write_protected(&bio);
+                return;
+            }
+            IoType::Flush => unsafe {
+                spdk_bdev_flush_blocks(
+                    desc,
+                    ch,
+                    bio.offset(),
+                    bio.num_blocks(),
+                    Some(ReadOnlyBdev::io_done),
+                    arg,
+                )
+            },
+            IoType::Reset => unsafe {
+                spdk_bdev_reset(desc, ch, Some(ReadOnlyBdev::io_done), arg)
+            },
+            io_type => {
+                error!("{}: unsupported IO type {:?}", ro.name, io_type);
+                bio.fail();
+                return;
+            }
+        };
+
+        if rc != 0 {
+            error!("{}: Failed to submit IO {:?}", ro.name, bio);
+            bio.fail();
+        }
+    }
+
+    /// called per core to create IO channels per read-only instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the read-only bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let ro = unsafe { ReadOnlyBdev::from_raw(ctx) };
+        ro.destruct();
+        let name = ro.name.clone();
+        // removing the bdev from the list should cause a drop
+        ReadOnlyModule::get_instances().retain(|r| r.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let ro = unsafe { ReadOnlyBdev::from_raw(ctx) };
+        let json = serde_json::json!({
+            "backing": ro.backing,
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "readonly\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
@@ -0,0 +1,94 @@
+use std::{cell::UnsafeCell, ffi::CString};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{
+    bdev::readonly::readonly_bdev::ReadOnlyBdev,
+    ffihelper::IntoCString,
+};
+
+pub const READONLY_MODULE_NAME: &str = "readonly";
+
+pub static READONLY_MODULE: Lazy<ReadOnlyModule> =
+    Lazy::new(ReadOnlyModule::new);
+
+#[derive(Default, Debug)]
+pub struct ReadOnlyInstances {
+    inner: UnsafeCell<Vec<Box<ReadOnlyBdev>>>,
+}
+
+#[derive(Debug)]
+pub struct ReadOnlyModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for ReadOnlyModule {}
+unsafe impl Sync for ReadOnlyInstances {}
+
+unsafe impl Send for ReadOnlyModule {}
+unsafe impl Send for ReadOnlyInstances {}
+
+impl ReadOnlyModule {
+    /// construct a new ReadOnlyModule instance and setup the main
+    /// properties, read-only bdevs are only created explicitly so there is
+    /// nothing to examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = READONLY_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::readonly_mod_init);
+        module.module_fini = Some(Self::readonly_mod_fini);
+        module.get_ctx_size = None;
+        module.examine_config = None;
+        module.examine_disk = None;
+        ReadOnlyModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<ReadOnlyBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static READONLY_INSTANCES: OnceCell<ReadOnlyInstances> =
+            OnceCell::new();
+
+        let global_instances =
+            READONLY_INSTANCES.get_or_init(|| ReadOnlyInstances {
+                inner: UnsafeCell::new(Vec::new()),
+            });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    extern "C" fn readonly_mod_init() -> i32 {
+        info!("Initializing Read-only Module");
+        0
+    }
+
+    extern "C" fn readonly_mod_fini() {
+        info!("Unloading Read-only Module");
+        let _ = unsafe { CString::from_raw((*(READONLY_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+    }
+}
+
+impl Default for ReadOnlyModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((READONLY_MODULE.0) as *const _ as *mut _);
+    }
+}
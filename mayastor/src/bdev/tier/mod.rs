@@ -0,0 +1,17 @@
+//!
+//! Tiered storage across a fast and a slow bdev, see [tier_bdev] for how the
+//! extents are laid out and migrated and [tier_map] for the policy deciding
+//! which extents are kept on the fast tier.
+
+pub use tier_bdev::{tier_lookup, TierBdev};
+pub use tier_map::{TierPolicy, TierStats};
+
+pub(crate) mod tier_bdev;
+mod tier_fn_table;
+pub(crate) mod tier_map;
+pub(crate) mod tier_module;
+
+/// public function which simply calls register module
+pub fn register_module() {
+    tier_module::register_module()
+}
@@ -0,0 +1,1052 @@
+//!
+//! The tier bdev is a virtual bdev on top of a fast and a slow bdev, of which
+//! it has the size of the slow one. Its blocks are grouped in extents, whose
+//! home is the slow bdev, and the extents which are read often are promoted
+//! to slots of the fast bdev, from where they are read and written until
+//! they are demoted again to make room for others.
+//!
+//! The fast bdev holds a header block, followed by a table with an entry of
+//! SLOT_ENTRY_LEN bytes for every slot, which is the extent it holds plus
+//! one, or zero if it is free, and then the slots. A fast bdev of zeroes is
+//! a new table.
+//!
+//! Extents are migrated by a worker per tier bdev, paced as background IO,
+//! while their range of the tier bdev is locked against writes. A promoted
+//! extent is copied and flushed before its entry is written, and a demoted
+//! one is copied back and flushed before its entry is cleared, so the table
+//! always points to a copy of the data if migration is interrupted.
+
+use std::{
+    cmp::min,
+    convert::TryFrom,
+    ffi::c_void,
+    fmt::{Debug, Display, Formatter},
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use futures_timer::Delay;
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use spdk_sys::{
+    spdk_bdev,
+    spdk_bdev_io,
+    spdk_bdev_io_complete,
+    spdk_bdev_io_get_buf,
+    spdk_bdev_register,
+    spdk_bdev_unregister,
+    spdk_io_channel,
+    spdk_io_device_register,
+    spdk_io_device_unregister,
+};
+
+use crate::{
+    bdev::{
+        nexus::nexus_io::{Bio, IoStatus},
+        tier::{
+            tier_fn_table::TierFnTable,
+            tier_map::{TierMap, TierPolicy, TierStats, SLOT_ENTRY_LEN},
+            tier_module::{TierModule, TIER_MODULE},
+        },
+        util::{
+            block::{decode_block, encode_block},
+            iov::{gather, scatter},
+        },
+    },
+    core::{
+        BackgroundClass,
+        BackgroundScheduler,
+        Bdev,
+        BdevHandle,
+        CoreError,
+        Descriptor,
+        DmaBuf,
+        IoChannel,
+        RangeContext,
+        Reactors,
+    },
+    ffihelper::{cb_arg, done_errno_cb, errno_result_from_i32, IntoCString},
+    nexus_uri::{self, NexusBdevError},
+};
+
+pub const TIER_PRODUCT_ID: &str = "Tier Bdev";
+
+/// the header is in the first block, the table follows it
+const TABLE_START: u64 = 1;
+
+/// the table is read in pieces of at most this many bytes
+const TABLE_READ_LEN: u64 = 1 << 20;
+
+/// magic of the header, "MTIR"
+const TIER_MAGIC: u32 = 0x4d54_4952;
+
+/// the header of the table, in the first block of the fast bdev
+#[derive(Debug, Serialize, Deserialize)]
+struct TierHeader {
+    magic: u32,
+    block_len: u32,
+    extent_len: u64,
+    slots: u64,
+    extents: u64,
+}
+
+/// why an IO to a tier bdev or the migration of an extent failed
+#[derive(Debug)]
+pub(crate) enum TierError {
+    Io(CoreError),
+    Lock(Errno),
+    NoMemory,
+    NoSlot,
+}
+
+impl Display for TierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Lock(errno) => write!(f, "failed to lock range: {}", errno),
+            Self::NoMemory => write!(f, "failed to allocate a buffer"),
+            Self::NoSlot => write!(f, "no free slot on the fast tier"),
+        }
+    }
+}
+
+impl From<CoreError> for TierError {
+    fn from(error: CoreError) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// handles to the fast and the slow bdev, per core
+pub(crate) struct TierHandles {
+    fast: BdevHandle,
+    slow: BdevHandle,
+}
+
+/// io channel, per core
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct TierChannel {
+    handles: *mut TierHandles,
+}
+
+impl TierChannel {
+    /// allocates the handles to the fast and the slow bdev for the channel
+    extern "C" fn create(device: *mut c_void, ctx: *mut c_void) -> i32 {
+        let tier = unsafe { TierBdev::from_raw(device) };
+        let ch = unsafe { &mut *(ctx as *mut TierChannel) };
+
+        match tier.handles() {
+            Ok(handles) => {
+                ch.handles = Box::into_raw(Box::new(handles));
+                0
+            }
+            Err(_) => {
+                error!("{}: failed to create IO channel", tier.name);
+                ch.handles = std::ptr::null_mut();
+                -(Errno::ENOMEM as i32)
+            }
+        }
+    }
+
+    /// function called on io channel destruction
+    extern "C" fn destroy(_device: *mut c_void, ctx: *mut c_void) {
+        let ch = unsafe { &mut *(ctx as *mut TierChannel) };
+        if !ch.handles.is_null() {
+            let _ = unsafe { Box::from_raw(ch.handles) };
+            ch.handles = std::ptr::null_mut();
+        }
+    }
+
+    /// get the handles of the given channel
+    pub(crate) fn handles<'a>(
+        channel: *mut spdk_io_channel,
+    ) -> &'a TierHandles {
+        unsafe {
+            let ctx = (channel as *mut u8)
+                .add(std::mem::size_of::<spdk_io_channel>())
+                as *mut TierChannel;
+            &*(*ctx).handles
+        }
+    }
+}
+
+pub struct TierBdev {
+    /// name of the tier bdev
+    pub name: String,
+    /// name of the fast bdev
+    pub fast: String,
+    /// name of the slow bdev
+    pub slow: String,
+    /// the tier bdev itself
+    pub(crate) bdev: Bdev,
+    bdev_raw: *mut spdk_bdev,
+    fast_desc: Option<Arc<Descriptor>>,
+    slow_desc: Option<Arc<Descriptor>>,
+    /// when extents are migrated
+    pub policy: TierPolicy,
+    /// block length of the fast and the slow bdev
+    block_len: u64,
+    /// number of blocks of an extent
+    extent_blocks: u64,
+    /// first block of the slots on the fast bdev
+    slots_start: u64,
+    map: Mutex<TierMap>,
+    /// extents to promote, taken by the migration worker, None once it has
+    /// been told to stop
+    queue: Mutex<Option<UnboundedSender<u64>>>,
+}
+
+impl Debug for TierBdev {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (fast: {}, slow: {}, {})",
+            self.name, self.fast, self.slow, self.policy
+        )
+    }
+}
+
+impl Drop for TierBdev {
+    fn drop(&mut self) {
+        unsafe {
+            let b: Box<spdk_bdev> = Box::from_raw(self.bdev_raw);
+            let _ = std::ffi::CString::from_raw(b.name);
+            let _ = std::ffi::CString::from_raw(b.product_name);
+        }
+    }
+}
+
+impl TierBdev {
+    /// the number of blocks of the table for the given number of slots
+    fn table_blocks(slots: u64, block_len: u64) -> u64 {
+        (slots * SLOT_ENTRY_LEN + block_len - 1) / block_len
+    }
+
+    /// the number of slots of extents of the given length that fit on a
+    /// fast bdev of the given number of blocks, along with their table
+    fn capacity(fast_blocks: u64, block_len: u64, extent_blocks: u64) -> u64 {
+        let needed = |slots: u64| {
+            TABLE_START
+                + Self::table_blocks(slots, block_len)
+                + slots * extent_blocks
+        };
+        let mut slots = fast_blocks.saturating_sub(TABLE_START) * block_len
+            / (SLOT_ENTRY_LEN + extent_blocks * block_len);
+        while slots > 0 && needed(slots) > fast_blocks {
+            slots -= 1;
+        }
+        slots
+    }
+
+    /// Read the header and the table of the fast bdev, None if it has never
+    /// been set up.
+    async fn read_table(
+        handle: &BdevHandle,
+        block_len: u64,
+    ) -> Result<Option<(TierHeader, Vec<u8>)>, String> {
+        let nomem = |_| "failed to allocate a buffer".to_string();
+        let mut buf = handle.dma_malloc(block_len).map_err(nomem)?;
+        handle
+            .read_at(0, &mut buf)
+            .await
+            .map_err(|e| e.to_string())?;
+        if buf.as_slice().iter().all(|b| *b == 0) {
+            return Ok(None);
+        }
+
+        let header = match decode_block::<TierHeader>(buf.as_slice()) {
+            Some(header)
+                if header.magic == TIER_MAGIC
+                    && u64::from(header.block_len) == block_len =>
+            {
+                header
+            }
+            _ => return Err("the bdev does not hold a tier table".into()),
+        };
+
+        let table_blocks = Self::table_blocks(header.slots, block_len);
+        let mut table = Vec::with_capacity((table_blocks * block_len) as usize);
+        let mut block = TABLE_START;
+        while block < TABLE_START + table_blocks {
+            let count = min(
+                TABLE_START + table_blocks - block,
+                TABLE_READ_LEN / block_len,
+            );
+            let mut buf =
+                handle.dma_malloc(count * block_len).map_err(nomem)?;
+            handle
+                .read_at(block * block_len, &mut buf)
+                .await
+                .map_err(|e| e.to_string())?;
+            table.extend_from_slice(buf.as_slice());
+            block += count;
+        }
+        Ok(Some((header, table)))
+    }
+
+    /// Create a tier bdev on top of the fast and the slow bdev, with extents
+    /// of the given length, and register it with SPDK. A fast bdev of zeroes
+    /// is set up as a new table, otherwise the extents in its table are
+    /// taken to be promoted.
+    pub(crate) async fn create(
+        name: &str,
+        fast: &str,
+        slow: &str,
+        extent_len: u64,
+        policy: TierPolicy,
+    ) -> Result<String, NexusBdevError> {
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(NexusBdevError::BdevExists {
+                name: name.to_string(),
+            });
+        }
+
+        let lookup = |backing: &str| {
+            Bdev::lookup_by_name(backing).ok_or_else(|| {
+                NexusBdevError::BdevNotFound {
+                    name: backing.to_string(),
+                }
+            })
+        };
+        let fast_bdev = lookup(fast)?;
+        let slow_bdev = lookup(slow)?;
+
+        let create_error = |source: Errno, msg: String| {
+            error!("{}: {}", name, msg);
+            NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            }
+        };
+
+        let block_len = u64::from(slow_bdev.block_len());
+        if fast == slow {
+            return Err(create_error(
+                Errno::EINVAL,
+                "the fast and the slow bdev must differ".into(),
+            ));
+        }
+        if u64::from(fast_bdev.block_len()) != block_len {
+            return Err(create_error(
+                Errno::EINVAL,
+                format!(
+                    "block length of {} differs from {}",
+                    fast, slow
+                ),
+            ));
+        }
+        if extent_len % block_len != 0 || SLOT_ENTRY_LEN > block_len {
+            return Err(create_error(
+                Errno::EINVAL,
+                format!(
+                    "extents of {} bytes do not fit blocks of {} bytes",
+                    extent_len, block_len
+                ),
+            ));
+        }
+        let extent_blocks = extent_len / block_len;
+        let extents = slow_bdev.num_blocks() / extent_blocks;
+
+        let open = |bdev: &Bdev| {
+            bdev.open(true).map(Arc::new).map_err(|error| {
+                let source = match error {
+                    CoreError::OpenBdev {
+                        source,
+                    } => source,
+                    _ => Errno::EINVAL,
+                };
+                NexusBdevError::CreateBdev {
+                    source,
+                    name: name.to_string(),
+                }
+            })
+        };
+        let fast_desc = open(&fast_bdev)?;
+        let slow_desc = open(&slow_bdev)?;
+        let io_error = |e: CoreError| create_error(Errno::EIO, e.to_string());
+
+        let handle =
+            BdevHandle::try_from(Arc::clone(&fast_desc)).map_err(io_error)?;
+        let (header, table) =
+            match Self::read_table(&handle, block_len).await {
+                Ok(Some((header, table))) => {
+                    if header.extent_len != extent_len
+                        || header.extents != extents
+                        || header.slots
+                            > Self::capacity(
+                                fast_bdev.num_blocks(),
+                                block_len,
+                                extent_blocks,
+                            )
+                    {
+                        return Err(create_error(
+                            Errno::EINVAL,
+                            format!(
+                                "{} is set up for {:?}, not for {} extents \
+                                 of {} bytes of {}",
+                                fast, header, extents, extent_len, slow
+                            ),
+                        ));
+                    }
+                    (header, table)
+                }
+                Ok(None) => {
+                    let header = TierHeader {
+                        magic: TIER_MAGIC,
+                        block_len: block_len as u32,
+                        extent_len,
+                        slots: Self::capacity(
+                            fast_bdev.num_blocks(),
+                            block_len,
+                            extent_blocks,
+                        ),
+                        extents,
+                    };
+                    if header.slots == 0 || header.extents == 0 {
+                        return Err(create_error(
+                            Errno::EINVAL,
+                            format!("{} or {} is too small", fast, slow),
+                        ));
+                    }
+                    let mut buf = handle.dma_malloc(block_len).map_err(|_| {
+                        create_error(
+                            Errno::ENOMEM,
+                            "failed to allocate a buffer".into(),
+                        )
+                    })?;
+                    encode_block(&header, buf.as_mut_slice());
+                    handle.write_at(0, &buf).await.map_err(io_error)?;
+                    info!("{}: set up {} for {:?}", name, fast, header);
+                    let len = Self::table_blocks(header.slots, block_len)
+                        * block_len;
+                    (header, vec![0; len as usize])
+                }
+                Err(msg) => {
+                    return Err(create_error(
+                        Errno::EINVAL,
+                        format!("{}: {}", fast, msg),
+                    ))
+                }
+            };
+        drop(handle);
+
+        let map = TierMap::load(table, header.slots, extents, block_len);
+
+        let mut b = Box::new(spdk_bdev::default());
+        b.name = name.into_cstring().into_raw();
+        b.product_name = TIER_PRODUCT_ID.into_cstring().into_raw();
+        b.fn_table = TierFnTable::table();
+        b.module = TIER_MODULE.as_ptr();
+        b.blocklen = block_len as u32;
+        b.blockcnt = extents * extent_blocks;
+        b.required_alignment = unsafe {
+            std::cmp::max(
+                (*fast_bdev.as_ptr()).required_alignment,
+                (*slow_bdev.as_ptr()).required_alignment,
+            )
+        };
+
+        let (sender, receiver) = unbounded();
+        let mut t = Box::new(TierBdev {
+            name: name.to_string(),
+            fast: fast.to_string(),
+            slow: slow.to_string(),
+            bdev: Bdev::from(&*b as *const _ as *mut spdk_bdev),
+            bdev_raw: Box::into_raw(b),
+            fast_desc: Some(fast_desc),
+            slow_desc: Some(slow_desc),
+            policy,
+            block_len,
+            extent_blocks,
+            slots_start: TABLE_START
+                + Self::table_blocks(header.slots, block_len),
+            map: Mutex::new(map),
+            queue: Mutex::new(Some(sender)),
+        });
+
+        // store a reference to the Self in the bdev structure.
+        unsafe {
+            (*t.bdev.as_ptr()).ctxt = t.as_ref() as *const _ as *mut c_void;
+        }
+
+        unsafe {
+            spdk_io_device_register(
+                t.as_ptr(),
+                Some(TierChannel::create),
+                Some(TierChannel::destroy),
+                std::mem::size_of::<TierChannel>() as u32,
+                (*t.bdev.as_ptr()).name,
+            );
+        }
+
+        let errno = unsafe { spdk_bdev_register(t.bdev.as_ptr()) };
+        if let Err(source) = errno_result_from_i32((), errno) {
+            unsafe {
+                spdk_io_device_unregister(t.as_ptr(), None);
+            }
+            t.fast_desc.take();
+            t.slow_desc.take();
+            return Err(NexusBdevError::CreateBdev {
+                source,
+                name: name.to_string(),
+            });
+        }
+
+        // the worker keeps the tier bdev open, so that it is not destructed
+        // before the worker has stopped
+        let tier: &'static Self = unsafe { Self::from_raw(t.as_ptr()) };
+        match Bdev::open_by_name(name, false) {
+            Ok(desc) => {
+                Reactors::current().send_future(tier.migrate(desc, receiver))
+            }
+            Err(error) => {
+                error!("{}: extents will not be migrated: {}", name, error);
+                tier.stop();
+            }
+        }
+
+        info!("{}: created {:?}", name, t);
+        TierModule::get_instances().push(t);
+        Ok(name.to_string())
+    }
+
+    /// Unregister the tier bdev, which closes the fast and the slow bdev
+    /// once the migration worker has stopped.
+    pub(crate) async fn destroy(name: &str) -> Result<(), NexusBdevError> {
+        let bdev = match tier_lookup(name) {
+            Some(tier) => {
+                tier.stop();
+                tier.bdev.clone()
+            }
+            None => {
+                return Err(NexusBdevError::BdevNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        let (s, r) = oneshot::channel::<Result<(), Errno>>();
+        unsafe {
+            // This will trigger a callback to destruct() in the fn_table.
+            spdk_bdev_unregister(bdev.as_ptr(), Some(done_errno_cb), cb_arg(s));
+        }
+
+        r.await
+            .context(nexus_uri::CancelBdev {
+                name: name.to_string(),
+            })?
+            .context(nexus_uri::DestroyBdev {
+                name: name.to_string(),
+            })
+    }
+
+    /// called when the tier bdev is unregistered
+    pub(crate) fn destruct(&mut self) {
+        unsafe {
+            spdk_io_device_unregister(self.as_ptr(), None);
+        }
+        // closes the fast and the slow bdev
+        self.fast_desc.take();
+        self.slow_desc.take();
+        info!("{}: destructed", self.name);
+    }
+
+    /// tell the migration worker to stop, extents queued are not migrated
+    pub(crate) fn stop(&self) {
+        self.queue.lock().unwrap().take();
+    }
+
+    /// the counters of the tier bdev
+    pub fn stats(&self) -> TierStats {
+        self.map.lock().unwrap().stats()
+    }
+
+    /// the length of the extents in bytes
+    pub fn extent_len(&self) -> u64 {
+        self.extent_blocks * self.block_len
+    }
+
+    /// whether the extent holding the given block is on the fast tier
+    pub fn is_promoted(&self, block: u64) -> bool {
+        self.map
+            .lock()
+            .unwrap()
+            .contains(block / self.extent_blocks)
+    }
+
+    /// takes self and converts into a raw pointer
+    pub(crate) fn as_ptr(&self) -> *mut c_void {
+        self as *const _ as *mut _
+    }
+
+    /// takes a raw pointer and casts it to Self
+    pub(crate) unsafe fn from_raw<'a>(n: *mut c_void) -> &'a mut Self {
+        &mut *(n as *mut TierBdev)
+    }
+
+    /// obtain the TierBdev the given IO has been submitted to
+    pub(crate) fn from_io<'a>(io: &Bio) -> &'a mut Self {
+        let b = io.bdev_as_ref();
+        assert_eq!(b.product_name(), TIER_PRODUCT_ID);
+        unsafe { Self::from_raw((*b.as_ptr()).ctxt) }
+    }
+
+    /// handles to the fast and the slow bdev on the current core
+    fn handles(&self) -> Result<TierHandles, CoreError> {
+        let handle = |desc: &Option<Arc<Descriptor>>| {
+            BdevHandle::try_from(Arc::clone(desc.as_ref().unwrap()))
+        };
+        Ok(TierHandles {
+            fast: handle(&self.fast_desc)?,
+            slow: handle(&self.slow_desc)?,
+        })
+    }
+
+    /// offset in bytes on the fast bdev of the given block of a slot
+    fn fast_offset(&self, slot: u32, block: u64) -> u64 {
+        (self.slots_start + u64::from(slot) * self.extent_blocks + block)
+            * self.block_len
+    }
+
+    /// offset in bytes on the slow bdev of the given block of an extent
+    fn slow_offset(&self, extent: u64, block: u64) -> u64 {
+        (extent * self.extent_blocks + block) * self.block_len
+    }
+
+    /// the extents the IO covers, with the first block and the number of
+    /// blocks of each of them it covers
+    fn pieces(&self, io: &Bio) -> Vec<(u64, u64, u64)> {
+        let end = io.offset() + io.num_blocks();
+        let mut pieces = Vec::new();
+        let mut block = io.offset();
+        while block < end {
+            let start = block % self.extent_blocks;
+            let len = min(self.extent_blocks - start, end - block);
+            pieces.push((block / self.extent_blocks, start, len));
+            block += len;
+        }
+        pieces
+    }
+
+    /// complete an IO submitted to the tier bdev
+    fn complete(&self, io: &Bio, result: Result<(), TierError>) {
+        let status = match result {
+            Ok(()) => IoStatus::Success,
+            Err(error) => {
+                error!("{}: IO {:?} failed: {}", self.name, io, error);
+                IoStatus::Failed
+            }
+        };
+        unsafe { spdk_bdev_io_complete(io.as_ptr(), status.into()) }
+    }
+
+    /// callback when the IO has buffer associated with itself
+    extern "C" fn get_buf_cb(
+        ch: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+        success: bool,
+    ) {
+        let bio = Bio::from(io);
+        let tier = Self::from_io(&bio);
+        if !success {
+            warn!("{}: Failed to get io buffer for io {:?}", tier.name, bio);
+            bio.fail();
+            return;
+        }
+        tier.readv(&bio, TierChannel::handles(ch));
+    }
+
+    /// read the blocks of the IO from the tier each extent is on
+    pub(crate) fn readv(
+        &'static self,
+        io: &Bio,
+        handles: &'static TierHandles,
+    ) {
+        // if there is no buffer space for us allocated within the request
+        // allocate it now, taking care of proper alignment
+        if io.need_buf() {
+            unsafe {
+                spdk_bdev_io_get_buf(
+                    io.as_ptr(),
+                    Some(Self::get_buf_cb),
+                    io.num_blocks() * self.block_len,
+                )
+            }
+            return;
+        }
+
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.read(&io, handles).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// write the blocks of the IO to the tier each extent is on
+    pub(crate) fn writev(
+        &'static self,
+        io: &Bio,
+        handles: &'static TierHandles,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = self.write(&io, handles).await;
+            self.complete(&io, result);
+        });
+    }
+
+    /// flush the fast and the slow bdev
+    pub(crate) fn flush(
+        &'static self,
+        io: &Bio,
+        handles: &'static TierHandles,
+    ) {
+        let io = io.clone();
+        Reactors::current().send_future(async move {
+            let result = match handles.fast.flush().await {
+                Ok(_) => handles.slow.flush().await,
+                Err(error) => Err(error),
+            };
+            self.complete(&io, result.map_err(TierError::from));
+        });
+    }
+
+    async fn read(
+        &self,
+        io: &Bio,
+        handles: &TierHandles,
+    ) -> Result<(), TierError> {
+        // no slot is freed until the read is done
+        let epoch = self.map.lock().unwrap().read_start();
+        let mut data =
+            Vec::with_capacity((io.num_blocks() * self.block_len) as usize);
+        let mut result = Ok(());
+        for (extent, start, len) in self.pieces(io) {
+            let slot = {
+                let mut map = self.map.lock().unwrap();
+                let slot = map.read(extent);
+                if slot.is_none() && map.heat(extent, &self.policy) {
+                    if let Some(queue) = self.queue.lock().unwrap().as_ref() {
+                        let _ = queue.unbounded_send(extent);
+                    }
+                }
+                slot
+            };
+            match self.read_piece(handles, extent, slot, start, len).await {
+                Ok(buf) => data.extend_from_slice(buf.as_slice()),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+        self.map.lock().unwrap().read_done(epoch);
+
+        result.map(|_| scatter(io, &data))
+    }
+
+    /// read blocks of an extent from the fast bdev if it is in a slot,
+    /// otherwise from the slow bdev
+    async fn read_piece(
+        &self,
+        handles: &TierHandles,
+        extent: u64,
+        slot: Option<u32>,
+        start: u64,
+        len: u64,
+    ) -> Result<DmaBuf, TierError> {
+        let (handle, offset) = match slot {
+            Some(slot) => (&handles.fast, self.fast_offset(slot, start)),
+            None => (&handles.slow, self.slow_offset(extent, start)),
+        };
+        let mut buf = handle
+            .dma_malloc(len * self.block_len)
+            .map_err(|_| TierError::NoMemory)?;
+        handle.read_at(offset, &mut buf).await?;
+        Ok(buf)
+    }
+
+    async fn write(
+        &self,
+        io: &Bio,
+        handles: &TierHandles,
+    ) -> Result<(), TierError> {
+        let data = gather(io, (io.num_blocks() * self.block_len) as usize);
+        let mut done = 0;
+        for (extent, start, len) in self.pieces(io) {
+            // the extent is not migrated while the IO is in flight, as its
+            // range is locked against writes
+            let slot = self.map.lock().unwrap().write(extent);
+            let (handle, offset) = match slot {
+                Some(slot) => (&handles.fast, self.fast_offset(slot, start)),
+                None => (&handles.slow, self.slow_offset(extent, start)),
+            };
+            let len = (len * self.block_len) as usize;
+            let mut buf = handle
+                .dma_malloc(len as u64)
+                .map_err(|_| TierError::NoMemory)?;
+            buf.as_mut_slice().copy_from_slice(&data[done .. done + len]);
+            handle.write_at(offset, &buf).await?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Promote the extents queued by reads one by one, and demote the least
+    /// recently used ones while the fast tier is under pressure, until told
+    /// to stop. The descriptor of the tier bdev is closed last, after which
+    /// the tier bdev may be gone.
+    async fn migrate(
+        &'static self,
+        desc: Descriptor,
+        mut queue: UnboundedReceiver<u64>,
+    ) {
+        let (ch, handles) = match (desc.get_channel(), self.handles()) {
+            (Some(ch), Ok(handles)) => (ch, handles),
+            _ => {
+                error!("{}: extents will not be migrated", self.name);
+                return;
+            }
+        };
+
+        while let Some(extent) = queue.next().await {
+            if self.queue.lock().unwrap().is_none() {
+                break;
+            }
+            if let Err(error) =
+                self.promote(&desc, &ch, &handles, extent).await
+            {
+                warn!(
+                    "{}: failed to promote extent {}: {}",
+                    self.name, extent, error
+                );
+            }
+            self.map.lock().unwrap().end_migration(extent);
+
+            loop {
+                let victim = {
+                    let map = self.map.lock().unwrap();
+                    if map.over_pressure(&self.policy) {
+                        map.victim()
+                    } else {
+                        None
+                    }
+                };
+                let (extent, slot, dirty) = match victim {
+                    Some(victim) => victim,
+                    None => break,
+                };
+                if let Err(error) = self
+                    .demote(&desc, &ch, &handles, extent, slot, dirty)
+                    .await
+                {
+                    warn!(
+                        "{}: failed to demote extent {}: {}",
+                        self.name, extent, error
+                    );
+                    break;
+                }
+            }
+        }
+
+        info!("{}: migration stopped", self.name);
+        drop(handles);
+        drop(ch);
+    }
+
+    /// Lock the range of the extent on the tier bdev, and wait for the
+    /// share of the background IO budget to copy it first.
+    async fn lock_extent(
+        &self,
+        desc: &Descriptor,
+        ch: &IoChannel,
+        ctx: &mut RangeContext,
+    ) -> Result<(), TierError> {
+        if let Some(wait) = BackgroundScheduler::reserve(
+            BackgroundClass::Migration,
+            self.extent_len(),
+        ) {
+            Delay::new(wait).await;
+        }
+        desc.lock_lba_range(ctx, ch).await.map_err(TierError::Lock)
+    }
+
+    /// Copy the extent to a free slot of the fast tier, demoting the least
+    /// recently used extent if there is none.
+    async fn promote(
+        &self,
+        desc: &Descriptor,
+        ch: &IoChannel,
+        handles: &TierHandles,
+        extent: u64,
+    ) -> Result<(), TierError> {
+        if !self.map.lock().unwrap().has_free() {
+            let victim = self.map.lock().unwrap().victim();
+            if let Some((victim, slot, dirty)) = victim {
+                self.demote(desc, ch, handles, victim, slot, dirty).await?;
+            }
+        }
+        let slot =
+            self.map.lock().unwrap().alloc().ok_or(TierError::NoSlot)?;
+
+        let mut ctx =
+            RangeContext::new(extent * self.extent_blocks, self.extent_blocks);
+        if let Err(error) = self.lock_extent(desc, ch, &mut ctx).await {
+            self.map.lock().unwrap().release(slot);
+            return Err(error);
+        }
+        let result = self.copy_in(handles, extent, slot).await;
+        if result.is_err() {
+            self.map.lock().unwrap().release(slot);
+        }
+        desc.unlock_lba_range(&mut ctx, ch)
+            .await
+            .map_err(TierError::Lock)?;
+        result
+    }
+
+    /// copy the extent from the slow bdev to the slot, and map it there once
+    /// its entry in the table has been written
+    async fn copy_in(
+        &self,
+        handles: &TierHandles,
+        extent: u64,
+        slot: u32,
+    ) -> Result<(), TierError> {
+        let mut buf = handles
+            .slow
+            .dma_malloc(self.extent_len())
+            .map_err(|_| TierError::NoMemory)?;
+        handles
+            .slow
+            .read_at(self.slow_offset(extent, 0), &mut buf)
+            .await?;
+        handles
+            .fast
+            .write_at(self.fast_offset(slot, 0), &buf)
+            .await?;
+        handles.fast.flush().await?;
+
+        self.write_entry(handles, slot, Some(extent)).await?;
+        self.map.lock().unwrap().promoted(extent, slot);
+        debug!("{}: promoted extent {} to slot {}", self.name, extent, slot);
+        Ok(())
+    }
+
+    /// Copy the extent back to the slow bdev if it has been written, and
+    /// unmap it from its slot once its entry in the table has been cleared.
+    async fn demote(
+        &self,
+        desc: &Descriptor,
+        ch: &IoChannel,
+        handles: &TierHandles,
+        extent: u64,
+        slot: u32,
+        dirty: bool,
+    ) -> Result<(), TierError> {
+        let mut ctx =
+            RangeContext::new(extent * self.extent_blocks, self.extent_blocks);
+        self.lock_extent(desc, ch, &mut ctx).await?;
+        let result = self.copy_out(handles, extent, slot, dirty).await;
+        desc.unlock_lba_range(&mut ctx, ch)
+            .await
+            .map_err(TierError::Lock)?;
+        result
+    }
+
+    async fn copy_out(
+        &self,
+        handles: &TierHandles,
+        extent: u64,
+        slot: u32,
+        dirty: bool,
+    ) -> Result<(), TierError> {
+        if dirty {
+            let mut buf = handles
+                .fast
+                .dma_malloc(self.extent_len())
+                .map_err(|_| TierError::NoMemory)?;
+            handles
+                .fast
+                .read_at(self.fast_offset(slot, 0), &mut buf)
+                .await?;
+            handles
+                .slow
+                .write_at(self.slow_offset(extent, 0), &buf)
+                .await?;
+            handles.slow.flush().await?;
+        }
+
+        if let Err(error) = self.write_entry(handles, slot, None).await {
+            // the extent stays in its slot
+            self.map.lock().unwrap().table_entry(slot, Some(extent));
+            return Err(error);
+        }
+        self.map.lock().unwrap().demoted(extent);
+        debug!("{}: demoted extent {} from slot {}", self.name, extent, slot);
+        Ok(())
+    }
+
+    /// set the entry of the slot in the table and write it to the fast bdev
+    async fn write_entry(
+        &self,
+        handles: &TierHandles,
+        slot: u32,
+        extent: Option<u64>,
+    ) -> Result<(), TierError> {
+        let mut buf = handles
+            .fast
+            .dma_malloc(self.block_len)
+            .map_err(|_| TierError::NoMemory)?;
+        let offset = {
+            let mut map = self.map.lock().unwrap();
+            let (offset, block) = map.table_entry(slot, extent);
+            buf.as_mut_slice().copy_from_slice(&block);
+            offset
+        };
+        handles
+            .fast
+            .write_at(TABLE_START * self.block_len + offset, &buf)
+            .await?;
+        handles.fast.flush().await?;
+        Ok(())
+    }
+}
+
+/// Lookup a tier bdev by its name.
+pub fn tier_lookup(name: &str) -> Option<&mut TierBdev> {
+    TierModule::get_instances()
+        .iter_mut()
+        .find(|t| t.name == name)
+        .map(|t| t.as_mut())
+}
+
+/// Unregister the tier bdevs on top of the given bdev which is being
+/// removed. The migration worker of a tier bdev which is being removed
+/// itself is stopped, so that it closes the tier bdev.
+pub(crate) fn backing_removed(backing: &str) {
+    for tier in TierModule::get_instances()
+        .iter()
+        .filter(|t| t.name == backing || t.fast == backing || t.slow == backing)
+    {
+        tier.stop();
+        if tier.name != backing {
+            info!("{}: backing bdev {} removed", tier.name, backing);
+            unsafe {
+                spdk_bdev_unregister(
+                    tier.bdev.as_ptr(),
+                    None,
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+    }
+}
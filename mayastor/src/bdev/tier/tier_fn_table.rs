@@ -0,0 +1,132 @@
+use std::ffi::{c_void, CString};
+
+use once_cell::sync::Lazy;
+
+use spdk_sys::{
+    spdk_bdev_fn_table,
+    spdk_bdev_io,
+    spdk_bdev_io_type,
+    spdk_get_io_channel,
+    spdk_io_channel,
+    spdk_json_write_ctx,
+    spdk_json_write_name,
+    spdk_json_write_val_raw,
+};
+
+use crate::bdev::{
+    nexus::nexus_io::{Bio, IoType},
+    tier::{
+        tier_bdev::{TierBdev, TierChannel},
+        tier_module::TierModule,
+    },
+};
+
+static TIER_FN_TBL: Lazy<TierFnTable> = Lazy::new(TierFnTable::new);
+
+pub struct TierFnTable {
+    pub(crate) f_tbl: spdk_bdev_fn_table,
+}
+
+unsafe impl Sync for TierFnTable {}
+unsafe impl Send for TierFnTable {}
+
+/// The FN table are function pointers called by SPDK when work is sent
+/// our way. The functions are static, and shared between all instances.
+impl TierFnTable {
+    fn new() -> Self {
+        let f_tbl = spdk_bdev_fn_table {
+            io_type_supported: Some(Self::io_supported),
+            submit_request: Some(Self::io_submit),
+            get_io_channel: Some(Self::io_channel),
+            destruct: Some(Self::destruct),
+            dump_info_json: Some(Self::dump_info_json),
+            write_config_json: None,
+            get_spin_time: None,
+            get_module_ctx: None,
+        };
+
+        TierFnTable {
+            f_tbl,
+        }
+    }
+
+    /// get a reference to this static function table to pass on to every
+    /// instance
+    pub fn table() -> &'static spdk_bdev_fn_table {
+        &TIER_FN_TBL.f_tbl
+    }
+
+    /// reads, writes and flushes are supported
+    extern "C" fn io_supported(
+        _ctx: *mut c_void,
+        io_type: spdk_bdev_io_type,
+    ) -> bool {
+        matches!(
+            IoType::from(io_type),
+            IoType::Read | IoType::Write | IoType::Flush
+        )
+    }
+
+    /// Submit an IO to the tier bdev, the extents it covers are read from
+    /// or written to the tier they are on.
+    extern "C" fn io_submit(
+        channel: *mut spdk_io_channel,
+        io: *mut spdk_bdev_io,
+    ) {
+        let bio = Bio::from(io);
+        let tier = TierBdev::from_io(&bio);
+        let handles = TierChannel::handles(channel);
+
+        match bio.io_type() {
+            IoType::Read => tier.readv(&bio, handles),
+            IoType::Write => tier.writev(&bio, handles),
+            IoType::Flush => tier.flush(&bio, handles),
+            io_type => {
+                error!("{}: unsupported IO type {:?}", tier.name, io_type);
+                bio.fail();
+            }
+        }
+    }
+
+    /// called per core to create IO channels per tier instance
+    extern "C" fn io_channel(ctx: *mut c_void) -> *mut spdk_io_channel {
+        unsafe { spdk_get_io_channel(ctx) }
+    }
+
+    /// called when the tier bdev is unregistered
+    extern "C" fn destruct(ctx: *mut c_void) -> i32 {
+        let tier = unsafe { TierBdev::from_raw(ctx) };
+        tier.destruct();
+        let name = tier.name.clone();
+        // removing the tier bdev from the list should cause a drop
+        TierModule::get_instances().retain(|t| t.name != name);
+        0
+    }
+
+    /// device specific information which is returned
+    /// by the get_bdevs RPC call.
+    extern "C" fn dump_info_json(
+        ctx: *mut c_void,
+        w: *mut spdk_json_write_ctx,
+    ) -> i32 {
+        let tier = unsafe { TierBdev::from_raw(ctx) };
+        let json = serde_json::json!({
+            "fast": tier.fast,
+            "slow": tier.slow,
+            "extent_len": tier.extent_len(),
+            "policy": tier.policy,
+            "stats": tier.stats(),
+        });
+
+        let data = CString::new(json.to_string()).unwrap();
+        unsafe {
+            spdk_json_write_name(w, "tier\0".as_ptr() as *mut i8);
+            spdk_json_write_val_raw(
+                w,
+                data.as_ptr() as *const _,
+                data.as_bytes().len() as u64,
+            );
+        }
+        0
+    }
+}
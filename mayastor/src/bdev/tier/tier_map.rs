@@ -0,0 +1,312 @@
+//!
+//! The map of the tier bdev, which extents are kept in which slots of the
+//! fast tier, along with the state the migration policy acts on: how often
+//! the extents on the slow tier have been read, and the order in which the
+//! extents on the fast tier have last been used.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryInto,
+    fmt::{Display, Formatter},
+};
+
+use serde::Serialize;
+
+use crate::bdev::util::block::{BlockAllocator, ReadEpoch};
+
+/// length of the entry of a slot in the table
+pub(super) const SLOT_ENTRY_LEN: u64 = 8;
+
+/// When the extents of a tier bdev migrate between the tiers.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct TierPolicy {
+    /// number of reads of an extent on the slow tier which promote it to
+    /// the fast tier
+    pub promote: u32,
+    /// percentage of the slots of the fast tier in use above which the
+    /// least recently used extents are demoted to the slow tier
+    pub demote: u32,
+}
+
+impl Default for TierPolicy {
+    fn default() -> Self {
+        Self {
+            promote: 2,
+            demote: 90,
+        }
+    }
+}
+
+impl Display for TierPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "promote after {} reads, demote above {}%",
+            self.promote, self.demote
+        )
+    }
+}
+
+/// Counters of the tier bdev, reads are counted per extent read.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct TierStats {
+    /// reads served from the fast tier
+    pub fast_reads: u64,
+    /// reads served from the slow tier
+    pub slow_reads: u64,
+    /// extents moved to the fast tier
+    pub promotions: u64,
+    /// extents moved to the slow tier
+    pub demotions: u64,
+    /// number of extents on the fast tier
+    pub fast_extents: u64,
+    /// number of extents the fast tier can hold
+    pub slots: u64,
+}
+
+#[derive(Debug)]
+struct FastExtent {
+    /// slot of the fast tier holding the extent
+    slot: u32,
+    /// key of the extent in the order of use
+    tick: u64,
+    /// set once the extent has been written on the fast tier, so it must be
+    /// copied back when it is demoted
+    dirty: bool,
+}
+
+#[derive(Debug)]
+pub(super) struct TierMap {
+    /// the table of the slots as it is stored, the extent plus one of every
+    /// slot or zero for a free slot
+    table: Vec<u8>,
+    /// block length of the fast tier
+    block_len: u64,
+    /// the extents on the fast tier
+    fast: HashMap<u64, FastExtent>,
+    /// the extents on the fast tier by the tick they were last used at
+    order: BTreeMap<u64, u64>,
+    /// the slots which hold an extent or are being copied to
+    allocator: BlockAllocator,
+    /// reads of the extents on the slow tier
+    heat: HashMap<u64, u32>,
+    /// extents queued for migration or being migrated
+    migrating: HashSet<u64>,
+    tick: u64,
+    stats: TierStats,
+}
+
+impl TierMap {
+    /// Load the map from the table of the given number of slots. Every
+    /// extent found on the fast tier counts as written, as it is not known
+    /// whether it was.
+    pub(super) fn load(
+        table: Vec<u8>,
+        slots: u64,
+        extents: u64,
+        block_len: u64,
+    ) -> Self {
+        let mut map = Self {
+            table,
+            block_len,
+            fast: HashMap::new(),
+            order: BTreeMap::new(),
+            allocator: BlockAllocator::new(slots),
+            heat: HashMap::new(),
+            migrating: HashSet::new(),
+            tick: 0,
+            stats: TierStats {
+                slots,
+                ..Default::default()
+            },
+        };
+        for slot in 0 .. slots as u32 {
+            match map.entry(slot) {
+                Some(extent)
+                    if extent < extents && !map.fast.contains_key(&extent) =>
+                {
+                    map.insert(extent, slot, true);
+                    map.allocator.set_used(slot, true);
+                }
+                Some(extent) => {
+                    error!("slot {} holds invalid extent {}", slot, extent);
+                    map.set_entry(slot, None);
+                }
+                None => {}
+            }
+        }
+        map
+    }
+
+    /// the extent in the slot according to the table
+    fn entry(&self, slot: u32) -> Option<u64> {
+        let start = (u64::from(slot) * SLOT_ENTRY_LEN) as usize;
+        let entry = u64::from_le_bytes(
+            self.table[start .. start + SLOT_ENTRY_LEN as usize]
+                .try_into()
+                .unwrap(),
+        );
+        entry.checked_sub(1)
+    }
+
+    fn set_entry(&mut self, slot: u32, extent: Option<u64>) {
+        let start = (u64::from(slot) * SLOT_ENTRY_LEN) as usize;
+        let entry = extent.map_or(0, |e| e + 1);
+        self.table[start .. start + SLOT_ENTRY_LEN as usize]
+            .copy_from_slice(&entry.to_le_bytes());
+    }
+
+    /// Set the entry of the slot in the table, returns the offset in the
+    /// table of the block with the entry and its contents to write.
+    pub(super) fn table_entry(
+        &mut self,
+        slot: u32,
+        extent: Option<u64>,
+    ) -> (u64, Vec<u8>) {
+        self.set_entry(slot, extent);
+        let offset = u64::from(slot) * SLOT_ENTRY_LEN / self.block_len
+            * self.block_len;
+        let start = offset as usize;
+        (
+            offset,
+            self.table[start .. start + self.block_len as usize].to_vec(),
+        )
+    }
+
+    fn insert(&mut self, extent: u64, slot: u32, dirty: bool) {
+        self.tick += 1;
+        self.fast.insert(
+            extent,
+            FastExtent {
+                slot,
+                tick: self.tick,
+                dirty,
+            },
+        );
+        self.order.insert(self.tick, extent);
+        self.stats.fast_extents += 1;
+    }
+
+    /// The slot of the fast tier holding the extent to be read, if it is
+    /// there, which makes it the most recently used one.
+    pub(super) fn read(&mut self, extent: u64) -> Option<u32> {
+        match self.fast.get_mut(&extent) {
+            Some(fast) => {
+                self.order.remove(&fast.tick);
+                self.tick += 1;
+                fast.tick = self.tick;
+                self.order.insert(self.tick, extent);
+                self.stats.fast_reads += 1;
+                Some(fast.slot)
+            }
+            None => {
+                self.stats.slow_reads += 1;
+                None
+            }
+        }
+    }
+
+    /// Count a read of an extent on the slow tier, returns true if it is due
+    /// to be promoted. The counts are halved once they are kept for too
+    /// many extents, so that extents which are no longer read cool down.
+    pub(super) fn heat(&mut self, extent: u64, policy: &TierPolicy) -> bool {
+        if self.fast.contains_key(&extent) || self.migrating.contains(&extent)
+        {
+            return false;
+        }
+        let limit = std::cmp::max(self.stats.slots as usize * 4, 1024);
+        if self.heat.len() >= limit {
+            self.heat.retain(|_, reads| {
+                *reads /= 2;
+                *reads > 0
+            });
+        }
+        let reads = self.heat.entry(extent).or_insert(0);
+        *reads += 1;
+        if *reads < policy.promote {
+            return false;
+        }
+        self.heat.remove(&extent);
+        self.migrating.insert(extent);
+        true
+    }
+
+    /// The slot of the fast tier holding the extent to be written, if it is
+    /// there, which is marked as written.
+    pub(super) fn write(&mut self, extent: u64) -> Option<u32> {
+        self.fast.get_mut(&extent).map(|fast| {
+            fast.dirty = true;
+            fast.slot
+        })
+    }
+
+    /// whether there is a free slot
+    pub(super) fn has_free(&self) -> bool {
+        self.allocator.has_free()
+    }
+
+    /// take a free slot for an extent to be promoted
+    pub(super) fn alloc(&mut self) -> Option<u32> {
+        self.allocator.alloc(1).map(|slots| slots[0])
+    }
+
+    /// map the extent to the slot it has been copied to
+    pub(super) fn promoted(&mut self, extent: u64, slot: u32) {
+        self.insert(extent, slot, false);
+        self.stats.promotions += 1;
+    }
+
+    /// the least recently used extent on the fast tier which is not being
+    /// migrated, with its slot and whether it has been written
+    pub(super) fn victim(&self) -> Option<(u64, u32, bool)> {
+        self.order
+            .values()
+            .find(|e| !self.migrating.contains(*e))
+            .map(|e| (*e, self.fast[e].slot, self.fast[e].dirty))
+    }
+
+    /// whether more slots are in use than the policy allows
+    pub(super) fn over_pressure(&self, policy: &TierPolicy) -> bool {
+        self.stats.fast_extents * 100
+            > self.stats.slots * u64::from(policy.demote)
+    }
+
+    /// Unmap the extent from the slot of the fast tier holding it, the slot
+    /// is free once no read which may still be reading it is in flight.
+    pub(super) fn demoted(&mut self, extent: u64) {
+        if let Some(fast) = self.fast.remove(&extent) {
+            self.order.remove(&fast.tick);
+            self.stats.fast_extents -= 1;
+            self.stats.demotions += 1;
+            self.allocator.free(&[fast.slot]);
+        }
+    }
+
+    /// whether the extent is on the fast tier
+    pub(super) fn contains(&self, extent: u64) -> bool {
+        self.fast.contains_key(&extent)
+    }
+
+    /// the migration of the extent is done, or has been given up
+    pub(super) fn end_migration(&mut self, extent: u64) {
+        self.migrating.remove(&extent);
+    }
+
+    /// give back a slot which was taken for a promotion that failed
+    pub(super) fn release(&mut self, slot: u32) {
+        self.set_entry(slot, None);
+        self.allocator.release(&[slot]);
+    }
+
+    pub(super) fn read_start(&mut self) -> ReadEpoch {
+        self.allocator.read_start()
+    }
+
+    pub(super) fn read_done(&mut self, epoch: ReadEpoch) {
+        self.allocator.read_done(epoch);
+    }
+
+    pub(super) fn stats(&self) -> TierStats {
+        self.stats
+    }
+}
@@ -0,0 +1,92 @@
+use std::{cell::UnsafeCell, ffi::CString};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+use spdk_sys::{spdk_bdev_module, spdk_bdev_module_list_add, spdk_get_thread};
+
+use crate::{bdev::tier::tier_bdev::TierBdev, ffihelper::IntoCString};
+
+pub const TIER_MODULE_NAME: &str = "tier";
+
+pub static TIER_MODULE: Lazy<TierModule> = Lazy::new(TierModule::new);
+
+#[derive(Default, Debug)]
+pub struct TierInstances {
+    inner: UnsafeCell<Vec<Box<TierBdev>>>,
+}
+
+#[derive(Debug)]
+pub struct TierModule(*mut spdk_bdev_module);
+
+unsafe impl Sync for TierModule {}
+unsafe impl Sync for TierInstances {}
+
+unsafe impl Send for TierModule {}
+unsafe impl Send for TierInstances {}
+
+impl TierModule {
+    /// construct a new TierModule instance and setup the main properties,
+    /// tier bdevs are only created explicitly so there is nothing to examine
+    pub fn new() -> Self {
+        let mut module = Box::new(spdk_bdev_module::default());
+        module.name = TIER_MODULE_NAME.into_cstring().into_raw();
+
+        module.async_init = false;
+        module.async_fini = false;
+        module.module_init = Some(Self::tier_mod_init);
+        module.module_fini = Some(Self::tier_mod_fini);
+        module.get_ctx_size = Some(Self::tier_ctx_size);
+        module.examine_config = None;
+        module.examine_disk = None;
+        TierModule(Box::into_raw(module))
+    }
+
+    pub fn as_ptr(&self) -> *mut spdk_bdev_module {
+        self.0
+    }
+
+    /// return instances, we ensure that this can only ever be called on a
+    /// properly allocated thread
+    pub fn get_instances() -> &'static mut Vec<Box<TierBdev>> {
+        let thread = unsafe { spdk_get_thread() };
+        if thread.is_null() {
+            panic!("not called from SPDK thread")
+        }
+
+        static TIER_INSTANCES: OnceCell<TierInstances> = OnceCell::new();
+
+        let global_instances = TIER_INSTANCES.get_or_init(|| TierInstances {
+            inner: UnsafeCell::new(Vec::new()),
+        });
+
+        unsafe { &mut *global_instances.inner.get() }
+    }
+
+    extern "C" fn tier_mod_init() -> i32 {
+        info!("Initializing Tier Module");
+        0
+    }
+
+    extern "C" fn tier_mod_fini() {
+        info!("Unloading Tier Module");
+        let _ = unsafe { CString::from_raw((*(TIER_MODULE.0)).name as _) };
+        Self::get_instances().clear();
+    }
+
+    /// the IOs are carried out by futures, which keep their own state
+    extern "C" fn tier_ctx_size() -> i32 {
+        0
+    }
+}
+
+impl Default for TierModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_module() {
+    unsafe {
+        spdk_bdev_module_list_add((TIER_MODULE.0) as *const _ as *mut _);
+    }
+}
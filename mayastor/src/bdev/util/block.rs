@@ -0,0 +1,186 @@
+//! Helpers for the metadata the virtual bdevs and the pools keep in blocks
+//! of a backing bdev: the encoding of a value in a block, and an allocator
+//! of the blocks of an area of the backing bdev.
+
+use std::{
+    cmp::min,
+    collections::{BTreeMap, VecDeque},
+    convert::TryInto,
+    io::Cursor,
+};
+
+use bincode::{deserialize_from, serialize, serialized_size};
+use crc::crc32;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// the length of the checksum and length preceding the value in a block
+pub(crate) const BLOCK_HEADER_LEN: usize = 8;
+
+/// the length of the value encoded in a block, including its header
+pub(crate) fn encoded_len<T: Serialize>(value: &T) -> u64 {
+    BLOCK_HEADER_LEN as u64 + serialized_size(value).unwrap()
+}
+
+/// Write the value to the block, preceded by its checksum and length, the
+/// rest of the block is zeroed. The value must fit the block.
+pub(crate) fn encode_block<T: Serialize>(value: &T, block: &mut [u8]) {
+    let body = serialize(value).unwrap();
+    let checksum = crc32::checksum_ieee(&body);
+    block.iter_mut().for_each(|b| *b = 0);
+    block[0 .. 4].copy_from_slice(&checksum.to_le_bytes());
+    block[4 .. 8].copy_from_slice(&(body.len() as u32).to_le_bytes());
+    block[BLOCK_HEADER_LEN .. BLOCK_HEADER_LEN + body.len()]
+        .copy_from_slice(&body);
+}
+
+/// The value in the block, if it holds a valid one. A block of zeroes or
+/// one torn by a crash while it was written does not.
+pub(crate) fn decode_block<T: DeserializeOwned>(block: &[u8]) -> Option<T> {
+    let checksum = u32::from_le_bytes(block[0 .. 4].try_into().unwrap());
+    let len = u32::from_le_bytes(block[4 .. 8].try_into().unwrap()) as usize;
+    if len == 0 {
+        return None;
+    }
+    let body = block.get(BLOCK_HEADER_LEN .. BLOCK_HEADER_LEN + len)?;
+    if crc32::checksum_ieee(body) != checksum {
+        return None;
+    }
+    deserialize_from::<_, T>(Cursor::new(body)).ok()
+}
+
+/// The epoch a read of the blocks of an allocator started in, which is
+/// given back when it is done.
+#[derive(Debug)]
+#[must_use]
+pub(crate) struct ReadEpoch(u64);
+
+/// Allocator of the blocks of an area of a backing bdev, with a bit for
+/// every block which is set while it is in use. The lowest free blocks are
+/// allocated first. Blocks freed while reads are in flight are not reused
+/// until the reads started before they were freed are done, as those may
+/// still be reading them. Every such free starts a new epoch, so reads
+/// started after it never hold the blocks back.
+#[derive(Debug)]
+pub(crate) struct BlockAllocator {
+    used: Vec<u64>,
+    /// the number of blocks of the area
+    blocks: u64,
+    /// the number of blocks in use, including the deferred ones
+    in_use: u64,
+    /// no block below this one is free
+    hint: u64,
+    /// the current epoch
+    epoch: u64,
+    /// the number of reads in flight by the epoch they started in
+    reads: BTreeMap<u64, usize>,
+    /// blocks freed while reads were in flight, by the epoch they were
+    /// freed in, oldest first
+    deferred: VecDeque<(u64, Vec<u32>)>,
+}
+
+impl BlockAllocator {
+    /// an allocator of the given number of blocks, all of them free
+    pub(crate) fn new(blocks: u64) -> Self {
+        Self {
+            used: vec![0; ((blocks + 63) / 64) as usize],
+            blocks,
+            in_use: 0,
+            hint: 0,
+            epoch: 0,
+            reads: BTreeMap::new(),
+            deferred: VecDeque::new(),
+        }
+    }
+
+    /// the number of blocks of the area
+    pub(crate) fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// whether any block is free
+    pub(crate) fn has_free(&self) -> bool {
+        self.in_use < self.blocks
+    }
+
+    pub(crate) fn is_used(&self, block: u32) -> bool {
+        let block = u64::from(block);
+        self.used[(block / 64) as usize] & (1 << (block % 64)) != 0
+    }
+
+    pub(crate) fn set_used(&mut self, block: u32, used: bool) {
+        if self.is_used(block) == used {
+            return;
+        }
+        let block = u64::from(block);
+        let word = &mut self.used[(block / 64) as usize];
+        if used {
+            *word |= 1 << (block % 64);
+            self.in_use += 1;
+        } else {
+            *word &= !(1 << (block % 64));
+            self.in_use -= 1;
+        }
+    }
+
+    /// allocate the lowest free blocks, None if there are not enough
+    pub(crate) fn alloc(&mut self, count: usize) -> Option<Vec<u32>> {
+        let mut blocks = Vec::with_capacity(count);
+        let mut block = self.hint;
+        while blocks.len() < count && block < self.blocks {
+            if self.used[(block / 64) as usize] == u64::MAX {
+                block = (block / 64 + 1) * 64;
+                continue;
+            }
+            if !self.is_used(block as u32) {
+                blocks.push(block as u32);
+            }
+            block += 1;
+        }
+        if blocks.len() < count {
+            return None;
+        }
+        blocks.iter().for_each(|b| self.set_used(*b, true));
+        // every free block below it has just been allocated
+        self.hint = block;
+        Some(blocks)
+    }
+
+    /// free the blocks, or defer it while reads are in flight
+    pub(crate) fn free(&mut self, blocks: &[u32]) {
+        if self.reads.is_empty() {
+            self.release(blocks);
+        } else if !blocks.is_empty() {
+            self.deferred.push_back((self.epoch, blocks.to_vec()));
+            self.epoch += 1;
+        }
+    }
+
+    /// free the blocks at once, for blocks no read can be reading
+    pub(crate) fn release(&mut self, blocks: &[u32]) {
+        for block in blocks {
+            self.set_used(*block, false);
+            self.hint = min(self.hint, u64::from(*block));
+        }
+    }
+
+    /// a read of the blocks starts, none is reused until it is done
+    pub(crate) fn read_start(&mut self) -> ReadEpoch {
+        *self.reads.entry(self.epoch).or_default() += 1;
+        ReadEpoch(self.epoch)
+    }
+
+    /// A read has completed, the blocks freed while it was in flight are
+    /// freed once no read which started before them is in flight either.
+    pub(crate) fn read_done(&mut self, epoch: ReadEpoch) {
+        let reads = self.reads.get_mut(&epoch.0).unwrap();
+        *reads -= 1;
+        if *reads == 0 {
+            self.reads.remove(&epoch.0);
+        }
+        let oldest = self.reads.keys().next().copied().unwrap_or(u64::MAX);
+        while self.deferred.front().map_or(false, |(e, _)| *e < oldest) {
+            let (_, blocks) = self.deferred.pop_front().unwrap();
+            self.release(&blocks);
+        }
+    }
+}
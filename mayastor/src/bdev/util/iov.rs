@@ -0,0 +1,38 @@
+//! Copying the data of an IO between its buffers and a contiguous buffer,
+//! for the virtual bdevs which transform the data of the IOs submitted to
+//! them.
+
+use std::{cmp::min, ptr};
+
+use spdk_sys::iovec;
+
+use crate::bdev::nexus::nexus_io::Bio;
+
+/// the data buffers of the IO
+fn iovs<'a>(io: &Bio) -> &'a [iovec] {
+    unsafe { std::slice::from_raw_parts(io.iovs(), io.iov_count() as usize) }
+}
+
+/// copy the first len bytes of the data buffers of the IO into a contiguous
+/// buffer
+pub(crate) fn gather(io: &Bio, len: usize) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(len);
+    for iov in iovs(io) {
+        let n = min(iov.iov_len as usize, len - buf.len());
+        buf.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(iov.iov_base as *const u8, n)
+        });
+    }
+    buf
+}
+
+/// copy the contiguous buffer into the data buffers of the IO
+pub(crate) fn scatter(io: &Bio, mut buf: &[u8]) {
+    for iov in iovs(io) {
+        let n = min(iov.iov_len as usize, buf.len());
+        unsafe {
+            ptr::copy_nonoverlapping(buf.as_ptr(), iov.iov_base as *mut u8, n)
+        };
+        buf = &buf[n ..];
+    }
+}
@@ -1,2 +1,4 @@
+pub(crate) mod block;
+pub(crate) mod iov;
 pub(super) mod uri;
 pub mod uring;
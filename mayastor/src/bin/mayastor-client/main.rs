@@ -24,6 +24,7 @@ mod pool_cli;
 mod rebuild_cli;
 mod replica_cli;
 mod snapshot_cli;
+mod state_cli;
 
 type MayaClient = MayastorClient<Channel>;
 type BdevClient = BdevRpcClient<Channel>;
@@ -86,6 +87,7 @@ async fn main() -> Result<(), Status> {
         .subcommand(perf_cli::subcommands())
         .subcommand(rebuild_cli::subcommands())
         .subcommand(snapshot_cli::subcommands())
+        .subcommand(state_cli::subcommands())
         .subcommand(jsonrpc_cli::subcommands())
         .get_matches();
 
@@ -100,6 +102,7 @@ async fn main() -> Result<(), Status> {
         ("replica", Some(args)) => replica_cli::handler(ctx, args).await?,
         ("rebuild", Some(args)) => rebuild_cli::handler(ctx, args).await?,
         ("snapshot", Some(args)) => snapshot_cli::handler(ctx, args).await?,
+        ("state", Some(args)) => state_cli::dump_state(ctx, args).await?,
         ("jsonrpc", Some(args)) => {
             jsonrpc_cli::json_rpc_call(ctx, args).await?
         }
@@ -51,6 +51,7 @@ async fn get_resource_usage(
             usage.hard_faults.to_string(),
             usage.vol_csw.to_string(),
             usage.invol_csw.to_string(),
+            usage.bdev_io_exhausted.to_string(),
         ]);
     }
 
@@ -60,6 +61,7 @@ async fn get_resource_usage(
             ">HARD_FAULTS",
             ">VOLUNTARY_CSW",
             ">INVOLUNTARY_CSW",
+            ">BDEV_IO_EXHAUSTED",
         ],
         table,
     );
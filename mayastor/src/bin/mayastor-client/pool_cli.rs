@@ -19,6 +19,37 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .multiple(true)
                 .index(2)
                 .help("Disk device files"),
+        )
+        .arg(
+            Arg::with_name("integrity-reserve")
+                .long("integrity-reserve")
+                .value_name("PERCENT")
+                .help("Percent of the pool reserved for integrity metadata"),
+        )
+        .arg(
+            Arg::with_name("journal")
+                .long("journal")
+                .takes_value(false)
+                .help("Journal the metadata operations of the pool"),
+        )
+        .arg(
+            Arg::with_name("dedup")
+                .long("dedup")
+                .takes_value(false)
+                .help("Deduplicate the blocks of the replicas of the pool"),
+        )
+        .arg(
+            Arg::with_name("dedup-hash")
+                .long("dedup-hash")
+                .value_name("HASH")
+                .requires("dedup")
+                .help("Hash of the blocks: crc32c, xxhash64 or sha256 (default crc32c)"),
+        )
+        .arg(
+            Arg::with_name("wipe")
+                .long("wipe")
+                .value_name("MODE")
+                .help("Clear the disk before creating the pool: none, metadata or full (default none)"),
         );
     let destroy = SubCommand::with_name("destroy")
         .about("Destroy storage pool")
@@ -59,19 +90,45 @@ async fn create(
     matches: &ArgMatches<'_>,
 ) -> Result<(), Status> {
     let name = matches.value_of("pool").unwrap().to_owned();
-    let disks = matches
-        .values_of("disk")
-        .unwrap()
-        .map(|dev| dev.to_owned())
-        .collect();
+    let disks = matches.values_of("disk").unwrap();
+    let integrity_reserve = matches
+        .value_of("integrity-reserve")
+        .unwrap_or("0")
+        .parse()
+        .map_err(|_| {
+            Status::invalid_argument("Invalid value of integrity reserve")
+        })?;
+    let dedup_hash = match matches.value_of("dedup-hash") {
+        None | Some("crc32c") => rpc::ChecksumAlgorithm::ChecksumCrc32c,
+        Some("xxhash64") => rpc::ChecksumAlgorithm::ChecksumXxhash64,
+        Some("sha256") => rpc::ChecksumAlgorithm::ChecksumSha256,
+        Some(_) => {
+            return Err(Status::invalid_argument("Invalid value of dedup hash"))
+        }
+    };
+
+    let wipe = match matches.value_of("wipe") {
+        None | Some("none") => rpc::PoolWipe::WipeNone,
+        Some("metadata") => rpc::PoolWipe::WipeMetadata,
+        Some("full") => rpc::PoolWipe::WipeFull,
+        Some(_) => {
+            return Err(Status::invalid_argument("Invalid value of wipe mode"))
+        }
+    };
+
+    let request = rpc::CreatePoolRequest::builder()
+        .name(name.clone())
+        .disks(disks)
+        .integrity_reserve(integrity_reserve)
+        .journal(matches.is_present("journal"))
+        .dedup(matches.is_present("dedup"))
+        .dedup_hash(dedup_hash)
+        .wipe(wipe)
+        .build()
+        .map_err(Status::invalid_argument)?;
 
     ctx.v2(&format!("Creating pool {}", name));
-    ctx.client
-        .create_pool(rpc::CreatePoolRequest {
-            name: name.clone(),
-            disks,
-        })
-        .await?;
+    ctx.client.create_pool(request).await?;
     ctx.v1(&format!("Created pool {}", name));
     Ok(())
 }
@@ -40,7 +40,32 @@ pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
                 .short("t")
                 .long("thin")
                 .takes_value(false)
-                .help("Whether replica is thin provisioned (default false)"));
+                .help("Whether replica is thin provisioned (default false)"))
+        .arg(
+            Arg::with_name("protection")
+                .long("protection")
+                .takes_value(false)
+                .help("Whether replica blocks carry T10 DIF protection information (default false)"))
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .takes_value(false)
+                .help("Whether a checksum of every replica block is stored and verified (default false)"))
+        .arg(
+            Arg::with_name("checksum-algorithm")
+                .long("checksum-algorithm")
+                .value_name("ALGORITHM")
+                .help("Algorithm of the checksums: crc32c, xxhash64 or sha256 (default crc32c)"))
+        .arg(
+            Arg::with_name("compression")
+                .long("compression")
+                .takes_value(false)
+                .help("Whether the replica data is compressed, which makes it thin provisioned (default false)"))
+        .arg(
+            Arg::with_name("compression-algorithm")
+                .long("compression-algorithm")
+                .value_name("ALGORITHM")
+                .help("Algorithm of the compression: lz4 or zstd (default lz4)"));
 
     let destroy = SubCommand::with_name("destroy")
         .about("Destroy replica")
@@ -104,6 +129,14 @@ async fn replica_create(
     let size = parse_size(matches.value_of("size").unwrap())
         .map_err(|s| Status::invalid_argument(format!("Bad size '{}'", s)))?;
     let thin = matches.is_present("thin");
+    let protection = matches.is_present("protection");
+    let checksum = matches.is_present("checksum");
+    let checksum_algorithm =
+        parse_checksum_algorithm(matches.value_of("checksum-algorithm"))?;
+    let compression = matches.is_present("compression");
+    let compression_algorithm = parse_compression_algorithm(
+        matches.value_of("compression-algorithm"),
+    )?;
     let share = parse_replica_protocol(matches.value_of("protocol"))?;
 
     ctx.v2(&format!("Creating replica {} on pool {}", uuid, pool));
@@ -112,6 +145,11 @@ async fn replica_create(
         pool,
         thin,
         share,
+        protection,
+        checksum,
+        checksum_algorithm,
+        compression,
+        compression_algorithm,
         size: size.get_bytes() as u64,
     };
     let resp = ctx.client.create_replica(rq).await?;
@@ -239,6 +277,35 @@ fn parse_replica_protocol(pcol: Option<&str>) -> Result<i32, Status> {
     }
 }
 
+fn parse_checksum_algorithm(algorithm: Option<&str>) -> Result<i32, Status> {
+    match algorithm {
+        None | Some("crc32c") => {
+            Ok(rpc::ChecksumAlgorithm::ChecksumCrc32c as i32)
+        }
+        Some("xxhash64") => Ok(rpc::ChecksumAlgorithm::ChecksumXxhash64 as i32),
+        Some("sha256") => Ok(rpc::ChecksumAlgorithm::ChecksumSha256 as i32),
+        Some(_) => Err(Status::new(
+            Code::Internal,
+            "Invalid value of checksum algorithm".to_owned(),
+        )),
+    }
+}
+
+fn parse_compression_algorithm(
+    algorithm: Option<&str>,
+) -> Result<i32, Status> {
+    match algorithm {
+        None | Some("lz4") => {
+            Ok(rpc::CompressionAlgorithm::CompressionLz4 as i32)
+        }
+        Some("zstd") => Ok(rpc::CompressionAlgorithm::CompressionZstd as i32),
+        Some(_) => Err(Status::new(
+            Code::Internal,
+            "Invalid value of compression algorithm".to_owned(),
+        )),
+    }
+}
+
 fn replica_protocol_to_str(idx: i32) -> &'static str {
     match rpc::ShareProtocolReplica::from_i32(idx) {
         Some(rpc::ShareProtocolReplica::ReplicaNone) => "none",
@@ -0,0 +1,23 @@
+use super::context::Context;
+use ::rpc::mayastor as rpc;
+use clap::{App, ArgMatches, SubCommand};
+use colored_json::ToColoredJson;
+use tonic::Status;
+
+pub fn subcommands<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("state")
+        .about("Dump the internal state of the data plane as JSON")
+}
+
+pub async fn dump_state(
+    mut ctx: Context,
+    _matches: &ArgMatches<'_>,
+) -> Result<(), Status> {
+    ctx.v2("Requesting the internal state");
+
+    let reply = ctx.client.dump_state(rpc::Null {}).await?;
+
+    println!("{}", reply.get_ref().json.to_colored_json_auto().unwrap());
+
+    Ok(())
+}
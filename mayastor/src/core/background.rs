@@ -0,0 +1,174 @@
+//!
+//! Scheduling of background IO, such as rebuilds, scrubs, read ahead and
+//! tier migration, against frontend IO. All background IO together is
+//! capped to a fraction of the bandwidth of the devices so that frontend
+//! latency stays bounded, and this budget is divided between the kinds of
+//! background IO which are active according to their weights. The options
+//! are taken from the config at startup and can be changed at runtime.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::subsys::{BackgroundOpts, Config};
+
+/// a kind of background IO takes part in the division of the budget if it
+/// has used its share within this period
+const ACTIVE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Kinds of background IO competing with frontend IO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundClass {
+    Rebuild,
+    Scrub,
+    Prefetch,
+    Migration,
+}
+
+const CLASSES: [BackgroundClass; 4] = [
+    BackgroundClass::Rebuild,
+    BackgroundClass::Scrub,
+    BackgroundClass::Prefetch,
+    BackgroundClass::Migration,
+];
+
+impl BackgroundClass {
+    /// weight of the class, a weight of 0 in the config counts as 1
+    fn weight(self, opts: &BackgroundOpts) -> u64 {
+        u64::from(std::cmp::max(
+            match self {
+                Self::Rebuild => opts.rebuild_weight,
+                Self::Scrub => opts.scrub_weight,
+                Self::Prefetch => opts.prefetch_weight,
+                Self::Migration => opts.migration_weight,
+            },
+            1,
+        ))
+    }
+}
+
+struct Scheduler {
+    opts: BackgroundOpts,
+    /// when each kind of background IO last used its share
+    active: [Option<Instant>; 4],
+    /// time until which each kind of background IO has used up its share
+    busy_until: [Instant; 4],
+}
+
+impl Scheduler {
+    /// The rate in bytes/s of the class, None if background IO is not
+    /// limited. The class is marked as active.
+    fn rate(&mut self, class: BackgroundClass, now: Instant) -> Option<u64> {
+        let budget = (self.opts.device_mbps << 20)
+            * u64::from(self.opts.background_percent)
+            / 100;
+        if budget == 0 {
+            return None;
+        }
+
+        self.active[class as usize] = Some(now);
+        let opts = &self.opts;
+        let weights: u64 = CLASSES
+            .iter()
+            .zip(self.active.iter())
+            .filter(|(_, active)| match active {
+                Some(at) => now.duration_since(*at) < ACTIVE_PERIOD,
+                None => false,
+            })
+            .map(|(class, _)| class.weight(opts))
+            .sum();
+
+        Some(std::cmp::max(budget * class.weight(opts) / weights, 1))
+    }
+
+    /// how long it takes to transfer the given number of bytes at the rate
+    fn cost(bytes: u64, rate: u64) -> Duration {
+        Duration::from_nanos(bytes * 1_000_000_000 / rate)
+    }
+}
+
+static SCHEDULER: Lazy<Mutex<Scheduler>> = Lazy::new(|| {
+    let now = Instant::now();
+    Mutex::new(Scheduler {
+        opts: Config::get().background_opts.clone(),
+        active: [None; 4],
+        busy_until: [now; 4],
+    })
+});
+
+/// Caps the background IO and divides it between its kinds.
+pub struct BackgroundScheduler;
+
+impl BackgroundScheduler {
+    /// The share of the background budget in bytes/s of the given kind of
+    /// background IO, None if background IO is not limited. Users pacing
+    /// their IO themselves call this before every IO.
+    pub fn rate(class: BackgroundClass) -> Option<u64> {
+        SCHEDULER.lock().unwrap().rate(class, Instant::now())
+    }
+
+    /// Reserve the bandwidth to transfer the given number of bytes, returns
+    /// how long to wait before issuing the IO.
+    pub fn reserve(class: BackgroundClass, bytes: u64) -> Option<Duration> {
+        let mut scheduler = SCHEDULER.lock().unwrap();
+        let now = Instant::now();
+        let rate = scheduler.rate(class, now)?;
+
+        let busy_until = &mut scheduler.busy_until[class as usize];
+        let start = std::cmp::max(now, *busy_until);
+        *busy_until = start + Scheduler::cost(bytes, rate);
+
+        if start > now {
+            Some(start - now)
+        } else {
+            None
+        }
+    }
+
+    /// Reserve the bandwidth to transfer the given number of bytes if it is
+    /// available right away, for background IO which can be skipped.
+    pub fn try_reserve(class: BackgroundClass, bytes: u64) -> bool {
+        let mut scheduler = SCHEDULER.lock().unwrap();
+        let now = Instant::now();
+        let rate = match scheduler.rate(class, now) {
+            Some(rate) => rate,
+            None => return true,
+        };
+
+        let busy_until = &mut scheduler.busy_until[class as usize];
+        if *busy_until > now {
+            return false;
+        }
+        *busy_until = now + Scheduler::cost(bytes, rate);
+        true
+    }
+
+    /// The options currently in use.
+    pub fn opts() -> BackgroundOpts {
+        SCHEDULER.lock().unwrap().opts.clone()
+    }
+
+    /// Change the options at runtime, the weights must not be 0.
+    pub fn set_opts(opts: BackgroundOpts) -> Result<(), String> {
+        if opts.background_percent > 100 {
+            return Err(format!(
+                "background_percent {} is greater than 100",
+                opts.background_percent
+            ));
+        }
+        if opts.rebuild_weight == 0
+            || opts.scrub_weight == 0
+            || opts.prefetch_weight == 0
+            || opts.migration_weight == 0
+        {
+            return Err("weights must be greater than 0".to_string());
+        }
+
+        info!("background IO options set to {:?}", opts);
+        SCHEDULER.lock().unwrap().opts = opts;
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@ use std::{
 use async_trait::async_trait;
 use futures::channel::oneshot;
 use nix::errno::Errno;
+use serde::Serialize;
 use snafu::ResultExt;
 
 use spdk_sys::{
@@ -20,7 +21,9 @@ use spdk_sys::{
     spdk_bdev_get_buf_align,
     spdk_bdev_get_by_name,
     spdk_bdev_get_device_stat,
+    spdk_bdev_get_dif_type,
     spdk_bdev_get_name,
+    spdk_bdev_get_md_size,
     spdk_bdev_get_num_blocks,
     spdk_bdev_get_product_name,
     spdk_bdev_get_uuid,
@@ -32,13 +35,23 @@ use spdk_sys::{
 };
 
 use crate::{
-    bdev::{lookup_child_from_bdev, nexus::nexus_io::IoType},
+    bdev::{
+        cache::cache_bdev::backing_removed,
+        compress::compress_bdev,
+        dedup::dedup_bdev,
+        pi::pi_bdev,
+        readonly::readonly_bdev,
+        tier::tier_bdev,
+        lookup_child_from_bdev,
+        nexus::{nexus_io::IoType, nexus_nbd::NbdDisk},
+    },
     core::{
         share::{Protocol, Share},
         uuid::Uuid,
         CoreError,
         Descriptor,
         ShareIscsi,
+        ShareNbd,
         ShareNvmf,
         UnshareIscsi,
         UnshareNvmf,
@@ -48,12 +61,20 @@ use crate::{
     target::{iscsi, nvmf, Side},
 };
 
-#[derive(Debug)]
+/// IO statistics of a bdev, summed over all its channels. The latencies are
+/// totals in ticks, of which there are `ticks_rate` per second.
+#[derive(Debug, Clone, Serialize)]
 pub struct BdevStats {
     pub num_read_ops: u64,
     pub num_write_ops: u64,
+    pub num_unmap_ops: u64,
     pub bytes_read: u64,
     pub bytes_written: u64,
+    pub bytes_unmapped: u64,
+    pub read_latency_ticks: u64,
+    pub write_latency_ticks: u64,
+    pub unmap_latency_ticks: u64,
+    pub ticks_rate: u64,
 }
 
 /// Newtype structure that represents a block device. The soundness of the API
@@ -72,16 +93,28 @@ impl Share for Bdev {
 
     /// share the bdev over iscsi
     async fn share_iscsi(&self) -> Result<Self::Output, Self::Error> {
+        self.check_share(Protocol::Iscsi)?;
         iscsi::share(&self.name(), &self, Side::Nexus).context(ShareIscsi {})
     }
 
     /// share the bdev over NVMe-OF TCP
     async fn share_nvmf(&self) -> Result<Self::Output, Self::Error> {
+        self.check_share(Protocol::Nvmf)?;
         let subsystem =
             NvmfSubsystem::try_from(self.clone()).context(ShareNvmf {})?;
         subsystem.start().await.context(ShareNvmf {})
     }
 
+    /// export the bdev as the first unused NBD device, for local testing
+    async fn share_nbd(&self) -> Result<Self::Output, Self::Error> {
+        self.check_share(Protocol::Nbd)?;
+        if let Some(disk) = NbdDisk::lookup(&self.name()) {
+            return Ok(disk.get_path());
+        }
+        let disk = NbdDisk::create(&self.name()).await.context(ShareNbd {})?;
+        Ok(disk.get_path())
+    }
+
     /// unshare the bdev regardless of current active share
     async fn unshare(&self) -> Result<Self::Output, Self::Error> {
         match self.shared() {
@@ -97,6 +130,11 @@ impl Share for Bdev {
                     .await
                     .context(UnshareIscsi {})?;
             }
+            Some(Protocol::Nbd) => {
+                if let Some(disk) = NbdDisk::lookup(&self.name()) {
+                    disk.destroy();
+                }
+            }
             Some(Protocol::Off) | None => {}
         }
 
@@ -109,16 +147,23 @@ impl Share for Bdev {
         match self.claimed_by() {
             Some(t) if t == "NVMe-oF Target" => Some(Protocol::Nvmf),
             Some(t) if t == "iSCSI Target" => Some(Protocol::Iscsi),
+            // NBD opens the bdev without claiming it
+            _ if NbdDisk::lookup(&self.name()).is_some() => {
+                Some(Protocol::Nbd)
+            }
             _ => Some(Protocol::Off),
         }
     }
 
-    /// return share URI for nvmf and iscsi (does "share path" not sound
+    /// return share URI for nvmf, iscsi and nbd (does "share path" not sound
     /// better?)
     fn share_uri(&self) -> Option<String> {
         match self.shared() {
             Some(Protocol::Nvmf) => nvmf::get_uri(&self.name()),
             Some(Protocol::Iscsi) => iscsi::get_uri(Side::Nexus, &self.name()),
+            Some(Protocol::Nbd) => {
+                NbdDisk::lookup(&self.name()).map(|disk| disk.as_uri())
+            }
             _ => Some(format!("bdev:///{}", self.name())),
         }
     }
@@ -141,6 +186,46 @@ impl Share for Bdev {
 }
 
 impl Bdev {
+    /// Share the bdev over NVMe-OF TCP like `share_nvmf()`, with the
+    /// reservations the hosts make on it persisted by the target.
+    pub async fn share_nvmf_with_reservations(
+        &self,
+    ) -> Result<String, CoreError> {
+        self.check_share(Protocol::Nvmf)?;
+        let subsystem =
+            NvmfSubsystem::new_with_reservations(&self.name(), self)
+                .context(ShareNvmf {})?;
+        subsystem.start().await.context(ShareNvmf {})
+    }
+
+    /// Share the bdev over NVMe-OF TCP like `share_nvmf()`, under the given
+    /// NQN prefix rather than the default one.
+    pub async fn share_nvmf_with_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<String, CoreError> {
+        self.check_share(Protocol::Nvmf)?;
+        let subsystem =
+            NvmfSubsystem::with_prefix(prefix, self).context(ShareNvmf {})?;
+        subsystem.start().await.context(ShareNvmf {})
+    }
+
+    /// fails if the bdev is shared over another protocol than the given one,
+    /// as a bdev is shared over one protocol at a time
+    pub(crate) fn check_share(
+        &self,
+        protocol: Protocol,
+    ) -> Result<(), CoreError> {
+        match self.shared() {
+            Some(Protocol::Off) | None => Ok(()),
+            Some(shared) if shared == protocol => Ok(()),
+            Some(shared) => Err(CoreError::AlreadyShared {
+                name: self.name(),
+                protocol: shared,
+            }),
+        }
+    }
+
     /// open a bdev by its name in read_write mode.
     pub fn open_by_name(
         name: &str,
@@ -169,6 +254,12 @@ impl Bdev {
                 if let Some(child) = lookup_child_from_bdev(&bdev.name()) {
                     child.remove();
                 }
+                backing_removed(&bdev.name());
+                pi_bdev::backing_removed(&bdev.name());
+                compress_bdev::backing_removed(&bdev.name());
+                dedup_bdev::backing_removed(&bdev.name());
+                readonly_bdev::backing_removed(&bdev.name());
+                tier_bdev::backing_removed(&bdev.name());
             }
             spdk_sys::SPDK_BDEV_EVENT_RESIZE => {
                 info!("Received resize event for bdev {}", bdev.name())
@@ -259,6 +350,17 @@ impl Bdev {
         }
     }
 
+    /// size of the metadata of a block in bytes
+    pub fn md_len(&self) -> u32 {
+        unsafe { spdk_bdev_get_md_size(self.0.as_ptr()) }
+    }
+
+    /// the type of the T10 DIF protection information of the blocks, 0 if
+    /// the bdev is not formatted with protection information
+    pub fn dif_type(&self) -> u32 {
+        unsafe { spdk_bdev_get_dif_type(self.0.as_ptr()) }
+    }
+
     /// return the bdev size in bytes
     pub fn size_in_bytes(&self) -> u64 {
         self.num_blocks() * self.block_len() as u64
@@ -403,8 +505,14 @@ impl Bdev {
             Ok(BdevStats {
                 num_read_ops: stat.num_read_ops,
                 num_write_ops: stat.num_write_ops,
+                num_unmap_ops: stat.num_unmap_ops,
                 bytes_read: stat.bytes_read,
                 bytes_written: stat.bytes_written,
+                bytes_unmapped: stat.bytes_unmapped,
+                read_latency_ticks: stat.read_latency_ticks,
+                write_latency_ticks: stat.write_latency_ticks,
+                unmap_latency_ticks: stat.unmap_latency_ticks,
+                ticks_rate: stat.ticks_rate,
             })
         }
     }
@@ -412,6 +520,13 @@ impl Bdev {
     pub fn bdev_first() -> Option<Bdev> {
         Self::from_ptr(unsafe { spdk_bdev_first() })
     }
+
+    /// returns an iterator over the bdevs of the given type, which is the
+    /// name of the module driving them, e.g. "aio", "lvol" or "nexus"
+    pub fn iter_by_type(kind: &str) -> impl Iterator<Item = Bdev> + '_ {
+        BdevIter(unsafe { spdk_bdev_first() })
+            .filter(move |b| b.driver() == kind)
+    }
 }
 
 pub struct BdevIter(*mut spdk_bdev);
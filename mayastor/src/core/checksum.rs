@@ -0,0 +1,163 @@
+//!
+//! Checksums of blocks of data used to detect corruption. The algorithms
+//! trade speed for collision resistance: CRC32C is the fastest, and uses the
+//! CRC instructions of the CPU where available, xxHash64 is nearly as fast
+//! with a larger digest, and SHA-256 is the slowest but collisions are
+//! practically impossible.
+
+use std::{
+    convert::TryInto,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use sha2::{Digest, Sha256};
+
+use spdk_sys::spdk_crc32c_update;
+
+/// Algorithms to compute the checksum of a block with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Crc32c,
+    XxHash64,
+    Sha256,
+}
+
+impl Default for ChecksumAlgorithm {
+    fn default() -> Self {
+        Self::Crc32c
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crc32c" => Ok(Self::Crc32c),
+            "xxhash64" => Ok(Self::XxHash64),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(format!("unknown checksum algorithm {}", s)),
+        }
+    }
+}
+
+impl Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Crc32c => write!(f, "crc32c"),
+            Self::XxHash64 => write!(f, "xxhash64"),
+            Self::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl ChecksumAlgorithm {
+    /// size of the digest in bytes
+    pub fn len(self) -> usize {
+        match self {
+            Self::Crc32c => 4,
+            Self::XxHash64 => 8,
+            Self::Sha256 => 32,
+        }
+    }
+
+    /// compute the digest of the data
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Crc32c => crc32c(data).to_be_bytes().to_vec(),
+            Self::XxHash64 => xxhash64(data, 0).to_be_bytes().to_vec(),
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+/// CRC32C of the data, computed by SPDK which uses the CRC32 instructions of
+/// the CPU if it has them
+fn crc32c(data: &[u8]) -> u32 {
+    let crc = unsafe {
+        spdk_crc32c_update(data.as_ptr() as *const _, data.len() as u64, !0)
+    };
+    crc ^ !0
+}
+
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+
+fn xxh64_round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn xxh64_merge(acc: u64, val: u64) -> u64 {
+    (acc ^ xxh64_round(0, val))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data[.. 8].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes(data[.. 4].try_into().unwrap())
+}
+
+/// XXH64 of the data with the given seed
+fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    let mut rest = data;
+    let mut hash = if data.len() >= 32 {
+        let mut v = [
+            seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+            seed.wrapping_add(PRIME64_2),
+            seed,
+            seed.wrapping_sub(PRIME64_1),
+        ];
+        while rest.len() >= 32 {
+            for (i, acc) in v.iter_mut().enumerate() {
+                *acc = xxh64_round(*acc, read_u64(&rest[i * 8 ..]));
+            }
+            rest = &rest[32 ..];
+        }
+        let hash = v[0]
+            .rotate_left(1)
+            .wrapping_add(v[1].rotate_left(7))
+            .wrapping_add(v[2].rotate_left(12))
+            .wrapping_add(v[3].rotate_left(18));
+        v.iter().fold(hash, |hash, acc| xxh64_merge(hash, *acc))
+    } else {
+        seed.wrapping_add(PRIME64_5)
+    };
+
+    hash = hash.wrapping_add(data.len() as u64);
+    while rest.len() >= 8 {
+        hash ^= xxh64_round(0, read_u64(rest));
+        hash = hash
+            .rotate_left(27)
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(PRIME64_4);
+        rest = &rest[8 ..];
+    }
+    if rest.len() >= 4 {
+        hash ^= u64::from(read_u32(rest)).wrapping_mul(PRIME64_1);
+        hash = hash
+            .rotate_left(23)
+            .wrapping_mul(PRIME64_2)
+            .wrapping_add(PRIME64_3);
+        rest = &rest[4 ..];
+    }
+    for b in rest {
+        hash ^= u64::from(*b).wrapping_mul(PRIME64_5);
+        hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^ (hash >> 32)
+}
@@ -8,6 +8,7 @@ use std::{
     slice::{from_raw_parts, from_raw_parts_mut},
 };
 
+use crc::crc32;
 use snafu::Snafu;
 
 use spdk_sys::{
@@ -28,12 +29,14 @@ pub enum DmaError {
 pub struct DmaBuf {
     /// a raw pointer to the buffer
     buf: *mut c_void,
-    /// the length of the allocated buffer
+    /// the length of the buffer as asked for, the memory allocated for it
+    /// may be larger to keep its alignment
     length: u64,
 }
 
 impl DmaBuf {
-    /// convert the buffer to a slice
+    /// convert the buffer to a slice of its length, leaving out any memory
+    /// allocated beyond it
     pub fn as_slice(&self) -> &[u8] {
         unsafe { from_raw_parts(self.buf as *mut u8, self.length as usize) }
     }
@@ -43,6 +46,18 @@ impl DmaBuf {
         unsafe { from_raw_parts_mut(self.buf as *mut u8, self.length as usize) }
     }
 
+    /// Returns a boolean indicating if the buffers hold the same bytes, up
+    /// to their length. Buffers of different lengths never do.
+    pub fn eq_bytes(&self, other: &DmaBuf) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+
+    /// CRC32 (IEEE) of the bytes of the buffer up to its length, to check
+    /// data read back against the data written without keeping the latter
+    pub fn checksum_crc32(&self) -> u32 {
+        crc32::checksum_ieee(self.as_slice())
+    }
+
     /// fill the buffer with the given value
     pub fn fill(&mut self, val: u8) {
         unsafe {
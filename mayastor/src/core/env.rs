@@ -40,14 +40,19 @@ use spdk_sys::{
 };
 
 use crate::{
-    bdev::nexus::nexus_child_status_config::ChildStatusConfig,
+    bdev::{
+        nexus::nexus_child_status_config::ChildStatusConfig,
+        nexus_failover_all,
+    },
     core::{
         reactor::{Reactor, ReactorState, Reactors},
         Cores,
+        IoPool,
         Mthread,
     },
+    events,
     grpc,
-    logger,
+    logger::{self, LogFormat},
     subsys::{self, Config},
     target::iscsi,
 };
@@ -66,12 +71,84 @@ fn parse_mb(src: &str) -> Result<i32, String> {
         } else {
             value = val.get_bytes() as i32
         }
+        if value <= 0 {
+            return Err(format!("Invalid memory size {}", src));
+        }
         Ok(value)
     } else {
         Err(format!("Invalid argument {}", src))
     }
 }
 
+fn parse_hugepages(src: &str) -> Result<u32, String> {
+    match src.parse::<u32>() {
+        Ok(0) | Err(_) => Err(format!("Invalid number of hugepages {}", src)),
+        Ok(count) => Ok(count),
+    }
+}
+
+/// the cores selected by a reactor mask, which is hexadecimal with or without
+/// a 0x prefix
+fn mask_cores(mask: &str) -> Result<Vec<u32>, String> {
+    let digits = mask.trim_start_matches("0x").trim_start_matches("0X");
+    let bits = u128::from_str_radix(digits, 16)
+        .map_err(|_| format!("Invalid reactor mask {:?}", mask))?;
+    if bits == 0 {
+        return Err(format!("Reactor mask {} selects no cores", mask));
+    }
+    Ok((0 .. 128).filter(|core| bits & (1 << core) != 0).collect())
+}
+
+/// the cores of a core list of ids and ranges of ids, such as 0-3,6
+fn list_cores(list: &str) -> Result<Vec<u32>, String> {
+    let invalid = || format!("Invalid core list {:?}", list);
+    let mut cores = Vec::new();
+    for item in list.split(',') {
+        let mut ends = item.splitn(2, '-').map(|id| id.trim().parse::<u32>());
+        let first = ends.next().unwrap().map_err(|_| invalid())?;
+        let last = ends.next().unwrap_or(Ok(first)).map_err(|_| invalid())?;
+        if last < first {
+            return Err(invalid());
+        }
+        cores.extend(first ..= last);
+    }
+    cores.sort_unstable();
+    cores.dedup();
+    Ok(cores)
+}
+
+/// the cores the reactors run on, from the core list when there is one or
+/// else from the reactor mask, which must all be present on the host
+fn reactor_cores(
+    reactor_mask: &str,
+    core_list: Option<&str>,
+) -> Result<Vec<u32>, String> {
+    let cores = match core_list {
+        Some(list) => list_cores(list)?,
+        None => mask_cores(reactor_mask)?,
+    };
+    let present =
+        unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) }.max(1) as u32;
+    let missing = cores
+        .iter()
+        .filter(|core| **core >= present)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Cores {:?} of {} are not present, there are {} cores",
+            missing,
+            core_list.unwrap_or(reactor_mask),
+            present
+        ));
+    }
+    Ok(cores)
+}
+
+fn parse_reactor_mask(src: &str) -> Result<String, String> {
+    reactor_cores(src, None)?;
+    Ok(src.to_string())
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "Mayastor",
@@ -86,8 +163,16 @@ pub struct MayastorCliArgs {
     #[structopt(short = "L")]
     /// Enable logging for sub components.
     pub log_components: Vec<String>,
-    #[structopt(short = "m", default_value = "0x1")]
-    /// The reactor mask to be used for starting up the instance
+    #[structopt(long = "log-format", default_value = "text")]
+    /// Format of the log output, either text or json.
+    pub log_format: LogFormat,
+    #[structopt(
+    short = "m",
+    default_value = "0x1",
+    parse(try_from_str = parse_reactor_mask)
+    )]
+    /// The reactor mask to be used for starting up the instance, all of the
+    /// cores it selects must be present.
     pub reactor_mask: String,
     #[structopt(short = "N")]
     /// Name of the node where mayastor is running (ID used by control plane)
@@ -96,13 +181,20 @@ pub struct MayastorCliArgs {
     /// Hostname/IP and port (optional) of the message bus server.
     pub mbus_endpoint: Option<String>,
     /// The maximum amount of hugepage memory we are allowed to allocate in MiB
-    /// a value of 0 means no limits.
+    /// there is no limit when it is not given.
     #[structopt(
     short = "s",
     parse(try_from_str = parse_mb),
-    default_value = "0"
     )]
-    pub mem_size: i32,
+    pub mem_size: Option<i32>,
+    /// The maximum amount of hugepage memory we are allowed to allocate as a
+    /// number of hugepages of the system, instead of in MiB.
+    #[structopt(
+    long = "huge-pages",
+    conflicts_with = "mem-size",
+    parse(try_from_str = parse_hugepages)
+    )]
+    pub hugepages: Option<u32>,
     #[structopt(short = "u")]
     /// Disable the use of PCIe devices.
     pub no_pci: bool,
@@ -125,6 +217,14 @@ pub struct MayastorCliArgs {
     /// List of cores to run on instead of using the core mask. When specified
     /// it supersedes the core mask (-m) argument.
     pub core_list: Option<String>,
+    #[structopt(long = "bdev-io-pool-size")]
+    /// Number of bdev IO structures in the shared pool, overrides the value
+    /// of the config file.
+    pub bdev_io_pool_size: Option<u32>,
+    #[structopt(long = "bdev-io-cache-size")]
+    /// Number of bdev IO structures cached per thread, overrides the value of
+    /// the config file.
+    pub bdev_io_cache_size: Option<u32>,
 }
 
 /// Defaults are redefined here in case of using it during tests
@@ -136,18 +236,31 @@ impl Default for MayastorCliArgs {
             node_name: None,
             env_context: None,
             reactor_mask: "0x1".into(),
-            mem_size: 0,
+            mem_size: None,
+            hugepages: None,
             rpc_address: "/var/tmp/mayastor.sock".to_string(),
             no_pci: true,
             log_components: vec![],
+            log_format: LogFormat::Text,
             mayastor_config: None,
             child_status_config: None,
             hugedir: None,
             core_list: None,
+            bdev_io_pool_size: None,
+            bdev_io_cache_size: None,
         }
     }
 }
 
+impl MayastorCliArgs {
+    /// The ids of the cores the reactors run on, in ascending order. These
+    /// are the cores of the core list when given, or else of the reactor
+    /// mask.
+    pub fn core_list(&self) -> Result<Vec<u32>, String> {
+        reactor_cores(&self.reactor_mask, self.core_list.as_deref())
+    }
+}
+
 /// Global exit code of the program, initially set to -1 to capture double
 /// shutdown during test cases
 pub static GLOBAL_RC: Lazy<Arc<Mutex<i32>>> =
@@ -222,6 +335,8 @@ pub struct MayastorEnvironment {
     unlink_hugepage: bool,
     log_component: Vec<String>,
     core_list: Option<String>,
+    bdev_io_pool_size: Option<u32>,
+    bdev_io_cache_size: Option<u32>,
 }
 
 impl Default for MayastorEnvironment {
@@ -257,6 +372,8 @@ impl Default for MayastorEnvironment {
             unlink_hugepage: true,
             log_component: vec![],
             core_list: None,
+            bdev_io_pool_size: None,
+            bdev_io_cache_size: None,
         }
     }
 }
@@ -278,6 +395,11 @@ async fn do_shutdown(arg: *mut c_void) {
         warn!("Mayastor stopped non-zero: {}", rc);
     }
 
+    // move the initiators of the nexuses to other paths while the
+    // subsystems are still there
+    nexus_failover_all().await;
+
+    events::stop_notify_poller();
     iscsi::fini();
     unsafe {
         spdk_rpc_finish();
@@ -322,6 +444,24 @@ extern "C" fn mayastor_signal_handler(signo: i32) {
     };
 }
 
+#[inline(always)]
+unsafe extern "C" fn reload_trampoline(_: *mut c_void) {
+    if let Err(e) = Config::get().reload() {
+        error!("{}", e);
+    }
+}
+
+/// called on SIGHUP, reloads the config file
+extern "C" fn mayastor_reload_handler(signo: i32) {
+    info!("Received SIGNO: {}", signo);
+    unsafe {
+        spdk_thread_send_critical_msg(
+            Mthread::get_init().into_raw(),
+            Some(reload_trampoline),
+        );
+    };
+}
+
 #[derive(Debug)]
 struct SubsystemCtx {
     rpc: CString,
@@ -338,18 +478,54 @@ impl MayastorEnvironment {
             mayastor_config: args.mayastor_config,
             child_status_config: args.child_status_config,
             log_component: args.log_components,
-            mem_size: args.mem_size,
+            mem_size: Self::mem_size_mb(args.mem_size, args.hugepages),
             no_pci: args.no_pci,
             reactor_mask: args.reactor_mask,
             rpc_addr: args.rpc_address,
             hugedir: args.hugedir,
             env_context: args.env_context,
             core_list: args.core_list,
+            bdev_io_pool_size: args.bdev_io_pool_size,
+            bdev_io_cache_size: args.bdev_io_cache_size,
             ..Default::default()
         }
         .setup_static()
     }
 
+    /// The hugepage memory in MiB given to the EAL, 0 meaning no limit as
+    /// when neither the size nor the number of hugepages is given.
+    fn mem_size_mb(mem_size: Option<i32>, hugepages: Option<u32>) -> i32 {
+        match (mem_size, hugepages) {
+            (Some(size), _) => size,
+            (None, Some(count)) => match IoPool::hugepage_size() {
+                Some(size) => ((u64::from(count) * size) >> 20) as i32,
+                None => panic!(
+                    "Failed to find the size of the hugepages for {} of them",
+                    count
+                ),
+            },
+            (None, None) => 0,
+        }
+    }
+
+    /// warn when more hugepage memory is asked for than the system has free,
+    /// which is not fatal as hugepages may be freed before they are needed
+    fn check_mem_size(&self) {
+        if self.mem_size <= 0 {
+            return;
+        }
+        if let Some(free) = IoPool::hugepages_free() {
+            let size = (self.mem_size as u64) << 20;
+            if size > free {
+                warn!(
+                    "{} MiB of hugepage memory asked for, only {} MiB free",
+                    self.mem_size,
+                    free >> 20
+                );
+            }
+        }
+    }
+
     fn setup_static(self) -> Self {
         MAYASTOR_DEFAULT_ENV.get_or_init(|| self.clone());
         self
@@ -379,9 +555,25 @@ impl MayastorEnvironment {
             })
         }
         .unwrap();
+
+        unsafe {
+            signal_hook::register(signal_hook::SIGHUP, || {
+                mayastor_reload_handler(signal_hook::SIGHUP)
+            })
+        }
+        .unwrap();
     }
 
     /// construct an array of options to be passed to EAL and start it
+    /// refuse cores which are not present before the EAL fails on them with
+    /// less of an explanation
+    fn check_cores(&self) {
+        match reactor_cores(&self.reactor_mask, self.core_list.as_deref()) {
+            Ok(cores) => info!("Starting reactors on cores {:?}", cores),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
     fn initialize_eal(&self) {
         let mut args: Vec<CString> = Vec::new();
 
@@ -590,20 +782,47 @@ impl MayastorEnvironment {
     /// load the config and apply it before any subsystems have started.
     /// there is currently no run time check that enforces this.
     fn load_yaml_config(&self) {
-        let cfg = if let Some(yaml) = &self.mayastor_config {
-            info!("loading YAML config file {}", yaml);
-            Config::get_or_init(|| {
+        let cfg = Config::get_or_init(|| {
+            let mut cfg = if let Some(yaml) = &self.mayastor_config {
+                info!("loading YAML config file {}", yaml);
                 if let Ok(cfg) = Config::read(yaml) {
                     cfg
                 } else {
                     // if the configuration is invalid exit early
                     panic!("Failed to load the mayastor configuration")
                 }
-            })
-        } else {
-            Config::get_or_init(Config::default)
-        };
+            } else {
+                Config::default()
+            };
+
+            // the sizes of the bdev IO pool given as arguments take
+            // precedence over the config file
+            if let Some(size) = self.bdev_io_pool_size {
+                cfg.bdev_opts.bdev_io_pool_size = size;
+            }
+            if let Some(size) = self.bdev_io_cache_size {
+                cfg.bdev_opts.bdev_io_cache_size = size;
+            }
+            cfg
+        });
         cfg.apply();
+
+        if let Some(level) = &cfg.log_level {
+            if let Err(e) = logger::set_filter(level) {
+                warn!("Failed to set the log level {}: {}", level, e);
+            }
+        }
+    }
+
+    /// check that the bdev IO pool fits before the subsystems allocate it
+    fn check_io_pool(&self) {
+        let cores = Cores::count().into_iter().count() as u32;
+        if let Err(e) =
+            IoPool::check(&Config::get().bdev_opts, self.mem_size, cores)
+        {
+            // if the pool can not be allocated exit early
+            panic!("Invalid bdev IO pool size: {}", e)
+        }
     }
 
     #[allow(dead_code)]
@@ -644,6 +863,10 @@ impl MayastorEnvironment {
 
         self.load_child_status();
 
+        self.check_mem_size();
+
+        self.check_cores();
+
         // bootstrap DPDK and its magic
         self.initialize_eal();
 
@@ -652,6 +875,8 @@ impl MayastorEnvironment {
             Cores::count().into_iter().count()
         );
 
+        self.check_io_pool();
+
         // setup our signal handlers
         self.install_signal_handlers();
 
@@ -697,6 +922,9 @@ impl MayastorEnvironment {
             assert_eq!(receiver.await.unwrap(), true);
         });
 
+        // publish the bdevs registered and unregistered from here on
+        events::start_notify_poller();
+
         // load any bdevs that need to be created
         Config::get().import_bdevs();
 
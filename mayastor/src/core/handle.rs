@@ -1,39 +1,124 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
     convert::TryFrom,
     fmt::Debug,
     mem::ManuallyDrop,
     os::raw::c_void,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-use futures::channel::oneshot;
+use futures::{
+    channel::oneshot,
+    future::{select, Either},
+};
+use futures_timer::Delay;
 use nix::errno::Errno;
 use serde::export::{fmt::Error, Formatter};
 
 use spdk_sys::{
+    iovec,
+    spdk_bdev_abort,
+    spdk_bdev_compare_blocks,
+    spdk_bdev_comparev_and_writev_blocks,
     spdk_bdev_desc,
+    spdk_bdev_flush,
     spdk_bdev_free_io,
     spdk_bdev_io,
+    spdk_bdev_io_wait_entry,
     spdk_bdev_nvme_admin_passthru_ro,
+    spdk_bdev_queue_io_wait,
     spdk_bdev_read,
+    spdk_bdev_readv,
     spdk_bdev_reset,
+    spdk_bdev_unmap,
     spdk_bdev_write,
+    spdk_bdev_write_zeroes,
+    spdk_bdev_writev,
     spdk_io_channel,
 };
 
 use crate::{
-    bdev::nexus::nexus_io::nvme_admin_opc,
-    core::{Bdev, CoreError, Descriptor, DmaBuf, DmaError, IoChannel},
+    bdev::{
+        nexus::nexus_io::{nvme_admin_opc, IoType},
+        Bio,
+    },
+    core::{
+        prefetch::{PrefetchStats, Prefetcher},
+        Bdev,
+        CoreError,
+        Descriptor,
+        DmaBuf,
+        DmaError,
+        IoChannel,
+        IoPool,
+        MediaErrorStatusCode,
+        NvmeStatus,
+    },
     ffihelper::cb_arg,
+    logger::RATE_LIMITED,
     subsys,
 };
 
+/// How [`BdevHandle::unmap_at_with_mode`] treats the parts of its range
+/// which are not whole units of the deallocation granularity of the device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnmapMode {
+    /// unmap the whole range, leaving it to the device to zero the parts of
+    /// it which are not whole units, as a file backing an aio bdev does
+    Default,
+    /// unmap only the whole units within the range, leaving the parts of it
+    /// which are not untouched, so that what is unmapped is deallocated
+    AlignedOnly,
+}
+
+/// What [`BdevHandle::unmap_at_with_mode`] did with each part of its range,
+/// in blocks of the bdev.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Unmapped {
+    /// blocks within whole units, deallocated by the device
+    pub deallocated: u64,
+    /// blocks outside whole units which the device zeroes rather than
+    /// deallocates, with `UnmapMode::Default`
+    pub zeroed: u64,
+    /// blocks outside whole units which are left untouched, with
+    /// `UnmapMode::AlignedOnly`
+    pub skipped: u64,
+}
+
+/// how long drain_and_close() waits for the IO in flight to complete once
+/// it has been aborted
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// the callback argument of an IO submitted through a handle, which keeps
+/// the IO in the set of those in flight on the handle until it completes
+struct IoCtx<T> {
+    sender: oneshot::Sender<T>,
+    inflight: Arc<Mutex<HashSet<usize>>>,
+}
+
+impl<T> IoCtx<T> {
+    /// the IO submitted with this context as its callback argument has
+    /// completed, pass on its result if it is still waited for
+    fn complete(self, arg: *mut c_void, result: T) {
+        self.inflight.lock().unwrap().remove(&(arg as usize));
+        let _ = self.sender.send(result);
+    }
+}
+
 /// A handle to a bdev, is an interface to submit IO. The ['Descriptor'] may be
 /// shared between cores freely. The ['IoChannel'] however, must be allocated on
 /// the core where the IO is submitted from.
 pub struct BdevHandle {
     pub desc: ManuallyDrop<Arc<Descriptor>>,
     pub channel: ManuallyDrop<IoChannel>,
+    /// reads ahead when prefetching is enabled
+    prefetch: RefCell<Option<Prefetcher>>,
+    /// read back and compare every write
+    verify_writes: Cell<bool>,
+    /// callback arguments of the IOs submitted which have not completed
+    inflight: Arc<Mutex<HashSet<usize>>>,
 }
 
 impl BdevHandle {
@@ -67,11 +152,56 @@ impl BdevHandle {
         BdevHandle::try_from(Arc::new(desc))
     }
 
-    /// close the BdevHandle causing
+    /// Close the handle, releasing its channel and descriptor right away.
+    /// No IO submitted through the handle may be in flight, which is the
+    /// case unless a future submitting IO has been dropped before the IO
+    /// completed. Use drain_and_close() when that may have happened.
     pub fn close(self) {
+        debug_assert_eq!(
+            self.inflight(),
+            0,
+            "{} closed with IO in flight",
+            self.get_bdev().name()
+        );
         drop(self);
     }
 
+    /// Close the handle once no IO submitted through it is in flight. The
+    /// IO in flight is aborted first, or the bdev is reset when it can not
+    /// be, after which the IO completes. IO which does not complete within
+    /// DRAIN_TIMEOUT regardless keeps the channel and the descriptor in
+    /// use, so they are leaked rather than released from under it.
+    pub async fn drain_and_close(self) {
+        let inflight = self.inflight.lock().unwrap().clone();
+        for arg in inflight {
+            // aborting an IO may complete others, by resetting the bdev
+            if self.inflight.lock().unwrap().contains(&arg) {
+                self.abort(arg as *mut c_void).await;
+            }
+        }
+
+        let start = Instant::now();
+        while self.inflight() > 0 {
+            if start.elapsed() > DRAIN_TIMEOUT {
+                error!(
+                    "{} IOs on {} did not complete after being aborted, \
+                     leaking the handle",
+                    self.inflight(),
+                    self.get_bdev().name()
+                );
+                std::mem::forget(self);
+                return;
+            }
+            Delay::new(Duration::from_millis(1)).await;
+        }
+        self.close();
+    }
+
+    /// number of IOs submitted through the handle which have not completed
+    pub fn inflight(&self) -> usize {
+        self.inflight.lock().unwrap().len()
+    }
+
     /// get the bdev associated with this handle
     pub fn get_bdev(&self) -> Bdev {
         self.desc.get_bdev()
@@ -82,6 +212,120 @@ impl BdevHandle {
         (self.desc.as_ptr(), self.channel.as_ptr())
     }
 
+    /// Enable prefetching: once reads through this handle are sequential,
+    /// the given number of bytes following the last read are read ahead.
+    pub fn enable_prefetch(&self, window: u64) {
+        *self.prefetch.borrow_mut() = Some(Prefetcher::new(window));
+    }
+
+    /// Disable prefetching, dropping any data read ahead.
+    pub fn disable_prefetch(&self) {
+        self.prefetch.borrow_mut().take();
+    }
+
+    /// Counters of the prefetcher, None if prefetching is disabled.
+    pub fn prefetch_stats(&self) -> Option<PrefetchStats> {
+        self.prefetch.borrow().as_ref().map(|p| p.stats())
+    }
+
+    /// Enable read-after-write verification: every write through this handle
+    /// is read back and compared with the data written, and fails with
+    /// WriteVerifyFailed when the device returns different data. This
+    /// catches failing hardware early, at the cost of a read of the same
+    /// size and a comparison after every write, which at least doubles the
+    /// latency of writes and roughly halves the write throughput. Writes to
+    /// the same blocks through other handles while a write is being verified
+    /// are reported as a mismatch.
+    pub fn enable_write_verify(&self) {
+        self.verify_writes.set(true);
+    }
+
+    /// Disable read-after-write verification.
+    pub fn disable_write_verify(&self) {
+        self.verify_writes.set(false);
+    }
+
+    /// Whether writes through this handle are read back and compared.
+    pub fn write_verify(&self) -> bool {
+        self.verify_writes.get()
+    }
+
+    /// read back the data written at the given offset and compare it with
+    /// the buffer written, when read-after-write verification is enabled
+    async fn verify_write(
+        &self,
+        offset: u64,
+        buffer: &DmaBuf,
+        timeout: Option<Duration>,
+    ) -> Result<(), CoreError> {
+        if !self.verify_writes.get() {
+            return Ok(());
+        }
+
+        let mut read = self.dma_malloc(buffer.len()).map_err(|source| {
+            CoreError::DmaAlloc {
+                source,
+                len: buffer.len(),
+            }
+        })?;
+        self.read_direct(offset, &mut read, timeout).await?;
+
+        if read.as_slice() != buffer.as_slice() {
+            error!(
+                "{}: data read back at offset {} length {} differs from the \
+                 data written",
+                self.get_bdev().name(),
+                offset,
+                buffer.len()
+            );
+            return Err(CoreError::WriteVerifyFailed {
+                offset,
+                len: buffer.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Serve the read from the data read ahead, returns false if the data
+    /// has not been read ahead.
+    async fn read_prefetched(&self, offset: u64, buffer: &mut DmaBuf) -> bool {
+        let (window, writes) = match self.prefetch.borrow_mut().as_mut() {
+            Some(p) => (p.read(offset, buffer.len()), p.writes()),
+            None => return false,
+        };
+
+        let window = match window {
+            Some(window) => window.ready().await,
+            None => None,
+        };
+
+        // the data is stale if there has been a write meanwhile
+        match (window, self.prefetch.borrow_mut().as_mut()) {
+            (Some(window), Some(p)) if p.writes() == writes => {
+                window.copy_to(offset, buffer);
+                p.hit(window, offset, buffer.len());
+                p.read_ahead(&self.desc);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// read ahead after a read, if prefetching is enabled
+    fn read_ahead(&self) {
+        if let Some(p) = self.prefetch.borrow_mut().as_mut() {
+            p.read_ahead(&self.desc);
+        }
+    }
+
+    /// drop the data read ahead which is about to be overwritten, or which
+    /// has been read while a write was in flight
+    fn prefetch_write(&self, offset: u64, len: u64) {
+        if let Some(p) = self.prefetch.borrow_mut().as_mut() {
+            p.write(offset, len);
+        }
+    }
+
     /// Allocate memory from the memory pool (the mem is zeroed out)
     /// with given size and proper alignment for the bdev.
     pub fn dma_malloc(&self, size: u64) -> Result<DmaBuf, DmaError> {
@@ -90,21 +334,54 @@ impl BdevHandle {
 
     /// private io completion callback that sends back the success status of the
     /// IO. When the IO is freed, it is returned to the memory pool. The
-    /// buffer is not freed.
+    /// buffer is not freed. The receiver is gone when the future waiting for
+    /// the IO has been dropped, in which case the status is dropped as well.
     extern "C" fn io_completion_cb(
         io: *mut spdk_bdev_io,
         success: bool,
         arg: *mut c_void,
     ) {
-        let sender = unsafe {
-            Box::from_raw(arg as *const _ as *mut oneshot::Sender<bool>)
+        let ctx = unsafe { Box::from_raw(arg as *mut IoCtx<bool>) };
+
+        unsafe {
+            spdk_bdev_free_io(io);
+        }
+
+        ctx.complete(arg, success);
+    }
+
+    /// completion callback passing on the NVMe status of a failed IO, if the
+    /// future waiting for the IO is still there to receive it
+    extern "C" fn io_status_cb(
+        io: *mut spdk_bdev_io,
+        success: bool,
+        arg: *mut c_void,
+    ) {
+        let ctx =
+            unsafe { Box::from_raw(arg as *mut IoCtx<Option<NvmeStatus>>) };
+
+        let status = if success {
+            None
+        } else {
+            Some(NvmeStatus::from(Bio::from(io)))
         };
 
         unsafe {
             spdk_bdev_free_io(io);
         }
 
-        sender.send(success).expect("io completion error");
+        ctx.complete(arg, status);
+    }
+
+    /// the IO failed as the protection information did not match the data
+    fn is_guard_error(status: &NvmeStatus) -> bool {
+        status.media_status_code()
+            == Some(MediaErrorStatusCode::GuardCheckError)
+    }
+
+    /// the IO failed as the data did not match the data compared
+    fn is_miscompare(status: &NvmeStatus) -> bool {
+        status.media_status_code() == Some(MediaErrorStatusCode::CompareFailure)
     }
 
     /// write the ['DmaBuf'] to the given offset. This function is implemented
@@ -114,99 +391,768 @@ impl BdevHandle {
         offset: u64,
         buffer: &DmaBuf,
     ) -> Result<usize, CoreError> {
-        let (s, r) = oneshot::channel::<bool>();
-        let errno = unsafe {
-            spdk_bdev_write(
-                self.desc.as_ptr(),
-                self.channel.as_ptr(),
-                **buffer,
+        self.write_at_with(offset, buffer, None).await
+    }
+
+    /// write the ['DmaBuf'] to the given offset like write_at, but abort the
+    /// IO and fail with WriteTimedOut when it does not complete in time.
+    pub async fn write_at_timeout(
+        &self,
+        offset: u64,
+        buffer: &DmaBuf,
+        timeout: Duration,
+    ) -> Result<usize, CoreError> {
+        self.write_at_with(offset, buffer, Some(timeout)).await
+    }
+
+    /// write the ['DmaBuf'] to the given offset, within the timeout if any
+    async fn write_at_with(
+        &self,
+        offset: u64,
+        buffer: &DmaBuf,
+        timeout: Option<Duration>,
+    ) -> Result<usize, CoreError> {
+        self.prefetch_write(offset, buffer.len());
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        let arg = self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_write(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    **buffer,
+                    offset,
+                    buffer.len() as u64,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::WriteDispatch {
+                source: Errno::from_i32(errno),
                 offset,
-                buffer.len() as u64,
-                Some(Self::io_completion_cb),
-                cb_arg(s),
-            )
-        };
+                len: buffer.len(),
+            })?;
+
+        let status = self.wait_io(r, arg, timeout).await;
+        self.prefetch_write(offset, buffer.len());
+        match status {
+            Ok(None) => {
+                self.verify_write(offset, buffer, timeout).await?;
+                Ok(buffer.len() as usize)
+            }
+            Ok(Some(status)) if Self::is_guard_error(&status) => {
+                Err(CoreError::GuardCheckFailed {
+                    offset,
+                    len: buffer.len(),
+                })
+            }
+            Ok(Some(_)) => Err(CoreError::WriteFailed {
+                offset,
+                len: buffer.len(),
+            }),
+            Err(timeout) => Err(CoreError::WriteTimedOut {
+                offset,
+                len: buffer.len(),
+                timeout,
+            }),
+        }
+    }
+
+    /// read at given offset into the ['DmaBuf'], when prefetching is enabled
+    /// the data may have been read ahead already
+    pub async fn read_at(
+        &self,
+        offset: u64,
+        buffer: &mut DmaBuf,
+    ) -> Result<u64, CoreError> {
+        self.read_at_with(offset, buffer, None).await
+    }
+
+    /// read at given offset into the ['DmaBuf'] like read_at, but abort the
+    /// IO and fail with ReadTimedOut when it does not complete in time.
+    pub async fn read_at_timeout(
+        &self,
+        offset: u64,
+        buffer: &mut DmaBuf,
+        timeout: Duration,
+    ) -> Result<u64, CoreError> {
+        self.read_at_with(offset, buffer, Some(timeout)).await
+    }
+
+    /// read at given offset into the ['DmaBuf'], within the timeout if any
+    async fn read_at_with(
+        &self,
+        offset: u64,
+        buffer: &mut DmaBuf,
+        timeout: Option<Duration>,
+    ) -> Result<u64, CoreError> {
+        if self.read_prefetched(offset, buffer).await {
+            return Ok(buffer.len());
+        }
 
-        if errno != 0 {
-            return Err(CoreError::WriteDispatch {
+        let len = self.read_direct(offset, buffer, timeout).await?;
+        self.read_ahead();
+        Ok(len)
+    }
+
+    /// read at given offset into the ['DmaBuf'] from the bdev itself, within
+    /// the timeout if any
+    async fn read_direct(
+        &self,
+        offset: u64,
+        buffer: &mut DmaBuf,
+        timeout: Option<Duration>,
+    ) -> Result<u64, CoreError> {
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        let arg = self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_read(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    **buffer,
+                    offset,
+                    buffer.len() as u64,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::ReadDispatch {
                 source: Errno::from_i32(errno),
                 offset,
                 len: buffer.len(),
+            })?;
+
+        match self.wait_io(r, arg, timeout).await {
+            Ok(None) => Ok(buffer.len()),
+            Ok(Some(status)) if Self::is_guard_error(&status) => {
+                Err(CoreError::GuardCheckFailed {
+                    offset,
+                    len: buffer.len(),
+                })
+            }
+            Ok(Some(_)) => Err(CoreError::ReadFailed {
+                offset,
+                len: buffer.len(),
+            }),
+            Err(timeout) => Err(CoreError::ReadTimedOut {
+                offset,
+                len: buffer.len(),
+                timeout,
+            }),
+        }
+    }
+
+    /// Returns the iovecs of the buffers and their combined length, which
+    /// must be a multiple of the block length, as must the offset. The
+    /// iovecs point into the buffers, so must not outlive them.
+    fn iovs(
+        &self,
+        offset: u64,
+        buffers: &[DmaBuf],
+    ) -> Result<(Vec<iovec>, u64), CoreError> {
+        let iovs = buffers
+            .iter()
+            .map(|b| iovec {
+                iov_base: **b,
+                iov_len: b.len(),
+            })
+            .collect::<Vec<_>>();
+        let len = buffers.iter().map(|b| b.len()).sum::<u64>();
+        self.check_aligned(offset, len)?;
+        Ok((iovs, len))
+    }
+
+    /// IO which is not for whole blocks, or for none at all, is refused
+    fn check_aligned(&self, offset: u64, len: u64) -> Result<(), CoreError> {
+        let block_len = u64::from(self.get_bdev().block_len());
+        if len == 0 || offset % block_len != 0 || len % block_len != 0 {
+            return Err(CoreError::UnalignedIo {
+                offset,
+                len,
             });
         }
+        Ok(())
+    }
+
+    /// Write the ['DmaBuf']s to the given offset as one IO, the data of each
+    /// buffer following that of the one before it. Their combined length
+    /// must be block aligned.
+    pub async fn writev_at(
+        &self,
+        offset: u64,
+        buffers: &[DmaBuf],
+    ) -> Result<usize, CoreError> {
+        let (mut iovs, len) = self.iovs(offset, buffers)?;
+        self.prefetch_write(offset, len);
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_writev(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    iovs.as_mut_ptr(),
+                    iovs.len() as i32,
+                    offset,
+                    len,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::WriteDispatch {
+                source: Errno::from_i32(errno),
+                offset,
+                len,
+            })?;
+
+        let status = r.await.expect("Failed awaiting writev IO");
+        self.prefetch_write(offset, len);
+        match status {
+            None => {
+                let mut at = offset;
+                for buffer in buffers {
+                    self.verify_write(at, buffer, None).await?;
+                    at += buffer.len();
+                }
+                Ok(len as usize)
+            }
+            Some(status) if Self::is_guard_error(&status) => {
+                Err(CoreError::GuardCheckFailed {
+                    offset,
+                    len,
+                })
+            }
+            Some(_) => Err(CoreError::WriteFailed {
+                offset,
+                len,
+            }),
+        }
+    }
+
+    /// Read at the given offset into the ['DmaBuf']s as one IO, each buffer
+    /// being filled after the one before it. Their combined length must be
+    /// block aligned.
+    pub async fn readv_at(
+        &self,
+        offset: u64,
+        buffers: &mut [DmaBuf],
+    ) -> Result<u64, CoreError> {
+        let (mut iovs, len) = self.iovs(offset, buffers)?;
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_readv(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    iovs.as_mut_ptr(),
+                    iovs.len() as i32,
+                    offset,
+                    len,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::ReadDispatch {
+                source: Errno::from_i32(errno),
+                offset,
+                len,
+            })?;
+
+        match r.await.expect("Failed awaiting readv IO") {
+            None => Ok(len),
+            Some(status) if Self::is_guard_error(&status) => {
+                Err(CoreError::GuardCheckFailed {
+                    offset,
+                    len,
+                })
+            }
+            Some(_) => Err(CoreError::ReadFailed {
+                offset,
+                len,
+            }),
+        }
+    }
+
+    /// Zero the given range of the bdev without transferring any data, SPDK
+    /// writes zeroed buffers instead should the bdev not support it. The
+    /// range must be block aligned.
+    pub async fn write_zeroes_at(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), CoreError> {
+        self.check_aligned(offset, len)?;
+        self.prefetch_write(offset, len);
+        let (s, r) = oneshot::channel::<bool>();
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_write_zeroes(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    offset,
+                    len,
+                    Some(Self::io_completion_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::WriteZeroesDispatch {
+                source: Errno::from_i32(errno),
+                offset,
+                len,
+            })?;
 
-        if r.await.expect("Failed awaiting write IO") {
-            Ok(buffer.len() as usize)
+        let success = r.await.expect("Failed awaiting write zeroes IO");
+        self.prefetch_write(offset, len);
+        if success {
+            Ok(())
         } else {
-            Err(CoreError::WriteFailed {
+            Err(CoreError::WriteZeroesFailed {
                 offset,
-                len: buffer.len(),
+                len,
             })
         }
     }
 
-    /// read at given offset into the ['DmaBuf']
-    pub async fn read_at(
+    /// Unmap the given range of the bdev. The range must be block aligned,
+    /// how much of it is deallocated is up to the device: a file backing an
+    /// aio bdev, for one, has only the blocks of the file system wholly
+    /// within the range punched out and the remainder zeroed.
+    pub async fn unmap_at(
         &self,
         offset: u64,
-        buffer: &mut DmaBuf,
-    ) -> Result<u64, CoreError> {
+        len: u64,
+    ) -> Result<(), CoreError> {
+        let block_len = u64::from(self.get_bdev().block_len());
+        self.unmap_at_with_mode(offset, len, block_len, UnmapMode::Default)
+            .await
+            .map(|_| ())
+    }
+
+    /// Unmap the given range of the bdev, given that the device deallocates
+    /// in units of `granularity` bytes, e.g. the block size of the file
+    /// system of a file backing an aio bdev. The offset and the length of
+    /// the range must be multiples of the block size of the bdev, as must
+    /// the granularity. Units start at offset 0 of the bdev, so the head of
+    /// the range up to the first multiple of the granularity and its tail
+    /// from the last one are not whole units, nor is a range within a
+    /// single unit. The mode decides whether those are unmapped, and so
+    /// zeroed by the device, or left untouched.
+    pub async fn unmap_at_with_mode(
+        &self,
+        offset: u64,
+        len: u64,
+        granularity: u64,
+        mode: UnmapMode,
+    ) -> Result<Unmapped, CoreError> {
+        let bdev = self.get_bdev();
+        if !bdev.io_type_supported(IoType::Unmap) {
+            return Err(CoreError::UnmapNotSupported {
+                name: bdev.name(),
+            });
+        }
+        let block_len = u64::from(bdev.block_len());
+        if granularity == 0 || granularity % block_len != 0 {
+            return Err(CoreError::UnmapGranularity {
+                granularity,
+                block_len,
+            });
+        }
+        self.check_aligned(offset, len)?;
+
+        let end = offset + len;
+        let first = (offset + granularity - 1) / granularity * granularity;
+        let last = end / granularity * granularity;
+        let whole = last.saturating_sub(first);
+        let partial = (len - whole) / block_len;
+
+        match mode {
+            UnmapMode::Default => {
+                self.unmap(offset, len).await?;
+                Ok(Unmapped {
+                    deallocated: whole / block_len,
+                    zeroed: partial,
+                    skipped: 0,
+                })
+            }
+            UnmapMode::AlignedOnly => {
+                if whole > 0 {
+                    self.unmap(first, whole).await?;
+                }
+                Ok(Unmapped {
+                    deallocated: whole / block_len,
+                    zeroed: 0,
+                    skipped: partial,
+                })
+            }
+        }
+    }
+
+    /// submit the unmap of the block aligned range
+    async fn unmap(&self, offset: u64, len: u64) -> Result<(), CoreError> {
+        self.prefetch_write(offset, len);
         let (s, r) = oneshot::channel::<bool>();
-        let errno = unsafe {
-            spdk_bdev_read(
-                self.desc.as_ptr(),
-                self.channel.as_ptr(),
-                **buffer,
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_unmap(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    offset,
+                    len,
+                    Some(Self::io_completion_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::UnmapDispatch {
+                source: Errno::from_i32(errno),
                 offset,
-                buffer.len() as u64,
-                Some(Self::io_completion_cb),
-                cb_arg(s),
-            )
-        };
+                len,
+            })?;
 
-        if errno != 0 {
-            return Err(CoreError::ReadDispatch {
+        let success = r.await.expect("Failed awaiting unmap IO");
+        self.prefetch_write(offset, len);
+        if success {
+            Ok(())
+        } else {
+            Err(CoreError::UnmapFailed {
+                offset,
+                len,
+            })
+        }
+    }
+
+    /// Compare the data at the given offset with the ['DmaBuf'], failing with
+    /// Miscompare should it differ. SPDK reads and compares the data itself
+    /// should the bdev not support compares.
+    pub async fn compare_at(
+        &self,
+        offset: u64,
+        buffer: &DmaBuf,
+    ) -> Result<(), CoreError> {
+        self.check_aligned(offset, buffer.len())?;
+        let block_len = u64::from(self.get_bdev().block_len());
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_compare_blocks(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    **buffer,
+                    offset / block_len,
+                    buffer.len() / block_len,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::CompareDispatch {
                 source: Errno::from_i32(errno),
                 offset,
                 len: buffer.len(),
+            })?;
+
+        match r.await.expect("Failed awaiting compare IO") {
+            None => Ok(()),
+            Some(status) if Self::is_miscompare(&status) => {
+                Err(CoreError::Miscompare {
+                    offset,
+                    len: buffer.len(),
+                })
+            }
+            Some(_) => Err(CoreError::CompareFailed {
+                offset,
+                len: buffer.len(),
+            }),
+        }
+    }
+
+    /// Write the write ['DmaBuf'] to the given offset only if the data there
+    /// matches the compare ['DmaBuf'], as one atomic operation, failing with
+    /// Miscompare otherwise. Both buffers must be of the same length, which
+    /// the bdev may limit further. Only bdevs which support the fused
+    /// operation themselves are written to, never unconditionally.
+    pub async fn compare_and_write_at(
+        &self,
+        offset: u64,
+        compare: &DmaBuf,
+        write: &DmaBuf,
+    ) -> Result<(), CoreError> {
+        let bdev = self.get_bdev();
+        if !bdev.io_type_supported(IoType::CompareAndWrite) {
+            return Err(CoreError::CompareAndWriteNotSupported {
+                name: bdev.name(),
+            });
+        }
+        self.check_aligned(offset, write.len())?;
+        if compare.len() != write.len() {
+            return Err(CoreError::CompareAndWriteDispatch {
+                source: Errno::EINVAL,
+                offset,
+                len: write.len(),
             });
         }
 
-        if r.await.expect("Failed awaiting read IO") {
-            Ok(buffer.len())
-        } else {
-            Err(CoreError::ReadFailed {
+        let block_len = u64::from(bdev.block_len());
+        let mut compare_iov = iovec {
+            iov_base: **compare,
+            iov_len: compare.len(),
+        };
+        let mut write_iov = iovec {
+            iov_base: **write,
+            iov_len: write.len(),
+        };
+        self.prefetch_write(offset, write.len());
+        let (s, r) = oneshot::channel::<Option<NvmeStatus>>();
+        self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_comparev_and_writev_blocks(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    &mut compare_iov,
+                    1,
+                    &mut write_iov,
+                    1,
+                    offset / block_len,
+                    write.len() / block_len,
+                    Some(Self::io_status_cb),
+                    arg,
+                )
+            })
+            .await
+            .map_err(|errno| CoreError::CompareAndWriteDispatch {
+                source: Errno::from_i32(errno),
                 offset,
-                len: buffer.len(),
+                len: write.len(),
+            })?;
+
+        let status = r.await.expect("Failed awaiting compare and write IO");
+        self.prefetch_write(offset, write.len());
+        match status {
+            None => self.verify_write(offset, write, None).await,
+            Some(status) if Self::is_miscompare(&status) => {
+                Err(CoreError::Miscompare {
+                    offset,
+                    len: write.len(),
+                })
+            }
+            Some(_) => Err(CoreError::CompareAndWriteFailed {
+                offset,
+                len: write.len(),
+            }),
+        }
+    }
+
+    /// Submit an IO with the callback argument passed to the submit
+    /// function, which carries the sender of its completion, and return the
+    /// argument. The IO is in flight on the handle until it completes. While
+    /// the bdev IO pool is exhausted, wait for an IO structure to be
+    /// returned to the pool and submit again rather than failing the IO.
+    async fn submit<T, F>(
+        &self,
+        sender: oneshot::Sender<T>,
+        mut submit: F,
+    ) -> Result<*mut c_void, i32>
+    where
+        F: FnMut(*mut c_void) -> i32,
+    {
+        let arg = Box::into_raw(Box::new(IoCtx {
+            sender,
+            inflight: Arc::clone(&self.inflight),
+        })) as *mut c_void;
+        // the IO is in flight before it is submitted, as it may complete
+        // before we get to record it
+        self.inflight.lock().unwrap().insert(arg as usize);
+        let failed = |errno| {
+            self.inflight.lock().unwrap().remove(&(arg as usize));
+            drop(unsafe { Box::from_raw(arg as *mut IoCtx<T>) });
+            Err(errno)
+        };
+
+        loop {
+            let errno = submit(arg);
+            if errno == 0 {
+                return Ok(arg);
+            }
+            if errno != -libc::ENOMEM {
+                return failed(errno);
+            }
+
+            IoPool::exhausted();
+            let (s, r) = oneshot::channel::<bool>();
+            let mut entry = spdk_bdev_io_wait_entry {
+                bdev: self.get_bdev().as_ptr(),
+                cb_fn: Some(Self::io_wait_cb),
+                cb_arg: cb_arg(s),
+                ..Default::default()
+            };
+            let errno = unsafe {
+                spdk_bdev_queue_io_wait(
+                    entry.bdev,
+                    self.channel.as_ptr(),
+                    &mut entry,
+                )
+            };
+
+            if errno != 0 {
+                drop(unsafe {
+                    Box::from_raw(entry.cb_arg as *mut oneshot::Sender<bool>)
+                });
+                return failed(errno);
+            }
+            r.await.expect("Failed awaiting bdev IO pool");
+        }
+    }
+
+    /// called when an IO structure has been returned to the bdev IO pool
+    extern "C" fn io_wait_cb(arg: *mut c_void) {
+        let sender =
+            unsafe { Box::from_raw(arg as *mut oneshot::Sender<bool>) };
+        let _ = sender.send(true);
+    }
+
+    /// Wait for the completion of the IO submitted with the given callback
+    /// argument, returning the NVMe status if it failed. If it does not
+    /// complete within the timeout, the IO is aborted and the timeout is
+    /// returned as the error. Even then we wait for the IO to complete, as
+    /// only its completion frees the IO and releases the buffer, which the
+    /// caller may drop as soon as we return.
+    async fn wait_io(
+        &self,
+        mut receiver: oneshot::Receiver<Option<NvmeStatus>>,
+        bio_cb_arg: *mut c_void,
+        timeout: Option<Duration>,
+    ) -> Result<Option<NvmeStatus>, Duration> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Ok(receiver.await.expect("Failed awaiting IO")),
+        };
+
+        if let Either::Left((status, _)) =
+            select(&mut receiver, Delay::new(timeout)).await
+        {
+            return Ok(status.expect("Failed awaiting IO"));
+        }
+
+        warn!(
+            target: RATE_LIMITED,
+            "IO on {} did not complete within {:?}, aborting it",
+            self.get_bdev().name(),
+            timeout
+        );
+        self.abort(bio_cb_arg).await;
+
+        // the IO may have completed successfully before it was aborted
+        match receiver.await.expect("Failed awaiting aborted IO") {
+            None => Ok(None),
+            Some(_) => Err(timeout),
+        }
+    }
+
+    /// abort the outstanding IO submitted with the given callback argument,
+    /// falling back to a reset when the bdev does not support aborts
+    async fn abort(&self, bio_cb_arg: *mut c_void) {
+        let (s, r) = oneshot::channel::<bool>();
+        let submitted = self
+            .submit(s, |arg| unsafe {
+                spdk_bdev_abort(
+                    self.desc.as_ptr(),
+                    self.channel.as_ptr(),
+                    bio_cb_arg,
+                    Some(Self::io_completion_cb),
+                    arg,
+                )
             })
+            .await;
+
+        if submitted.is_ok() && r.await.expect("Failed awaiting abort IO") {
+            return;
+        }
+
+        if let Err(error) = self.reset().await {
+            error!(
+                "Failed to reset {} after IO timeout: {}",
+                self.get_bdev().name(),
+                error
+            );
         }
     }
 
-    pub async fn reset(&self) -> Result<usize, CoreError> {
+    /// Reset the bdev and wait for the reset to complete, which aborts all
+    /// IO outstanding on it first. Without any IO outstanding the reset
+    /// completes right away and the handle stays usable either way.
+    pub async fn reset(&self) -> Result<(), CoreError> {
+        let bdev = self.get_bdev();
+        if !bdev.io_type_supported(IoType::Reset) {
+            return Err(CoreError::ResetNotSupported {
+                name: bdev.name(),
+            });
+        }
+
         let (s, r) = oneshot::channel::<bool>();
-        let errno = unsafe {
+        self.submit(s, |arg| unsafe {
             spdk_bdev_reset(
                 self.desc.as_ptr(),
                 self.channel.as_ptr(),
                 Some(Self::io_completion_cb),
-                cb_arg(s),
+                arg,
             )
-        };
-
-        if errno != 0 {
-            return Err(CoreError::ResetDispatch {
-                source: Errno::from_i32(errno),
-            });
-        }
+        })
+        .await
+        .map_err(|errno| CoreError::ResetDispatch {
+            source: Errno::from_i32(errno),
+        })?;
 
         if r.await.expect("Failed awaiting reset IO") {
-            Ok(0)
+            Ok(())
         } else {
             Err(CoreError::ResetFailed {})
         }
     }
 
-    /// create a snapshot, only works for nvme bdev
+    /// flush the write cache of the bdev, so that all writes completed before
+    /// are durable
+    pub async fn flush(&self) -> Result<(), CoreError> {
+        self.flush_range(0, self.get_bdev().size_in_bytes()).await
+    }
+
+    /// Flush the write cache for the given range of the bdev only. A bdev
+    /// which does not support flushes has no volatile cache, so there is
+    /// nothing to flush and this succeeds right away.
+    pub async fn flush_range(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), CoreError> {
+        if !self.get_bdev().io_type_supported(IoType::Flush) {
+            return Ok(());
+        }
+
+        let (s, r) = oneshot::channel::<bool>();
+        self.submit(s, |arg| unsafe {
+            spdk_bdev_flush(
+                self.desc.as_ptr(),
+                self.channel.as_ptr(),
+                offset,
+                len,
+                Some(Self::io_completion_cb),
+                arg,
+            )
+        })
+        .await
+        .map_err(|errno| CoreError::FlushDispatch {
+            source: Errno::from_i32(errno),
+        })?;
+
+        if r.await.expect("Failed awaiting flush IO") {
+            Ok(())
+        } else {
+            Err(CoreError::FlushFailed {})
+        }
+    }
+
     /// returns snapshot time as u64 seconds since Unix epoch
     pub async fn create_snapshot(&self) -> Result<u64, CoreError> {
         let mut cmd = spdk_sys::spdk_nvme_cmd::default();
@@ -248,30 +1194,26 @@ impl BdevHandle {
         let (s, r) = oneshot::channel::<bool>();
         // Use the spdk-sys variant spdk_bdev_nvme_admin_passthru that
         // assumes read commands
-        let errno = unsafe {
+        let (buf, len) = match buffer {
+            Some(b) => (**b, b.len()),
+            None => (std::ptr::null_mut(), 0),
+        };
+        self.submit(s, |arg| unsafe {
             spdk_bdev_nvme_admin_passthru_ro(
                 self.desc.as_ptr(),
                 self.channel.as_ptr(),
                 &*nvme_cmd,
-                match buffer {
-                    Some(ref b) => ***b,
-                    None => std::ptr::null_mut(),
-                },
-                match buffer {
-                    Some(b) => b.len(),
-                    None => 0,
-                },
+                buf,
+                len,
                 Some(Self::io_completion_cb),
-                cb_arg(s),
+                arg,
             )
-        };
-
-        if errno != 0 {
-            return Err(CoreError::NvmeAdminDispatch {
-                source: Errno::from_i32(errno),
-                opcode: (*nvme_cmd).opc(),
-            });
-        }
+        })
+        .await
+        .map_err(|errno| CoreError::NvmeAdminDispatch {
+            source: Errno::from_i32(errno),
+            opcode: (*nvme_cmd).opc(),
+        })?;
 
         if r.await.expect("Failed awaiting NVMe Admin IO") {
             Ok(())
@@ -309,6 +1251,9 @@ impl TryFrom<Descriptor> for BdevHandle {
             return Ok(Self {
                 desc: ManuallyDrop::new(Arc::new(desc)),
                 channel: ManuallyDrop::new(channel),
+                prefetch: RefCell::new(None),
+                verify_writes: Cell::new(false),
+                inflight: Arc::new(Mutex::new(HashSet::new())),
             });
         }
 
@@ -326,6 +1271,9 @@ impl TryFrom<Arc<Descriptor>> for BdevHandle {
             return Ok(Self {
                 desc: ManuallyDrop::new(desc),
                 channel: ManuallyDrop::new(channel),
+                prefetch: RefCell::new(None),
+                verify_writes: Cell::new(false),
+                inflight: Arc::new(Mutex::new(HashSet::new())),
             });
         }
 
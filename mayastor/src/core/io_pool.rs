@@ -0,0 +1,109 @@
+//!
+//! The shared pool of bdev IO structures. Every IO submitted to a bdev takes
+//! a structure from this pool; when the pool runs out the submission fails
+//! with ENOMEM and the IO stalls until other IOs complete. The sizes of the
+//! pool and of the per thread caches are taken from the config, can be
+//! overridden on the command line and are checked against the available
+//! hugepage memory at startup. The number of times the pool ran out is kept
+//! so that undersized pools show up in the stats.
+
+use std::{
+    fs,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::subsys::{BdevOpts, Config};
+
+/// estimate of the hugepage memory taken by a bdev IO structure, including
+/// the per IO context of the bdev modules and the mempool overhead
+const BDEV_IO_SIZE: u64 = 2048;
+
+static EXHAUSTED: AtomicU64 = AtomicU64::new(0);
+
+/// Sizes and usage of the bdev IO pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoPoolStats {
+    /// number of bdev IO structures in the shared pool
+    pub pool_size: u32,
+    /// number of bdev IO structures cached per thread
+    pub cache_size: u32,
+    /// number of times an IO could not be submitted as the pool was empty
+    pub exhausted: u64,
+}
+
+/// Accounting and validation of the shared bdev IO pool.
+pub struct IoPool;
+
+impl IoPool {
+    /// the sizes of the pool in use and how often it ran out
+    pub fn stats() -> IoPoolStats {
+        let opts = &Config::get().bdev_opts;
+        IoPoolStats {
+            pool_size: opts.bdev_io_pool_size,
+            cache_size: opts.bdev_io_cache_size,
+            exhausted: EXHAUSTED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// record that the submission of an IO failed as the pool was empty
+    pub(crate) fn exhausted() {
+        EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Check that the caches of the given number of cores leave IO
+    /// structures in the shared pool and that the pool fits in the hugepage
+    /// memory. The memory is limited to mem_size MiB when it is greater
+    /// than 0, otherwise to the free hugepages of the system.
+    pub fn check(
+        opts: &BdevOpts,
+        mem_size: i32,
+        cores: u32,
+    ) -> Result<(), String> {
+        // each core and the init thread take their cache out of the pool
+        let cached =
+            u64::from(opts.bdev_io_cache_size) * (u64::from(cores) + 1);
+        if cached > u64::from(opts.bdev_io_pool_size) {
+            return Err(format!(
+                "bdev IO pool of {} is too small for {} cores caching {}",
+                opts.bdev_io_pool_size, cores, opts.bdev_io_cache_size
+            ));
+        }
+
+        let available = if mem_size > 0 {
+            Some((mem_size as u64) << 20)
+        } else {
+            Self::hugepages_free()
+        };
+        let required = u64::from(opts.bdev_io_pool_size) * BDEV_IO_SIZE;
+        match available {
+            Some(available) if required > available => Err(format!(
+                "bdev IO pool of {} needs {} MiB of hugepages, only {} MiB \
+                 available",
+                opts.bdev_io_pool_size,
+                required >> 20,
+                available >> 20
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// free hugepage memory of the system in bytes, if it can be determined
+    pub(crate) fn hugepages_free() -> Option<u64> {
+        Some(meminfo("HugePages_Free:")? * Self::hugepage_size()?)
+    }
+
+    /// size of the hugepages of the system in bytes, if it can be determined
+    pub(crate) fn hugepage_size() -> Option<u64> {
+        Some(meminfo("Hugepagesize:")? << 10)
+    }
+}
+
+/// the value of the field of /proc/meminfo with the given name
+fn meminfo(name: &str) -> Option<u64> {
+    fs::read_to_string("/proc/meminfo")
+        .ok()?
+        .lines()
+        .find(|l| l.starts_with(name))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse::<u64>().ok())
+}
@@ -1,12 +1,20 @@
 //!
 //! core contains the primary abstractions around the SPDK primitives.
+use std::time::Duration;
+
 pub use ::uuid::Uuid;
 use nix::errno::Errno;
 use snafu::Snafu;
 
-use crate::{subsys::NvmfError, target::iscsi};
+use crate::{
+    bdev::nexus::nexus_nbd::NbdError,
+    subsys::NvmfError,
+    target::iscsi,
+};
+pub use background::{BackgroundClass, BackgroundScheduler};
 pub use bdev::{Bdev, BdevIter, BdevStats};
 pub use channel::IoChannel;
+pub use checksum::ChecksumAlgorithm;
 pub use cpu_cores::{Core, Cores};
 pub use descriptor::{Descriptor, RangeContext};
 pub use dma::{DmaBuf, DmaError};
@@ -18,22 +26,28 @@ pub use env::{
     SIG_RECEIVED,
 };
 
-pub use handle::BdevHandle;
-pub use nvme::{GenericStatusCode, NvmeStatus};
+pub use handle::{BdevHandle, UnmapMode, Unmapped};
+pub use io_pool::{IoPool, IoPoolStats};
+pub use nvme::{GenericStatusCode, MediaErrorStatusCode, NvmeStatus};
+pub use prefetch::PrefetchStats;
 pub use reactor::{Reactor, ReactorState, Reactors, REACTOR_LIST};
 pub use share::{Protocol, Share};
 pub use thread::Mthread;
 
+mod background;
 mod bdev;
 mod channel;
+mod checksum;
 mod cpu_cores;
 mod descriptor;
 mod dma;
 mod env;
 mod handle;
 pub mod io_driver;
+mod io_pool;
 mod nvme;
 pub mod poller;
+mod prefetch;
 mod reactor;
 mod share;
 pub(crate) mod thread;
@@ -61,6 +75,15 @@ pub enum CoreError {
     InvalidOffset {
         offset: u64,
     },
+    #[snafu(display(
+        "IO at offset {} length {} is not block aligned",
+        offset,
+        len
+    ))]
+    UnalignedIo {
+        offset: u64,
+        len: u64,
+    },
     #[snafu(display(
         "Failed to dispatch write at offset {} length {}",
         offset,
@@ -81,10 +104,75 @@ pub enum CoreError {
         offset: u64,
         len: u64,
     },
+    #[snafu(display(
+        "Failed to dispatch write zeroes at offset {} length {}",
+        offset,
+        len
+    ))]
+    WriteZeroesDispatch {
+        source: Errno,
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Failed to dispatch unmap at offset {} length {}",
+        offset,
+        len
+    ))]
+    UnmapDispatch {
+        source: Errno,
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display("bdev {} does not support unmap", name))]
+    UnmapNotSupported {
+        name: String,
+    },
+    #[snafu(display(
+        "unmap granularity {} is not a multiple of the block size {}",
+        granularity,
+        block_len
+    ))]
+    UnmapGranularity {
+        granularity: u64,
+        block_len: u64,
+    },
+    #[snafu(display(
+        "Failed to dispatch compare at offset {} length {}",
+        offset,
+        len
+    ))]
+    CompareDispatch {
+        source: Errno,
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Failed to dispatch compare and write at offset {} length {}",
+        offset,
+        len
+    ))]
+    CompareAndWriteDispatch {
+        source: Errno,
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display("bdev {} does not support compare and write", name))]
+    CompareAndWriteNotSupported {
+        name: String,
+    },
     #[snafu(display("Failed to dispatch reset",))]
     ResetDispatch {
         source: Errno,
     },
+    #[snafu(display("bdev {} does not support reset", name))]
+    ResetNotSupported {
+        name: String,
+    },
+    #[snafu(display("Failed to dispatch flush"))]
+    FlushDispatch {
+        source: Errno,
+    },
     #[snafu(display("Failed to dispatch NVMe Admin command {:x}h", opcode))]
     NvmeAdminDispatch {
         source: Errno,
@@ -100,8 +188,92 @@ pub enum CoreError {
         offset: u64,
         len: u64,
     },
+    #[snafu(display(
+        "Write zeroes failed at offset {} length {}",
+        offset,
+        len
+    ))]
+    WriteZeroesFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display("Unmap failed at offset {} length {}", offset, len))]
+    UnmapFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display("Compare failed at offset {} length {}", offset, len))]
+    CompareFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Compare and write failed at offset {} length {}",
+        offset,
+        len
+    ))]
+    CompareAndWriteFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Data differs from the data compared at offset {} length {}",
+        offset,
+        len
+    ))]
+    Miscompare {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Protection information guard check failed at offset {} length {}",
+        offset,
+        len
+    ))]
+    GuardCheckFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display(
+        "Data read back differs from the data written at offset {} length {}",
+        offset,
+        len
+    ))]
+    WriteVerifyFailed {
+        offset: u64,
+        len: u64,
+    },
+    #[snafu(display("Failed to allocate a buffer of {} bytes", len))]
+    DmaAlloc {
+        source: DmaError,
+        len: u64,
+    },
+    #[snafu(display(
+        "Write timed out after {:?} at offset {} length {}",
+        timeout,
+        offset,
+        len
+    ))]
+    WriteTimedOut {
+        offset: u64,
+        len: u64,
+        timeout: Duration,
+    },
+    #[snafu(display(
+        "Read timed out after {:?} at offset {} length {}",
+        timeout,
+        offset,
+        len
+    ))]
+    ReadTimedOut {
+        offset: u64,
+        len: u64,
+        timeout: Duration,
+    },
     #[snafu(display("Reset failed"))]
     ResetFailed {},
+    #[snafu(display("Flush failed"))]
+    FlushFailed {},
     #[snafu(display("NVMe Admin command {:x}h failed", opcode))]
     NvmeAdminFailed {
         opcode: u16,
@@ -122,6 +294,15 @@ pub enum CoreError {
     UnshareIscsi {
         source: iscsi::Error,
     },
+    #[snafu(display("failed to share {}", source))]
+    ShareNbd {
+        source: NbdError,
+    },
+    #[snafu(display("{} is already shared over {}", name, protocol))]
+    AlreadyShared {
+        name: String,
+        protocol: Protocol,
+    },
     #[snafu(display("the operation is invalid for this bdev: {}", source))]
     NotSupported {
         source: Errno,
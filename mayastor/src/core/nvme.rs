@@ -63,6 +63,7 @@ pub enum GenericStatusCode {
     CommandAbortPreemt,
     SanitizeFailed,
     SanitizeInProgress,
+    NamespaceWriteProtected,
     Reserved,
 }
 
@@ -99,6 +100,7 @@ impl From<i32> for GenericStatusCode {
             0x1B => Self::CommandAbortPreemt,
             0x1C => Self::SanitizeFailed,
             0x1D => Self::SanitizeInProgress,
+            0x20 => Self::NamespaceWriteProtected,
             _ => {
                 error!("unknown code {}", i);
                 Self::Reserved
@@ -107,6 +109,38 @@ impl From<i32> for GenericStatusCode {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialOrd, PartialEq)]
+pub enum MediaErrorStatusCode {
+    WriteFault,
+    UnrecoveredReadError,
+    GuardCheckError,
+    ApplicationTagCheckError,
+    ReferenceTagCheckError,
+    CompareFailure,
+    AccessDenied,
+    DeallocatedOrUnwrittenBlock,
+    Reserved,
+}
+
+impl From<i32> for MediaErrorStatusCode {
+    fn from(i: i32) -> Self {
+        match i {
+            0x80 => Self::WriteFault,
+            0x81 => Self::UnrecoveredReadError,
+            0x82 => Self::GuardCheckError,
+            0x83 => Self::ApplicationTagCheckError,
+            0x84 => Self::ReferenceTagCheckError,
+            0x85 => Self::CompareFailure,
+            0x86 => Self::AccessDenied,
+            0x87 => Self::DeallocatedOrUnwrittenBlock,
+            _ => {
+                error!("unknown media error code {}", i);
+                Self::Reserved
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NvmeStatus {
     /// NVMe completion queue entry
@@ -115,6 +149,8 @@ pub struct NvmeStatus {
     sct: StatusCodeType,
     /// NVMe status code
     sc: GenericStatusCode,
+    /// NVMe status code of media and data integrity errors
+    media_sc: Option<MediaErrorStatusCode>,
 }
 
 impl NvmeStatus {
@@ -124,6 +160,26 @@ impl NvmeStatus {
     pub fn status_type(&self) -> StatusCodeType {
         self.sct
     }
+    /// the status code if the status is a media or data integrity error
+    pub fn media_status_code(&self) -> Option<MediaErrorStatusCode> {
+        self.media_sc
+    }
+
+    fn from_raw(cdw0: u32, sct: i32, sc: i32) -> Self {
+        let sct = StatusCodeType::from(sct);
+        let (sc, media_sc) = if sct == MediaDataIntegrityErrors {
+            (GenericStatusCode::Reserved, Some(sc.into()))
+        } else {
+            (GenericStatusCode::from(sc), None)
+        };
+
+        Self {
+            cdw0,
+            sct,
+            sc,
+            media_sc,
+        }
+    }
 }
 
 impl From<Bio> for NvmeStatus {
@@ -141,11 +197,7 @@ impl From<Bio> for NvmeStatus {
             )
         }
 
-        Self {
-            cdw0,
-            sct: StatusCodeType::from(sct),
-            sc: GenericStatusCode::from(sc),
-        }
+        Self::from_raw(cdw0, sct, sc)
     }
 }
 
@@ -164,11 +216,7 @@ impl From<&mut Bio> for NvmeStatus {
             )
         }
 
-        Self {
-            cdw0,
-            sct: StatusCodeType::from(sct),
-            sc: GenericStatusCode::from(sc),
-        }
+        Self::from_raw(cdw0, sct, sc)
     }
 }
 impl From<&Bio> for NvmeStatus {
@@ -186,10 +234,6 @@ impl From<&Bio> for NvmeStatus {
             )
         }
 
-        Self {
-            cdw0,
-            sct: StatusCodeType::from(sct),
-            sc: GenericStatusCode::from(sc),
-        }
+        Self::from_raw(cdw0, sct, sc)
     }
 }
@@ -0,0 +1,259 @@
+//!
+//! Read ahead for streaming reads through a [BdevHandle]. Once a number of
+//! reads in a row have been sequential, the window of data following the
+//! last read is read from the bdev in the background, so that the next reads
+//! are served from memory rather than waiting for the bdev. Any read which
+//! is not sequential resets the detector and drops the data read ahead, as
+//! does a write through the handle to the region read ahead.
+//!
+//! [BdevHandle]: crate::core::BdevHandle
+
+use std::{os::raw::c_void, sync::Arc};
+
+use futures::channel::oneshot;
+
+use spdk_sys::{spdk_bdev_free_io, spdk_bdev_io, spdk_bdev_read};
+
+use crate::core::{
+    BackgroundClass,
+    BackgroundScheduler,
+    Descriptor,
+    DmaBuf,
+    IoChannel,
+};
+
+/// number of back to back sequential reads after which we read ahead
+const SEQUENTIAL_READS: u32 = 2;
+
+/// Counters of the prefetcher of a handle.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PrefetchStats {
+    /// windows read ahead
+    pub issued: u64,
+    /// reads served from data read ahead
+    pub hits: u64,
+    /// windows dropped before they were read entirely, because of random
+    /// access or writes
+    pub discarded: u64,
+    /// windows not read ahead as the background IO budget was used up
+    pub throttled: u64,
+}
+
+enum Data {
+    /// the read of the window has not completed yet
+    InFlight(oneshot::Receiver<Option<DmaBuf>>),
+    Ready(DmaBuf),
+}
+
+/// A region of the bdev which has been read ahead.
+pub(crate) struct Window {
+    offset: u64,
+    len: u64,
+    data: Data,
+}
+
+impl Window {
+    /// Read the window in the background, the buffer as well as the
+    /// descriptor and channel used are owned by the IO until it completes.
+    fn submit(desc: &Arc<Descriptor>, offset: u64, len: u64) -> Option<Self> {
+        let buf = DmaBuf::new(len, desc.get_bdev().alignment()).ok()?;
+        let channel = desc.get_channel()?;
+        let (sender, receiver) = oneshot::channel();
+
+        let ptr = *buf;
+        let ch = channel.as_ptr();
+        let ctx = Box::into_raw(Box::new(ReadaheadCtx {
+            buf,
+            sender,
+            _channel: channel,
+            _desc: Arc::clone(desc),
+        }));
+
+        let errno = unsafe {
+            spdk_bdev_read(
+                desc.as_ptr(),
+                ch,
+                ptr,
+                offset,
+                len,
+                Some(readahead_cb),
+                ctx as *mut c_void,
+            )
+        };
+
+        if errno != 0 {
+            debug!("failed to read ahead at {}: {}", offset, errno);
+            let _ = unsafe { Box::from_raw(ctx) };
+            return None;
+        }
+
+        Some(Self {
+            offset,
+            len,
+            data: Data::InFlight(receiver),
+        })
+    }
+
+    fn covers(&self, offset: u64, len: u64) -> bool {
+        offset >= self.offset && offset + len <= self.offset + self.len
+    }
+
+    fn overlaps(&self, offset: u64, len: u64) -> bool {
+        offset < self.offset + self.len && self.offset < offset + len
+    }
+
+    /// Wait for the window to be read, returns None if the read failed.
+    pub(crate) async fn ready(self) -> Option<Self> {
+        match self.data {
+            Data::Ready(_) => Some(self),
+            Data::InFlight(receiver) => match receiver.await {
+                Ok(Some(buf)) => Some(Self {
+                    data: Data::Ready(buf),
+                    ..self
+                }),
+                _ => None,
+            },
+        }
+    }
+
+    /// Copy the data at the given offset into the buffer, the window must
+    /// have been read and must cover the buffer.
+    pub(crate) fn copy_to(&self, offset: u64, buffer: &mut DmaBuf) {
+        if let Data::Ready(data) = &self.data {
+            let start = (offset - self.offset) as usize;
+            let end = start + buffer.len() as usize;
+            buffer
+                .as_mut_slice()
+                .copy_from_slice(&data.as_slice()[start .. end]);
+        }
+    }
+}
+
+struct ReadaheadCtx {
+    buf: DmaBuf,
+    sender: oneshot::Sender<Option<DmaBuf>>,
+    // keep the channel and the descriptor alive until the IO completes
+    _channel: IoChannel,
+    _desc: Arc<Descriptor>,
+}
+
+extern "C" fn readahead_cb(
+    io: *mut spdk_bdev_io,
+    success: bool,
+    arg: *mut c_void,
+) {
+    let ctx = unsafe { Box::from_raw(arg as *mut ReadaheadCtx) };
+    unsafe {
+        spdk_bdev_free_io(io);
+    }
+
+    // the window may have been dropped in the meantime, in which case the
+    // buffer is freed here
+    let _ = ctx.sender.send(if success { Some(ctx.buf) } else { None });
+}
+
+/// Detects sequential reads and keeps the window read ahead.
+pub(crate) struct Prefetcher {
+    /// number of bytes to read ahead
+    window: u64,
+    /// offset following the last read, where the next read starts if it is
+    /// sequential (none of them is before the first read)
+    next_offset: u64,
+    /// number of reads in a row which followed the previous read
+    streak: u32,
+    window_ahead: Option<Window>,
+    /// incremented by every write, to detect writes while waiting for the
+    /// window to be read
+    writes: u64,
+    stats: PrefetchStats,
+}
+
+impl Prefetcher {
+    pub(crate) fn new(window: u64) -> Self {
+        Self {
+            window,
+            next_offset: u64::MAX,
+            streak: 0,
+            window_ahead: None,
+            writes: 0,
+            stats: PrefetchStats::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> PrefetchStats {
+        self.stats
+    }
+
+    pub(crate) fn writes(&self) -> u64 {
+        self.writes
+    }
+
+    fn discard(&mut self) {
+        if self.window_ahead.take().is_some() {
+            self.stats.discarded += 1;
+        }
+    }
+
+    /// Record a read, returns the window read ahead if it covers the read.
+    pub(crate) fn read(&mut self, offset: u64, len: u64) -> Option<Window> {
+        if offset == self.next_offset {
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+            self.discard();
+        }
+        self.next_offset = offset + len;
+
+        match &self.window_ahead {
+            Some(window) if window.covers(offset, len) => {
+                self.window_ahead.take()
+            }
+            _ => None,
+        }
+    }
+
+    /// Keep the window after a read was served from it, unless the read
+    /// has consumed it.
+    pub(crate) fn hit(&mut self, window: Window, offset: u64, len: u64) {
+        self.stats.hits += 1;
+        if offset + len < window.offset + window.len {
+            self.window_ahead = Some(window);
+        }
+    }
+
+    /// Drop the window read ahead if it overlaps with the write.
+    pub(crate) fn write(&mut self, offset: u64, len: u64) {
+        self.writes += 1;
+        if let Some(window) = &self.window_ahead {
+            if window.overlaps(offset, len) {
+                self.discard();
+            }
+        }
+    }
+
+    /// Read the next window ahead if the reads are sequential and the
+    /// previous window has been consumed.
+    pub(crate) fn read_ahead(&mut self, desc: &Arc<Descriptor>) {
+        if self.streak + 1 < SEQUENTIAL_READS || self.window_ahead.is_some() {
+            return;
+        }
+
+        let bdev = desc.get_bdev();
+        let block_len = u64::from(bdev.block_len());
+        let size = bdev.size_in_bytes();
+        if self.next_offset >= size || self.next_offset % block_len != 0 {
+            return;
+        }
+
+        let window = std::cmp::max(self.window / block_len, 1) * block_len;
+        let len = std::cmp::min(window, size - self.next_offset);
+        if !BackgroundScheduler::try_reserve(BackgroundClass::Prefetch, len) {
+            self.stats.throttled += 1;
+            return;
+        }
+        if let Some(window) = Window::submit(desc, self.next_offset, len) {
+            self.stats.issued += 1;
+            self.window_ahead = Some(window);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use async_trait::async_trait;
 use pin_utils::core_reexport::fmt::Formatter;
+use serde::Serialize;
 use std::fmt::Display;
 
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Serialize)]
 /// Indicates what protocol the bdev is shared as
 pub enum Protocol {
     /// not shared by any of the variants
@@ -11,6 +12,8 @@ pub enum Protocol {
     Nvmf,
     /// shared as iSCSI
     Iscsi,
+    /// exported as a local NBD device, which is not exposed over gRPC
+    Nbd,
 }
 
 impl From<i32> for Protocol {
@@ -36,6 +39,7 @@ impl Display for Protocol {
             Self::Off => "Not shared",
             Self::Iscsi => "iSCSI",
             Self::Nvmf => "NVMe-oF TCP",
+            Self::Nbd => "NBD",
         };
         write!(f, "{}", p)
     }
@@ -47,7 +51,13 @@ pub trait Share: std::fmt::Debug {
     type Output: std::fmt::Display + std::fmt::Debug;
     async fn share_iscsi(&self) -> Result<Self::Output, Self::Error>;
     async fn share_nvmf(&self) -> Result<Self::Output, Self::Error>;
+    /// export it as a local NBD device and return the path of the device
+    async fn share_nbd(&self) -> Result<Self::Output, Self::Error>;
+    /// unshare whichever protocol it is shared over, which is not an error
+    /// when it is not shared at all
     async fn unshare(&self) -> Result<Self::Output, Self::Error>;
+    /// returns the protocol it is currently shared over, as found from the
+    /// targets rather than remembered, so it holds after a restart
     fn shared(&self) -> Option<Protocol>;
     fn share_uri(&self) -> Option<String>;
     fn bdev_uri(&self) -> Option<String>;
@@ -0,0 +1,271 @@
+//! Internal event bus, on which state changes of the resources of mayastor
+//! are published as they happen rather than found out by polling for them.
+//! Every subscriber gets its own bounded queue of events. Publishing never
+//! blocks the reactors: when the queue of a slow subscriber is full, the
+//! event is dropped for that subscriber alone and counted, and it is up to
+//! the subscriber to notice the count going up and query the state it
+//! missed. Subscriptions are removed once they are dropped.
+//!
+//! Bdevs registered and unregistered are learned about from the notify
+//! library of SPDK, which only keeps a history of events, so it is read by
+//! a poller on the init thread. That poller also compares the hosts
+//! connected to the nvmf subsystems with those it saw last, so hosts going
+//! away are noticed whether they disconnected or were lost, the latter once
+//! the transport fails or the keep alive timeout expires.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    os::raw::c_void,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+    },
+};
+
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+use once_cell::sync::Lazy;
+
+use spdk_sys::{spdk_notify_event, spdk_notify_foreach_event};
+
+use crate::{
+    bdev::ChildState,
+    core::poller::{Builder, Poller},
+    ffihelper::AsStr,
+    subsys::{NvmfController, NvmfSubsystem, NvmfTarget},
+};
+
+/// the number of events queued for a subscriber before they are dropped
+pub const EVENT_QUEUE_DEPTH: usize = 1024;
+
+/// the interval at which the notifications of SPDK are read
+const NOTIFY_POLL_INTERVAL_US: u64 = 100_000;
+
+/// The state of a pool as far as this node is concerned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PoolState {
+    /// the pool is not on this node
+    Absent,
+    /// the pool is imported and usable
+    Online,
+    /// the pool has been exported and remains on its disk
+    Exported,
+}
+
+/// A state change of a resource.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// a bdev has been registered
+    BdevRegistered {
+        name: String,
+    },
+    /// a bdev has been unregistered
+    BdevUnregistered {
+        name: String,
+    },
+    /// an lvol has been created in a pool
+    LvolCreated {
+        pool: String,
+        name: String,
+        uuid: String,
+    },
+    /// an lvol has been destroyed
+    LvolDestroyed {
+        pool: String,
+        name: String,
+        uuid: String,
+    },
+    /// a pool has been created, imported, exported or destroyed
+    PoolStateChanged {
+        name: String,
+        old: PoolState,
+        new: PoolState,
+    },
+    /// a child of a nexus has changed state
+    ChildStateChanged {
+        nexus: String,
+        child: String,
+        old: ChildState,
+        new: ChildState,
+    },
+    /// a host has connected to an nvmf subsystem
+    HostConnected {
+        nqn: String,
+        host: String,
+        cntlid: u16,
+    },
+    /// a host has disconnected from an nvmf subsystem, or has been lost
+    HostDisconnected {
+        nqn: String,
+        host: String,
+        cntlid: u16,
+    },
+}
+
+struct Subscriber {
+    sender: Sender<Event>,
+    dropped: Arc<AtomicU64>,
+}
+
+static SUBSCRIBERS: Lazy<Mutex<Vec<Subscriber>>> = Lazy::new(Default::default);
+
+/// The receiving end of a subscription to the events.
+pub struct Subscription {
+    receiver: Receiver<Event>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// the queue of events of the subscription
+    pub fn receiver(&self) -> &Receiver<Event> {
+        &self.receiver
+    }
+
+    /// the number of events dropped as the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// subscribe to all events published from now on
+pub fn subscribe() -> Subscription {
+    let (sender, receiver) = bounded(EVENT_QUEUE_DEPTH);
+    let dropped = Arc::new(AtomicU64::new(0));
+    SUBSCRIBERS.lock().unwrap().push(Subscriber {
+        sender,
+        dropped: Arc::clone(&dropped),
+    });
+    Subscription {
+        receiver,
+        dropped,
+    }
+}
+
+/// Publish the event to all subscribers, the subscribers whose queue is
+/// full miss it.
+pub fn publish(event: Event) {
+    SUBSCRIBERS.lock().unwrap().retain(|s| {
+        match s.sender.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(event)) => {
+                s.dropped.fetch_add(1, Ordering::Relaxed);
+                debug!("event queue full, dropped {:?}", event);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    });
+}
+
+thread_local! {
+    /// the poller reading the notifications of SPDK
+    static NOTIFY_POLLER: RefCell<Option<Poller<'static>>> =
+        RefCell::new(None);
+}
+
+/// publish a notification of SPDK as an event, if it is one we know about
+extern "C" fn notify_cb(
+    idx: u64,
+    event: *const spdk_notify_event,
+    ctx: *mut c_void,
+) -> i32 {
+    let next = unsafe { &mut *(ctx as *mut u64) };
+    *next = idx + 1;
+
+    let event = unsafe { &*event };
+    let name = event.ctx.as_str().to_string();
+    match event.type_.as_str() {
+        "bdev_register" => publish(Event::BdevRegistered {
+            name,
+        }),
+        "bdev_unregister" => publish(Event::BdevUnregistered {
+            name,
+        }),
+        _ => {}
+    }
+    0
+}
+
+/// Publish the hosts which connected to or disconnected from the nvmf
+/// subsystems since last seen, returns whether any did.
+fn publish_host_changes(
+    seen: &mut HashMap<String, Vec<NvmfController>>,
+) -> bool {
+    if !NvmfTarget::running() {
+        return false;
+    }
+
+    let mut current = HashMap::new();
+    if let Some(subsystem) = NvmfSubsystem::first() {
+        for s in subsystem.into_iter() {
+            current.insert(s.get_nqn(), s.controllers());
+        }
+    }
+
+    let mut changed = false;
+    for (nqn, controllers) in seen.iter() {
+        let now = current.get(nqn);
+        for c in controllers {
+            if now.map_or(true, |now| !now.contains(c)) {
+                changed = true;
+                publish(Event::HostDisconnected {
+                    nqn: nqn.clone(),
+                    host: c.hostnqn.clone(),
+                    cntlid: c.cntlid,
+                });
+            }
+        }
+    }
+    for (nqn, controllers) in current.iter() {
+        let before = seen.get(nqn);
+        for c in controllers {
+            if before.map_or(true, |before| !before.contains(c)) {
+                changed = true;
+                publish(Event::HostConnected {
+                    nqn: nqn.clone(),
+                    host: c.hostnqn.clone(),
+                    cntlid: c.cntlid,
+                });
+            }
+        }
+    }
+
+    *seen = current;
+    changed
+}
+
+/// Start publishing the bdevs registered and unregistered and the hosts
+/// connecting to and disconnecting from the nvmf subsystems, must be called
+/// on the init thread.
+pub(crate) fn start_notify_poller() {
+    let mut next: u64 = 0;
+    let mut hosts = HashMap::new();
+    let poller = Builder::new()
+        .with_name("mayastor_events")
+        .with_interval(NOTIFY_POLL_INTERVAL_US)
+        .with_poll_fn(move || {
+            let count = unsafe {
+                spdk_notify_foreach_event(
+                    next,
+                    u64::MAX,
+                    Some(notify_cb),
+                    &mut next as *mut u64 as *mut c_void,
+                )
+            };
+            let changed = publish_host_changes(&mut hosts);
+            (count > 0 || changed) as i32
+        })
+        .build();
+    NOTIFY_POLLER.with(|p| *p.borrow_mut() = Some(poller));
+}
+
+/// stop publishing the bdevs registered and unregistered and the hosts
+/// connecting and disconnecting
+pub(crate) fn stop_notify_poller() {
+    NOTIFY_POLLER.with(|p| {
+        if let Some(poller) = p.borrow_mut().take() {
+            poller.stop();
+        }
+    });
+}
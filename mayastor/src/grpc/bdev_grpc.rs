@@ -150,11 +150,13 @@ impl BdevRpc for BdevSvc {
         sync_config(async {
             let name = request.into_inner().name;
             let hdl = Reactors::master().spawn_local(async move {
-                let bdev = Bdev::lookup_by_name(&name).unwrap();
-                let _ = bdev
-                    .unshare()
-                    .await
-                    .map_err(|e| Status::internal(e.to_string()));
+                // a bdev which is gone is not shared either
+                if let Some(bdev) = Bdev::lookup_by_name(&name) {
+                    let _ = bdev
+                        .unshare()
+                        .await
+                        .map_err(|e| Status::internal(e.to_string()));
+                }
             });
 
             hdl.await;
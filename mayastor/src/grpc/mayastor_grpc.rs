@@ -27,6 +27,7 @@ use crate::{
             uuid_to_name,
         },
         pool_grpc,
+        state_grpc,
         sync_config,
         GrpcResult,
     },
@@ -117,6 +118,15 @@ impl mayastor_server::Mayastor for MayastorSvc {
         sync_config(pool_grpc::share_replica(args)).await
     }
 
+    #[instrument(level = "debug", err)]
+    async fn resize_replica(
+        &self,
+        request: Request<ResizeReplicaRequest>,
+    ) -> GrpcResult<Replica> {
+        let args = request.into_inner();
+        sync_config(pool_grpc::resize_replica(args)).await
+    }
+
     #[instrument(level = "info", err)]
     async fn create_nexus(
         &self,
@@ -226,6 +236,26 @@ impl mayastor_server::Mayastor for MayastorSvc {
         .await
     }
 
+    #[instrument(level = "debug", err)]
+    async fn resize_nexus(
+        &self,
+        request: Request<ResizeNexusRequest>,
+    ) -> GrpcResult<Nexus> {
+        sync_config(async {
+            let args = request.into_inner();
+            trace!("{:?}", args);
+            let uuid = args.uuid.clone();
+            debug!("Resizing nexus {} to {} bytes ...", uuid, args.size);
+            locally! { async move {
+                nexus_lookup(&args.uuid)?.resize(args.size).await
+            }};
+            let nexus = nexus_lookup(&uuid)?;
+            info!("Resized nexus {}", uuid);
+            Ok(Response::new(nexus.to_grpc()))
+        })
+        .await
+    }
+
     #[instrument(level = "debug", err)]
     async fn publish_nexus(
         &self,
@@ -427,6 +457,32 @@ impl mayastor_server::Mayastor for MayastorSvc {
         .await
     }
 
+    #[instrument(level = "debug", err)]
+    async fn list_snapshots(
+        &self,
+        _request: Request<Null>,
+    ) -> GrpcResult<ListSnapshotsReply> {
+        pool_grpc::list_snapshots()
+    }
+
+    #[instrument(level = "debug", err)]
+    async fn destroy_snapshot(
+        &self,
+        request: Request<DestroySnapshotRequest>,
+    ) -> GrpcResult<Null> {
+        let args = request.into_inner();
+        sync_config(pool_grpc::destroy_snapshot(args)).await
+    }
+
+    #[instrument(level = "debug", err)]
+    async fn clone_snapshot(
+        &self,
+        request: Request<CloneSnapshotRequest>,
+    ) -> GrpcResult<Replica> {
+        let args = request.into_inner();
+        sync_config(pool_grpc::clone_snapshot(args)).await
+    }
+
     #[instrument(level = "debug", err)]
     async fn list_block_devices(
         &self,
@@ -452,4 +508,12 @@ impl mayastor_server::Mayastor for MayastorSvc {
         trace!("{:?}", reply);
         Ok(Response::new(reply))
     }
+
+    #[instrument(level = "debug", err)]
+    async fn dump_state(
+        &self,
+        _request: Request<Null>,
+    ) -> GrpcResult<DumpStateReply> {
+        state_grpc::dump_state()
+    }
 }
@@ -1,4 +1,5 @@
 pub mod pool_grpc;
+pub mod state_grpc;
 
 use std::error::Error;
 
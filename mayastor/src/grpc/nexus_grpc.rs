@@ -49,6 +49,11 @@ impl NexusChild {
             uri: self.name.clone(),
             state: rpc::ChildState::from(self.state()) as i32,
             rebuild_progress: self.get_rebuild_progress(),
+            breaker_open: self.breaker.is_open(),
+            breaker_cooldown_ms: self
+                .breaker
+                .cooldown_left()
+                .map_or(0, |cooldown| cooldown.as_millis() as u64),
         }
     }
 }
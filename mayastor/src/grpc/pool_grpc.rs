@@ -8,27 +8,35 @@ use tonic::{Response, Status};
 use tracing::instrument;
 
 use rpc::mayastor::{
+    ChecksumAlgorithm as RpcChecksumAlgorithm,
+    CloneSnapshotRequest,
+    CompressionAlgorithm as RpcCompressionAlgorithm,
     CreatePoolRequest,
     CreateReplicaRequest,
     DestroyPoolRequest,
     DestroyReplicaRequest,
+    DestroySnapshotRequest,
     ListPoolsReply,
     ListReplicasReply,
+    ListSnapshotsReply,
     Null,
     Pool,
     PoolState,
     Replica,
     ReplicaStats,
+    ResizeReplicaRequest,
     ShareReplicaReply,
     ShareReplicaRequest,
+    Snapshot,
     StatReplicasReply,
     Stats,
 };
 
 use crate::{
-    core::{Bdev, BdevStats, CoreError, Protocol, Share},
+    bdev::CompressionAlgorithm,
+    core::{Bdev, BdevStats, ChecksumAlgorithm, CoreError, Protocol, Share},
     grpc::{rpc_call, GrpcResult},
-    lvs::{Error as LvsError, Error, Lvol, Lvs},
+    lvs::{Error as LvsError, Error, Lvol, Lvs, PropValue},
     nexus_uri::NexusBdevError,
 };
 
@@ -44,6 +52,30 @@ impl From<LvsError> for Status {
             Error::Invalid {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::SnapshotInUse {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    ..
+                },
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::PoolVersionMismatch {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            Error::RepNoSpace {
+                ..
+            } => Status::resource_exhausted(e.to_string()),
+            Error::RepInflateNoSpace {
+                ..
+            } => Status::resource_exhausted(e.to_string()),
+            Error::PoolNotFound {
+                ..
+            } => Status::not_found(e.to_string()),
+            Error::PoolBusy {
+                ..
+            } => Status::unavailable(e.to_string()),
             _ => Status::internal(e.to_string()),
         }
     }
@@ -55,6 +87,8 @@ impl From<Protocol> for i32 {
             Protocol::Off => 0,
             Protocol::Nvmf => 1,
             Protocol::Iscsi => 2,
+            // a local NBD export is not exposed to the clients
+            Protocol::Nbd => 0,
         }
     }
 }
@@ -90,10 +124,28 @@ impl From<Lvol> for Replica {
             thin: l.is_thin(),
             size: l.size(),
             share: l.shared().unwrap().into(),
-            uri: l.share_uri().unwrap(),
+            uri: l.share_uri().unwrap_or_default(),
+        }
+    }
+}
+impl From<Lvol> for Snapshot {
+    fn from(l: Lvol) -> Self {
+        let name = l.name();
+        let (source, timestamp) = Lvol::parse_snapshot_name(&name)
+            .map(|(source, time)| (source.to_string(), time))
+            .unwrap_or_default();
+        Self {
+            uuid: l.uuid(),
+            pool: l.pool(),
+            size: l.size(),
+            source,
+            timestamp,
+            clones: l.clones().iter().map(|c| c.name()).collect(),
+            name,
         }
     }
 }
+
 /// create a pool to that can be used to provision replicas.
 ///
 /// This method should be idempotent if the pool exists. To validate
@@ -121,14 +173,37 @@ pub fn list() -> GrpcResult<ListPoolsReply> {
     }))
 }
 
+/// the checksum algorithm of the given RPC value
+fn checksum_algorithm(algorithm: i32) -> Option<ChecksumAlgorithm> {
+    match RpcChecksumAlgorithm::from_i32(algorithm)? {
+        RpcChecksumAlgorithm::ChecksumCrc32c => Some(ChecksumAlgorithm::Crc32c),
+        RpcChecksumAlgorithm::ChecksumXxhash64 => {
+            Some(ChecksumAlgorithm::XxHash64)
+        }
+        RpcChecksumAlgorithm::ChecksumSha256 => Some(ChecksumAlgorithm::Sha256),
+    }
+}
+
+/// the compression algorithm of the given RPC value
+fn compression_algorithm(algorithm: i32) -> Option<CompressionAlgorithm> {
+    match RpcCompressionAlgorithm::from_i32(algorithm)? {
+        RpcCompressionAlgorithm::CompressionLz4 => {
+            Some(CompressionAlgorithm::Lz4)
+        }
+        RpcCompressionAlgorithm::CompressionZstd => {
+            Some(CompressionAlgorithm::Zstd)
+        }
+    }
+}
+
 /// create a replica on the given pool returns an OK if the lvol already
 /// exist. If replica fails to share, it will be destroyed prior to returning
 /// an error.
 #[instrument(level = "debug", err)]
 pub async fn create_replica(args: CreateReplicaRequest) -> GrpcResult<Replica> {
-    if Lvs::lookup(&args.pool).is_none() {
-        return Err(Status::not_found(args.pool));
-    }
+    // a pool which is still being imported is reported as unavailable
+    // rather than missing, so the request is retried
+    Lvs::lookup_result(&args.pool)?;
 
     if let Some(b) = Bdev::lookup_by_name(&args.uuid) {
         let lvol = Lvol::try_from(b)?;
@@ -142,9 +217,72 @@ pub async fn create_replica(args: CreateReplicaRequest) -> GrpcResult<Replica> {
         )));
     }
 
+    let algorithm = checksum_algorithm(args.checksum_algorithm).ok_or_else(|| {
+        Status::invalid_argument(format!(
+            "invalid checksum algorithm {}",
+            args.checksum_algorithm
+        ))
+    })?;
+
+    let compression = compression_algorithm(args.compression_algorithm)
+        .ok_or_else(|| {
+            Status::invalid_argument(format!(
+                "invalid compression algorithm {}",
+                args.compression_algorithm
+            ))
+        })?;
+
+    if args.compression && (args.protection || args.checksum) {
+        return Err(Status::invalid_argument(
+            "compressed replicas can not have checksums or protection \
+             information",
+        ));
+    }
+
+    // the replicas of a pool with a dedup store are all deduplicated
+    let dedup = Lvs::lookup(&args.pool)
+        .map_or(false, |p| p.dedup_store().is_some());
+    if dedup && (args.compression || args.protection || args.checksum) {
+        return Err(Status::invalid_argument(
+            "replicas of a deduplicated pool can not be compressed or have \
+             checksums or protection information",
+        ));
+    }
+
     rpc_call(async move {
         let p = Lvs::lookup(&args.pool).unwrap();
-        match p.create_lvol(&args.uuid, args.size, false).await {
+        let created = if dedup {
+            p.create_dedup_lvol(&args.uuid, args.size).await
+        } else if args.compression {
+            p.create_compressed_lvol(&args.uuid, args.size, compression)
+                .await
+        } else {
+            p.create_lvol(&args.uuid, args.size, false).await
+        };
+        let lvol = match created {
+            Ok(lvol) if args.protection || args.checksum => {
+                let result = if args.protection {
+                    lvol.set(PropValue::Protected(true)).await
+                } else {
+                    match lvol.set(PropValue::Checksum(true)).await {
+                        Ok(_) => {
+                            lvol.set(PropValue::ChecksumAlgorithm(algorithm))
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    }
+                };
+                match result {
+                    Ok(_) => Ok(lvol),
+                    Err(e) => {
+                        let _ = lvol.destroy().await;
+                        Err(e)
+                    }
+                }
+            }
+            result => result,
+        };
+        match lvol {
             Ok(lvol) if Protocol::from(args.share) == Protocol::Nvmf => {
                 match lvol.share_nvmf().await {
                     Ok(s) => {
@@ -162,10 +300,21 @@ pub async fn create_replica(args: CreateReplicaRequest) -> GrpcResult<Replica> {
                     }
                 }
             }
-            Ok(lvol) => {
-                debug!("created lvol {}", lvol);
-                Ok(lvol)
-            }
+            Ok(lvol) => match lvol.open_local().await {
+                Ok(_) => {
+                    debug!("created lvol {}", lvol);
+                    Ok(lvol)
+                }
+                Err(e) => {
+                    debug!(
+                        "failed to open created lvol {}: {} .. destroying",
+                        lvol,
+                        e.to_string()
+                    );
+                    let _ = lvol.destroy().await;
+                    Err(e)
+                }
+            },
             Err(e) => Err(e),
         }
     })
@@ -194,7 +343,9 @@ pub fn list_replicas() -> GrpcResult<ListReplicasReply> {
         replicas = bdev
             .into_iter()
             .filter(|b| b.driver() == "lvol")
-            .map(|b| Replica::from(Lvol::try_from(b).unwrap()))
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| !l.is_reserved())
+            .map(Replica::from)
             .collect::<Vec<_>>();
     }
 
@@ -217,14 +368,17 @@ pub async fn share_replica(
 
             // if we are already shared return OK
             if lvol.shared() == Some(Protocol::from(args.share)) {
-                return Ok(ShareReplicaReply {
-                    uri: lvol.share_uri().unwrap(),
-                });
+                if let Some(uri) = lvol.share_uri() {
+                    return Ok(ShareReplicaReply {
+                        uri,
+                    });
+                }
             }
             match Protocol::from(args.share) {
                 Protocol::Off => {
-                    lvol.unshare().await.map(|_| ShareReplicaReply {
-                        uri: format!("bdev:///{}", lvol.name()),
+                    lvol.unshare().await?;
+                    lvol.open_local().await.map(|_| ShareReplicaReply {
+                        uri: lvol.share_uri().unwrap(),
                     })
                 }
 
@@ -233,12 +387,12 @@ pub async fn share_replica(
                         uri: lvol.share_uri().unwrap(),
                     })
                 }
-                Protocol::Iscsi => Err(LvsError::LvolShare {
-                    source: CoreError::NotSupported {
-                        source: Errno::ENOSYS,
-                    },
-                    name: args.uuid,
-                }),
+                Protocol::Iscsi => {
+                    lvol.share_iscsi().await.map(|uri| ShareReplicaReply {
+                        uri,
+                    })
+                }
+                Protocol::Nbd => unreachable!("NBD is not shared over gRPC"),
             }
         } else {
             Err(LvsError::InvalidBdev {
@@ -251,6 +405,109 @@ pub async fn share_replica(
     })
 }
 
+/// grow the replica to the requested size, returns OK if the replica already
+/// has that size
+#[instrument(level = "debug", err)]
+pub async fn resize_replica(args: ResizeReplicaRequest) -> GrpcResult<Replica> {
+    rpc_call(async move {
+        if let Some(b) = Bdev::lookup_by_name(&args.uuid) {
+            let lvol = Lvol::try_from(b)?;
+            lvol.resize(args.size, false).await?;
+            Ok(Replica::from(lvol))
+        } else {
+            Err(LvsError::InvalidBdev {
+                source: NexusBdevError::BdevNotFound {
+                    name: args.uuid.clone(),
+                },
+                name: args.uuid,
+            })
+        }
+    })
+}
+
+/// list all the snapshots
+#[instrument(level = "debug", err)]
+pub fn list_snapshots() -> GrpcResult<ListSnapshotsReply> {
+    let mut snapshots = Vec::new();
+    if let Some(bdev) = Bdev::bdev_first() {
+        snapshots = bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.is_snapshot())
+            .map(Snapshot::from)
+            .collect::<Vec<_>>();
+    }
+
+    Ok(Response::new(ListSnapshotsReply {
+        snapshots,
+    }))
+}
+
+/// destroy the snapshot, returning OK if the snapshot was not found. Fails
+/// with FAILED_PRECONDITION while there are replicas cloned from it.
+#[instrument(level = "debug", err)]
+pub async fn destroy_snapshot(
+    args: DestroySnapshotRequest,
+) -> GrpcResult<Null> {
+    rpc_call(async move {
+        match Bdev::lookup_by_name(&args.name) {
+            Some(b) => {
+                let lvol = Lvol::try_from(b)?;
+                if !lvol.is_snapshot() {
+                    return Err(LvsError::Invalid {
+                        source: Errno::EINVAL,
+                        msg: format!("{} is not a snapshot", args.name),
+                    });
+                }
+                lvol.destroy_snapshot().await.map(|_r| Null {})
+            }
+            None => Ok(Null {}),
+        }
+    })
+}
+
+/// create a replica as a clone of the snapshot, in the pool of the snapshot.
+/// Returns OK if the replica already exists.
+#[instrument(level = "debug", err)]
+pub async fn clone_snapshot(args: CloneSnapshotRequest) -> GrpcResult<Replica> {
+    if let Some(b) = Bdev::lookup_by_name(&args.uuid) {
+        let lvol = Lvol::try_from(b)?;
+        return Ok(Response::new(Replica::from(lvol)));
+    }
+
+    if Bdev::lookup_by_name(&args.snapshot).is_none() {
+        return Err(Status::not_found(args.snapshot));
+    }
+
+    if !matches!(Protocol::from(args.share), Protocol::Off | Protocol::Nvmf) {
+        return Err(Status::invalid_argument(format!(
+            "invalid protocol {}",
+            args.share
+        )));
+    }
+
+    rpc_call(async move {
+        let snapshot =
+            Lvol::try_from(Bdev::lookup_by_name(&args.snapshot).unwrap())?;
+        let lvol = snapshot.create_clone(&args.uuid).await?;
+        let result = match Protocol::from(args.share) {
+            Protocol::Nvmf => lvol.share_nvmf().await.map(|_| ()),
+            _ => lvol.open_local().await.map(|_| ()),
+        };
+        if let Err(e) = result {
+            debug!(
+                "failed to share or open cloned lvol {}: {} .. destroying",
+                lvol,
+                e.to_string()
+            );
+            let _ = lvol.destroy().await;
+            return Err(e);
+        }
+        Ok(lvol)
+    })
+}
+
 /// get the stats of replica's (lvol's only)
 #[instrument(level = "debug", err)]
 pub async fn stat_replica() -> GrpcResult<StatReplicasReply> {
@@ -259,7 +516,9 @@ pub async fn stat_replica() -> GrpcResult<StatReplicasReply> {
         if let Some(bdev) = Bdev::bdev_first() {
             bdev.into_iter()
                 .filter(|b| b.driver() == "lvol")
-                .for_each(|b| lvols.push(Lvol::try_from(b).unwrap()))
+                .map(|b| Lvol::try_from(b).unwrap())
+                .filter(|l| !l.is_reserved())
+                .for_each(|l| lvols.push(l))
         }
 
         let mut replicas = Vec::new();
@@ -269,10 +528,13 @@ pub async fn stat_replica() -> GrpcResult<StatReplicasReply> {
                 error!("failed to get stats for lvol: {}", l);
             }
 
+            let usage = l.stats();
             replicas.push(ReplicaStats {
                 uuid: l.name(),
                 pool: l.pool(),
                 stats: stats.ok().map(Stats::from),
+                size: usage.provisioned_bytes,
+                allocated: usage.allocated_bytes,
             });
         }
 
@@ -0,0 +1,119 @@
+//!
+//! Read-only snapshot of the data plane, the bdevs, pools, lvols and nexuses
+//! of this instance, as served by the DumpState gRPC method. The snapshot is
+//! taken on the management core without yielding in between, so nothing is
+//! created or destroyed while it is taken and no IO is done to take it.
+
+use serde::Serialize;
+use tonic::{Response, Status};
+
+use rpc::mayastor::DumpStateReply;
+
+use crate::{
+    bdev::nexus::{
+        instances,
+        nexus_bdev::{Nexus, NexusStatus},
+        nexus_child::{ChildState, NexusChild},
+    },
+    core::{Bdev, Protocol, Share},
+    grpc::GrpcResult,
+    lvs::Lvs,
+};
+
+/// the state of a bdev of any type
+#[derive(Debug, Serialize)]
+pub struct BdevState {
+    name: String,
+    product_name: String,
+    size: u64,
+    block_len: u32,
+    claimed_by: Option<String>,
+}
+
+impl From<Bdev> for BdevState {
+    fn from(b: Bdev) -> Self {
+        Self {
+            name: b.name(),
+            product_name: b.product_name(),
+            size: b.size_in_bytes(),
+            block_len: b.block_len(),
+            claimed_by: b.claimed_by(),
+        }
+    }
+}
+
+/// the health of a nexus child and the progress of its rebuild, if any
+#[derive(Debug, Serialize)]
+pub struct ChildDump {
+    name: String,
+    state: ChildState,
+    rebuild_progress: Option<i32>,
+}
+
+impl From<&NexusChild> for ChildDump {
+    fn from(c: &NexusChild) -> Self {
+        let progress = c.get_rebuild_progress();
+        Self {
+            name: c.name.clone(),
+            state: c.state(),
+            rebuild_progress: if progress < 0 { None } else { Some(progress) },
+        }
+    }
+}
+
+/// the state of a nexus and its children
+#[derive(Debug, Serialize)]
+pub struct NexusDump {
+    name: String,
+    size: u64,
+    status: NexusStatus,
+    shared: Option<Protocol>,
+    share_uri: Option<String>,
+    children: Vec<ChildDump>,
+}
+
+impl From<&Nexus> for NexusDump {
+    fn from(n: &Nexus) -> Self {
+        Self {
+            name: n.name.clone(),
+            size: n.size,
+            status: n.status(),
+            shared: n.shared(),
+            share_uri: n.get_share_uri(),
+            children: n.children.iter().map(ChildDump::from).collect(),
+        }
+    }
+}
+
+/// the state of the data plane, pools are serialized along with their lvols
+#[derive(Serialize)]
+pub struct StateDump {
+    bdevs: Vec<BdevState>,
+    pools: Vec<Lvs>,
+    nexuses: Vec<NexusDump>,
+}
+
+/// take a snapshot of the state of the data plane, which must be done on the
+/// management core
+pub fn dump() -> serde_json::Value {
+    let state = StateDump {
+        bdevs: Bdev::bdev_first()
+            .into_iter()
+            .flat_map(|b| b.into_iter())
+            .map(BdevState::from)
+            .collect(),
+        pools: Lvs::iter().collect(),
+        nexuses: instances().iter().map(|n| NexusDump::from(&**n)).collect(),
+    };
+
+    serde_json::to_value(state).expect("state dump must serialize")
+}
+
+pub fn dump_state() -> GrpcResult<DumpStateReply> {
+    let json = serde_json::to_string_pretty(&dump())
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(Response::new(DumpStateReply {
+        json,
+    }))
+}
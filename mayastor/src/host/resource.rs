@@ -1,10 +1,13 @@
 //!
 //! This module implements the get_resource_usage() gRPC method,
-//! which retrieves information via the getrusage(2) system call.
+//! which retrieves information via the getrusage(2) system call
+//! along with the exhaustion count of the bdev IO pool.
 
 use ::rpc::mayastor::ResourceUsage;
 use std::{io::Error, mem::MaybeUninit, os::raw::c_int};
 
+use crate::core::IoPool;
+
 fn getrusage(who: c_int) -> Result<libc::rusage, Error> {
     let mut data: MaybeUninit<libc::rusage> = MaybeUninit::uninit();
 
@@ -31,6 +34,7 @@ impl From<Usage<'_>> for ResourceUsage {
             signals: rusage.ru_nsignals,
             vol_csw: rusage.ru_nvcsw,
             invol_csw: rusage.ru_nivcsw,
+            bdev_io_exhausted: IoPool::stats().exhausted,
         }
     }
 }
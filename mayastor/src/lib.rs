@@ -12,6 +12,7 @@ extern crate spdk_sys;
 pub mod bdev;
 pub mod core;
 pub mod delay;
+pub mod events;
 pub mod ffihelper;
 pub mod grpc;
 pub mod host;
@@ -37,4 +38,10 @@ macro_rules! CPS_INIT {
 pub extern "C" fn cps_init() {
     subsys::register_subsystem();
     bdev::nexus::register_module();
+    bdev::cache::register_module();
+    bdev::compress::register_module();
+    bdev::dedup::register_module();
+    bdev::pi::register_module();
+    bdev::readonly::register_module();
+    bdev::tier::register_module();
 }
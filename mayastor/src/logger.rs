@@ -1,8 +1,29 @@
-use std::{ffi::CStr, os::raw::c_char, path::Path};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    ffi::CStr,
+    fmt::Write,
+    os::raw::c_char,
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use ansi_term::{Colour, Style};
-
-use tracing_core::{event::Event, Metadata};
+use once_cell::sync::{Lazy, OnceCell};
+
+use tracing_core::{
+    callsite::Callsite,
+    event::Event,
+    field::{Field, Value, Visit},
+    metadata,
+    metadata::{Kind, LevelFilter},
+    span,
+    subscriber::{Interest, Subscriber},
+    Level,
+    Metadata,
+};
 use tracing_log::{LogTracer, NormalizeEvent};
 use tracing_subscriber::{
     fmt::{
@@ -11,6 +32,7 @@ use tracing_subscriber::{
         FormattedFields,
     },
     registry::LookupSpan,
+    reload,
     EnvFilter,
 };
 
@@ -258,27 +280,332 @@ where
 ///
 /// We might want to suppress certain messages, as some of them are redundant,
 /// in particular, the NOTICE messages as such, they are mapped to debug.
+/// replaces the filter of the subscriber set up by init
+type FilterReload = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+static FILTER_RELOAD: OnceCell<FilterReload> = OnceCell::new();
+
+/// the filter last set at runtime
+static FILTER: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Change which trace events are logged at runtime, the filter has the
+/// syntax of RUST_LOG, e.g. "info,mayastor=debug".
+pub fn set_filter(filter: &str) -> Result<(), String> {
+    let env_filter = EnvFilter::try_new(filter).map_err(|e| e.to_string())?;
+    match FILTER_RELOAD.get() {
+        Some(reload) => reload(env_filter)?,
+        None => return Err("the logger is not initialised".to_string()),
+    }
+    *FILTER.lock().unwrap() = Some(filter.to_string());
+    Ok(())
+}
+
+/// the filter set at runtime, if any
+pub fn filter() -> Option<String> {
+    FILTER.lock().unwrap().clone()
+}
+
+/// Format in which the trace events are logged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LogFormat {
+    /// human readable text
+    Text,
+    /// one JSON object per event, with its timestamp, level, target and
+    /// fields, for log aggregation
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
 pub fn init(level: &str) {
+    init_with_format(level, LogFormat::Text)
+}
+
+pub fn init_with_format(level: &str, format: LogFormat) {
     // Set up a "logger" that simply translates any "log" messages it receives
     // to trace events. This is for our custom spdk log messages, but also
     // for any other third party crates still using the logging facade.
     LogTracer::init().expect("failed to initialise LogTracer");
 
-    // Our own custom format for displaying trace events.
-    let format = CustomFormat {
-        ansi: atty::is(atty::Stream::Stdout),
-    };
-
     // Create a default subscriber.
     let builder = tracing_subscriber::fmt::Subscriber::builder()
-        .with_span_events(FmtSpan::FULL)
-        .event_format(format);
+        .with_span_events(FmtSpan::FULL);
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(level));
+
+    match format {
+        LogFormat::Text => {
+            // Our own custom format for displaying trace events.
+            let format = CustomFormat {
+                ansi: atty::is(atty::Stream::Stdout),
+            };
+            let builder = builder
+                .event_format(format)
+                .with_env_filter(filter)
+                .with_filter_reloading();
+            set_filter_reload(builder.reload_handle());
+            set_subscriber(builder.finish());
+        }
+        LogFormat::Json => {
+            let builder = builder
+                .with_ansi(false)
+                .json()
+                .with_env_filter(filter)
+                .with_filter_reloading();
+            set_filter_reload(builder.reload_handle());
+            set_subscriber(builder.finish());
+        }
+    }
+}
+
+/// keep the handle through which set_filter replaces the filter
+fn set_filter_reload<S>(handle: reload::Handle<EnvFilter, S>)
+where
+    S: Subscriber + Send + Sync + 'static,
+{
+    FILTER_RELOAD
+        .set(Box::new(move |filter| {
+            handle.reload(filter).map_err(|e| e.to_string())
+        }))
+        .ok();
+}
 
-    let subscriber = match EnvFilter::try_from_default_env() {
-        Ok(filter) => builder.with_env_filter(filter).finish(),
-        Err(_) => builder.with_env_filter(level).finish(),
-    };
+/// set the subscriber, rate limited, as the global default
+fn set_subscriber<S>(subscriber: S)
+where
+    S: Subscriber + Send + Sync + 'static,
+{
+    let subscriber = RateLimit::new(subscriber, RATE_LIMIT_PERIOD);
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("failed to set default subscriber");
 }
+
+/// Target of trace events which are rate limited, for warnings on hot paths
+/// which may repeat for every IO, e.g.
+/// `warn!(target: RATE_LIMITED, "{}: IO error on child {}", nexus, child)`.
+pub const RATE_LIMITED: &str = "mayastor::rate_limited";
+
+/// period over which repeats of a rate limited event are collapsed
+pub const RATE_LIMIT_PERIOD: Duration = Duration::from_secs(10);
+
+/// the number of distinct rate limited events kept track of, further ones
+/// are not limited until the periods of others have passed
+const RATE_LIMIT_KEYS: usize = 1024;
+
+/// the fields of an event as they are formatted
+struct EventFields(String);
+
+impl Visit for EventFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = if field.name() == "message" {
+            write!(self.0, "{:?}", value)
+        } else {
+            write!(self.0, "{}={:?}", field.name(), value)
+        };
+    }
+}
+
+struct SummaryCallsite;
+
+static SUMMARY_CALLSITE: SummaryCallsite = SummaryCallsite;
+
+static SUMMARY_METADATA: Metadata<'static> = metadata! {
+    name: "rate limit summary",
+    target: module_path!(),
+    level: Level::WARN,
+    fields: &["message"],
+    callsite: &SUMMARY_CALLSITE,
+    kind: Kind::EVENT,
+};
+
+impl Callsite for SummaryCallsite {
+    fn set_interest(&self, _interest: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        &SUMMARY_METADATA
+    }
+}
+
+/// the period of a rate limited event
+struct Window {
+    start: Instant,
+    /// the number of repeats suppressed within the period
+    suppressed: u64,
+}
+
+/// Rate limits the trace events with the RATE_LIMITED target before they
+/// reach the wrapped subscriber. The first occurrence of an event, by its
+/// fields, is passed on and its repeats within the period are suppressed.
+/// The next occurrence after the period is passed on preceded by a summary
+/// of the number of occurrences suppressed. All other events and spans are
+/// passed on as they are.
+pub struct RateLimit<S> {
+    inner: S,
+    period: Duration,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl<S: Subscriber> RateLimit<S> {
+    pub fn new(inner: S, period: Duration) -> Self {
+        Self {
+            inner,
+            period,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the event with the given fields is passed on, along with
+    /// the number of its occurrences suppressed and over how long if a
+    /// summary is due.
+    fn admit(&self, key: &str) -> (bool, Option<(u64, Duration)>) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        if let Some(window) = windows.get_mut(key) {
+            let elapsed = now.duration_since(window.start);
+            if elapsed < self.period {
+                window.suppressed += 1;
+                return (false, None);
+            }
+            let summary = match window.suppressed {
+                0 => None,
+                suppressed => Some((suppressed, elapsed)),
+            };
+            window.start = now;
+            window.suppressed = 0;
+            return (true, summary);
+        }
+
+        if windows.len() >= RATE_LIMIT_KEYS {
+            let period = self.period;
+            windows.retain(|_, w| now.duration_since(w.start) < period);
+        }
+        if windows.len() < RATE_LIMIT_KEYS {
+            windows.insert(
+                key.to_string(),
+                Window {
+                    start: now,
+                    suppressed: 0,
+                },
+            );
+        }
+        (true, None)
+    }
+
+    /// pass a summary of the suppressed occurrences of an event on to the
+    /// wrapped subscriber
+    fn summary(&self, key: &str, suppressed: u64, elapsed: Duration) {
+        let fields = SUMMARY_METADATA.fields();
+        let message = fields.field("message").unwrap();
+        let args = format_args!(
+            "{} more occurrences in last {:.0?}: {}",
+            suppressed,
+            elapsed,
+            key
+        );
+        let values = [(&message, Some(&args as &dyn Value))];
+        self.inner
+            .event(&Event::new(&SUMMARY_METADATA, &fields.value_set(&values)));
+    }
+}
+
+impl<S: Subscriber> Subscriber for RateLimit<S> {
+    fn register_callsite(
+        &self,
+        metadata: &'static Metadata<'static>,
+    ) -> Interest {
+        self.inner.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        self.inner.new_span(span)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        self.inner.record(span, values)
+    }
+
+    fn record_follows_from(&self, span: &span::Id, follows: &span::Id) {
+        self.inner.record_follows_from(span, follows)
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        if event.metadata().target() == RATE_LIMITED {
+            let mut fields = EventFields(String::new());
+            event.record(&mut fields);
+            let (admit, summary) = self.admit(&fields.0);
+            if let Some((suppressed, elapsed)) = summary {
+                self.summary(&fields.0, suppressed, elapsed);
+            }
+            if !admit {
+                return;
+            }
+        }
+        self.inner.event(event)
+    }
+
+    fn enter(&self, span: &span::Id) {
+        self.inner.enter(span)
+    }
+
+    fn exit(&self, span: &span::Id) {
+        self.inner.exit(span)
+    }
+
+    fn clone_span(&self, id: &span::Id) -> span::Id {
+        self.inner.clone_span(id)
+    }
+
+    fn try_close(&self, id: span::Id) -> bool {
+        self.inner.try_close(id)
+    }
+
+    fn current_span(&self) -> span::Current {
+        self.inner.current_span()
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(self as *const _ as *const ())
+        } else {
+            self.inner.downcast_raw(id)
+        }
+    }
+}
@@ -38,6 +38,106 @@ pub enum Error {
     #[snafu(display("failed to destroy lvol {}", name))]
     RepDestroy { source: Errno, name: String },
 
+    #[snafu(display("failed to resize lvol {}", name))]
+    RepResize { source: Errno, name: String },
+
+    #[snafu(display(
+        "pool {} has {} bytes available, not enough to grow lvol {} to {} \
+         bytes",
+        pool,
+        available,
+        name,
+        size
+    ))]
+    RepNoSpace {
+        source: Errno,
+        name: String,
+        pool: String,
+        size: u64,
+        available: u64,
+    },
+
+    #[snafu(display("failed to inflate lvol {}", name))]
+    RepInflate { source: Errno, name: String },
+
+    #[snafu(display(
+        "pool {} has {} bytes available, not enough to allocate all of lvol {}",
+        pool,
+        available,
+        name
+    ))]
+    RepInflateNoSpace {
+        source: Errno,
+        name: String,
+        pool: String,
+        available: u64,
+    },
+
+    #[snafu(display("failed to snapshot lvol {} as {}", name, snapshot))]
+    RepSnapshot {
+        source: Errno,
+        name: String,
+        snapshot: String,
+    },
+
+    #[snafu(display("failed to clone snapshot {} as {}", snapshot, name))]
+    RepClone {
+        source: Errno,
+        snapshot: String,
+        name: String,
+    },
+
+    #[snafu(display("snapshot {} still has clones: {}", name, clones))]
+    SnapshotInUse {
+        source: Errno,
+        name: String,
+        clones: String,
+    },
+
+    #[snafu(display("pool {} is not pool {}", found, uuid))]
+    UuidMismatch {
+        source: Errno,
+        uuid: String,
+        found: String,
+    },
+
+    #[snafu(display("pool {} not found", name))]
+    PoolNotFound { source: Errno, name: String },
+
+    #[snafu(display(
+        "pool {} is busy being imported, created, exported or destroyed",
+        name
+    ))]
+    PoolBusy { source: Errno, name: String },
+
+    #[snafu(display("pool {} is in an inconsistent state: {}", name, msg))]
+    PoolCorrupt {
+        source: Errno,
+        name: String,
+        msg: String,
+    },
+
+    #[snafu(display(
+        "pool {} has format version {}, this version of mayastor supports up \
+         to {}",
+        name,
+        found,
+        supported
+    ))]
+    PoolVersionMismatch {
+        source: Errno,
+        name: String,
+        found: u32,
+        supported: u32,
+    },
+
+    #[snafu(display("metadata journal of pool {} failed: {}", name, msg))]
+    Journal {
+        source: Errno,
+        name: String,
+        msg: String,
+    },
+
     #[snafu(display("bdev {} is not a lvol", name))]
     NotALvol { source: Errno, name: String },
 
@@ -47,6 +147,33 @@ pub enum Error {
     #[snafu(display("failed to unshare lvol {}", name))]
     LvolUnShare { source: CoreError, name: String },
 
+    #[snafu(display("failed to open lvol {}", name))]
+    LvolOpen { source: CoreError, name: String },
+
+    #[snafu(display("failed to protect lvol {}", name))]
+    Protect {
+        source: NexusBdevError,
+        name: String,
+    },
+
+    #[snafu(display("failed to compress lvol {}", name))]
+    Compress {
+        source: NexusBdevError,
+        name: String,
+    },
+
+    #[snafu(display("failed to share lvol {} read-only", name))]
+    ReadOnly {
+        source: NexusBdevError,
+        name: String,
+    },
+
+    #[snafu(display("failed to deduplicate lvol {}", name))]
+    Dedup {
+        source: NexusBdevError,
+        name: String,
+    },
+
     #[snafu(display(
         "failed to get property {} ({}) from {}",
         prop,
@@ -1,7 +1,7 @@
 use std::{
     convert::TryFrom,
     ffi::{c_void, CStr},
-    fmt::Display,
+    fmt::{Debug, Display},
     os::raw::c_char,
     ptr::NonNull,
 };
@@ -10,23 +10,51 @@ use async_trait::async_trait;
 use futures::channel::oneshot;
 use nix::errno::Errno;
 use pin_utils::core_reexport::fmt::Formatter;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use tracing::instrument;
 
 use spdk_sys::{
+    spdk_blob_get_num_allocated_clusters,
+    spdk_blob_get_parent_snapshot,
     spdk_blob_get_xattr_value,
     spdk_blob_is_read_only,
     spdk_blob_is_snapshot,
     spdk_blob_set_xattr,
     spdk_blob_sync_md,
     spdk_lvol,
+    spdk_lvol_inflate,
+    vbdev_lvol_create_clone,
     vbdev_lvol_create_snapshot,
     vbdev_lvol_destroy,
     vbdev_lvol_get_from_bdev,
+    vbdev_lvol_resize,
 };
 
 use crate::{
-    bdev::nexus::nexus_bdev::Nexus,
-    core::{Bdev, CoreError, Mthread, Protocol, Share},
+    bdev::{
+        compress_lookup,
+        dedup_lookup,
+        nexus::nexus_bdev::Nexus,
+        pi_lookup,
+        readonly_lookup,
+        CompressBdev,
+        CompressStats,
+        CompressionAlgorithm,
+        DedupBdev,
+        PiBdev,
+        PiFormat,
+        ReadOnlyBdev,
+    },
+    core::{
+        Bdev,
+        BdevHandle,
+        ChecksumAlgorithm,
+        CoreError,
+        Mthread,
+        Protocol,
+        Reactors,
+        Share,
+    },
     ffihelper::{
         cb_arg,
         errno_result_from_i32,
@@ -35,8 +63,10 @@ use crate::{
         FfiResult,
         IntoCString,
     },
-    lvs::{error::Error, lvs_pool::Lvs},
-    subsys::NvmfReq,
+    events::{self, Event},
+    lvs::{error::Error, lvs_pool::Lvs, Journal, JournalOp},
+    subsys::{NvmfReq, NvmfSubsystem, Reservations},
+    target::{iscsi, nvmf, Side},
 };
 
 /// properties we allow for being set on the lvol, this information is stored on
@@ -45,18 +75,48 @@ use crate::{
 #[non_exhaustive]
 pub enum PropValue {
     Shared(bool),
+    Protected(bool),
+    Checksum(bool),
+    ChecksumAlgorithm(ChecksumAlgorithm),
+    VerifyWrites(bool),
+    IntegrityReserve(u32),
+    IntegrityMetadata(u64),
+    Compressed(bool),
+    CompressionAlgorithm(CompressionAlgorithm),
+    Dedup(bool),
+    Clone(bool),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[non_exhaustive]
 pub enum PropName {
     Shared,
+    Protected,
+    Checksum,
+    ChecksumAlgorithm,
+    VerifyWrites,
+    IntegrityReserve,
+    IntegrityMetadata,
+    Compressed,
+    CompressionAlgorithm,
+    Dedup,
+    Clone,
 }
 
 impl From<PropValue> for PropName {
     fn from(v: PropValue) -> Self {
         match v {
             PropValue::Shared(_) => Self::Shared,
+            PropValue::Protected(_) => Self::Protected,
+            PropValue::Checksum(_) => Self::Checksum,
+            PropValue::ChecksumAlgorithm(_) => Self::ChecksumAlgorithm,
+            PropValue::VerifyWrites(_) => Self::VerifyWrites,
+            PropValue::IntegrityReserve(_) => Self::IntegrityReserve,
+            PropValue::IntegrityMetadata(_) => Self::IntegrityMetadata,
+            PropValue::Compressed(_) => Self::Compressed,
+            PropValue::CompressionAlgorithm(_) => Self::CompressionAlgorithm,
+            PropValue::Dedup(_) => Self::Dedup,
+            PropValue::Clone(_) => Self::Clone,
         }
     }
 }
@@ -65,12 +125,31 @@ impl Display for PropName {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let name = match self {
             PropName::Shared => "shared",
+            PropName::Protected => "protected",
+            PropName::Checksum => "checksum",
+            PropName::ChecksumAlgorithm => "checksum_algorithm",
+            PropName::VerifyWrites => "verify_writes",
+            PropName::IntegrityReserve => "integrity_reserve",
+            PropName::IntegrityMetadata => "integrity_metadata",
+            PropName::Compressed => "compressed",
+            PropName::CompressionAlgorithm => "compression_algorithm",
+            PropName::Dedup => "dedup",
+            PropName::Clone => "clone",
         };
         write!(f, "{}", name)
     }
 }
 
-#[derive(Debug)]
+/// The space an lvol is provisioned with and the part of it allocated in its
+/// pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LvolStats {
+    /// the size of the lvol
+    pub provisioned_bytes: u64,
+    /// the bytes allocated to the lvol, see Lvol::allocated()
+    pub allocated_bytes: u64,
+}
+
 /// struct representing an lvol
 pub struct Lvol(pub(crate) NonNull<spdk_lvol>);
 
@@ -99,9 +178,55 @@ impl From<Lvol> for Bdev {
     }
 }
 
+/// renders the lvol as `pool/name (uuid, size bytes)`
 impl Display for Lvol {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}/{}", self.pool(), self.name())
+        write!(
+            f,
+            "{}/{} ({}, {} bytes)",
+            self.pool(),
+            self.name(),
+            self.uuid(),
+            self.size()
+        )
+    }
+}
+
+/// the live state of the lvol, as far as it is known without doing IO
+impl Serialize for Lvol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Lvol", 11)?;
+        s.serialize_field("name", &self.name())?;
+        s.serialize_field("pool", &self.pool())?;
+        s.serialize_field("uuid", &self.uuid())?;
+        s.serialize_field("size", &self.size())?;
+        s.serialize_field("allocated", &self.allocated())?;
+        s.serialize_field("thin", &self.is_thin())?;
+        s.serialize_field("snapshot", &self.is_snapshot())?;
+        s.serialize_field("read_only", &self.is_read_only())?;
+        s.serialize_field("shared", &self.shared())?;
+        s.serialize_field("share_uri", &self.share_uri())?;
+        s.serialize_field("compression", &self.compression_stats())?;
+        s.end()
+    }
+}
+
+impl Debug for Lvol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lvol")
+            .field("name", &self.name())
+            .field("pool", &self.pool())
+            .field("uuid", &self.uuid())
+            .field("size", &self.size())
+            .field("allocated", &self.allocated())
+            .field("thin", &self.is_thin())
+            .field("snapshot", &self.is_snapshot())
+            .field("shared", &self.shared())
+            .field("share_uri", &self.share_uri())
+            .finish()
     }
 }
 
@@ -110,34 +235,98 @@ impl Share for Lvol {
     type Error = Error;
     type Output = String;
 
-    /// we dont (want to) support replica's over iSCSI
+    /// Share the lvol as an iscsi target and return its URI, for initiators
+    /// which do not speak nvmf. The share is not recorded on disk, so it does
+    /// not outlive the pool being exported.
+    #[instrument(level = "debug", err)]
     async fn share_iscsi(&self) -> Result<Self::Output, Self::Error> {
-        Err(Error::LvolShare {
-            source: CoreError::NotSupported {
-                source: Errno::EINVAL,
+        self.check_share(Protocol::Iscsi)?;
+        if self.shared() == Some(Protocol::Iscsi) {
+            return Ok(self.share_uri().unwrap());
+        }
+        self.check_not_layered()?;
+
+        iscsi::share(&self.name(), &self.as_bdev(), Side::Replica).map_err(
+            |e| Error::LvolShare {
+                source: CoreError::ShareIscsi {
+                    source: e,
+                },
+                name: self.name(),
             },
-            name: self.name(),
-        })
+        )?;
+        info!("shared {} over iscsi", self);
+        Ok(self.share_uri().unwrap())
     }
 
-    /// share the lvol as a nvmf target
+    /// share the lvol as a nvmf target, a lvol with protection information is
+    /// shared through the PI bdev on top of it, a compressed lvol through
+    /// the compress bdev on top of it and a deduplicated lvol through the
+    /// dedup bdev on top of it. Snapshots are always shared read-only.
     #[instrument(level = "debug", err)]
     async fn share_nvmf(&self) -> Result<Self::Output, Self::Error> {
-        let share = self.as_bdev().share_nvmf().await.map_err(|e| {
-            Error::LvolShare {
-                source: e,
+        self.check_share(Protocol::Nvmf)?;
+        if self.is_snapshot() {
+            return self.share_nvmf_ro().await;
+        }
+        if self.is_shared_read_only() || self.is_shared_with_reservations() {
+            return Err(Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    name: self.name(),
+                    protocol: Protocol::Nvmf,
+                },
                 name: self.name(),
+            });
+        }
+        let share = match (
+            self.pi_format().await,
+            self.compression(),
+            self.is_dedup(),
+        ) {
+            (Some(format), ..) => self.share_protected(format).await?,
+            (None, Some(algorithm), _) => {
+                self.share_compressed(algorithm).await?
             }
-        })?;
+            (None, None, true) => self.share_dedup().await?,
+            (None, None, false) => {
+                self.as_bdev().share_nvmf().await.map_err(|e| {
+                    Error::LvolShare {
+                        source: e,
+                        name: self.name(),
+                    }
+                })?
+            }
+        };
 
         self.set(PropValue::Shared(true)).await?;
         info!("shared {}", self);
         Ok(share)
     }
 
-    /// unshare the nvmf target
+    /// Export the lvol as a local NBD device and return its path, to put a
+    /// filesystem on it in tests. Like iscsi, the export is not recorded on
+    /// disk.
+    #[instrument(level = "debug", err)]
+    async fn share_nbd(&self) -> Result<Self::Output, Self::Error> {
+        self.check_share(Protocol::Nbd)?;
+        self.check_not_layered()?;
+
+        let path = self.as_bdev().share_nbd().await.map_err(|e| {
+            Error::LvolShare {
+                source: e,
+                name: self.name(),
+            }
+        })?;
+        info!("exported {} as {}", self, path);
+        Ok(path)
+    }
+
+    /// unshare the lvol, whichever protocol it is shared over
     #[instrument(level = "debug", err)]
     async fn unshare(&self) -> Result<Self::Output, Self::Error> {
+        self.unshare_protected().await?;
+        self.unshare_compressed().await?;
+        self.unshare_dedup().await?;
+        self.unshare_readonly().await?;
         let share =
             self.as_bdev()
                 .unshare()
@@ -154,12 +343,34 @@ impl Share for Lvol {
 
     /// return the protocol this bdev is shared under
     fn shared(&self) -> Option<Protocol> {
-        self.as_bdev().shared()
+        if let Some(pi) = pi_lookup(&self.pi_name()) {
+            pi.bdev.shared()
+        } else if let Some(compress) = compress_lookup(&self.compress_name()) {
+            compress.bdev.shared()
+        } else if let Some(dedup) = dedup_lookup(&self.dedup_name()) {
+            dedup.bdev.shared()
+        } else if let Some(ro) = readonly_lookup(&self.readonly_name()) {
+            ro.bdev.shared()
+        } else {
+            self.as_bdev().shared()
+        }
     }
 
-    /// returns the share URI this lvol is shared as
+    /// Returns the share URI this lvol is shared as. An lvol which is not
+    /// shared is accessed locally through the bdev on top of it if it has
+    /// one, which it must be opened with open_local() for, and never as
+    /// itself.
     fn share_uri(&self) -> Option<String> {
-        self.as_bdev().share_uri()
+        match self.shared() {
+            Some(Protocol::Nvmf) => nvmf::get_uri(&self.name()),
+            Some(Protocol::Iscsi) => {
+                iscsi::get_uri(Side::Replica, &self.name())
+            }
+            Some(Protocol::Off) | None if self.is_layered() => {
+                self.layer().and_then(|bdev| bdev.share_uri())
+            }
+            _ => self.as_bdev().share_uri(),
+        }
     }
 
     /// returns the URI that is used to construct the bdev. This is always None
@@ -170,7 +381,238 @@ impl Share for Lvol {
     }
 }
 
+/// Guard for an lvol shared over nvmf, which unshares the lvol when dropped
+/// so a share scoped to a block of code does not outlive it, whichever way
+/// the block is left. As unsharing is async, dropping the guard only starts
+/// it, use `unshare()` to wait for it and get its result.
+#[derive(Debug)]
+pub struct ShareGuard {
+    name: String,
+    uri: String,
+    released: bool,
+}
+
+impl ShareGuard {
+    /// returns the URI the lvol is shared as
+    pub fn share_uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// unshare the lvol and wait for it
+    pub async fn unshare(mut self) -> Result<String, Error> {
+        self.released = true;
+        Self::unshare_lvol(&self.name).await
+    }
+
+    /// unshare the lvol, should it still exist
+    async fn unshare_lvol(name: &str) -> Result<String, Error> {
+        match Bdev::lookup_by_name(name) {
+            Some(bdev) => Lvol::try_from(bdev)?.unshare().await,
+            None => Ok(String::new()),
+        }
+    }
+}
+
+impl Drop for ShareGuard {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        let name = self.name.clone();
+        Reactors::current().send_future(async move {
+            if let Err(error) = Self::unshare_lvol(&name).await {
+                error!("failed to unshare lvol {}: {}", name, error);
+            }
+        });
+    }
+}
+
+/// context of a snapshot being created, with the journal record to commit
+/// once it has been
+struct SnapshotCtx {
+    /// the argument of the completion callback
+    arg: *mut c_void,
+    pool: String,
+    seq: Option<u64>,
+}
+
+impl SnapshotCtx {
+    /// Called on completion of the snapshot, commits its journal record and
+    /// returns the argument of the completion callback.
+    fn complete(ctx: *mut c_void) -> *mut c_void {
+        let ctx = unsafe { Box::from_raw(ctx as *mut SnapshotCtx) };
+        if let Some(seq) = ctx.seq {
+            let pool = ctx.pool.clone();
+            Reactors::master().send_future(async move {
+                let journal = Lvs::lookup(&pool).and_then(|p| p.journal());
+                if let Some(journal) = journal {
+                    if let Err(error) = journal.commit(seq).await {
+                        error!("failed to commit snapshot: {}", error);
+                    }
+                }
+            });
+        }
+        ctx.arg
+    }
+}
+
 impl Lvol {
+    /// Share the lvol as a nvmf target for as long as the returned guard
+    /// lives.
+    pub async fn share_nvmf_guarded(&self) -> Result<ShareGuard, Error> {
+        let share = self.share_nvmf().await?;
+        Ok(ShareGuard {
+            name: self.name(),
+            uri: self.share_uri().unwrap_or(share),
+            released: false,
+        })
+    }
+
+    /// returns the NQNs of the hosts allowed to connect to the lvol shared
+    /// over nvmf, which is empty if any host is or the lvol is not shared
+    pub fn allowed_hosts(&self) -> Vec<String> {
+        NvmfSubsystem::nqn_lookup(&self.name())
+            .map(|s| s.allowed_hosts())
+            .unwrap_or_default()
+    }
+
+    /// Allow only the hosts with the given NQNs to connect to the lvol shared
+    /// over nvmf, or any host if there are none. Hosts which are connected
+    /// already stay connected. The hosts are not recorded on disk and any
+    /// host is allowed again once the lvol is shared anew.
+    #[instrument(level = "debug", err)]
+    pub async fn set_allowed_hosts(
+        &self,
+        nqns: &[String],
+    ) -> Result<(), Error> {
+        let subsystem =
+            NvmfSubsystem::nqn_lookup(&self.name()).ok_or_else(|| {
+                Error::Invalid {
+                    source: Errno::ENOENT,
+                    msg: format!("{} is not shared over nvmf", self.name()),
+                }
+            })?;
+        subsystem
+            .set_allowed_hosts(nqns)
+            .await
+            .map_err(|e| Error::LvolShare {
+                source: CoreError::ShareNvmf {
+                    source: e,
+                },
+                name: self.name(),
+            })
+    }
+
+    /// returns a boolean indicating if the lvol is shared with reservations
+    pub fn is_shared_with_reservations(&self) -> bool {
+        NvmfSubsystem::nqn_lookup(&self.name())
+            .map_or(false, |s| s.ptpl_file().is_some())
+    }
+
+    /// Share the lvol as a nvmf target with the reservations the initiators
+    /// make on it honored and persisted by the target, for initiators which
+    /// coordinate their access through reservations. Snapshots are not
+    /// shared with reservations. The share is not recorded on disk, so it
+    /// does not outlive the pool being exported, but the reservations are
+    /// restored once the lvol is shared with reservations again.
+    #[instrument(level = "debug", err)]
+    pub async fn share_nvmf_with_reservations(&self) -> Result<String, Error> {
+        self.check_share(Protocol::Nvmf)?;
+        if self.shared() == Some(Protocol::Nvmf) {
+            return if self.is_shared_with_reservations() {
+                Ok(self.share_uri().unwrap())
+            } else {
+                Err(Error::LvolShare {
+                    source: CoreError::AlreadyShared {
+                        name: self.name(),
+                        protocol: Protocol::Nvmf,
+                    },
+                    name: self.name(),
+                })
+            };
+        }
+        self.check_not_layered()?;
+        if self.is_snapshot() {
+            return Err(Error::LvolShare {
+                source: CoreError::NotSupported {
+                    source: Errno::ENOTSUP,
+                },
+                name: self.name(),
+            });
+        }
+
+        self.as_bdev().share_nvmf_with_reservations().await.map_err(|e| {
+            Error::LvolShare {
+                source: e,
+                name: self.name(),
+            }
+        })?;
+        info!("shared {} with reservations", self);
+        Ok(self.share_uri().unwrap())
+    }
+
+    /// Share the lvol as a nvmf target like `share_nvmf()`, under the given
+    /// NQN prefix rather than the default one, so the lvols of instances
+    /// sharing to the same hosts have distinct NQNs. Snapshots are only
+    /// shared under the default prefix. The share is not recorded on disk,
+    /// so it does not outlive the pool being exported.
+    #[instrument(level = "debug", err)]
+    pub async fn share_nvmf_with_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<String, Error> {
+        self.check_share(Protocol::Nvmf)?;
+        let nqn = format!("{}:{}", prefix, self.name());
+        if let Some(subsystem) = NvmfSubsystem::nqn_lookup(&self.name()) {
+            return if subsystem.get_nqn() == nqn {
+                Ok(self.share_uri().unwrap())
+            } else {
+                Err(Error::LvolShare {
+                    source: CoreError::AlreadyShared {
+                        name: self.name(),
+                        protocol: Protocol::Nvmf,
+                    },
+                    name: self.name(),
+                })
+            };
+        }
+        self.check_not_layered()?;
+        if self.is_snapshot() {
+            return Err(Error::LvolShare {
+                source: CoreError::NotSupported {
+                    source: Errno::ENOTSUP,
+                },
+                name: self.name(),
+            });
+        }
+
+        self.as_bdev().share_nvmf_with_prefix(prefix).await.map_err(|e| {
+            Error::LvolShare {
+                source: e,
+                name: self.name(),
+            }
+        })?;
+        info!("shared {} as {}", self, nqn);
+        Ok(self.share_uri().unwrap())
+    }
+
+    /// returns the reservation the initiators hold on the lvol shared with
+    /// reservations
+    pub fn reservations(&self) -> Result<Reservations, Error> {
+        let subsystem = NvmfSubsystem::nqn_lookup(&self.name())
+            .filter(|s| s.ptpl_file().is_some())
+            .ok_or_else(|| Error::Invalid {
+                source: Errno::ENOENT,
+                msg: format!("{} is not shared with reservations", self.name()),
+            })?;
+        subsystem.reservations().map_err(|e| Error::LvolShare {
+            source: CoreError::ShareNvmf {
+                source: e,
+            },
+            name: self.name(),
+        })
+    }
+
     /// generic callback for lvol operations
     pub(crate) extern "C" fn lvol_cb(
         sender_ptr: *mut c_void,
@@ -208,10 +650,64 @@ impl Lvol {
 
     /// returns the pool of the lvol
     pub fn pool(&self) -> String {
-        unsafe {
-            Lvs(NonNull::new_unchecked(self.0.as_ref().lvol_store))
-                .name()
-                .to_string()
+        self.lvs().name().to_string()
+    }
+
+    /// publish the creation of the lvol on the event bus
+    pub(crate) fn publish_created(&self) {
+        events::publish(Event::LvolCreated {
+            pool: self.pool(),
+            name: self.name(),
+            uuid: self.uuid(),
+        });
+    }
+
+    /// returns the lvol store of the lvol
+    pub(crate) fn lvs(&self) -> Lvs {
+        unsafe { Lvs(NonNull::new_unchecked(self.0.as_ref().lvol_store)) }
+    }
+
+    /// returns a boolean indicating if the lvol holds the space of its pool
+    /// reserved for integrity metadata, rather than being a replica
+    pub fn is_integrity_reserve(&self) -> bool {
+        self.name() == Lvs::integrity_reserve_name(&self.pool())
+    }
+
+    /// returns a boolean indicating if the lvol holds the metadata journal
+    /// of its pool
+    pub fn is_journal(&self) -> bool {
+        self.name() == Journal::lvol_name(&self.pool())
+    }
+
+    /// returns a boolean indicating if the lvol holds space its pool keeps
+    /// for itself, rather than being a replica
+    pub fn is_reserved(&self) -> bool {
+        self.is_integrity_reserve()
+            || self.is_journal()
+            || self.is_dedup_store()
+    }
+
+    /// returns a boolean indicating if the lvol holds the dedup store of its
+    /// pool
+    pub fn is_dedup_store(&self) -> bool {
+        self.name() == Lvs::dedup_store_name(&self.pool())
+    }
+
+    /// returns the bytes allocated to the lvol in its pool, the size of the
+    /// clusters allocated to its blob, which is less than its size for a
+    /// thin provisioned lvol which has not been written in full
+    pub fn allocated(&self) -> u64 {
+        let clusters = unsafe {
+            spdk_blob_get_num_allocated_clusters(self.0.as_ref().blob)
+        };
+        clusters * self.lvs().cluster_size()
+    }
+
+    /// returns the space the lvol is provisioned with and allocated
+    pub fn stats(&self) -> LvolStats {
+        LvolStats {
+            provisioned_bytes: self.size(),
+            allocated_bytes: self.allocated(),
         }
     }
 
@@ -230,146 +726,1219 @@ impl Lvol {
         unsafe { spdk_blob_is_snapshot(self.0.as_ref().blob) }
     }
 
-    /// destroy the lvol
-    #[instrument(level = "debug", err)]
-    pub async fn destroy(self) -> Result<String, Error> {
-        extern "C" fn destroy_cb(sender: *mut c_void, errno: i32) {
-            let sender =
-                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
-            sender.send(errno).unwrap();
-        }
+    /// returns a boolean indicating if the blocks of the lvol carry T10 DIF
+    /// protection information when it is shared
+    pub async fn is_protected(&self) -> bool {
+        matches!(
+            self.get(PropName::Protected).await,
+            Ok(PropValue::Protected(true))
+        )
+    }
 
-        let name = self.name();
+    /// returns a boolean indicating if a checksum of every block of the lvol
+    /// is stored and verified when it is shared
+    pub async fn is_checksummed(&self) -> bool {
+        matches!(
+            self.get(PropName::Checksum).await,
+            Ok(PropValue::Checksum(true))
+        )
+    }
 
-        // we must always unshare before destroying bdev
-        let _ = self.unshare().await;
+    /// the algorithm the checksums of the blocks of the lvol are computed
+    /// with, CRC32C unless another one has been chosen
+    pub async fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+        match self.get(PropName::ChecksumAlgorithm).await {
+            Ok(PropValue::ChecksumAlgorithm(algorithm)) => algorithm,
+            _ => ChecksumAlgorithm::default(),
+        }
+    }
 
-        let (s, r) = pair::<i32>();
-        unsafe {
-            vbdev_lvol_destroy(self.0.as_ptr(), Some(destroy_cb), cb_arg(s))
-        };
+    /// the format of the protection information of the lvol, if any, T10 DIF
+    /// takes precedence over checksums
+    pub async fn pi_format(&self) -> Option<PiFormat> {
+        if self.is_protected().await {
+            Some(PiFormat::Dif)
+        } else if self.is_checksummed().await {
+            Some(PiFormat::Checksum(self.checksum_algorithm().await))
+        } else {
+            None
+        }
+    }
 
-        r.await
-            .expect("lvol destroy callback is gone")
-            .to_result(|e| Error::RepDestroy {
-                source: Errno::from_i32(e),
-                name: self.name(),
-            })?;
+    /// the algorithm the lvol is compressed with, if it is compressed
+    pub fn compression(&self) -> Option<CompressionAlgorithm> {
+        match self.get_xattr(PropName::Compressed) {
+            Ok(PropValue::Compressed(true)) => {}
+            _ => return None,
+        }
+        match self.get_xattr(PropName::CompressionAlgorithm) {
+            Ok(PropValue::CompressionAlgorithm(algorithm)) => Some(algorithm),
+            _ => Some(CompressionAlgorithm::default()),
+        }
+    }
 
-        info!("Destroyed {}", name);
-        Ok(name)
+    /// how well the data written to a compressed lvol has compressed, as
+    /// long as the compress bdev on top of it is there
+    pub fn compression_stats(&self) -> Option<CompressStats> {
+        compress_lookup(&self.compress_name()).map(|c| c.stats())
     }
 
-    /// callback executed after synchronizing the lvols metadata
-    extern "C" fn blob_sync_cb(sender_ptr: *mut c_void, errno: i32) {
-        let sender =
-            unsafe { Box::from_raw(sender_ptr as *mut oneshot::Sender<i32>) };
-        sender.send(errno).expect("blob cb receiver is gone");
+    /// returns a boolean indicating if the data of the lvol is kept in the
+    /// dedup store of its pool, the lvol only holds the map of its blocks
+    pub fn is_dedup(&self) -> bool {
+        matches!(self.get_xattr(PropName::Dedup), Ok(PropValue::Dedup(true)))
     }
 
-    /// write the property prop on to the lvol which is stored on disk
-    #[allow(clippy::unit_arg)] // here to silence the Ok(()) variant
-    #[instrument(level = "debug", err)]
-    pub async fn set(&self, prop: PropValue) -> Result<(), Error> {
-        let blob = unsafe { self.0.as_ref().blob };
-        assert_ne!(blob.is_null(), true);
+    /// returns a boolean indicating if the lvol was created as a clone of a
+    /// snapshot by create_clone()
+    pub fn is_clone(&self) -> bool {
+        matches!(self.get_xattr(PropName::Clone), Ok(PropValue::Clone(true)))
+    }
 
-        if self.is_snapshot() {
-            warn!("ignoring set property on snapshot {}", self.name());
-            return Ok(());
-        }
-        if self.is_read_only() {
-            warn!("{} is read-only", self.name());
+    /// returns a boolean indicating if writes through the handles opened
+    /// with open_handle() are read back and compared
+    pub async fn is_write_verified(&self) -> bool {
+        matches!(
+            self.get(PropName::VerifyWrites).await,
+            Ok(PropValue::VerifyWrites(true))
+        )
+    }
+
+    /// Open a handle to the lvol, through the bdev it is accessed through
+    /// locally. When read-after-write verification has been enabled for the
+    /// lvol, every write through the handle is read back and compared, see
+    /// BdevHandle::enable_write_verify() for its cost.
+    pub async fn open_handle(
+        &self,
+        read_write: bool,
+    ) -> Result<BdevHandle, Error> {
+        let bdev = self.open_local().await?;
+        let handle = BdevHandle::open_with_bdev(&bdev, read_write)
+            .map_err(|e| Error::LvolOpen {
+                source: e,
+                name: self.name(),
+            })?;
+        if self.is_write_verified().await {
+            handle.enable_write_verify();
         }
-        match prop {
-            PropValue::Shared(val) => {
-                let name = PropName::from(prop).to_string().into_cstring();
-                let value = if val { "true" } else { "false" }.into_cstring();
-                unsafe {
-                    spdk_blob_set_xattr(
-                        blob,
-                        name.as_ptr(),
-                        value.as_bytes_with_nul().as_ptr() as *const _,
-                        value.as_bytes_with_nul().len() as u16,
-                    )
-                }
-                .to_result(|e| Error::SetProperty {
-                    source: Errno::from_i32(e),
-                    prop: prop.into(),
-                    name: self.name(),
-                })?;
-            }
-        };
+        Ok(handle)
+    }
 
-        let (s, r) = pair::<i32>();
-        unsafe {
-            spdk_blob_sync_md(blob, Some(Self::blob_sync_cb), cb_arg(s));
-        };
+    /// Returns a boolean indicating if the lvol is accessed through a bdev
+    /// on top of it, as what is kept in the lvol is not the data read and
+    /// written through that bdev: an lvol with protection information or
+    /// checksums, a compressed or a deduplicated lvol. Accessing such an
+    /// lvol directly would corrupt it, so it is only shared through that
+    /// bdev over nvmf by share_nvmf(), and locally through open_local().
+    pub(crate) fn is_layered(&self) -> bool {
+        matches!(
+            self.get_xattr(PropName::Protected),
+            Ok(PropValue::Protected(true))
+        ) || matches!(
+            self.get_xattr(PropName::Checksum),
+            Ok(PropValue::Checksum(true))
+        ) || self.compression().is_some()
+            || self.is_dedup()
+    }
 
-        r.await.expect("sync callback is gone").to_result(|e| {
-            Error::SyncProperty {
-                source: Errno::from_i32(e),
+    /// Fails if the lvol is accessed through a bdev on top of it, for the
+    /// shares which expose the lvol itself, see is_layered().
+    fn check_not_layered(&self) -> Result<(), Error> {
+        if self.is_layered() {
+            return Err(Error::LvolShare {
+                source: CoreError::NotSupported {
+                    source: Errno::ENOTSUP,
+                },
                 name: self.name(),
-            }
-        })?;
-
+            });
+        }
         Ok(())
     }
 
-    /// get/read a property from this lvol from disk
-    #[instrument(level = "debug", err)]
-    pub async fn get(&self, prop: PropName) -> Result<PropValue, Error> {
-        let blob = unsafe { self.0.as_ref().blob };
-        assert_ne!(blob.is_null(), true);
+    /// the bdev on top of the lvol it is accessed through, if it is there
+    fn layer(&self) -> Option<Bdev> {
+        if let Some(pi) = pi_lookup(&self.pi_name()) {
+            Some(pi.bdev.clone())
+        } else if let Some(compress) = compress_lookup(&self.compress_name()) {
+            Some(compress.bdev.clone())
+        } else {
+            dedup_lookup(&self.dedup_name()).map(|dedup| dedup.bdev.clone())
+        }
+    }
 
-        match prop {
-            PropName::Shared => {
-                let name = prop.to_string().into_cstring();
-                let mut value: *const libc::c_char =
-                    std::ptr::null::<libc::c_char>();
-                let mut value_len: u64 = 0;
-                unsafe {
-                    spdk_blob_get_xattr_value(
-                        blob,
-                        name.as_ptr(),
-                        &mut value as *mut *const c_char as *mut *const c_void,
-                        &mut value_len,
-                    )
-                }
-                .to_result(|e| Error::GetProperty {
-                    source: Errno::from_i32(e),
-                    prop,
+    /// The bdev the lvol is accessed through locally: the PI bdev on top of
+    /// an lvol with protection information, the compress bdev on top of a
+    /// compressed lvol or the dedup bdev on top of a deduplicated lvol,
+    /// which is created if it is not there yet, and the lvol itself
+    /// otherwise. The bdev on top of it is destroyed when the lvol is
+    /// unshared.
+    pub async fn open_local(&self) -> Result<Bdev, Error> {
+        match (
+            self.pi_format().await,
+            self.compression(),
+            self.is_dedup(),
+        ) {
+            (Some(format), ..) => self.open_protected(format).await,
+            (None, Some(algorithm), _) => {
+                self.open_compressed(algorithm).await
+            }
+            (None, None, true) => self.open_dedup().await,
+            (None, None, false) => Ok(self.as_bdev()),
+        }
+    }
+
+    /// name of the PI bdev on top of the lvol when it is protected
+    fn pi_name(&self) -> String {
+        format!("{}-pi", self.name())
+    }
+
+    /// The PI bdev on top of the lvol, which is created if it is not there
+    /// yet. The protection information of the blocks is kept in the lvol, so
+    /// it is preserved when the pool is exported and imported.
+    async fn open_protected(&self, format: PiFormat) -> Result<Bdev, Error> {
+        let name = self.pi_name();
+        if pi_lookup(&name).is_none() {
+            PiBdev::create(&name, &self.name(), format).await.map_err(|e| {
+                Error::Protect {
+                    source: e,
                     name: self.name(),
-                })?;
-                match unsafe { CStr::from_ptr(value).to_str() } {
-                    Ok("true") => Ok(PropValue::Shared(true)),
-                    Ok("false") => Ok(PropValue::Shared(false)),
-                    _ => Err(Error::Property {
-                        source: Errno::EINVAL,
-                        name: self.name(),
-                    }),
                 }
-            }
+            })?;
         }
+        Ok(pi_lookup(&name).unwrap().bdev.clone())
     }
 
-    /// Format snapshot name
-    /// base_name is the nexus or replica UUID
-    pub fn format_snapshot_name(base_name: &str, snapshot_time: u64) -> String {
-        format!("{}-snap-{}", base_name, snapshot_time)
+    /// share the lvol through a PI bdev on top of it, under the NQN of the
+    /// lvol itself
+    async fn share_protected(&self, format: PiFormat) -> Result<String, Error> {
+        let bdev = self.open_protected(format).await?;
+        self.share_through(&bdev).await
     }
 
-    /// Create a snapshot
-    pub async fn create_snapshot(
-        &self,
-        nvmf_req: &NvmfReq,
-        snapshot_name: &str,
-    ) {
-        extern "C" fn snapshot_done_cb(
-            nvmf_req_ptr: *mut c_void,
-            _lvol_ptr: *mut spdk_lvol,
-            errno: i32,
-        ) {
+    /// fails if the lvol is shared over another protocol than the given one,
+    /// as an lvol is shared over one protocol at a time
+    fn check_share(&self, protocol: Protocol) -> Result<(), Error> {
+        match self.shared() {
+            Some(Protocol::Off) | None => Ok(()),
+            Some(shared) if shared == protocol => Ok(()),
+            Some(shared) => Err(Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    name: self.name(),
+                    protocol: shared,
+                },
+                name: self.name(),
+            }),
+        }
+    }
+
+    /// share the bdev on top of the lvol under the NQN of the lvol itself
+    async fn share_through(&self, bdev: &Bdev) -> Result<String, Error> {
+        let subsystem = NvmfSubsystem::new_with_uuid(&self.name(), bdev)
+            .map_err(|e| Error::LvolShare {
+                source: CoreError::ShareNvmf {
+                    source: e,
+                },
+                name: self.name(),
+            })?;
+        subsystem.start().await.map_err(|e| Error::LvolShare {
+            source: CoreError::ShareNvmf {
+                source: e,
+            },
+            name: self.name(),
+        })
+    }
+
+    /// Unshare a protected lvol and destroy the PI bdev on top of it, without
+    /// changing the shared property. Does nothing if there is no PI bdev.
+    pub(crate) async fn unshare_protected(&self) -> Result<(), Error> {
+        let name = self.pi_name();
+        if pi_lookup(&name).is_none() {
+            return Ok(());
+        }
+
+        self.unshare_through().await?;
+        PiBdev::destroy(&name).await.map_err(|e| Error::Protect {
+            source: e,
+            name: self.name(),
+        })
+    }
+
+    /// stop and destroy the subsystem sharing the bdev on top of the lvol
+    async fn unshare_through(&self) -> Result<(), Error> {
+        if let Some(subsystem) = NvmfSubsystem::nqn_lookup(&self.name()) {
+            subsystem.stop().await.map_err(|e| Error::LvolUnShare {
+                source: CoreError::UnshareNvmf {
+                    source: e,
+                },
+                name: self.name(),
+            })?;
+            subsystem.destroy();
+        }
+        Ok(())
+    }
+
+    /// name of the compress bdev on top of the lvol when it is compressed
+    fn compress_name(&self) -> String {
+        format!("{}-compress", self.name())
+    }
+
+    /// the compress bdev on top of the lvol, which is created if it is not
+    /// there yet
+    async fn open_compressed(
+        &self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Bdev, Error> {
+        let name = self.compress_name();
+        if compress_lookup(&name).is_none() {
+            CompressBdev::create(&name, &self.name(), algorithm)
+                .await
+                .map_err(|e| Error::Compress {
+                    source: e,
+                    name: self.name(),
+                })?;
+        }
+        Ok(compress_lookup(&name).unwrap().bdev.clone())
+    }
+
+    /// Share the lvol through a compress bdev on top of it, under the NQN of
+    /// the lvol itself.
+    async fn share_compressed(
+        &self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<String, Error> {
+        let bdev = self.open_compressed(algorithm).await?;
+        self.share_through(&bdev).await
+    }
+
+    /// Unshare a compressed lvol and destroy the compress bdev on top of it,
+    /// without changing the shared property. Does nothing if there is no
+    /// compress bdev.
+    pub(crate) async fn unshare_compressed(&self) -> Result<(), Error> {
+        let name = self.compress_name();
+        if compress_lookup(&name).is_none() {
+            return Ok(());
+        }
+
+        self.unshare_through().await?;
+        CompressBdev::destroy(&name).await.map_err(|e| Error::Compress {
+            source: e,
+            name: self.name(),
+        })
+    }
+
+    /// name of the dedup bdev on top of the lvol when it is deduplicated
+    fn dedup_name(&self) -> String {
+        format!("{}-dedup", self.name())
+    }
+
+    /// the dedup bdev on top of the lvol, which is created if it is not
+    /// there yet
+    async fn open_dedup(&self) -> Result<Bdev, Error> {
+        let name = self.dedup_name();
+        if dedup_lookup(&name).is_none() {
+            let store = self.lvs().dedup_store().ok_or_else(|| {
+                Error::Invalid {
+                    source: Errno::ENODEV,
+                    msg: format!(
+                        "the dedup store of pool {} is not open",
+                        self.pool()
+                    ),
+                }
+            })?;
+            DedupBdev::create(&name, &self.name(), store)
+                .await
+                .map_err(|e| Error::Dedup {
+                    source: e,
+                    name: self.name(),
+                })?;
+        }
+        Ok(dedup_lookup(&name).unwrap().bdev.clone())
+    }
+
+    /// Share the lvol through a dedup bdev on top of it, under the NQN of
+    /// the lvol itself.
+    async fn share_dedup(&self) -> Result<String, Error> {
+        let bdev = self.open_dedup().await?;
+        self.share_through(&bdev).await
+    }
+
+    /// Unshare a deduplicated lvol and destroy the dedup bdev on top of it,
+    /// without changing the shared property. Does nothing if there is no
+    /// dedup bdev.
+    pub(crate) async fn unshare_dedup(&self) -> Result<(), Error> {
+        let name = self.dedup_name();
+        if dedup_lookup(&name).is_none() {
+            return Ok(());
+        }
+
+        self.unshare_through().await?;
+        DedupBdev::destroy(&name).await.map_err(|e| Error::Dedup {
+            source: e,
+            name: self.name(),
+        })
+    }
+
+    /// name of the read-only bdev on top of the lvol when it is shared
+    /// read-only
+    fn readonly_name(&self) -> String {
+        format!("{}-ro", self.name())
+    }
+
+    /// returns a boolean indicating if the lvol is shared read-only
+    pub fn is_shared_read_only(&self) -> bool {
+        readonly_lookup(&self.readonly_name()).is_some()
+    }
+
+    /// Share the lvol read-only as a nvmf target, through a read-only bdev on
+    /// top of it under the NQN of the lvol itself, so the writes of the
+    /// initiators are failed at the target. The share is not recorded on
+    /// disk, so it does not outlive the pool being exported.
+    #[instrument(level = "debug", err)]
+    pub async fn share_nvmf_ro(&self) -> Result<String, Error> {
+        self.check_share(Protocol::Nvmf)?;
+        if self.shared() == Some(Protocol::Nvmf) {
+            return if self.is_shared_read_only() {
+                Ok(self.share_uri().unwrap())
+            } else {
+                Err(Error::LvolShare {
+                    source: CoreError::AlreadyShared {
+                        name: self.name(),
+                        protocol: Protocol::Nvmf,
+                    },
+                    name: self.name(),
+                })
+            };
+        }
+        self.check_not_layered()?;
+
+        let name = self.readonly_name();
+        if readonly_lookup(&name).is_none() {
+            ReadOnlyBdev::create(&name, &self.name()).await.map_err(|e| {
+                Error::ReadOnly {
+                    source: e,
+                    name: self.name(),
+                }
+            })?;
+        }
+
+        let bdev = readonly_lookup(&name).unwrap().bdev.clone();
+        if let Err(e) = self.share_through(&bdev).await {
+            let _ = ReadOnlyBdev::destroy(&name).await;
+            return Err(e);
+        }
+        info!("shared {} read-only", self);
+        Ok(self.share_uri().unwrap())
+    }
+
+    /// Unshare an lvol shared read-only and destroy the read-only bdev on top
+    /// of it. Does nothing if there is no read-only bdev.
+    pub(crate) async fn unshare_readonly(&self) -> Result<(), Error> {
+        let name = self.readonly_name();
+        if readonly_lookup(&name).is_none() {
+            return Ok(());
+        }
+
+        self.unshare_through().await?;
+        ReadOnlyBdev::destroy(&name).await.map_err(|e| Error::ReadOnly {
+            source: e,
+            name: self.name(),
+        })
+    }
+
+    /// Drop the references the map of a deduplicated lvol holds to the
+    /// blocks of the dedup store, once the lvol is gone. The blocks leak
+    /// until the pool is imported again should this fail.
+    async fn release_dedup(lvs: &Lvs, name: &str, blocks: Vec<u32>) {
+        let store = match lvs.dedup_store() {
+            Some(store) => store,
+            None => return,
+        };
+        let handle = match store.handle() {
+            Ok(handle) => handle,
+            Err(error) => {
+                error!("{}: failed to release its blocks: {}", name, error);
+                return;
+            }
+        };
+        for block in blocks {
+            if let Err(error) = store.release(&handle, block).await {
+                error!("{}: failed to release its blocks: {}", name, error);
+                return;
+            }
+        }
+    }
+
+    /// destroy the lvol
+    #[instrument(level = "debug", err)]
+    pub async fn destroy(self) -> Result<String, Error> {
+        extern "C" fn destroy_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).unwrap();
+        }
+
+        // snapshots are kept as long as clones read their unmodified blocks
+        // from them
+        let clones = if self.is_snapshot() {
+            self.clones()
+        } else {
+            Vec::new()
+        };
+        if !clones.is_empty() {
+            return Err(Error::SnapshotInUse {
+                source: Errno::EBUSY,
+                name: self.name(),
+                clones: clones
+                    .iter()
+                    .map(|c| c.name())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            });
+        }
+
+        let name = self.name();
+        let uuid = self.uuid();
+        let lvs = self.lvs();
+        let metadata = self.integrity_metadata();
+
+        // we must always unshare before destroying bdev
+        let _ = self.unshare().await;
+
+        // the blocks of the dedup store the lvol maps to
+        let dedup = if self.is_dedup() {
+            Some(DedupBdev::references(&name).await.map_err(|e| {
+                Error::Dedup {
+                    source: e,
+                    name: name.clone(),
+                }
+            })?)
+        } else {
+            None
+        };
+
+        let journal = lvs.journal().filter(|_| !self.is_journal());
+        let seq = match &journal {
+            Some(journal) => Some(
+                journal
+                    .begin(JournalOp::DestroyLvol {
+                        name: name.clone(),
+                    })
+                    .await?,
+            ),
+            None => None,
+        };
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            vbdev_lvol_destroy(self.0.as_ptr(), Some(destroy_cb), cb_arg(s))
+        };
+
+        let destroyed = r
+            .await
+            .expect("lvol destroy callback is gone")
+            .to_result(|e| Error::RepDestroy {
+                source: Errno::from_i32(e),
+                name: name.clone(),
+            });
+
+        // the lvol is left as it was when it failed to be destroyed
+        if let (Some(journal), Some(seq)) = (journal, seq) {
+            journal.commit(seq).await?;
+        }
+        destroyed?;
+
+        if let Some(blocks) = dedup {
+            Self::release_dedup(&lvs, &name, blocks).await;
+        }
+
+        // the space of the integrity metadata goes back to the reserve
+        if metadata > 0 {
+            if let Some(reserve) = lvs.integrity_reserve_lvol() {
+                if let Err(error) =
+                    reserve.resize_to(reserve.size() + metadata).await
+                {
+                    error!(
+                        "failed to give back the integrity metadata of {}: {}",
+                        name, error
+                    );
+                }
+            }
+        }
+
+        info!("Destroyed {}", name);
+        events::publish(Event::LvolDestroyed {
+            pool: lvs.name().to_string(),
+            name: name.clone(),
+            uuid,
+        });
+        Ok(name)
+    }
+
+    /// Resize the lvol to the given size in bytes and return the size it
+    /// has been resized to. An lvol is made up of whole clusters of its pool,
+    /// so the size is rounded up to a multiple of Lvs::cluster_size(), for
+    /// example to 8MiB when 5MiB are asked for on a pool with clusters of
+    /// 4MiB. Shrinking drops the data beyond the new size and is refused
+    /// unless shrink is set. Initiators of an lvol shared over nvmf see the
+    /// new size of its namespace.
+    #[instrument(level = "debug", err)]
+    pub async fn resize(&self, size: u64, shrink: bool) -> Result<u64, Error> {
+        let lvs = self.lvs();
+        let cluster_size = lvs.cluster_size();
+        let size = (size + cluster_size - 1) / cluster_size * cluster_size;
+
+        if size == 0 || (size < self.size() && !shrink) {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "cannot shrink lvol {} from {} to {} bytes",
+                    self.name(),
+                    self.size(),
+                    size
+                ),
+            });
+        }
+        if size == self.size() {
+            return Ok(size);
+        }
+
+        // the protection information, the chunk table or the block map
+        // kept in the lvol is laid out for the size it was created with
+        if self.is_layered() {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "lvol {} is accessed through a bdev on top of it, which \
+                     can not be resized",
+                    self.name()
+                ),
+            });
+        }
+
+        // the bdev on top of the lvol it is shared through keeps its size
+        if self.shared() != self.as_bdev().shared() {
+            return Err(Error::Invalid {
+                source: Errno::EBUSY,
+                msg: format!(
+                    "lvol {} is shared through a bdev on top of it, unshare \
+                     it before resizing it",
+                    self.name()
+                ),
+            });
+        }
+
+        if !self.is_thin() && size > self.size() + lvs.available() {
+            return Err(Error::RepNoSpace {
+                source: Errno::ENOSPC,
+                name: self.name(),
+                pool: self.pool(),
+                size,
+                available: lvs.available(),
+            });
+        }
+
+        self.resize_to(size).await?;
+        Ok(self.size())
+    }
+
+    /// resize the lvol to the given size in bytes, which may also shrink it
+    pub(crate) async fn resize_to(&self, size: u64) -> Result<(), Error> {
+        extern "C" fn resize_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).unwrap();
+        }
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            vbdev_lvol_resize(self.0.as_ptr(), size, Some(resize_cb), cb_arg(s))
+        };
+
+        r.await
+            .expect("lvol resize callback is gone")
+            .to_result(|e| Error::RepResize {
+                source: Errno::from_i32(e),
+                name: self.name(),
+            })?;
+
+        info!("Resized {} to {} bytes", self, self.size());
+        Ok(())
+    }
+
+    /// Allocate the clusters of a thin provisioned lvol which are not
+    /// allocated yet, so that writing to it can no longer run its pool out of
+    /// space. A clone gets a copy of the clusters it shares with its snapshot
+    /// and no longer depends on it. The lvol is thick provisioned afterwards,
+    /// an lvol which already is is left as it is and snapshots are refused.
+    /// When the pool runs out of space the clusters allocated so far remain
+    /// allocated and the lvol remains thin provisioned.
+    #[instrument(level = "debug", err)]
+    pub async fn inflate(&self) -> Result<(), Error> {
+        extern "C" fn inflate_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).unwrap();
+        }
+
+        if self.is_read_only() {
+            return Err(Error::Invalid {
+                source: Errno::EPERM,
+                msg: format!("cannot inflate read-only lvol {}", self.name()),
+            });
+        }
+        if !self.is_thin() {
+            return Ok(());
+        }
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            spdk_lvol_inflate(self.0.as_ptr(), Some(inflate_cb), cb_arg(s))
+        };
+
+        let errno = r.await.expect("lvol inflate callback is gone");
+        if errno.abs() == libc::ENOSPC {
+            return Err(Error::RepInflateNoSpace {
+                source: Errno::ENOSPC,
+                name: self.name(),
+                pool: self.pool(),
+                available: self.lvs().available(),
+            });
+        }
+        errno.to_result(|e| Error::RepInflate {
+            source: Errno::from_i32(e),
+            name: self.name(),
+        })?;
+
+        // the blob is no longer thin provisioned, which the lvol is told as
+        // it only looks at it when it is opened
+        unsafe { (*self.0.as_ptr()).thin_provision = false };
+        info!("Inflated {}", self);
+        Ok(())
+    }
+
+    /// callback executed after synchronizing the lvols metadata
+    extern "C" fn blob_sync_cb(sender_ptr: *mut c_void, errno: i32) {
+        let sender =
+            unsafe { Box::from_raw(sender_ptr as *mut oneshot::Sender<i32>) };
+        sender.send(errno).expect("blob cb receiver is gone");
+    }
+
+    /// write the property prop on to the lvol which is stored on disk.
+    /// Enabling checksums or protection information takes the space for the
+    /// integrity metadata of the lvol from the space reserved for it on the
+    /// pool, disabling both gives it back.
+    #[instrument(level = "debug", err)]
+    pub async fn set(&self, prop: PropValue) -> Result<(), Error> {
+        let (val, other) = match prop {
+            _ if self.is_snapshot() => return self.set_xattr(prop).await,
+            PropValue::Compressed(_) | PropValue::CompressionAlgorithm(_) => {
+                return Err(Error::Invalid {
+                    source: Errno::EINVAL,
+                    msg: format!(
+                        "compression of {} can only be chosen when it is \
+                         created",
+                        self.name()
+                    ),
+                })
+            }
+            PropValue::Dedup(_) => {
+                return Err(Error::Invalid {
+                    source: Errno::EINVAL,
+                    msg: format!(
+                        "deduplication of {} is chosen by its pool",
+                        self.name()
+                    ),
+                })
+            }
+            PropValue::Clone(_) => {
+                return Err(Error::Invalid {
+                    source: Errno::EINVAL,
+                    msg: format!(
+                        "{} can only be made a clone when it is created",
+                        self.name()
+                    ),
+                })
+            }
+            PropValue::Protected(true) | PropValue::Checksum(true)
+                if self.is_dedup() =>
+            {
+                return Err(Error::Invalid {
+                    source: Errno::EINVAL,
+                    msg: format!(
+                        "{} is deduplicated, checksums and protection \
+                         information can not be enabled on it",
+                        self.name()
+                    ),
+                })
+            }
+            PropValue::Protected(true) | PropValue::Checksum(true)
+                if self.compression().is_some() =>
+            {
+                return Err(Error::Invalid {
+                    source: Errno::EINVAL,
+                    msg: format!(
+                        "{} is compressed, checksums and protection \
+                         information can not be enabled on it",
+                        self.name()
+                    ),
+                })
+            }
+            PropValue::Protected(val) => (val, self.is_checksummed().await),
+            PropValue::Checksum(val) => (val, self.is_protected().await),
+            _ => return self.set_xattr(prop).await,
+        };
+        let claimed = self.integrity_metadata();
+        if (val || other) && claimed == 0 {
+            self.claim_integrity_metadata().await?;
+        } else if !val && !other && claimed > 0 {
+            self.release_integrity_metadata(claimed).await?;
+        }
+        self.set_xattr(prop).await
+    }
+
+    /// the space of the lvol taken from the space of its pool reserved for
+    /// integrity metadata in bytes
+    pub fn integrity_metadata(&self) -> u64 {
+        match self.get_xattr(PropName::IntegrityMetadata) {
+            Ok(PropValue::IntegrityMetadata(len)) => len,
+            _ => 0,
+        }
+    }
+
+    /// the space needed for the integrity metadata of the lvol, enough for
+    /// any format of protection information so that the checksum algorithm
+    /// can be changed later on
+    fn integrity_metadata_len(&self) -> u64 {
+        let block_len = u64::from(self.as_bdev().block_len());
+        let per_block = block_len / u64::from(PiFormat::max_tuple_len());
+        let blocks = (self.size() / block_len + per_block - 1) / per_block;
+        let cluster = self.lvs().cluster_size();
+        (blocks * block_len + cluster - 1) / cluster * cluster
+    }
+
+    /// Grow the lvol by the space for its integrity metadata, which the
+    /// integrity reserve of the pool shrinks by.
+    async fn claim_integrity_metadata(&self) -> Result<(), Error> {
+        let lvs = self.lvs();
+        let reserve = lvs.integrity_reserve_lvol().ok_or_else(|| {
+            Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "pool {} has no space reserved for integrity metadata, \
+                     checksums and protection information can not be \
+                     enabled on {}",
+                    lvs.name(),
+                    self.name()
+                ),
+            }
+        })?;
+        let len = self.integrity_metadata_len();
+        if len > reserve.size() {
+            return Err(Error::Invalid {
+                source: Errno::ENOSPC,
+                msg: format!(
+                    "{} bytes of integrity metadata needed for {}, but only \
+                     {} bytes of the space reserved for it on pool {} left",
+                    len,
+                    self.name(),
+                    reserve.size(),
+                    lvs.name()
+                ),
+            });
+        }
+
+        reserve.resize_to(reserve.size() - len).await?;
+        if let Err(error) = self.resize_to(self.size() + len).await {
+            let _ = reserve.resize_to(reserve.size() + len).await;
+            return Err(error);
+        }
+        self.set_xattr(PropValue::IntegrityMetadata(len)).await?;
+        info!("{}: took {} bytes for integrity metadata", self, len);
+        Ok(())
+    }
+
+    /// Shrink the lvol by the space for its integrity metadata, and give it
+    /// back to the integrity reserve of the pool.
+    async fn release_integrity_metadata(&self, len: u64) -> Result<(), Error> {
+        self.resize_to(self.size() - len).await?;
+        self.set_xattr(PropValue::IntegrityMetadata(0)).await?;
+        if let Some(reserve) = self.lvs().integrity_reserve_lvol() {
+            reserve.resize_to(reserve.size() + len).await?;
+        }
+        info!("{}: gave back {} bytes of integrity metadata", self, len);
+        Ok(())
+    }
+
+    /// write the property prop on to the lvol which is stored on disk
+    #[allow(clippy::unit_arg)] // here to silence the Ok(()) variant
+    pub(crate) async fn set_xattr(&self, prop: PropValue) -> Result<(), Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert_ne!(blob.is_null(), true);
+
+        if self.is_snapshot() {
+            warn!("ignoring set property on snapshot {}", self.name());
+            return Ok(());
+        }
+        if self.is_read_only() {
+            warn!("{} is read-only", self.name());
+        }
+        let value = match prop {
+            PropValue::Shared(val)
+            | PropValue::Protected(val)
+            | PropValue::Checksum(val)
+            | PropValue::VerifyWrites(val)
+            | PropValue::Compressed(val)
+            | PropValue::Dedup(val)
+            | PropValue::Clone(val) => {
+                if val { "true" } else { "false" }.to_string()
+            }
+            PropValue::ChecksumAlgorithm(algorithm) => algorithm.to_string(),
+            PropValue::CompressionAlgorithm(algorithm) => algorithm.to_string(),
+            PropValue::IntegrityReserve(percent) => percent.to_string(),
+            PropValue::IntegrityMetadata(len) => len.to_string(),
+        };
+        let name = PropName::from(prop).to_string().into_cstring();
+        let value = value.into_cstring();
+        unsafe {
+            spdk_blob_set_xattr(
+                blob,
+                name.as_ptr(),
+                value.as_bytes_with_nul().as_ptr() as *const _,
+                value.as_bytes_with_nul().len() as u16,
+            )
+        }
+        .to_result(|e| Error::SetProperty {
+            source: Errno::from_i32(e),
+            prop: prop.into(),
+            name: self.name(),
+        })?;
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            spdk_blob_sync_md(blob, Some(Self::blob_sync_cb), cb_arg(s));
+        };
+
+        r.await.expect("sync callback is gone").to_result(|e| {
+            Error::SyncProperty {
+                source: Errno::from_i32(e),
+                name: self.name(),
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// get/read a property from this lvol from disk
+    #[instrument(level = "debug", err)]
+    pub async fn get(&self, prop: PropName) -> Result<PropValue, Error> {
+        self.get_xattr(prop)
+    }
+
+    /// read a property of the lvol, the properties are kept in memory so
+    /// this does not wait for the disk
+    pub(crate) fn get_xattr(&self, prop: PropName) -> Result<PropValue, Error> {
+        let blob = unsafe { self.0.as_ref().blob };
+        assert_ne!(blob.is_null(), true);
+
+        let name = prop.to_string().into_cstring();
+        let mut value: *const libc::c_char = std::ptr::null::<libc::c_char>();
+        let mut value_len: u64 = 0;
+        unsafe {
+            spdk_blob_get_xattr_value(
+                blob,
+                name.as_ptr(),
+                &mut value as *mut *const c_char as *mut *const c_void,
+                &mut value_len,
+            )
+        }
+        .to_result(|e| Error::GetProperty {
+            source: Errno::from_i32(e),
+            prop,
+            name: self.name(),
+        })?;
+
+        let value = unsafe { CStr::from_ptr(value).to_str() };
+        let flag = match value {
+            Ok("true") => Some(true),
+            Ok("false") => Some(false),
+            _ => None,
+        };
+        let value = match prop {
+            PropName::Shared => flag.map(PropValue::Shared),
+            PropName::Protected => flag.map(PropValue::Protected),
+            PropName::Checksum => flag.map(PropValue::Checksum),
+            PropName::VerifyWrites => flag.map(PropValue::VerifyWrites),
+            PropName::Compressed => flag.map(PropValue::Compressed),
+            PropName::Dedup => flag.map(PropValue::Dedup),
+            PropName::Clone => flag.map(PropValue::Clone),
+            PropName::ChecksumAlgorithm => value
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(PropValue::ChecksumAlgorithm),
+            PropName::IntegrityReserve => value
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(PropValue::IntegrityReserve),
+            PropName::IntegrityMetadata => value
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(PropValue::IntegrityMetadata),
+            PropName::CompressionAlgorithm => value
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(PropValue::CompressionAlgorithm),
+        };
+        value.ok_or_else(|| Error::Property {
+            source: Errno::EINVAL,
+            name: self.name(),
+        })
+    }
+
+    /// Format snapshot name
+    /// base_name is the nexus or replica UUID
+    pub fn format_snapshot_name(base_name: &str, snapshot_time: u64) -> String {
+        format!("{}-snap-{}", base_name, snapshot_time)
+    }
+
+    /// Parse a snapshot name into the name of the replica the snapshot was
+    /// taken of and the time the snapshot was taken at
+    pub fn parse_snapshot_name(name: &str) -> Option<(&str, u64)> {
+        let pos = name.rfind("-snap-")?;
+        let time = name[pos + "-snap-".len() ..].parse::<u64>().ok()?;
+        Some((&name[.. pos], time))
+    }
+
+    /// returns the lvols which have been cloned from this snapshot. The lvol
+    /// the snapshot was taken of, and later snapshots of it, are not clones.
+    /// Clones are marked as such by create_clone(), for the snapshots named
+    /// by format_snapshot_name() clones are also told apart by their names.
+    pub fn clones(&self) -> Vec<Lvol> {
+        let name = self.name();
+        let source = Lvol::parse_snapshot_name(&name).map(|(source, _)| source);
+        let (bs, blob_id) = unsafe {
+            let lvol = self.0.as_ref();
+            ((*lvol.lvol_store).blobstore, lvol.blob_id)
+        };
+        let lvs = self.lvs();
+
+        match lvs.lvols() {
+            Some(lvols) => lvols
+                .filter(|l| {
+                    let parent = unsafe {
+                        spdk_blob_get_parent_snapshot(bs, l.0.as_ref().blob_id)
+                    };
+                    parent == blob_id
+                })
+                .filter(|l| {
+                    if l.is_clone() {
+                        return true;
+                    }
+                    let name = l.name();
+                    let base = Lvol::parse_snapshot_name(&name)
+                        .map(|(base, _)| base)
+                        .unwrap_or(&name);
+                    source.map_or(false, |source| base != source)
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// create a writable clone of this snapshot named name
+    #[instrument(level = "debug", err)]
+    pub async fn create_clone(&self, name: &str) -> Result<Lvol, Error> {
+        if !self.is_snapshot() {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("{} is not a snapshot", self.name()),
+            });
+        }
+
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(Error::RepExists {
+                source: Errno::EEXIST,
+                name: name.to_string(),
+            });
+        }
+
+        let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
+
+        let cname = name.into_cstring();
+        unsafe {
+            vbdev_lvol_create_clone(
+                self.0.as_ptr(),
+                cname.as_ptr(),
+                Some(Lvol::lvol_cb),
+                cb_arg(s),
+            )
+        };
+
+        let lvol = r
+            .await
+            .expect("lvol clone callback dropped")
+            .map_err(|e| Error::RepClone {
+                source: e,
+                snapshot: self.name(),
+                name: name.to_string(),
+            })
+            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()))?;
+
+        if let Err(error) = lvol.set_xattr(PropValue::Clone(true)).await {
+            let _ = lvol.destroy().await;
+            return Err(error);
+        }
+
+        info!("cloned {} from {}", lvol, self);
+        lvol.publish_created();
+        Ok(lvol)
+    }
+
+    /// destroy the snapshot, which is refused as long as there are clones
+    /// reading their unmodified blocks from it
+    #[instrument(level = "debug", err)]
+    pub async fn destroy_snapshot(self) -> Result<String, Error> {
+        self.destroy().await
+    }
+
+    /// Create a read-only snapshot of the lvol named name in the same pool,
+    /// the lvol itself continues to be writable. IO to the lvol, which may be
+    /// shared, is paused while the snapshot is taken.
+    #[instrument(level = "debug", err)]
+    pub async fn create_snapshot(&self, name: &str) -> Result<Lvol, Error> {
+        extern "C" fn snapshot_cb(
+            ctx: *mut c_void,
+            lvol_ptr: *mut spdk_lvol,
+            errno: i32,
+        ) {
+            Lvol::lvol_cb(SnapshotCtx::complete(ctx), lvol_ptr, errno);
+        }
+
+        if self.is_snapshot() {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("{} is a snapshot", self.name()),
+            });
+        }
+
+        if Bdev::lookup_by_name(name).is_some() {
+            return Err(Error::RepExists {
+                source: Errno::EEXIST,
+                name: name.to_string(),
+            });
+        }
+
+        let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
+
+        let (ctx, recorded) = self.begin_snapshot(name, cb_arg(s)).await;
+        if recorded {
+            let cname = name.into_cstring();
+            unsafe {
+                vbdev_lvol_create_snapshot(
+                    self.0.as_ptr(),
+                    cname.as_ptr(),
+                    Some(snapshot_cb),
+                    ctx,
+                )
+            };
+        } else {
+            snapshot_cb(ctx, std::ptr::null_mut(), Errno::EIO as i32);
+        }
+
+        let snapshot = r
+            .await
+            .expect("lvol snapshot callback dropped")
+            .map_err(|e| Error::RepSnapshot {
+                source: e,
+                name: self.name(),
+                snapshot: name.to_string(),
+            })
+            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()))?;
+
+        info!("created snapshot {} of {}", snapshot, self);
+        snapshot.publish_created();
+        Ok(snapshot)
+    }
+
+    /// returns the snapshots the data of the lvol builds on, the most recent
+    /// one first. For a clone these continue with the snapshot it was cloned
+    /// from and the snapshots that one builds on.
+    pub fn snapshots(&self) -> Vec<Lvol> {
+        let bs = unsafe { (*self.0.as_ref().lvol_store).blobstore };
+        let lvs = self.lvs();
+
+        let mut snapshots = Vec::new();
+        let mut blob_id = unsafe { self.0.as_ref().blob_id };
+        loop {
+            let parent = unsafe { spdk_blob_get_parent_snapshot(bs, blob_id) };
+            let snapshot = lvs.lvols().and_then(|mut lvols| {
+                lvols.find(|l| unsafe { l.0.as_ref().blob_id } == parent)
+            });
+            match snapshot {
+                Some(snapshot) => {
+                    snapshots.push(snapshot);
+                    blob_id = parent;
+                }
+                None => return snapshots,
+            }
+        }
+    }
+
+    /// Record the snapshot about to be created in the journal of the pool,
+    /// returns the context to create it with and whether it is to be
+    /// created. It is not if it could not be recorded, or if the lvol is
+    /// deduplicated as the map of a snapshot would share the blocks of the
+    /// dedup store without holding references to them.
+    async fn begin_snapshot(
+        &self,
+        snapshot_name: &str,
+        arg: *mut c_void,
+    ) -> (*mut c_void, bool) {
+        let mut ctx = SnapshotCtx {
+            arg,
+            pool: self.pool(),
+            seq: None,
+        };
+        if self.is_dedup() {
+            error!(
+                "{} is deduplicated, refusing snapshot {}",
+                self.name(),
+                snapshot_name
+            );
+            return (Box::into_raw(Box::new(ctx)).cast(), false);
+        }
+        let mut recorded = true;
+        if let Some(journal) = self.lvs().journal() {
+            let op = JournalOp::CreateSnapshot {
+                lvol: self.name(),
+                snapshot: snapshot_name.to_string(),
+            };
+            match journal.begin(op).await {
+                Ok(seq) => ctx.seq = Some(seq),
+                Err(error) => {
+                    error!(
+                        "failed to journal snapshot {}: {}",
+                        snapshot_name, error
+                    );
+                    recorded = false;
+                }
+            }
+        }
+        (Box::into_raw(Box::new(ctx)).cast(), recorded)
+    }
+
+    /// Create a snapshot on behalf of a nvmf admin command, which is
+    /// completed once the snapshot has been created
+    pub async fn create_snapshot_nvmf(
+        &self,
+        nvmf_req: &NvmfReq,
+        snapshot_name: &str,
+    ) {
+        extern "C" fn snapshot_done_cb(
+            ctx: *mut c_void,
+            _lvol_ptr: *mut spdk_lvol,
+            errno: i32,
+        ) {
+            let nvmf_req_ptr = SnapshotCtx::complete(ctx);
             let nvmf_req = NvmfReq::from(nvmf_req_ptr);
             let mut rsp = nvmf_req.response();
             let nvme_status = rsp.status();
@@ -389,13 +1958,21 @@ impl Lvol {
             }
         }
 
+        let (ctx, recorded) = self
+            .begin_snapshot(snapshot_name, nvmf_req.0.as_ptr().cast())
+            .await;
+        if !recorded {
+            snapshot_done_cb(ctx, std::ptr::null_mut(), Errno::EIO as i32);
+            return;
+        }
+
         let c_snapshot_name = snapshot_name.into_cstring();
         unsafe {
             vbdev_lvol_create_snapshot(
                 self.0.as_ptr(),
                 c_snapshot_name.as_ptr(),
                 Some(snapshot_done_cb),
-                nvmf_req.0.as_ptr().cast(),
+                ctx,
             )
         };
 
@@ -409,10 +1986,11 @@ impl Lvol {
         snapshot_name: &str,
     ) {
         extern "C" fn snapshot_done_cb(
-            bio_ptr: *mut c_void,
+            ctx: *mut c_void,
             _lvol_ptr: *mut spdk_lvol,
             errno: i32,
         ) {
+            let bio_ptr = SnapshotCtx::complete(ctx);
             if errno != 0 {
                 error!("vbdev_lvol_create_snapshot errno {}", errno);
             }
@@ -423,13 +2001,20 @@ impl Lvol {
             .with(|| Nexus::io_completion_local(errno == 0, bio_ptr));
         }
 
+        let (ctx, recorded) =
+            self.begin_snapshot(snapshot_name, io.cast()).await;
+        if !recorded {
+            snapshot_done_cb(ctx, std::ptr::null_mut(), Errno::EIO as i32);
+            return;
+        }
+
         let c_snapshot_name = snapshot_name.into_cstring();
         unsafe {
             vbdev_lvol_create_snapshot(
                 self.0.as_ptr(),
                 c_snapshot_name.as_ptr(),
                 Some(snapshot_done_cb),
-                io.cast(),
+                ctx,
             )
         };
 
@@ -0,0 +1,275 @@
+//! Write-ahead journal of the metadata operations of a pool, for pools
+//! created with one. An operation is recorded in the journal before it is
+//! applied to the blobstore, and marked committed once it has been applied.
+//! An operation found uncommitted when the pool is imported again was
+//! interrupted by a crash, and is rolled back or forward so that the pool
+//! is consistent again:
+//!  - an lvol or snapshot being created is destroyed, as its creation was
+//!    never acknowledged and it may lack its properties
+//!  - an lvol being destroyed is destroyed
+//!
+//! The journal is held by a thick provisioned lvol of one cluster, which is
+//! used as a ring of records of one block each. An operation keeps the block
+//! of its record, and committing it rewrites that block. Every record is
+//! checksummed, so a record torn by a crash while it was written is ignored:
+//! a torn intent means the operation was never started, a torn commit that
+//! it completed, and either way there is nothing to recover. A record is
+//! only overwritten once the ring has wrapped around, so an operation must
+//! complete before as many others are started as the ring has blocks.
+
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+use nix::errno::Errno;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bdev::util::block::{decode_block, encode_block, encoded_len},
+    core::{Bdev, BdevHandle, CoreError, DmaBuf},
+    lvs::{Error, Lvol, Lvs},
+};
+
+/// magic of a journal record, "MJNL"
+const JOURNAL_MAGIC: u32 = 0x4d4a_4e4c;
+
+/// the sequence number of the next record of the journal of each pool
+static NEXT_SEQ: Lazy<Mutex<HashMap<String, u64>>> =
+    Lazy::new(Default::default);
+
+/// A metadata operation of a pool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JournalOp {
+    /// create an lvol
+    CreateLvol { name: String, size: u64, thin: bool },
+    /// destroy an lvol
+    DestroyLvol { name: String },
+    /// create a snapshot of an lvol
+    CreateSnapshot { lvol: String, snapshot: String },
+}
+
+/// one record of the journal, as stored in a block of it
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalRecord {
+    magic: u32,
+    seq: u64,
+    committed: bool,
+    op: JournalOp,
+}
+
+/// The journal of a pool.
+pub struct Journal {
+    pool: String,
+    lvol: Lvol,
+}
+
+impl Journal {
+    /// name of the lvol holding the journal of the pool
+    pub(crate) fn lvol_name(pool: &str) -> String {
+        format!("{}-journal", pool)
+    }
+
+    /// returns the journal of the pool, if it was created with one
+    pub fn lookup(pool: &Lvs) -> Option<Journal> {
+        Bdev::lookup_by_name(&Self::lvol_name(pool.name()))
+            .and_then(|b| Lvol::try_from(b).ok())
+            .filter(|l| l.pool() == pool.name())
+            .map(|lvol| Journal {
+                pool: pool.name().to_string(),
+                lvol,
+            })
+    }
+
+    /// Create the journal of a new pool, it takes one cluster of the pool.
+    pub(crate) async fn create(pool: &Lvs) -> Result<Journal, Error> {
+        let name = Self::lvol_name(pool.name());
+        let size = pool.cluster_size();
+        let lvol = pool.create_lvol(&name, size, false).await?;
+        NEXT_SEQ.lock().unwrap().insert(pool.name().to_string(), 1);
+        info!("{}: created the metadata journal", pool.name());
+        Ok(Journal {
+            pool: pool.name().to_string(),
+            lvol,
+        })
+    }
+
+    /// the size of the journal in bytes
+    pub fn size(&self) -> u64 {
+        self.lvol.size()
+    }
+
+    /// the number of records the journal holds
+    fn slots(&self) -> u64 {
+        self.lvol.size() / self.block_len()
+    }
+
+    fn block_len(&self) -> u64 {
+        u64::from(self.lvol.as_bdev().block_len())
+    }
+
+    fn error(&self, msg: String) -> Error {
+        Error::Journal {
+            source: Errno::EIO,
+            name: self.pool.clone(),
+            msg,
+        }
+    }
+
+    fn io_error(&self, error: CoreError) -> Error {
+        self.error(error.to_string())
+    }
+
+    fn handle(&self) -> Result<BdevHandle, Error> {
+        BdevHandle::open(&self.lvol.name(), true, false)
+            .map_err(|e| self.io_error(e))
+    }
+
+    fn dma_malloc(&self, hdl: &BdevHandle, len: u64) -> Result<DmaBuf, Error> {
+        hdl.dma_malloc(len).map_err(|e| {
+            self.error(format!("failed to allocate a buffer: {}", e))
+        })
+    }
+
+    /// Record the operation as about to be applied, returns the sequence
+    /// number to commit it with once it has been.
+    pub async fn begin(&self, op: JournalOp) -> Result<u64, Error> {
+        let seq = self.next_seq().await?;
+        self.write(&JournalRecord {
+            magic: JOURNAL_MAGIC,
+            seq,
+            committed: false,
+            op,
+        })
+        .await?;
+        Ok(seq)
+    }
+
+    /// Mark the operation with the given sequence number as applied, it is
+    /// no longer recovered when the pool is imported.
+    pub async fn commit(&self, seq: u64) -> Result<(), Error> {
+        let slot = seq % self.slots();
+        let hdl = self.handle()?;
+        let mut buf = self.dma_malloc(&hdl, self.block_len())?;
+        hdl.read_at(slot * self.block_len(), &mut buf)
+            .await
+            .map_err(|e| self.io_error(e))?;
+        let record = Self::parse(buf.as_slice(), slot, self.slots())
+            .filter(|r| r.seq == seq)
+            .ok_or_else(|| self.error(format!("no record {}", seq)))?;
+        self.write(&JournalRecord {
+            committed: true,
+            ..record
+        })
+        .await
+    }
+
+    /// the operations which have not been committed, oldest first
+    pub async fn pending(&self) -> Result<Vec<(u64, JournalOp)>, Error> {
+        let mut pending = self
+            .read_all()
+            .await?
+            .into_iter()
+            .filter(|r| !r.committed)
+            .map(|r| (r.seq, r.op))
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|(seq, _)| *seq);
+        Ok(pending)
+    }
+
+    /// Roll the operations interrupted by a crash back or forward, returns
+    /// the number of operations recovered.
+    pub(crate) async fn replay(&self, pool: &Lvs) -> Result<usize, Error> {
+        // the pool may have been imported elsewhere since it was last here
+        NEXT_SEQ.lock().unwrap().remove(&self.pool);
+        let pending = self.pending().await?;
+        for (seq, op) in &pending {
+            warn!("{}: recovering interrupted {:?}", self.pool, op);
+            let name = match op {
+                JournalOp::CreateLvol {
+                    name, ..
+                }
+                | JournalOp::DestroyLvol {
+                    name,
+                } => name,
+                JournalOp::CreateSnapshot {
+                    snapshot, ..
+                } => snapshot,
+            };
+            if let Some(lvol) = Bdev::lookup_by_name(name)
+                .and_then(|b| Lvol::try_from(b).ok())
+                .filter(|l| l.pool() == pool.name())
+            {
+                lvol.destroy().await?;
+            }
+            self.commit(*seq).await?;
+        }
+        if !pending.is_empty() {
+            info!(
+                "{}: recovered {} interrupted metadata operations",
+                self.pool,
+                pending.len()
+            );
+        }
+        Ok(pending.len())
+    }
+
+    /// the sequence number of the next record
+    async fn next_seq(&self) -> Result<u64, Error> {
+        if !NEXT_SEQ.lock().unwrap().contains_key(&self.pool) {
+            let last = self.read_all().await?.iter().map(|r| r.seq).max();
+            NEXT_SEQ
+                .lock()
+                .unwrap()
+                .entry(self.pool.clone())
+                .or_insert_with(|| last.unwrap_or(0) + 1);
+        }
+        let mut next = NEXT_SEQ.lock().unwrap();
+        let seq = next.get_mut(&self.pool).unwrap();
+        *seq += 1;
+        Ok(*seq - 1)
+    }
+
+    /// write the record to its block, and make it durable
+    async fn write(&self, record: &JournalRecord) -> Result<(), Error> {
+        let len = encoded_len(record);
+        let block_len = self.block_len();
+        if len > block_len {
+            return Err(self.error(format!(
+                "record of {} bytes does not fit a block",
+                len
+            )));
+        }
+
+        let hdl = self.handle()?;
+        let mut buf = self.dma_malloc(&hdl, block_len)?;
+        encode_block(record, buf.as_mut_slice());
+
+        let offset = (record.seq % self.slots()) * block_len;
+        hdl.write_at(offset, &buf)
+            .await
+            .map_err(|e| self.io_error(e))?;
+        hdl.flush().await.map_err(|e| self.io_error(e))
+    }
+
+    /// the valid records of the journal, torn or stale blocks are skipped
+    async fn read_all(&self) -> Result<Vec<JournalRecord>, Error> {
+        let hdl = self.handle()?;
+        let mut buf = self.dma_malloc(&hdl, self.lvol.size())?;
+        hdl.read_at(0, &mut buf)
+            .await
+            .map_err(|e| self.io_error(e))?;
+        Ok(buf
+            .as_slice()
+            .chunks(self.block_len() as usize)
+            .enumerate()
+            .filter_map(|(slot, block)| {
+                Self::parse(block, slot as u64, self.slots())
+            })
+            .collect())
+    }
+
+    /// the record in the block of the given slot, if it holds a valid one
+    fn parse(block: &[u8], slot: u64, slots: u64) -> Option<JournalRecord> {
+        decode_block::<JournalRecord>(block)
+            .filter(|r| r.magic == JOURNAL_MAGIC && r.seq % slots == slot)
+    }
+}
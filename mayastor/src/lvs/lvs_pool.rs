@@ -1,15 +1,35 @@
-use std::{convert::TryFrom, fmt::Debug, os::raw::c_void, ptr::NonNull};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    ffi::CStr,
+    fmt::{Debug, Display},
+    os::raw::{c_char, c_void},
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
 
-use futures::channel::oneshot;
+use futures::{channel::oneshot, stream, Stream};
 use nix::errno::Errno;
+use once_cell::sync::Lazy;
 use pin_utils::core_reexport::fmt::Formatter;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use tracing::instrument;
 
-use rpc::mayastor::CreatePoolRequest;
+use rpc::mayastor::{
+    ChecksumAlgorithm as RpcChecksumAlgorithm,
+    CreatePoolRequest,
+    PoolWipe,
+};
 use spdk_sys::{
     lvol_store_bdev,
+    spdk_blob,
+    spdk_blob_close,
+    spdk_blob_get_xattr_value,
+    spdk_blob_set_xattr,
+    spdk_blob_sync_md,
     spdk_bs_free_cluster_count,
     spdk_bs_get_cluster_size,
+    spdk_bs_open_blob,
     spdk_bs_total_data_cluster_count,
     spdk_lvol,
     spdk_lvol_store,
@@ -29,10 +49,29 @@ use spdk_sys::{
 use url::Url;
 
 use crate::{
-    bdev::{nexus::nexus_io::IoType, util::uring, Uri},
-    core::{Bdev, Share, Uuid},
-    ffihelper::{cb_arg, pair, AsStr, ErrnoResult, FfiResult, IntoCString},
-    lvs::{Error, Lvol, PropName, PropValue},
+    bdev::{
+        nexus::nexus_io::IoType,
+        util::uring,
+        BdevCreateDestroy,
+        CompressBdev,
+        CompressionAlgorithm,
+        DedupBdev,
+        DedupStats,
+        DedupStore,
+        Uri,
+    },
+    core::{Bdev, BdevHandle, ChecksumAlgorithm, Share, Uuid},
+    events::{self, Event, PoolState},
+    ffihelper::{
+        cb_arg,
+        errno_result_from_i32,
+        pair,
+        AsStr,
+        ErrnoResult,
+        FfiResult,
+        IntoCString,
+    },
+    lvs::{Error, Journal, JournalOp, Lvol, PropName, PropValue},
     nexus_uri::{bdev_destroy, NexusBdevError},
 };
 
@@ -82,13 +121,53 @@ impl IntoIterator for Lvs {
 }
 
 impl Debug for Lvs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lvs")
+            .field("name", &self.name())
+            .field("uuid", &self.uuid())
+            .field("base_bdev", &self.base_bdev().name())
+            .field("capacity", &self.capacity())
+            .field("used", &self.used())
+            .field("available", &self.available())
+            .field("integrity_reserve", &self.integrity_reserve())
+            .field("dedup", &self.dedup_stats())
+            .finish()
+    }
+}
+
+/// the live state of the pool and its lvols, as far as it is known without
+/// doing IO
+impl Serialize for Lvs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Lvs", 9)?;
+        s.serialize_field("name", self.name())?;
+        s.serialize_field("uuid", &self.uuid())?;
+        s.serialize_field("disk", &self.base_bdev().name())?;
+        s.serialize_field("capacity", &self.capacity())?;
+        s.serialize_field("used", &self.used())?;
+        s.serialize_field("available", &self.available())?;
+        s.serialize_field("integrity_reserve", &self.integrity_reserve())?;
+        s.serialize_field("dedup", &self.dedup_stats())?;
+        s.serialize_field(
+            "lvols",
+            &self.lvols().map_or_else(Vec::new, |l| l.collect::<Vec<_>>()),
+        )?;
+        s.end()
+    }
+}
+
+/// renders the pool as `name (uuid, capacity bytes)`
+impl Display for Lvs {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "name: {}, uuid: {}, base_bdev: {}",
+            "{} ({}, {} bytes)",
             self.name(),
             self.uuid(),
-            self.base_bdev().name()
+            self.capacity()
         )
     }
 }
@@ -96,6 +175,94 @@ impl Debug for Lvs {
 /// Logical Volume Store (LVS) stores the lvols
 pub struct Lvs(pub(crate) NonNull<spdk_lvol_store>);
 
+/// The specification of an lvol to create along with its pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LvolSpec {
+    /// the name of the lvol
+    pub name: String,
+    /// the size of the lvol in bytes
+    pub size: u64,
+    /// whether the lvol is thin provisioned
+    pub thin: bool,
+}
+
+impl LvolSpec {
+    pub fn new(name: &str, size: u64, thin: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            thin,
+        }
+    }
+}
+
+/// The capacity and usage of a pool, as found in its blobstore at the time
+/// they are asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+    /// the capacity of the pool, less the space it keeps for itself
+    pub total_bytes: u64,
+    /// the bytes of the capacity allocated to lvols
+    pub used_bytes: u64,
+    /// the bytes of the capacity left to allocate
+    pub free_bytes: u64,
+    /// the size of the clusters space is allocated in
+    pub cluster_size: u64,
+    /// the number of clusters making up the capacity
+    pub num_clusters: u64,
+}
+
+/// the names of the pools being imported, created, exported or destroyed,
+/// with the number of such operations in flight for each of them
+static BUSY_POOLS: Lazy<Mutex<HashMap<String, usize>>> =
+    Lazy::new(Default::default);
+
+/// version of the on-disk format of the pools this version of mayastor
+/// creates, which is kept in the super blob of the blobstore of the pool.
+/// Pools created before the version was recorded have version 1.
+pub const POOL_FORMAT_VERSION: u32 = 1;
+
+/// name of the xattr of the super blob holding the format version
+const FORMAT_VERSION_XATTR: &str = "mayastor_format_version";
+
+/// size of the start of the disk of a new pool which is zeroed to clear the
+/// metadata of whatever was kept on the disk before
+const WIPE_METADATA_SIZE: u64 = 4 * 1024 * 1024;
+
+/// size of the chunks the disk of a new pool is zeroed in
+const WIPE_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// marks a pool as busy for as long as it lives
+struct BusyPool(String);
+
+impl BusyPool {
+    fn new(name: &str) -> Self {
+        *BUSY_POOLS
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert(0) += 1;
+        Self(name.to_string())
+    }
+
+    /// returns a boolean indicating if the named pool is busy
+    fn is_busy(name: &str) -> bool {
+        BUSY_POOLS.lock().unwrap().contains_key(name)
+    }
+}
+
+impl Drop for BusyPool {
+    fn drop(&mut self) {
+        let mut busy = BUSY_POOLS.lock().unwrap();
+        if let Some(count) = busy.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                busy.remove(&self.0);
+            }
+        }
+    }
+}
+
 impl Lvs {
     /// generic lvol store callback
     extern "C" fn lvs_cb(
@@ -128,7 +295,57 @@ impl Lvs {
         LvsIterator::default()
     }
 
-    /// lookup a lvol store by its name
+    /// Returns a stream over all lvol stores, which are walked one at a time
+    /// as the stream is polled rather than collected upfront. A store
+    /// destroyed while the stream is held is not yielded, and should the
+    /// store yielded last be destroyed, the stream ends early.
+    pub fn stream() -> impl Stream<Item = Lvs> {
+        stream::unfold(None, |last: Option<String>| async move {
+            let next = match &last {
+                None => Lvs::iter().next(),
+                Some(last) => {
+                    Lvs::iter().skip_while(|l| l.name() != *last).nth(1)
+                }
+            }?;
+            let name = next.name().to_string();
+            Some((next, Some(name)))
+        })
+    }
+
+    /// Lookup a lvol store by its name, telling a pool which does not exist
+    /// apart from one which is busy being imported, created, exported or
+    /// destroyed and should be looked up again, and one which is in an
+    /// inconsistent state.
+    pub fn lookup_result(name: &str) -> Result<Self, Error> {
+        let busy = BusyPool::is_busy(name);
+        let lvs = match Self::lookup(name) {
+            Some(lvs) if !busy => lvs,
+            None if !busy => {
+                return Err(Error::PoolNotFound {
+                    source: Errno::ENOENT,
+                    name: name.to_string(),
+                })
+            }
+            _ => {
+                return Err(Error::PoolBusy {
+                    source: Errno::EBUSY,
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        if unsafe { vbdev_get_lvs_bdev_by_lvs(lvs.0.as_ptr()) }.is_null() {
+            return Err(Error::PoolCorrupt {
+                source: Errno::EIO,
+                name: name.to_string(),
+                msg: "the pool has no base bdev".into(),
+            });
+        }
+        Ok(lvs)
+    }
+
+    /// lookup a lvol store by its name, a pool is returned even while it is
+    /// busy or inconsistent, see `lookup_result()`
     pub fn lookup(name: &str) -> Option<Self> {
         let name = name.into_cstring();
 
@@ -145,8 +362,16 @@ impl Lvs {
         unsafe { self.0.as_ref().name.as_str() }
     }
 
-    /// returns the total capacity of the store
+    /// returns the total capacity of the store, less the space reserved for
+    /// integrity metadata and the metadata journal
     pub fn capacity(&self) -> u64 {
+        self.data_capacity()
+            - self.integrity_reserve()
+            - self.journal().map_or(0, |j| j.size())
+    }
+
+    /// returns the capacity of all data clusters of the store
+    fn data_capacity(&self) -> u64 {
         let blobs = unsafe { self.0.as_ref().blobstore };
         unsafe {
             spdk_bs_get_cluster_size(blobs)
@@ -154,6 +379,12 @@ impl Lvs {
         }
     }
 
+    /// returns the size of the clusters of the store
+    pub fn cluster_size(&self) -> u64 {
+        let blobs = unsafe { self.0.as_ref().blobstore };
+        unsafe { spdk_bs_get_cluster_size(blobs) }
+    }
+
     /// returns the available capacity
     pub fn available(&self) -> u64 {
         let blobs = unsafe { self.0.as_ref().blobstore };
@@ -162,9 +393,192 @@ impl Lvs {
         }
     }
 
-    /// returns the used capacity
+    /// returns the used capacity, the integrity metadata of lvols does not
+    /// count as it is taken from the space reserved for it
     pub fn used(&self) -> u64 {
-        self.capacity() - self.available()
+        self.capacity().saturating_sub(self.available())
+    }
+
+    /// returns the capacity and usage of the store
+    pub fn stats(&self) -> PoolStats {
+        let total_bytes = self.capacity();
+        let cluster_size = self.cluster_size();
+        PoolStats {
+            total_bytes,
+            used_bytes: self.used(),
+            free_bytes: self.available(),
+            cluster_size,
+            num_clusters: total_bytes / cluster_size,
+        }
+    }
+
+    /// name of the lvol holding the space of the pool reserved for integrity
+    /// metadata
+    pub(crate) fn integrity_reserve_name(pool: &str) -> String {
+        format!("{}-integrity-reserve", pool)
+    }
+
+    /// returns the lvol holding the space of the pool reserved for integrity
+    /// metadata which has not been taken by lvols yet, if there is any
+    pub(crate) fn integrity_reserve_lvol(&self) -> Option<Lvol> {
+        Bdev::lookup_by_name(&Self::integrity_reserve_name(self.name()))
+            .and_then(|b| Lvol::try_from(b).ok())
+            .filter(|l| l.pool() == self.name())
+    }
+
+    /// returns the percentage of the pool reserved for integrity metadata
+    pub fn integrity_reserve_percent(&self) -> u32 {
+        match self
+            .integrity_reserve_lvol()
+            .map(|l| l.get_xattr(PropName::IntegrityReserve))
+        {
+            Some(Ok(PropValue::IntegrityReserve(percent))) => percent,
+            _ => 0,
+        }
+    }
+
+    /// returns the space of the pool reserved for integrity metadata in
+    /// bytes, including the part of it taken by lvols with checksums or
+    /// protection information
+    pub fn integrity_reserve(&self) -> u64 {
+        self.integrity_reserve_len(self.integrity_reserve_percent())
+    }
+
+    /// returns the space reserved for integrity metadata which has not been
+    /// taken by lvols yet in bytes
+    pub fn integrity_reserve_available(&self) -> u64 {
+        self.integrity_reserve_lvol().map_or(0, |l| l.size())
+    }
+
+    /// the given percentage of the pool in bytes, in whole clusters
+    fn integrity_reserve_len(&self, percent: u32) -> u64 {
+        let cluster = self.cluster_size();
+        let len = self.data_capacity() * u64::from(percent) / 100;
+        (len + cluster - 1) / cluster * cluster
+    }
+
+    /// returns the metadata journal of the pool, if it was created with one
+    pub fn journal(&self) -> Option<Journal> {
+        Journal::lookup(self)
+    }
+
+    /// Reserve the given percentage of the pool for integrity metadata. The
+    /// space is held by a thick provisioned lvol, which lvols with checksums
+    /// or protection information take the space for their metadata from.
+    async fn reserve_integrity(&self, percent: u32) -> Result<(), Error> {
+        let name = Self::integrity_reserve_name(self.name());
+        let len = self.integrity_reserve_len(percent);
+        let lvol = self.create_lvol(&name, len, false).await?;
+        if let Err(error) = lvol.set(PropValue::IntegrityReserve(percent)).await
+        {
+            let _ = lvol.destroy().await;
+            return Err(error);
+        }
+        info!(
+            "{}: reserved {}% ({} bytes) for integrity metadata",
+            self.name(),
+            percent,
+            len
+        );
+        Ok(())
+    }
+
+    /// name of the lvol holding the dedup store of the pool
+    pub(crate) fn dedup_store_name(pool: &str) -> String {
+        format!("{}-dedup-store", pool)
+    }
+
+    /// returns the dedup store of the pool, if it was created with one and
+    /// it is open
+    pub fn dedup_store(&self) -> Option<Arc<DedupStore>> {
+        DedupStore::lookup(&Self::dedup_store_name(self.name()))
+    }
+
+    /// returns how well the data of the pool has deduplicated, if it was
+    /// created with a dedup store
+    pub fn dedup_stats(&self) -> Option<DedupStats> {
+        self.dedup_store().map(|s| s.stats())
+    }
+
+    /// the hash of the given RPC value to deduplicate blocks by
+    fn dedup_hash(hash: i32) -> Option<ChecksumAlgorithm> {
+        match RpcChecksumAlgorithm::from_i32(hash)? {
+            RpcChecksumAlgorithm::ChecksumCrc32c => {
+                Some(ChecksumAlgorithm::Crc32c)
+            }
+            RpcChecksumAlgorithm::ChecksumXxhash64 => {
+                Some(ChecksumAlgorithm::XxHash64)
+            }
+            RpcChecksumAlgorithm::ChecksumSha256 => {
+                Some(ChecksumAlgorithm::Sha256)
+            }
+        }
+    }
+
+    /// Set up the dedup store of a new pool, which the data of its lvols is
+    /// kept in. It is held by a thin provisioned lvol as large as the pool,
+    /// so that it only takes the space of the distinct blocks stored.
+    async fn reserve_dedup(
+        &self,
+        hash: ChecksumAlgorithm,
+    ) -> Result<(), Error> {
+        let name = Self::dedup_store_name(self.name());
+        let lvol = self.create_lvol(&name, self.data_capacity(), true).await?;
+        if let Err(error) = DedupStore::open(&name, Some(hash)).await {
+            let _ = lvol.destroy().await;
+            return Err(Error::Dedup {
+                source: error,
+                name,
+            });
+        }
+        info!("{}: deduplicating blocks by {}", self.name(), hash);
+        Ok(())
+    }
+
+    /// Open the dedup store of an imported pool, if it has one. The
+    /// references to its blocks are counted again from the maps of the
+    /// deduplicated lvols, as a crash may have left blocks referenced which
+    /// no lvol maps to.
+    async fn open_dedup(&self) {
+        let name = Self::dedup_store_name(self.name());
+        if Bdev::lookup_by_name(&name).is_none() {
+            return;
+        }
+        let store = match DedupStore::open(&name, None).await {
+            Ok(store) => store,
+            Err(error) => {
+                error!("{}: failed to open the dedup store: {}", self, error);
+                return;
+            }
+        };
+
+        let mut refs = HashMap::new();
+        for lvol in self.lvols().into_iter().flatten() {
+            if !lvol.is_dedup() {
+                continue;
+            }
+            match DedupBdev::references(&lvol.name()).await {
+                Ok(blocks) => {
+                    for block in blocks {
+                        *refs.entry(block).or_insert(0) += 1;
+                    }
+                }
+                Err(error) => {
+                    // the blocks it maps to could be freed otherwise
+                    error!(
+                        "{}: not counting the references to the dedup \
+                         store, failed to read the map of {}: {}",
+                        self,
+                        lvol.name(),
+                        error
+                    );
+                    return;
+                }
+            }
+        }
+        if let Err(error) = store.reconcile(&refs).await {
+            error!("{}: failed to count the references: {}", self, error);
+        }
     }
 
     /// returns the base bdev of this lvs
@@ -174,19 +588,296 @@ impl Lvs {
         })
     }
 
+    /// publish a state change of the pool on the event bus
+    fn publish_state(&self, old: PoolState, new: PoolState) {
+        events::publish(Event::PoolStateChanged {
+            name: self.name().to_string(),
+            old,
+            new,
+        });
+    }
+
     /// returns the UUID of the lvs
     pub fn uuid(&self) -> String {
         let t = unsafe { self.0.as_ref().uuid.u.raw };
         Uuid::from_bytes(t).to_string()
     }
 
+    /// opens the super blob of the blobstore, which holds the name, the UUID
+    /// and the format version of the pool
+    async fn open_super_blob(&self) -> Result<*mut spdk_blob, Errno> {
+        extern "C" fn open_cb(
+            sender_ptr: *mut c_void,
+            blob: *mut spdk_blob,
+            errno: i32,
+        ) {
+            let sender = unsafe {
+                Box::from_raw(
+                    sender_ptr
+                        as *mut oneshot::Sender<ErrnoResult<*mut spdk_blob>>,
+                )
+            };
+            sender
+                .send(errno_result_from_i32(blob, errno))
+                .expect("receiver gone");
+        }
+
+        let (s, r) = pair::<ErrnoResult<*mut spdk_blob>>();
+        unsafe {
+            let lvs = self.0.as_ref();
+            spdk_bs_open_blob(
+                lvs.blobstore,
+                lvs.super_blob_id,
+                Some(open_cb),
+                cb_arg(s),
+            );
+        }
+        r.await.expect("super blob open callback is gone")
+    }
+
+    /// closes the super blob opened by open_super_blob()
+    async fn close_super_blob(blob: *mut spdk_blob) -> Result<(), Errno> {
+        let (s, r) = pair::<i32>();
+        unsafe { spdk_blob_close(blob, Some(Self::lvs_op_cb), cb_arg(s)) };
+        errno_result_from_i32((), r.await.expect("blob close callback is gone"))
+    }
+
+    /// returns the version of the on-disk format of the pool
+    pub async fn format_version(&self) -> Result<u32, Error> {
+        let blob = self.open_super_blob().await.map_err(|e| Error::Import {
+            source: e,
+            name: self.name().to_string(),
+        })?;
+
+        let name = FORMAT_VERSION_XATTR.into_cstring();
+        let mut value: *const c_void = std::ptr::null();
+        let mut value_len: u64 = 0;
+        let rc = unsafe {
+            spdk_blob_get_xattr_value(
+                blob,
+                name.as_ptr(),
+                &mut value,
+                &mut value_len,
+            )
+        };
+        let version = if rc != 0 {
+            // pools created before the version was recorded have none, and
+            // are of version 1
+            Some(1)
+        } else {
+            unsafe { CStr::from_ptr(value as *const c_char) }
+                .to_str()
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+        };
+
+        let closed = Self::close_super_blob(blob).await;
+        let version = version.ok_or_else(|| Error::PoolCorrupt {
+            source: Errno::EINVAL,
+            name: self.name().to_string(),
+            msg: String::from("invalid format version"),
+        })?;
+        closed.map_err(|e| Error::Import {
+            source: e,
+            name: self.name().to_string(),
+        })?;
+        Ok(version)
+    }
+
+    /// Records the format version of the pool, as when its on-disk format
+    /// is upgraded. The pool is not imported by versions of mayastor which
+    /// do not support the format version it records.
+    pub async fn set_format_version(&self, version: u32) -> Result<(), Error> {
+        self.write_format_version(version).await.map_err(|e| {
+            Error::SyncProperty {
+                source: e,
+                name: self.name().to_string(),
+            }
+        })
+    }
+
+    /// records the format version in the super blob of the pool
+    async fn write_format_version(&self, version: u32) -> Result<(), Errno> {
+        let blob = self.open_super_blob().await?;
+
+        let name = FORMAT_VERSION_XATTR.into_cstring();
+        let value = version.to_string().into_cstring();
+        let rc = unsafe {
+            spdk_blob_set_xattr(
+                blob,
+                name.as_ptr(),
+                value.as_bytes_with_nul().as_ptr() as *const _,
+                value.as_bytes_with_nul().len() as u16,
+            )
+        };
+        let written = if rc != 0 {
+            Err(Errno::from_i32(rc.abs()))
+        } else {
+            let (s, r) = pair::<i32>();
+            unsafe {
+                spdk_blob_sync_md(blob, Some(Self::lvs_op_cb), cb_arg(s))
+            };
+            errno_result_from_i32((), r.await.expect("sync callback is gone"))
+        };
+
+        let closed = Self::close_super_blob(blob).await;
+        written.and(closed)
+    }
+
+    /// Checks that the format of a pool which has just been loaded is
+    /// supported. A pool of a newer format, or one whose format can not be
+    /// determined, is exported again rather than imported.
+    async fn check_format(self) -> Result<Lvs, Error> {
+        let supported = POOL_FORMAT_VERSION;
+        let error = match self.format_version().await {
+            Ok(found) if found <= supported => return Ok(self),
+            Ok(found) => Error::PoolVersionMismatch {
+                source: Errno::EPROTO,
+                name: self.name().to_string(),
+                found,
+                supported,
+            },
+            Err(error) => error,
+        };
+
+        error!("{} -- unloading the pool", error);
+        self.export().await?;
+        Err(error)
+    }
+
     /// imports a pool based on its name and base bdev name
     #[instrument(level = "debug", err)]
     pub async fn import(name: &str, bdev: &str) -> Result<Lvs, Error> {
-        let (sender, receiver) = pair::<ErrnoResult<Lvs>>();
+        let _busy = BusyPool::new(name);
 
         debug!("Trying to import pool {} on {}", name, bdev);
 
+        let lvs = Self::examine(name, bdev).await?;
+
+        if name != lvs.name() {
+            warn!("no pool with name {} found on this device -- unloading the pool", name);
+            lvs.export().await.unwrap();
+            Err(Error::Import {
+                source: Errno::EINVAL,
+                name: name.into(),
+            })
+        } else {
+            let lvs = lvs.check_format().await?;
+            lvs.online().await;
+            Ok(lvs)
+        }
+    }
+
+    /// Imports the pool with the given UUID from the disks, which are
+    /// created like those of `create_or_import()`. Should the pool on the
+    /// disks have another UUID, for example as the disks have been reordered,
+    /// it is not imported and UuidMismatch is returned.
+    #[instrument(level = "debug", err)]
+    pub async fn import_by_uuid(
+        uuid: &str,
+        disks: Vec<String>,
+    ) -> Result<Lvs, Error> {
+        let uuid = Uuid::parse_str(uuid)
+            .map_err(|_| Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("invalid pool UUID {}", uuid),
+            })?
+            .to_string();
+        let _busy = BusyPool::new(&uuid);
+        let parsed = Self::parse_disks(&uuid, &disks)?;
+
+        if let Some(pool) = Self::iter().find(|p| p.uuid() == uuid) {
+            return if pool.base_bdev().name() == parsed.get_name() {
+                Ok(pool)
+            } else {
+                Err(Error::Import {
+                    source: Errno::EEXIST,
+                    name: pool.name().to_string(),
+                })
+            };
+        }
+
+        let bdev = Self::create_disk(&parsed, &disks[0]).await?;
+
+        debug!("Trying to import pool {} on {}", uuid, bdev);
+
+        let lvs = Self::examine(&uuid, &bdev).await?;
+
+        if lvs.uuid() != uuid {
+            let found = lvs.uuid();
+            warn!(
+                "pool {} on {} is not pool {} -- unloading the pool",
+                found, bdev, uuid
+            );
+            lvs.export().await?;
+            return Err(Error::UuidMismatch {
+                source: Errno::EINVAL,
+                uuid,
+                found,
+            });
+        }
+
+        let lvs = lvs.check_format().await?;
+        lvs.online().await;
+        // recover the metadata operations a crash interrupted
+        if let Some(journal) = lvs.journal() {
+            journal.replay(&lvs).await?;
+        }
+        Ok(lvs)
+    }
+
+    /// Imports the pools found on the bdevs which are not in use, such as
+    /// after a restart once the bdevs of the pools have been created again.
+    /// Bdevs without a pool are skipped. A pool failing to import does not
+    /// stop those on other bdevs from being imported, so the result of each
+    /// bdev with a pool is returned.
+    pub async fn import_all() -> Vec<Result<Lvs, Error>> {
+        let bdevs = match Bdev::bdev_first() {
+            Some(bdev) => bdev
+                .into_iter()
+                .filter(|b| {
+                    !b.is_claimed()
+                        && b.driver() != "lvol"
+                        && b.driver() != "nexus"
+                })
+                .map(|b| b.name())
+                .collect::<Vec<_>>(),
+            None => return Vec::new(),
+        };
+
+        let mut results = Vec::new();
+        for bdev in bdevs {
+            match Self::import_found(&bdev).await {
+                Err(Error::Import {
+                    source, ..
+                }) if source == Errno::EILSEQ => {
+                    debug!("No pool found on {}", bdev);
+                }
+                result => results.push(result),
+            }
+        }
+        results
+    }
+
+    /// imports the pool found on the bdev, whatever its name
+    async fn import_found(bdev: &str) -> Result<Lvs, Error> {
+        debug!("Looking for a pool to import on {}", bdev);
+        let lvs = Self::examine(bdev, bdev).await?;
+        let _busy = BusyPool::new(lvs.name());
+        let lvs = lvs.check_format().await?;
+        lvs.online().await;
+        // recover the metadata operations a crash interrupted
+        if let Some(journal) = lvs.journal() {
+            journal.replay(&lvs).await?;
+        }
+        Ok(lvs)
+    }
+
+    /// loads the pool found on the base bdev, name is that of the pool looked
+    /// for, which the pool found need not have
+    async fn examine(name: &str, bdev: &str) -> Result<Lvs, Error> {
+        let (sender, receiver) = pair::<ErrnoResult<Lvs>>();
+
         let bdev = Bdev::lookup_by_name(bdev).ok_or(Error::InvalidBdev {
             source: NexusBdevError::BdevNotFound {
                 name: bdev.to_string(),
@@ -216,31 +907,27 @@ impl Lvs {
 
         // when no pool name can be determined the or failed to compare to the
         // desired pool name EILSEQ is returned
-        let lvs = receiver
+        receiver
             .await
             .expect("Cancellation is not supported")
             .map_err(|err| Error::Import {
                 source: err,
                 name: name.into(),
-            })?;
-
-        if name != lvs.name() {
-            warn!("no pool with name {} found on this device -- unloading the pool", name);
-            lvs.export().await.unwrap();
-            Err(Error::Import {
-                source: Errno::EINVAL,
-                name: name.into(),
             })
-        } else {
-            lvs.share_all().await;
-            info!("The pool '{}' has been imported", name);
-            Ok(lvs)
-        }
+    }
+
+    /// brings a pool which has just been loaded online
+    async fn online(&self) {
+        self.open_dedup().await;
+        self.share_all().await;
+        info!("The pool {} has been imported", self);
+        self.publish_state(PoolState::Exported, PoolState::Online);
     }
 
     #[instrument(level = "debug", err)]
     /// Create a pool on base bdev
     pub async fn create(name: &str, bdev: &str) -> Result<Lvs, Error> {
+        let _busy = BusyPool::new(name);
         let pool_name = name.into_cstring();
         let bdev_name = bdev.into_cstring();
 
@@ -274,7 +961,17 @@ impl Lvs {
 
         match Self::lookup(&name) {
             Some(pool) => {
-                info!("The pool '{}' has been created on {}", name, bdev);
+                if let Err(e) =
+                    pool.write_format_version(POOL_FORMAT_VERSION).await
+                {
+                    let _ = pool.destroy().await;
+                    return Err(Error::Create {
+                        source: e,
+                        name: name.to_string(),
+                    });
+                }
+                info!("The pool {} has been created on {}", pool, bdev);
+                pool.publish_state(PoolState::Absent, PoolState::Online);
                 Ok(pool)
             }
             None => Err(Error::Create {
@@ -284,48 +981,59 @@ impl Lvs {
         }
     }
 
+    /// Set up the reserved space of a new pool the request asks for, the
+    /// metadata journal comes first so that the rest is journaled.
+    async fn reserve(&self, args: &CreatePoolRequest) -> Result<(), Error> {
+        if args.journal {
+            Journal::create(self).await?;
+        }
+        if args.integrity_reserve > 0 {
+            self.reserve_integrity(args.integrity_reserve).await?;
+        }
+        if args.dedup {
+            // checked when the request was received
+            let hash = Self::dedup_hash(args.dedup_hash).unwrap();
+            self.reserve_dedup(hash).await?;
+        }
+        Ok(())
+    }
+
     /// imports the pool if it exists, otherwise try to create it
     #[instrument(level = "debug", err)]
     pub async fn create_or_import(
         args: CreatePoolRequest,
     ) -> Result<Lvs, Error> {
-        if args.disks.len() != 1 {
+        let _busy = BusyPool::new(&args.name);
+        if args.integrity_reserve >= 100 {
             return Err(Error::Invalid {
                 source: Errno::EINVAL,
                 msg: format!(
-                    "invalid number {} of devices {:?}",
-                    args.disks.len(),
-                    args.disks
+                    "invalid integrity reserve of {}% for pool {}",
+                    args.integrity_reserve, args.name
                 ),
             });
         }
 
-        // default to uring if kernel supports it
-        let disks = args
-            .disks
-            .iter()
-            .map(|d| {
-                if Url::parse(d).is_err() {
-                    format!(
-                        "{}://{}",
-                        if uring::kernel_support() {
-                            "uring"
-                        } else {
-                            "aio"
-                        },
-                        d,
-                    )
-                } else {
-                    d.clone()
-                }
-            })
-            .collect::<Vec<_>>();
+        if args.dedup && Self::dedup_hash(args.dedup_hash).is_none() {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "invalid dedup hash {} for pool {}",
+                    args.dedup_hash, args.name
+                ),
+            });
+        }
 
-        let parsed = Uri::parse(&disks[0]).map_err(|e| Error::InvalidBdev {
-            source: e,
-            name: args.name.clone(),
+        let wipe = PoolWipe::from_i32(args.wipe).ok_or_else(|| Error::Invalid {
+            source: Errno::EINVAL,
+            msg: format!(
+                "invalid wipe mode {} for pool {}",
+                args.wipe, args.name
+            ),
         })?;
 
+        let parsed = Self::parse_disks(&args.name, &args.disks)?;
+
         if let Some(pool) = Self::lookup(&args.name) {
             return if pool.base_bdev().name() == parsed.get_name() {
                 Ok(pool)
@@ -337,21 +1045,16 @@ impl Lvs {
             };
         }
 
-        let bdev = match parsed.create().await {
-            Err(e) => match e {
-                NexusBdevError::BdevExists {
-                    ..
-                } => Ok(parsed.get_name()),
-                _ => Err(Error::InvalidBdev {
-                    source: e,
-                    name: args.disks[0].clone(),
-                }),
-            },
-            Ok(name) => Ok(name),
-        }?;
+        let bdev = Self::create_disk(&parsed, &args.disks[0]).await?;
 
         match Self::import(&args.name, &bdev).await {
-            Ok(pool) => Ok(pool),
+            Ok(pool) => {
+                // recover the metadata operations a crash interrupted
+                if let Some(journal) = pool.journal() {
+                    journal.replay(&pool).await?;
+                }
+                Ok(pool)
+            }
             Err(Error::Import {
                 source,
                 name,
@@ -367,7 +1070,12 @@ impl Lvs {
             Err(Error::Import {
                 source, ..
             }) if source == Errno::EILSEQ => {
-                match Self::create(&args.name, &bdev).await {
+                let created =
+                    match Self::wipe(&args.name, &bdev, wipe).await {
+                        Ok(_) => Self::create(&args.name, &bdev).await,
+                        Err(e) => Err(e),
+                    };
+                match created {
                     Err(create) => {
                         let _ = parsed.destroy().await.map_err(|_e| {
                             // we failed to delete the base_bdev be loud about it
@@ -377,7 +1085,13 @@ impl Lvs {
                         });
                         Err(create)
                     }
-                    Ok(pool) => Ok(pool),
+                    Ok(pool) => match pool.reserve(&args).await {
+                        Ok(_) => Ok(pool),
+                        Err(reserve) => {
+                            let _ = pool.destroy().await;
+                            Err(reserve)
+                        }
+                    },
                 }
             }
             // some other error, bubble it back up
@@ -385,15 +1099,169 @@ impl Lvs {
         }
     }
 
+    /// Clears the disk a pool is about to be created on as asked for, so that
+    /// whatever was kept on it before does not confuse other tools. The disk
+    /// is zeroed a chunk at a time rather than with a buffer as large as it.
+    async fn wipe(name: &str, bdev: &str, wipe: PoolWipe) -> Result<(), Error> {
+        let size = match wipe {
+            PoolWipe::WipeNone => return Ok(()),
+            PoolWipe::WipeMetadata => WIPE_METADATA_SIZE,
+            PoolWipe::WipeFull => u64::MAX,
+        };
+        let failed = |msg: String| {
+            error!("failed to clear disk {} of pool {}: {}", bdev, name, msg);
+            Error::Create {
+                source: Errno::EIO,
+                name: name.to_string(),
+            }
+        };
+
+        let hdl = BdevHandle::open(bdev, true, false)
+            .map_err(|e| failed(e.to_string()))?;
+        let size = std::cmp::min(size, hdl.get_bdev().size_in_bytes());
+        let mut buf = hdl
+            .dma_malloc(std::cmp::min(size, WIPE_CHUNK_SIZE))
+            .map_err(|e| failed(e.to_string()))?;
+        buf.fill(0);
+
+        info!("clearing {} bytes of disk {} of pool {}", size, bdev, name);
+        let mut offset = 0;
+        while offset < size {
+            if size - offset < buf.len() {
+                buf = hdl
+                    .dma_malloc(size - offset)
+                    .map_err(|e| failed(e.to_string()))?;
+                buf.fill(0);
+            }
+            hdl.write_at(offset, &buf)
+                .await
+                .map_err(|e| failed(e.to_string()))?;
+            offset += buf.len();
+        }
+        Ok(())
+    }
+
+    /// parses the disks of the pool named name, of which there must be one,
+    /// a disk which is not a URI is opened with uring if the kernel supports
+    /// it and aio otherwise
+    fn parse_disks(
+        name: &str,
+        disks: &[String],
+    ) -> Result<Box<dyn BdevCreateDestroy<Error = NexusBdevError>>, Error>
+    {
+        if disks.len() != 1 {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!(
+                    "invalid number {} of devices {:?}",
+                    disks.len(),
+                    disks
+                ),
+            });
+        }
+
+        // default to uring if kernel supports it
+        let disk = if Url::parse(&disks[0]).is_err() {
+            format!(
+                "{}://{}",
+                if uring::kernel_support() {
+                    "uring"
+                } else {
+                    "aio"
+                },
+                disks[0],
+            )
+        } else {
+            disks[0].clone()
+        };
+
+        Uri::parse(&disk).map_err(|e| Error::InvalidBdev {
+            source: e,
+            name: name.to_string(),
+        })
+    }
+
+    /// creates the base bdev of a pool unless it exists, returns its name
+    async fn create_disk(
+        parsed: &dyn BdevCreateDestroy<Error = NexusBdevError>,
+        disk: &str,
+    ) -> Result<String, Error> {
+        match parsed.create().await {
+            Err(e) => match e {
+                NexusBdevError::BdevExists {
+                    ..
+                } => Ok(parsed.get_name()),
+                _ => Err(Error::InvalidBdev {
+                    source: e,
+                    name: disk.to_string(),
+                }),
+            },
+            Ok(name) => Ok(name),
+        }
+    }
+
+    /// Create or import the pool like `create_or_import()`, and create the
+    /// given lvols on it. Should any of the lvols fail to be created, the
+    /// lvols created so far are destroyed again, as is the pool unless it
+    /// holds other lvols, so the pool is either set up in full or not at all.
+    #[instrument(level = "debug", err)]
+    pub async fn create_with_lvols(
+        args: CreatePoolRequest,
+        lvols: &[LvolSpec],
+    ) -> Result<Lvs, Error> {
+        if let Some((_, spec)) = lvols
+            .iter()
+            .enumerate()
+            .find(|(i, l)| lvols[.. *i].iter().any(|o| o.name == l.name))
+        {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("lvol {} is specified more than once", spec.name),
+            });
+        }
+
+        let _busy = BusyPool::new(&args.name);
+        let pool = Self::create_or_import(args).await?;
+
+        let mut created = Vec::with_capacity(lvols.len());
+        for spec in lvols {
+            match pool.create_lvol(&spec.name, spec.size, spec.thin).await {
+                Ok(lvol) => created.push(lvol),
+                Err(error) => {
+                    error!(
+                        "{}: failed to create lvol {}, rolling back: {}",
+                        pool.name(),
+                        spec.name,
+                        error
+                    );
+                    for lvol in created {
+                        let name = lvol.name();
+                        if let Err(e) = lvol.destroy().await {
+                            error!("failed to destroy lvol {}: {}", name, e);
+                        }
+                    }
+                    if pool.lvols().map_or(true, |mut l| l.next().is_none()) {
+                        if let Err(e) = pool.destroy().await {
+                            error!("failed to destroy pool: {}", e);
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(pool)
+    }
+
     /// export the given lvl
     #[allow(clippy::unit_arg)] // here to silence the () argument
     #[instrument(level = "debug", err)]
     pub async fn export(self) -> Result<(), Error> {
         let pool = self.name().to_string();
+        let _busy = BusyPool::new(&pool);
         let base_bdev = self.base_bdev();
         let (s, r) = pair::<i32>();
 
-        self.unshare_all().await;
+        self.unload_shares().await;
 
         unsafe {
             vbdev_lvs_unload(self.0.as_ptr(), Some(Self::lvs_op_cb), cb_arg(s))
@@ -407,6 +1275,11 @@ impl Lvs {
             })?;
 
         info!("pool {} exported successfully", pool);
+        events::publish(Event::PoolStateChanged {
+            name: pool.clone(),
+            old: PoolState::Online,
+            new: PoolState::Exported,
+        });
         bdev_destroy(&base_bdev.bdev_uri().unwrap())
             .await
             .map_err(|e| Error::Destroy {
@@ -416,20 +1289,100 @@ impl Lvs {
         Ok(())
     }
 
-    /// unshare all lvols prior to export or destroy
-    async fn unshare_all(&self) {
+    /// unshare all lvols prior to export or destroy, keeping their shared
+    /// property, and close the dedup store once the dedup bdevs using it are
+    /// gone
+    async fn unload_shares(&self) {
         for l in self.lvols().unwrap() {
             // notice we dont use the unshare impl of the bdev
             // here. we do this to avoid the on disk persistence
+            if let Err(e) = l.unshare_protected().await {
+                error!("failed to unshare lvol {} error {}", l, e.to_string())
+            }
+            if let Err(e) = l.unshare_compressed().await {
+                error!("failed to unshare lvol {} error {}", l, e.to_string())
+            }
+            if let Err(e) = l.unshare_dedup().await {
+                error!("failed to unshare lvol {} error {}", l, e.to_string())
+            }
+            if let Err(e) = l.unshare_readonly().await {
+                error!("failed to unshare lvol {} error {}", l, e.to_string())
+            }
             let bdev = l.as_bdev();
             if let Err(e) = bdev.unshare().await {
                 error!("failed to unshare lvol {} error {}", l, e.to_string())
             }
         }
+        DedupStore::close(&Self::dedup_store_name(self.name()));
+    }
+
+    /// Unshare every lvol of the pool, whichever protocol it is shared over,
+    /// and clear its shared property so that it is not shared again when
+    /// the pool is imported. Lvols which are not shared are left as they
+    /// are. The lvols failing to be unshared do not stop the others from
+    /// being unshared, the error of each of them is returned.
+    pub async fn unshare_all(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for lvol in self.lvols().into_iter().flatten() {
+            if let Err(error) = lvol.unshare().await {
+                error!("failed to unshare lvol {}: {}", lvol, error);
+                errors.push(error);
+            }
+        }
+        errors
+    }
+
+    /// Unshare and destroy all lvols of the pool, clones before the
+    /// snapshots they depend on, and then destroy the pool. The lvols
+    /// failing to be unshared or destroyed do not stop the others from
+    /// being destroyed, nor the pool, which takes them with it. Fails with
+    /// the errors of all of them, and that of the pool should it fail to be
+    /// destroyed as well.
+    pub async fn destroy_recursive(self) -> Result<(), Vec<Error>> {
+        let mut errors = self.unshare_all().await;
+
+        let mut lvols = self
+            .lvols()
+            .into_iter()
+            .flatten()
+            .filter(|l| !l.is_reserved())
+            .collect::<Vec<_>>();
+        while !lvols.is_empty() {
+            let count = lvols.len();
+            let mut in_use = Vec::new();
+            for lvol in lvols {
+                if lvol.is_snapshot() && !lvol.clones().is_empty() {
+                    in_use.push(lvol);
+                } else if let Err(error) = lvol.destroy().await {
+                    errors.push(error);
+                }
+            }
+
+            // the clones of these snapshots failed to be destroyed
+            if in_use.len() == count {
+                for lvol in in_use {
+                    if let Err(error) = lvol.destroy().await {
+                        errors.push(error);
+                    }
+                }
+                break;
+            }
+            lvols = in_use;
+        }
+
+        if let Err(error) = self.destroy().await {
+            errors.push(error);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 
     /// share all lvols who have the shared property set, this is implicitly
-    /// shared over nvmf
+    /// shared over nvmf, and open the others which are accessed through a
+    /// bdev on top of them locally
     async fn share_all(&self) {
         if let Some(lvols) = self.lvols() {
             for l in lvols {
@@ -444,9 +1397,16 @@ impl Lvs {
                                 );
                             }
                         }
-                        PropValue::Shared(false) => {
-                            debug!("{} not shared on disk", l.name())
+                        _ if l.is_layered() && !l.is_snapshot() => {
+                            if let Err(e) = l.open_local().await {
+                                error!(
+                                    "failed to open {} {}",
+                                    l.name(),
+                                    e.to_string()
+                                );
+                            }
                         }
+                        _ => debug!("{} not shared on disk", l.name()),
                     }
                 }
             }
@@ -459,10 +1419,11 @@ impl Lvs {
     #[instrument(level = "debug", err)]
     pub async fn destroy(self) -> Result<(), Error> {
         let pool = self.name().to_string();
+        let _busy = BusyPool::new(&pool);
         let (s, r) = pair::<i32>();
 
         // when destroying a pool unshare all volumes
-        self.unshare_all().await;
+        self.unload_shares().await;
 
         let base_bdev = self.base_bdev();
 
@@ -482,6 +1443,11 @@ impl Lvs {
             })?;
 
         info!("pool {} destroyed successfully", pool);
+        events::publish(Event::PoolStateChanged {
+            name: pool.clone(),
+            old: PoolState::Online,
+            new: PoolState::Absent,
+        });
 
         bdev_destroy(&base_bdev.bdev_uri().unwrap())
             .await
@@ -506,13 +1472,36 @@ impl Lvs {
                                 .iter()
                                 .any(|a| a.contains(&pool_name))
                     })
-                    .map(|b| Lvol::try_from(b).unwrap()),
+                    .map(|b| Lvol::try_from(b).unwrap())
+                    .filter(|l| !l.is_reserved()),
             )
         } else {
             None
         }
     }
 
+    /// Returns a stream over the lvols of this pool, which are walked one at
+    /// a time as the stream is polled. The pool is looked up again for each
+    /// lvol, so the stream ends once the pool is gone, and like with
+    /// `stream()` it ends early should the lvol yielded last be destroyed.
+    pub fn lvol_stream(&self) -> impl Stream<Item = Lvol> {
+        let pool = self.name().to_string();
+        stream::unfold(None, move |last: Option<String>| {
+            let pool = pool.clone();
+            async move {
+                let mut lvols = Lvs::lookup(&pool)?.lvols()?;
+                let next = match &last {
+                    None => lvols.next(),
+                    Some(last) => {
+                        lvols.skip_while(|l| l.name() != *last).nth(1)
+                    }
+                }?;
+                let name = next.name();
+                Some((next, Some(name)))
+            }
+        })
+    }
+
     #[instrument(level = "debug", err)]
     /// create a new lvol on this pool
     pub async fn create_lvol(
@@ -535,6 +1524,20 @@ impl Lvs {
             });
         };
 
+        let journal = self.journal();
+        let seq = match &journal {
+            Some(journal) => Some(
+                journal
+                    .begin(JournalOp::CreateLvol {
+                        name: name.to_string(),
+                        size,
+                        thin,
+                    })
+                    .await?,
+            ),
+            None => None,
+        };
+
         let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
 
         let cname = name.into_cstring();
@@ -561,9 +1564,71 @@ impl Lvs {
                 source: e,
                 name: name.to_string(),
             })
-            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()))?;
+            .map(|lvol| Lvol(NonNull::new(lvol).unwrap()));
+
+        // a failed creation leaves nothing behind to recover either
+        if let (Some(journal), Some(seq)) = (journal, seq) {
+            journal.commit(seq).await?;
+        }
+        let lvol = lvol?;
 
         info!("created {}", lvol);
+        lvol.publish_created();
+        Ok(lvol)
+    }
+
+    /// Create a new lvol whose data is compressed with the given algorithm,
+    /// it is thin provisioned so that the space saved is not allocated. The
+    /// lvol is sized for the data of the given size and the table of its
+    /// chunks, and its data is read and written through the compress bdev
+    /// on top of it.
+    pub async fn create_compressed_lvol(
+        &self,
+        name: &str,
+        size: u64,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Lvol, Error> {
+        let block_len = self.base_bdev().block_len();
+        let backing_size = CompressBdev::backing_size(size, block_len);
+        let lvol = self.create_lvol(name, backing_size, true).await?;
+        let result = match lvol.set_xattr(PropValue::Compressed(true)).await {
+            Ok(_) => {
+                lvol.set_xattr(PropValue::CompressionAlgorithm(algorithm))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            let _ = lvol.destroy().await;
+            return Err(e);
+        }
+        info!("{} is compressed with {}", lvol, algorithm);
+        Ok(lvol)
+    }
+
+    /// Create a new lvol whose data is deduplicated in the dedup store of
+    /// the pool. It is thin provisioned, as it only holds the map of its
+    /// blocks to the blocks of the store, and its data is read and written
+    /// through the dedup bdev on top of it.
+    pub async fn create_dedup_lvol(
+        &self,
+        name: &str,
+        size: u64,
+    ) -> Result<Lvol, Error> {
+        if self.dedup_store().is_none() {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("pool {} has no dedup store", self.name()),
+            });
+        }
+        let block_len = self.base_bdev().block_len();
+        let backing_size = DedupBdev::backing_size(size, block_len);
+        let lvol = self.create_lvol(name, backing_size, true).await?;
+        if let Err(e) = lvol.set_xattr(PropValue::Dedup(true)).await {
+            let _ = lvol.destroy().await;
+            return Err(e);
+        }
+        info!("{} is deduplicated", lvol);
         Ok(lvol)
     }
 }
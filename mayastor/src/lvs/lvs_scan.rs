@@ -0,0 +1,402 @@
+//!
+//! Background scanning of pools for bitrot. The scanner of a pool reads
+//! back every block of all its lvols, so that blocks which have gone bad are
+//! found before an application reads them. Lvols with checksums are read
+//! through their PI bdev while they are shared, which verifies the data
+//! against its checksums, other lvols only detect blocks the device fails to
+//! read. Blocks of thin provisioned lvols which have never been written are
+//! read too, they are served without touching the device.
+//!
+//! The scan of a pool runs as a low priority task on the master core: it is
+//! paced at the rate given when it is started and takes its share of the
+//! background IO budget as a scrub. A full pass over all lvols is repeated
+//! after the given interval, and a scan can be paused and resumed at any
+//! time. Every finding is logged, recorded in the status of the scan and
+//! published on the message bus for the control plane.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use futures_timer::Delay;
+use nix::errno::Errno;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use mbus_api::{v0::PoolScanEvent, Message};
+
+use crate::{
+    bdev::pi_lookup,
+    core::{
+        BackgroundClass,
+        BackgroundScheduler,
+        BdevHandle,
+        CoreError,
+        MayastorEnvironment,
+        Reactors,
+    },
+    lvs::{Error, Lvs},
+};
+
+/// size of the chunks the lvols are read in
+const SCAN_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// how often a paused scan checks whether it has been resumed
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// the number of findings kept in the status of a scan, older ones are
+/// dropped
+const MAX_FINDINGS: usize = 1024;
+
+/// Options of the scan of a pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanOpts {
+    /// time between the start of two full passes over the pool
+    pub interval: Duration,
+    /// maximum rate at which the lvols are read in bytes/s
+    pub rate: u64,
+}
+
+impl Default for ScanOpts {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(7 * 24 * 60 * 60),
+            rate: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Blocks of an lvol found to be bad by a scan.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanFinding {
+    /// name of the lvol
+    pub lvol: String,
+    /// offset of the blocks in the lvol in bytes
+    pub offset: u64,
+    /// length of the blocks in bytes
+    pub len: u64,
+    /// what failed
+    pub error: String,
+}
+
+/// Status of the scan of a pool.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanStatus {
+    /// whether the scan is paused
+    pub paused: bool,
+    /// when the last full pass over the pool finished
+    pub last_full_scan: Option<SystemTime>,
+    /// the number of full passes over the pool
+    pub passes: u64,
+    /// the number of bytes read in the current pass
+    pub scanned: u64,
+    /// the latest findings of all passes
+    pub findings: Vec<ScanFinding>,
+}
+
+struct PoolScan {
+    /// identifies the task of the scan, the task of a scan which has been
+    /// stopped exits as soon as it notices, also if the pool is scanned
+    /// again meanwhile
+    id: u64,
+    opts: ScanOpts,
+    status: ScanStatus,
+}
+
+/// the scans of all pools by the name of the pool
+static SCANS: Lazy<Mutex<HashMap<String, PoolScan>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Scans pools for bitrot in the background.
+pub struct PoolScanner;
+
+impl PoolScanner {
+    /// Start scanning the given pool, the first pass starts right away.
+    pub fn start(pool: &str, opts: ScanOpts) -> Result<(), Error> {
+        if Lvs::lookup(pool).is_none() {
+            return Err(Error::Invalid {
+                source: Errno::ENOENT,
+                msg: format!("pool {} not found", pool),
+            });
+        }
+        if opts.rate == 0 {
+            return Err(Error::Invalid {
+                source: Errno::EINVAL,
+                msg: format!("scan rate of pool {} must not be 0", pool),
+            });
+        }
+
+        let mut scans = SCANS.lock().unwrap();
+        if scans.contains_key(pool) {
+            return Err(Error::Invalid {
+                source: Errno::EEXIST,
+                msg: format!("pool {} is already being scanned", pool),
+            });
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        scans.insert(
+            pool.to_string(),
+            PoolScan {
+                id,
+                opts,
+                status: ScanStatus::default(),
+            },
+        );
+
+        info!("{}: started scanning with {:?}", pool, opts);
+        Reactors::master().send_future(scan_pool(pool.to_string(), id));
+        Ok(())
+    }
+
+    /// Stop scanning the given pool, returns false if it is not scanned.
+    pub fn stop(pool: &str) -> bool {
+        if SCANS.lock().unwrap().remove(pool).is_some() {
+            info!("{}: stopped scanning", pool);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Pause the scan of the given pool until it is resumed, returns false
+    /// if the pool is not scanned.
+    pub fn pause(pool: &str) -> bool {
+        Self::set_paused(pool, true)
+    }
+
+    /// Resume the scan of the given pool where it has been paused, returns
+    /// false if the pool is not scanned.
+    pub fn resume(pool: &str) -> bool {
+        Self::set_paused(pool, false)
+    }
+
+    fn set_paused(pool: &str, paused: bool) -> bool {
+        match SCANS.lock().unwrap().get_mut(pool) {
+            Some(scan) => {
+                scan.status.paused = paused;
+                info!("{}: scan paused: {}", pool, paused);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The status of the scan of the given pool, None if it is not scanned.
+    pub fn status(pool: &str) -> Option<ScanStatus> {
+        SCANS.lock().unwrap().get(pool).map(|s| s.status.clone())
+    }
+}
+
+/// Update the scan of the pool with the given id, None if it has been
+/// stopped.
+fn with_scan<T>(
+    pool: &str,
+    id: u64,
+    f: impl FnOnce(&mut PoolScan) -> T,
+) -> Option<T> {
+    match SCANS.lock().unwrap().get_mut(pool) {
+        Some(scan) if scan.id == id => Some(f(scan)),
+        _ => None,
+    }
+}
+
+/// Wait while the scan of the pool is paused, returns false if it has been
+/// stopped.
+async fn wait_resumed(pool: &str, id: u64) -> bool {
+    loop {
+        match with_scan(pool, id, |s| s.status.paused) {
+            Some(false) => return true,
+            Some(true) => (),
+            None => return false,
+        }
+        Delay::new(PAUSE_POLL).await;
+    }
+}
+
+/// Scan all lvols of the pool, pass after pass, until the scan is stopped
+/// or the pool is gone.
+async fn scan_pool(pool: String, id: u64) {
+    loop {
+        let start = Instant::now();
+        let lvols = match Lvs::lookup(&pool) {
+            Some(lvs) => lvs
+                .lvols()
+                .map(|lvols| lvols.map(|l| l.name()).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            None => {
+                if with_scan(&pool, id, |_| ()).is_some() {
+                    PoolScanner::stop(&pool);
+                }
+                info!("{}: pool gone, stopped scanning", pool);
+                return;
+            }
+        };
+
+        for lvol in lvols {
+            if !scan_lvol(&pool, id, &lvol).await {
+                return;
+            }
+        }
+
+        let opts = match with_scan(&pool, id, |scan| {
+            scan.status.last_full_scan = Some(SystemTime::now());
+            scan.status.passes += 1;
+            info!(
+                "{}: full scan {} finished in {:?}, {} bytes read",
+                pool,
+                scan.status.passes,
+                start.elapsed(),
+                scan.status.scanned
+            );
+            scan.status.scanned = 0;
+            scan.opts
+        }) {
+            Some(opts) => opts,
+            None => return,
+        };
+
+        // wait for the next pass in steps, so that stopping the scan does
+        // not have to wait for it
+        let next = start + opts.interval;
+        while Instant::now() < next {
+            if with_scan(&pool, id, |_| ()).is_none() {
+                return;
+            }
+            Delay::new(std::cmp::min(next - Instant::now(), PAUSE_POLL)).await;
+        }
+    }
+}
+
+/// Read back all blocks of the lvol, returns false if the scan has been
+/// stopped.
+async fn scan_lvol(pool: &str, id: u64, lvol: &str) -> bool {
+    // lvols with checksums are read through their PI bdev when there is one
+    let pi_name = format!("{}-pi", lvol);
+    let name = match pi_lookup(&pi_name) {
+        Some(_) => pi_name,
+        None => lvol.to_string(),
+    };
+
+    let mut offset = 0;
+    loop {
+        if !wait_resumed(pool, id).await {
+            return false;
+        }
+        let opts = match with_scan(pool, id, |s| s.opts) {
+            Some(opts) => opts,
+            None => return false,
+        };
+
+        // the lvol is opened for every chunk, so that the scan does not keep
+        // it from being destroyed
+        let hdl = match BdevHandle::open(&name, false, false) {
+            Ok(hdl) => hdl,
+            Err(_) => {
+                debug!("{}: {} gone, skipping it", pool, name);
+                return true;
+            }
+        };
+        let bdev = hdl.get_bdev();
+        let block_len = u64::from(bdev.block_len());
+        // the blocks of a PI bdev may carry their protection information
+        let data_len = block_len - u64::from(bdev.md_len());
+        let size = bdev.size_in_bytes();
+        if offset >= size {
+            return true;
+        }
+        let chunk = std::cmp::max(SCAN_CHUNK_SIZE / block_len, 1) * block_len;
+        let len = std::cmp::min(chunk, size - offset);
+
+        if let Some(wait) =
+            BackgroundScheduler::reserve(BackgroundClass::Scrub, len)
+        {
+            Delay::new(wait).await;
+        }
+
+        let start = Instant::now();
+        let result = match hdl.dma_malloc(len) {
+            Ok(mut buf) => hdl.read_at(offset, &mut buf).await.map(|_| ()),
+            Err(_) => {
+                error!("{}: failed to allocate scan buffer", pool);
+                Ok(())
+            }
+        };
+        drop(hdl);
+
+        if let Err(error) = result {
+            let (data_offset, data_len) =
+                (offset / block_len * data_len, len / block_len * data_len);
+            finding(pool, id, lvol, data_offset, data_len, error).await;
+        }
+        with_scan(pool, id, |s| s.status.scanned += len);
+        offset += len;
+
+        // pace the scan at its rate
+        let duration = Duration::from_nanos(len * 1_000_000_000 / opts.rate);
+        let elapsed = start.elapsed();
+        if duration > elapsed {
+            Delay::new(duration - elapsed).await;
+        }
+    }
+}
+
+/// Record the failure to read back blocks of the lvol, and publish it for
+/// the control plane.
+async fn finding(
+    pool: &str,
+    id: u64,
+    lvol: &str,
+    offset: u64,
+    len: u64,
+    error: CoreError,
+) {
+    let error = match error {
+        CoreError::GuardCheckFailed {
+            ..
+        } => "data does not match its checksums".to_string(),
+        error => error.to_string(),
+    };
+    warn!(
+        "{}: scan of {} failed at offset {} length {}: {}",
+        pool, lvol, offset, len, error
+    );
+
+    let finding = ScanFinding {
+        lvol: lvol.to_string(),
+        offset,
+        len,
+        error,
+    };
+    let recorded = with_scan(pool, id, |scan| {
+        if scan.status.findings.len() == MAX_FINDINGS {
+            scan.status.findings.remove(0);
+        }
+        scan.status.findings.push(finding.clone());
+    });
+    if recorded.is_none() {
+        return;
+    }
+
+    let args = MayastorEnvironment::global_or_default();
+    if args.mbus_endpoint.is_none() {
+        return;
+    }
+    let event = PoolScanEvent {
+        node: args.node_name.into(),
+        id: pool.into(),
+        replica: finding.lvol.into(),
+        offset,
+        len,
+        error: finding.error,
+    };
+    if let Err(error) = event.publish().await {
+        error!("{}: failed to publish {:?}: {:?}", pool, event, error);
+    }
+}
@@ -1,7 +1,11 @@
 pub use error::Error;
-pub use lvol::{Lvol, PropName, PropValue};
-pub use lvs_pool::Lvs;
+pub use lvol::{Lvol, LvolStats, PropName, PropValue, ShareGuard};
+pub use lvs_journal::{Journal, JournalOp};
+pub use lvs_pool::{Lvs, LvolSpec, PoolStats, POOL_FORMAT_VERSION};
+pub use lvs_scan::{PoolScanner, ScanFinding, ScanOpts, ScanStatus};
 
 mod error;
 mod lvol;
+mod lvs_journal;
 mod lvs_pool;
+mod lvs_scan;
@@ -1,10 +1,16 @@
-use std::{convert::TryFrom, num::ParseIntError, str::ParseBoolError};
+use std::{
+    convert::TryFrom,
+    num::ParseIntError,
+    str::ParseBoolError,
+    time::Duration,
+};
 
 use crate::{bdev::Uri, core::Bdev};
 use futures::channel::oneshot::Canceled;
+use futures_timer::Delay;
 use nix::errno::Errno;
 use snafu::Snafu;
-use tracing::instrument;
+use tracing::{debug, instrument};
 use url::ParseError;
 
 // parse URI and bdev create/destroy errors common for all types of bdevs
@@ -66,6 +72,48 @@ pub enum NexusBdevError {
     DestroyBdev { source: Errno, name: String },
     #[snafu(display("Command canceled for bdev {}", name))]
     CancelBdev { source: Canceled, name: String },
+    #[snafu(display(
+        "Failed to create bdev from URI \"{}\" after {} attempts",
+        uri,
+        attempts
+    ))]
+    CreateBdevAttempts {
+        source: Box<NexusBdevError>,
+        uri: String,
+        attempts: u32,
+    },
+}
+
+impl NexusBdevError {
+    /// Whether the creation of the bdev may succeed if tried again, i.e.
+    /// it failed as the device is not reachable (yet), e.g. a remote target
+    /// which is still coming up or not yet exporting, but not because of the
+    /// URI, nor if the bdev exists already.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::InvalidParams {
+                source,
+                ..
+            }
+            | Self::CreateBdev {
+                source,
+                ..
+            } => matches!(
+                source,
+                Errno::ENODEV
+                    | Errno::ENXIO
+                    | Errno::EIO
+                    | Errno::EAGAIN
+                    | Errno::EBUSY
+                    | Errno::ECONNREFUSED
+                    | Errno::ECONNRESET
+                    | Errno::ETIMEDOUT
+                    | Errno::EHOSTUNREACH
+                    | Errno::ENETUNREACH
+            ),
+            _ => false,
+        }
+    }
 }
 
 /// Parse URI and create bdev described in the URI.
@@ -75,6 +123,43 @@ pub async fn bdev_create(uri: &str) -> Result<String, NexusBdevError> {
     Uri::parse(uri)?.create().await
 }
 
+/// Parse URI and create bdev described in the URI as bdev_create does,
+/// trying again up to `attempts` times in all should the creation fail
+/// transiently (see `NexusBdevError::is_transient`), waiting for `backoff`
+/// before the second attempt and twice as long before each one after it.
+/// Other errors fail the creation straight away. Errors are returned as
+/// CreateBdevAttempts, with the number of attempts made and the last error.
+#[instrument]
+pub async fn bdev_create_with_retry(
+    uri: &str,
+    attempts: u32,
+    backoff: Duration,
+) -> Result<String, NexusBdevError> {
+    let mut attempt = 1;
+    let mut delay = backoff;
+    loop {
+        match bdev_create(uri).await {
+            Ok(name) => return Ok(name),
+            Err(error) if error.is_transient() && attempt < attempts => {
+                debug!(
+                    "attempt {} to create bdev {} failed: {}, retrying in {:?}",
+                    attempt, uri, error, delay
+                );
+                Delay::new(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(error) => {
+                return Err(NexusBdevError::CreateBdevAttempts {
+                    source: Box::new(error),
+                    uri: uri.to_string(),
+                    attempts: attempt,
+                })
+            }
+        }
+    }
+}
+
 /// Parse URI and destroy bdev described in the URI.
 #[instrument]
 pub async fn bdev_destroy(uri: &str) -> Result<(), NexusBdevError> {
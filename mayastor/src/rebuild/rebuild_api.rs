@@ -1,6 +1,6 @@
 #![warn(missing_docs)]
 
-use std::fmt;
+use std::{fmt, time::Instant};
 
 use crossbeam::channel::{Receiver, Sender};
 use futures::channel::oneshot;
@@ -10,6 +10,7 @@ use crate::{
     bdev::VerboseError,
     core::{CoreError, Descriptor, DmaError},
     nexus_uri::NexusBdevError,
+    subsys::RebuildOpts,
 };
 
 use super::rebuild_impl::*;
@@ -123,6 +124,14 @@ pub struct RebuildJob {
     pub(super) next: u64,
     pub(super) segment_size_blks: u64,
     pub(super) task_pool: RebuildTasks,
+    /// time until which the job has used up its share of the rebuild rate
+    /// limit
+    pub(super) throttle_until: Instant,
+    /// number of blocks from the start of the range which have been rebuilt
+    /// and flushed to the destination
+    pub(super) checkpoint: u64,
+    /// when the checkpoint was last saved
+    pub(super) checkpoint_time: Instant,
     pub(super) notify_fn: fn(String, String) -> (),
     /// channel used to signal rebuild update
     pub notify_chan: (Sender<RebuildState>, Receiver<RebuildState>),
@@ -232,6 +241,16 @@ impl RebuildJob {
         self.states.current
     }
 
+    /// Number of blocks from the start of the range which are durably
+    /// rebuilt, if any, the rebuild resumes from there after a restart
+    pub fn checkpoint(&self) -> Option<u64> {
+        if self.checkpoint > 0 {
+            Some(self.checkpoint)
+        } else {
+            None
+        }
+    }
+
     /// Error description
     pub fn error_desc(&self) -> String {
         match self.error.as_ref() {
@@ -240,6 +259,18 @@ impl RebuildJob {
         }
     }
 
+    /// The rebuild options currently in use
+    pub fn opts() -> RebuildOpts {
+        REBUILD_OPTS.lock().unwrap().clone()
+    }
+
+    /// Change the rebuild options at runtime, running jobs copy their next
+    /// segment at the new rate
+    pub fn set_opts(opts: RebuildOpts) {
+        info!("rebuild options set to {:?}", opts);
+        *REBUILD_OPTS.lock().unwrap() = opts;
+    }
+
     /// ClientOperations trait
     /// todo: nexus should use this for all interaction with the job
     pub fn as_client(&mut self) -> &mut impl ClientOperations {
@@ -1,26 +1,55 @@
 #![warn(missing_docs)]
 #![allow(clippy::unknown_clippy_lints)]
 
-use std::{cell::UnsafeCell, collections::HashMap};
+use std::{
+    cell::UnsafeCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use crossbeam::channel::unbounded;
 use futures::{
     channel::{mpsc, oneshot},
     StreamExt,
 };
-use once_cell::sync::OnceCell;
+use futures_timer::Delay;
+use once_cell::sync::{Lazy, OnceCell};
 use snafu::ResultExt;
 
 use spdk_sys::{spdk_get_thread, SPDK_BDEV_LARGE_BUF_MAX_SIZE};
 
 use crate::{
-    bdev::VerboseError,
-    core::{Bdev, BdevHandle, DmaBuf, RangeContext, Reactors},
+    bdev::{
+        nexus::{
+            nexus_child::NexusChild,
+            nexus_child_status_config::ChildStatusConfig,
+        },
+        VerboseError,
+    },
+    core::{
+        BackgroundClass,
+        BackgroundScheduler,
+        Bdev,
+        BdevHandle,
+        DmaBuf,
+        RangeContext,
+        Reactors,
+    },
     nexus_uri::bdev_get_name,
+    subsys::{Config, RebuildOpts},
 };
 
 use super::rebuild_api::*;
 
+/// The rebuild options in use, taken from the config at startup and
+/// changed at runtime, running jobs pick them up with their next segment.
+pub(super) static REBUILD_OPTS: Lazy<Mutex<RebuildOpts>> =
+    Lazy::new(|| Mutex::new(Config::get().rebuild_opts.clone()));
+
 /// Global list of rebuild jobs using a static OnceCell
 pub(super) struct RebuildInstances {
     inner: UnsafeCell<HashMap<String, Box<RebuildJob>>>,
@@ -47,11 +76,17 @@ const SEGMENT_TASKS: usize = 16;
 /// Size of each segment used by the copy task
 pub const SEGMENT_SIZE: u64 = SPDK_BDEV_LARGE_BUF_MAX_SIZE as u64;
 
+/// Number of rebuild jobs currently copying segments, amongst which the
+/// rebuild rate limit is shared
+static RUNNING_JOBS: AtomicUsize = AtomicUsize::new(0);
+
 /// Each rebuild task needs a unique buffer to read/write from source to target
 /// A mpsc channel is used to communicate with the management task
 #[derive(Debug)]
 struct RebuildTask {
     buffer: DmaBuf,
+    /// block of the segment being copied, if any
+    blk: Option<u64>,
     sender: mpsc::Sender<TaskResult>,
     error: Option<TaskResult>,
 }
@@ -143,6 +178,7 @@ impl RebuildJob {
                 .context(NoCopyBuffer {})?;
             tasks.tasks.push(RebuildTask {
                 buffer: copy_buffer,
+                blk: None,
                 sender: tasks.channel.0.clone(),
                 error: None,
             });
@@ -159,16 +195,44 @@ impl RebuildJob {
                 bdev: nexus.to_string(),
             })?;
 
+        // resume from the checkpoint of a rebuild interrupted by a restart
+        let checkpoint = match ChildStatusConfig::take_checkpoint(&destination)
+        {
+            Some(blocks)
+                if blocks < range.end - range.start
+                    && blocks % segment_size_blks == 0 =>
+            {
+                info!(
+                    "Resuming rebuild of {} from block {}",
+                    destination,
+                    range.start + blocks
+                );
+                tasks.segments_done = blocks / segment_size_blks;
+                blocks
+            }
+            Some(blocks) => {
+                warn!(
+                    "Ignoring invalid rebuild checkpoint {} of {}",
+                    blocks, destination
+                );
+                0
+            }
+            None => 0,
+        };
+
         Ok(Self {
             nexus,
             nexus_descriptor,
             source,
             destination,
-            next: range.start,
+            next: range.start + checkpoint,
             range,
             block_size,
             segment_size_blks,
             task_pool: tasks,
+            throttle_until: Instant::now(),
+            checkpoint,
+            checkpoint_time: Instant::now(),
             notify_fn,
             notify_chan: unbounded::<RebuildState>(),
             states: Default::default(),
@@ -181,11 +245,13 @@ impl RebuildJob {
     // awaits each completion. When any task completes it kicks off another
     // until the bdev is fully rebuilt
     async fn run(&mut self) {
+        RUNNING_JOBS.fetch_add(1, Ordering::SeqCst);
         self.start_all_tasks();
         while self.task_pool.active > 0 {
             match self.await_one_task().await {
                 Some(r) => match r.error {
                     None => {
+                        self.save_checkpoint().await;
                         match self.states.pending {
                             None | Some(RebuildState::Running) => {
                                 self.start_task_by_id(r.id);
@@ -214,6 +280,7 @@ impl RebuildJob {
                 }
             }
         }
+        RUNNING_JOBS.fetch_sub(1, Ordering::SeqCst);
         self.reconcile();
     }
 
@@ -226,6 +293,91 @@ impl RebuildJob {
         self.segment_size_blks
     }
 
+    /// Periodically save the number of blocks rebuilt so far, so that the
+    /// rebuild resumes from there after a restart. Segments complete out of
+    /// order, so only the blocks below the first segment still being copied
+    /// count, and these are flushed before they are saved so that the
+    /// checkpoint is never ahead of the data on the destination.
+    async fn save_checkpoint(&mut self) {
+        let interval = REBUILD_OPTS.lock().unwrap().checkpoint_interval_ms;
+        if interval == 0
+            || self.checkpoint_time.elapsed() < Duration::from_millis(interval)
+        {
+            return;
+        }
+        self.checkpoint_time = Instant::now();
+
+        let blocks = self
+            .task_pool
+            .tasks
+            .iter()
+            .filter_map(|t| t.blk)
+            .min()
+            .unwrap_or(self.next)
+            - self.range.start;
+        if blocks <= self.checkpoint {
+            return;
+        }
+
+        let hdl = match RebuildJob::open_handle(&self.destination, true, false)
+        {
+            Ok(hdl) => hdl,
+            Err(e) => {
+                return warn!(
+                    "Failed to save rebuild checkpoint of {}: {}",
+                    self.destination,
+                    e.verbose()
+                );
+            }
+        };
+        if let Err(e) = hdl.flush().await {
+            return warn!(
+                "Failed to flush {} for its rebuild checkpoint: {}",
+                self.destination,
+                e.verbose()
+            );
+        }
+
+        debug!(
+            "Rebuild job {}: checkpoint at block {}",
+            self.destination,
+            self.range.start + blocks
+        );
+        self.checkpoint = blocks;
+        NexusChild::save_state_change();
+    }
+
+    /// Reserve the bandwidth needed to copy `len` blocks out of the share of
+    /// the rebuild rate of this job, and return how long to wait before
+    /// copying them. The rate of the rebuilds is their share of the
+    /// background IO budget, further capped by the rebuild rate limit, and
+    /// it is shared equally between all running jobs.
+    fn throttle(&mut self, len: u64) -> Option<Duration> {
+        let limit = REBUILD_OPTS.lock().unwrap().rate_limit_mbps << 20;
+        let rate = match BackgroundScheduler::rate(BackgroundClass::Rebuild) {
+            Some(share) if limit == 0 || share < limit => share,
+            _ => limit,
+        };
+        if rate == 0 {
+            return None;
+        }
+
+        let jobs = std::cmp::max(RUNNING_JOBS.load(Ordering::SeqCst), 1);
+        let cost = Duration::from_nanos(
+            len * self.block_size * jobs as u64 * 1_000_000_000 / rate,
+        );
+
+        let now = Instant::now();
+        let start = std::cmp::max(now, self.throttle_until);
+        self.throttle_until = start + cost;
+
+        if start > now {
+            Some(start - now)
+        } else {
+            None
+        }
+    }
+
     /// Copies one segment worth of data from source into destination. During
     /// this time the LBA range being copied is locked so that there cannot be
     /// front end I/O to the same LBA range.
@@ -245,6 +397,11 @@ impl RebuildJob {
         blk: u64,
     ) -> Result<(), RebuildError> {
         let len = self.get_segment_size_blks(blk);
+
+        if let Some(wait) = self.throttle(len) {
+            Delay::new(wait).await;
+        }
+
         // The nexus children have metadata and data partitions, whereas the
         // nexus has a data partition only. Because we are locking the range on
         // the nexus, we need to calculate the offset from the start of the data
@@ -590,6 +747,7 @@ impl RebuildJob {
         self.task_pool.channel.1.next().await.map(|f| {
             self.task_pool.active -= 1;
             if f.error.is_none() {
+                self.task_pool.tasks[f.id].blk = None;
                 self.task_pool.segments_done += 1;
             } else {
                 self.task_pool.tasks[f.id].error = Some(f.clone());
@@ -618,7 +776,7 @@ impl RebuildJob {
 
     /// Sends one segment worth of data in a reactor future and notifies the
     /// management channel. Returns the next segment offset to rebuild, if any
-    fn send_segment_task(&self, id: usize) -> Option<u64> {
+    fn send_segment_task(&mut self, id: usize) -> Option<u64> {
         if self.next >= self.range.end {
             None
         } else {
@@ -628,6 +786,7 @@ impl RebuildJob {
                 self.range.end,
             );
             let name = self.destination.clone();
+            self.task_pool.tasks[id].blk = Some(blk);
 
             Reactors::current().send_future(async move {
                 let job = Self::lookup(&name).unwrap();
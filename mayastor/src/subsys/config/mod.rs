@@ -38,14 +38,17 @@ use crate::{
         nexus_create,
         VerboseError,
     },
-    core::{Bdev, Cores, Reactor, Share},
+    core::{BackgroundScheduler, Bdev, Cores, Reactor, Share},
     jsonrpc::{jsonrpc_register, Code, RpcErrorCode},
+    logger,
     lvs::Lvs,
     nexus_uri::bdev_create,
     pool::PoolsIter,
+    rebuild::RebuildJob,
     replica::{ReplicaIter, ShareType},
     subsys::{
         config::opts::{
+            BackgroundOpts,
             BdevOpts,
             ErrStoreOpts,
             GetOpts,
@@ -54,17 +57,38 @@ use crate::{
             NvmeBdevOpts,
             NvmfTgtConfig,
             PosixSocketOpts,
+            RebuildOpts,
         },
         NvmfSubsystem,
     },
 };
 
 #[derive(Debug, Clone, Snafu)]
-pub enum Error {}
+pub enum Error {
+    #[snafu(display("Invalid background IO options: {}", reason))]
+    InvalidBackgroundOpts { reason: String },
+    #[snafu(display("Invalid log level {}: {}", level, reason))]
+    InvalidLogLevel { level: String, reason: String },
+    #[snafu(display("No config file to reload"))]
+    NoConfigFile {},
+    #[snafu(display("Failed to reload config file {}: {}", file, reason))]
+    ReloadFailed { file: String, reason: String },
+}
 
 impl RpcErrorCode for Error {
     fn rpc_error_code(&self) -> Code {
-        Code::InternalError
+        match self {
+            Error::InvalidBackgroundOpts {
+                ..
+            } => Code::InvalidParams,
+            Error::InvalidLogLevel {
+                ..
+            } => Code::InvalidParams,
+            Error::NoConfigFile {} => Code::NotFound,
+            Error::ReloadFailed {
+                ..
+            } => Code::InternalError,
+        }
     }
 }
 pub(crate) mod opts;
@@ -102,6 +126,32 @@ impl ConfigSubsystem {
             f.boxed_local()
         });
 
+        // tune the background IO scheduling at runtime, options which are
+        // not given take their default value
+        jsonrpc_register::<BackgroundOpts, _, _, Error>(
+            "mayastor_set_background_opts",
+            |opts| {
+                let f = async move {
+                    BackgroundScheduler::set_opts(opts.clone()).map_err(
+                        |reason| Error::InvalidBackgroundOpts {
+                            reason,
+                        },
+                    )?;
+                    Ok(opts)
+                };
+
+                f.boxed_local()
+            },
+        );
+
+        // re-read the config file and apply the settings which can change at
+        // runtime, the same as on SIGHUP
+        jsonrpc_register::<(), _, _, Error>("mayastor_config_reload", |_| {
+            let f = async move { Config::get().reload() };
+
+            f.boxed_local()
+        });
+
         unsafe { spdk_subsystem_init_next(0) };
     }
 
@@ -138,6 +188,15 @@ impl ConfigSubsystem {
     }
 }
 
+/// The outcome of reloading the config file.
+#[derive(Debug, Default, Serialize)]
+pub struct ReloadReport {
+    /// the settings which changed and took effect
+    pub applied: Vec<String>,
+    /// the settings which changed but only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
 /// Main config structure of Mayastor. This structure can be persisted to disk.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
@@ -157,6 +216,13 @@ pub struct Config {
     pub nexus_opts: NexusOpts,
     /// error store opts
     pub err_store_opts: ErrStoreOpts,
+    /// rebuild options
+    pub rebuild_opts: RebuildOpts,
+    /// background IO scheduling options
+    pub background_opts: BackgroundOpts,
+    /// filter of the log messages with the syntax of RUST_LOG, overrides the
+    /// log level given on the command line
+    pub log_level: Option<String>,
     /// list of pools to create on load
     pub pools: Option<Vec<Pool>>,
     ///
@@ -183,6 +249,9 @@ impl Default for Config {
             bdev_opts: Default::default(),
             nexus_opts: Default::default(),
             err_store_opts: Default::default(),
+            rebuild_opts: Default::default(),
+            background_opts: Default::default(),
+            log_level: None,
             base_bdevs: None,
             nexus_bdevs: None,
             pools: None,
@@ -260,6 +329,9 @@ impl Config {
             pools: None,
             implicit_share_base: self.implicit_share_base,
             err_store_opts: self.err_store_opts.get(),
+            rebuild_opts: RebuildJob::opts(),
+            background_opts: BackgroundScheduler::opts(),
+            log_level: logger::filter().or_else(|| self.log_level.clone()),
             sync_disable: self.sync_disable,
             socket_opts: self.socket_opts.get(),
         };
@@ -302,6 +374,13 @@ impl Config {
                 Pool {
                     name: p.get_name().into(),
                     disks: vec![base.bdev_uri().unwrap_or_else(|| base.name())],
+                    integrity_reserve: Lvs::lookup(p.get_name())
+                        .map_or(0, |l| l.integrity_reserve_percent()),
+                    journal: Lvs::lookup(p.get_name())
+                        .map_or(false, |l| l.journal().is_some()),
+                    dedup: Lvs::lookup(p.get_name())
+                        .and_then(|l| l.dedup_store())
+                        .map(|s| s.hash().to_string()),
                     replicas: ReplicaIter::new()
                         .map(|p| Replica {
                             name: p.get_uuid().to_string(),
@@ -317,6 +396,85 @@ impl Config {
         current
     }
 
+    /// Re-read the config file and apply the rebuild, background IO and log
+    /// settings, which take effect right away. The other settings are only
+    /// applied at startup, those which differ from the ones mayastor was
+    /// started with are reported as requiring a restart. If a setting is
+    /// invalid, the settings applied before it stay in effect.
+    pub fn reload(&self) -> Result<ReloadReport, Error> {
+        let file = self.source.clone().ok_or(Error::NoConfigFile {})?;
+        info!("reloading YAML config file {}", file);
+        // reading a missing file yields the default config
+        if !Path::new(&file).exists() {
+            return Err(Error::ReloadFailed {
+                file,
+                reason: "the file does not exist".into(),
+            });
+        }
+        let new = Config::read(&file).map_err(|e| Error::ReloadFailed {
+            file: file.clone(),
+            reason: e.to_string(),
+        })?;
+
+        let mut report = ReloadReport::default();
+
+        if new.background_opts != BackgroundScheduler::opts() {
+            BackgroundScheduler::set_opts(new.background_opts.clone())
+                .map_err(|reason| Error::InvalidBackgroundOpts {
+                    reason,
+                })?;
+            report.applied.push("background_opts".into());
+        }
+
+        if let Some(level) = &new.log_level {
+            if logger::filter().as_ref() != Some(level) {
+                logger::set_filter(level).map_err(|reason| {
+                    Error::InvalidLogLevel {
+                        level: level.clone(),
+                        reason,
+                    }
+                })?;
+                report.applied.push("log_level".into());
+            }
+        }
+
+        if new.rebuild_opts != RebuildJob::opts() {
+            RebuildJob::set_opts(new.rebuild_opts.clone());
+            report.applied.push("rebuild_opts".into());
+        }
+
+        let restart = [
+            (
+                "nvmf_tcp_tgt_conf",
+                new.nvmf_tcp_tgt_conf != self.nvmf_tcp_tgt_conf,
+            ),
+            ("iscsi_tgt_conf", new.iscsi_tgt_conf != self.iscsi_tgt_conf),
+            ("nvme_bdev_opts", new.nvme_bdev_opts != self.nvme_bdev_opts),
+            ("bdev_opts", new.bdev_opts != self.bdev_opts),
+            ("nexus_opts", new.nexus_opts != self.nexus_opts),
+            ("err_store_opts", new.err_store_opts != self.err_store_opts),
+            ("pools", new.pools != self.pools),
+            ("base_bdevs", new.base_bdevs != self.base_bdevs),
+            ("nexus_bdevs", new.nexus_bdevs != self.nexus_bdevs),
+            (
+                "implicit_share_base",
+                new.implicit_share_base != self.implicit_share_base,
+            ),
+            ("socket_opts", new.socket_opts != self.socket_opts),
+        ];
+        report.restart_required = restart
+            .iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        info!("reloaded config file {}: {:?}", file, report);
+        for name in &report.restart_required {
+            warn!("{} changed in {}, restart to apply it", name, file);
+        }
+        Ok(report)
+    }
+
     /// write the current pool configuration to disk
     pub fn write_pools<P>(&self, file: P) -> Result<(), std::io::Error>
     where
@@ -589,6 +747,16 @@ pub struct Pool {
     pub name: String,
     /// bdevs to create outside of the nexus control
     pub disks: Vec<String>,
+    /// percent of the pool reserved for integrity metadata when it is created
+    #[serde(default)]
+    pub integrity_reserve: u32,
+    /// journal the metadata operations of the pool when it is created
+    #[serde(default)]
+    pub journal: bool,
+    /// hash to deduplicate the blocks of the replicas of the pool by when it
+    /// is created, crc32c, xxhash64 or sha256, if they are to be
+    #[serde(default)]
+    pub dedup: Option<String>,
     /// list of replicas (not required, informational only)
     pub replicas: Vec<Replica>,
 }
@@ -596,9 +764,23 @@ pub struct Pool {
 /// Convert Pool into a gRPC request payload
 impl From<&Pool> for rpc::mayastor::CreatePoolRequest {
     fn from(o: &Pool) -> Self {
+        use rpc::mayastor::ChecksumAlgorithm;
+        // an unknown hash is refused when the pool is created
+        let dedup_hash = match o.dedup.as_deref() {
+            None | Some("crc32c") => ChecksumAlgorithm::ChecksumCrc32c as i32,
+            Some("xxhash64") => ChecksumAlgorithm::ChecksumXxhash64 as i32,
+            Some("sha256") => ChecksumAlgorithm::ChecksumSha256 as i32,
+            Some(_) => -1,
+        };
         Self {
             name: o.name.clone(),
             disks: o.disks.clone(),
+            integrity_reserve: o.integrity_reserve,
+            journal: o.journal,
+            dedup: o.dedup.is_some(),
+            dedup_hash,
+            // pools of the config are mostly imported, never clear them
+            wipe: rpc::mayastor::PoolWipe::WipeNone as i32,
         }
     }
 }
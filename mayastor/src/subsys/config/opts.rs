@@ -53,6 +53,9 @@ pub struct NexusOpts {
     pub iscsi_nexus_port: u16,
     /// Port for replica target portal
     pub iscsi_replica_port: u16,
+    /// how long a clean shutdown waits for the initiators of the nexuses
+    /// shared over nvmf to switch to another path, 0 does not wait
+    pub failover_grace_ms: u64,
 }
 
 /// Default nvmf port used for replicas.
@@ -75,6 +78,7 @@ impl Default for NexusOpts {
             iscsi_enable: true,
             iscsi_nexus_port: ISCSI_PORT_NEXUS,
             iscsi_replica_port: ISCSI_PORT_REPLICA,
+            failover_grace_ms: 1_000,
         }
     }
 }
@@ -94,6 +98,9 @@ pub struct NvmfTgtConfig {
     pub max_namespaces: u32,
     /// TCP transport options
     pub opts: NvmfTcpTransportOpts,
+    /// directory holding the reservations of the namespaces shared with
+    /// reservations, so they persist across restarts of the target
+    pub ptpl_dir: String,
 }
 
 impl From<NvmfTgtConfig> for Box<spdk_nvmf_target_opts> {
@@ -117,6 +124,7 @@ impl Default for NvmfTgtConfig {
             name: "mayastor_target".to_string(),
             max_namespaces: 110,
             opts: NvmfTcpTransportOpts::default(),
+            ptpl_dir: "/var/tmp/mayastor/ptpl".to_string(),
         }
     }
 }
@@ -329,9 +337,9 @@ impl From<&NvmeBdevOpts> for spdk_bdev_nvme_opts {
 #[serde(default, deny_unknown_fields)]
 pub struct BdevOpts {
     /// number of bdev IO structures in the shared mempool
-    bdev_io_pool_size: u32,
+    pub bdev_io_pool_size: u32,
     /// number of bdev IO structures cached per thread
-    bdev_io_cache_size: u32,
+    pub bdev_io_cache_size: u32,
 }
 
 impl GetOpts for BdevOpts {
@@ -607,6 +615,16 @@ pub struct ErrStoreOpts {
 
     /// the maximum number of IO attempts per IO
     pub max_io_attempts: i32,
+
+    /// the number of failures of a child within the breaker window which
+    /// trips its circuit breaker, 0 disables the breaker
+    pub breaker_max_failures: u32,
+
+    /// failures older than this do not count towards tripping the breaker
+    pub breaker_window_ns: u64,
+
+    /// how long a child is not rebuilt after its breaker tripped
+    pub breaker_cooldown_ns: u64,
 }
 
 impl Default for ErrStoreOpts {
@@ -618,6 +636,9 @@ impl Default for ErrStoreOpts {
             max_errors: 64,
             retention_ns: 10_000_000_000,
             max_io_attempts: 1,
+            breaker_max_failures: 3,
+            breaker_window_ns: 300_000_000_000,
+            breaker_cooldown_ns: 600_000_000_000,
         }
     }
 }
@@ -627,3 +648,74 @@ impl GetOpts for ErrStoreOpts {
         self.clone()
     }
 }
+
+#[serde(default, deny_unknown_fields)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebuildOpts {
+    /// the total rate in MiB/s at which all running rebuild jobs copy data,
+    /// shared equally between them, 0 means unlimited
+    pub rate_limit_mbps: u64,
+
+    /// how often the progress of a rebuild is saved, so that it resumes from
+    /// there after a restart rather than starting over, 0 disables it
+    pub checkpoint_interval_ms: u64,
+}
+
+impl Default for RebuildOpts {
+    fn default() -> Self {
+        Self {
+            rate_limit_mbps: 0,
+            checkpoint_interval_ms: 5_000,
+        }
+    }
+}
+
+impl GetOpts for RebuildOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+}
+
+#[serde(default, deny_unknown_fields)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackgroundOpts {
+    /// bandwidth of the devices in MiB/s, 0 means background IO is not
+    /// limited
+    pub device_mbps: u64,
+
+    /// percentage of the bandwidth of the devices which background IO may
+    /// use, the rest is left to frontend IO
+    pub background_percent: u32,
+
+    /// weight of rebuilds in the share of the background budget
+    pub rebuild_weight: u32,
+
+    /// weight of scrubs in the share of the background budget
+    pub scrub_weight: u32,
+
+    /// weight of read ahead in the share of the background budget
+    pub prefetch_weight: u32,
+
+    /// weight of the migration of extents between the tiers of tier bdevs
+    /// in the share of the background budget
+    pub migration_weight: u32,
+}
+
+impl Default for BackgroundOpts {
+    fn default() -> Self {
+        Self {
+            device_mbps: 0,
+            background_percent: 20,
+            rebuild_weight: 4,
+            scrub_weight: 2,
+            prefetch_weight: 1,
+            migration_weight: 1,
+        }
+    }
+}
+
+impl GetOpts for BackgroundOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+}
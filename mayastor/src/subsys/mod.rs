@@ -2,20 +2,31 @@
 //! Main file to register additional subsystems
 
 pub use config::{
-    opts::{NexusOpts, NvmeBdevOpts},
+    opts::{
+        BackgroundOpts,
+        BdevOpts,
+        NexusOpts,
+        NvmeBdevOpts,
+        RebuildOpts,
+    },
     BaseBdev,
     Config,
     ConfigSubsystem,
     NexusBdev,
     Pool,
+    ReloadReport,
 };
 pub use nvmf::{
     create_snapshot,
+    AnaState,
     set_snapshot_time,
     Error as NvmfError,
+    Controller as NvmfController,
     NvmeCpl,
     NvmfReq,
     NvmfSubsystem,
+    Registrant,
+    Reservations,
     SubType,
     Target as NvmfTarget,
 };
@@ -20,7 +20,14 @@ use spdk_sys::{
     spdk_subsystem_fini_next,
     spdk_subsystem_init_next,
 };
-pub use subsystem::{NvmfSubsystem, SubType};
+pub use subsystem::{
+    AnaState,
+    Controller,
+    NvmfSubsystem,
+    Registrant,
+    Reservations,
+    SubType,
+};
 pub use target::Target;
 
 use crate::{
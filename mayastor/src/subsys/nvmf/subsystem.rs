@@ -2,32 +2,46 @@ use std::{
     ffi::{c_void, CString},
     fmt,
     fmt::{Debug, Display},
+    fs,
     mem::size_of,
+    path::{Path, PathBuf},
     ptr,
     ptr::NonNull,
 };
 
 use futures::channel::oneshot;
 use nix::errno::Errno;
-use serde::export::{Formatter, TryFrom};
+use serde::{
+    export::{Formatter, TryFrom},
+    Deserialize,
+    Serialize,
+};
 
 use spdk_sys::{
+    nvmf_subsystem_set_ana_state,
     spdk_bdev_nvme_opts,
+    spdk_nvme_ana_state,
+    spdk_nvmf_host_get_nqn,
     spdk_nvmf_ns_get_bdev,
     spdk_nvmf_ns_opts,
     spdk_nvmf_subsystem,
+    spdk_nvmf_subsystem_add_host,
     spdk_nvmf_subsystem_add_listener,
     spdk_nvmf_subsystem_add_ns,
     spdk_nvmf_subsystem_create,
     spdk_nvmf_subsystem_destroy,
+    spdk_nvmf_subsystem_get_allow_any_host,
     spdk_nvmf_subsystem_get_first,
+    spdk_nvmf_subsystem_get_first_host,
     spdk_nvmf_subsystem_get_first_listener,
     spdk_nvmf_subsystem_get_first_ns,
     spdk_nvmf_subsystem_get_next,
+    spdk_nvmf_subsystem_get_next_host,
     spdk_nvmf_subsystem_get_next_listener,
     spdk_nvmf_subsystem_get_nqn,
     spdk_nvmf_subsystem_listener_get_trid,
     spdk_nvmf_subsystem_pause,
+    spdk_nvmf_subsystem_remove_host,
     spdk_nvmf_subsystem_resume,
     spdk_nvmf_subsystem_set_allow_any_host,
     spdk_nvmf_subsystem_set_ana_reporting,
@@ -36,6 +50,9 @@ use spdk_sys::{
     spdk_nvmf_subsystem_start,
     spdk_nvmf_subsystem_stop,
     spdk_nvmf_tgt,
+    SPDK_NVME_ANA_INACCESSIBLE_STATE,
+    SPDK_NVME_ANA_NON_OPTIMIZED_STATE,
+    SPDK_NVME_ANA_OPTIMIZED_STATE,
     SPDK_NVMF_SUBTYPE_DISCOVERY,
     SPDK_NVMF_SUBTYPE_NVME,
 };
@@ -49,6 +66,45 @@ use crate::{
     },
 };
 
+/// A controller of a subsystem, through which a host is connected to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Controller {
+    /// the controller identifier, unique within the subsystem
+    pub cntlid: u16,
+    /// the NQN of the host connected through the controller
+    pub hostnqn: String,
+}
+
+/// a host registered on a namespace with its reservation key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Registrant {
+    /// the host identifier of the host
+    pub host_uuid: String,
+    /// the reservation key the host registered with
+    pub cr_key: u64,
+}
+
+/// The reservation of a namespace shared with reservations, as persisted by
+/// the target, which is not reserved when there is no holder.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reservations {
+    /// the reservation type as defined by the NVMe specification
+    pub rtype: u32,
+    /// the reservation key of the holder
+    pub crkey: u64,
+    /// the host identifier of the holder, empty if there is none
+    pub holder_uuid: String,
+    /// the hosts registered on the namespace
+    pub registrants: Vec<Registrant>,
+}
+
+/// the prefix of the NQN of the subsystems unless told otherwise
+pub const NQN_PREFIX: &str = "nqn.2019-05.io.openebs";
+
+/// the maximum length of an NQN, as defined by the NVMe specification
+const NQN_MAX_LEN: usize = 223;
+
 #[derive(Debug, PartialOrd, PartialEq)]
 pub enum SubType {
     Nvme,
@@ -64,6 +120,26 @@ impl Display for SubType {
     }
 }
 
+/// Asymmetric Namespace Access (ANA) state of the paths to a subsystem, as
+/// reported to the hosts connected through them. Multipath hosts send their
+/// IO down the optimized paths and stop using inaccessible ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnaState {
+    Optimized,
+    NonOptimized,
+    Inaccessible,
+}
+
+impl From<AnaState> for spdk_nvme_ana_state {
+    fn from(state: AnaState) -> Self {
+        match state {
+            AnaState::Optimized => SPDK_NVME_ANA_OPTIMIZED_STATE,
+            AnaState::NonOptimized => SPDK_NVME_ANA_NON_OPTIMIZED_STATE,
+            AnaState::Inaccessible => SPDK_NVME_ANA_INACCESSIBLE_STATE,
+        }
+    }
+}
+
 pub struct NvmfSubsystem(pub(crate) NonNull<spdk_nvmf_subsystem>);
 pub struct NvmfSubsystemIterator(*mut spdk_nvmf_subsystem);
 
@@ -123,21 +199,36 @@ impl TryFrom<Bdev> for NvmfSubsystem {
     type Error = Error;
 
     fn try_from(bdev: Bdev) -> Result<Self, Self::Error> {
-        let ss = NvmfSubsystem::new(bdev.name().as_str())?;
-        ss.set_ana_reporting(true)?;
-        ss.allow_any(true);
-        if let Err(e) = ss.add_namespace(&bdev) {
-            ss.destroy();
-            return Err(e);
-        }
-        Ok(ss)
+        NvmfSubsystem::with_prefix(NQN_PREFIX, &bdev)
     }
 }
 
 impl NvmfSubsystem {
     /// create a new subsystem where the NQN is based on the UUID
     pub fn new(uuid: &str) -> Result<Self, Error> {
-        let nqn = gen_nqn(uuid).into_cstring();
+        NvmfSubsystem::new_with_prefix(NQN_PREFIX, uuid)
+    }
+
+    /// Create a new subsystem where the NQN is based on the UUID, under the
+    /// given NQN prefix rather than the default one, so the subsystems of
+    /// instances sharing to the same hosts are told apart. The prefix must be
+    /// an NQN of the form `nqn.yyyy-mm.reverse.domain[:identifier]`.
+    pub fn new_with_prefix(prefix: &str, uuid: &str) -> Result<Self, Error> {
+        check_nqn_prefix(prefix).map_err(|msg| Error::Subsystem {
+            source: Errno::EINVAL,
+            nqn: uuid.into(),
+            msg,
+        })?;
+        let nqn = format!("{}:{}", prefix, uuid);
+        if nqn.len() > NQN_MAX_LEN {
+            return Err(Error::Subsystem {
+                source: Errno::EINVAL,
+                nqn: uuid.into(),
+                msg: format!("NQN {} is too long", nqn),
+            });
+        }
+
+        let nqn = nqn.into_cstring();
         let ss = NVMF_TGT
             .with(|t| {
                 let tgt = t.borrow().tgt.as_ptr();
@@ -177,6 +268,19 @@ impl NvmfSubsystem {
         Ok(NvmfSubsystem(ss))
     }
 
+    /// create a new subsystem for the bdev, where the NQN is based on the
+    /// name of the bdev under the given NQN prefix, see `new_with_prefix()`
+    pub fn with_prefix(prefix: &str, bdev: &Bdev) -> Result<Self, Error> {
+        let ss = NvmfSubsystem::new_with_prefix(prefix, &bdev.name())?;
+        ss.set_ana_reporting(true)?;
+        ss.allow_any(true);
+        if let Err(e) = ss.add_namespace(bdev) {
+            ss.destroy();
+            return Err(e);
+        }
+        Ok(ss)
+    }
+
     /// unfortunately, we cannot always use the bdev UUID which is a shame and
     /// mostly due to testing.
     pub fn new_with_uuid(uuid: &str, bdev: &Bdev) -> Result<Self, Error> {
@@ -187,19 +291,56 @@ impl NvmfSubsystem {
         Ok(ss)
     }
 
+    /// Create a new subsystem like `new_with_uuid()`, with the reservations
+    /// the hosts make on its namespace persisted in a file named after the
+    /// UUID of the bdev, from which they are restored when the bdev is shared
+    /// with reservations again.
+    pub fn new_with_reservations(
+        uuid: &str,
+        bdev: &Bdev,
+    ) -> Result<Self, Error> {
+        let dir = PathBuf::from(&Config::get().nvmf_tcp_tgt_conf.ptpl_dir);
+        fs::create_dir_all(&dir).map_err(|e| Error::Subsystem {
+            source: e.raw_os_error().map_or(Errno::EIO, Errno::from_i32),
+            nqn: uuid.into(),
+            msg: format!("failed to create {}", dir.display()),
+        })?;
+        let ptpl = dir.join(format!("{}.json", bdev.uuid_as_string()));
+
+        let ss = NvmfSubsystem::new(uuid)?;
+        ss.set_ana_reporting(true)?;
+        ss.allow_any(true);
+        if let Err(e) = ss.add_ns(bdev, Some(&ptpl)) {
+            ss.destroy();
+            return Err(e);
+        }
+        NVMF_TGT.with(|t| {
+            t.borrow_mut().reservations.insert(ss.get_nqn(), ptpl)
+        });
+        Ok(ss)
+    }
+
     /// add the given bdev to this namespace
     pub fn add_namespace(&self, bdev: &Bdev) -> Result<(), Error> {
+        self.add_ns(bdev, None)
+    }
+
+    /// add the given bdev to this namespace, persisting its reservations in
+    /// the given file if any
+    fn add_ns(&self, bdev: &Bdev, ptpl: Option<&Path>) -> Result<(), Error> {
         let opts = spdk_nvmf_ns_opts {
             nguid: bdev.uuid().as_bytes(),
             ..Default::default()
         };
+        let ptpl = ptpl.map(|p| p.display().to_string().into_cstring());
         let ns_id = unsafe {
             spdk_nvmf_subsystem_add_ns(
                 self.0.as_ptr(),
                 bdev.as_ptr(),
                 &opts as *const _,
                 size_of::<spdk_bdev_nvme_opts>() as u64,
-                ptr::null_mut(),
+                ptpl.as_ref()
+                    .map_or(ptr::null_mut(), |p| p.as_ptr() as *mut _),
             )
         };
 
@@ -219,9 +360,43 @@ impl NvmfSubsystem {
 
     /// destroy the subsystem
     pub fn destroy(&self) {
+        let nqn = self.get_nqn();
+        NVMF_TGT.with(|t| t.borrow_mut().reservations.remove(&nqn));
         unsafe { spdk_nvmf_subsystem_destroy(self.0.as_ptr()) }
     }
 
+    /// returns the file the reservations on the namespace are persisted in,
+    /// if the subsystem was created with reservations
+    pub fn ptpl_file(&self) -> Option<PathBuf> {
+        let nqn = self.get_nqn();
+        NVMF_TGT.with(|t| t.borrow().reservations.get(&nqn).cloned())
+    }
+
+    /// Returns the reservation on the namespace of a subsystem created with
+    /// reservations. The target persists the reservation once a host has
+    /// made one, until then the namespace is reported as not reserved.
+    pub fn reservations(&self) -> Result<Reservations, Error> {
+        let ptpl = self.ptpl_file().ok_or_else(|| Error::Subsystem {
+            source: Errno::ENOTSUP,
+            nqn: self.get_nqn(),
+            msg: "reservations are not enabled".into(),
+        })?;
+        if !ptpl.exists() {
+            return Ok(Reservations::default());
+        }
+
+        let file = fs::File::open(&ptpl).map_err(|e| Error::Subsystem {
+            source: e.raw_os_error().map_or(Errno::EIO, Errno::from_i32),
+            nqn: self.get_nqn(),
+            msg: format!("failed to open {}", ptpl.display()),
+        })?;
+        serde_json::from_reader(file).map_err(|e| Error::Subsystem {
+            source: Errno::EINVAL,
+            nqn: self.get_nqn(),
+            msg: format!("failed to parse {}: {}", ptpl.display(), e),
+        })
+    }
+
     /// Get NVMe subsystem's NQN
     pub fn get_nqn(&self) -> String {
         unsafe {
@@ -238,6 +413,87 @@ impl NvmfSubsystem {
         };
     }
 
+    /// returns the NQNs of the hosts allowed to connect to the subsystem,
+    /// which is empty if any host is
+    pub fn allowed_hosts(&self) -> Vec<String> {
+        if unsafe { spdk_nvmf_subsystem_get_allow_any_host(self.0.as_ptr()) } {
+            return Vec::new();
+        }
+        self.hosts()
+    }
+
+    /// returns the NQNs of the hosts on the list of the subsystem, which is
+    /// ignored while any host is allowed to connect
+    fn hosts(&self) -> Vec<String> {
+        let mut hosts = Vec::new();
+        let mut host =
+            unsafe { spdk_nvmf_subsystem_get_first_host(self.0.as_ptr()) };
+        while !host.is_null() {
+            let nqn = unsafe { spdk_nvmf_host_get_nqn(host) };
+            hosts.push(nqn.as_str().to_string());
+            host = unsafe {
+                spdk_nvmf_subsystem_get_next_host(self.0.as_ptr(), host)
+            };
+        }
+        hosts
+    }
+
+    /// Allow only the hosts with the given NQNs to connect to the subsystem,
+    /// or any host if there are none. Hosts which are connected already stay
+    /// connected. The subsystem is paused meanwhile.
+    pub async fn set_allowed_hosts(
+        &self,
+        hosts: &[String],
+    ) -> Result<(), Error> {
+        if let Some(host) = hosts.iter().find(|h| !h.starts_with("nqn.")) {
+            return Err(Error::Subsystem {
+                source: Errno::EINVAL,
+                nqn: self.get_nqn(),
+                msg: format!("invalid host NQN {}", host),
+            });
+        }
+
+        self.pause().await?;
+        let result = self.replace_hosts(hosts);
+        self.resume().await?;
+
+        if result.is_ok() {
+            info!("{}: allowed hosts set to {:?}", self.get_nqn(), hosts);
+        }
+        result
+    }
+
+    /// replace the hosts on the list of the subsystem with the given ones
+    fn replace_hosts(&self, hosts: &[String]) -> Result<(), Error> {
+        for host in self.hosts().iter().filter(|h| !hosts.contains(h)) {
+            let nqn = host.clone().into_cstring();
+            unsafe {
+                spdk_nvmf_subsystem_remove_host(self.0.as_ptr(), nqn.as_ptr())
+            }
+            .to_result(|e| Error::Subsystem {
+                source: Errno::from_i32(e),
+                nqn: self.get_nqn(),
+                msg: format!("failed to remove host {}", host),
+            })?;
+        }
+
+        let current = self.hosts();
+        for host in hosts.iter().filter(|h| !current.contains(h)) {
+            let nqn = host.clone().into_cstring();
+            unsafe {
+                spdk_nvmf_subsystem_add_host(self.0.as_ptr(), nqn.as_ptr())
+            }
+            .to_result(|e| Error::Subsystem {
+                source: Errno::from_i32(e),
+                nqn: self.get_nqn(),
+                msg: format!("failed to add host {}", host),
+            })?;
+        }
+
+        self.allow_any(hosts.is_empty());
+        Ok(())
+    }
+
     /// enable Asymmetric Namespace Access (ANA) reporting
     pub fn set_ana_reporting(&self, enable: bool) -> Result<(), Error> {
         unsafe {
@@ -251,6 +507,64 @@ impl NvmfSubsystem {
         Ok(())
     }
 
+    /// Set the ANA state of all the listeners of the subsystem, the
+    /// connected hosts are notified of the change. The subsystem is paused
+    /// meanwhile.
+    pub async fn set_ana_state(&self, state: AnaState) -> Result<(), Error> {
+        extern "C" fn ana_cb(arg: *mut c_void, status: i32) {
+            let s = unsafe { Box::from_raw(arg as *mut oneshot::Sender<i32>) };
+            s.send(status).unwrap();
+        }
+
+        self.pause().await?;
+        let mut result = Ok(());
+        for trid in self.listeners_to_vec().unwrap_or_default() {
+            let (s, r) = oneshot::channel::<i32>();
+            unsafe {
+                nvmf_subsystem_set_ana_state(
+                    self.0.as_ptr(),
+                    &trid.0,
+                    state.into(),
+                    Some(ana_cb),
+                    cb_arg(s),
+                );
+            }
+
+            result = r.await.expect("ANA state callback gone").to_result(|e| {
+                Error::Subsystem {
+                    source: Errno::from_i32(e),
+                    nqn: self.get_nqn(),
+                    msg: format!("failed to set ANA state {:?}", state),
+                }
+            });
+            if result.is_err() {
+                break;
+            }
+        }
+        self.resume().await?;
+
+        if result.is_ok() {
+            info!("{}: ANA state set to {:?}", self.get_nqn(), state);
+        }
+        result
+    }
+
+    /// the ANA state of the first listener of the subsystem, None if it has
+    /// no listeners
+    pub fn ana_state(&self) -> Option<AnaState> {
+        let listener =
+            unsafe { spdk_nvmf_subsystem_get_first_listener(self.0.as_ptr()) };
+        if listener.is_null() {
+            return None;
+        }
+
+        match unsafe { (*listener).ana_state } {
+            SPDK_NVME_ANA_OPTIMIZED_STATE => Some(AnaState::Optimized),
+            SPDK_NVME_ANA_NON_OPTIMIZED_STATE => Some(AnaState::NonOptimized),
+            _ => Some(AnaState::Inaccessible),
+        }
+    }
+
     // we currently allow all listeners to the subsystem
     async fn add_listener(&self) -> Result<(), Error> {
         extern "C" fn listen_cb(arg: *mut c_void, status: i32) {
@@ -504,13 +818,12 @@ impl NvmfSubsystem {
         })
     }
 
-    /// lookup a subsystem by its UUID
+    /// lookup a subsystem by its UUID, whichever prefix its NQN has
     pub fn nqn_lookup(uuid: &str) -> Option<NvmfSubsystem> {
-        let nqn = gen_nqn(uuid);
-        NvmfSubsystem::first()
-            .unwrap()
+        let suffix = format!(":{}", uuid);
+        NvmfSubsystem::first()?
             .into_iter()
-            .find(|s| s.get_nqn() == nqn)
+            .find(|s| s.get_nqn().ends_with(&suffix))
     }
 
     /// get the bdev associated with this subsystem -- we implicitly assume the
@@ -566,6 +879,31 @@ impl NvmfSubsystem {
         }
     }
 
+    /// The controllers of the subsystem, one for each connection of a host.
+    /// A controller goes away once its admin queue pair is disconnected,
+    /// be it by the host, on a transport error such as the connection being
+    /// reset, or once the host has not been heard from within the keep alive
+    /// timeout.
+    pub fn controllers(&self) -> Vec<Controller> {
+        let mut controllers = Vec::new();
+        let mut ctrlr = unsafe { self.0.as_ref().ctrlrs.tqh_first };
+        while !ctrlr.is_null() {
+            let c = unsafe { &*ctrlr };
+            controllers.push(Controller {
+                cntlid: c.cntlid,
+                hostnqn: c.hostnqn.as_str().to_string(),
+            });
+            ctrlr = c.link.tqe_next;
+        }
+        controllers
+    }
+
+    /// the number of hosts connected to the subsystem, counting a host
+    /// connected through several controllers as many times
+    pub fn connected_count(&self) -> usize {
+        self.controllers().len()
+    }
+
     /// return the URI's this subsystem is listening on
     pub fn uri_endpoints(&self) -> Option<Vec<String>> {
         if let Some(v) = self.listeners_to_vec() {
@@ -581,6 +919,46 @@ impl NvmfSubsystem {
     }
 }
 
-fn gen_nqn(id: &str) -> String {
-    format!("nqn.2019-05.io.openebs:{}", id)
+/// Checks the prefix is an NQN of the form `nqn.yyyy-mm.reverse.domain`,
+/// optionally followed by `:` and an identifier, returning why it is not.
+fn check_nqn_prefix(prefix: &str) -> Result<(), String> {
+    let invalid = |reason: &str| {
+        Err(format!("invalid NQN prefix {}: {}", prefix, reason))
+    };
+
+    let rest = match prefix.strip_prefix("nqn.") {
+        Some(rest) => rest,
+        None => return invalid("it does not start with nqn."),
+    };
+    let date = rest.as_bytes();
+    if date.len() < 8
+        || !date[.. 4].iter().all(u8::is_ascii_digit)
+        || date[4] != b'-'
+        || !date[5 .. 7].iter().all(u8::is_ascii_digit)
+        || date[7] != b'.'
+    {
+        return invalid("it has no date of the form yyyy-mm");
+    }
+
+    let mut parts = rest[8 ..].splitn(2, ':');
+    let domain = parts.next().unwrap();
+    if domain.is_empty()
+        || domain.starts_with('.')
+        || domain.ends_with('.')
+        || !domain.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.'
+        })
+    {
+        return invalid("it has no valid reverse domain name");
+    }
+    if let Some(identifier) = parts.next() {
+        if identifier.is_empty()
+            || !identifier.chars().all(|c| {
+                c.is_ascii_alphanumeric() || "-._:".contains(c)
+            })
+        {
+            return invalid("it has an invalid identifier");
+        }
+    }
+    Ok(())
 }
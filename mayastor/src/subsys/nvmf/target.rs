@@ -1,6 +1,8 @@
 use std::{
     cell::RefCell,
+    collections::HashMap,
     ffi::{c_void, CString},
+    path::PathBuf,
     ptr::NonNull,
 };
 
@@ -59,6 +61,9 @@ pub struct Target {
     poll_group_count: u16,
     /// The current state of the target
     next_state: TargetState,
+    /// the files the reservations of the subsystems shared with
+    /// reservations are persisted in, by NQN
+    pub(crate) reservations: HashMap<String, PathBuf>,
 }
 
 impl Default for Target {
@@ -107,9 +112,15 @@ impl Target {
             acceptor_poller: NonNull::dangling(),
             poll_group_count: 0,
             next_state: TargetState::Init,
+            reservations: HashMap::new(),
         }
     }
 
+    /// whether the target of this thread is up and serving subsystems
+    pub(crate) fn running() -> bool {
+        NVMF_TGT.with(|t| t.borrow().next_state == TargetState::Running)
+    }
+
     /// initialize the target and advance states
     fn init(&mut self) -> Result<()> {
         let cfg = Config::get();
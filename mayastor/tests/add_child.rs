@@ -2,8 +2,8 @@
 extern crate assert_matches;
 
 use mayastor::{
-    bdev::{nexus_create, nexus_lookup, ChildState, Reason},
-    core::MayastorCliArgs,
+    bdev::{nexus_create, nexus_lookup, ChildState, NexusStatus, Reason},
+    core::{Bdev, MayastorCliArgs},
 };
 
 static NEXUS_NAME: &str = "nexus";
@@ -16,6 +16,8 @@ static BDEVNAME1: &str = "aio:///tmp/disk1.img?blk_size=512";
 static DISKNAME2: &str = "/tmp/disk2.img";
 static BDEVNAME2: &str = "aio:///tmp/disk2.img?blk_size=512";
 
+static SMALL_BDEV: &str = "malloc:///small?size_mb=32";
+
 pub mod common;
 use common::MayastorTest;
 
@@ -57,6 +59,26 @@ async fn add_child() {
             nexus.children[1].state(),
             ChildState::Faulted(Reason::OutOfSync)
         );
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+    })
+    .await;
+
+    // Test adding a child smaller than the nexus
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let err = nexus.add_child(SMALL_BDEV, false).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Child small of nexus {} is too small, child size {} nexus \
+                 size {}",
+                NEXUS_NAME,
+                32 * 1024 * 1024,
+                FILE_SIZE
+            )
+        );
+        assert_eq!(nexus.children.len(), 2);
+        assert!(Bdev::lookup_by_name("small").is_none());
     })
     .await;
 
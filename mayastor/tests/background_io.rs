@@ -0,0 +1,165 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use futures::channel::oneshot;
+use rand::Rng;
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{
+        BackgroundClass,
+        BackgroundScheduler,
+        BdevHandle,
+        MayastorCliArgs,
+        PrefetchStats,
+        Reactors,
+    },
+    rebuild::{RebuildJob, RebuildState},
+    subsys::BackgroundOpts,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "BackgroundNexus";
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+const NEXUS_SIZE_MB: u64 = 48;
+/// bandwidth of the devices and the share of it for background IO
+const DEVICE_MBPS: u64 = 200;
+const BACKGROUND_PERCENT: u32 = 10;
+
+const IO_SIZE: u64 = 4096;
+const FRONTEND_IOS: usize = 2000;
+/// how much the p99 latency of the frontend IO may grow by with background
+/// IO, over the one measured without
+const P99_FACTOR: u32 = 5;
+
+static STOP_READER: AtomicBool = AtomicBool::new(false);
+
+/// read the nexus sequentially with read ahead enabled until told to stop
+async fn sequential_reader() -> PrefetchStats {
+    let hdl = BdevHandle::open(NEXUS_NAME, false, false).unwrap();
+    let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+    let blocks = (NEXUS_SIZE_MB << 20) / IO_SIZE;
+    hdl.enable_prefetch(32 * IO_SIZE);
+    let mut i = 0;
+    while !STOP_READER.load(Ordering::Relaxed) {
+        hdl.read_at((i % blocks) * IO_SIZE, &mut buf).await.unwrap();
+        i += 1;
+    }
+    hdl.prefetch_stats().unwrap()
+}
+
+/// write to random offsets of the nexus and return the p99 latency
+async fn frontend_p99() -> Duration {
+    let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+    let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+    buf.fill(0xaa);
+    let blocks = (NEXUS_SIZE_MB << 20) / IO_SIZE;
+    let mut rng = rand::thread_rng();
+    let mut latencies = Vec::with_capacity(FRONTEND_IOS);
+    for _ in 0 .. FRONTEND_IOS {
+        let offset = rng.gen_range(0, blocks) * IO_SIZE;
+        let start = Instant::now();
+        hdl.write_at(offset, &buf).await.unwrap();
+        latencies.push(start.elapsed());
+    }
+    latencies.sort();
+    latencies[FRONTEND_IOS * 99 / 100]
+}
+
+#[tokio::test]
+async fn background_io() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // cap the background IO at runtime
+    ms.spawn(async {
+        assert_eq!(BackgroundScheduler::opts(), BackgroundOpts::default());
+        assert!(BackgroundScheduler::rate(BackgroundClass::Rebuild).is_none());
+
+        let opts = BackgroundOpts {
+            device_mbps: DEVICE_MBPS,
+            background_percent: BACKGROUND_PERCENT,
+            ..Default::default()
+        };
+        BackgroundScheduler::set_opts(opts.clone()).unwrap();
+        assert_eq!(BackgroundScheduler::opts(), opts);
+        assert!(BackgroundScheduler::set_opts(BackgroundOpts {
+            rebuild_weight: 0,
+            ..opts
+        })
+        .is_err());
+    })
+    .await;
+
+    // rebuild a child and read ahead in the background
+    let (sender, reader) = oneshot::channel();
+    let (start, rebuild) = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NEXUS_SIZE_MB << 20,
+                None,
+                &[CHILD_1.to_string()],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.add_child(CHILD_2, true).await.unwrap();
+
+            Reactors::current().send_future(async move {
+                let _ = sender.send(sequential_reader().await);
+            });
+            (Instant::now(), nexus.start_rebuild(CHILD_2).await.unwrap())
+        })
+        .await;
+
+    // the latency of the frontend IO meanwhile
+    let p99 = ms
+        .spawn(async {
+            let p99 = frontend_p99().await;
+            let job = RebuildJob::lookup(CHILD_2).unwrap();
+            assert_eq!(job.state(), RebuildState::Running);
+            p99
+        })
+        .await;
+    println!("frontend p99 latency with background IO: {:?}", p99);
+
+    // the rebuild is paced to its share of the background budget
+    let state = ms.spawn(async { rebuild.await.unwrap() }).await;
+    assert_eq!(state, RebuildState::Completed);
+    let budget = DEVICE_MBPS * u64::from(BACKGROUND_PERCENT) / 100;
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(NEXUS_SIZE_MB * 1000 / budget),
+        "rebuilt too fast: {:?}",
+        elapsed
+    );
+
+    // the read ahead is throttled to its share as well
+    STOP_READER.store(true, Ordering::Relaxed);
+    let stats = ms.spawn(async { reader.await.unwrap() }).await;
+    assert!(stats.issued > 0);
+    assert!(stats.throttled > 0);
+
+    // the latency of the frontend IO stayed bounded compared to the one
+    // measured once the background IO is done, with both children healthy
+    let baseline = ms.spawn(frontend_p99()).await;
+    println!("frontend p99 latency without background IO: {:?}", baseline);
+    assert!(
+        p99 < baseline * P99_FACTOR,
+        "p99 latency {:?}, {:?} without background IO",
+        p99,
+        baseline
+    );
+
+    ms.spawn(async {
+        BackgroundScheduler::set_opts(BackgroundOpts::default()).unwrap();
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
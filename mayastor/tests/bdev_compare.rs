@@ -0,0 +1,60 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///compared?size_mb=64";
+static MALLOC_NAME: &str = "compared";
+
+const KB: u64 = 1024;
+
+#[tokio::test]
+async fn bdev_compare() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4 * KB).unwrap();
+        buf.fill(0xaa);
+        hdl.write_at(8 * KB, &buf).await.unwrap();
+
+        // a mismatch is told apart from a failed IO
+        hdl.compare_at(8 * KB, &buf).await.unwrap();
+        match hdl.compare_at(4 * KB, &buf).await {
+            Err(CoreError::Miscompare {
+                offset,
+                len,
+            }) => {
+                assert_eq!(offset, 4 * KB);
+                assert_eq!(len, 4 * KB);
+            }
+            r => panic!("compare of different data: {:?}", r),
+        }
+        assert!(matches!(
+            hdl.compare_at(100, &buf).await,
+            Err(CoreError::UnalignedIo {
+                ..
+            })
+        ));
+
+        // the malloc bdev has no fused compare and write, which is not
+        // replaced by a plain write
+        let mut write = hdl.dma_malloc(4 * KB).unwrap();
+        write.fill(0x55);
+        assert!(matches!(
+            hdl.compare_and_write_at(8 * KB, &buf, &write).await,
+            Err(CoreError::CompareAndWriteNotSupported {
+                ..
+            })
+        ));
+        hdl.compare_at(8 * KB, &buf).await.unwrap();
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
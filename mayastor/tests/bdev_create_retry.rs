@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    nexus_uri::{bdev_create_with_retry, bdev_destroy, NexusBdevError},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///retried?size_mb=8";
+static INVALID_BDEV: &str = "malloc:///retried?size_mb=8&blk_size=100";
+// nothing listens on the port, so connections to it are refused
+static NVMF_BDEV: &str = "nvmf://127.0.0.1:4/nqn.2019-05.io.openebs:absent";
+
+const BACKOFF: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn bdev_create_retry() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let name = bdev_create_with_retry(MALLOC_BDEV, 3, BACKOFF)
+            .await
+            .unwrap();
+        assert_eq!(name, "retried");
+
+        // the bdev exists already, which is not worth trying again
+        match bdev_create_with_retry(MALLOC_BDEV, 3, BACKOFF).await {
+            Err(NexusBdevError::CreateBdevAttempts {
+                source,
+                attempts,
+                ..
+            }) => {
+                assert_eq!(attempts, 1);
+                assert!(!source.is_transient());
+            }
+            r => panic!("recreating the bdev: {:?}", r),
+        }
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+
+        // and neither is an invalid URI
+        match bdev_create_with_retry(INVALID_BDEV, 3, BACKOFF).await {
+            Err(NexusBdevError::CreateBdevAttempts {
+                attempts,
+                ..
+            }) => assert_eq!(attempts, 1),
+            r => panic!("creating the bdev of an invalid URI: {:?}", r),
+        }
+
+        // while an unreachable target is, backing off between attempts
+        let start = Instant::now();
+        match bdev_create_with_retry(NVMF_BDEV, 3, BACKOFF).await {
+            Err(NexusBdevError::CreateBdevAttempts {
+                source,
+                attempts,
+                ..
+            }) => {
+                assert_eq!(attempts, 3);
+                assert!(source.is_transient(), "{:?}", source);
+            }
+            r => panic!("creating the bdev of no target: {:?}", r),
+        }
+        assert!(start.elapsed() >= BACKOFF * 3);
+    })
+    .await;
+}
@@ -0,0 +1,51 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///flushed?size_mb=64";
+static MALLOC_NAME: &str = "flushed";
+static NULL_BDEV: &str = "null:///uncached?size_mb=64";
+static NULL_NAME: &str = "uncached";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn bdev_flush() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        for (uri, name) in &[(MALLOC_BDEV, MALLOC_NAME), (NULL_BDEV, NULL_NAME)]
+        {
+            bdev_create(uri).await.unwrap();
+            let hdl = BdevHandle::open(name, true, false).unwrap();
+            let mut buf = hdl.dma_malloc(MB).unwrap();
+            buf.fill(0xaa);
+            hdl.write_at(MB, &buf).await.unwrap();
+
+            // the null bdev has no cache to flush, which is no error
+            hdl.flush().await.unwrap();
+            hdl.flush_range(MB, MB).await.unwrap();
+            drop(hdl);
+            bdev_destroy(uri).await.unwrap();
+        }
+    })
+    .await;
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        assert!(matches!(
+            hdl.flush_range(64 * MB, MB).await,
+            Err(CoreError::FlushDispatch {
+                ..
+            })
+        ));
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,38 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///reset?size_mb=64";
+static MALLOC_NAME: &str = "reset";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn bdev_reset() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+
+        // without IO outstanding, there is nothing to abort
+        hdl.reset().await.unwrap();
+        hdl.reset().await.unwrap();
+
+        // and the handle is used as before
+        let mut buf = hdl.dma_malloc(MB).unwrap();
+        buf.fill(0xaa);
+        hdl.write_at(0, &buf).await.unwrap();
+        buf.fill(0);
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xaa));
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
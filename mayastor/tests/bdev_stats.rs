@@ -0,0 +1,48 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///counted?size_mb=64";
+static MALLOC_NAME: &str = "counted";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn bdev_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+        let before = bdev.stats().await.unwrap();
+
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(MB).unwrap();
+        hdl.write_at(0, &buf).await.unwrap();
+        hdl.write_at(MB, &buf).await.unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        hdl.unmap_at(0, 2 * MB).await.unwrap();
+
+        // every IO is counted, with the time it took
+        let stats = bdev.stats().await.unwrap();
+        assert_eq!(stats.num_write_ops - before.num_write_ops, 2);
+        assert_eq!(stats.bytes_written - before.bytes_written, 2 * MB);
+        assert_eq!(stats.num_read_ops - before.num_read_ops, 1);
+        assert_eq!(stats.bytes_read - before.bytes_read, MB);
+        assert_eq!(stats.num_unmap_ops - before.num_unmap_ops, 1);
+        assert_eq!(stats.bytes_unmapped - before.bytes_unmapped, 2 * MB);
+        assert!(stats.write_latency_ticks > before.write_latency_ticks);
+        assert!(stats.ticks_rate > 0);
+
+        let value = serde_json::to_value(stats.clone()).unwrap();
+        assert_eq!(value["bytes_unmapped"], stats.bytes_unmapped);
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
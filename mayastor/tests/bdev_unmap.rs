@@ -0,0 +1,124 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs, UnmapMode, Unmapped},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///unmapped?size_mb=64";
+static MALLOC_NAME: &str = "unmapped";
+static NULL_BDEV: &str = "null:///kept?size_mb=64";
+static NULL_NAME: &str = "kept";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn bdev_unmap() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4 * MB).unwrap();
+        buf.fill(0xff);
+        hdl.write_at(0, &buf).await.unwrap();
+
+        // the malloc bdev reads back unmapped blocks as zeroes
+        hdl.unmap_at(MB, 2 * MB).await.unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        let data = buf.as_slice();
+        let mb = MB as usize;
+        assert!(data[.. mb].iter().all(|b| *b == 0xff));
+        assert!(data[mb .. 3 * mb].iter().all(|b| *b == 0));
+        assert!(data[3 * mb ..].iter().all(|b| *b == 0xff));
+
+        for (offset, len) in &[(100, MB), (0, MB + 100), (0, 0)] {
+            assert!(matches!(
+                hdl.unmap_at(*offset, *len).await,
+                Err(CoreError::UnalignedIo {
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            hdl.unmap_at(64 * MB, MB).await,
+            Err(CoreError::UnmapDispatch {
+                ..
+            })
+        ));
+
+        // with a granularity of 1 MiB, only the MiB wholly within the range
+        // is unmapped, the half MiB either side of it is left untouched
+        let blocks = |len: u64| len / 512;
+        hdl.write_at(0, &buf).await.unwrap();
+        let unmapped = hdl
+            .unmap_at_with_mode(MB / 2, 2 * MB, MB, UnmapMode::AlignedOnly)
+            .await
+            .unwrap();
+        assert_eq!(
+            unmapped,
+            Unmapped {
+                deallocated: blocks(MB),
+                zeroed: 0,
+                skipped: blocks(MB),
+            }
+        );
+        hdl.read_at(0, &mut buf).await.unwrap();
+        let data = buf.as_slice();
+        assert!(data[.. mb].iter().all(|b| *b == 0xff));
+        assert!(data[mb .. 2 * mb].iter().all(|b| *b == 0));
+        assert!(data[2 * mb ..].iter().all(|b| *b == 0xff));
+
+        // a range within a single unit is not unmapped at all
+        let unmapped = hdl
+            .unmap_at_with_mode(3 * MB, MB / 2, MB, UnmapMode::AlignedOnly)
+            .await
+            .unwrap();
+        assert_eq!(unmapped.deallocated, 0);
+        assert_eq!(unmapped.skipped, blocks(MB / 2));
+
+        // while by default it is, and reported as zeroed
+        let unmapped = hdl
+            .unmap_at_with_mode(MB / 2, 2 * MB, MB, UnmapMode::Default)
+            .await
+            .unwrap();
+        assert_eq!(
+            unmapped,
+            Unmapped {
+                deallocated: blocks(MB),
+                zeroed: blocks(MB),
+                skipped: 0,
+            }
+        );
+
+        for granularity in &[0, 100, MB + 100] {
+            assert!(matches!(
+                hdl.unmap_at_with_mode(0, MB, *granularity, UnmapMode::Default)
+                    .await,
+                Err(CoreError::UnmapGranularity {
+                    ..
+                })
+            ));
+        }
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+
+    // the null bdev does not support unmaps
+    ms.spawn(async {
+        bdev_create(NULL_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(NULL_NAME, true, false).unwrap();
+        match hdl.unmap_at(0, MB).await {
+            Err(CoreError::UnmapNotSupported {
+                name,
+            }) => assert_eq!(name, NULL_NAME),
+            r => panic!("unmap of the null bdev: {:?}", r),
+        }
+        drop(hdl);
+        bdev_destroy(NULL_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,151 @@
+use std::{
+    ffi::CString,
+    time::{Duration, Instant},
+};
+
+use common::MayastorTest;
+use futures::channel::oneshot;
+use mayastor::{
+    bdev::cache_lookup,
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    ffihelper::{cb_arg, done_cb},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use spdk_sys::{create_delay_disk, delete_delay_disk};
+
+pub mod common;
+
+static BASE_BDEV: &str = "malloc:///base?size_mb=64";
+static DELAY_BDEV: &str = "slow";
+static CACHE_BDEV: &str = "cache:///slow?size_mb=4&policy=lru";
+static CACHE_NAME: &str = "slow-cache";
+
+/// latency of every IO to the backing bdev in usec
+const LATENCY_US: u64 = 2_000;
+/// size of the IOs and the number of them covering the hot region
+const IO_SIZE: u64 = 4096;
+const HOT_IOS: u64 = 64;
+/// number of times the hot region is read once it is cached
+const PASSES: u64 = 10;
+
+/// read the hot region once and return how long it took
+async fn read_hot_region(hdl: &BdevHandle, val: u8) -> Duration {
+    let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+    let start = Instant::now();
+    for i in 0 .. HOT_IOS {
+        hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == val));
+    }
+    start.elapsed()
+}
+
+#[tokio::test]
+async fn cache_bdev() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let base = bdev_create(BASE_BDEV).await.unwrap();
+        let base = CString::new(base).unwrap();
+        let name = CString::new(DELAY_BDEV).unwrap();
+        let errno = unsafe {
+            create_delay_disk(
+                base.as_ptr(),
+                name.as_ptr(),
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+            )
+        };
+        assert_eq!(errno, 0);
+
+        assert_eq!(bdev_create(CACHE_BDEV).await.unwrap(), CACHE_NAME);
+        let bdev = Bdev::lookup_by_name(CACHE_NAME).unwrap();
+        let backing = Bdev::lookup_by_name(DELAY_BDEV).unwrap();
+        assert_eq!(bdev.num_blocks(), backing.num_blocks());
+        assert_eq!(bdev.block_len(), backing.block_len());
+    })
+    .await;
+
+    // the first pass misses and populates the cache, all following passes
+    // are served from memory
+    ms.spawn(async {
+        let hdl = BdevHandle::open(CACHE_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0xaa);
+        for i in 0 .. HOT_IOS {
+            hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+        }
+
+        let cold = read_hot_region(&hdl, 0xaa).await;
+        let stats = cache_lookup(CACHE_NAME).unwrap().stats();
+        assert_eq!(stats.misses, HOT_IOS);
+        assert_eq!(stats.hits, 0);
+
+        let mut warm = Duration::default();
+        for _ in 0 .. PASSES {
+            warm += read_hot_region(&hdl, 0xaa).await;
+        }
+        let warm = warm / PASSES as u32;
+        println!(
+            "hot region of {} KiB: cold read {:?}, cached read {:?}",
+            HOT_IOS * IO_SIZE / 1024,
+            cold,
+            warm
+        );
+
+        let stats = cache_lookup(CACHE_NAME).unwrap().stats();
+        assert_eq!(stats.misses, HOT_IOS);
+        assert_eq!(stats.hits, HOT_IOS * PASSES);
+        assert_eq!(stats.cached_blocks, HOT_IOS * IO_SIZE / 512);
+        assert!(warm * 4 < cold, "cached reads should be much faster");
+    })
+    .await;
+
+    // a write drops the blocks it covers, so they are not read stale
+    ms.spawn(async {
+        let hdl = BdevHandle::open(CACHE_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x55);
+        hdl.write_at(0, &buf).await.unwrap();
+
+        buf.fill(0);
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x55));
+        hdl.read_at(IO_SIZE, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xaa));
+
+        let stats = cache_lookup(CACHE_NAME).unwrap().stats();
+        assert_eq!(stats.invalidations, IO_SIZE / 512);
+    })
+    .await;
+
+    // reading more than the cache can hold evicts the oldest blocks
+    ms.spawn(async {
+        let hdl = BdevHandle::open(CACHE_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        let capacity = cache_lookup(CACHE_NAME).unwrap().stats().capacity;
+        for i in 0 .. 2 * capacity * 512 / IO_SIZE {
+            hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        }
+
+        let stats = cache_lookup(CACHE_NAME).unwrap().stats();
+        assert_eq!(stats.cached_blocks, capacity);
+        assert!(stats.evictions > 0);
+    })
+    .await;
+
+    ms.spawn(async {
+        bdev_destroy(CACHE_BDEV).await.unwrap();
+        assert!(cache_lookup(CACHE_NAME).is_none());
+
+        let bdev = Bdev::lookup_by_name(DELAY_BDEV).unwrap();
+        let (s, r) = oneshot::channel::<i32>();
+        unsafe {
+            delete_delay_disk(bdev.as_ptr(), Some(done_cb), cb_arg(s));
+        }
+        assert_eq!(r.await.unwrap(), 0);
+        bdev_destroy(BASE_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,100 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, Reason},
+    core::MayastorCliArgs,
+};
+use tokio::time::Duration;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "BreakerNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+/// number of failures tripping the breaker with the default config
+const MAX_FAILURES: usize = 3;
+
+async fn child_state(ms: &MayastorTest<'_>) -> ChildState {
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME)
+            .unwrap()
+            .children
+            .iter()
+            .find(|c| c.name == CHILD_2)
+            .unwrap()
+            .state()
+    })
+    .await
+}
+
+/// fault the child because of I/O errors and mark it closed again, as is
+/// done when the connection to a faulted child is re-established
+async fn flap(ms: &MayastorTest<'_>) {
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.fault_child(CHILD_2, Reason::IoError).await.unwrap();
+        nexus
+            .children
+            .iter()
+            .find(|c| c.name == CHILD_2)
+            .unwrap()
+            .state
+            .store(ChildState::Closed);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn child_breaker() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.add_child(CHILD_2, false).await.unwrap();
+    })
+    .await;
+
+    let mut ticker = tokio::time::interval(Duration::from_millis(100));
+
+    // every failure but the last one is followed by a rebuild of the child
+    for _ in 1 .. MAX_FAILURES {
+        while child_state(&ms).await != ChildState::Open {
+            ticker.tick().await;
+        }
+        flap(&ms).await;
+        ms.spawn(async {
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.online_child(CHILD_2).await.unwrap();
+            let child = nexus.children.iter().find(|c| c.name == CHILD_2);
+            assert!(!child.unwrap().to_grpc().breaker_open);
+        })
+        .await;
+    }
+
+    while child_state(&ms).await != ChildState::Open {
+        ticker.tick().await;
+    }
+    flap(&ms).await;
+
+    // the breaker has tripped, the child is neither onlined nor rebuilt
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.online_child(CHILD_2).await.is_err());
+        assert!(nexus.start_rebuild(CHILD_2).await.is_err());
+
+        let child = nexus.children.iter().find(|c| c.name == CHILD_2);
+        let child = child.unwrap().to_grpc();
+        assert!(child.breaker_open);
+        assert!(child.breaker_cooldown_ms > 0);
+    })
+    .await;
+    assert_eq!(child_state(&ms).await, ChildState::Closed);
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -1,3 +1,4 @@
+use futures::{future, stream, StreamExt, TryStreamExt};
 use mayastor::core::{BdevHandle, CoreError};
 
 pub async fn write_some(
@@ -40,3 +41,52 @@ pub async fn read_some(
     assert_eq!(slice[512], 0);
     Ok(())
 }
+
+/// write and read back ios blocks of io_size bytes, keeping qd IOs
+/// outstanding at a time
+pub async fn write_read_qd(
+    nexus_name: &str,
+    qd: usize,
+    ios: u64,
+    io_size: u64,
+) -> Result<(), CoreError> {
+    let h = BdevHandle::open(nexus_name, true, false)?;
+    let h = &h;
+
+    stream::iter(0 .. ios)
+        .map(|i| async move {
+            let mut buf =
+                h.dma_malloc(io_size).expect("failed to allocate buffer");
+            buf.fill(i as u8);
+            h.write_at(i * io_size, &buf).await?;
+
+            buf.fill(0);
+            h.read_at(i * io_size, &mut buf).await?;
+            assert!(buf.as_slice().iter().all(|b| *b == i as u8));
+            Ok(())
+        })
+        .buffer_unordered(qd)
+        .try_for_each(|_| future::ok(()))
+        .await
+}
+
+/// fill the first MiB of the bdev with the given value
+pub async fn write_mib(name: &str, fill: u8) {
+    let h = BdevHandle::open(name, true, false).unwrap();
+    let mut buf = h.dma_malloc(1024 * 1024).unwrap();
+    buf.fill(fill);
+    h.write_at(0, &buf).await.unwrap();
+}
+
+/// check that the first MiB of the bdev holds the given value
+pub async fn verify_mib(name: &str, fill: u8) {
+    let h = BdevHandle::open(name, false, false).unwrap();
+    let mut buf = h.dma_malloc(1024 * 1024).unwrap();
+    h.read_at(0, &mut buf).await.unwrap();
+    assert!(
+        buf.as_slice().iter().all(|b| *b == fill),
+        "{} does not hold {}",
+        name,
+        fill
+    );
+}
@@ -4,7 +4,7 @@ use crossbeam::crossbeam_channel::bounded;
 use std::future::Future;
 use tokio::sync::oneshot::channel;
 
-use crate::common::mayastor_test_init;
+use crate::common::mayastor_test_init_with_format;
 use mayastor::core::{
     mayastor_env_stop,
     MayastorCliArgs,
@@ -13,8 +13,25 @@ use mayastor::core::{
     Reactors,
     GLOBAL_RC,
 };
+use once_cell::sync::OnceCell;
 use std::time::Duration;
 
+/// reactor mask of the mayastor instance started in this process, the EAL,
+/// the reactors and the logger are process wide so only one can be started
+static INSTANCE: OnceCell<String> = OnceCell::new();
+
+/// whether the two reactor masks share a core, masks which do not parse are
+/// taken to overlap
+fn masks_overlap(a: &str, b: &str) -> bool {
+    let parse = |mask: &str| {
+        u128::from_str_radix(mask.trim_start_matches("0x"), 16)
+    };
+    match (parse(a), parse(b)) {
+        (Ok(a), Ok(b)) => a & b != 0,
+        _ => true,
+    }
+}
+
 /// Mayastor test structure that simplifies sending futures. Mayastor has
 /// its own reactor, which is not tokio based, so we need to handle properly
 #[derive(Debug)]
@@ -68,8 +85,34 @@ impl<'a> MayastorTest<'a> {
     }
 
     pub fn new(args: MayastorCliArgs) -> MayastorTest<'static> {
+        Self::try_new(args).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// starts mayastor as `new` does, failing if an instance has already
+    /// been started in this process: a second instance cannot run alongside
+    /// the first, not even on other cores, nor be started once it is stopped
+    pub fn try_new(
+        args: MayastorCliArgs,
+    ) -> Result<MayastorTest<'static>, String> {
+        if let Some(running) = INSTANCE.get() {
+            return Err(if masks_overlap(running, &args.reactor_mask) {
+                format!(
+                    "reactor mask {} overlaps with the reactor mask {} of \
+                     the mayastor instance of this process",
+                    args.reactor_mask, running
+                )
+            } else {
+                format!(
+                    "a mayastor instance with reactor mask {} has already \
+                     been started, only one can be started per process",
+                    running
+                )
+            });
+        }
+        INSTANCE.set(args.reactor_mask.clone()).ok();
+
         let (tx, rx) = bounded(1);
-        mayastor_test_init();
+        mayastor_test_init_with_format(args.log_format);
         let thdl = std::thread::Builder::new()
             .name("mayastor_master".into())
             .spawn(move || {
@@ -81,10 +124,10 @@ impl<'a> MayastorTest<'a> {
             .unwrap();
 
         let reactor = rx.recv().unwrap();
-        MayastorTest {
+        Ok(MayastorTest {
             reactor,
             thdl: Some(thdl),
-        }
+        })
     }
 
     /// explicitly stop mayastor
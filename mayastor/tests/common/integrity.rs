@@ -0,0 +1,31 @@
+//! Helpers for the tests of the protection information and the checksums of
+//! lvols.
+//!
+//! There is no fault injection which corrupts the data of an IO, so the
+//! tests corrupt a block by writing to the bdev below the PI bdev directly,
+//! which the PI bdev then finds when the block is read through it.
+
+use mayastor::{core::BdevHandle, lvs::Lvs};
+use rpc::mayastor::CreatePoolRequest;
+
+/// create a pool on the disk with an integrity reserve, which holds the
+/// protection information and the checksums of its lvols
+pub async fn create_pool(name: &str, disk: &str) -> Lvs {
+    Lvs::create_or_import(CreatePoolRequest {
+        name: name.into(),
+        disks: vec![disk.into()],
+        integrity_reserve: 25,
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// flip a bit of the block of the bdev at the given offset
+pub async fn corrupt_block(name: &str, offset: u64, block_len: u64) {
+    let hdl = BdevHandle::open(name, true, false).unwrap();
+    let mut buf = hdl.dma_malloc(block_len).unwrap();
+    hdl.read_at(offset, &mut buf).await.unwrap();
+    buf.as_mut_slice()[block_len as usize / 2] ^= 0x01;
+    hdl.write_at(offset, &buf).await.unwrap();
+}
@@ -15,13 +15,14 @@ use tracing::{error, info, trace};
 
 use mayastor::{
     core::{MayastorEnvironment, Mthread},
-    logger,
+    logger::{self, LogFormat},
     rebuild::{ClientOperations, RebuildJob, RebuildState},
 };
 
 pub mod bdev_io;
 pub mod compose;
 pub mod error_bdev;
+pub mod integrity;
 
 pub use compose::MayastorTest;
 
@@ -117,6 +118,10 @@ macro_rules! test_init {
 }
 
 pub fn mayastor_test_init() {
+    mayastor_test_init_with_format(LogFormat::Text);
+}
+
+pub fn mayastor_test_init_with_format(log_format: LogFormat) {
     fn binary_present(name: &str) -> Result<bool, std::env::VarError> {
         std::env::var("PATH").map(|paths| {
             paths
@@ -133,7 +138,7 @@ pub fn mayastor_test_init() {
                 panic!("binary: {} not present in path", binary);
             }
         });
-    logger::init("info,mayastor=DEBUG");
+    logger::init_with_format("info,mayastor=DEBUG", log_format);
     mayastor::CPS_INIT!();
 }
 
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{BdevHandle, MayastorCliArgs},
+    rebuild::{RebuildJob, RebuildState},
+    subsys::Config,
+};
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "reload_nexus";
+static CHILD_1: &str = "malloc:///reload0?blk_size=512&size_mb=24";
+static CHILD_2: &str = "malloc:///reload1?blk_size=512&size_mb=24";
+
+static YAML_CONFIG_FILE: &str = "/tmp/config_reload.yaml";
+
+const NEXUS_SIZE_MB: u64 = 16;
+const IO_SIZE: u64 = 64 * 1024;
+
+/// write to the start of the nexus while it is being rebuilt
+async fn write_nexus() {
+    let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+    let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+    buf.fill(0x5a);
+    for i in 0 .. 16 {
+        hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn config_reload() {
+    // the rebuild crawls at 1 MiB/s to begin with
+    let mut config = Config::default();
+    config.rebuild_opts.rate_limit_mbps = 1;
+    config.write(YAML_CONFIG_FILE).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        mayastor_config: Some(YAML_CONFIG_FILE.to_string()),
+        ..Default::default()
+    });
+
+    let rebuild = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NEXUS_SIZE_MB << 20,
+                None,
+                &[CHILD_1.to_string()],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.add_child(CHILD_2, true).await.unwrap();
+            assert_eq!(RebuildJob::opts().rate_limit_mbps, 1);
+
+            // nothing changed in the file
+            let report = Config::get().reload().unwrap();
+            assert!(report.applied.is_empty());
+            assert!(report.restart_required.is_empty());
+
+            nexus.start_rebuild(CHILD_2).await.unwrap()
+        })
+        .await;
+
+    tokio::time::delay_for(Duration::from_secs(1)).await;
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let progress = nexus.get_rebuild_progress(CHILD_2).unwrap().progress;
+        assert!(progress < 50, "progress {}", progress);
+        write_nexus().await;
+    })
+    .await;
+
+    // lift the rate limit, and change a setting which needs a restart
+    let mut config = Config::default();
+    config.rebuild_opts.rate_limit_mbps = 0;
+    config.bdev_opts.bdev_io_pool_size *= 2;
+    config.write(YAML_CONFIG_FILE).unwrap();
+    kill(Pid::this(), Signal::SIGHUP).unwrap();
+
+    let start = Instant::now();
+    loop {
+        let limit = ms
+            .spawn(async {
+                write_nexus().await;
+                RebuildJob::opts().rate_limit_mbps
+            })
+            .await;
+        if limit == 0 {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+    }
+
+    // the rebuild finishes long before it would have at 1 MiB/s
+    let state = ms.spawn(async { rebuild.await.unwrap() }).await;
+    assert_eq!(state, RebuildState::Completed);
+    assert!(
+        start.elapsed() < Duration::from_secs(NEXUS_SIZE_MB / 2),
+        "rebuilt too slowly: {:?}",
+        start.elapsed()
+    );
+
+    ms.spawn(async {
+        // the settings which were applied no longer count as changed
+        let report = Config::get().reload().unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(report.restart_required, vec!["bdev_opts".to_string()]);
+        assert_eq!(Config::get().refresh().rebuild_opts.rate_limit_mbps, 0);
+
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[YAML_CONFIG_FILE.to_string()]);
+}
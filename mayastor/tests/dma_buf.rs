@@ -0,0 +1,55 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, DmaBuf, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///dmabuf?size_mb=64";
+
+#[tokio::test]
+async fn dma_buf() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // the memory allocated beyond the length for the alignment is left out
+    ms.spawn(async {
+        let mut a = DmaBuf::new(1000, 4096).unwrap();
+        let mut b = DmaBuf::new(1000, 4096).unwrap();
+        assert_eq!(a.as_slice().len(), 1000);
+        a.fill(0xff);
+        b.fill(0xff);
+        assert!(a.eq_bytes(&b));
+        assert_eq!(a.checksum_crc32(), b.checksum_crc32());
+        let expected = crc::crc32::checksum_ieee(&[0xff; 1000]);
+        assert_eq!(a.checksum_crc32(), expected);
+
+        b.as_mut_slice()[999] = 0;
+        assert!(!a.eq_bytes(&b));
+        assert_ne!(a.checksum_crc32(), b.checksum_crc32());
+
+        let mut c = DmaBuf::new(1024, 4096).unwrap();
+        c.fill(0xff);
+        assert!(!a.eq_bytes(&c));
+    })
+    .await;
+
+    // and the data read back is checked against that written
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open("dmabuf", true, false).unwrap();
+        let mut write = hdl.dma_malloc(4096).unwrap();
+        write.fill(0xa5);
+        let checksum = write.checksum_crc32();
+        hdl.write_at(0, &write).await.unwrap();
+
+        let mut read = hdl.dma_malloc(4096).unwrap();
+        hdl.read_at(0, &mut read).await.unwrap();
+        assert!(read.eq_bytes(&write));
+        assert_eq!(read.checksum_crc32(), checksum);
+
+        hdl.close();
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    events::{self, Event, PoolState, Subscription},
+    lvs::Lvs,
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "events-pool";
+static POOL_DISK: &str = "malloc:///events-disk?size_mb=64";
+static LVOL_NAME: &str = "events-lvol";
+
+/// the events received so far
+fn received(sub: &Subscription) -> Vec<Event> {
+    sub.receiver().try_iter().collect()
+}
+
+#[tokio::test]
+async fn events() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    let sub = events::subscribe();
+
+    let uuid = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![POOL_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let lvol =
+                pool.create_lvol(LVOL_NAME, 8 << 20, true).await.unwrap();
+            let uuid = lvol.uuid();
+            lvol.destroy().await.unwrap();
+            uuid
+        })
+        .await;
+
+    // the events carry the resource and its state, without having to look
+    // them up
+    let events = received(&sub);
+    let position = |event: &Event| {
+        events
+            .iter()
+            .position(|e| e == event)
+            .unwrap_or_else(|| panic!("no event {:?} in {:?}", event, events))
+    };
+    let online = position(&Event::PoolStateChanged {
+        name: POOL_NAME.into(),
+        old: PoolState::Absent,
+        new: PoolState::Online,
+    });
+    let created = position(&Event::LvolCreated {
+        pool: POOL_NAME.into(),
+        name: LVOL_NAME.into(),
+        uuid: uuid.clone(),
+    });
+    let destroyed = position(&Event::LvolDestroyed {
+        pool: POOL_NAME.into(),
+        name: LVOL_NAME.into(),
+        uuid,
+    });
+    assert!(online < created && created < destroyed);
+
+    // the bdevs come and go as the notifications of SPDK are read
+    let mut events = Vec::new();
+    for _ in 0 .. 20 {
+        events.extend(received(&sub));
+        if events.contains(&Event::BdevUnregistered {
+            name: LVOL_NAME.into(),
+        }) {
+            break;
+        }
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+    }
+    assert!(events.contains(&Event::BdevUnregistered {
+        name: LVOL_NAME.into(),
+    }));
+
+    // a subscription which is not read from drops the events that do not
+    // fit its queue, and counts them
+    let slow = events::subscribe();
+    for _ in 0 ..= events::EVENT_QUEUE_DEPTH {
+        events::publish(Event::BdevRegistered {
+            name: "events-flood".into(),
+        });
+    }
+    assert!(slow.dropped() >= 1);
+    assert_eq!(received(&slow).len(), events::EVENT_QUEUE_DEPTH);
+    drop(slow);
+    received(&sub);
+
+    ms.spawn(async {
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+    assert!(received(&sub).contains(&Event::PoolStateChanged {
+        name: POOL_NAME.into(),
+        old: PoolState::Online,
+        new: PoolState::Absent,
+    }));
+}
@@ -0,0 +1,55 @@
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{IoPool, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "PoolNexus";
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+/// a pool raised well above the needs of the queue depth, with the same
+/// workload as in io_pool_exhausted the pool never runs out
+const POOL_SIZE: u32 = 8192;
+const CACHE_SIZE: u32 = 256;
+
+const QUEUE_DEPTH: usize = 128;
+const NUM_IOS: u64 = 4096;
+const IO_SIZE: u64 = 4096;
+
+#[tokio::test]
+async fn io_pool() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        bdev_io_pool_size: Some(POOL_SIZE),
+        bdev_io_cache_size: Some(CACHE_SIZE),
+        ..Default::default()
+    });
+
+    let stats = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NUM_IOS * IO_SIZE,
+                None,
+                &[CHILD_1.to_string(), CHILD_2.to_string()],
+            )
+            .await
+            .unwrap();
+            bdev_io::write_read_qd(NEXUS_NAME, QUEUE_DEPTH, NUM_IOS, IO_SIZE)
+                .await
+                .unwrap();
+            IoPool::stats()
+        })
+        .await;
+
+    assert_eq!(stats.pool_size, POOL_SIZE);
+    assert_eq!(stats.cache_size, CACHE_SIZE);
+    assert_eq!(stats.exhausted, 0, "the bdev IO pool should not run out");
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
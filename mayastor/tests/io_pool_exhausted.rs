@@ -0,0 +1,80 @@
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{IoPool, MayastorCliArgs},
+    subsys::BdevOpts,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ExhaustedNexus";
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+/// a pool far too small for the queue depth
+const POOL_SIZE: u32 = 64;
+const CACHE_SIZE: u32 = 8;
+
+const QUEUE_DEPTH: usize = 128;
+const NUM_IOS: u64 = 4096;
+const IO_SIZE: u64 = 4096;
+
+#[tokio::test]
+async fn io_pool_exhausted() {
+    // pools which do not fit are rejected
+    let opts = BdevOpts {
+        bdev_io_pool_size: 1 << 20,
+        bdev_io_cache_size: 512,
+    };
+    assert!(IoPool::check(&opts, 1024, 1).is_err());
+    let opts = BdevOpts {
+        bdev_io_pool_size: 1024,
+        bdev_io_cache_size: 512,
+    };
+    assert!(IoPool::check(&opts, 1024, 2).is_err());
+    let opts = BdevOpts {
+        bdev_io_pool_size: POOL_SIZE,
+        bdev_io_cache_size: CACHE_SIZE,
+    };
+    assert!(IoPool::check(&opts, 1024, 1).is_ok());
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        bdev_io_pool_size: Some(POOL_SIZE),
+        bdev_io_cache_size: Some(CACHE_SIZE),
+        ..Default::default()
+    });
+
+    // the IOs stall when the pool runs out but all of them complete
+    let stats = ms
+        .spawn(async {
+            let stats = IoPool::stats();
+            assert_eq!(stats.pool_size, POOL_SIZE);
+            assert_eq!(stats.cache_size, CACHE_SIZE);
+            assert_eq!(stats.exhausted, 0);
+
+            nexus_create(
+                NEXUS_NAME,
+                NUM_IOS * IO_SIZE,
+                None,
+                &[CHILD_1.to_string(), CHILD_2.to_string()],
+            )
+            .await
+            .unwrap();
+            bdev_io::write_read_qd(NEXUS_NAME, QUEUE_DEPTH, NUM_IOS, IO_SIZE)
+                .await
+                .unwrap();
+            IoPool::stats()
+        })
+        .await;
+
+    println!(
+        "bdev IO pool of {} exhausted {} times",
+        POOL_SIZE, stats.exhausted
+    );
+    assert!(stats.exhausted > 0);
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
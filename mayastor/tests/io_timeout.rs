@@ -0,0 +1,119 @@
+use std::{ffi::CString, time::Duration};
+
+use common::MayastorTest;
+use futures::{channel::oneshot, future::select};
+use futures_timer::Delay;
+use mayastor::{
+    core::{Bdev, BdevHandle, CoreError, MayastorCliArgs},
+    ffihelper::{cb_arg, done_cb},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use spdk_sys::{create_delay_disk, delete_delay_disk};
+
+pub mod common;
+
+static BASE_BDEV: &str = "malloc:///base?size_mb=64";
+static DELAY_BDEV: &str = "delayed";
+
+/// latency of every IO to the delay bdev in usec
+const LATENCY_US: u64 = 2_000_000;
+
+#[tokio::test]
+async fn io_timeout() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let base = bdev_create(BASE_BDEV).await.unwrap();
+        let base = CString::new(base).unwrap();
+        let name = CString::new(DELAY_BDEV).unwrap();
+        let errno = unsafe {
+            create_delay_disk(
+                base.as_ptr(),
+                name.as_ptr(),
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+            )
+        };
+        assert_eq!(errno, 0);
+        assert!(Bdev::lookup_by_name(DELAY_BDEV).is_some());
+    })
+    .await;
+
+    // the IO is aborted once the timeout expires
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xaa);
+
+        let timeout = Duration::from_millis(500);
+        match hdl.write_at_timeout(0, &buf, timeout).await {
+            Err(CoreError::WriteTimedOut {
+                timeout: t, ..
+            }) => assert_eq!(t, timeout),
+            other => panic!("write should have timed out: {:?}", other),
+        }
+        match hdl.read_at_timeout(0, &mut buf, timeout).await {
+            Err(CoreError::ReadTimedOut {
+                ..
+            }) => {}
+            other => panic!("read should have timed out: {:?}", other),
+        }
+    })
+    .await;
+
+    // an IO which is no longer waited for completes without its completion
+    // having anywhere to go
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xaa);
+
+        let write = Box::pin(hdl.write_at(0, &buf));
+        select(write, Delay::new(Duration::from_millis(500))).await;
+        Delay::new(Duration::from_micros(2 * LATENCY_US)).await;
+        assert_eq!(hdl.inflight(), 0);
+    })
+    .await;
+
+    // a handle with IO in flight is closed once the IO has been aborted
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let buf = hdl.dma_malloc(4096).unwrap();
+
+        let write = Box::pin(hdl.write_at(0, &buf));
+        select(write, Delay::new(Duration::from_millis(100))).await;
+        assert_eq!(hdl.inflight(), 1);
+        hdl.drain_and_close().await;
+    })
+    .await;
+
+    // an IO completing in time is not affected by the timeout, and the
+    // aborted IOs have not left anything behind which gets in the way
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0x55);
+
+        let timeout = Duration::from_secs(10);
+        hdl.write_at_timeout(0, &buf, timeout).await.unwrap();
+        buf.fill(0);
+        hdl.read_at_timeout(0, &mut buf, timeout).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x55));
+        assert_eq!(hdl.inflight(), 0);
+        hdl.close();
+    })
+    .await;
+
+    ms.spawn(async {
+        let bdev = Bdev::lookup_by_name(DELAY_BDEV).unwrap();
+        let (s, r) = oneshot::channel::<i32>();
+        unsafe {
+            delete_delay_disk(bdev.as_ptr(), Some(done_cb), cb_arg(s));
+        }
+        assert_eq!(r.await.unwrap(), 0);
+        bdev_destroy(BASE_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,32 @@
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    logger::LogFormat,
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use structopt::StructOpt;
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///logged?size_mb=8";
+
+#[tokio::test]
+async fn log_format() {
+    assert_eq!(MayastorCliArgs::default().log_format, LogFormat::Text);
+    let args =
+        MayastorCliArgs::from_iter(&["mayastor", "--log-format", "JSON"]);
+    assert_eq!(args.log_format, LogFormat::Json);
+    let invalid = &["mayastor", "--log-format", "xml"];
+    assert!(MayastorCliArgs::from_iter_safe(invalid).is_err());
+
+    // mayastor runs with its events logged as json
+    let ms = MayastorTest::new(MayastorCliArgs {
+        log_format: args.log_format,
+        ..Default::default()
+    });
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,80 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mayastor::logger::{RateLimit, RATE_LIMITED};
+
+/// how often the error is logged
+const FLOOD: u64 = 10_000;
+/// period of the rate limit in the test
+const PERIOD: Duration = Duration::from_millis(200);
+
+/// collects the formatted log output
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+fn flood(child: &str) {
+    for _ in 0 .. FLOOD {
+        tracing::error!(target: RATE_LIMITED, "child {} read error", child);
+    }
+}
+
+#[test]
+fn log_rate_limit() {
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = RateLimit::new(
+        tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish(),
+        PERIOD,
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        // a flood of the same error is logged once per period, other
+        // errors and events without the target are not affected
+        flood("child1");
+        flood("child2");
+        for i in 0 .. 3 {
+            tracing::error!("unrelated error {}", i);
+        }
+        let lines = output.lines();
+        assert_eq!(lines.len(), 5, "{:#?}", lines);
+        assert!(lines[0].contains("child child1 read error"));
+        assert!(lines[1].contains("child child2 read error"));
+
+        // the repeats are summarised once the period has passed
+        std::thread::sleep(PERIOD);
+        flood("child1");
+        let lines = output.lines();
+        assert_eq!(lines.len(), 7, "{:#?}", lines);
+        let summary = format!("{} more occurrences in last ", FLOOD - 1);
+        assert!(lines[5].contains(&summary), "{}", lines[5]);
+        assert!(lines[5].ends_with(": child child1 read error"));
+        assert!(lines[6].contains("child child1 read error"));
+    });
+}
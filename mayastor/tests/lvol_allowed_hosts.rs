@@ -0,0 +1,103 @@
+use std::process::Command;
+
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Share},
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "hosts-pool";
+static POOL_DISK: &str = "malloc:///hosts-disk?size_mb=64";
+static READ_FILE: &str = "/tmp/allowed-hosts-read";
+static HOST_A: &str = "nqn.2019-05.io.openebs:host-a";
+static HOST_B: &str = "nqn.2019-05.io.openebs:host-b";
+
+const MB: u64 = 1024 * 1024;
+
+/// read from the share as the given host, returns if the read succeeded
+fn read_as(uri: &str, host: &str) -> bool {
+    Command::new("../target/debug/initiator")
+        .args(&[&format!("{}?hostnqn={}", uri, host), "read", READ_FILE])
+        .status()
+        .expect("failed to run the initiator")
+        .success()
+}
+
+#[tokio::test]
+async fn lvol_allowed_hosts() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let uri = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![POOL_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+
+            // only shared lvols have hosts to allow
+            assert!(matches!(
+                lvol.set_allowed_hosts(&[HOST_A.into()]).await,
+                Err(Error::Invalid {
+                    ..
+                })
+            ));
+
+            let uri = lvol.share_nvmf().await.unwrap();
+            assert!(lvol.allowed_hosts().is_empty());
+            assert!(matches!(
+                lvol.set_allowed_hosts(&["host-a".into()]).await,
+                Err(Error::LvolShare {
+                    ..
+                })
+            ));
+            lvol.set_allowed_hosts(&[HOST_A.into()]).await.unwrap();
+            assert_eq!(lvol.allowed_hosts(), vec![HOST_A]);
+            uri
+        })
+        .await;
+
+    // only the allowed host connects
+    assert!(read_as(&uri, HOST_A));
+    assert!(!read_as(&uri, HOST_B));
+
+    ms.spawn(async {
+        let lvol = Lvs::lookup(POOL_NAME)
+            .unwrap()
+            .lvols()
+            .unwrap()
+            .find(|l| l.name() == "vol")
+            .unwrap();
+        lvol.set_allowed_hosts(&[]).await.unwrap();
+        assert!(lvol.allowed_hosts().is_empty());
+    })
+    .await;
+
+    // any host does once there are none
+    assert!(read_as(&uri, HOST_B));
+
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool.lvols().unwrap().find(|l| l.name() == "vol").unwrap();
+
+        // the hosts do not outlive the share
+        lvol.set_allowed_hosts(&[HOST_A.into()]).await.unwrap();
+        lvol.unshare().await.unwrap();
+        assert!(lvol.allowed_hosts().is_empty());
+        lvol.share_nvmf().await.unwrap();
+        assert!(lvol.allowed_hosts().is_empty());
+        lvol.unshare().await.unwrap();
+
+        lvol.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[READ_FILE.into()]);
+}
@@ -0,0 +1,118 @@
+use common::{integrity, MayastorTest};
+use mayastor::{
+    bdev::{pi_lookup, PiFormat},
+    core::{
+        Bdev,
+        BdevHandle,
+        ChecksumAlgorithm,
+        CoreError,
+        MayastorCliArgs,
+        Protocol,
+        Share,
+    },
+    lvs::{Lvs, PropValue},
+};
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/checksum-disk.img";
+static POOL_DISK: &str = "aio:///tmp/checksum-disk.img";
+static POOL_NAME: &str = "checksum-pool";
+static LVOL_NAME: &str = "checksum-lvol";
+static PI_NAME: &str = "checksum-lvol-pi";
+
+const BLOCK_LEN: u64 = 512;
+/// number of blocks written at once
+const BLOCKS: u64 = 16;
+
+#[tokio::test]
+async fn lvol_checksum() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // a checksummed lvol is shared through a PI bdev in the checksum format,
+    // which keeps the block size of the lvol, CRC32C is the default
+    ms.spawn(async {
+        let pool = integrity::create_pool(POOL_NAME, POOL_DISK).await;
+        let lvol = pool
+            .create_lvol(LVOL_NAME, 8 * 1024 * 1024, false)
+            .await
+            .unwrap();
+        assert_eq!(lvol.pi_format().await, None);
+        lvol.set(PropValue::Checksum(true)).await.unwrap();
+        assert!(lvol.is_checksummed().await);
+        assert_eq!(
+            lvol.pi_format().await,
+            Some(PiFormat::Checksum(ChecksumAlgorithm::Crc32c))
+        );
+
+        lvol.share_nvmf().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        assert_eq!(
+            pi_lookup(PI_NAME).unwrap().format,
+            PiFormat::Checksum(ChecksumAlgorithm::Crc32c)
+        );
+        let bdev = Bdev::lookup_by_name(PI_NAME).unwrap();
+        assert_eq!(bdev.block_len() as u64, BLOCK_LEN);
+        assert_eq!(bdev.md_len(), 0);
+
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        for (i, lba) in [0, 16, 200].iter().enumerate() {
+            buf.fill(i as u8 + 1);
+            hdl.write_at(lba * BLOCK_LEN, &buf).await.unwrap();
+        }
+        for (i, lba) in [0, 16, 200].iter().enumerate() {
+            hdl.read_at(lba * BLOCK_LEN, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == i as u8 + 1));
+        }
+        // blocks never written are not checked
+        hdl.read_at(1000 * BLOCK_LEN, &mut buf).await.unwrap();
+    })
+    .await;
+
+    // a block corrupted below the PI bdev is detected on read, also after
+    // the pool has been exported and imported again
+    ms.spawn(async {
+        integrity::corrupt_block(LVOL_NAME, 20 * BLOCK_LEN, BLOCK_LEN).await;
+
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        assert!(matches!(
+            hdl.read_at(16 * BLOCK_LEN, &mut buf).await,
+            Err(CoreError::GuardCheckFailed { .. })
+        ));
+        hdl.read_at(0, &mut buf).await.unwrap();
+        drop(hdl);
+
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        pool.export().await.unwrap();
+        assert!(pi_lookup(PI_NAME).is_none());
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+        let lvol =
+            pool.lvols().unwrap().find(|l| l.name() == LVOL_NAME).unwrap();
+        assert!(lvol.is_checksummed().await);
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        hdl.read_at(200 * BLOCK_LEN, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 3));
+        assert!(matches!(
+            hdl.read_at(16 * BLOCK_LEN, &mut buf).await,
+            Err(CoreError::GuardCheckFailed { .. })
+        ));
+
+        // rewriting the block repairs it
+        buf.fill(2);
+        hdl.write_at(16 * BLOCK_LEN, &buf).await.unwrap();
+        hdl.read_at(16 * BLOCK_LEN, &mut buf).await.unwrap();
+        drop(hdl);
+
+        lvol.unshare().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
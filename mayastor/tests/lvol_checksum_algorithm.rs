@@ -0,0 +1,166 @@
+use common::{integrity, MayastorTest};
+use mayastor::{
+    bdev::{pi_lookup, PiFormat},
+    core::{
+        Bdev,
+        BdevHandle,
+        ChecksumAlgorithm,
+        CoreError,
+        MayastorCliArgs,
+        Protocol,
+        Share,
+    },
+    lvs::{Lvs, PropValue},
+};
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/checksum-algorithm-disk.img";
+static POOL_DISK: &str = "aio:///tmp/checksum-algorithm-disk.img";
+static POOL_NAME: &str = "checksum-algorithm-pool";
+
+const BLOCK_LEN: u64 = 512;
+/// number of blocks written at once
+const BLOCKS: u64 = 16;
+/// blocks of the lvols written, the last one shares its block of checksums
+/// with none of the others for every algorithm
+const LBAS: [u64; 3] = [0, 40, 900];
+
+const ALGORITHMS: [ChecksumAlgorithm; 3] = [
+    ChecksumAlgorithm::Crc32c,
+    ChecksumAlgorithm::XxHash64,
+    ChecksumAlgorithm::Sha256,
+];
+
+fn lvol_name(algorithm: ChecksumAlgorithm) -> String {
+    format!("checksum-{}", algorithm)
+}
+
+fn pi_name(algorithm: ChecksumAlgorithm) -> String {
+    format!("checksum-{}-pi", algorithm)
+}
+
+/// read the blocks written back through the PI bdev of the lvol
+async fn check_lvol(algorithm: ChecksumAlgorithm) {
+    let hdl = BdevHandle::open(&pi_name(algorithm), true, false).unwrap();
+    let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+    for (i, lba) in LBAS.iter().enumerate() {
+        hdl.read_at(lba * BLOCK_LEN, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == i as u8 + 1));
+    }
+}
+
+#[tokio::test]
+async fn lvol_checksum_algorithm() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // the digests of the algorithms match their reference values
+    ms.spawn(async {
+        assert_eq!(
+            ChecksumAlgorithm::Crc32c.digest(b"123456789"),
+            0xe306_9283u32.to_be_bytes().to_vec()
+        );
+        assert_eq!(
+            ChecksumAlgorithm::XxHash64.digest(b"abc"),
+            0x44bc_2cf5_ad77_0999u64.to_be_bytes().to_vec()
+        );
+        assert_eq!(
+            &ChecksumAlgorithm::Sha256.digest(b"abc")[.. 8],
+            &[0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea]
+        );
+        assert_eq!(
+            "sha256".parse::<PiFormat>().unwrap(),
+            PiFormat::Checksum(ChecksumAlgorithm::Sha256)
+        );
+        assert_eq!(
+            "crc".parse::<PiFormat>().unwrap(),
+            PiFormat::Checksum(ChecksumAlgorithm::Crc32c)
+        );
+        assert!("md5".parse::<PiFormat>().is_err());
+    })
+    .await;
+
+    // every lvol is shared through a PI bdev computing the checksums with
+    // the algorithm chosen for it
+    ms.spawn(async {
+        let pool = integrity::create_pool(POOL_NAME, POOL_DISK).await;
+
+        for algorithm in ALGORITHMS.iter() {
+            let lvol = pool
+                .create_lvol(&lvol_name(*algorithm), 8 * 1024 * 1024, false)
+                .await
+                .unwrap();
+            lvol.set(PropValue::Checksum(true)).await.unwrap();
+            lvol.set(PropValue::ChecksumAlgorithm(*algorithm))
+                .await
+                .unwrap();
+            assert_eq!(lvol.checksum_algorithm().await, *algorithm);
+            lvol.share_nvmf().await.unwrap();
+
+            let pi = pi_lookup(&pi_name(*algorithm)).unwrap();
+            assert_eq!(pi.format, PiFormat::Checksum(*algorithm));
+            let bdev = Bdev::lookup_by_name(&pi_name(*algorithm)).unwrap();
+            assert_eq!(bdev.block_len() as u64, BLOCK_LEN);
+
+            let hdl =
+                BdevHandle::open(&pi_name(*algorithm), true, false).unwrap();
+            let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+            for (i, lba) in LBAS.iter().enumerate() {
+                buf.fill(i as u8 + 1);
+                hdl.write_at(lba * BLOCK_LEN, &buf).await.unwrap();
+            }
+            drop(hdl);
+            check_lvol(*algorithm).await;
+        }
+    })
+    .await;
+
+    // the algorithm is stored with the lvol, so the checksums written before
+    // exporting the pool are verified with it after importing the pool, and
+    // a block corrupted below the PI bdev is detected
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        pool.export().await.unwrap();
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+
+        for algorithm in ALGORITHMS.iter() {
+            let lvol = pool
+                .lvols()
+                .unwrap()
+                .find(|l| l.name() == lvol_name(*algorithm))
+                .unwrap();
+            assert_eq!(lvol.checksum_algorithm().await, *algorithm);
+            assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+            assert_eq!(
+                pi_lookup(&pi_name(*algorithm)).unwrap().format,
+                PiFormat::Checksum(*algorithm)
+            );
+            check_lvol(*algorithm).await;
+
+            integrity::corrupt_block(
+                &lvol.name(),
+                LBAS[2] * BLOCK_LEN,
+                BLOCK_LEN,
+            )
+            .await;
+
+            let hdl =
+                BdevHandle::open(&pi_name(*algorithm), true, false).unwrap();
+            let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+            assert!(matches!(
+                hdl.read_at(LBAS[2] * BLOCK_LEN, &mut buf).await,
+                Err(CoreError::GuardCheckFailed { .. })
+            ));
+            hdl.read_at(LBAS[1] * BLOCK_LEN, &mut buf).await.unwrap();
+            drop(hdl);
+
+            lvol.unshare().await.unwrap();
+        }
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,86 @@
+use std::convert::TryFrom;
+
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    core::{Bdev, MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "clone-pool";
+static POOL_DISK: &str = "malloc:///clone-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+fn lookup(name: &str) -> Lvol {
+    Lvol::try_from(Bdev::lookup_by_name(name).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn lvol_clone() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+        bdev_io::write_mib("vol", 0xaa).await;
+        let snapshot = lvol.create_snapshot("vol-snap").await.unwrap();
+        bdev_io::write_mib("vol", 0x55).await;
+
+        // only lvols which are not snapshots can be cloned
+        assert!(matches!(
+            lvol.create_clone("vol-clone").await,
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+        assert!(snapshot.clones().is_empty());
+
+        // the clone starts out with the data of the snapshot and is written
+        // to independently of it and of the lvol the snapshot was taken of
+        let clone = snapshot.create_clone("vol-clone").await.unwrap();
+        assert!(clone.is_clone());
+        assert!(!clone.is_read_only());
+        assert!(!lvol.is_clone());
+        assert_eq!(clone.size(), lvol.size());
+        bdev_io::verify_mib("vol-clone", 0xaa).await;
+        bdev_io::write_mib("vol-clone", 0x11).await;
+        bdev_io::verify_mib("vol-clone", 0x11).await;
+        bdev_io::verify_mib("vol-snap", 0xaa).await;
+        bdev_io::verify_mib("vol", 0x55).await;
+
+        let names = snapshot
+            .clones()
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["vol-clone"]);
+
+        // the clone is shared like any other lvol
+        clone.share_nvmf().await.unwrap();
+        assert_eq!(clone.shared(), Some(Protocol::Nvmf));
+        clone.unshare().await.unwrap();
+
+        // the snapshot is kept as long as the clone reads from it
+        match snapshot.destroy().await {
+            Err(Error::SnapshotInUse {
+                clones, ..
+            }) => assert_eq!(clones, "vol-clone"),
+            r => panic!("snapshot destroyed while in use: {:?}", r),
+        }
+
+        clone.destroy().await.unwrap();
+        lvol.destroy().await.unwrap();
+        lookup("vol-snap").destroy_snapshot().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,218 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{compress_lookup, CompressionAlgorithm},
+    core::{BdevHandle, MayastorCliArgs, Share},
+    lvs::{Lvol, Lvs, PropValue},
+};
+use rand::RngCore;
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/compress-disk.img";
+static POOL_DISK: &str = "aio:///tmp/compress-disk.img";
+static POOL_NAME: &str = "compress-pool";
+static LZ4_NAME: &str = "compress-lz4";
+static ZSTD_NAME: &str = "compress-zstd";
+
+const MB: u64 = 1024 * 1024;
+const CHUNK_LEN: u64 = 4096;
+/// logical size of the lvols
+const SIZE: u64 = 16 * MB;
+/// number of chunks written at once
+const CHUNKS: u64 = 64;
+
+/// a chunk of text which compresses well, different for every chunk
+fn chunk(i: u64) -> Vec<u8> {
+    format!("chunk {} of very compressible data, ", i)
+        .bytes()
+        .cycle()
+        .take(CHUNK_LEN as usize)
+        .collect()
+}
+
+/// share the lvol, which opens the compress bdev on top of it
+async fn open(lvol: &Lvol) -> BdevHandle {
+    lvol.share_nvmf().await.unwrap();
+    let name = format!("{}-compress", lvol.name());
+    BdevHandle::open(&name, true, false).unwrap()
+}
+
+async fn write_all(hdl: &BdevHandle) {
+    let mut buf = hdl.dma_malloc(CHUNKS * CHUNK_LEN).unwrap();
+    for io in 0 .. SIZE / CHUNK_LEN / CHUNKS {
+        for (i, data) in buf
+            .as_mut_slice()
+            .chunks_mut(CHUNK_LEN as usize)
+            .enumerate()
+        {
+            data.copy_from_slice(&chunk(io * CHUNKS + i as u64));
+        }
+        hdl.write_at(io * CHUNKS * CHUNK_LEN, &buf).await.unwrap();
+    }
+}
+
+async fn verify_all(hdl: &BdevHandle) {
+    let mut buf = hdl.dma_malloc(CHUNKS * CHUNK_LEN).unwrap();
+    for io in 0 .. SIZE / CHUNK_LEN / CHUNKS {
+        hdl.read_at(io * CHUNKS * CHUNK_LEN, &mut buf).await.unwrap();
+        for (i, data) in buf.as_slice().chunks(CHUNK_LEN as usize).enumerate()
+        {
+            assert_eq!(data, &chunk(io * CHUNKS + i as u64)[..]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn lvol_compress() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // compressible data takes fewer clusters of the pool than it would
+    // take uncompressed
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool
+            .create_compressed_lvol(LZ4_NAME, SIZE, CompressionAlgorithm::Lz4)
+            .await
+            .unwrap();
+        assert!(lvol.is_thin());
+        assert_eq!(lvol.compression(), Some(CompressionAlgorithm::Lz4));
+
+        let available = pool.available();
+        let hdl = open(&lvol).await;
+        assert_eq!(hdl.get_bdev().block_len() as u64, CHUNK_LEN);
+        assert!(hdl.get_bdev().size_in_bytes() >= SIZE);
+        write_all(&hdl).await;
+        verify_all(&hdl).await;
+
+        let used = available - pool.available();
+        assert!(
+            used / pool.cluster_size() < SIZE / pool.cluster_size(),
+            "{} bytes of compressed data took {} bytes",
+            SIZE,
+            used
+        );
+        let stats = lvol.compression_stats().unwrap();
+        assert_eq!(stats.chunks, SIZE / CHUNK_LEN);
+        assert_eq!(stats.incompressible, 0);
+        assert_eq!(stats.logical_bytes, SIZE);
+        assert!(stats.ratio() > 4.0, "ratio of {}", stats.ratio());
+        hdl.close();
+    })
+    .await;
+
+    // a chunk which does not compress is stored as it is, and a chunk of
+    // zeroes is not stored at all
+    ms.spawn(async {
+        let lvol = Lvs::lookup(POOL_NAME)
+            .unwrap()
+            .lvols()
+            .unwrap()
+            .find(|l| l.name() == LZ4_NAME)
+            .unwrap();
+        let name = format!("{}-compress", LZ4_NAME);
+        let hdl = BdevHandle::open(&name, true, false).unwrap();
+        let before = lvol.compression_stats().unwrap();
+
+        let mut buf = hdl.dma_malloc(CHUNK_LEN).unwrap();
+        rand::thread_rng().fill_bytes(buf.as_mut_slice());
+        let random = buf.as_slice().to_vec();
+        hdl.write_at(0, &buf).await.unwrap();
+        buf.fill(0);
+        hdl.write_at(CHUNK_LEN, &buf).await.unwrap();
+
+        let stats = lvol.compression_stats().unwrap();
+        assert_eq!(stats.incompressible, 1);
+        assert_eq!(stats.chunks, before.chunks - 1);
+        assert!(stats.stored_bytes > before.stored_bytes);
+
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &random[..]);
+        hdl.read_at(CHUNK_LEN, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0));
+        hdl.close();
+
+        // the chunks are found again when the compress bdev is opened again
+        lvol.unshare().await.unwrap();
+        assert!(compress_lookup(&name).is_none());
+        let hdl = open(&lvol).await;
+        assert_eq!(lvol.compression_stats().unwrap(), stats);
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &random[..]);
+        hdl.read_at(2 * CHUNK_LEN, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &chunk(2)[..]);
+        hdl.close();
+
+        // unshared, the lvol is accessed locally through the compress bdev
+        // and never as itself
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.share_uri(), None);
+        lvol.open_local().await.unwrap();
+        assert_eq!(lvol.share_uri().unwrap(), format!("bdev:///{}", name));
+        let hdl = lvol.open_handle(false).await.unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &random[..]);
+        hdl.close();
+
+        // compression is chosen when the lvol is created and is exclusive
+        // of checksums
+        assert!(lvol.set(PropValue::Compressed(false)).await.is_err());
+        assert!(lvol.set(PropValue::Checksum(true)).await.is_err());
+        assert_eq!(lvol.compression(), Some(CompressionAlgorithm::Lz4));
+    })
+    .await;
+
+    // the same goes for zstd
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool
+            .create_compressed_lvol(ZSTD_NAME, SIZE, CompressionAlgorithm::Zstd)
+            .await
+            .unwrap();
+        let available = pool.available();
+        let hdl = open(&lvol).await;
+        write_all(&hdl).await;
+        verify_all(&hdl).await;
+        assert!(available - pool.available() < SIZE);
+        let stats = lvol.compression_stats().unwrap();
+        assert_eq!(stats.chunks, SIZE / CHUNK_LEN);
+        assert!(stats.ratio() > 4.0, "ratio of {}", stats.ratio());
+        assert_eq!(
+            compress_lookup(&format!("{}-compress", ZSTD_NAME))
+                .unwrap()
+                .algorithm(),
+            CompressionAlgorithm::Zstd
+        );
+
+        // a chunk can be overwritten when none of them compresses
+        let mut buf = hdl.dma_malloc(CHUNKS * CHUNK_LEN).unwrap();
+        for io in 0 .. SIZE / CHUNK_LEN / CHUNKS {
+            rand::thread_rng().fill_bytes(buf.as_mut_slice());
+            hdl.write_at(io * CHUNKS * CHUNK_LEN, &buf).await.unwrap();
+        }
+        assert_eq!(
+            lvol.compression_stats().unwrap().incompressible,
+            SIZE / CHUNK_LEN
+        );
+        let mut buf = hdl.dma_malloc(CHUNK_LEN).unwrap();
+        rand::thread_rng().fill_bytes(buf.as_mut_slice());
+        let random = buf.as_slice().to_vec();
+        hdl.write_at(CHUNK_LEN, &buf).await.unwrap();
+        hdl.read_at(CHUNK_LEN, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &random[..]);
+        hdl.close();
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
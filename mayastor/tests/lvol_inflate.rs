@@ -0,0 +1,80 @@
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "inflate-pool";
+static POOL_DISK: &str = "malloc:///inflate-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvol_inflate() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let used = pool.used();
+
+        // a thin lvol takes space from the pool as it is written to, and
+        // all of it once inflated, keeping what was written
+        let lvol = pool.create_lvol("thin", 16 * MB, true).await.unwrap();
+        assert!(lvol.is_thin());
+        assert_eq!(lvol.stats().allocated_bytes, 0);
+        bdev_io::write_mib("thin", 0xaa).await;
+        assert!(pool.used() - used < lvol.size());
+        assert_eq!(lvol.allocated(), pool.used() - used);
+
+        lvol.inflate().await.unwrap();
+        assert!(!lvol.is_thin());
+        let stats = lvol.stats();
+        assert_eq!(stats.allocated_bytes, stats.provisioned_bytes);
+        assert_eq!(pool.used() - used, lvol.size());
+        bdev_io::verify_mib("thin", 0xaa).await;
+
+        // which does nothing to a thick lvol
+        lvol.inflate().await.unwrap();
+        assert_eq!(pool.used() - used, lvol.size());
+
+        // a clone no longer depends on its snapshot once inflated
+        let snapshot = lvol.create_snapshot("thin-snap").await.unwrap();
+        let clone = snapshot.create_clone("thin-clone").await.unwrap();
+        assert!(matches!(
+            snapshot.inflate().await,
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+        clone.inflate().await.unwrap();
+        assert!(!clone.is_clone());
+        assert!(snapshot.clones().is_empty());
+        bdev_io::verify_mib("thin-clone", 0xaa).await;
+        clone.destroy().await.unwrap();
+        lvol.destroy().await.unwrap();
+        snapshot.destroy().await.unwrap();
+
+        // an lvol larger than the space left in the pool can not be inflated
+        let lvol = pool.create_lvol("large", 128 * MB, true).await.unwrap();
+        assert!(matches!(
+            lvol.inflate().await,
+            Err(Error::RepInflateNoSpace {
+                ..
+            })
+        ));
+        assert!(lvol.is_thin());
+        lvol.destroy().await.unwrap();
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
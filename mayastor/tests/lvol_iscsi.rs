@@ -0,0 +1,70 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{CoreError, MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "iscsi-pool";
+static POOL_DISK: &str = "malloc:///iscsi-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvol_iscsi() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+
+        // sharing over iscsi returns the URI of the share, and is idempotent
+        let uri = lvol.share_iscsi().await.unwrap();
+        assert!(uri.starts_with("iscsi://"), "{}", uri);
+        assert_eq!(lvol.shared(), Some(Protocol::Iscsi));
+        assert_eq!(lvol.share_uri().unwrap(), uri);
+        assert_eq!(lvol.share_iscsi().await.unwrap(), uri);
+
+        // an lvol is shared over one protocol at a time
+        match lvol.share_nvmf().await {
+            Err(Error::LvolShare {
+                source:
+                    CoreError::AlreadyShared {
+                        protocol, ..
+                    },
+                ..
+            }) => assert_eq!(protocol, Protocol::Iscsi),
+            r => panic!("shared over both protocols: {:?}", r),
+        }
+        assert_eq!(lvol.share_uri().unwrap(), uri);
+
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+
+        lvol.share_nvmf().await.unwrap();
+        assert!(matches!(
+            lvol.share_iscsi().await,
+            Err(Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    ..
+                },
+                ..
+            })
+        ));
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+
+        lvol.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,91 @@
+use std::{convert::TryFrom, path::Path};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, CoreError, MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "nbd-pool";
+static POOL_DISK: &str = "malloc:///nbd-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+fn lookup(name: &str) -> Lvol {
+    Lvol::try_from(Bdev::lookup_by_name(name).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn lvol_nbd() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let path = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![POOL_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+
+            // exporting over nbd returns the path of the device, and is
+            // idempotent
+            let path = lvol.share_nbd().await.unwrap();
+            assert!(path.starts_with("/dev/nbd"), "{}", path);
+            assert_eq!(lvol.shared(), Some(Protocol::Nbd));
+            assert_eq!(lvol.share_uri().unwrap(), format!("file://{}", path));
+            assert_eq!(lvol.share_nbd().await.unwrap(), path);
+
+            // an lvol is shared over one protocol at a time
+            match lvol.share_nvmf().await {
+                Err(Error::LvolShare {
+                    source:
+                        CoreError::AlreadyShared {
+                            protocol, ..
+                        },
+                    ..
+                }) => assert_eq!(protocol, Protocol::Nbd),
+                r => panic!("shared over both protocols: {:?}", r),
+            }
+            assert!(lvol.share_iscsi().await.is_err());
+            path
+        })
+        .await;
+
+    // the device is written to from outside of the reactor
+    assert!(Path::new(&path).exists());
+    assert_eq!(common::dd_urandom_blkdev(&path), 0);
+
+    ms.spawn(async {
+        let lvol = lookup("vol");
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+
+        lvol.share_nvmf().await.unwrap();
+        assert!(matches!(
+            lvol.share_nbd().await,
+            Err(Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    ..
+                },
+                ..
+            })
+        ));
+        lvol.unshare().await.unwrap();
+
+        // once unshared the device is released and the lvol no longer open
+        lvol.share_nbd().await.unwrap();
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+        assert_eq!(lvol.share_uri().unwrap(), "bdev:///vol");
+
+        lvol.destroy().await.unwrap();
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,130 @@
+use std::{fs, process::Command};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Share},
+    lvs::{Error, Lvs},
+    subsys::Config,
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "resv-pool";
+static POOL_DISK: &str = "malloc:///resv-disk?size_mb=64";
+static NQN: &str = "nqn.2019-05.io.openebs:resv-vol";
+static PTPL_DIR: &str = "/tmp/lvol_reservations";
+static YAML_CONFIG_FILE: &str = "/tmp/lvol_reservations.yaml";
+
+const MB: u64 = 1024 * 1024;
+const KEY: &str = "0xabc";
+
+/// run the nvme command with the given arguments
+fn nvme(args: &[&str]) {
+    let status = Command::new("nvme").args(args).status().unwrap();
+    assert!(status.success(), "nvme {:?} failed, {}", args, status);
+}
+
+/// returns the device of the namespace of the connected subsystem
+fn nvme_device(nqn: &str) -> String {
+    fs::read_dir("/sys/class/nvme")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .find(|p| {
+            fs::read_to_string(p.join("subsysnqn"))
+                .map_or(false, |s| s.trim() == nqn)
+        })
+        .map(|p| {
+            format!("/dev/{}n1", p.file_name().unwrap().to_str().unwrap())
+        })
+        .expect("no controller connected")
+}
+
+#[tokio::test]
+async fn lvol_reservations() {
+    let mut config = Config::default();
+    config.nvmf_tcp_tgt_conf.ptpl_dir = PTPL_DIR.into();
+    config.write(YAML_CONFIG_FILE).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        mayastor_config: Some(YAML_CONFIG_FILE.to_string()),
+        ..Default::default()
+    });
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool.create_lvol("resv-vol", 8 * MB, false).await.unwrap();
+
+        // reservations are opt-in
+        lvol.share_nvmf().await.unwrap();
+        assert!(!lvol.is_shared_with_reservations());
+        assert!(matches!(
+            lvol.reservations(),
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+        assert!(lvol.share_nvmf_with_reservations().await.is_err());
+        lvol.unshare().await.unwrap();
+
+        let uri = lvol.share_nvmf_with_reservations().await.unwrap();
+        assert_eq!(lvol.share_nvmf_with_reservations().await.unwrap(), uri);
+        assert!(lvol.share_nvmf().await.is_err());
+        assert_eq!(lvol.reservations().unwrap().registrants.len(), 0);
+    })
+    .await;
+
+    nvme(&["connect", "-t", "tcp", "-a", "127.0.0.1", "-s", "8420", "-n", NQN]);
+    let device = nvme_device(NQN);
+    nvme(&["resv-register", &device, "--nrkey", KEY, "--cptpl", "3"]);
+    nvme(&["resv-acquire", &device, "--crkey", KEY, "--rtype", "1"]);
+
+    ms.spawn(async {
+        let lvol = Lvs::lookup(POOL_NAME)
+            .unwrap()
+            .lvols()
+            .unwrap()
+            .find(|l| l.name() == "resv-vol")
+            .unwrap();
+        let reservations = lvol.reservations().unwrap();
+        assert_eq!(reservations.rtype, 1);
+        assert_eq!(reservations.crkey, 0xabc);
+        assert!(!reservations.holder_uuid.is_empty());
+        assert_eq!(reservations.registrants.len(), 1);
+        assert_eq!(reservations.registrants[0].cr_key, 0xabc);
+    })
+    .await;
+
+    nvme(&["resv-release", &device, "--crkey", KEY, "--rtype", "1"]);
+    nvme(&["disconnect", "-n", NQN]);
+
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool
+            .lvols()
+            .unwrap()
+            .find(|l| l.name() == "resv-vol")
+            .unwrap();
+        let reservations = lvol.reservations().unwrap();
+        assert_eq!(reservations.rtype, 0);
+        assert_eq!(reservations.registrants.len(), 1);
+
+        // the registration outlives the share
+        lvol.unshare().await.unwrap();
+        assert!(!lvol.is_shared_with_reservations());
+        lvol.share_nvmf_with_reservations().await.unwrap();
+        assert_eq!(lvol.reservations().unwrap().registrants.len(), 1);
+        lvol.unshare().await.unwrap();
+
+        lvol.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[PTPL_DIR.into(), YAML_CONFIG_FILE.into()]);
+}
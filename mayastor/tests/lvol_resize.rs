@@ -0,0 +1,76 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "resize-pool";
+static POOL_DISK: &str = "malloc:///resize-disk?size_mb=64";
+
+#[tokio::test]
+async fn lvol_resize() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let cluster = pool.cluster_size();
+        let lvol = pool.create_lvol("vol", 2 * cluster, false).await.unwrap();
+
+        // the size is rounded up to whole clusters
+        let size = lvol.resize(2 * cluster + 1, false).await.unwrap();
+        assert_eq!(size, 3 * cluster);
+        assert_eq!(lvol.size(), 3 * cluster);
+        assert_eq!(lvol.resize(3 * cluster, false).await.unwrap(), 3 * cluster);
+
+        // shrinking must be asked for
+        assert!(matches!(
+            lvol.resize(cluster, false).await,
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+        assert_eq!(lvol.size(), 3 * cluster);
+        assert_eq!(lvol.resize(cluster, true).await.unwrap(), cluster);
+        assert_eq!(lvol.size(), cluster);
+
+        // a thick lvol can not grow beyond the space left in the pool
+        let available = pool.available();
+        match lvol.resize(cluster + available + cluster, false).await {
+            Err(Error::RepNoSpace {
+                size, ..
+            }) => assert_eq!(size, available + 2 * cluster),
+            r => panic!("lvol grown beyond the pool: {:?}", r),
+        }
+        assert_eq!(lvol.size(), cluster);
+        assert_eq!(pool.available(), available);
+
+        // a thin one can
+        let thin = pool.create_lvol("thin", cluster, true).await.unwrap();
+        assert_eq!(
+            thin.resize(4 * available, false).await.unwrap(),
+            4 * available
+        );
+
+        // a shared lvol is resized in place
+        lvol.share_nvmf().await.unwrap();
+        let uri = lvol.share_uri().unwrap();
+        assert_eq!(lvol.resize(4 * cluster, false).await.unwrap(), 4 * cluster);
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        assert_eq!(lvol.share_uri().unwrap(), uri);
+        lvol.unshare().await.unwrap();
+
+        thin.destroy().await.unwrap();
+        lvol.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
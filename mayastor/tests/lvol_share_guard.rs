@@ -0,0 +1,98 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Protocol, Share},
+    lvs::{Lvs, PropName, PropValue},
+    subsys::NvmfSubsystem,
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "guard-pool";
+static POOL_DISK: &str = "malloc:///guard-disk?size_mb=64";
+static LVOL_NAME: &str = "guard-lvol";
+
+const MB: u64 = 1024 * 1024;
+
+fn subsystems() -> usize {
+    NvmfSubsystem::first().map_or(0, |s| s.into_iter().count())
+}
+
+#[tokio::test]
+async fn lvol_share_guard() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let before = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![POOL_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            pool.create_lvol(LVOL_NAME, 8 * MB, false).await.unwrap();
+            subsystems()
+        })
+        .await;
+
+    // dropping the guard unshares the lvol in the background
+    ms.spawn(async move {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool.lvols().unwrap().next().unwrap();
+        let guard = lvol.share_nvmf_guarded().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        assert_eq!(Some(guard.share_uri().to_string()), lvol.share_uri());
+        assert_eq!(subsystems(), before + 1);
+        drop(guard);
+    })
+    .await;
+
+    let mut unshared = false;
+    for _ in 0 .. 50 {
+        unshared = ms
+            .spawn(async move {
+                let pool = Lvs::lookup(POOL_NAME).unwrap();
+                let lvol = pool.lvols().unwrap().next().unwrap();
+                lvol.shared() == Some(Protocol::Off)
+                    && subsystems() == before
+            })
+            .await;
+        if unshared {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(unshared);
+
+    // unsharing through the guard waits for it, and the guard does not
+    // unshare once more when dropped
+    ms.spawn(async move {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool.lvols().unwrap().next().unwrap();
+        assert_eq!(
+            lvol.get(PropName::Shared).await.unwrap(),
+            PropValue::Shared(false)
+        );
+
+        let guard = lvol.share_nvmf_guarded().await.unwrap();
+        assert_eq!(subsystems(), before + 1);
+        guard.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+        assert_eq!(subsystems(), before);
+        assert_eq!(
+            lvol.get(PropName::Shared).await.unwrap(),
+            PropValue::Shared(false)
+        );
+
+        // a guard outliving its lvol has nothing to unshare
+        let guard = lvol.share_nvmf_guarded().await.unwrap();
+        lvol.unshare().await.unwrap();
+        lvol.destroy().await.unwrap();
+        guard.unshare().await.unwrap();
+        assert_eq!(subsystems(), before);
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
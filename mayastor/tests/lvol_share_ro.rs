@@ -0,0 +1,99 @@
+use std::{convert::TryFrom, process::Command};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "ro-pool";
+static POOL_DISK: &str = "malloc:///ro-disk?size_mb=64";
+static READ_FILE: &str = "/tmp/share-ro-read";
+static WRITE_FILE: &str = "/tmp/share-ro-write";
+
+const MB: u64 = 1024 * 1024;
+
+/// run the initiator against the share, returns if the IO succeeded
+fn initiator(uri: &str, op: &str, file: &str) -> bool {
+    Command::new("../target/debug/initiator")
+        .args(&[uri, op, file])
+        .status()
+        .expect("failed to run the initiator")
+        .success()
+}
+
+#[tokio::test]
+async fn lvol_share_ro() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let uri = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![POOL_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+            let hdl = BdevHandle::open("vol", true, false).unwrap();
+            let mut buf = hdl.dma_malloc(MB).unwrap();
+            buf.fill(0xaa);
+            hdl.write_at(0, &buf).await.unwrap();
+            drop(hdl);
+
+            let uri = lvol.share_nvmf_ro().await.unwrap();
+            assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+            assert!(lvol.is_shared_read_only());
+            assert_eq!(lvol.share_uri().unwrap(), uri);
+            assert_eq!(lvol.share_nvmf_ro().await.unwrap(), uri);
+            uri
+        })
+        .await;
+    nvmeadm::NvmeTarget::try_from(uri.as_str()).unwrap();
+
+    // the initiator reads what is on the lvol, but can not change it
+    std::fs::write(WRITE_FILE, vec![0x55; 512]).unwrap();
+    assert!(initiator(&uri, "read", READ_FILE));
+    assert!(std::fs::read(READ_FILE).unwrap().iter().all(|b| *b == 0xaa));
+    assert!(!initiator(&uri, "write", WRITE_FILE));
+    assert!(initiator(&uri, "read", READ_FILE));
+    assert!(std::fs::read(READ_FILE).unwrap().iter().all(|b| *b == 0xaa));
+
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvol = pool.lvols().unwrap().find(|l| l.name() == "vol").unwrap();
+
+        // it is not shared read-write at the same time
+        assert!(matches!(
+            lvol.share_nvmf().await,
+            Err(Error::LvolShare {
+                source: CoreError::AlreadyShared {
+                    ..
+                },
+                ..
+            })
+        ));
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+        assert!(!lvol.is_shared_read_only());
+
+        // snapshots are always shared read-only
+        let snapshot = lvol.create_snapshot("vol-snap").await.unwrap();
+        snapshot.share_nvmf().await.unwrap();
+        assert!(snapshot.is_shared_read_only());
+        assert_eq!(snapshot.shared(), Some(Protocol::Nvmf));
+        snapshot.unshare().await.unwrap();
+        assert!(!snapshot.is_shared_read_only());
+
+        lvol.destroy().await.unwrap();
+        snapshot.destroy_snapshot().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[READ_FILE.into(), WRITE_FILE.into()]);
+}
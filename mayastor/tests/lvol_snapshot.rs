@@ -0,0 +1,78 @@
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    core::{MayastorCliArgs, Protocol, Share},
+    lvs::{Error, Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "snapshot-pool";
+static POOL_DISK: &str = "malloc:///snapshot-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvol_snapshot() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+        assert!(lvol.snapshots().is_empty());
+        bdev_io::write_mib("vol", 0xaa).await;
+
+        // the snapshot is taken while the lvol is shared and keeps the data
+        // written before it, later writes only go to the lvol
+        lvol.share_nvmf().await.unwrap();
+        let snap1 = lvol.create_snapshot("vol-snap1").await.unwrap();
+        assert!(snap1.is_snapshot());
+        assert!(snap1.is_read_only());
+        assert_eq!(snap1.pool(), POOL_NAME);
+        assert_eq!(snap1.size(), lvol.size());
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        lvol.unshare().await.unwrap();
+
+        bdev_io::write_mib("vol", 0x55).await;
+        bdev_io::verify_mib("vol", 0x55).await;
+        bdev_io::verify_mib("vol-snap1", 0xaa).await;
+
+        // a second snapshot builds on the first one
+        let snap2 = lvol.create_snapshot("vol-snap2").await.unwrap();
+        bdev_io::write_mib("vol", 0x11).await;
+        bdev_io::verify_mib("vol-snap2", 0x55).await;
+        bdev_io::verify_mib("vol-snap1", 0xaa).await;
+        let names = |l: &Lvol| {
+            l.snapshots().iter().map(|s| s.name()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&lvol), vec!["vol-snap2", "vol-snap1"]);
+        assert_eq!(names(&snap2), vec!["vol-snap1"]);
+        assert!(snap1.snapshots().is_empty());
+
+        // names must be unique and snapshots can not be snapshotted
+        assert!(matches!(
+            lvol.create_snapshot("vol-snap1").await,
+            Err(Error::RepExists {
+                ..
+            })
+        ));
+        assert!(matches!(
+            snap2.create_snapshot("vol-snap3").await,
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+
+        lvol.destroy().await.unwrap();
+        snap2.destroy_snapshot().await.unwrap();
+        snap1.destroy_snapshot().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
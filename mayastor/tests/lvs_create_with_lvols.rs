@@ -0,0 +1,98 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs},
+    lvs::{Error, LvolSpec, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "spec-pool";
+static POOL_DISK: &str = "malloc:///spec-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+fn request() -> CreatePoolRequest {
+    CreatePoolRequest {
+        name: POOL_NAME.into(),
+        disks: vec![POOL_DISK.into()],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn lvs_create_with_lvols() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let specs = (0 .. 4)
+            .map(|i| LvolSpec::new(&format!("spec-{}", i), 4 * MB, i % 2 == 0))
+            .collect::<Vec<_>>();
+        let pool = Lvs::create_with_lvols(request(), &specs).await.unwrap();
+        let mut lvols = pool.lvols().unwrap().collect::<Vec<_>>();
+        lvols.sort_by_key(|l| l.name());
+        assert_eq!(lvols.len(), specs.len());
+        for (lvol, spec) in lvols.iter().zip(specs.iter()) {
+            assert_eq!(lvol.name(), spec.name);
+            assert_eq!(lvol.size(), spec.size);
+            assert_eq!(lvol.is_thin(), spec.thin);
+        }
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    // an lvol which does not fit the pool rolls back the lvols created
+    // before it, and the pool itself
+    ms.spawn(async {
+        let specs = vec![
+            LvolSpec::new("spec-0", 4 * MB, false),
+            LvolSpec::new("spec-1", 4 * MB, true),
+            LvolSpec::new("spec-big", 1024 * MB, false),
+            LvolSpec::new("spec-3", 4 * MB, false),
+        ];
+        assert!(matches!(
+            Lvs::create_with_lvols(request(), &specs).await,
+            Err(Error::RepCreate { name, .. }) if name == "spec-big"
+        ));
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+        for spec in &specs {
+            assert!(Bdev::lookup_by_name(&spec.name).is_none());
+        }
+        assert_eq!(Bdev::iter_by_type("lvol").count(), 0);
+        assert!(Bdev::lookup_by_name("spec-disk").is_none());
+    })
+    .await;
+
+    // lvols are validated before the pool is created
+    ms.spawn(async {
+        let specs = vec![
+            LvolSpec::new("spec-0", 4 * MB, false),
+            LvolSpec::new("spec-0", 8 * MB, false),
+        ];
+        assert!(matches!(
+            Lvs::create_with_lvols(request(), &specs).await,
+            Err(Error::Invalid { .. })
+        ));
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+    })
+    .await;
+
+    // an existing pool holding other lvols is kept on failure
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request()).await.unwrap();
+        pool.create_lvol("kept", 4 * MB, false).await.unwrap();
+        let specs = vec![
+            LvolSpec::new("spec-0", 4 * MB, false),
+            LvolSpec::new("kept", 4 * MB, false),
+        ];
+        assert!(matches!(
+            Lvs::create_with_lvols(request(), &specs).await,
+            Err(Error::RepExists { .. })
+        ));
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let lvols = pool.lvols().unwrap().map(|l| l.name()).collect::<Vec<_>>();
+        assert_eq!(lvols, vec!["kept".to_string()]);
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,250 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{dedup_lookup, DedupStats, DEDUP_BLOCK_LEN},
+    core::{BdevHandle, ChecksumAlgorithm, MayastorCliArgs, Share},
+    lvs::{Lvol, Lvs, PropValue},
+};
+use rand::RngCore;
+use rpc::mayastor::{
+    ChecksumAlgorithm as RpcChecksumAlgorithm,
+    CreatePoolRequest,
+};
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/dedup-disk.img";
+static POOL_DISK: &str = "aio:///tmp/dedup-disk.img";
+static POOL_NAME: &str = "dedup-pool";
+static FIRST: &str = "dedup-first";
+static SECOND: &str = "dedup-second";
+
+const MB: u64 = 1024 * 1024;
+/// logical size of the lvols
+const SIZE: u64 = 8 * MB;
+/// number of blocks of the lvols
+const BLOCKS: u64 = SIZE / DEDUP_BLOCK_LEN;
+/// number of blocks written at once
+const BATCH: u64 = 64;
+
+/// a block of data, different for every block
+fn block(i: u64) -> Vec<u8> {
+    format!("block {} of data written to both lvols, ", i)
+        .bytes()
+        .cycle()
+        .take(DEDUP_BLOCK_LEN as usize)
+        .collect()
+}
+
+fn lookup(name: &str) -> Lvol {
+    Lvs::lookup(POOL_NAME)
+        .unwrap()
+        .lvols()
+        .unwrap()
+        .find(|l| l.name() == name)
+        .unwrap()
+}
+
+fn stats() -> DedupStats {
+    Lvs::lookup(POOL_NAME).unwrap().dedup_stats().unwrap()
+}
+
+/// open the dedup bdev on top of the lvol, which is there once it is shared
+fn open(name: &str) -> BdevHandle {
+    BdevHandle::open(&format!("{}-dedup", name), true, false).unwrap()
+}
+
+async fn write_all(hdl: &BdevHandle) {
+    let mut buf = hdl.dma_malloc(BATCH * DEDUP_BLOCK_LEN).unwrap();
+    for io in 0 .. BLOCKS / BATCH {
+        for (i, data) in buf
+            .as_mut_slice()
+            .chunks_mut(DEDUP_BLOCK_LEN as usize)
+            .enumerate()
+        {
+            data.copy_from_slice(&block(io * BATCH + i as u64));
+        }
+        hdl.write_at(io * BATCH * DEDUP_BLOCK_LEN, &buf).await.unwrap();
+    }
+}
+
+/// check the blocks from the given one on hold the data they were written
+/// with
+async fn verify_from(hdl: &BdevHandle, first: u64) {
+    let mut buf = hdl.dma_malloc(DEDUP_BLOCK_LEN).unwrap();
+    for i in first .. BLOCKS {
+        hdl.read_at(i * DEDUP_BLOCK_LEN, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &block(i)[..], "block {}", i);
+    }
+}
+
+#[tokio::test]
+async fn lvs_dedup() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // identical data written to two lvols is stored once
+    ms.spawn(async {
+        // xxHash64 digests are compared with the data before a block is
+        // shared
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            dedup: true,
+            dedup_hash: RpcChecksumAlgorithm::ChecksumXxhash64 as i32,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let store = pool.dedup_store().unwrap();
+        assert_eq!(store.hash(), ChecksumAlgorithm::XxHash64);
+        assert_eq!(pool.lvols().unwrap().count(), 0);
+        assert_eq!(stats(), DedupStats::default());
+
+        for name in &[FIRST, SECOND] {
+            let lvol = pool.create_dedup_lvol(name, SIZE).await.unwrap();
+            assert!(lvol.is_dedup());
+            assert!(lvol.is_thin());
+            lvol.share_nvmf().await.unwrap();
+        }
+        let first = open(FIRST);
+        let second = open(SECOND);
+        assert_eq!(first.get_bdev().block_len() as u64, DEDUP_BLOCK_LEN);
+        assert!(first.get_bdev().size_in_bytes() >= SIZE);
+
+        let available = pool.available();
+        write_all(&first).await;
+        assert!(available - pool.available() >= SIZE);
+        assert_eq!(
+            stats(),
+            DedupStats {
+                blocks: BLOCKS,
+                references: BLOCKS,
+            }
+        );
+
+        // the second lvol only takes a cluster for its map
+        let available = pool.available();
+        write_all(&second).await;
+        assert!(
+            available - pool.available() <= pool.cluster_size(),
+            "{} bytes of duplicate data took {} bytes",
+            SIZE,
+            available - pool.available()
+        );
+        assert_eq!(
+            stats(),
+            DedupStats {
+                blocks: BLOCKS,
+                references: 2 * BLOCKS,
+            }
+        );
+        assert_eq!(stats().saved_bytes(), SIZE);
+        let dedup = dedup_lookup(&format!("{}-dedup", FIRST)).unwrap();
+        assert_eq!(dedup.mapped(), BLOCKS);
+        verify_from(&first, 0).await;
+        verify_from(&second, 0).await;
+        first.close();
+        second.close();
+    })
+    .await;
+
+    // overwriting a shared block leaves the other lvol with its data, and
+    // a block of zeroes drops its reference
+    ms.spawn(async {
+        let first = open(FIRST);
+        let second = open(SECOND);
+
+        let mut buf = first.dma_malloc(DEDUP_BLOCK_LEN).unwrap();
+        rand::thread_rng().fill_bytes(buf.as_mut_slice());
+        let random = buf.as_slice().to_vec();
+        first.write_at(0, &buf).await.unwrap();
+        buf.fill(0);
+        first.write_at(DEDUP_BLOCK_LEN, &buf).await.unwrap();
+        assert_eq!(
+            stats(),
+            DedupStats {
+                blocks: BLOCKS + 1,
+                references: 2 * BLOCKS - 1,
+            }
+        );
+
+        first.read_at(0, &mut buf).await.unwrap();
+        assert_eq!(buf.as_slice(), &random[..]);
+        first.read_at(DEDUP_BLOCK_LEN, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0));
+        verify_from(&first, 2).await;
+        verify_from(&second, 0).await;
+        first.close();
+        second.close();
+
+        // deduplication is chosen by the pool and is exclusive of checksums
+        let lvol = lookup(FIRST);
+        assert!(lvol.set(PropValue::Dedup(false)).await.is_err());
+        assert!(lvol.set(PropValue::Checksum(true)).await.is_err());
+        assert!(lvol.is_dedup());
+    })
+    .await;
+
+    // the store and the maps are found again when the pool is imported
+    ms.spawn(async {
+        let before = stats();
+        Lvs::lookup(POOL_NAME).unwrap().export().await.unwrap();
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let store = pool.dedup_store().unwrap();
+        assert_eq!(store.hash(), ChecksumAlgorithm::XxHash64);
+        assert_eq!(stats(), before);
+
+        // the lvols were shared, so they are shared again
+        let first = open(FIRST);
+        let second = open(SECOND);
+        verify_from(&first, 2).await;
+        verify_from(&second, 0).await;
+        first.close();
+        second.close();
+    })
+    .await;
+
+    // destroying an lvol drops the references of its blocks, the blocks
+    // only it referenced are freed
+    ms.spawn(async {
+        lookup(SECOND).destroy().await.unwrap();
+        assert_eq!(
+            stats(),
+            DedupStats {
+                blocks: BLOCKS - 1,
+                references: BLOCKS - 1,
+            }
+        );
+        let first = open(FIRST);
+        verify_from(&first, 2).await;
+        first.close();
+
+        // unshared, the lvol is accessed locally through the dedup bdev and
+        // never as itself
+        let lvol = lookup(FIRST);
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.share_uri(), None);
+        lvol.open_local().await.unwrap();
+        assert_eq!(
+            lvol.share_uri().unwrap(),
+            format!("bdev:///{}-dedup", FIRST)
+        );
+        let first = lvol.open_handle(false).await.unwrap();
+        verify_from(&first, 2).await;
+        first.close();
+
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,54 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs, Protocol, Share},
+    lvs::{Lvs, PropName, PropValue},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "recursive-pool";
+static POOL_DISK: &str = "malloc:///recursive-disk?size_mb=64";
+
+const SIZE: u64 = 4 * 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_destroy_recursive() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let lvol = pool.create_lvol("vol", SIZE, true).await.unwrap();
+        let snapshot = lvol.create_snapshot("vol-snap").await.unwrap();
+        let clone = snapshot.create_clone("vol-clone").await.unwrap();
+        pool.create_lvol("idle", SIZE, true).await.unwrap();
+        lvol.share_nvmf().await.unwrap();
+        clone.share_nvmf().await.unwrap();
+
+        // the shares are withdrawn for good, as often as asked to
+        assert!(pool.unshare_all().await.is_empty());
+        for l in pool.lvols().unwrap() {
+            assert_eq!(l.shared(), Some(Protocol::Off));
+            assert_eq!(
+                l.get(PropName::Shared).await.unwrap(),
+                PropValue::Shared(false)
+            );
+        }
+        assert!(pool.unshare_all().await.is_empty());
+
+        // and everything goes at once, the clone before its snapshot
+        lvol.share_nvmf().await.unwrap();
+        clone.share_nvmf().await.unwrap();
+        pool.destroy_recursive().await.unwrap();
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+        assert!(Bdev::lookup_by_name("recursive-disk").is_none());
+    })
+    .await;
+}
@@ -0,0 +1,72 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Share},
+    lvs::Lvs,
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "display-pool";
+static POOL_DISK: &str = "malloc:///display-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_display() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            pool.to_string(),
+            format!(
+                "{} ({}, {} bytes)",
+                POOL_NAME,
+                pool.uuid(),
+                pool.capacity()
+            )
+        );
+        let debug = format!("{:?}", pool);
+        assert!(debug.starts_with("Lvs {"));
+        assert!(debug.contains(&format!("used: {}", pool.used())));
+
+        let thick = pool.create_lvol("thick", 8 * MB, false).await.unwrap();
+        assert_eq!(
+            thick.to_string(),
+            format!("{}/thick ({}, {} bytes)", POOL_NAME, thick.uuid(), 8 * MB)
+        );
+        assert_eq!(thick.allocated(), 8 * MB);
+        let debug = format!("{:?}", thick);
+        assert!(debug.starts_with("Lvol {"));
+        assert!(debug.contains(&format!("allocated: {}", 8 * MB)));
+        assert!(debug.contains("shared: Some(Off)"));
+        assert!(debug.contains("share_uri: Some(\"bdev:///thick\")"));
+
+        thick.share_nvmf().await.unwrap();
+        let debug = format!("{:?}", thick);
+        assert!(debug.contains("shared: Some(Nvmf)"));
+        assert!(debug.contains(&format!(
+            "share_uri: Some({:?})",
+            thick.share_uri().unwrap()
+        )));
+        thick.unshare().await.unwrap();
+
+        let thin = pool.create_lvol("thin", 8 * MB, true).await.unwrap();
+        assert_eq!(thin.allocated(), 0);
+        let debug = format!("{:?}", thin);
+        assert!(debug.contains("allocated: 0"));
+        assert!(debug.contains("thin: true"));
+
+        thin.destroy().await.unwrap();
+        thick.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
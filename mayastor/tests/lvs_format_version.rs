@@ -0,0 +1,59 @@
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Error, Lvs, POOL_FORMAT_VERSION},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "version-pool";
+static DISK: &str = "/tmp/format-version.img";
+
+fn request() -> CreatePoolRequest {
+    CreatePoolRequest {
+        name: POOL_NAME.into(),
+        disks: vec![format!("aio://{}", DISK)],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn lvs_format_version() {
+    common::delete_file(&[DISK.into()]);
+    common::truncate_file(DISK, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        // pools are created with the current format version
+        let pool = Lvs::create_or_import(request()).await.unwrap();
+        assert_eq!(pool.format_version().await.unwrap(), POOL_FORMAT_VERSION);
+        pool.export().await.unwrap();
+
+        // a pool written by a newer version of mayastor
+        let pool = Lvs::create_or_import(request()).await.unwrap();
+        pool.set_format_version(POOL_FORMAT_VERSION + 1).await.unwrap();
+        assert_eq!(
+            pool.format_version().await.unwrap(),
+            POOL_FORMAT_VERSION + 1
+        );
+        pool.export().await.unwrap();
+
+        // is not imported by this one
+        match Lvs::create_or_import(request()).await {
+            Err(Error::PoolVersionMismatch {
+                found,
+                supported,
+                ..
+            }) => {
+                assert_eq!(found, POOL_FORMAT_VERSION + 1);
+                assert_eq!(supported, POOL_FORMAT_VERSION);
+            }
+            r => panic!("imported a pool of a newer format: {:?}", r),
+        }
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+    })
+    .await;
+
+    common::delete_file(&[DISK.into()]);
+}
@@ -0,0 +1,70 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs},
+    lvs::Lvs,
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKS: [&str; 2] = ["/tmp/import_all0.img", "/tmp/import_all1.img"];
+static POOLS: [(&str, &str); 2] = [
+    ("import-all-0", "aio:///tmp/import_all0.img"),
+    ("import-all-1", "aio:///tmp/import_all1.img"),
+];
+static MALLOC_BDEV: &str = "malloc:///nopool?size_mb=64";
+
+#[tokio::test]
+async fn lvs_import_all() {
+    common::delete_file(&[DISKS[0].into(), DISKS[1].into()]);
+    for disk in &DISKS {
+        common::truncate_file(disk, 64 * 1024);
+    }
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        for (name, disk) in &POOLS {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: name.to_string(),
+                disks: vec![disk.to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            pool.create_lvol("vol", 4 * 1024 * 1024, true).await.unwrap();
+            pool.export().await.unwrap();
+        }
+        assert_eq!(Lvs::iter().count(), 0);
+    })
+    .await;
+
+    // once their bdevs are back the pools are found without naming them,
+    // skipping the bdevs without a pool
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        for (_, disk) in &POOLS {
+            bdev_create(disk).await.unwrap();
+        }
+
+        let imported = Lvs::import_all().await;
+        assert_eq!(imported.len(), 2, "{:?}", imported);
+        assert!(imported.iter().all(|r| r.is_ok()), "{:?}", imported);
+        for (name, _) in &POOLS {
+            let pool = Lvs::lookup(name).unwrap();
+            assert_eq!(pool.lvols().unwrap().count(), 1);
+        }
+        assert!(!Bdev::lookup_by_name("nopool").unwrap().is_claimed());
+
+        // nor are the pools imported twice
+        assert!(Lvs::import_all().await.is_empty());
+
+        for (name, _) in &POOLS {
+            Lvs::lookup(name).unwrap().destroy().await.unwrap();
+        }
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKS[0].into(), DISKS[1].into()]);
+}
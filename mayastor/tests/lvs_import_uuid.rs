@@ -0,0 +1,90 @@
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISK1: &str = "/tmp/import-uuid1.img";
+static DISK2: &str = "/tmp/import-uuid2.img";
+
+fn disk(name: &str) -> Vec<String> {
+    vec![format!("aio://{}", name)]
+}
+
+/// create a pool on the disk and export it again, returns its UUID
+async fn create_pool(name: &str, disk_name: &str) -> String {
+    let pool = Lvs::create_or_import(CreatePoolRequest {
+        name: name.into(),
+        disks: disk(disk_name),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let uuid = pool.uuid();
+    pool.export().await.unwrap();
+    uuid
+}
+
+#[tokio::test]
+async fn lvs_import_uuid() {
+    common::delete_file(&[DISK1.into(), DISK2.into()]);
+    common::truncate_file(DISK1, 64 * 1024);
+    common::truncate_file(DISK2, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let uuid1 = create_pool("uuid-pool1", DISK1).await;
+        let uuid2 = create_pool("uuid-pool2", DISK2).await;
+        assert_ne!(uuid1, uuid2);
+
+        // the pool on the other disk is not imported
+        match Lvs::import_by_uuid(&uuid1, disk(DISK2)).await {
+            Err(Error::UuidMismatch {
+                uuid,
+                found,
+                ..
+            }) => {
+                assert_eq!(uuid, uuid1);
+                assert_eq!(found, uuid2);
+            }
+            r => panic!("imported the wrong pool: {:?}", r),
+        }
+        assert!(Lvs::lookup("uuid-pool2").is_none());
+
+        // the pool on the right disk is, whatever the case of the UUID
+        let pool = Lvs::import_by_uuid(&uuid1.to_uppercase(), disk(DISK1))
+            .await
+            .unwrap();
+        assert_eq!(pool.name(), "uuid-pool1");
+        assert_eq!(pool.uuid(), uuid1);
+        assert_eq!(Lvs::lookup("uuid-pool1").unwrap().uuid(), uuid1);
+
+        // importing it again returns the pool already imported
+        let again = Lvs::import_by_uuid(&uuid1, disk(DISK1)).await.unwrap();
+        assert_eq!(again.name(), "uuid-pool1");
+        assert!(matches!(
+            Lvs::import_by_uuid(&uuid1, disk(DISK2)).await,
+            Err(Error::Import {
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            Lvs::import_by_uuid("not-a-uuid", disk(DISK2)).await,
+            Err(Error::Invalid {
+                ..
+            })
+        ));
+
+        pool.destroy().await.unwrap();
+        let pool = Lvs::import_by_uuid(&uuid2, disk(DISK2)).await.unwrap();
+        assert_eq!(pool.name(), "uuid-pool2");
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISK1.into(), DISK2.into()]);
+}
@@ -0,0 +1,182 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::pi_lookup,
+    core::{Bdev, MayastorCliArgs, Share},
+    lvs::{Error, Lvs, PropValue},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static PLAIN_DISK: &str = "malloc:///plain-disk?size_mb=64";
+static PLAIN_POOL: &str = "plain-pool";
+
+static DISKNAME: &str = "/tmp/reserve-disk.img";
+static POOL_DISK: &str = "aio:///tmp/reserve-disk.img";
+static POOL_NAME: &str = "reserve-pool";
+static LVOL_NAME: &str = "reserve-lvol";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_integrity_reserve() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // checksums and protection information can not be enabled on lvols of a
+    // pool created without an integrity reserve
+    let plain_capacity = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: PLAIN_POOL.into(),
+                disks: vec![PLAIN_DISK.into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            assert_eq!(pool.integrity_reserve(), 0);
+            assert_eq!(pool.integrity_reserve_percent(), 0);
+
+            let lvol =
+                pool.create_lvol(LVOL_NAME, 8 * MB, false).await.unwrap();
+            assert!(matches!(
+                lvol.set(PropValue::Checksum(true)).await,
+                Err(Error::Invalid { msg, .. })
+                    if msg.contains("no space reserved")
+            ));
+            assert!(lvol.set(PropValue::Protected(true)).await.is_err());
+            assert!(!lvol.is_checksummed().await);
+            assert!(!lvol.is_protected().await);
+            assert_eq!(lvol.size(), 8 * MB);
+            lvol.destroy().await.unwrap();
+
+            let capacity = pool.capacity();
+            pool.destroy().await.unwrap();
+            capacity
+        })
+        .await;
+
+    // the reserved space does not count towards the capacity of the pool,
+    // and the lvol holding it is no replica
+    ms.spawn(async move {
+        assert!(Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            integrity_reserve: 100,
+            journal: false,
+            dedup: false,
+            dedup_hash: 0,
+            wipe: 0,
+        })
+        .await
+        .is_err());
+
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            integrity_reserve: 25,
+            journal: false,
+            dedup: false,
+            dedup_hash: 0,
+            wipe: 0,
+        })
+        .await
+        .unwrap();
+        let reserve = pool.integrity_reserve();
+        assert_eq!(pool.integrity_reserve_percent(), 25);
+        assert!(reserve >= plain_capacity / 4);
+        assert_eq!(reserve % pool.cluster_size(), 0);
+        assert_eq!(pool.integrity_reserve_available(), reserve);
+        assert_eq!(pool.capacity() + reserve, plain_capacity);
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.lvols().unwrap().count(), 0);
+    })
+    .await;
+
+    // a checksummed lvol takes the space for its metadata from the reserve,
+    // which is kept with the pool
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let reserve = pool.integrity_reserve();
+        let capacity = pool.capacity();
+        let cluster = pool.cluster_size();
+
+        let lvol = pool.create_lvol(LVOL_NAME, 8 * MB, false).await.unwrap();
+        assert_eq!(pool.used(), 8 * MB);
+        lvol.set(PropValue::Checksum(true)).await.unwrap();
+        assert_eq!(lvol.integrity_metadata(), cluster);
+        assert_eq!(lvol.size(), 8 * MB + cluster);
+        assert_eq!(pool.integrity_reserve_available(), reserve - cluster);
+        assert_eq!(pool.capacity(), capacity);
+        assert_eq!(pool.used(), 8 * MB);
+
+        // the space is taken once, whatever the format of the metadata
+        lvol.set(PropValue::Protected(true)).await.unwrap();
+        lvol.set(PropValue::Protected(false)).await.unwrap();
+        assert_eq!(lvol.integrity_metadata(), cluster);
+
+        lvol.share_nvmf().await.unwrap();
+        let pi = format!("{}-pi", LVOL_NAME);
+        assert!(pi_lookup(&pi).is_some());
+        let bdev = Bdev::lookup_by_name(&pi).unwrap();
+        assert!(bdev.size_in_bytes() >= 8 * MB);
+
+        pool.export().await.unwrap();
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+        assert_eq!(pool.integrity_reserve_percent(), 25);
+        assert_eq!(pool.integrity_reserve(), reserve);
+        assert_eq!(pool.integrity_reserve_available(), reserve - cluster);
+        assert_eq!(pool.capacity(), capacity);
+        assert_eq!(pool.used(), 8 * MB);
+        let lvol =
+            pool.lvols().unwrap().find(|l| l.name() == LVOL_NAME).unwrap();
+        assert_eq!(pool.lvols().unwrap().count(), 1);
+        assert_eq!(lvol.integrity_metadata(), cluster);
+
+        // disabling checksums gives the space back
+        lvol.unshare().await.unwrap();
+        lvol.set(PropValue::Checksum(false)).await.unwrap();
+        assert_eq!(lvol.integrity_metadata(), 0);
+        assert_eq!(lvol.size(), 8 * MB);
+        assert_eq!(pool.integrity_reserve_available(), reserve);
+        lvol.destroy().await.unwrap();
+    })
+    .await;
+
+    // the reserve runs out after as many lvols as it has clusters, and
+    // destroying a checksummed lvol gives its space back
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let reserve = pool.integrity_reserve();
+        let cluster = pool.cluster_size();
+        let count = reserve / cluster;
+
+        let mut lvols = Vec::new();
+        for i in 0 ..= count {
+            let name = format!("{}-{}", LVOL_NAME, i);
+            lvols.push(pool.create_lvol(&name, cluster, false).await.unwrap());
+        }
+        for lvol in &lvols[.. count as usize] {
+            lvol.set(PropValue::Checksum(true)).await.unwrap();
+        }
+        assert_eq!(pool.integrity_reserve_available(), 0);
+        let last = lvols.pop().unwrap();
+        assert!(matches!(
+            last.set(PropValue::Checksum(true)).await,
+            Err(Error::Invalid { msg, .. }) if msg.contains("reserved")
+        ));
+        assert!(!last.is_checksummed().await);
+        last.destroy().await.unwrap();
+
+        for lvol in lvols {
+            lvol.destroy().await.unwrap();
+        }
+        assert_eq!(pool.integrity_reserve_available(), reserve);
+        assert_eq!(pool.used(), 0);
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
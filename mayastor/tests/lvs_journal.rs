@@ -0,0 +1,140 @@
+use std::convert::TryFrom;
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    lvs::{JournalOp, Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/journal-disk.img";
+static POOL_DISK: &str = "aio:///tmp/journal-disk.img";
+static POOL_NAME: &str = "journal-pool";
+static JOURNAL_NAME: &str = "journal-pool-journal";
+
+const MB: u64 = 1024 * 1024;
+
+fn request() -> CreatePoolRequest {
+    CreatePoolRequest {
+        name: POOL_NAME.into(),
+        disks: vec![POOL_DISK.into()],
+        journal: true,
+        ..Default::default()
+    }
+}
+
+/// export the pool and import it again, as after a crash
+async fn reimport() -> Lvs {
+    Lvs::lookup(POOL_NAME).unwrap().export().await.unwrap();
+    Lvs::create_or_import(request()).await.unwrap()
+}
+
+fn lvol(name: &str) -> Option<Lvol> {
+    Bdev::lookup_by_name(name).and_then(|b| Lvol::try_from(b).ok())
+}
+
+fn create(name: &str) -> JournalOp {
+    JournalOp::CreateLvol {
+        name: name.into(),
+        size: 4 * MB,
+        thin: false,
+    }
+}
+
+#[tokio::test]
+async fn lvs_journal() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // the journal is kept in reserved space, and the operations applied in
+    // full are committed
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request()).await.unwrap();
+        let journal = pool.journal().unwrap();
+        assert_eq!(journal.size(), pool.cluster_size());
+        assert_eq!(pool.used(), 0);
+        assert_eq!(pool.lvols().unwrap().count(), 0);
+        assert!(lvol(JOURNAL_NAME).unwrap().is_reserved());
+
+        pool.create_lvol("kept", 4 * MB, false).await.unwrap();
+        assert!(journal.pending().await.unwrap().is_empty());
+    })
+    .await;
+
+    // a crash between the write of the journal and the creation of the lvol
+    // leaves nothing to roll back
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let journal = pool.journal().unwrap();
+        journal.begin(create("lost")).await.unwrap();
+        assert_eq!(journal.pending().await.unwrap().len(), 1);
+
+        let pool = reimport().await;
+        assert!(pool.journal().unwrap().pending().await.unwrap().is_empty());
+        assert!(lvol("lost").is_none());
+        assert!(lvol("kept").is_some());
+        pool.create_lvol("lost", 4 * MB, false).await.unwrap();
+    })
+    .await;
+
+    // a crash after the lvol was created but before it was committed rolls
+    // the creation back, while a crash during a destroy rolls it forward
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let journal = pool.journal().unwrap();
+        journal.begin(create("partial")).await.unwrap();
+        pool.create_lvol("partial", 4 * MB, false).await.unwrap();
+        journal
+            .begin(JournalOp::DestroyLvol {
+                name: "lost".into(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(journal.pending().await.unwrap().len(), 2);
+
+        let pool = reimport().await;
+        assert!(pool.journal().unwrap().pending().await.unwrap().is_empty());
+        assert!(lvol("partial").is_none());
+        assert!(lvol("lost").is_none());
+        assert!(lvol("kept").is_some());
+    })
+    .await;
+
+    // a record torn by a crash while it was written is ignored, as the
+    // operation it records was never started
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        let journal = pool.journal().unwrap();
+        let seq = journal.begin(create("torn")).await.unwrap();
+
+        let hdl = BdevHandle::open(JOURNAL_NAME, true, false).unwrap();
+        let block_len = u64::from(hdl.get_bdev().block_len());
+        let offset = seq % (journal.size() / block_len) * block_len;
+        let mut buf = hdl.dma_malloc(block_len).unwrap();
+        hdl.read_at(offset, &mut buf).await.unwrap();
+        for b in &mut buf.as_mut_slice()[16 ..] {
+            *b = 0;
+        }
+        hdl.write_at(offset, &buf).await.unwrap();
+        hdl.close();
+        assert!(journal.pending().await.unwrap().is_empty());
+
+        let pool = reimport().await;
+        assert!(pool.journal().unwrap().pending().await.unwrap().is_empty());
+        assert!(lvol("torn").is_none());
+        assert_eq!(
+            pool.lvols()
+                .unwrap()
+                .map(|l| l.name())
+                .collect::<Vec<_>>(),
+            vec!["kept".to_string()]
+        );
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,75 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{MayastorCliArgs, Share},
+    lvs::Lvs,
+};
+use rpc::mayastor::CreatePoolRequest;
+use serde_json::json;
+
+pub mod common;
+
+static POOL_NAME: &str = "json-pool";
+static POOL_DISK: &str = "malloc:///json-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_json() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let thick = pool.create_lvol("thick", 8 * MB, false).await.unwrap();
+        let thin = pool.create_lvol("thin", 4 * MB, true).await.unwrap();
+        thick.share_nvmf().await.unwrap();
+
+        let value = serde_json::to_value(&pool).unwrap();
+        assert_eq!(value["name"], POOL_NAME);
+        assert_eq!(value["uuid"], pool.uuid());
+        assert_eq!(value["disk"], pool.base_bdev().name());
+        assert_eq!(value["capacity"], pool.capacity());
+        assert_eq!(value["used"], pool.used());
+        assert_eq!(value["available"], pool.available());
+        assert_eq!(value["integrity_reserve"], 0);
+
+        let lvols = value["lvols"].as_array().unwrap();
+        assert_eq!(lvols.len(), 2);
+        let lvol = |name: &str| {
+            lvols.iter().find(|l| l["name"] == name).unwrap().clone()
+        };
+        assert_eq!(
+            lvol("thick"),
+            json!({
+                "name": "thick",
+                "pool": POOL_NAME,
+                "uuid": thick.uuid(),
+                "size": 8 * MB,
+                "allocated": 8 * MB,
+                "thin": false,
+                "snapshot": false,
+                "read_only": false,
+                "shared": "Nvmf",
+                "share_uri": thick.share_uri().unwrap(),
+            })
+        );
+        assert_eq!(lvol("thin")["allocated"], serde_json::Value::Null);
+        assert_eq!(lvol("thin")["thin"], true);
+        assert_eq!(lvol("thin")["shared"], "Off");
+        assert_eq!(lvol("thin"), serde_json::to_value(&thin).unwrap());
+
+        // serializing does not change the pool
+        assert_eq!(serde_json::to_value(&pool).unwrap(), value);
+
+        thick.unshare().await.unwrap();
+        thin.destroy().await.unwrap();
+        thick.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
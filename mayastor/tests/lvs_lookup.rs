@@ -0,0 +1,79 @@
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Error, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "lookup-pool";
+static POOL_DISK: &str = "malloc:///lookup-disk?size_mb=64";
+
+fn request() -> CreatePoolRequest {
+    CreatePoolRequest {
+        name: POOL_NAME.into(),
+        disks: vec![POOL_DISK.into()],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn lvs_lookup() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // a pool which does not exist is not found
+    ms.spawn(async {
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+        assert!(matches!(
+            Lvs::lookup_result(POOL_NAME),
+            Err(Error::PoolNotFound { name, .. }) if name == POOL_NAME
+        ));
+    })
+    .await;
+
+    // a pool being created is busy rather than missing, the lookup races
+    // with the creation which has yielded waiting for the disk to be
+    // examined
+    ms.spawn(async {
+        let (pool, during) = futures::join!(
+            Lvs::create_or_import(request()),
+            async { Lvs::lookup_result(POOL_NAME) }
+        );
+        assert!(matches!(during, Err(Error::PoolBusy { .. })));
+        let pool = pool.unwrap();
+        assert_eq!(Lvs::lookup_result(POOL_NAME).unwrap().name(), pool.name());
+        assert!(Lvs::lookup(POOL_NAME).is_some());
+    })
+    .await;
+
+    // as is a pool being exported or destroyed
+    ms.spawn(async {
+        let pool = Lvs::lookup_result(POOL_NAME).unwrap();
+        let (exported, during) =
+            futures::join!(pool.export(), async {
+                (Lvs::lookup(POOL_NAME), Lvs::lookup_result(POOL_NAME))
+            });
+        exported.unwrap();
+        assert!(during.0.is_some());
+        assert!(matches!(during.1, Err(Error::PoolBusy { .. })));
+        assert!(matches!(
+            Lvs::lookup_result(POOL_NAME),
+            Err(Error::PoolNotFound { .. })
+        ));
+
+        let pool = Lvs::create_or_import(request()).await.unwrap();
+        assert!(Lvs::lookup_result(POOL_NAME).is_ok());
+
+        let (destroyed, during) = futures::join!(pool.destroy(), async {
+            Lvs::lookup_result(POOL_NAME)
+        });
+        destroyed.unwrap();
+        assert!(matches!(during, Err(Error::PoolBusy { .. })));
+        assert!(matches!(
+            Lvs::lookup_result(POOL_NAME),
+            Err(Error::PoolNotFound { .. })
+        ));
+    })
+    .await;
+}
@@ -35,6 +35,7 @@ async fn lvs_pool_test() {
         Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -48,6 +49,7 @@ async fn lvs_pool_test() {
             Lvs::create_or_import(CreatePoolRequest {
                 name: "tpool".into(),
                 disks: vec!["aio:///tmp/disk1.img".into()],
+                ..Default::default()
             })
             .await
             .is_ok(),
@@ -156,6 +158,7 @@ async fn lvs_pool_test() {
         let pool2 = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool2".to_string(),
             disks: vec!["malloc:///malloc0?size_mb=64".to_string()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -171,6 +174,9 @@ async fn lvs_pool_test() {
 
         let pool = Lvs::lookup("tpool").unwrap();
         assert_eq!(pool.lvols().unwrap().count(), 10);
+
+        // the lvols of both pools are found by their type
+        assert_eq!(Bdev::iter_by_type("lvol").count(), 15);
     })
     .await;
 
@@ -184,6 +190,7 @@ async fn lvs_pool_test() {
         let pool = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["aio:///tmp/disk1.img".to_string()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -305,6 +312,7 @@ async fn lvs_pool_test() {
         let pool = Lvs::create_or_import(CreatePoolRequest {
             name: "tpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -333,6 +341,7 @@ async fn lvs_pool_test() {
         Lvs::create_or_import(CreatePoolRequest {
             name: "jpool".into(),
             disks: vec!["aio:///tmp/disk1.img".into()],
+            ..Default::default()
         })
         .await
         .err()
@@ -30,6 +30,7 @@ async fn lvs_pool_rpc() {
         .create_pool(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -38,6 +39,7 @@ async fn lvs_pool_rpc() {
         .create_pool(CreatePoolRequest {
             name: "tpool".to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -54,6 +56,11 @@ async fn lvs_pool_rpc() {
             size: 4 * 1024,
             thin: false,
             share: 0,
+            protection: false,
+            checksum: false,
+            checksum_algorithm: 0,
+            compression: false,
+            compression_algorithm: 0,
         })
         .await
         .unwrap();
@@ -66,6 +73,11 @@ async fn lvs_pool_rpc() {
             size: 4 * 1024,
             thin: false,
             share: 0,
+            protection: false,
+            checksum: false,
+            checksum_algorithm: 0,
+            compression: false,
+            compression_algorithm: 0,
         })
         .await
         .unwrap();
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use common::{integrity, MayastorTest};
+use mayastor::{
+    core::{BdevHandle, MayastorCliArgs, Share},
+    lvs::{Lvs, PoolScanner, PropValue, ScanOpts},
+};
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/scan-disk.img";
+static POOL_DISK: &str = "aio:///tmp/scan-disk.img";
+static POOL_NAME: &str = "scan-pool";
+static CHECKSUM_LVOL: &str = "scan-checksum-lvol";
+static CHECKSUM_PI: &str = "scan-checksum-lvol-pi";
+static PLAIN_LVOL: &str = "scan-plain-lvol";
+
+const BLOCK_LEN: u64 = 512;
+const MB: u64 = 1024 * 1024;
+/// the block corrupted below the PI bdev
+const BAD_LBA: u64 = 3000;
+
+const OPTS: ScanOpts = ScanOpts {
+    interval: Duration::from_millis(200),
+    rate: 1024 * MB,
+};
+
+/// the number of full passes of the scan of the pool
+async fn passes(ms: &MayastorTest<'_>) -> u64 {
+    ms.spawn(async { PoolScanner::status(POOL_NAME).unwrap().passes })
+        .await
+}
+
+/// wait until the scan of the pool has made the given number of full passes
+async fn wait_passes(ms: &MayastorTest<'_>, n: u64) {
+    for _ in 0 .. 200 {
+        if passes(ms).await >= n {
+            return;
+        }
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+    }
+    panic!("scan did not make {} passes", n);
+}
+
+#[tokio::test]
+async fn lvs_scan() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // a pool with a checksummed and a plain lvol, with data written to both
+    ms.spawn(async {
+        let pool = integrity::create_pool(POOL_NAME, POOL_DISK).await;
+        let lvol = pool
+            .create_lvol(CHECKSUM_LVOL, 8 * MB, false)
+            .await
+            .unwrap();
+        lvol.set(PropValue::Checksum(true)).await.unwrap();
+        lvol.share_nvmf().await.unwrap();
+        pool.create_lvol(PLAIN_LVOL, 8 * MB, false).await.unwrap();
+
+        for name in &[CHECKSUM_PI, PLAIN_LVOL] {
+            let hdl = BdevHandle::open(name, true, false).unwrap();
+            let mut buf = hdl.dma_malloc(MB).unwrap();
+            buf.fill(0x5a);
+            for i in 0 .. 4 {
+                hdl.write_at(i * MB, &buf).await.unwrap();
+            }
+        }
+
+        assert!(PoolScanner::status(POOL_NAME).is_none());
+        assert!(PoolScanner::start("nope", OPTS).is_err());
+    })
+    .await;
+
+    // a block corrupted below the PI bdev is found by the scan in the chunk
+    // it falls in, and nothing else
+    ms.spawn(async {
+        integrity::corrupt_block(CHECKSUM_LVOL, BAD_LBA * BLOCK_LEN, BLOCK_LEN)
+            .await;
+
+        PoolScanner::start(POOL_NAME, OPTS).unwrap();
+        assert!(PoolScanner::start(POOL_NAME, OPTS).is_err());
+    })
+    .await;
+
+    wait_passes(&ms, 1).await;
+    ms.spawn(async {
+        let status = PoolScanner::status(POOL_NAME).unwrap();
+        assert!(status.last_full_scan.is_some());
+        assert!(!status.findings.is_empty());
+        let bad = BAD_LBA * BLOCK_LEN;
+        for finding in &status.findings {
+            assert_eq!(finding.lvol, CHECKSUM_LVOL);
+            assert!(finding.offset <= bad);
+            assert!(bad < finding.offset + finding.len);
+        }
+    })
+    .await;
+
+    // a paused scan makes no progress until it is resumed
+    ms.spawn(async {
+        assert!(PoolScanner::pause(POOL_NAME));
+        assert!(PoolScanner::status(POOL_NAME).unwrap().paused);
+    })
+    .await;
+    tokio::time::delay_for(Duration::from_millis(300)).await;
+    let paused = passes(&ms).await;
+    tokio::time::delay_for(Duration::from_millis(500)).await;
+    assert_eq!(passes(&ms).await, paused);
+    ms.spawn(async {
+        assert!(PoolScanner::resume(POOL_NAME));
+    })
+    .await;
+    wait_passes(&ms, paused + 1).await;
+
+    // once the block has been rewritten, the scans find nothing new
+    ms.spawn(async {
+        let hdl = BdevHandle::open(CHECKSUM_PI, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+        buf.fill(0x5a);
+        hdl.write_at(BAD_LBA * BLOCK_LEN, &buf).await.unwrap();
+    })
+    .await;
+    let repaired = passes(&ms).await;
+    wait_passes(&ms, repaired + 1).await;
+    let findings = ms
+        .spawn(async { PoolScanner::status(POOL_NAME).unwrap().findings.len() })
+        .await;
+    wait_passes(&ms, repaired + 3).await;
+    ms.spawn(async move {
+        let status = PoolScanner::status(POOL_NAME).unwrap();
+        assert_eq!(status.findings.len(), findings);
+
+        assert!(PoolScanner::stop(POOL_NAME));
+        assert!(!PoolScanner::stop(POOL_NAME));
+        assert!(PoolScanner::status(POOL_NAME).is_none());
+
+        let pool = Lvs::lookup(POOL_NAME).unwrap();
+        for lvol in pool.lvols().unwrap() {
+            lvol.unshare().await.unwrap();
+        }
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
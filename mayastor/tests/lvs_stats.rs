@@ -0,0 +1,68 @@
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Lvs, LvolStats},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOL_NAME: &str = "stats-pool";
+static POOL_DISK: &str = "malloc:///stats-disk?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let empty = pool.stats();
+        assert_eq!(empty.total_bytes, pool.capacity());
+        assert_eq!(empty.used_bytes, 0);
+        assert_eq!(empty.free_bytes, empty.total_bytes);
+        assert_eq!(empty.cluster_size, pool.cluster_size());
+        assert_eq!(empty.num_clusters * empty.cluster_size, empty.total_bytes);
+
+        // thick lvols take their space from the pool right away
+        let thick = pool.create_lvol("thick", 8 * MB, false).await.unwrap();
+        assert_eq!(
+            thick.stats(),
+            LvolStats {
+                provisioned_bytes: 8 * MB,
+                allocated_bytes: 8 * MB,
+            }
+        );
+        let stats = pool.stats();
+        assert_eq!(stats.used_bytes, 8 * MB);
+        assert_eq!(stats.free_bytes, empty.free_bytes - 8 * MB);
+        assert_eq!(stats.total_bytes, empty.total_bytes);
+
+        // thin ones do not
+        let thin = pool.create_lvol("thin", 8 * MB, true).await.unwrap();
+        assert_eq!(
+            thin.stats(),
+            LvolStats {
+                provisioned_bytes: 8 * MB,
+                allocated_bytes: 0,
+            }
+        );
+        assert_eq!(pool.stats().used_bytes, 8 * MB);
+
+        // the stats are those of the pool as it is now
+        thick.destroy().await.unwrap();
+        assert_eq!(pool.stats().used_bytes, 0);
+        assert_eq!(pool.stats(), empty);
+
+        thin.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,99 @@
+use futures::StreamExt;
+
+use common::MayastorTest;
+use mayastor::{
+    core::MayastorCliArgs,
+    lvs::{Lvs, PropValue},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static POOLS: [(&str, &str); 3] = [
+    ("stream-pool-0", "malloc:///stream-disk-0?size_mb=64"),
+    ("stream-pool-1", "malloc:///stream-disk-1?size_mb=64"),
+    ("stream-pool-2", "malloc:///stream-disk-2?size_mb=64"),
+];
+
+const MB: u64 = 1024 * 1024;
+const LVOLS: usize = 8;
+
+#[tokio::test]
+async fn lvs_stream() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        assert_eq!(Lvs::stream().count().await, 0);
+
+        for (name, disk) in POOLS.iter() {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: name.to_string(),
+                disks: vec![disk.to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            assert_eq!(pool.lvol_stream().count().await, 0);
+            for i in 0 .. LVOLS {
+                pool.create_lvol(&format!("{}-{}", name, i), 4 * MB, true)
+                    .await
+                    .unwrap();
+            }
+        }
+    })
+    .await;
+
+    // the stream yields the same pools and lvols as the iterators, with
+    // awaits in between
+    ms.spawn(async {
+        let names = Lvs::iter()
+            .map(|p| p.name().to_string())
+            .collect::<Vec<_>>();
+        let mut streamed = Vec::new();
+        let mut pools = Box::pin(Lvs::stream());
+        while let Some(pool) = pools.next().await {
+            let mut lvols = Box::pin(pool.lvol_stream());
+            let mut count = 0;
+            while let Some(lvol) = lvols.next().await {
+                assert!(lvol.name().starts_with(pool.name()));
+                lvol.set(PropValue::Shared(true)).await.unwrap();
+                count += 1;
+            }
+            assert_eq!(count, LVOLS);
+            assert_eq!(count, pool.lvols().unwrap().count());
+            streamed.push(pool.name().to_string());
+        }
+        assert_eq!(streamed, names);
+    })
+    .await;
+
+    // lvols destroyed behind the stream are not yielded
+    ms.spawn(async {
+        let pool = Lvs::lookup(POOLS[0].0).unwrap();
+        let mut lvols = Box::pin(pool.lvol_stream());
+        let first = lvols.next().await.unwrap();
+        let doomed = pool
+            .lvols()
+            .unwrap()
+            .filter(|l| l.name() != first.name())
+            .take(2)
+            .collect::<Vec<_>>();
+        let doomed_names =
+            doomed.iter().map(|l| l.name()).collect::<Vec<_>>();
+        for lvol in doomed {
+            lvol.destroy().await.unwrap();
+        }
+        let rest = lvols.collect::<Vec<_>>().await;
+        assert_eq!(rest.len(), LVOLS - 3);
+        assert!(rest.iter().all(|l| !doomed_names.contains(&l.name())));
+    })
+    .await;
+
+    ms.spawn(async {
+        for pool in Lvs::stream().collect::<Vec<_>>().await {
+            pool.destroy().await.unwrap();
+        }
+        assert_eq!(Lvs::iter().count(), 0);
+    })
+    .await;
+}
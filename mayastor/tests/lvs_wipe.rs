@@ -0,0 +1,99 @@
+use std::{
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use common::MayastorTest;
+use mayastor::{core::MayastorCliArgs, lvs::Lvs};
+use rpc::mayastor::{CreatePoolRequest, PoolWipe};
+
+pub mod common;
+
+static POOL_NAME: &str = "wipe-pool";
+static DISK: &str = "/tmp/wipe.img";
+
+const MB: u64 = 1024 * 1024;
+
+fn request(wipe: PoolWipe) -> CreatePoolRequest {
+    CreatePoolRequest {
+        name: POOL_NAME.into(),
+        disks: vec![format!("aio://{}", DISK)],
+        wipe: wipe as i32,
+        ..Default::default()
+    }
+}
+
+/// fill len bytes of the disk at offset with the given value
+fn fill(offset: u64, len: u64, val: u8) {
+    let mut file = OpenOptions::new().write(true).open(DISK).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(&vec![val; len as usize]).unwrap();
+    file.sync_all().unwrap();
+}
+
+/// check that len bytes of the disk at offset hold the given value
+fn verify(offset: u64, len: u64, val: u8) {
+    let mut file = OpenOptions::new().read(true).open(DISK).unwrap();
+    let mut buf = vec![0; len as usize];
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert!(
+        buf.iter().all(|b| *b == val),
+        "{} bytes at {} do not hold {}",
+        len,
+        offset,
+        val
+    );
+}
+
+#[tokio::test]
+async fn lvs_wipe() {
+    common::delete_file(&[DISK.into()]);
+    common::truncate_file(DISK, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // clearing the metadata leaves the rest of the disk as it was
+    fill(0, 64 * MB, 0xa5);
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request(PoolWipe::WipeMetadata))
+            .await
+            .unwrap();
+        pool.export().await.unwrap();
+    })
+    .await;
+    verify(60 * MB, 4 * MB, 0xa5);
+
+    // while clearing all of it does not
+    fill(0, 64 * MB, 0xa5);
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request(PoolWipe::WipeFull))
+            .await
+            .unwrap();
+        pool.create_lvol("vol", 8 * MB, false).await.unwrap();
+        pool.export().await.unwrap();
+    })
+    .await;
+    verify(60 * MB, 4 * MB, 0);
+
+    // a pool which is imported is never cleared
+    fill(60 * MB, 4 * MB, 0xa5);
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request(PoolWipe::WipeFull))
+            .await
+            .unwrap();
+        assert!(pool.lvols().unwrap().any(|l| l.name() == "vol"));
+        pool.export().await.unwrap();
+    })
+    .await;
+    verify(60 * MB, 4 * MB, 0xa5);
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(request(PoolWipe::WipeNone))
+            .await
+            .unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISK.into()]);
+}
@@ -0,0 +1,24 @@
+use common::MayastorTest;
+use mayastor::core::MayastorCliArgs;
+
+pub mod common;
+
+#[tokio::test]
+async fn mayastor_test_instances() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        reactor_mask: "0x1".into(),
+        ..Default::default()
+    });
+
+    // the EAL and the reactors are process wide
+    for mask in &["0x1", "0x3", "0x2"] {
+        let error = MayastorTest::try_new(MayastorCliArgs {
+            reactor_mask: mask.to_string(),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(error.contains("overlaps"), *mask != "0x2", "{}", error);
+    }
+
+    assert!(ms.spawn(async { true }).await);
+}
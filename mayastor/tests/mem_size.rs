@@ -0,0 +1,56 @@
+use std::fs;
+
+use mayastor::core::{MayastorCliArgs, MayastorEnvironment};
+use structopt::StructOpt;
+
+/// the size of the hugepages of the system in MiB, from /proc/meminfo
+fn hugepage_size_mb() -> Option<i32> {
+    fs::read_to_string("/proc/meminfo")
+        .ok()?
+        .lines()
+        .find(|l| l.starts_with("Hugepagesize:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse::<i32>().ok())
+        .map(|kb| kb >> 10)
+}
+
+#[test]
+fn mem_size() {
+    // the memory is not limited unless asked for
+    let args = MayastorCliArgs::from_iter_safe(&["mayastor"]).unwrap();
+    assert_eq!(args.mem_size, None);
+    assert_eq!(args.hugepages, None);
+    assert_eq!(MayastorCliArgs::default().mem_size, None);
+
+    let args =
+        MayastorCliArgs::from_iter_safe(&["mayastor", "-s", "512"]).unwrap();
+    assert_eq!(args.mem_size, Some(512));
+    let args =
+        MayastorCliArgs::from_iter_safe(&["mayastor", "-s", "1GiB"]).unwrap();
+    assert_eq!(args.mem_size, Some(1024));
+
+    // no memory at all is refused, as is a size and a number of hugepages
+    let invalid: &[&[&str]] = &[
+        &["mayastor", "-s", "0"],
+        &["mayastor", "-s", "-1"],
+        &["mayastor", "--huge-pages", "0"],
+        &["mayastor", "--huge-pages", "many"],
+        &["mayastor", "-s", "512", "--huge-pages", "256"],
+    ];
+    for invalid in invalid {
+        assert!(
+            MayastorCliArgs::from_iter_safe(*invalid).is_err(),
+            "{:?} is accepted",
+            invalid
+        );
+    }
+
+    // the number of hugepages is given to the EAL as their size
+    let args =
+        MayastorCliArgs::from_iter_safe(&["mayastor", "--huge-pages", "256"])
+            .unwrap();
+    assert_eq!(args.hugepages, Some(256));
+    if let Some(size) = hugepage_size_mb() {
+        assert_eq!(MayastorEnvironment::new(args).mem_size, 256 * size);
+    }
+}
@@ -0,0 +1,76 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, NexusStatus, Reason},
+    core::{BdevHandle, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "state_nexus";
+static CHILD_1: &str = "malloc:///state0?blk_size=512&size_mb=16";
+static CHILD_2: &str = "malloc:///state1?blk_size=512&size_mb=16";
+
+const MB: u64 = 1024 * 1024;
+
+/// fill the MiB of the bdev at the given offset with the value
+async fn write(name: &str, offset: u64, val: u8) {
+    let hdl = BdevHandle::open(name, true, false).unwrap();
+    let mut buf = hdl.dma_malloc(MB).unwrap();
+    buf.fill(val);
+    hdl.write_at(offset, &buf).await.unwrap();
+}
+
+/// check that the MiB of the bdev at the given offset holds the value
+async fn verify(name: &str, offset: u64, val: u8) {
+    let hdl = BdevHandle::open(name, false, false).unwrap();
+    let mut buf = hdl.dma_malloc(MB).unwrap();
+    hdl.read_at(offset, &mut buf).await.unwrap();
+    assert!(
+        buf.as_slice().iter().all(|b| *b == val),
+        "{} does not hold {}",
+        name,
+        val
+    );
+}
+
+#[tokio::test]
+async fn nexus_child_state() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * MB,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let offset = nexus.data_ent_offset * 512;
+        assert_eq!(nexus.child_state(CHILD_1).unwrap(), ChildState::Open);
+        assert!(nexus.child_state("malloc:///other").is_err());
+        write(NEXUS_NAME, 0, 0xaa).await;
+        verify("state1", offset, 0xaa).await;
+
+        // the faulted child is no longer written to
+        nexus.fault_child(CHILD_2, Reason::Rpc).await.unwrap();
+        assert_eq!(
+            nexus.child_state(CHILD_2).unwrap(),
+            ChildState::Faulted(Reason::Rpc)
+        );
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+        write(NEXUS_NAME, 0, 0x55).await;
+        verify(NEXUS_NAME, 0, 0x55).await;
+        verify("state0", offset, 0x55).await;
+        verify("state1", offset, 0xaa).await;
+
+        // and the last healthy child is kept in the IO path
+        assert!(nexus.fault_child(CHILD_1, Reason::Rpc).await.is_err());
+        assert_eq!(nexus.child_state(CHILD_1).unwrap(), ChildState::Open);
+        verify(NEXUS_NAME, 0, 0x55).await;
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,139 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, NexusStatus, ScrubSource},
+    core::{BdevHandle, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "compare_nexus";
+static CHILD_1: &str = "malloc:///compare0?size_mb=16";
+static CHILD_2: &str = "malloc:///compare1?size_mb=16";
+static CHILD_3: &str = "malloc:///compare2?size_mb=16";
+
+const MB: u64 = 1024 * 1024;
+const BLOCK_LEN: u64 = 512;
+const IO_SIZE: u64 = 64 * 1024;
+/// the block made to diverge on one child
+const BAD_OFFSET: u64 = 3 * MB + 7 * BLOCK_LEN;
+
+#[tokio::test]
+async fn nexus_compare() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * MB,
+            None,
+            &[CHILD_1.into(), CHILD_2.into(), CHILD_3.into()],
+        )
+        .await
+        .unwrap();
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x5a);
+        for i in 0 .. 8 * MB / IO_SIZE {
+            hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+        }
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let comparison = nexus.compare_replicas(0, 8 * MB).await.unwrap();
+        assert_eq!(comparison.agree.len(), 3);
+        assert!(comparison.disagree.is_empty());
+        assert!(comparison.mismatches.is_empty());
+
+        assert!(nexus.compare_replicas(100, BLOCK_LEN).await.is_err());
+        assert!(nexus.compare_replicas(0, 100).await.is_err());
+        assert!(nexus.compare_replicas(0, 0).await.is_err());
+        assert!(nexus.compare_replicas(8 * MB, BLOCK_LEN).await.is_err());
+    })
+    .await;
+
+    // A single block of one child is overwritten below the nexus, as there
+    // is no fault injection which corrupts the data of a write. Comparing a
+    // range which covers it finds the offending child, while the nexus stays
+    // online and takes frontend IO meanwhile.
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let child = nexus.children[2].handle().unwrap();
+        let mut buf = child.dma_malloc(BLOCK_LEN).unwrap();
+        buf.fill(0xee);
+        child
+            .write_at(nexus.data_ent_offset * BLOCK_LEN + BAD_OFFSET, &buf)
+            .await
+            .unwrap();
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x5a);
+        let (comparison, written) = futures::join!(
+            nexus.compare_replicas(2 * MB, 2 * MB),
+            hdl.write_at(6 * MB, &buf)
+        );
+        written.unwrap();
+        let comparison = comparison.unwrap();
+        assert_eq!(comparison.offset, 2 * MB);
+        assert_eq!(comparison.len, 2 * MB);
+        assert_eq!(
+            comparison.agree,
+            vec![CHILD_1.to_string(), CHILD_2.to_string()]
+        );
+        assert_eq!(comparison.disagree, vec![CHILD_3.to_string()]);
+        assert_eq!(comparison.mismatches.len(), 1);
+        let mismatch = &comparison.mismatches[0];
+        assert!(mismatch.offset <= BAD_OFFSET);
+        assert!(BAD_OFFSET < mismatch.offset + mismatch.len);
+        assert_eq!(mismatch.children, vec![CHILD_3.to_string()]);
+        assert!(!mismatch.repaired);
+        assert_eq!(nexus.status(), NexusStatus::Online);
+
+        // ranges not covering the block all agree
+        let comparison = nexus.compare_replicas(0, 3 * MB).await.unwrap();
+        assert!(comparison.disagree.is_empty());
+        let comparison = nexus
+            .compare_replicas(BAD_OFFSET + BLOCK_LEN, BLOCK_LEN)
+            .await
+            .unwrap();
+        assert!(comparison.disagree.is_empty());
+
+        // the divergent block is still there as nothing was repaired
+        let comparison =
+            nexus.compare_replicas(BAD_OFFSET, BLOCK_LEN).await.unwrap();
+        assert_eq!(comparison.disagree, vec![CHILD_3.to_string()]);
+    })
+    .await;
+
+    // repairing the range rewrites the offending child only
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let report = nexus
+            .repair_range(3 * MB, MB, ScrubSource::Majority)
+            .await
+            .unwrap();
+        assert_eq!(report.scrubbed, MB);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].children, vec![CHILD_3.to_string()]);
+        assert!(report.mismatches[0].repaired);
+
+        let comparison = nexus.compare_replicas(0, 8 * MB).await.unwrap();
+        assert_eq!(comparison.agree.len(), 3);
+        assert!(comparison.disagree.is_empty());
+
+        let child = nexus.children[2].handle().unwrap();
+        let mut buf = child.dma_malloc(BLOCK_LEN).unwrap();
+        child
+            .read_at(nexus.data_ent_offset * BLOCK_LEN + BAD_OFFSET, &mut buf)
+            .await
+            .unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x5a));
+
+        assert!(nexus
+            .repair_range(0, MB, ScrubSource::Child("nope".into()))
+            .await
+            .is_err());
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,195 @@
+//! Graceful failover of a multipath NVMf nexus
+//! The same nexus is shared on both nodes, with a replica on the remote node
+//! as their child. The local nexus is failed over the way a clean shutdown
+//! does, while the initiator keeps writing to the volume.
+use mayastor::{
+    bdev::{nexus_create, nexus_failover_all, nexus_lookup},
+    core::MayastorCliArgs,
+    subsys::{AnaState, NvmfSubsystem},
+};
+use rpc::mayastor::{
+    CreateNexusRequest,
+    CreatePoolRequest,
+    CreateReplicaRequest,
+    PublishNexusRequest,
+    ShareProtocolNexus,
+    ShareReplicaRequest,
+};
+use std::{fs, process::Command};
+
+pub mod common;
+use common::{compose::Builder, MayastorTest};
+
+static POOL_NAME: &str = "tpool";
+static UUID: &str = "5f7b3e2a-6c1d-4e8f-9a0b-1c2d3e4f5a6b";
+static HOSTNQN: &str = "nqn.2019-05.io.openebs";
+
+/// the multipath block device of the subsystem with the given NQN
+fn nvme_device(nqn: &str) -> String {
+    for entry in fs::read_dir("/sys/class/nvme-subsystem").unwrap() {
+        let path = entry.unwrap().path();
+        let subsysnqn = fs::read_to_string(path.join("subsysnqn")).unwrap();
+        if subsysnqn.trim() != nqn {
+            continue;
+        }
+        // the namespace of the subsystem, next to its controllers
+        for dev in fs::read_dir(&path).unwrap() {
+            let name = dev.unwrap().file_name().into_string().unwrap();
+            if name.starts_with("nvme") && name[4 ..].contains('n') {
+                return format!("/dev/{}", name);
+            }
+        }
+    }
+    panic!("no block device for {}", nqn);
+}
+
+/// write the given number of MiB to the device, bypassing the page cache
+fn dd(device: &str, mb: u32) -> Command {
+    let mut cmd = Command::new("dd");
+    cmd.args(&["if=/dev/urandom", &format!("of={}", device)])
+        .args(&["bs=1M", &format!("count={}", mb), "oflag=direct"]);
+    cmd
+}
+
+#[tokio::test]
+async fn nexus_failover() {
+    let test = Builder::new()
+        .name("nexus_failover_test")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let mut hdls = test.grpc_handles().await.unwrap();
+
+    hdls[0]
+        .mayastor
+        .create_pool(CreatePoolRequest {
+            name: POOL_NAME.to_string(),
+            disks: vec!["malloc:///disk0?size_mb=64".into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    hdls[0]
+        .mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: UUID.to_string(),
+            pool: POOL_NAME.to_string(),
+            size: 32 * 1024 * 1024,
+            thin: false,
+            share: 0,
+            protection: false,
+            checksum: false,
+            checksum_algorithm: 0,
+            compression: false,
+            compression_algorithm: 0,
+        })
+        .await
+        .unwrap();
+
+    hdls[0]
+        .mayastor
+        .create_nexus(CreateNexusRequest {
+            uuid: UUID.to_string(),
+            size: 32 * 1024 * 1024,
+            children: [format!("loopback:///{}", UUID)].to_vec(),
+        })
+        .await
+        .unwrap();
+
+    hdls[0]
+        .mayastor
+        .share_replica(ShareReplicaRequest {
+            uuid: UUID.to_string(),
+            share: 1,
+        })
+        .await
+        .unwrap();
+
+    let mayastor = MayastorTest::new(MayastorCliArgs::default());
+    let ip0 = hdls[0].endpoint.ip();
+    let nexus_name = format!("nexus-{}", UUID);
+    let name = nexus_name.clone();
+    mayastor
+        .spawn(async move {
+            nexus_create(
+                &name,
+                32 * 1024 * 1024,
+                Some(UUID),
+                &[format!("nvmf://{}:8420/{}:{}", ip0, HOSTNQN, UUID)],
+            )
+            .await
+            .unwrap();
+            nexus_lookup(&name)
+                .unwrap()
+                .share(ShareProtocolNexus::NexusNvmf, None)
+                .await
+                .unwrap();
+        })
+        .await;
+
+    hdls[0]
+        .mayastor
+        .publish_nexus(PublishNexusRequest {
+            uuid: UUID.to_string(),
+            key: "".to_string(),
+            share: ShareProtocolNexus::NexusNvmf as i32,
+        })
+        .await
+        .unwrap();
+
+    // connect to the local nexus first, so that IO starts out on it
+    let nqn = format!("{}:nexus-{}", HOSTNQN, UUID);
+    for ip in &["127.0.0.1".to_string(), ip0.to_string()] {
+        // the first attempt often fails with "Duplicate cntlid x with y"
+        // error from kernel
+        let connected = (0 .. 2).any(|_| {
+            Command::new("nvme")
+                .args(&["connect", "-t", "tcp", "-a", ip, "-s", "8420"])
+                .args(&["-n", &nqn])
+                .status()
+                .unwrap()
+                .success()
+        });
+        assert!(connected, "failed to connect to the nexus on {}", ip);
+    }
+    let device = nvme_device(&nqn);
+
+    // the path through the local nexus is made inaccessible while writing,
+    // and the initiator carries on through the remote nexus
+    let writer = dd(&device, 32).spawn().unwrap();
+    let name = nexus_name.clone();
+    let state = mayastor
+        .spawn(async move {
+            nexus_failover_all().await;
+            NvmfSubsystem::nqn_lookup(&name).unwrap().ana_state()
+        })
+        .await;
+    assert_eq!(state, Some(AnaState::Inaccessible));
+
+    // tearing down the local nexus does not fail the IO either
+    let name = nexus_name.clone();
+    mayastor
+        .spawn(async move {
+            nexus_lookup(&name).unwrap().destroy().await.unwrap();
+        })
+        .await;
+    let output = writer.wait_with_output().unwrap();
+    assert!(output.status.success(), "IO failed during failover");
+    let status = dd(&device, 8).status().unwrap();
+    assert!(status.success(), "IO failed after failover");
+
+    let output = Command::new("nvme")
+        .args(&["disconnect", "-n", &nqn])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "failed to disconnect from nexuses, {}",
+        output.status
+    );
+}
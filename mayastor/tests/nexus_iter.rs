@@ -0,0 +1,55 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, ChildState, Nexus},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static CHILDREN: [&str; 3] = [
+    "malloc:///iter0?size_mb=64",
+    "malloc:///iter1?size_mb=64",
+    "malloc:///iter2?size_mb=64",
+];
+
+#[tokio::test]
+async fn nexus_iter() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        assert_eq!(Nexus::iter().count(), 0);
+        assert!(Nexus::lookup("iter-a").is_none());
+
+        nexus_create("iter-a", 32 * 1024 * 1024, None, &[
+            CHILDREN[0].to_string(),
+            CHILDREN[1].to_string(),
+        ])
+        .await
+        .unwrap();
+        nexus_create("iter-b", 32 * 1024 * 1024, None, &[
+            CHILDREN[2].to_string()
+        ])
+        .await
+        .unwrap();
+
+        let mut names =
+            Nexus::iter().map(|n| n.name().to_string()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["iter-a", "iter-b"]);
+
+        let nexus = Nexus::lookup("iter-a").unwrap();
+        assert_eq!(nexus.name(), "iter-a");
+        assert_eq!(nexus.children(), vec![
+            (CHILDREN[0].to_string(), ChildState::Open),
+            (CHILDREN[1].to_string(), ChildState::Open),
+        ]);
+
+        // the nexuses can be cleaned up without knowing their names
+        for nexus in Nexus::iter() {
+            nexus.destroy().await.unwrap();
+        }
+        assert_eq!(Nexus::iter().count(), 0);
+        assert!(Nexus::lookup("iter-b").is_none());
+    })
+    .await;
+}
@@ -41,6 +41,7 @@ async fn nexus_multipath() {
         .create_pool(CreatePoolRequest {
             name: POOL_NAME.to_string(),
             disks: vec!["malloc:///disk0?size_mb=64".into()],
+            ..Default::default()
         })
         .await
         .unwrap();
@@ -54,6 +55,11 @@ async fn nexus_multipath() {
             size: 32 * 1024 * 1024,
             thin: false,
             share: 0,
+            protection: false,
+            checksum: false,
+            checksum_algorithm: 0,
+            compression: false,
+            compression_algorithm: 0,
         })
         .await
         .unwrap();
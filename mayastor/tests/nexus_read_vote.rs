@@ -0,0 +1,167 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ReadVote},
+    core::{BdevHandle, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "vote_nexus";
+static CHILD_1: &str = "malloc:///vote0?size_mb=16";
+static CHILD_2: &str = "malloc:///vote1?size_mb=16";
+static CHILD_3: &str = "malloc:///vote2?size_mb=16";
+
+static NEXUS_PAIR: &str = "vote_pair";
+static PAIR_1: &str = "malloc:///pair0?size_mb=16";
+static PAIR_2: &str = "malloc:///pair1?size_mb=16";
+
+const MB: u64 = 1024 * 1024;
+const BLOCK_LEN: u64 = 512;
+const IO_SIZE: u64 = 64 * 1024;
+/// the block made to diverge on one child
+const BAD_OFFSET: u64 = 3 * MB;
+
+/// overwrite the block at the given offset of the nexus on one of its
+/// children, below the nexus, as there is no fault injection which corrupts
+/// the data of a write
+async fn corrupt(child: usize, offset: u64, pattern: u8) {
+    let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+    let hdl = nexus.children[child].handle().unwrap();
+    let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+    buf.fill(pattern);
+    hdl.write_at(nexus.data_ent_offset * BLOCK_LEN + offset, &buf)
+        .await
+        .unwrap();
+}
+
+/// read the block at the given offset of the nexus on one of its children
+async fn child_block(child: usize, offset: u64) -> Vec<u8> {
+    let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+    let hdl = nexus.children[child].handle().unwrap();
+    let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+    hdl.read_at(nexus.data_ent_offset * BLOCK_LEN + offset, &mut buf)
+        .await
+        .unwrap();
+    buf.as_slice().to_vec()
+}
+
+#[tokio::test]
+async fn nexus_read_vote() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * MB,
+            None,
+            &[CHILD_1.into(), CHILD_2.into(), CHILD_3.into()],
+        )
+        .await
+        .unwrap();
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x5a);
+        for i in 0 .. 8 * MB / IO_SIZE {
+            hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+        }
+        corrupt(2, BAD_OFFSET, 0xee).await;
+
+        // without voting, reads go to one child in turn and one of them
+        // returns the corrupt block
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.read_vote(), ReadVote::Off);
+        let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+        let mut corrupt = 0;
+        for _ in 0 .. 3 {
+            hdl.read_at(BAD_OFFSET, &mut buf).await.unwrap();
+            if buf.as_slice().iter().all(|b| *b == 0xee) {
+                corrupt += 1;
+            }
+        }
+        assert_eq!(corrupt, 1);
+    })
+    .await;
+
+    // flagging returns the majority data, and leaves the child corrupt
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.set_read_vote(ReadVote::Flag).unwrap();
+        assert_eq!(nexus.read_vote(), ReadVote::Flag);
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        for _ in 0 .. 3 {
+            hdl.read_at(BAD_OFFSET, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == 0x5a));
+        }
+        assert_eq!(nexus.children[0].vote_mismatches(), 0);
+        assert_eq!(nexus.children[1].vote_mismatches(), 0);
+        assert_eq!(nexus.children[2].vote_mismatches(), 3);
+
+        // reads not covering the block agree
+        hdl.read_at(BAD_OFFSET + IO_SIZE, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x5a));
+        assert_eq!(nexus.children[2].vote_mismatches(), 3);
+
+        assert!(child_block(2, BAD_OFFSET).await.iter().all(|b| *b == 0xee));
+    })
+    .await;
+
+    // repairing rewrites the minority in the background
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.set_read_vote(ReadVote::Repair).unwrap();
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+        hdl.read_at(BAD_OFFSET, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x5a));
+        assert_eq!(nexus.children[2].vote_mismatches(), 4);
+    })
+    .await;
+
+    let mut repaired = false;
+    for _ in 0 .. 50 {
+        repaired = ms
+            .spawn(async {
+                child_block(2, BAD_OFFSET).await.iter().all(|b| *b == 0x5a)
+            })
+            .await;
+        if repaired {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(repaired);
+
+    // without a majority the read fails
+    ms.spawn(async {
+        corrupt(1, BAD_OFFSET, 0xee).await;
+        corrupt(2, BAD_OFFSET, 0xaa).await;
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.set_read_vote(ReadVote::Flag).unwrap();
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+        assert!(hdl.read_at(BAD_OFFSET, &mut buf).await.is_err());
+
+        nexus.set_read_vote(ReadVote::Off).unwrap();
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    // voting needs three healthy children
+    ms.spawn(async {
+        nexus_create(NEXUS_PAIR, 8 * MB, None, &[PAIR_1.into(), PAIR_2.into()])
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_PAIR).unwrap();
+        assert!(nexus.set_read_vote(ReadVote::Flag).is_err());
+        assert!(nexus.set_read_vote(ReadVote::Repair).is_err());
+        assert_eq!(nexus.read_vote(), ReadVote::Off);
+        nexus.set_read_vote(ReadVote::Off).unwrap();
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState},
+    core::MayastorCliArgs,
+    rebuild::RebuildState,
+    subsys::Config,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "progress_nexus";
+static CHILD_1: &str = "malloc:///progress0?blk_size=512&size_mb=8";
+static CHILD_2: &str = "malloc:///progress1?blk_size=512&size_mb=8";
+
+static YAML_CONFIG_FILE: &str = "/tmp/nexus_rebuild_progress.yaml";
+
+const NEXUS_SIZE_MB: u64 = 4;
+
+#[tokio::test]
+async fn nexus_rebuild_progress() {
+    // the rebuild crawls at 1 MiB/s, to be watched
+    let mut config = Config::default();
+    config.rebuild_opts.rate_limit_mbps = 1;
+    config.write(YAML_CONFIG_FILE).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        mayastor_config: Some(YAML_CONFIG_FILE.to_string()),
+        ..Default::default()
+    });
+
+    let rebuild = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NEXUS_SIZE_MB << 20,
+                None,
+                &[CHILD_1.to_string()],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.add_child(CHILD_2, true).await.unwrap();
+            assert!(nexus.rebuild_progress(CHILD_2).is_err());
+            assert_eq!(
+                nexus.rebuild_progress(CHILD_1).unwrap().state,
+                RebuildState::Completed
+            );
+
+            nexus.start_rebuild(CHILD_2).await.unwrap()
+        })
+        .await;
+
+    tokio::time::delay_for(Duration::from_secs(1)).await;
+    let rebuilt = ms
+        .spawn(async {
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            let progress = nexus.rebuild_progress(CHILD_2).unwrap();
+            assert_eq!(progress.state, RebuildState::Running);
+            assert_eq!(progress.blocks_total, (NEXUS_SIZE_MB << 20) / 512);
+            assert!(progress.blocks_rebuilt > 0);
+            assert!(progress.blocks_rebuilt < progress.blocks_total);
+            assert!(progress.percentage < 100);
+
+            nexus.pause_rebuild(CHILD_2).await.unwrap();
+            assert_eq!(
+                nexus.rebuild_progress(CHILD_2).unwrap().state,
+                RebuildState::Paused
+            );
+            progress.blocks_rebuilt
+        })
+        .await;
+
+    // no more than the segments in flight are rebuilt while paused
+    tokio::time::delay_for(Duration::from_secs(1)).await;
+    ms.spawn(async move {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let progress = nexus.rebuild_progress(CHILD_2).unwrap();
+        assert_eq!(progress.state, RebuildState::Paused);
+        assert!(progress.blocks_rebuilt < progress.blocks_total);
+        assert!(progress.blocks_rebuilt >= rebuilt);
+        nexus.resume_rebuild(CHILD_2).await.unwrap();
+        assert_eq!(
+            nexus.rebuild_progress(CHILD_2).unwrap().state,
+            RebuildState::Running
+        );
+    })
+    .await;
+
+    // once done, the child becomes healthy
+    let state = ms.spawn(async { rebuild.await.unwrap() }).await;
+    assert_eq!(state, RebuildState::Completed);
+    let start = Instant::now();
+    while ms
+        .spawn(async {
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.child_lookup("progress1").unwrap().state() != ChildState::Open
+        })
+        .await
+    {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+    }
+
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let progress = nexus.rebuild_progress(CHILD_2).unwrap();
+        assert_eq!(progress.state, RebuildState::Completed);
+        assert_eq!(progress.percentage, 100);
+        assert_eq!(progress.blocks_rebuilt, progress.blocks_total);
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[YAML_CONFIG_FILE.into()]);
+}
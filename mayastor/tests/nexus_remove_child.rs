@@ -0,0 +1,63 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, NexusStatus},
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "remove-nexus";
+static CHILD1: &str = "malloc:///remove-child1?size_mb=64";
+static CHILD2: &str = "malloc:///remove-child2?size_mb=64";
+static CHILD3: &str = "malloc:///remove-child3?size_mb=64";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn nexus_remove_child() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, 32 * MB, None, &[CHILD1.into(), CHILD2.into()])
+            .await
+            .unwrap();
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(MB).unwrap();
+        buf.fill(0xaa);
+        hdl.write_at(0, &buf).await.unwrap();
+        drop(hdl);
+
+        // a child out of sync does not count as healthy
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.add_child(CHILD3, true).await.unwrap();
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+
+        // the child removed is gone along with its bdev
+        nexus.remove_child(CHILD1).await.unwrap();
+        assert_eq!(nexus.children.len(), 2);
+        assert!(Bdev::lookup_by_name("remove-child1").is_none());
+
+        // while the last healthy child stays
+        let err = nexus.remove_child(CHILD2).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Cannot delete the last healthy child {} of nexus {}",
+                CHILD2, NEXUS_NAME
+            )
+        );
+        assert_eq!(nexus.children.len(), 2);
+
+        // and the nexus is read from it
+        let hdl = BdevHandle::open(NEXUS_NAME, false, false).unwrap();
+        let mut buf = hdl.dma_malloc(MB).unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xaa));
+        drop(hdl);
+
+        nexus.remove_child(CHILD3).await.unwrap();
+        assert_eq!(nexus.status(), NexusStatus::Online);
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,83 @@
+use std::convert::TryFrom;
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{Bdev, MayastorCliArgs},
+    lvs::{Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME1: &str = "/tmp/resize.img";
+static NEXUS_NAME: &str = "resize_nexus";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn nexus_resize_test() {
+    common::delete_file(&[DISKNAME1.into()]);
+    common::truncate_file(DISKNAME1, 128 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: "rpool".into(),
+            disks: vec![format!("aio://{}", DISKNAME1)],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        pool.create_lvol("rvol", 16 * MB, false).await.unwrap();
+
+        nexus_create(NEXUS_NAME, 16 * MB, None, &["bdev:///rvol".into()])
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // the nexus can not grow beyond the size of its children
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.resize(32 * MB).await.is_err());
+    })
+    .await;
+
+    // grow the replica first and then the nexus on top of it
+    ms.spawn(async {
+        let lvol = Bdev::lookup_by_name("rvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .unwrap();
+        lvol.resize(32 * MB, false).await.unwrap();
+        assert_eq!(lvol.size(), 32 * MB);
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let before = nexus.size();
+        nexus.resize(32 * MB).await.unwrap();
+        assert!(nexus.size() > before);
+        assert!(nexus.size() <= 32 * MB);
+    })
+    .await;
+
+    // shrinking is not supported
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.resize(16 * MB).await.is_err());
+
+        let lvol = Bdev::lookup_by_name("rvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .unwrap();
+        assert!(lvol.resize(16 * MB, false).await.is_err());
+    })
+    .await;
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+        Lvs::lookup("rpool").unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME1.into()]);
+}
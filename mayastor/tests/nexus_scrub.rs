@@ -0,0 +1,125 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ScrubSource},
+    core::{BdevHandle, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "scrub_nexus";
+static CHILD_1: &str = "malloc:///scrub0?size_mb=16";
+static CHILD_2: &str = "malloc:///scrub1?size_mb=16";
+static CHILD_3: &str = "malloc:///scrub2?size_mb=16";
+
+const MB: u64 = 1024 * 1024;
+const BLOCK_LEN: u64 = 512;
+const IO_SIZE: u64 = 64 * 1024;
+
+/// overwrite part of the data of a child of the nexus, below the nexus
+async fn corrupt_child(child: usize, offset: u64) {
+    let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+    let hdl = nexus.children[child].handle().unwrap();
+    let mut buf = hdl.dma_malloc(BLOCK_LEN).unwrap();
+    buf.fill(0xee);
+    hdl.write_at(nexus.data_ent_offset * BLOCK_LEN + offset, &buf)
+        .await
+        .unwrap();
+}
+
+/// check that all children of the nexus hold the given value
+async fn check_children(val: u8) {
+    let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+    for child in &nexus.children {
+        let hdl = child.handle().unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        let start = nexus.data_ent_offset * BLOCK_LEN;
+        for i in 0 .. 8 * MB / IO_SIZE {
+            hdl.read_at(start + i * IO_SIZE, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == val));
+        }
+    }
+}
+
+#[tokio::test]
+async fn nexus_scrub() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * MB,
+            None,
+            &[CHILD_1.into(), CHILD_2.into(), CHILD_3.into()],
+        )
+        .await
+        .unwrap();
+
+        let hdl = BdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x5a);
+        for i in 0 .. 8 * MB / IO_SIZE {
+            hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+        }
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let report = nexus.scrub(None).await.unwrap();
+        assert_eq!(report.scrubbed, 8 * MB);
+        assert!(report.mismatches.is_empty());
+    })
+    .await;
+
+    // a scrub without a source only reports the divergent segments
+    ms.spawn(async {
+        corrupt_child(1, MB + 4096).await;
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let report = nexus.scrub(None).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].offset, MB);
+        assert_eq!(report.mismatches[0].len, IO_SIZE);
+        assert!(!report.mismatches[0].repaired);
+
+        let report = nexus.scrub(None).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+    })
+    .await;
+
+    // the majority repairs the corrupted child
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let report = nexus.scrub(Some(ScrubSource::Majority)).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].children, vec![CHILD_2.to_string()]);
+        assert!(report.mismatches[0].repaired);
+
+        assert!(nexus.scrub(None).await.unwrap().mismatches.is_empty());
+        check_children(0x5a).await;
+    })
+    .await;
+
+    // a designated child repairs the others wherever they differ from it
+    ms.spawn(async {
+        corrupt_child(0, 4 * MB).await;
+        corrupt_child(2, 6 * MB).await;
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let report = nexus
+            .scrub(Some(ScrubSource::Child(CHILD_2.into())))
+            .await
+            .unwrap();
+        assert_eq!(report.mismatches.len(), 2);
+        assert_eq!(report.mismatches[0].offset, 4 * MB);
+        assert_eq!(report.mismatches[0].children, vec![CHILD_1.to_string()]);
+        assert_eq!(report.mismatches[1].offset, 6 * MB);
+        assert_eq!(report.mismatches[1].children, vec![CHILD_3.to_string()]);
+        assert!(report.mismatches.iter().all(|m| m.repaired));
+
+        assert!(nexus.scrub(None).await.unwrap().mismatches.is_empty());
+        check_children(0x5a).await;
+
+        assert!(nexus
+            .scrub(Some(ScrubSource::Child("nope".into())))
+            .await
+            .is_err());
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,65 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, IoType},
+    core::{Bdev, MayastorCliArgs},
+    lvs::Lvs,
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/unmap-disk.img";
+static AIO_CHILD: &str = "aio:///tmp/unmap-disk.img?blk_size=512";
+
+static POOL_NAME: &str = "unmap-pool";
+static NEXUS_NAME: &str = "unmap_nexus";
+static MIXED_NEXUS: &str = "unmap_mixed";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn nexus_unmap() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 32 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec!["malloc:///unmap-disk?size_mb=64".into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        pool.create_lvol("uvol", 16 * MB, true).await.unwrap();
+        pool.create_lvol("uvol2", 16 * MB, true).await.unwrap();
+
+        // unmaps of the nexus reach the thin lvols, so it advertises them
+        nexus_create(NEXUS_NAME, 8 * MB, None, &["bdev:///uvol".into()])
+            .await
+            .unwrap();
+        let nexus = Bdev::lookup_by_name(NEXUS_NAME).unwrap();
+        assert!(nexus.io_type_supported(IoType::Unmap));
+        assert!(nexus.io_type_supported(IoType::Flush));
+
+        // but not when one of its children can not unmap
+        nexus_create(
+            MIXED_NEXUS,
+            8 * MB,
+            None,
+            &["bdev:///uvol2".into(), AIO_CHILD.into()],
+        )
+        .await
+        .unwrap();
+        let mixed = Bdev::lookup_by_name(MIXED_NEXUS).unwrap();
+        assert!(!mixed.io_type_supported(IoType::Unmap));
+        assert!(mixed.io_type_supported(IoType::Flush));
+
+        nexus_lookup(MIXED_NEXUS).unwrap().destroy().await.unwrap();
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
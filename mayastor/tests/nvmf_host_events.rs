@@ -0,0 +1,106 @@
+use std::{convert::TryFrom, process::Command, time::Duration};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs, Share},
+    events::{self, Event, Subscription},
+    nexus_uri::{bdev_create, bdev_destroy},
+    subsys::NvmfSubsystem,
+};
+use nvmeadm::NvmeTarget;
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///hostev?size_mb=64";
+static MALLOC_NAME: &str = "hostev";
+
+/// run the nvme command with the given arguments
+fn nvme(args: &[&str]) {
+    let status = Command::new("nvme").args(args).status().unwrap();
+    assert!(status.success(), "nvme {:?} failed, {}", args, status);
+}
+
+/// wait for an event of the subscription matching the predicate
+async fn wait_for(sub: &Subscription, f: impl Fn(&Event) -> bool) -> Event {
+    for _ in 0 .. 50 {
+        if let Some(event) = sub.receiver().try_iter().find(|e| f(e)) {
+            return event;
+        }
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+    }
+    panic!("no event received");
+}
+
+#[tokio::test]
+async fn nvmf_host_events() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    let sub = events::subscribe();
+
+    let uri = ms
+        .spawn(async {
+            bdev_create(MALLOC_BDEV).await.unwrap();
+            let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+            bdev.share_nvmf().await.unwrap();
+            bdev.share_uri().unwrap()
+        })
+        .await;
+    let nqn = NvmeTarget::try_from(uri.as_str())
+        .unwrap()
+        .subsysnqn()
+        .to_string();
+
+    let connected_count = |nqn: String| {
+        ms.spawn(async move {
+            NvmfSubsystem::first()
+                .unwrap()
+                .into_iter()
+                .find(|s| s.get_nqn() == nqn)
+                .unwrap()
+                .connected_count()
+        })
+    };
+    assert_eq!(connected_count(nqn.clone()).await, 0);
+
+    nvme(&[
+        "connect", "-t", "tcp", "-a", "127.0.0.1", "-s", "8420", "-n", &nqn,
+    ]);
+    let connected = wait_for(&sub, |e| {
+        matches!(e, Event::HostConnected { nqn: n, .. } if *n == nqn)
+    })
+    .await;
+    assert_eq!(connected_count(nqn.clone()).await, 1);
+
+    // the host going away is seen on the controller it had
+    nvme(&["disconnect", "-n", &nqn]);
+    let disconnected = wait_for(&sub, |e| {
+        matches!(e, Event::HostDisconnected { nqn: n, .. } if *n == nqn)
+    })
+    .await;
+    assert_eq!(connected_count(nqn.clone()).await, 0);
+    match (connected, disconnected) {
+        (
+            Event::HostConnected {
+                host,
+                cntlid,
+                ..
+            },
+            Event::HostDisconnected {
+                host: gone,
+                cntlid: gone_cntlid,
+                ..
+            },
+        ) => {
+            assert!(!host.is_empty());
+            assert_eq!(host, gone);
+            assert_eq!(cntlid, gone_cntlid);
+        }
+        events => panic!("unexpected events {:?}", events),
+    }
+
+    ms.spawn(async {
+        let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+        bdev.unshare().await.unwrap();
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,77 @@
+use std::{convert::TryFrom, process::Command};
+
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs, Protocol, Share},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use nvmeadm::NvmeTarget;
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///prefixed?size_mb=64";
+static MALLOC_NAME: &str = "prefixed";
+static PREFIX: &str = "nqn.2021-02.io.openebs.mayastor:instance-1";
+static READ_FILE: &str = "/tmp/nvmf-nqn-prefix-read";
+
+#[tokio::test]
+async fn nvmf_nqn_prefix() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let uri = ms
+        .spawn(async {
+            bdev_create(MALLOC_BDEV).await.unwrap();
+            let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+
+            // prefixes which are not NQNs are refused
+            for prefix in &[
+                "",
+                "io.openebs",
+                "nqn.io.openebs",
+                "nqn.2021-2.io.openebs",
+                "nqn.2021-02.",
+                "nqn.2021-02.io/openebs",
+                "nqn.2021-02.io.OpenEBS",
+                "nqn.2021-02.io.openebs:",
+                "nqn.2021-02.io.openebs:a b",
+            ] {
+                assert!(
+                    bdev.share_nvmf_with_prefix(prefix).await.is_err(),
+                    "{} is accepted",
+                    prefix
+                );
+            }
+
+            // as are those making an NQN too long
+            let long = format!("{}:{}", PREFIX, "x".repeat(200));
+            assert!(bdev.share_nvmf_with_prefix(&long).await.is_err());
+            assert_eq!(bdev.shared(), Some(Protocol::Off));
+
+            bdev.share_nvmf_with_prefix(PREFIX).await.unwrap();
+            bdev.share_uri().unwrap()
+        })
+        .await;
+
+    let target = NvmeTarget::try_from(uri.as_str()).unwrap();
+    assert_eq!(
+        target.subsysnqn(),
+        format!("{}:{}", PREFIX, MALLOC_NAME).as_str()
+    );
+
+    // the share under the prefix is connected to
+    let status = Command::new("../target/debug/initiator")
+        .args(&[&uri, "read", READ_FILE])
+        .status()
+        .expect("failed to run the initiator");
+    assert!(status.success());
+
+    ms.spawn(async {
+        let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+        bdev.unshare().await.unwrap();
+        assert!(bdev.share_uri().unwrap().starts_with("bdev:///"));
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[READ_FILE.into()]);
+}
@@ -0,0 +1,221 @@
+use common::{integrity, MayastorTest};
+use mayastor::{
+    bdev::pi_lookup,
+    core::{
+        Bdev,
+        BdevHandle,
+        CoreError,
+        DmaBuf,
+        MayastorCliArgs,
+        Protocol,
+        Share,
+    },
+    lvs::{Lvs, PropValue},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use spdk_sys::{
+    iovec,
+    spdk_dif_ctx,
+    spdk_dif_ctx_init,
+    spdk_dif_generate,
+    SPDK_DIF_FLAGS_GUARD_CHECK,
+    SPDK_DIF_FLAGS_REFTAG_CHECK,
+    SPDK_DIF_TYPE1,
+};
+
+pub mod common;
+
+static BASE_BDEV: &str = "malloc:///base?size_mb=64";
+static BASE_NAME: &str = "base";
+static PI_BDEV: &str = "pi:///base";
+static PI_NAME: &str = "base-pi";
+
+static DISKNAME: &str = "/tmp/pi-disk.img";
+static POOL_DISK: &str = "aio:///tmp/pi-disk.img";
+static POOL_NAME: &str = "pi-pool";
+static LVOL_NAME: &str = "pi-lvol";
+
+const DATA_LEN: u64 = 512;
+const BLOCK_LEN: u64 = DATA_LEN + 8;
+/// number of blocks written at once
+const BLOCKS: u64 = 16;
+
+/// generate the protection information of the blocks in the buffer, which
+/// start at the given LBA
+fn generate_pi(buf: &mut DmaBuf, lba: u64) {
+    let mut ctx = spdk_dif_ctx::default();
+    let mut iov = iovec {
+        iov_base: **buf,
+        iov_len: buf.len(),
+    };
+    unsafe {
+        assert_eq!(
+            spdk_dif_ctx_init(
+                &mut ctx,
+                BLOCK_LEN as u32,
+                8,
+                true,
+                false,
+                SPDK_DIF_TYPE1,
+                SPDK_DIF_FLAGS_GUARD_CHECK | SPDK_DIF_FLAGS_REFTAG_CHECK,
+                lba as u32,
+                0,
+                0,
+                0,
+                0,
+            ),
+            0
+        );
+        let blocks = (buf.len() / BLOCK_LEN) as u32;
+        assert_eq!(spdk_dif_generate(&mut iov, 1, blocks, &ctx), 0);
+    }
+}
+
+/// the data of the blocks in the buffer without their protection information
+fn data(buf: &DmaBuf) -> Vec<u8> {
+    buf.as_slice()
+        .chunks(BLOCK_LEN as usize)
+        .flat_map(|b| b[.. DATA_LEN as usize].to_vec())
+        .collect()
+}
+
+#[tokio::test]
+async fn pi_bdev() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(BASE_BDEV).await.unwrap();
+        assert_eq!(bdev_create(PI_BDEV).await.unwrap(), PI_NAME);
+
+        let bdev = Bdev::lookup_by_name(PI_NAME).unwrap();
+        let base = Bdev::lookup_by_name(BASE_NAME).unwrap();
+        assert_eq!(bdev.block_len() as u64, BLOCK_LEN);
+        assert_eq!(bdev.md_len(), 8);
+        assert_eq!(bdev.dif_type(), SPDK_DIF_TYPE1);
+        // one block of protection information for every 64 blocks of data
+        assert_eq!(bdev.num_blocks(), base.num_blocks() * 64 / 65);
+    })
+    .await;
+
+    // blocks written with valid protection information read back fine,
+    // including the ones sharing a block of protection information with
+    // blocks written before
+    ms.spawn(async {
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        for (i, lba) in [0, 60, 100].iter().enumerate() {
+            buf.fill(i as u8 + 1);
+            generate_pi(&mut buf, *lba);
+            hdl.write_at(lba * BLOCK_LEN, &buf).await.unwrap();
+        }
+
+        let mut read = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        for (i, lba) in [0, 60, 100].iter().enumerate() {
+            buf.fill(i as u8 + 1);
+            generate_pi(&mut buf, *lba);
+            hdl.read_at(lba * BLOCK_LEN, &mut read).await.unwrap();
+            assert_eq!(read.as_slice(), buf.as_slice());
+        }
+
+        // blocks never written are not checked
+        hdl.read_at(1000 * BLOCK_LEN, &mut read).await.unwrap();
+    })
+    .await;
+
+    // a write with a guard which does not match its data fails with a guard
+    // check error and is not written
+    ms.spawn(async {
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        buf.fill(0x55);
+        generate_pi(&mut buf, 0);
+        buf.as_mut_slice()[DATA_LEN as usize] ^= 0xff;
+        assert!(matches!(
+            hdl.write_at(0, &buf).await,
+            Err(CoreError::GuardCheckFailed { .. })
+        ));
+
+        let mut read = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        hdl.read_at(0, &mut read).await.unwrap();
+        assert!(data(&read).iter().all(|b| *b == 1));
+    })
+    .await;
+
+    // corrupting the data below the PI bdev is detected when it is read
+    ms.spawn(async {
+        integrity::corrupt_block(BASE_NAME, 61 * DATA_LEN, DATA_LEN).await;
+
+        let hdl = BdevHandle::open(PI_NAME, true, false).unwrap();
+        let mut read = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        assert!(matches!(
+            hdl.read_at(60 * BLOCK_LEN, &mut read).await,
+            Err(CoreError::GuardCheckFailed { .. })
+        ));
+        // the blocks around it are fine
+        hdl.read_at(0, &mut read).await.unwrap();
+        hdl.read_at(100 * BLOCK_LEN, &mut read).await.unwrap();
+
+        bdev_destroy(PI_BDEV).await.unwrap();
+        bdev_destroy(BASE_BDEV).await.unwrap();
+    })
+    .await;
+
+    // a protected lvol is shared through a PI bdev, its protection
+    // information survives exporting and importing the pool
+    ms.spawn(async {
+        let pool = integrity::create_pool(POOL_NAME, POOL_DISK).await;
+        let lvol = pool
+            .create_lvol(LVOL_NAME, 8 * 1024 * 1024, false)
+            .await
+            .unwrap();
+        assert!(!lvol.is_protected().await);
+        lvol.set(PropValue::Protected(true)).await.unwrap();
+        assert!(lvol.is_protected().await);
+
+        lvol.share_nvmf().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+        assert!(lvol.share_uri().unwrap().contains(LVOL_NAME));
+        let pi = format!("{}-pi", LVOL_NAME);
+        assert!(pi_lookup(&pi).is_some());
+
+        let hdl = BdevHandle::open(&pi, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        buf.fill(0xaa);
+        generate_pi(&mut buf, 8);
+        hdl.write_at(8 * BLOCK_LEN, &buf).await.unwrap();
+        drop(hdl);
+
+        pool.export().await.unwrap();
+        assert!(pi_lookup(&pi).is_none());
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+        let lvol =
+            pool.lvols().unwrap().find(|l| l.name() == LVOL_NAME).unwrap();
+        assert!(lvol.is_protected().await);
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+
+        let hdl = BdevHandle::open(&pi, true, false).unwrap();
+        let mut read = hdl.dma_malloc(BLOCKS * BLOCK_LEN).unwrap();
+        hdl.read_at(8 * BLOCK_LEN, &mut read).await.unwrap();
+        assert_eq!(read.as_slice(), buf.as_slice());
+        drop(hdl);
+
+        lvol.unshare().await.unwrap();
+        assert!(pi_lookup(&pi).is_none());
+
+        // unshared, the lvol is accessed locally through the PI bdev and
+        // never as itself
+        assert_eq!(lvol.share_uri(), None);
+        lvol.open_local().await.unwrap();
+        assert_eq!(lvol.share_uri().unwrap(), format!("bdev:///{}", pi));
+        assert!(lvol.resize(16 * 1024 * 1024, false).await.is_err());
+        lvol.unshare().await.unwrap();
+        assert!(pi_lookup(&pi).is_none());
+        assert!(lvol.resize(16 * 1024 * 1024, false).await.is_err());
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
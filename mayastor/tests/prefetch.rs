@@ -0,0 +1,139 @@
+use std::{
+    ffi::CString,
+    time::{Duration, Instant},
+};
+
+use common::MayastorTest;
+use futures::channel::oneshot;
+use mayastor::{
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    ffihelper::{cb_arg, done_cb},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use spdk_sys::{create_delay_disk, delete_delay_disk};
+
+pub mod common;
+
+static BASE_BDEV: &str = "malloc:///base?size_mb=64";
+static DELAY_BDEV: &str = "slow";
+
+/// latency of every IO to the bdev in usec
+const LATENCY_US: u64 = 1_000;
+const IO_SIZE: u64 = 4096;
+/// number of IOs of the sequential read benchmark
+const NUM_IOS: u64 = 256;
+/// number of bytes read ahead
+const WINDOW: u64 = 128 * 1024;
+
+/// read NUM_IOS blocks sequentially and return how long it took
+async fn read_sequential(hdl: &BdevHandle, val: u8) -> Duration {
+    let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+    let start = Instant::now();
+    for i in 0 .. NUM_IOS {
+        hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == val));
+    }
+    start.elapsed()
+}
+
+#[tokio::test]
+async fn prefetch() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let base = bdev_create(BASE_BDEV).await.unwrap();
+        let base = CString::new(base).unwrap();
+        let name = CString::new(DELAY_BDEV).unwrap();
+        let errno = unsafe {
+            create_delay_disk(
+                base.as_ptr(),
+                name.as_ptr(),
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+                LATENCY_US,
+            )
+        };
+        assert_eq!(errno, 0);
+
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(NUM_IOS * IO_SIZE).unwrap();
+        buf.fill(0xaa);
+        hdl.write_at(0, &buf).await.unwrap();
+    })
+    .await;
+
+    // sequential reads with and without prefetching
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, false, false).unwrap();
+        let off = read_sequential(&hdl, 0xaa).await;
+        assert_eq!(hdl.prefetch_stats(), None);
+
+        hdl.enable_prefetch(WINDOW);
+        let on = read_sequential(&hdl, 0xaa).await;
+        println!(
+            "sequential read of {} KiB: prefetch off {:?}, prefetch on {:?}",
+            NUM_IOS * IO_SIZE / 1024,
+            off,
+            on
+        );
+
+        let stats = hdl.prefetch_stats().unwrap();
+        assert!(stats.issued > 0);
+        assert!(stats.hits > NUM_IOS / 2);
+        assert!(on * 2 < off, "prefetching should reduce the latency");
+    })
+    .await;
+
+    // random access resets the detector and drops the data read ahead
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, false, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        hdl.enable_prefetch(WINDOW);
+        for i in 0 .. 3 {
+            hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        }
+        assert_eq!(hdl.prefetch_stats().unwrap().issued, 1);
+
+        for i in &[100, 7, 50, 3, 80] {
+            hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        }
+        let stats = hdl.prefetch_stats().unwrap();
+        assert_eq!(stats.issued, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.discarded, 1);
+    })
+    .await;
+
+    // a write to the region read ahead discards the data read ahead
+    ms.spawn(async {
+        let hdl = BdevHandle::open(DELAY_BDEV, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        hdl.enable_prefetch(WINDOW);
+        for i in 0 .. 2 {
+            hdl.read_at(i * IO_SIZE, &mut buf).await.unwrap();
+        }
+        assert_eq!(hdl.prefetch_stats().unwrap().issued, 1);
+
+        buf.fill(0x55);
+        hdl.write_at(2 * IO_SIZE, &buf).await.unwrap();
+        assert_eq!(hdl.prefetch_stats().unwrap().discarded, 1);
+
+        buf.fill(0);
+        hdl.read_at(2 * IO_SIZE, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0x55));
+        assert_eq!(hdl.prefetch_stats().unwrap().hits, 0);
+    })
+    .await;
+
+    ms.spawn(async {
+        let bdev = Bdev::lookup_by_name(DELAY_BDEV).unwrap();
+        let (s, r) = oneshot::channel::<i32>();
+        unsafe {
+            delete_delay_disk(bdev.as_ptr(), Some(done_cb), cb_arg(s));
+        }
+        assert_eq!(r.await.unwrap(), 0);
+        bdev_destroy(BASE_BDEV).await.unwrap();
+    })
+    .await;
+}
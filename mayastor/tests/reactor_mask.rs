@@ -0,0 +1,60 @@
+use mayastor::core::MayastorCliArgs;
+use structopt::StructOpt;
+
+/// the number of cores online on the host
+fn cores() -> u32 {
+    unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) as u32 }
+}
+
+#[test]
+fn reactor_mask() {
+    let args = MayastorCliArgs::from_iter_safe(&["mayastor"]).unwrap();
+    assert_eq!(args.core_list(), Ok(vec![0]));
+    assert_eq!(MayastorCliArgs::default().core_list(), Ok(vec![0]));
+
+    // masks which are empty, select no cores or cores not on the host are
+    // refused before anything is initialised
+    let too_many = format!("{:#x}", 1u128 << 127);
+    for mask in &["", "0x", "0x0", "0", "0xg", "mask", too_many.as_str()] {
+        assert!(
+            MayastorCliArgs::from_iter_safe(&["mayastor", "-m", mask])
+                .is_err(),
+            "{:?} is accepted",
+            mask
+        );
+    }
+    let args = MayastorCliArgs {
+        reactor_mask: too_many,
+        ..Default::default()
+    };
+    let error = args.core_list().unwrap_err();
+    assert!(error.contains("[127]"), "{}", error);
+
+    // the cores of the mask are those of its bits, with or without a prefix
+    let args =
+        MayastorCliArgs::from_iter_safe(&["mayastor", "-m", "1"]).unwrap();
+    assert_eq!(args.core_list(), Ok(vec![0]));
+    if cores() >= 3 {
+        for mask in &["0x5", "0X5", "5"] {
+            let args =
+                MayastorCliArgs::from_iter_safe(&["mayastor", "-m", mask])
+                    .unwrap();
+            assert_eq!(args.core_list(), Ok(vec![0, 2]));
+        }
+    }
+
+    // a core list supersedes the mask
+    let args = MayastorCliArgs {
+        reactor_mask: "0x1".into(),
+        core_list: Some("0,0-0".into()),
+        ..Default::default()
+    };
+    assert_eq!(args.core_list(), Ok(vec![0]));
+    for list in &["", "1-0", "0,", "a-b"] {
+        let args = MayastorCliArgs {
+            core_list: Some(list.to_string()),
+            ..Default::default()
+        };
+        assert!(args.core_list().is_err(), "{:?} is accepted", list);
+    }
+}
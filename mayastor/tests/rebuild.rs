@@ -143,7 +143,7 @@ async fn rebuild_not_required() {
     assert_eq!(get_num_rebuilds(nexus_hdl).await, 0);
 }
 
-/// Test removing the source of a rebuild.
+/// Test removing the source of a rebuild, which is the last healthy child.
 #[tokio::test]
 async fn rebuild_src_removal() {
     let test = start_infrastructure("rebuild_src_removal").await;
@@ -164,14 +164,19 @@ async fn rebuild_src_removal() {
     .unwrap());
     check_nexus_state(nexus_hdl, NexusState::NexusDegraded).await;
 
-    // Remove the rebuild source.
+    // Removing the rebuild source is refused, as it is the last healthy
+    // child.
     let src_child = &get_share_uri(&ms2);
-    remove_child(nexus_hdl, src_child).await;
-    // Give a little time for the rebuild to fail.
-    std::thread::sleep(Duration::from_secs(1));
-    assert_eq!(get_num_rebuilds(nexus_hdl).await, 0);
-    // Nexus must be faulted because it doesn't have any healthy children.
-    check_nexus_state(nexus_hdl, NexusState::NexusFaulted).await;
+    nexus_hdl
+        .mayastor
+        .remove_child_nexus(RemoveChildNexusRequest {
+            uuid: NEXUS_UUID.into(),
+            uri: src_child.into(),
+        })
+        .await
+        .expect_err("Removed the last healthy child");
+    assert_eq!(get_num_rebuilds(nexus_hdl).await, 1);
+    check_nexus_state(nexus_hdl, NexusState::NexusDegraded).await;
 }
 
 /// Test removing the destination of a rebuild.
@@ -0,0 +1,136 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_lookup, ChildState},
+    core::MayastorCliArgs,
+    rebuild::{ClientOperations, RebuildJob},
+    subsys::{Config, NexusBdev},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "CheckpointNexus";
+static NEXUS_UUID: &str = "00000000-0000-0000-0000-000000000425";
+static DISK_SRC: &str = "/tmp/rebuild_checkpoint-disk0.img";
+static DISK_DST: &str = "/tmp/rebuild_checkpoint-disk1.img";
+static CHILD_SRC: &str = "aio:///tmp/rebuild_checkpoint-disk0.img?blk_size=512";
+static CHILD_DST: &str = "aio:///tmp/rebuild_checkpoint-disk1.img?blk_size=512";
+
+static YAML_CONFIG_FILE: &str = "/tmp/rebuild_checkpoint.yaml";
+static CHILD_STATUS_FILE: &str = "/tmp/rebuild_checkpoint_status.yaml";
+
+const DISK_SIZE: usize = 64 << 20;
+const NEXUS_SIZE_MB: u64 = 32;
+const BLOCK_SIZE: u64 = 512;
+/// blocks rebuilt before the restart, half of the nexus
+const CHECKPOINT: u64 = (NEXUS_SIZE_MB << 20) / BLOCK_SIZE / 2;
+
+/// the checkpoint of the destination child saved in the child status file
+fn saved_checkpoint() -> Option<u64> {
+    let status = fs::read(CHILD_STATUS_FILE).unwrap();
+    let status: serde_yaml::Value = serde_yaml::from_slice(&status).unwrap();
+    status["rebuild_checkpoints"][CHILD_DST].as_u64()
+}
+
+/// the block of the disk is filled with the given byte
+fn block_is(disk: &mut File, blk: u64, val: u8) -> bool {
+    let mut buf = vec![0; BLOCK_SIZE as usize];
+    disk.seek(SeekFrom::Start(blk * BLOCK_SIZE)).unwrap();
+    disk.read_exact(&mut buf).unwrap();
+    buf.iter().all(|b| *b == val)
+}
+
+#[tokio::test]
+async fn rebuild_checkpoint() {
+    fs::write(DISK_SRC, vec![0xaa; DISK_SIZE]).unwrap();
+    fs::write(DISK_DST, vec![0x55; DISK_SIZE]).unwrap();
+
+    // the state left behind by an instance which was killed when half of the
+    // destination child had been rebuilt
+    let mut config = Config::default();
+    config.nexus_bdevs = Some(vec![NexusBdev {
+        name: NEXUS_NAME.to_string(),
+        uuid: NEXUS_UUID.to_string(),
+        size: format!("{}MiB", NEXUS_SIZE_MB),
+        children: vec![CHILD_SRC.to_string(), CHILD_DST.to_string()],
+    }]);
+    config.rebuild_opts.rate_limit_mbps = 8;
+    config.rebuild_opts.checkpoint_interval_ms = 100;
+    config.write(YAML_CONFIG_FILE).unwrap();
+    fs::write(
+        CHILD_STATUS_FILE,
+        format!(
+            "---\nstatus:\n  \"{}\":\n    Faulted: OutOfSync\n\
+             rebuild_checkpoints:\n  \"{}\": {}\n",
+            CHILD_DST, CHILD_DST, CHECKPOINT
+        ),
+    )
+    .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        mayastor_config: Some(YAML_CONFIG_FILE.to_string()),
+        child_status_config: Some(CHILD_STATUS_FILE.to_string()),
+        ..Default::default()
+    });
+
+    // the rebuild resumes from the checkpoint rather than starting over
+    ms.spawn(async {
+        let job = RebuildJob::lookup(CHILD_DST).unwrap();
+        let stats = job.as_client().stats();
+        assert!(stats.blocks_recovered >= CHECKPOINT);
+        assert!(stats.blocks_recovered < stats.blocks_total);
+    })
+    .await;
+
+    // and keeps saving its progress while it runs
+    tokio::time::delay_for(Duration::from_secs(1)).await;
+    let checkpoint = saved_checkpoint().unwrap();
+    assert!(checkpoint > CHECKPOINT, "checkpoint {}", checkpoint);
+
+    let mut ticker = tokio::time::interval(Duration::from_millis(100));
+    let offset = loop {
+        let rebuilt = ms
+            .spawn(async {
+                let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+                let child = nexus.children.iter().find(|c| c.name == CHILD_DST);
+                if child.unwrap().state() == ChildState::Open {
+                    Some(nexus.data_ent_offset)
+                } else {
+                    None
+                }
+            })
+            .await;
+        if let Some(offset) = rebuilt {
+            break offset;
+        }
+        ticker.tick().await;
+    };
+
+    // once rebuilt, the checkpoint is dropped
+    assert_eq!(saved_checkpoint(), None);
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    // the blocks below the checkpoint were not copied again
+    let mut disk = File::open(DISK_DST).unwrap();
+    let end = offset + 2 * CHECKPOINT;
+    assert!(block_is(&mut disk, offset, 0x55));
+    assert!(block_is(&mut disk, offset + CHECKPOINT - 1, 0x55));
+    assert!(block_is(&mut disk, offset + CHECKPOINT, 0xaa));
+    assert!(block_is(&mut disk, end - 1, 0xaa));
+
+    common::delete_file(&[
+        DISK_SRC.to_string(),
+        DISK_DST.to_string(),
+        YAML_CONFIG_FILE.to_string(),
+        CHILD_STATUS_FILE.to_string(),
+    ]);
+}
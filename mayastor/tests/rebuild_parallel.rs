@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState},
+    core::MayastorCliArgs,
+    rebuild::RebuildState,
+    subsys::Config,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ParallelRebuildNexus";
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=40";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=40";
+static CHILD_3: &str = "malloc:///malloc2?blk_size=512&size_mb=40";
+
+static YAML_CONFIG_FILE: &str = "/tmp/rebuild_parallel.yaml";
+
+/// total rate of all rebuilds in MiB/s
+const RATE_LIMIT_MBPS: u64 = 32;
+const NEXUS_SIZE_MB: u64 = 32;
+
+#[tokio::test]
+async fn rebuild_parallel() {
+    let mut config = Config::default();
+    config.rebuild_opts.rate_limit_mbps = RATE_LIMIT_MBPS;
+    config.write(YAML_CONFIG_FILE).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        mayastor_config: Some(YAML_CONFIG_FILE.to_string()),
+        ..Default::default()
+    });
+
+    // two children of the nexus are degraded and rebuilt at the same time
+    let (start, first, second) = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NEXUS_SIZE_MB << 20,
+                None,
+                &[CHILD_1.to_string()],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            nexus.add_child(CHILD_2, true).await.unwrap();
+            nexus.add_child(CHILD_3, true).await.unwrap();
+
+            let start = Instant::now();
+            let first = nexus.start_rebuild(CHILD_2).await.unwrap();
+            let second = nexus.start_rebuild(CHILD_3).await.unwrap();
+            (start, first, second)
+        })
+        .await;
+
+    // halfway through both rebuilds are making progress, at the same pace
+    let total = Duration::from_secs(2 * NEXUS_SIZE_MB / RATE_LIMIT_MBPS);
+    tokio::time::delay_for(total / 2).await;
+    ms.spawn(async {
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let first = nexus.get_rebuild_progress(CHILD_2).unwrap().progress;
+        let second = nexus.get_rebuild_progress(CHILD_3).unwrap().progress;
+        assert!(first > 0 && first < 100, "progress {}", first);
+        assert!(second > 0 && second < 100, "progress {}", second);
+        assert!(
+            (first as i64 - second as i64).abs() <= 20,
+            "rebuilds should share the rate limit equally: {}% vs {}%",
+            first,
+            second
+        );
+
+        // the children being rebuilt are not read from
+        let open = nexus
+            .children
+            .iter()
+            .filter(|c| c.state() == ChildState::Open);
+        assert_eq!(open.count(), 1);
+    })
+    .await;
+
+    let (first, second) =
+        ms.spawn(async { (first.await.unwrap(), second.await.unwrap()) })
+            .await;
+    let elapsed = start.elapsed();
+    assert_eq!(first, RebuildState::Completed);
+    assert_eq!(second, RebuildState::Completed);
+
+    // copying both children must take at least as long as copying their
+    // data at the rate limit
+    println!(
+        "rebuilt 2 x {} MiB at {} MiB/s in {:?}",
+        NEXUS_SIZE_MB, RATE_LIMIT_MBPS, elapsed
+    );
+    assert!(elapsed >= total * 9 / 10, "rebuilt too fast: {:?}", elapsed);
+
+    ms.spawn(async {
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[YAML_CONFIG_FILE.to_string()]);
+}
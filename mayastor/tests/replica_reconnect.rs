@@ -0,0 +1,139 @@
+use common::{
+    bdev_io,
+    compose::{Builder, ComposeTest},
+    MayastorTest,
+};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup, ChildState, NexusStatus},
+    core::MayastorCliArgs,
+    subsys::{Config, NvmeBdevOpts},
+};
+use rpc::mayastor::{BdevShareRequest, BdevUri, Null};
+use tokio::time::Duration;
+
+pub mod common;
+static NXNAME: &str = "reconnect_nexus";
+static LOCAL_CHILD: &str = "malloc:///local?size_mb=64";
+
+/// create the malloc bdev on the container and share it over nvmf
+async fn create_remote_bdev(test: &ComposeTest) {
+    let mut hdl = test.grpc_handle("ms1").await.unwrap();
+    hdl.bdev.list(Null {}).await.unwrap();
+    hdl.bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdl.bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+        })
+        .await
+        .unwrap();
+}
+
+/// state of the remote child of the nexus
+async fn remote_state(ms: &MayastorTest<'_>, uri: &str) -> ChildState {
+    let uri = uri.to_string();
+    ms.spawn(async move {
+        nexus_lookup(NXNAME)
+            .unwrap()
+            .children
+            .iter()
+            .find(|c| c.name == uri)
+            .unwrap()
+            .state()
+    })
+    .await
+}
+
+#[tokio::test]
+async fn replica_reconnect() {
+    // Use shorter timeouts than the defaults to reduce test runtime
+    Config::get_or_init(|| Config {
+        nvme_bdev_opts: NvmeBdevOpts {
+            timeout_us: 2_000_000,
+            keep_alive_timeout_ms: 2_000,
+            retry_count: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+    let test = Builder::new()
+        .name("replica_reconnect")
+        .network("10.1.0.0/16")
+        .add_container("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    create_remote_bdev(&test).await;
+    let ip = test.grpc_handle("ms1").await.unwrap().endpoint.ip();
+    let remote = format!(
+        "nvmf://{}:8420/nqn.2019-05.io.openebs:disk0?reconnect_delay_ms=500&reconnect_max_delay_ms=2000&reconnect_attempts=30",
+        ip
+    );
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let children = vec![LOCAL_CHILD.to_string(), remote.clone()];
+    ms.spawn(async move {
+        nexus_create(NXNAME, 32 * 1024 * 1024, None, &children)
+            .await
+            .unwrap();
+        assert_eq!(
+            nexus_lookup(NXNAME).unwrap().status(),
+            NexusStatus::Online
+        );
+    })
+    .await;
+
+    // the remote child fails on the first I/O after the target went away,
+    // the nexus carries on with the local child
+    test.stop("ms1").await.unwrap();
+    ms.spawn(async {
+        let _ = bdev_io::write_some(NXNAME, 0, 0xaa).await;
+    })
+    .await;
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    for _ in 0 .. 5 {
+        ticker.tick().await;
+    }
+    assert_ne!(remote_state(&ms, &remote).await, ChildState::Open);
+    ms.spawn(async {
+        let nexus = nexus_lookup(NXNAME).unwrap();
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+        bdev_io::write_some(NXNAME, 0, 0x55).await.unwrap();
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+    })
+    .await;
+
+    // bring the target back, the child is reconnected and rebuilt
+    test.start("ms1").await.unwrap();
+    create_remote_bdev(&test).await;
+
+    let mut online = false;
+    for _ in 0 .. 60 {
+        ticker.tick().await;
+        let status = ms
+            .spawn(async { nexus_lookup(NXNAME).unwrap().status() })
+            .await;
+        if status == NexusStatus::Online {
+            online = true;
+            break;
+        }
+    }
+    assert!(online, "the remote child should have been reconnected");
+    assert_eq!(remote_state(&ms, &remote).await, ChildState::Open);
+
+    ms.spawn(async {
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+        nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+}
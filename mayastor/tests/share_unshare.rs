@@ -0,0 +1,80 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{Bdev, MayastorCliArgs, Protocol, Share},
+    lvs::{Lvs, PropName, PropValue},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///unshared?size_mb=64";
+static MALLOC_NAME: &str = "unshared";
+static DISKNAME: &str = "/tmp/share_unshare.img";
+static POOL_DISK: &str = "aio:///tmp/share_unshare.img";
+static POOL_NAME: &str = "unshare-pool";
+
+#[tokio::test]
+async fn share_unshare() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // unsharing what is not shared is not an error
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let bdev = Bdev::lookup_by_name(MALLOC_NAME).unwrap();
+        assert_eq!(bdev.shared(), Some(Protocol::Off));
+        bdev.unshare().await.unwrap();
+
+        bdev.share_nvmf().await.unwrap();
+        assert_eq!(bdev.shared(), Some(Protocol::Nvmf));
+        bdev.unshare().await.unwrap();
+        bdev.unshare().await.unwrap();
+        assert_eq!(bdev.shared(), Some(Protocol::Off));
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool.create_lvol("vol", 4 * 1024, true).await.unwrap();
+        lvol.unshare().await.unwrap();
+        let snapshot = lvol.create_snapshot("snap").await.unwrap();
+        snapshot.unshare().await.unwrap();
+
+        // the share is lost while the lvol is still marked shared on disk
+        lvol.share_nvmf().await.unwrap();
+        lvol.as_bdev().unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+        assert_eq!(
+            lvol.get(PropName::Shared).await.unwrap(),
+            PropValue::Shared(true)
+        );
+        pool.export().await.unwrap();
+    })
+    .await;
+
+    // and is found again once the pool is imported
+    ms.spawn(async {
+        bdev_create(POOL_DISK).await.unwrap();
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+        let lvol = pool.lvols().unwrap().find(|l| l.name() == "vol").unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Nvmf));
+
+        lvol.unshare().await.unwrap();
+        lvol.unshare().await.unwrap();
+        assert_eq!(lvol.shared(), Some(Protocol::Off));
+        assert_eq!(
+            lvol.get(PropName::Shared).await.unwrap(),
+            PropValue::Shared(false)
+        );
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,126 @@
+use std::convert::TryFrom;
+
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{Bdev, MayastorCliArgs},
+    lvs::{Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME1: &str = "/tmp/snapshot_clone.img";
+static POOL_NAME: &str = "snap_pool";
+
+static UUID1: &str = "00000000-76b6-4fcf-864d-1027d4038756";
+static UUID2: &str = "11111111-76b6-4fcf-864d-1027d4038756";
+
+static NXNAME: &str = "snapshot_clone_test";
+static NXNAME_CLONE: &str = "snapshot_clone_test-clone";
+
+const SIZE: u64 = 32 * 1024 * 1024;
+
+fn lvol(name: &str) -> Lvol {
+    Lvol::try_from(Bdev::lookup_by_name(name).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn snapshot_clone() {
+    common::delete_file(&[DISKNAME1.into()]);
+    common::truncate_file(DISKNAME1, 128 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // write to the volume, snapshot it and overwrite the data afterwards
+    let snapshot = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![format!("aio://{}", DISKNAME1)],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            pool.create_lvol(UUID1, SIZE, false).await.unwrap();
+            nexus_create(
+                NXNAME,
+                SIZE,
+                None,
+                &[format!("loopback:///{}", UUID1)],
+            )
+            .await
+            .unwrap();
+
+            bdev_io::write_some(NXNAME, 0, 0xaa).await.unwrap();
+            let reply = nexus_lookup(NXNAME)
+                .unwrap()
+                .create_snapshot()
+                .await
+                .unwrap();
+            bdev_io::write_some(NXNAME, 0, 0x55).await.unwrap();
+            bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+            reply.name
+        })
+        .await;
+
+    // the replica snapshot is named after the replica, not the nexus
+    let (nexus, time) = Lvol::parse_snapshot_name(&snapshot).unwrap();
+    assert_eq!(nexus, NXNAME);
+    let snapshot = Lvol::format_snapshot_name(UUID1, time);
+
+    // restore the snapshot to a new volume which must see the old data
+    let name = snapshot.clone();
+    ms.spawn(async move {
+        let snapshot = lvol(&name);
+        assert!(snapshot.is_snapshot());
+        assert!(snapshot.clones().is_empty());
+
+        snapshot.create_clone(UUID2).await.unwrap();
+        nexus_create(
+            NXNAME_CLONE,
+            SIZE,
+            None,
+            &[format!("loopback:///{}", UUID2)],
+        )
+        .await
+        .unwrap();
+
+        bdev_io::read_some(NXNAME_CLONE, 0, 0xaa).await.unwrap();
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+
+        // writes to the clone do not show through to the volume
+        bdev_io::write_some(NXNAME_CLONE, 0, 0x11).await.unwrap();
+        bdev_io::read_some(NXNAME_CLONE, 0, 0x11).await.unwrap();
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+
+        let clones = snapshot.clones();
+        assert_eq!(clones.len(), 1);
+        assert_eq!(clones[0].name(), UUID2);
+    })
+    .await;
+
+    // the snapshot can not be destroyed while the clone exists
+    let name = snapshot.clone();
+    ms.spawn(async move {
+        assert!(lvol(&name).destroy_snapshot().await.is_err());
+        assert!(Bdev::lookup_by_name(&name).is_some());
+
+        nexus_lookup(NXNAME_CLONE).unwrap().destroy().await.unwrap();
+        lvol(UUID2).destroy().await.unwrap();
+
+        lvol(&name).destroy_snapshot().await.unwrap();
+        assert!(Bdev::lookup_by_name(&name).is_none());
+
+        // the volume the snapshot was taken of is intact
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+    })
+    .await;
+
+    ms.spawn(async {
+        nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME1.into()]);
+}
@@ -0,0 +1,108 @@
+use common::MayastorTest;
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{Bdev, MayastorCliArgs, Share},
+    grpc::state_grpc,
+    lvs::Lvs,
+};
+use rpc::mayastor::CreatePoolRequest;
+use serde_json::{json, Value};
+
+pub mod common;
+
+static POOL_NAME: &str = "dump-pool";
+static POOL_DISK: &str = "malloc:///dump-disk?size_mb=64";
+static NEXUS_NAME: &str = "dump-nexus";
+static NEXUS_CHILD: &str = "bdev:///nexus-vol";
+
+const MB: u64 = 1024 * 1024;
+
+/// find the entry with the given name in a list of the dump
+fn entry(list: &Value, name: &str) -> Value {
+    list.as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["name"] == name)
+        .unwrap_or_else(|| panic!("{} not in {}", name, list))
+        .clone()
+}
+
+#[tokio::test]
+async fn state_dump() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let shared =
+            pool.create_lvol("shared-vol", 8 * MB, false).await.unwrap();
+        let replica =
+            pool.create_lvol("nexus-vol", 8 * MB, false).await.unwrap();
+        shared.share_nvmf().await.unwrap();
+        nexus_create(NEXUS_NAME, 8 * MB, None, &[NEXUS_CHILD.into()])
+            .await
+            .unwrap();
+
+        let state = state_grpc::dump();
+
+        // every bdev is listed, whatever its type
+        for name in &["dump-disk", "shared-vol", "nexus-vol", NEXUS_NAME] {
+            let bdev = Bdev::lookup_by_name(name).unwrap();
+            assert_eq!(
+                entry(&state["bdevs"], name),
+                json!({
+                    "name": name,
+                    "product_name": bdev.product_name(),
+                    "size": bdev.size_in_bytes(),
+                    "block_len": bdev.block_len(),
+                    "claimed_by": bdev.claimed_by(),
+                })
+            );
+        }
+        assert_eq!(entry(&state["bdevs"], "dump-disk")["size"], 64 * MB);
+
+        // pools are listed with their lvols and share state
+        let value = entry(&state["pools"], POOL_NAME);
+        assert_eq!(value, serde_json::to_value(&pool).unwrap());
+        assert_eq!(value["capacity"], pool.capacity());
+        assert_eq!(value["used"], pool.used());
+        assert_eq!(entry(&value["lvols"], "shared-vol")["shared"], "Nvmf");
+        assert_eq!(
+            entry(&value["lvols"], "shared-vol")["share_uri"],
+            shared.share_uri().unwrap()
+        );
+        assert_eq!(entry(&value["lvols"], "nexus-vol")["shared"], "Off");
+
+        // nexuses are listed with the health of their children
+        assert_eq!(
+            entry(&state["nexuses"], NEXUS_NAME),
+            json!({
+                "name": NEXUS_NAME,
+                "size": 8 * MB,
+                "status": "Online",
+                "shared": "Off",
+                "share_uri": null,
+                "children": [{
+                    "name": NEXUS_CHILD,
+                    "state": "Open",
+                    "rebuild_progress": null,
+                }],
+            })
+        );
+
+        // taking the dump does not change the state
+        assert_eq!(state_grpc::dump(), state);
+
+        nexus_lookup(NEXUS_NAME).unwrap().destroy().await.unwrap();
+        shared.unshare().await.unwrap();
+        shared.destroy().await.unwrap();
+        replica.destroy().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+use common::MayastorTest;
+use mayastor::{
+    bdev::{tier_lookup, TierStats},
+    core::{Bdev, BdevHandle, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static FAST_BDEV: &str = "malloc:///tier-fast?size_mb=2";
+static SLOW_BDEV: &str = "malloc:///tier-slow?size_mb=8";
+static TIER_BDEV: &str = "tier:///tier-slow?fast=tier-fast&promote=2";
+static TIER_NAME: &str = "tier-slow-tier";
+
+/// length of the extents, the default
+const EXTENT_LEN: u64 = 64 * 1024;
+/// number of extents of the slow bdev
+const EXTENTS: u64 = (8 << 20) / EXTENT_LEN;
+/// the extent which is read repeatedly
+const HOT: u64 = 5;
+/// how long to wait for the migration worker
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn stats() -> TierStats {
+    tier_lookup(TIER_NAME).unwrap().stats()
+}
+
+fn is_promoted(extent: u64) -> bool {
+    let block_len = Bdev::lookup_by_name(TIER_NAME).unwrap().block_len();
+    tier_lookup(TIER_NAME)
+        .unwrap()
+        .is_promoted(extent * EXTENT_LEN / u64::from(block_len))
+}
+
+/// read the first block of the extent, which holds the given value
+async fn read_extent(hdl: &BdevHandle, extent: u64, val: u8) {
+    let mut buf = hdl.dma_malloc(4096).unwrap();
+    hdl.read_at(extent * EXTENT_LEN, &mut buf).await.unwrap();
+    assert!(
+        buf.as_slice().iter().all(|b| *b == val),
+        "extent {} does not hold {}",
+        extent,
+        val
+    );
+}
+
+/// wait until the stats of the tier bdev satisfy the condition
+async fn wait_for(ms: &MayastorTest<'_>, f: fn(&TierStats) -> bool) {
+    let start = Instant::now();
+    loop {
+        let stats = ms.spawn(async { stats() }).await;
+        if f(&stats) {
+            return;
+        }
+        assert!(start.elapsed() < TIMEOUT, "timed out at {:?}", stats);
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[tokio::test]
+async fn tier_bdev() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // every extent starts out on the slow bdev
+    ms.spawn(async {
+        bdev_create(FAST_BDEV).await.unwrap();
+        bdev_create(SLOW_BDEV).await.unwrap();
+        assert_eq!(bdev_create(TIER_BDEV).await.unwrap(), TIER_NAME);
+
+        let bdev = Bdev::lookup_by_name(TIER_NAME).unwrap();
+        assert_eq!(bdev.size_in_bytes(), EXTENTS * EXTENT_LEN);
+        let stats = stats();
+        assert!(stats.slots > 0 && stats.slots < EXTENTS);
+        assert_eq!(stats.fast_extents, 0);
+
+        let hdl = BdevHandle::open(TIER_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(EXTENT_LEN).unwrap();
+        for extent in 0 .. EXTENTS {
+            buf.fill(extent as u8);
+            hdl.write_at(extent * EXTENT_LEN, &buf).await.unwrap();
+        }
+
+        // a single read does not promote an extent
+        read_extent(&hdl, HOT, HOT as u8).await;
+        assert!(!is_promoted(HOT));
+        read_extent(&hdl, HOT, HOT as u8).await;
+    })
+    .await;
+
+    // the repeatedly read extent migrates to the fast bdev, from where it
+    // is read and written from then on
+    wait_for(&ms, |s| s.promotions == 1).await;
+    ms.spawn(async {
+        assert!(is_promoted(HOT));
+        let hdl = BdevHandle::open(TIER_NAME, true, false).unwrap();
+        let fast_reads = stats().fast_reads;
+        read_extent(&hdl, HOT, HOT as u8).await;
+        assert_eq!(stats().fast_reads, fast_reads + 1);
+
+        let mut buf = hdl.dma_malloc(EXTENT_LEN).unwrap();
+        buf.fill(0xaa);
+        hdl.write_at(HOT * EXTENT_LEN, &buf).await.unwrap();
+        read_extent(&hdl, HOT, 0xaa).await;
+    })
+    .await;
+
+    // promoting more extents than the fast bdev holds demotes the least
+    // recently used ones, the written one is copied back
+    ms.spawn(async {
+        let hdl = BdevHandle::open(TIER_NAME, true, false).unwrap();
+        for extent in (0 .. EXTENTS).filter(|e| *e != HOT) {
+            read_extent(&hdl, extent, extent as u8).await;
+            read_extent(&hdl, extent, extent as u8).await;
+        }
+    })
+    .await;
+    wait_for(&ms, |s| {
+        s.promotions == EXTENTS && s.fast_extents * 100 <= s.slots * 90
+    })
+    .await;
+    ms.spawn(async {
+        assert!(stats().demotions > 0);
+        assert!(!is_promoted(HOT));
+        assert!(is_promoted(EXTENTS - 1));
+
+        let hdl = BdevHandle::open(TIER_NAME, true, false).unwrap();
+        for extent in 0 .. EXTENTS {
+            let val = if extent == HOT { 0xaa } else { extent as u8 };
+            read_extent(&hdl, extent, val).await;
+        }
+    })
+    .await;
+
+    // the extents on the fast bdev are found again when the tier bdev is
+    // created anew
+    ms.spawn(async {
+        let before = stats();
+        bdev_destroy(TIER_BDEV).await.unwrap();
+        assert!(tier_lookup(TIER_NAME).is_none());
+
+        bdev_create(TIER_BDEV).await.unwrap();
+        assert_eq!(stats().fast_extents, before.fast_extents);
+        assert!(is_promoted(EXTENTS - 1));
+        let hdl = BdevHandle::open(TIER_NAME, true, false).unwrap();
+        for extent in 0 .. EXTENTS {
+            let val = if extent == HOT { 0xaa } else { extent as u8 };
+            read_extent(&hdl, extent, val).await;
+        }
+        hdl.close();
+
+        bdev_destroy(TIER_BDEV).await.unwrap();
+        bdev_destroy(FAST_BDEV).await.unwrap();
+        bdev_destroy(SLOW_BDEV).await.unwrap();
+    })
+    .await;
+}
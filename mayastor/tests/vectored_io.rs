@@ -0,0 +1,77 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///vectored?size_mb=64";
+static MALLOC_NAME: &str = "vectored";
+
+const KB: u64 = 1024;
+
+#[tokio::test]
+async fn vectored_io() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+
+        // buffers of different sizes are written as one IO, back to back
+        let bufs = (1 .. 4)
+            .map(|i| {
+                let mut buf = hdl.dma_malloc(i * 4 * KB).unwrap();
+                buf.fill(i as u8);
+                buf
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hdl.writev_at(64 * KB, &bufs).await.unwrap() as u64,
+            24 * KB
+        );
+
+        let mut buf = hdl.dma_malloc(24 * KB).unwrap();
+        hdl.read_at(64 * KB, &mut buf).await.unwrap();
+        let data = buf.as_slice();
+        assert!(data[.. 4096].iter().all(|b| *b == 1));
+        assert!(data[4096 .. 12288].iter().all(|b| *b == 2));
+        assert!(data[12288 ..].iter().all(|b| *b == 3));
+
+        // and read back split differently
+        let mut bufs = vec![
+            hdl.dma_malloc(12 * KB).unwrap(),
+            hdl.dma_malloc(12 * KB).unwrap(),
+        ];
+        assert_eq!(hdl.readv_at(64 * KB, &mut bufs).await.unwrap(), 24 * KB);
+        assert_eq!([bufs[0].as_slice(), bufs[1].as_slice()].concat(), data);
+
+        // IO which is not block aligned is refused
+        let odd =
+            vec![hdl.dma_malloc(4 * KB).unwrap(), hdl.dma_malloc(100).unwrap()];
+        assert!(matches!(
+            hdl.writev_at(0, &odd).await,
+            Err(CoreError::UnalignedIo {
+                ..
+            })
+        ));
+        let mut bufs = vec![hdl.dma_malloc(4 * KB).unwrap()];
+        assert!(matches!(
+            hdl.readv_at(100, &mut bufs).await,
+            Err(CoreError::UnalignedIo {
+                ..
+            })
+        ));
+        assert!(matches!(
+            hdl.readv_at(0, &mut []).await,
+            Err(CoreError::UnalignedIo {
+                ..
+            })
+        ));
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
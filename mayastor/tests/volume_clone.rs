@@ -0,0 +1,122 @@
+use std::convert::TryFrom;
+
+use common::{bdev_io, MayastorTest};
+use mayastor::{
+    bdev::{nexus_create, nexus_lookup},
+    core::{Bdev, MayastorCliArgs},
+    lvs::{Lvol, Lvs},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static DISKNAME1: &str = "/tmp/volume_clone.img";
+static POOL_NAME: &str = "clone_pool";
+
+static UUID1: &str = "00000000-76b6-4fcf-864d-1027d4038756";
+static UUID2: &str = "22222222-76b6-4fcf-864d-1027d4038756";
+
+static NXNAME: &str = "volume_clone_test";
+static NXNAME_CLONE: &str = "volume_clone_test-clone";
+
+const SIZE: u64 = 16 * 1024 * 1024;
+const CLONE_SIZE: u64 = 2 * SIZE;
+
+fn lvol(name: &str) -> Lvol {
+    Lvol::try_from(Bdev::lookup_by_name(name).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn volume_clone() {
+    common::delete_file(&[DISKNAME1.into()]);
+    common::truncate_file(DISKNAME1, 128 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // a clone of a volume is restored from a snapshot taken of the volume
+    let snapshot = ms
+        .spawn(async {
+            let pool = Lvs::create_or_import(CreatePoolRequest {
+                name: POOL_NAME.into(),
+                disks: vec![format!("aio://{}", DISKNAME1)],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            pool.create_lvol(UUID1, SIZE, false).await.unwrap();
+            nexus_create(
+                NXNAME,
+                SIZE,
+                None,
+                &[format!("loopback:///{}", UUID1)],
+            )
+            .await
+            .unwrap();
+
+            bdev_io::write_some(NXNAME, 0, 0xaa).await.unwrap();
+            let reply = nexus_lookup(NXNAME)
+                .unwrap()
+                .create_snapshot()
+                .await
+                .unwrap();
+            reply.name
+        })
+        .await;
+
+    let (_, time) = Lvol::parse_snapshot_name(&snapshot).unwrap();
+    let snapshot = Lvol::format_snapshot_name(UUID1, time);
+
+    // the clone is bigger than the source, so it is grown after cloning
+    let name = snapshot.clone();
+    ms.spawn(async move {
+        lvol(&name).create_clone(UUID2).await.unwrap();
+        let clone = lvol(UUID2);
+        clone.resize(CLONE_SIZE, false).await.unwrap();
+        assert_eq!(clone.size(), CLONE_SIZE);
+
+        nexus_create(
+            NXNAME_CLONE,
+            CLONE_SIZE,
+            None,
+            &[format!("loopback:///{}", UUID2)],
+        )
+        .await
+        .unwrap();
+
+        // the clone has the data of the source at the time of cloning
+        bdev_io::read_some(NXNAME_CLONE, 0, 0xaa).await.unwrap();
+        let nexus = nexus_lookup(NXNAME_CLONE).unwrap();
+        assert!(nexus.size() > SIZE);
+    })
+    .await;
+
+    // the source remains usable and does not see writes to the clone
+    ms.spawn(async {
+        bdev_io::write_some(NXNAME_CLONE, 0, 0x11).await.unwrap();
+        bdev_io::read_some(NXNAME, 0, 0xaa).await.unwrap();
+
+        bdev_io::write_some(NXNAME, 0, 0x55).await.unwrap();
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+        bdev_io::read_some(NXNAME_CLONE, 0, 0x11).await.unwrap();
+        assert_eq!(lvol(UUID1).size(), SIZE);
+    })
+    .await;
+
+    // destroying the clone allows the snapshot to be destroyed too
+    let name = snapshot.clone();
+    ms.spawn(async move {
+        nexus_lookup(NXNAME_CLONE).unwrap().destroy().await.unwrap();
+        lvol(UUID2).destroy().await.unwrap();
+        lvol(&name).destroy_snapshot().await.unwrap();
+
+        bdev_io::read_some(NXNAME, 0, 0x55).await.unwrap();
+    })
+    .await;
+
+    ms.spawn(async {
+        nexus_lookup(NXNAME).unwrap().destroy().await.unwrap();
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME1.into()]);
+}
@@ -0,0 +1,124 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs},
+    lvs::{Lvs, PropValue},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+use rpc::mayastor::CreatePoolRequest;
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///verified?size_mb=64";
+static MALLOC_NAME: &str = "verified";
+static NULL_BDEV: &str = "null:///dropped?size_mb=64";
+static NULL_NAME: &str = "dropped";
+
+static DISKNAME: &str = "/tmp/verify-disk.img";
+static POOL_DISK: &str = "aio:///tmp/verify-disk.img";
+static POOL_NAME: &str = "verify-pool";
+static LVOL_NAME: &str = "verify-lvol";
+
+const IO_SIZE: u64 = 64 * 1024;
+
+#[tokio::test]
+async fn write_verify() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    // writes which land as written pass the verification, which is enabled
+    // per handle
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        let other = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        assert!(!hdl.write_verify());
+        hdl.enable_write_verify();
+        assert!(hdl.write_verify());
+        assert!(!other.write_verify());
+
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        for i in 0 .. 16 {
+            buf.fill(i as u8);
+            hdl.write_at(i * IO_SIZE, &buf).await.unwrap();
+        }
+        drop(other);
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+
+    // the null bdev completes writes successfully but discards their data,
+    // like a device on which writes land corrupted
+    ms.spawn(async {
+        bdev_create(NULL_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(NULL_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0xa5);
+        hdl.write_at(IO_SIZE, &buf).await.unwrap();
+
+        hdl.enable_write_verify();
+        assert!(matches!(
+            hdl.write_at(IO_SIZE, &buf).await,
+            Err(CoreError::WriteVerifyFailed {
+                offset,
+                len,
+            }) if offset == IO_SIZE && len == IO_SIZE
+        ));
+        assert!(matches!(
+            hdl.write_at_timeout(
+                IO_SIZE,
+                &buf,
+                std::time::Duration::from_secs(5)
+            )
+            .await,
+            Err(CoreError::WriteVerifyFailed { .. })
+        ));
+
+        hdl.disable_write_verify();
+        hdl.write_at(IO_SIZE, &buf).await.unwrap();
+        drop(hdl);
+        bdev_destroy(NULL_BDEV).await.unwrap();
+    })
+    .await;
+
+    // verification is opted into per lvol, and kept with the lvol
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(CreatePoolRequest {
+            name: POOL_NAME.into(),
+            disks: vec![POOL_DISK.into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let lvol = pool
+            .create_lvol(LVOL_NAME, 8 * 1024 * 1024, false)
+            .await
+            .unwrap();
+        assert!(!lvol.is_write_verified().await);
+        assert!(!lvol.open_handle(true).await.unwrap().write_verify());
+
+        lvol.set(PropValue::VerifyWrites(true)).await.unwrap();
+        let hdl = lvol.open_handle(true).await.unwrap();
+        assert!(hdl.write_verify());
+        let mut buf = hdl.dma_malloc(IO_SIZE).unwrap();
+        buf.fill(0x3c);
+        hdl.write_at(0, &buf).await.unwrap();
+        drop(hdl);
+
+        pool.export().await.unwrap();
+        let pool = Lvs::import(POOL_NAME, POOL_DISK).await.unwrap();
+        let lvol =
+            pool.lvols().unwrap().find(|l| l.name() == LVOL_NAME).unwrap();
+        assert!(lvol.is_write_verified().await);
+        let hdl = lvol.open_handle(true).await.unwrap();
+        assert!(hdl.write_verify());
+        hdl.write_at(IO_SIZE, &buf).await.unwrap();
+        drop(hdl);
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
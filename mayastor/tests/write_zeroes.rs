@@ -0,0 +1,56 @@
+use common::MayastorTest;
+use mayastor::{
+    core::{BdevHandle, CoreError, MayastorCliArgs},
+    nexus_uri::{bdev_create, bdev_destroy},
+};
+
+pub mod common;
+
+static MALLOC_BDEV: &str = "malloc:///zeroed?size_mb=64";
+static MALLOC_NAME: &str = "zeroed";
+
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn write_zeroes() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(MALLOC_BDEV).await.unwrap();
+        let hdl = BdevHandle::open(MALLOC_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4 * MB).unwrap();
+        buf.fill(0xff);
+        hdl.write_at(0, &buf).await.unwrap();
+
+        // only the range zeroed is, the data around it is kept
+        hdl.write_zeroes_at(MB, 2 * MB).await.unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        let data = buf.as_slice();
+        let mb = MB as usize;
+        assert!(data[.. mb].iter().all(|b| *b == 0xff));
+        assert!(data[mb .. 3 * mb].iter().all(|b| *b == 0));
+        assert!(data[3 * mb ..].iter().all(|b| *b == 0xff));
+
+        // partial blocks are refused
+        for (offset, len) in &[(100, MB), (0, MB + 100), (0, 0)] {
+            assert!(matches!(
+                hdl.write_zeroes_at(*offset, *len).await,
+                Err(CoreError::UnalignedIo {
+                    ..
+                })
+            ));
+        }
+
+        // as is zeroing beyond the end of the bdev
+        assert!(matches!(
+            hdl.write_zeroes_at(64 * MB, MB).await,
+            Err(CoreError::WriteZeroesDispatch {
+                ..
+            })
+        ));
+
+        drop(hdl);
+        bdev_destroy(MALLOC_BDEV).await.unwrap();
+    })
+    .await;
+}
@@ -26,7 +26,13 @@ pub use send::*;
 use serde::{Deserialize, Serialize};
 use smol::io;
 use snafu::{ResultExt, Snafu};
-use std::{fmt::Debug, marker::PhantomData, str::FromStr, time::Duration};
+use std::{
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    str::FromStr,
+    time::Duration,
+};
 
 /// Result wrapper for send/receive
 pub type BusResult<T> = Result<T, Error>;
@@ -108,7 +114,44 @@ pub enum Error {
     #[snafu(display("Reply message came back with an error"))]
     ReplyWithError { source: ReplyError },
     #[snafu(display("Service error whilst handling request: {}", message))]
-    ServiceError { message: String },
+    ServiceError {
+        kind: ReplyErrorKind,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Kind of the error, as it should be returned over the bus
+    pub fn kind(&self) -> ReplyErrorKind {
+        match self {
+            Error::ReplyWithError {
+                source,
+            } => source.kind,
+            Error::ServiceError {
+                kind,
+                ..
+            } => *kind,
+            Error::RequestTimeout {
+                ..
+            }
+            | Error::Publish {
+                ..
+            }
+            | Error::Flush {
+                ..
+            }
+            | Error::Subscribe {
+                ..
+            } => ReplyErrorKind::Unavailable,
+            Error::WrongMessageId {
+                ..
+            }
+            | Error::DeserializeSend {
+                ..
+            } => ReplyErrorKind::InvalidArgument,
+            _ => ReplyErrorKind::Internal,
+        }
+    }
 }
 
 /// Report error chain
@@ -228,6 +271,34 @@ impl ToString for MessageId {
 /// Sender identification (eg which mayastor instance sent the message)
 pub type SenderId = String;
 
+/// Correlation identification which follows a request through all the
+/// services it traverses, allowing their traces to be stitched together
+pub type CorrelationId = String;
+
+tokio::task_local! {
+    static CORRELATION_ID: CorrelationId;
+}
+
+/// Runs the future `f` with the given correlation id, which is then carried
+/// by every message sent from within it
+pub async fn with_correlation_id<F: Future>(
+    correlation_id: CorrelationId,
+    f: F,
+) -> F::Output {
+    CORRELATION_ID.scope(correlation_id, f).await
+}
+
+/// Get the correlation id of the current task, if it's running within
+/// `with_correlation_id`
+pub fn correlation_id() -> Option<CorrelationId> {
+    CORRELATION_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Generate a new unique correlation id
+pub fn new_correlation_id() -> CorrelationId {
+    uuid::Uuid::new_v4().to_string()
+}
+
 /// This trait defines all Bus Messages which must:
 /// 1 - be uniquely identifiable via MessageId
 /// 2 - have a default Channel on which they are sent/received
@@ -264,6 +335,16 @@ pub trait Message {
         channel: C,
         options: TimeoutOptions,
     ) -> BusResult<Self::Reply>;
+    /// publish a message on the given channel with a request for a
+    /// `Self::Reply` reply, failing with `Error::RequestTimeout` if none
+    /// arrives within the timeout, without retrying. Each request waits on
+    /// its own reply subject, so a reply arriving after the timeout is
+    /// dropped rather than taken for the reply to a later request.
+    async fn request_on_timeout<C: Into<Channel> + Send>(
+        &self,
+        channel: C,
+        timeout: Duration,
+    ) -> BusResult<Self::Reply>;
 }
 
 /// The preamble is used to peek into messages so allowing for them to be routed
@@ -271,30 +352,147 @@ pub trait Message {
 #[derive(Serialize, Deserialize, Debug)]
 struct Preamble {
     pub(crate) id: MessageId,
+    #[serde(default = "new_correlation_id")]
+    pub(crate) correlation_id: CorrelationId,
 }
 
 /// Unsolicited (send) messages carry the message identifier, the sender
-/// identifier and finally the message payload itself
+/// identifier, the correlation identifier and finally the message payload
+/// itself. Messages from senders which predate the correlation identifier
+/// are given a new one when received
 #[derive(Serialize, Deserialize)]
 struct SendPayload<T> {
     pub(crate) id: MessageId,
     pub(crate) sender: SenderId,
+    #[serde(default = "new_correlation_id")]
+    pub(crate) correlation_id: CorrelationId,
     pub(crate) data: T,
 }
 
+/// Kind of the error returned over the bus, allowing the requester to tell
+/// the failures apart
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum_macros::AsRefStr,
+)]
+pub enum ReplyErrorKind {
+    /// the resource does not exist
+    NotFound,
+    /// the resource already exists
+    AlreadyExists,
+    /// the request is not valid
+    InvalidArgument,
+    /// the request failed unexpectedly
+    Internal,
+    /// the request cannot be serviced at the moment, eg: the service or
+    /// the node is not reachable
+    Unavailable,
+}
+
 /// Error type which is returned over the bus
 /// for any other operation
-#[derive(Serialize, Deserialize, Debug, Snafu, strum_macros::AsRefStr)]
-#[allow(missing_docs)]
-pub enum ReplyError {
-    #[snafu(display("Generic Failure, message={}", message))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ReplyErrorRepr")]
+pub struct ReplyError {
+    /// kind of the error
+    pub kind: ReplyErrorKind,
+    /// details of the error, if any
+    pub message: Option<String>,
+}
+
+impl ReplyError {
+    /// New error of the given `kind` with the `message`
+    pub fn new(kind: ReplyErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: Some(message.into()),
+        }
+    }
+    /// New `ReplyErrorKind::NotFound` error with the `message`
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ReplyErrorKind::NotFound, message)
+    }
+    /// New `ReplyErrorKind::AlreadyExists` error with the `message`
+    pub fn already_exists(message: impl Into<String>) -> Self {
+        Self::new(ReplyErrorKind::AlreadyExists, message)
+    }
+    /// New `ReplyErrorKind::InvalidArgument` error with the `message`
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(ReplyErrorKind::InvalidArgument, message)
+    }
+    /// New `ReplyErrorKind::Internal` error with the `message`
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ReplyErrorKind::Internal, message)
+    }
+    /// New `ReplyErrorKind::Unavailable` error with the `message`
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(ReplyErrorKind::Unavailable, message)
+    }
+}
+
+impl std::fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}: '{}'", self.kind.as_ref(), message),
+            None => write!(f, "{}", self.kind.as_ref()),
+        }
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
+/// Wire representations of the `ReplyError`, which include those sent by
+/// older services, which are then seen as `ReplyErrorKind::Internal` errors
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReplyErrorRepr {
+    Typed {
+        kind: ReplyErrorKind,
+        #[serde(default)]
+        message: Option<String>,
+    },
+    Legacy(LegacyReplyError),
+    Plain(String),
+}
+
+/// Error type which was returned over the bus before it carried a kind
+#[derive(Deserialize)]
+enum LegacyReplyError {
     WithMessage { message: String },
-    #[snafu(display("Failed to deserialize the request: '{}'", message))]
     DeserializeReq { message: String },
-    #[snafu(display("Failed to process the request: '{}'", message))]
     Process { message: String },
 }
 
+impl From<ReplyErrorRepr> for ReplyError {
+    fn from(repr: ReplyErrorRepr) -> Self {
+        match repr {
+            ReplyErrorRepr::Typed {
+                kind,
+                message,
+            } => Self {
+                kind,
+                message,
+            },
+            ReplyErrorRepr::Legacy(LegacyReplyError::DeserializeReq {
+                message,
+            }) => Self::invalid_argument(message),
+            ReplyErrorRepr::Legacy(LegacyReplyError::WithMessage {
+                message,
+            })
+            | ReplyErrorRepr::Legacy(LegacyReplyError::Process {
+                message,
+            })
+            | ReplyErrorRepr::Plain(message) => Self::internal(message),
+        }
+    }
+}
+
 /// Payload returned to the sender
 /// Includes an error as the operations may be fallible
 #[derive(Serialize, Deserialize, Debug)]
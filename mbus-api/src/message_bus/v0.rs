@@ -32,6 +32,10 @@ pub type BusResult<T> = Result<T, BusError>;
 pub type Node = crate::v0::Node;
 /// Node list
 pub type Nodes = crate::v0::Nodes;
+/// Node labels
+pub type NodeLabels = crate::v0::NodeLabels;
+/// Set Node Labels
+pub type SetNodeLabels = crate::v0::SetNodeLabels;
 /// Pool
 pub type Pool = crate::v0::Pool;
 /// Pool list
@@ -50,6 +54,18 @@ pub type CreatePool = crate::v0::CreatePool;
 pub type DestroyReplica = crate::v0::DestroyReplica;
 /// Pool Destroy
 pub type DestroyPool = crate::v0::DestroyPool;
+/// Pool Threshold
+pub type SetPoolThreshold = crate::v0::SetPoolThreshold;
+/// Pool Capacity Event
+pub type PoolCapacityEvent = crate::v0::PoolCapacityEvent;
+/// Pool Scan Event
+pub type PoolScanEvent = crate::v0::PoolScanEvent;
+/// Pool Stats
+pub type PoolStats = crate::v0::PoolStats;
+/// Replica Stats
+pub type ReplicaStats = crate::v0::ReplicaStats;
+/// IO counters of a pool or replica
+pub type IoStats = crate::v0::IoStats;
 /// Replica Share
 pub type ShareReplica = crate::v0::ShareReplica;
 /// Replica Unshare
@@ -86,12 +102,24 @@ pub type Volume = crate::v0::Volume;
 pub type Volumes = crate::v0::Volumes;
 /// Create Volume
 pub type CreateVolume = crate::v0::CreateVolume;
+/// Volume replica topology constraint
+pub type Topology = crate::v0::Topology;
 /// Delete Volume
 pub type DestroyVolume = crate::v0::DestroyVolume;
 /// Add Volume Nexus
 pub type AddVolumeNexus = crate::v0::AddVolumeNexus;
 /// Remove Volume Nexus
 pub type RemoveVolumeNexus = crate::v0::RemoveVolumeNexus;
+/// Set Volume Replica Count
+pub type SetReplicaCount = crate::v0::SetReplicaCount;
+/// Publish Volume
+pub type PublishVolume = crate::v0::PublishVolume;
+/// Unpublish Volume
+pub type UnpublishVolume = crate::v0::UnpublishVolume;
+/// Move Volume Replica
+pub type MoveReplica = crate::v0::MoveReplica;
+/// Failover Volume
+pub type FailoverVolume = crate::v0::FailoverVolume;
 /// Id of a mayastor node
 pub type NodeId = crate::v0::NodeId;
 /// Id of a mayastor pool
@@ -123,6 +151,35 @@ macro_rules! only_one {
 /// services via the message bus
 #[async_trait]
 pub trait MessageBusTrait: Sized {
+    /// Probe the liveness of the services on the given channels, returning
+    /// the result of each. The probes run concurrently, each failing once
+    /// its timeout is up, so a service which does not reply does not hold
+    /// up the results of the others.
+    async fn check_liveness(
+        channels: Vec<ChannelVs>,
+        timeout: std::time::Duration,
+    ) -> Vec<(ChannelVs, BusResult<()>)> {
+        let probes = channels
+            .into_iter()
+            .map(|channel| {
+                let probe = tokio::spawn({
+                    let channel = channel.clone();
+                    async move {
+                        Liveness {}.request_on_timeout(channel, timeout).await
+                    }
+                });
+                (channel, probe)
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        for (channel, probe) in probes {
+            let result = probe.await.expect("liveness probe panicked");
+            results.push((channel, result.map_err(BusError::from)));
+        }
+        results
+    }
+
     /// Get all known nodes from the registry
     #[tracing::instrument(level = "debug", err)]
     async fn get_nodes() -> BusResult<Vec<Node>> {
@@ -140,6 +197,13 @@ pub trait MessageBusTrait: Sized {
         only_one!(nodes)
     }
 
+    /// set the labels of a node
+    #[tracing::instrument(level = "debug", err)]
+    async fn set_node_labels(request: SetNodeLabels) -> BusResult<()> {
+        request.request().await?;
+        Ok(())
+    }
+
     /// Get pool with filter
     #[tracing::instrument(level = "debug", err)]
     async fn get_pool(filter: Filter) -> BusResult<Pool> {
@@ -171,6 +235,24 @@ pub trait MessageBusTrait: Sized {
         Ok(())
     }
 
+    /// set pool capacity threshold
+    #[tracing::instrument(level = "debug", err)]
+    async fn set_pool_threshold(request: SetPoolThreshold) -> BusResult<()> {
+        request.request().await?;
+        Ok(())
+    }
+
+    /// Get the live stats of the pool matching the filter
+    #[tracing::instrument(level = "debug", err)]
+    async fn get_pool_stats(filter: Filter) -> BusResult<PoolStats> {
+        let stats = GetPoolStats {
+            filter,
+        }
+        .request()
+        .await?;
+        Ok(stats)
+    }
+
     /// Get replica with filter
     #[tracing::instrument(level = "debug", err)]
     async fn get_replica(filter: Filter) -> BusResult<Replica> {
@@ -189,6 +271,17 @@ pub trait MessageBusTrait: Sized {
         Ok(replicas.into_inner())
     }
 
+    /// Get the live stats of the replica matching the filter
+    #[tracing::instrument(level = "debug", err)]
+    async fn get_replica_stats(filter: Filter) -> BusResult<ReplicaStats> {
+        let stats = GetReplicaStats {
+            filter,
+        }
+        .request()
+        .await?;
+        Ok(stats)
+    }
+
     /// create replica
     #[tracing::instrument(level = "debug", err)]
     async fn create_replica(request: CreateReplica) -> BusResult<Replica> {
@@ -317,6 +410,37 @@ pub trait MessageBusTrait: Sized {
         request.request().await?;
         Ok(())
     }
+
+    /// set the replica count of a volume
+    #[tracing::instrument(level = "debug", err)]
+    async fn set_replica_count(request: SetReplicaCount) -> BusResult<Volume> {
+        Ok(request.request().await?)
+    }
+
+    /// publish volume
+    #[tracing::instrument(level = "debug", err)]
+    async fn publish_volume(request: PublishVolume) -> BusResult<String> {
+        Ok(request.request().await?)
+    }
+
+    /// unpublish volume
+    #[tracing::instrument(level = "debug", err)]
+    async fn unpublish_volume(request: UnpublishVolume) -> BusResult<()> {
+        request.request().await?;
+        Ok(())
+    }
+
+    /// move a volume replica to another node
+    #[tracing::instrument(level = "debug", err)]
+    async fn move_replica(request: MoveReplica) -> BusResult<Volume> {
+        Ok(request.request().await?)
+    }
+
+    /// fail a volume over to another node
+    #[tracing::instrument(level = "debug", err)]
+    async fn failover_volume(request: FailoverVolume) -> BusResult<String> {
+        Ok(request.request().await?)
+    }
 }
 
 /// Implementation of the bus interface trait
@@ -409,6 +533,7 @@ mod tests {
                 id: mayastor.clone(),
                 grpc_endpoint: "0.0.0.0:10124".to_string(),
                 state: NodeState::Online,
+                labels: Default::default(),
             }
         );
         let node = MessageBus::get_node(mayastor).await?;
@@ -418,6 +543,7 @@ mod tests {
                 id: mayastor.clone(),
                 grpc_endpoint: "0.0.0.0:10124".to_string(),
                 state: NodeState::Online,
+                labels: Default::default(),
             }
         );
 
@@ -426,6 +552,67 @@ mod tests {
         tokio::time::delay_for(std::time::Duration::from_millis(250)).await;
         assert!(MessageBus::get_nodes().await?.is_empty());
 
+        // a service which is gone fails the request once the timeout is up
+        let timeout = std::time::Duration::from_secs(1);
+        Liveness {}.request_on_timeout(ChannelVs::Node, timeout).await?;
+        let live = MessageBus::check_liveness(
+            vec![ChannelVs::Node, ChannelVs::Pool],
+            timeout,
+        )
+        .await;
+        assert!(matches!(live[0], (ChannelVs::Node, Ok(()))));
+        assert!(matches!(live[1], (ChannelVs::Pool, Err(_))));
+        test.stop("node").await?;
+        let start = std::time::Instant::now();
+        let result = Liveness {}
+            .request_on_timeout(ChannelVs::Node, timeout)
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::Error::RequestTimeout {
+                ..
+            })
+        ));
+        assert!(start.elapsed() < 2 * timeout);
+
         Ok(())
     }
+
+    #[tokio::test]
+    async fn correlation_id() {
+        assert_eq!(crate::correlation_id(), None);
+        let id = new_correlation_id();
+        let current = async { crate::correlation_id() };
+        let inner = with_correlation_id(id.clone(), current).await;
+        assert_eq!(inner, Some(id));
+        assert_eq!(crate::correlation_id(), None);
+
+        // a correlation id is made up for messages which lack it
+        let payload = br#"{"id":"v0/liveness","sender":"old","data":{}}"#;
+        let preamble: Preamble = serde_json::from_slice(payload).unwrap();
+        assert!(!preamble.correlation_id.is_empty());
+    }
+
+    #[test]
+    fn reply_error() {
+        let error = ReplyError::not_found("pool 'p0' not found");
+        let json = serde_json::to_string(&error).unwrap();
+        let reply: ReplyError = serde_json::from_str(&json).unwrap();
+        assert_eq!(reply, error);
+
+        let reply: ReplyError =
+            serde_json::from_str(r#"{"kind":"Unavailable"}"#).unwrap();
+        assert_eq!(reply.kind, ReplyErrorKind::Unavailable);
+        assert_eq!(reply.message, None);
+
+        // errors sent by older services are seen as internal errors
+        let legacy = r#"{"WithMessage":{"message":"Config is missing"}}"#;
+        let reply: ReplyError = serde_json::from_str(legacy).unwrap();
+        assert_eq!(reply, ReplyError::internal("Config is missing"));
+        let reply: ReplyError = serde_json::from_str(r#""failed""#).unwrap();
+        assert_eq!(reply, ReplyError::internal("failed"));
+
+        let other = r#"{"Ok":null}"#;
+        assert!(serde_json::from_str::<ReplyError>(other).is_err());
+    }
 }
@@ -50,6 +50,10 @@ where
     pub fn sender(&self) -> SenderId {
         self.request.sender.clone()
     }
+    /// Get the correlation identifier
+    pub fn correlation_id(&self) -> CorrelationId {
+        self.request.correlation_id.clone()
+    }
 
     /// Reply back to the sender with the `reply` payload wrapped by
     /// a Result-like type.
@@ -79,8 +83,9 @@ where
             })?;
         if request.id == request.data.id() {
             log::trace!(
-                "Received message from '{}': {:?}",
+                "Received message from '{}' ({}): {:?}",
                 request.sender,
+                request.correlation_id,
                 request.data
             );
             Ok(Self {
@@ -142,6 +147,17 @@ impl<'a> ReceivedRawMessage<'a> {
         Ok(preamble.id)
     }
 
+    /// Get the correlation identifier of this message.
+    /// May fail if the raw data cannot be deserialized into the preamble.
+    pub fn correlation_id(&self) -> BusResult<CorrelationId> {
+        let preamble: Preamble = serde_json::from_slice(&self.bus_msg.data)
+            .context(DeserializeSend {
+                receiver: std::any::type_name::<Preamble>(),
+                payload: String::from_utf8(self.bus_msg.data.clone()),
+            })?;
+        Ok(preamble.correlation_id)
+    }
+
     /// Channel where this message traversed
     pub fn channel(&self) -> Channel {
         self.bus_msg.subject.clone().parse().unwrap()
@@ -166,6 +166,16 @@ macro_rules! bus_impl_message {
             ) -> BusResult<$R> {
                 $T::Request_Ext(self, channel.into(), bus(), options).await
             }
+            async fn request_on_timeout<C: Into<Channel> + Send>(
+                &self,
+                channel: C,
+                timeout: std::time::Duration,
+            ) -> BusResult<$R> {
+                let options = TimeoutOptions::new()
+                    .with_timeout(timeout)
+                    .with_max_retries(0);
+                $T::Request_Ext(self, channel.into(), bus(), options).await
+            }
         }
     };
 }
@@ -277,12 +287,16 @@ where
     /// Creates a new request `Message` with the required payload
     /// using an existing `bus` which is used to sent the payload
     /// via the `channel`.
+    /// The message carries the correlation id of the current task or a new
+    /// one if there's none.
     pub(crate) fn new(payload: &'a S, channel: Channel, bus: DynBus) -> Self {
         Self {
             payload: SendPayload {
                 id: payload.id(),
                 data: payload,
                 sender: Self::name(),
+                correlation_id: correlation_id()
+                    .unwrap_or_else(new_correlation_id),
             },
             reply_type: Default::default(),
             bus,
@@ -3,7 +3,7 @@ use super::*;
 use paperclip::actix::Apiv2Schema;
 use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, fmt::Debug};
+use std::{cmp::Ordering, collections::HashMap, fmt::Debug};
 use strum_macros::{EnumString, ToString};
 
 /// Versioned Channels
@@ -24,6 +24,8 @@ pub enum ChannelVs {
     Nexus,
     /// Keep it In Sync Service
     Kiiss,
+    /// Events emitted by the control plane services
+    Event,
 }
 impl Default for ChannelVs {
     fn default() -> Self {
@@ -56,6 +58,8 @@ pub enum MessageIdVs {
     /// Node Service
     /// Get all node information
     GetNodes,
+    /// Set the labels of a node
+    SetNodeLabels,
     /// Pool Service
     ///
     /// Get pools with filter
@@ -74,6 +78,16 @@ pub enum MessageIdVs {
     ShareReplica,
     /// Unshare Replica,
     UnshareReplica,
+    /// Set the used capacity threshold of a pool
+    SetPoolThreshold,
+    /// Pool used capacity crossed its threshold
+    PoolCapacityEvent,
+    /// Background scan of a pool found a block which could not be read back
+    PoolScanEvent,
+    /// Get the live capacity and IO stats of a pool
+    GetPoolStats,
+    /// Get the live capacity and IO stats of a replica
+    GetReplicaStats,
     /// Volume Service
     ///
     /// Get nexuses with filter
@@ -100,6 +114,16 @@ pub enum MessageIdVs {
     AddVolumeNexus,
     /// Remove nexus from volume
     RemoveVolumeNexus,
+    /// Set the number of replicas of a volume
+    SetReplicaCount,
+    /// Publish a volume on a node
+    PublishVolume,
+    /// Unpublish a volume
+    UnpublishVolume,
+    /// Move a volume replica to another node
+    MoveReplica,
+    /// Publish a volume on another node before its nexus goes away
+    FailoverVolume,
 }
 
 // Only V0 should export this macro
@@ -239,11 +263,28 @@ pub struct Node {
     pub grpc_endpoint: String,
     /// deemed state of the node
     pub state: NodeState,
+    /// key/value labels of the node, eg: topology information
+    #[serde(default)]
+    pub labels: NodeLabels,
 }
 
 bus_impl_vector_request!(Nodes, Node);
 bus_impl_message_all!(GetNodes, GetNodes, Nodes, Node);
 
+/// Key/value labels of a node
+pub type NodeLabels = HashMap<String, String>;
+
+/// Set the labels of a node, replacing any existing labels
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SetNodeLabels {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// new labels of the node
+    pub labels: NodeLabels,
+}
+bus_impl_message_all!(SetNodeLabels, SetNodeLabels, (), Node);
+
 /// Filter Objects based on one of the following criteria
 /// # Example:
 /// // Get all nexuses from the node `node_id`
@@ -492,6 +533,131 @@ bus_impl_message_all!(DestroyPool, DestroyPool, (), Pool);
 bus_impl_vector_request!(Pools, Pool);
 bus_impl_message_all!(GetPools, GetPools, Pools, Pool);
 
+/// Set the high watermark threshold of the used capacity of a pool
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPoolThreshold {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// id of the pool
+    pub id: PoolId,
+    /// threshold as a percentage of the pool capacity (1-100)
+    /// None disables the threshold
+    pub threshold: Option<u8>,
+}
+bus_impl_message_all!(SetPoolThreshold, SetPoolThreshold, (), Pool);
+
+/// Kind of pool capacity event
+#[derive(
+    Serialize, Deserialize, Debug, Clone, EnumString, ToString, Eq, PartialEq,
+)]
+pub enum PoolCapacityEventKind {
+    /// the used capacity rose above the threshold
+    Alert,
+    /// the used capacity dropped back below the threshold
+    Clear,
+}
+impl Default for PoolCapacityEventKind {
+    fn default() -> Self {
+        Self::Alert
+    }
+}
+
+/// Pool capacity event, published when the used capacity of a pool crosses
+/// its high watermark threshold
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolCapacityEvent {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// id of the pool
+    pub id: PoolId,
+    /// size of the pool in bytes
+    pub capacity: u64,
+    /// used bytes from the pool
+    pub used: u64,
+    /// threshold as a percentage of the pool capacity
+    pub threshold: u8,
+    /// alert or clear
+    pub kind: PoolCapacityEventKind,
+}
+bus_impl_message_all!(PoolCapacityEvent, PoolCapacityEvent, (), Event);
+
+/// Pool scan event, published by a mayastor instance when the background
+/// scan of a pool fails to read back the data of a replica or finds it does
+/// not match its checksums
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolScanEvent {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// id of the pool
+    pub id: PoolId,
+    /// uuid of the replica
+    pub replica: ReplicaId,
+    /// offset of the failed blocks in the replica in bytes
+    pub offset: u64,
+    /// length of the failed blocks in bytes
+    pub len: u64,
+    /// what failed
+    pub error: String,
+}
+bus_impl_message_all!(PoolScanEvent, PoolScanEvent, (), Event);
+
+/// IO counters of a pool or replica, since it was last created or imported
+#[derive(
+    Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq, Apiv2Schema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct IoStats {
+    /// number of read operations
+    pub num_read_ops: u64,
+    /// number of write operations
+    pub num_write_ops: u64,
+    /// bytes read
+    pub bytes_read: u64,
+    /// bytes written
+    pub bytes_written: u64,
+}
+
+impl std::ops::AddAssign for IoStats {
+    fn add_assign(&mut self, other: Self) {
+        self.num_read_ops += other.num_read_ops;
+        self.num_write_ops += other.num_write_ops;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+/// Get the live stats of the pool matching the filter, which is fetched
+/// from its mayastor instance rather than from the cache of the registry
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct GetPoolStats {
+    /// Filter request
+    pub filter: Filter,
+}
+
+/// Pool stats
+#[derive(
+    Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq, Apiv2Schema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStats {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// id of the pool
+    pub id: PoolId,
+    /// size of the pool in bytes
+    pub capacity: u64,
+    /// used bytes from the pool
+    pub used: u64,
+    /// free bytes of the pool
+    pub free: u64,
+    /// IO counters of the pool, summed over its replicas
+    pub io: IoStats,
+}
+bus_impl_message_all!(GetPoolStats, GetPoolStats, PoolStats, Pool);
+
 /// Get all the replicas from specific node and pool
 /// or None for all nodes or all pools
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -525,6 +691,38 @@ pub struct Replica {
 bus_impl_vector_request!(Replicas, Replica);
 bus_impl_message_all!(GetReplicas, GetReplicas, Replicas, Pool);
 
+/// Get the live stats of the replica matching the filter, which is fetched
+/// from its mayastor instance rather than from the cache of the registry
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct GetReplicaStats {
+    /// Filter request
+    pub filter: Filter,
+}
+
+/// Replica stats
+#[derive(
+    Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq, Apiv2Schema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicaStats {
+    /// id of the mayastor instance
+    pub node: NodeId,
+    /// uuid of the replica
+    pub uuid: ReplicaId,
+    /// id of the pool
+    pub pool: PoolId,
+    /// size of the replica in bytes
+    pub capacity: u64,
+    /// bytes allocated to the replica, not known when thin provisioned
+    pub used: Option<u64>,
+    /// bytes of the replica left to allocate, not known when thin
+    /// provisioned
+    pub free: Option<u64>,
+    /// IO counters of the replica
+    pub io: IoStats,
+}
+bus_impl_message_all!(GetReplicaStats, GetReplicaStats, ReplicaStats, Pool);
+
 /// Create Replica Request
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -918,9 +1116,39 @@ pub struct CreateVolume {
     /// preferred nodes for the nexuses
     #[serde(default)]
     pub preferred_nexus_nodes: Vec<NodeId>,
+    /// topology constraints for the placement of the replicas
+    #[serde(default)]
+    pub topology: Option<Topology>,
+    /// only nodes with all of these labels can be used for the replicas
+    #[serde(default)]
+    pub node_selector: NodeLabels,
+    /// replicas must be placed on nodes which also hold replicas of each of
+    /// these volumes
+    #[serde(default)]
+    pub affinity: Vec<VolumeId>,
+    /// replicas must not be placed on nodes which hold replicas of any of
+    /// these volumes
+    #[serde(default)]
+    pub anti_affinity: Vec<VolumeId>,
 }
 bus_impl_message_all!(CreateVolume, CreateVolume, Volume, Volume);
 
+/// Replica placement constraint based on a node topology label, eg: "zone"
+#[derive(
+    Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq, Apiv2Schema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Topology {
+    /// key of the topology label
+    pub key: String,
+    /// place each replica on a node with a distinct value of the label
+    #[serde(default)]
+    pub spread: bool,
+    /// only place replicas on nodes where the label has this value
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
 /// Delete volume
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -951,3 +1179,72 @@ pub struct RemoveVolumeNexus {
     pub node: Option<NodeId>,
 }
 bus_impl_message_all!(RemoveVolumeNexus, RemoveVolumeNexus, (), Volume);
+
+/// Set the replica count of a volume
+/// Replicas are added to or removed from the volume's nexus, online
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReplicaCount {
+    /// uuid of the volume
+    pub uuid: VolumeId,
+    /// new number of replicas
+    pub replicas: u64,
+}
+bus_impl_message_all!(SetReplicaCount, SetReplicaCount, Volume, Volume);
+
+/// Publish a volume on a node
+/// The volume's nexus is created on the node, if required, and shared over
+/// nvmf. Replies with the share uri of the nexus
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishVolume {
+    /// uuid of the volume
+    pub uuid: VolumeId,
+    /// id of the node where the volume is published
+    pub node: NodeId,
+    /// move the volume's nexus to the node, even if already published on
+    /// another node
+    #[serde(default)]
+    pub force: bool,
+}
+bus_impl_message_all!(PublishVolume, PublishVolume, String, Volume);
+
+/// Unpublish a volume
+/// The volume's nexus is unshared and destroyed, the replicas are kept
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UnpublishVolume {
+    /// uuid of the volume
+    pub uuid: VolumeId,
+}
+bus_impl_message_all!(UnpublishVolume, UnpublishVolume, (), Volume);
+
+/// Move a replica of a volume from one node to another
+/// A new replica is created on the destination node and rebuilt before the
+/// source replica is removed, so the volume never loses redundancy
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveReplica {
+    /// uuid of the volume
+    pub uuid: VolumeId,
+    /// id of the node which currently holds the replica
+    pub from_node: NodeId,
+    /// id of the node where the replica is moved to
+    pub to_node: NodeId,
+}
+bus_impl_message_all!(MoveReplica, MoveReplica, Volume, Volume);
+
+/// Fail a published volume over to another node
+/// The node where the volume is published is shutting down. The volume is
+/// published on another node which holds one of its replicas, under the same
+/// NQN, so that multipath initiators switch to the new path before the old
+/// one goes away. Returns the URI of the new path.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FailoverVolume {
+    /// uuid of the volume
+    pub uuid: VolumeId,
+    /// id of the node which is shutting down
+    pub node: NodeId,
+}
+bus_impl_message_all!(FailoverVolume, FailoverVolume, String, Volume);
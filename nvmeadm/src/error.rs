@@ -46,8 +46,14 @@ pub enum NvmeError {
     },
     #[snafu(display("NVMe URI invalid: {}", source))]
     UrlError { source: url::ParseError },
+    #[snafu(display("NVMe URI {} invalid: {}", uri, reason))]
+    InvalidUri { uri: String, reason: String },
     #[snafu(display("Transport type {} not supported", trtype))]
     TransportError { trtype: String },
+    #[snafu(display("Invalid connect options: {}", reason))]
+    InvalidConnectOptions { reason: String },
+    #[snafu(display("None of the {} paths to {} connected", paths, nqn))]
+    NoPathConnected { nqn: String, paths: usize },
 }
 
 impl From<std::io::Error> for NvmeError {
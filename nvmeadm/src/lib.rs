@@ -40,7 +40,8 @@ use error::{IoError, NvmeError};
 use snafu::ResultExt;
 mod nvme_uri;
 
-pub use nvme_uri::NvmeTarget;
+pub use nvme_uri::{MultipathConnection, NvmeTarget, NvmeTargetBuilder};
+pub use nvmf_discovery::ConnectOptions;
 /// the device entry in /dev for issuing ioctls to the kernels nvme driver
 const NVME_FABRICS_PATH: &str = "/dev/nvme-fabrics";
 /// ioctl for passing any NVMe command to the kernel
@@ -1,6 +1,11 @@
-use std::{convert::TryFrom, time::Duration};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    time::Duration,
+};
 
-use url::{ParseError, Url};
+use url::{form_urlencoded::Serializer, Url};
 
 use crate::{
     error::NvmeError,
@@ -8,13 +13,44 @@ use crate::{
     nvmf_discovery::disconnect,
 };
 
-use super::nvmf_discovery::connect;
+use super::nvmf_discovery::{
+    connect_with_options,
+    default_hostnqn,
+    ConnectOptions,
+};
 
+/// An NVMe over fabrics target as given by a share URI of the form
+/// `nvmf[+tcp|+rdma]://host[:port]/nqn[?transport=..&port=..&hostnqn=..]`,
+/// where the query parameters are optional.
+#[derive(Debug, Clone, PartialEq, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct NvmeTarget {
+    #[builder(setter(into))]
     host: String,
+    #[builder(default = "DEFAULT_PORT")]
     port: u16,
+    #[builder(setter(into))]
     subsysnqn: String,
+    #[builder(setter(into), default = "\"tcp\".into()")]
     trtype: String,
+    /// the nqn this host connects as, instead of the default one
+    #[builder(setter(into), default)]
+    hostnqn: Option<String>,
+}
+
+/// the port targets listen on unless told otherwise
+const DEFAULT_PORT: u16 = 4420;
+
+/// A target connected over multiple paths, see
+/// [NvmeTarget::connect_multipath].
+#[derive(Debug)]
+pub struct MultipathConnection {
+    /// the namespace device the kernel presents for the paths
+    pub device: NvmeDevice,
+    /// the controllers of the paths which came up, such as nvme1
+    pub controllers: Vec<String>,
+    /// the addresses of the paths which did not come up, with the reason
+    pub failed: Vec<(String, NvmeError)>,
 }
 
 impl TryFrom<String> for NvmeTarget {
@@ -28,60 +64,276 @@ impl TryFrom<&str> for NvmeTarget {
     type Error = NvmeError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = |reason: String| NvmeError::InvalidUri {
+            uri: value.to_string(),
+            reason,
+        };
+
         let url = Url::parse(&value).map_err(|source| NvmeError::UrlError {
             source,
         })?;
 
-        let trtype = match url.scheme() {
-            "nvmf" | "nvmf+tcp" => Ok("tcp"),
-            _ => Err(NvmeError::UrlError {
-                source: ParseError::IdnaError,
-            }),
-        }?
-        .into();
-
-        let host = url
-            .host_str()
-            .ok_or(NvmeError::UrlError {
-                source: ParseError::EmptyHost,
-            })?
-            .into();
-
-        let subnqn = match url.path_segments() {
-            None => Err(NvmeError::UrlError {
-                source: ParseError::RelativeUrlWithCannotBeABaseBase,
-            }),
-            Some(s) => {
-                let segments = s.collect::<Vec<&str>>();
-                if segments[0].is_empty() {
-                    Err(NvmeError::UrlError {
-                        source: ParseError::RelativeUrlWithCannotBeABaseBase,
-                    })
-                } else {
-                    Ok(segments[0].to_string())
+        let mut trtype = match url.scheme() {
+            "nvmf" => None,
+            "nvmf+tcp" => Some("tcp"),
+            "nvmf+rdma" => Some("rdma"),
+            scheme => {
+                return Err(invalid(format!("unsupported scheme {}", scheme)))
+            }
+        }
+        .map(String::from);
+
+        let host = match url.host_str() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => return Err(invalid("missing host".into())),
+        };
+
+        let subsysnqn = match url
+            .path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        {
+            Some(segments) if segments.len() == 1 => segments[0].to_string(),
+            Some(segments) if segments.len() > 1 => {
+                return Err(invalid("more than one nqn in the path".into()))
+            }
+            _ => return Err(invalid("missing nqn".into())),
+        };
+
+        let mut port = url.port();
+        let mut hostnqn = None;
+        let mut seen = Vec::new();
+        for (key, value) in url.query_pairs() {
+            if seen.contains(&key) {
+                return Err(invalid(format!("parameter {} given twice", key)));
+            }
+            match key.as_ref() {
+                "transport" | "trtype" => {
+                    let value = value.to_lowercase();
+                    if value != "tcp" && value != "rdma" {
+                        return Err(invalid(format!(
+                            "unsupported transport {}",
+                            value
+                        )));
+                    }
+                    if trtype.as_ref().map_or(false, |t| *t != value) {
+                        return Err(invalid(format!(
+                            "transport {} conflicts with the scheme",
+                            value
+                        )));
+                    }
+                    trtype = Some(value);
+                }
+                "port" | "trsvcid" => {
+                    let value = value
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|p| *p != 0)
+                        .ok_or_else(|| {
+                            invalid(format!("invalid port {}", value))
+                        })?;
+                    if port.map_or(false, |p| p != value) {
+                        return Err(invalid(format!(
+                            "port {} conflicts with the host",
+                            value
+                        )));
+                    }
+                    port = Some(value);
+                }
+                "hostnqn" => {
+                    if value.is_empty() {
+                        return Err(invalid("empty hostnqn".into()));
+                    }
+                    hostnqn = Some(value.to_string());
+                }
+                _ => {
+                    return Err(invalid(format!("unknown parameter {}", key)))
                 }
             }
-        }?;
+            seen.push(key);
+        }
 
         Ok(Self {
-            trtype,
+            trtype: trtype.unwrap_or_else(|| "tcp".into()),
             host,
-            port: url.port().unwrap_or(4420),
-            subsysnqn: subnqn,
+            port: port.unwrap_or(DEFAULT_PORT),
+            subsysnqn,
+            hostnqn,
         })
     }
 }
 
+impl Display for NvmeTarget {
+    /// the URI of the target, which parses back into the same target
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "nvmf://{}:{}/{}", self.host, self.port, self.subsysnqn)?;
+        let mut query = Serializer::new(String::new());
+        if self.trtype != "tcp" {
+            query.append_pair("transport", &self.trtype);
+        }
+        if let Some(hostnqn) = &self.hostnqn {
+            query.append_pair("hostnqn", hostnqn);
+        }
+        let query = query.finish();
+        if !query.is_empty() {
+            write!(f, "?{}", query)?;
+        }
+        Ok(())
+    }
+}
+
+impl NvmeTargetBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if self.host.as_ref().map_or(false, |h| h.is_empty()) {
+            return Err("host must not be empty".into());
+        }
+        if self.subsysnqn.as_ref().map_or(false, |n| n.is_empty()) {
+            return Err("nqn must not be empty".into());
+        }
+        if self.port == Some(0) {
+            return Err("invalid port 0".into());
+        }
+        if let Some(trtype) = &self.trtype {
+            if trtype != "tcp" && trtype != "rdma" {
+                return Err(format!("unsupported transport {}", trtype));
+            }
+        }
+        if let Some(Some(hostnqn)) = &self.hostnqn {
+            if hostnqn.is_empty() {
+                return Err("hostnqn must not be empty".into());
+            }
+        }
+        Ok(())
+    }
+}
+
 impl NvmeTarget {
+    /// builder for a target, to construct one without a URI
+    pub fn builder() -> NvmeTargetBuilder {
+        NvmeTargetBuilder::default()
+    }
+
+    /// the host address of the target
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// the port of the target
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// the nqn of the subsystem of the target
+    pub fn subsysnqn(&self) -> &str {
+        &self.subsysnqn
+    }
+
+    /// the transport type of the target
+    pub fn trtype(&self) -> &str {
+        &self.trtype
+    }
+
+    /// the nqn this host connects as, if not the default one
+    pub fn hostnqn(&self) -> Option<&str> {
+        self.hostnqn.as_deref()
+    }
+
     pub fn connect(&self) -> Result<Vec<NvmeDevice>, NvmeError> {
+        self.connect_with(&ConnectOptions::default())
+    }
+
+    /// Connect to the subsystem of the target like `connect()`, creating the
+    /// controller with the given options, which are validated before
+    /// connecting.
+    pub fn connect_with(
+        &self,
+        options: &ConnectOptions,
+    ) -> Result<Vec<NvmeDevice>, NvmeError> {
+        self.check_transport()?;
+        self.connect_path(&self.host, self.port, options)?;
+        Ok(self.devices())
+    }
+
+    /// Connect to the subsystem of the target over its own address and each
+    /// of the given ones, for the kernel to present a single multipath
+    /// device for all of them. Succeeds as long as one path comes up,
+    /// reporting the paths which did not. Disconnecting the target tears
+    /// down all the paths.
+    pub fn connect_multipath(
+        &self,
+        extra_addrs: &[SocketAddr],
+    ) -> Result<MultipathConnection, NvmeError> {
+        self.check_transport()?;
+
+        let mut paths = vec![(self.host.clone(), self.port)];
+        paths.extend(
+            extra_addrs.iter().map(|a| (a.ip().to_string(), a.port())),
+        );
+
+        let mut controllers = Vec::new();
+        let mut failed = Vec::new();
+        for (host, port) in paths {
+            match self.connect_path(&host, port, &ConnectOptions::default()) {
+                Ok(controller) => controllers.push(controller),
+                Err(error) => {
+                    failed.push((format!("{}:{}", host, port), error))
+                }
+            }
+        }
+        if controllers.is_empty() {
+            return Err(NvmeError::NoPathConnected {
+                nqn: self.subsysnqn.clone(),
+                paths: failed.len(),
+            });
+        }
+
+        let device = self.devices().pop().ok_or_else(|| NvmeError::NqnNotFound {
+            text: self.subsysnqn.clone(),
+        })?;
+        Ok(MultipathConnection {
+            device,
+            controllers,
+            failed,
+        })
+    }
+
+    /// fails if the transport of the target is not supported
+    fn check_transport(&self) -> Result<(), NvmeError> {
         if self.trtype != "tcp" {
             return Err(NvmeError::TransportError {
                 trtype: self.trtype.clone(),
             });
         }
+        Ok(())
+    }
 
-        connect(&self.host, self.port, &self.subsysnqn)?;
+    /// connect to the subsystem of the target over the given address and
+    /// return the controller connected, such as nvme1
+    fn connect_path(
+        &self,
+        host: &str,
+        port: u16,
+        options: &ConnectOptions,
+    ) -> Result<String, NvmeError> {
+        let hostnqn = self.hostnqn.clone().unwrap_or_else(default_hostnqn);
+        let reply = connect_with_options(
+            host,
+            port,
+            &self.subsysnqn,
+            &hostnqn,
+            options,
+        )?;
+
+        // the kernel replies with instance=N,cntlid=M
+        reply
+            .trim()
+            .split(',')
+            .find_map(|p| p.strip_prefix("instance="))
+            .map(|i| format!("nvme{}", i))
+            .ok_or(NvmeError::ParseError {})
+    }
 
+    /// wait for the devices of the subsystem of the target to show up
+    fn devices(&self) -> Vec<NvmeDevice> {
         let mut retries = 10;
         let mut all_nvme_devices;
         loop {
@@ -98,9 +350,11 @@ impl NvmeTarget {
             }
         }
 
-        Ok(all_nvme_devices)
+        all_nvme_devices
     }
 
+    /// disconnect from the subsystem of the target, over all its paths,
+    /// returning the number of controllers disconnected
     pub fn disconnect(&self) -> Result<usize, NvmeError> {
         disconnect(&self.subsysnqn)
     }
@@ -126,3 +380,113 @@ fn nvme_parse_uri() {
     assert_eq!(target.trtype, "tcp");
     assert_eq!(target.subsysnqn, "testnqn.what-ever.foo");
 }
+
+#[test]
+fn nvme_parse_uri_params() {
+    let uri = "nvmf://1.2.3.4/nqn.2019-05.io.openebs:00000000-0000-0000";
+    let target = NvmeTarget::try_from(uri).unwrap();
+    assert_eq!(target.port, DEFAULT_PORT);
+    assert_eq!(target.trtype, "tcp");
+    assert_eq!(target.subsysnqn, "nqn.2019-05.io.openebs:00000000-0000-0000");
+    assert_eq!(target.hostnqn, None);
+
+    let uri = "nvmf://1.2.3.4:8420/nqn.2021-02.io.openebs.mayastor:node-1:vol";
+    let target = NvmeTarget::try_from(uri).unwrap();
+    assert_eq!(target.subsysnqn, "nqn.2021-02.io.openebs.mayastor:node-1:vol");
+
+    let target = NvmeTarget::try_from(
+        "nvmf://1.2.3.4/testnqn/?transport=rdma&port=8420&hostnqn=nqn.host:a",
+    )
+    .unwrap();
+    assert_eq!(target.port, 8420);
+    assert_eq!(target.host, "1.2.3.4");
+    assert_eq!(target.trtype, "rdma");
+    assert_eq!(target.subsysnqn, "testnqn");
+    assert_eq!(target.hostnqn.as_deref(), Some("nqn.host:a"));
+
+    let target =
+        NvmeTarget::try_from("nvmf+rdma://1.2.3.4:8420/testnqn?trsvcid=8420")
+            .unwrap();
+    assert_eq!(target.port, 8420);
+    assert_eq!(target.trtype, "rdma");
+}
+
+#[test]
+fn nvme_parse_uri_invalid() {
+    let invalid = |uri: &str| match NvmeTarget::try_from(uri) {
+        Err(NvmeError::InvalidUri {
+            reason, ..
+        }) => reason,
+        Err(e) => panic!("{}: unexpected error {}", uri, e),
+        Ok(t) => panic!("{}: parsed as {}", uri, t),
+    };
+
+    assert!(NvmeTarget::try_from("not a uri").is_err());
+    assert!(invalid("iscsi://1.2.3.4/testnqn").contains("scheme"));
+    assert!(invalid("nvmf:///testnqn").contains("host"));
+    assert!(invalid("nvmf://1.2.3.4").contains("missing nqn"));
+    assert!(invalid("nvmf://1.2.3.4/").contains("missing nqn"));
+    assert!(invalid("nvmf://1.2.3.4/a/b").contains("more than one"));
+    assert!(invalid("nvmf://1.2.3.4/n?transport=fc").contains("transport"));
+    assert!(invalid("nvmf+tcp://1.2.3.4/n?transport=rdma").contains("scheme"));
+    assert!(invalid("nvmf://1.2.3.4/n?port=http").contains("port"));
+    assert!(invalid("nvmf://1.2.3.4/n?port=0").contains("port"));
+    assert!(invalid("nvmf://1.2.3.4:1/n?port=2").contains("conflicts"));
+    assert!(invalid("nvmf://1.2.3.4/n?hostnqn=").contains("hostnqn"));
+    assert!(invalid("nvmf://1.2.3.4/n?port=1&port=1").contains("twice"));
+    assert!(invalid("nvmf://1.2.3.4/n?nqn=n").contains("unknown"));
+}
+
+#[test]
+fn nvme_target_builder() {
+    let target = NvmeTarget::builder()
+        .host("1.2.3.4")
+        .subsysnqn("nqn.2019-05.io.openebs:nexus-1")
+        .build()
+        .unwrap();
+    assert_eq!(target.port(), DEFAULT_PORT);
+    assert_eq!(target.trtype(), "tcp");
+    assert_eq!(target.hostnqn(), None);
+    assert_eq!(
+        target.to_string(),
+        "nvmf://1.2.3.4:4420/nqn.2019-05.io.openebs:nexus-1"
+    );
+
+    assert!(NvmeTarget::builder().host("1.2.3.4").build().is_err());
+    assert!(NvmeTarget::builder().subsysnqn("n").build().is_err());
+    assert!(NvmeTarget::builder().host("").subsysnqn("n").build().is_err());
+    assert!(NvmeTarget::builder()
+        .host("1.2.3.4")
+        .subsysnqn("n")
+        .trtype("fc")
+        .build()
+        .is_err());
+    assert!(NvmeTarget::builder()
+        .host("1.2.3.4")
+        .subsysnqn("n")
+        .port(0)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn nvme_uri_round_trip() {
+    let targets = vec![
+        NvmeTarget::try_from("nvmf://10.1.0.5:8420/nqn.2019-05.io.openebs:x")
+            .unwrap(),
+        NvmeTarget::try_from("nvmf+tcp://10.1.0.5/n?hostnqn=nqn.h%3Aa%26b")
+            .unwrap(),
+        NvmeTarget::builder()
+            .host("10.1.0.5")
+            .port(8420u16)
+            .subsysnqn("nqn.2019-05.io.openebs:y")
+            .trtype("rdma")
+            .hostnqn("nqn.2019-05.io.openebs.mayastor:host".to_string())
+            .build()
+            .unwrap(),
+    ];
+    assert_eq!(targets[1].hostnqn(), Some("nqn.h:a&b"));
+    for target in targets {
+        assert_eq!(NvmeTarget::try_from(target.to_string()).unwrap(), target);
+    }
+}
@@ -478,19 +478,122 @@ pub fn connect(
     port: u16,
     nqn: &str,
 ) -> Result<String, NvmeError> {
+    connect_host(ip_addr, port, nqn, &default_hostnqn())
+}
+
+/// the nqn this host connects as unless told otherwise
+pub(crate) fn default_hostnqn() -> String {
+    format!("nqn.2019-05.io.openebs.mayastor:{}", HOST_ID.as_str())
+}
+
+/// This method connects to a specific NVMf device available over tcp like
+/// `connect`, identifying this host by the given host nqn, which the target
+/// may be restricted to.
+pub fn connect_host(
+    ip_addr: &str,
+    port: u16,
+    nqn: &str,
+    hostnqn: &str,
+) -> Result<String, NvmeError> {
+    connect_with_options(
+        ip_addr,
+        port,
+        nqn,
+        hostnqn,
+        &ConnectOptions::default(),
+    )
+}
+
+/// Options of the controller created when connecting, which are left to the
+/// defaults of the kernel when not set.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConnectOptions {
+    /// seconds without a keep alive before the connection is considered lost
+    pub keep_alive_timeout: Option<u32>,
+    /// seconds between attempts to reconnect a lost connection
+    pub reconnect_delay: Option<u32>,
+    /// seconds to attempt reconnecting before the controller is removed,
+    /// -1 attempts forever
+    pub ctrl_loss_timeout: Option<i32>,
+    /// the number of IO queues to create
+    pub nr_io_queues: Option<u32>,
+}
+
+impl ConnectOptions {
+    /// fails if the options are out of range or contradict each other
+    pub fn validate(&self) -> Result<(), NvmeError> {
+        let invalid = |reason: &str| {
+            Err(NvmeError::InvalidConnectOptions {
+                reason: reason.to_string(),
+            })
+        };
+
+        if self.keep_alive_timeout == Some(0) {
+            return invalid("keep_alive_timeout must be at least 1");
+        }
+        if self.reconnect_delay == Some(0) {
+            return invalid("reconnect_delay must be at least 1");
+        }
+        if self.ctrl_loss_timeout.map_or(false, |t| t < -1) {
+            return invalid("ctrl_loss_timeout must be -1 or more");
+        }
+        if self.nr_io_queues == Some(0) {
+            return invalid("nr_io_queues must be at least 1");
+        }
+        if let (Some(delay), Some(timeout)) =
+            (self.reconnect_delay, self.ctrl_loss_timeout)
+        {
+            if timeout > 0 && delay > timeout as u32 {
+                return invalid(
+                    "reconnect_delay must not exceed ctrl_loss_timeout",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// the connect arguments for the options which are set
+    fn connect_args(&self) -> String {
+        let mut args = String::new();
+        if let Some(timeout) = self.keep_alive_timeout {
+            args.push_str(&format!(",keep_alive_tmo={}", timeout));
+        }
+        if let Some(delay) = self.reconnect_delay {
+            args.push_str(&format!(",reconnect_delay={}", delay));
+        }
+        if let Some(timeout) = self.ctrl_loss_timeout {
+            args.push_str(&format!(",ctrl_loss_tmo={}", timeout));
+        }
+        if let Some(queues) = self.nr_io_queues {
+            args.push_str(&format!(",nr_io_queues={}", queues));
+        }
+        args
+    }
+}
+
+/// This method connects to a specific NVMf device available over tcp like
+/// `connect_host`, creating the controller with the given options, which
+/// are validated before connecting.
+pub fn connect_with_options(
+    ip_addr: &str,
+    port: u16,
+    nqn: &str,
+    hostnqn: &str,
+    options: &ConnectOptions,
+) -> Result<String, NvmeError> {
+    options.validate()?;
+
     let mut connect_args = String::new();
     let host_id = HOST_ID.as_str();
 
     connect_args.push_str(&format!("nqn={},", nqn));
-    connect_args.push_str(&format!(
-        "hostnqn={},",
-        format!("nqn.2019-05.io.openebs.mayastor:{}", host_id)
-    ));
+    connect_args.push_str(&format!("hostnqn={},", hostnqn));
     connect_args.push_str(&format!("hostid={},", host_id));
 
     connect_args.push_str(&format!("transport={},", "tcp"));
     connect_args.push_str(&format!("traddr={},", ip_addr));
     connect_args.push_str(&format!("trsvcid={}", port));
+    connect_args.push_str(&options.connect_args());
     let p = Path::new(NVME_FABRICS_PATH);
 
     let mut file = OpenOptions::new().write(true).read(true).open(&p).context(
@@ -531,3 +634,61 @@ pub fn disconnect(nqn: &str) -> Result<usize, NvmeError> {
         .collect();
     Ok(subsys?.len())
 }
+
+#[test]
+fn connect_options() {
+    let options = ConnectOptions::default();
+    assert!(options.validate().is_ok());
+    assert_eq!(options.connect_args(), "");
+
+    let options = ConnectOptions {
+        keep_alive_timeout: Some(1),
+        reconnect_delay: Some(2),
+        ctrl_loss_timeout: Some(10),
+        nr_io_queues: Some(4),
+    };
+    assert!(options.validate().is_ok());
+    assert_eq!(
+        options.connect_args(),
+        ",keep_alive_tmo=1,reconnect_delay=2,ctrl_loss_tmo=10,nr_io_queues=4"
+    );
+
+    let invalid = |options: ConnectOptions| match options.validate() {
+        Err(NvmeError::InvalidConnectOptions {
+            reason,
+        }) => reason,
+        r => panic!("{:?}: unexpected {:?}", options, r),
+    };
+    assert!(invalid(ConnectOptions {
+        keep_alive_timeout: Some(0),
+        ..Default::default()
+    })
+    .contains("keep_alive_timeout"));
+    assert!(invalid(ConnectOptions {
+        ctrl_loss_timeout: Some(-2),
+        ..Default::default()
+    })
+    .contains("ctrl_loss_timeout"));
+    assert!(invalid(ConnectOptions {
+        nr_io_queues: Some(0),
+        ..Default::default()
+    })
+    .contains("nr_io_queues"));
+    assert!(invalid(ConnectOptions {
+        reconnect_delay: Some(10),
+        ctrl_loss_timeout: Some(5),
+        ..Default::default()
+    })
+    .contains("exceed"));
+
+    // reconnecting forever or never is not bounded by the delay
+    for timeout in &[-1, 0] {
+        assert!(ConnectOptions {
+            reconnect_delay: Some(10),
+            ctrl_loss_timeout: Some(*timeout),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+    }
+}
@@ -2,7 +2,12 @@ use crate::{error, parse_value};
 use error::{FileIoError, InvalidPath, NvmeError, SubSysError};
 use glob::glob;
 use snafu::ResultExt;
-use std::{fs::OpenOptions, io::Write, path::Path, str::FromStr};
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+    str::FromStr,
+};
 
 /// Subsystem struct shows us all the connect fabrics. This does not include
 /// NVMe devices that are connected by trtype=PCIe
@@ -82,17 +87,25 @@ impl Subsystem {
         })?;
         Ok(())
     }
-    /// disconnects the transport dropping all namespaces
+    /// disconnects the transport dropping all namespaces, a controller
+    /// which has gone away already is considered to be disconnected
     pub fn disconnect(&self) -> Result<(), NvmeError> {
         let filename =
             format!("/sys/class/nvme/{}/delete_controller", self.name);
         let path = Path::new(&filename);
 
-        let mut file = OpenOptions::new().write(true).open(&path).context(
-            FileIoError {
-                filename: &filename,
-            },
-        )?;
+        let mut file = match OpenOptions::new().write(true).open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Ok(());
+            }
+            Err(source) => {
+                return Err(NvmeError::FileIoError {
+                    filename,
+                    source,
+                });
+            }
+        };
         file.write_all(b"1").context(FileIoError {
             filename,
         })?;
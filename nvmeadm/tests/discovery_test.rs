@@ -1,4 +1,8 @@
-use nvmeadm::nvmf_discovery::{disconnect, DiscoveryBuilder};
+use nvmeadm::{
+    nvmf_discovery::{disconnect, DiscoveryBuilder},
+    ConnectOptions,
+    NvmeTarget,
+};
 
 use std::{
     fs::File,
@@ -7,7 +11,7 @@ use std::{
     path::Path,
     process::Command,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 static CONFIG_TEXT: &str = "sync_disable: true
@@ -163,7 +167,8 @@ fn disconnect_test() {
 #[test]
 fn test_against_real_target() {
     // Start an SPDK-based nvmf target
-    let _target = NvmfTarget::new(CONFIG_FILE, &TARGET_PORT.to_string());
+    let mut nvmf_target =
+        NvmfTarget::new(CONFIG_FILE, &TARGET_PORT.to_string());
 
     // Perform discovery
     let mut explorer = DiscoveryBuilder::default()
@@ -191,4 +196,56 @@ fn test_against_real_target() {
     // Check that we CAN disconnect from a served NQN
     let num_disconnects = disconnect(SERVED_DISK_NQN);
     assert_eq!(num_disconnects.unwrap(), 1);
+
+    // Check that a multipath connect comes up over the reachable path and
+    // reports the unreachable one
+    let target = NvmeTarget::builder()
+        .host("127.0.0.1")
+        .port(TARGET_PORT as u16)
+        .subsysnqn(SERVED_DISK_NQN)
+        .build()
+        .unwrap();
+    let unreachable: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let connection = target
+        .connect_multipath(&[unreachable])
+        .expect("Problem connecting to valid target");
+    assert_eq!(connection.controllers.len(), 1);
+    assert_eq!(connection.failed.len(), 1);
+    assert_eq!(connection.failed[0].0, "127.0.0.1:9");
+    assert_eq!(connection.device.subsysnqn, SERVED_DISK_NQN);
+    assert_eq!(target.disconnect().unwrap(), 1);
+
+    // and fails when no path comes up
+    assert!(NvmeTarget::builder()
+        .host("127.0.0.1")
+        .port(9u16)
+        .subsysnqn(SERVED_DISK_NQN)
+        .build()
+        .unwrap()
+        .connect_multipath(&[unreachable])
+        .is_err());
+
+    // Check that with a short ctrl_loss_timeout, the device goes away soon
+    // after the target does
+    let options = ConnectOptions {
+        keep_alive_timeout: Some(1),
+        reconnect_delay: Some(1),
+        ctrl_loss_timeout: Some(2),
+        ..Default::default()
+    };
+    let devices = target
+        .connect_with(&options)
+        .expect("Problem connecting to valid target");
+    let device = Path::new(&devices[0].path).to_path_buf();
+    assert!(device.exists());
+    nvmf_target.spdk_proc.kill().expect("Failed to kill SPDK process");
+    let start = Instant::now();
+    while device.exists() {
+        assert!(
+            start.elapsed() < Duration::from_secs(15),
+            "{} still exists",
+            device.display()
+        );
+        thread::sleep(Duration::from_millis(500));
+    }
 }
@@ -7,7 +7,9 @@ pub(crate) fn configure(cfg: &mut paperclip::actix::web::ServiceConfig) {
         .service(get_node_pool)
         .service(put_node_pool)
         .service(del_node_pool)
-        .service(del_pool);
+        .service(del_pool)
+        .service(get_pool_stats)
+        .service(get_node_pool_stats);
 }
 
 #[get("/v0/pools", tags(Pools))]
@@ -37,6 +39,21 @@ async fn get_node_pool(
     )
 }
 
+#[get("/v0/pools/{id}/stats", tags(Pools))]
+async fn get_pool_stats(
+    web::Path(pool_id): web::Path<PoolId>,
+) -> Result<Json<PoolStats>, RestError> {
+    RestRespond::result(MessageBus::get_pool_stats(Filter::Pool(pool_id)).await)
+}
+#[get("/v0/nodes/{node_id}/pools/{pool_id}/stats", tags(Pools))]
+async fn get_node_pool_stats(
+    web::Path((node_id, pool_id)): web::Path<(NodeId, PoolId)>,
+) -> Result<Json<PoolStats>, RestError> {
+    RestRespond::result(
+        MessageBus::get_pool_stats(Filter::NodePool(node_id, pool_id)).await,
+    )
+}
+
 #[put("/v0/nodes/{node_id}/pools/{pool_id}", tags(Pools))]
 async fn put_node_pool(
     web::Path((node_id, pool_id)): web::Path<(NodeId, PoolId)>,
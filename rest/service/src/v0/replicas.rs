@@ -13,7 +13,9 @@ pub(crate) fn configure(cfg: &mut paperclip::actix::web::ServiceConfig) {
         .service(put_node_pool_replica_share)
         .service(put_pool_replica_share)
         .service(del_node_pool_replica_share)
-        .service(del_pool_replica_share);
+        .service(del_pool_replica_share)
+        .service(get_replica_stats)
+        .service(get_node_pool_replica_stats);
 }
 
 #[get("/v0/replicas", tags(Replicas))]
@@ -63,6 +65,33 @@ async fn get_node_pool_replica(
     )
 }
 
+#[get("/v0/replicas/{id}/stats", tags(Replicas))]
+async fn get_replica_stats(
+    web::Path(replica_id): web::Path<ReplicaId>,
+) -> Result<Json<ReplicaStats>, RestError> {
+    RestRespond::result(
+        MessageBus::get_replica_stats(Filter::Replica(replica_id)).await,
+    )
+}
+#[get(
+    "/v0/nodes/{node_id}/pools/{pool_id}/replicas/{replica_id}/stats",
+    tags(Replicas)
+)]
+async fn get_node_pool_replica_stats(
+    web::Path((node_id, pool_id, replica_id)): web::Path<(
+        NodeId,
+        PoolId,
+        ReplicaId,
+    )>,
+) -> Result<Json<ReplicaStats>, RestError> {
+    RestRespond::result(
+        MessageBus::get_replica_stats(Filter::NodePoolReplica(
+            node_id, pool_id, replica_id,
+        ))
+        .await,
+    )
+}
+
 #[put(
     "/v0/nodes/{node_id}/pools/{pool_id}/replicas/{replica_id}",
     tags(Replicas)
@@ -76,6 +76,37 @@ impl ActixRestClient {
             Err(_) => Ok(vec![serde_json::from_slice::<R>(&rest_body)?]),
         }
     }
+    async fn get<R>(&self, urn: String) -> anyhow::Result<R>
+    where
+        for<'de> R: Deserialize<'de>,
+    {
+        let uri = format!("{}{}", self.url, urn);
+
+        let result = if self.trace {
+            self.client.get(uri.clone()).trace_request().send().await
+        } else {
+            self.client.get(uri.clone()).send().await
+        };
+
+        let mut rest_response = result.map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to get uri '{}' from rest, err={:?}",
+                uri,
+                error
+            )
+        })?;
+
+        let status = rest_response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get uri '{}' from rest, status={}",
+                uri,
+                status
+            ));
+        }
+        let rest_body = rest_response.body().await?;
+        Ok(serde_json::from_slice::<R>(&rest_body)?)
+    }
     async fn put<R, B: Into<Body>>(
         &self,
         urn: String,
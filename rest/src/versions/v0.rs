@@ -11,6 +11,7 @@ use async_trait::async_trait;
 use mbus_api::{
     message_bus::{v0, v0::BusError},
     ErrorChain,
+    ReplyErrorKind,
 };
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
@@ -46,6 +47,12 @@ pub type ShareReplica = v0::ShareReplica;
 pub type UnshareReplica = v0::UnshareReplica;
 /// Pool Destroy
 pub type DestroyPool = v0::DestroyPool;
+/// Live capacity and IO stats of a pool
+pub type PoolStats = v0::PoolStats;
+/// Live capacity and IO stats of a replica
+pub type ReplicaStats = v0::ReplicaStats;
+/// IO counters of a pool or replica
+pub type IoStats = v0::IoStats;
 /// Create Replica Body JSON
 #[derive(Serialize, Deserialize, Default, Debug, Clone, Apiv2Schema)]
 pub struct CreateReplicaBody {
@@ -179,6 +186,10 @@ pub type Volumes = v0::Volumes;
 pub type CreateVolume = v0::CreateVolume;
 /// Destroy Volume
 pub type DestroyVolume = v0::DestroyVolume;
+/// Volume replica topology constraint
+pub type Topology = v0::Topology;
+/// Node labels
+pub type NodeLabels = v0::NodeLabels;
 /// Id of a mayastor node
 pub type NodeId = v0::NodeId;
 /// Id of a mayastor pool
@@ -210,6 +221,20 @@ pub struct CreateVolumeBody {
     /// preferred nodes for the nexuses
     #[serde(default)]
     pub preferred_nexus_nodes: Option<Vec<NodeId>>,
+    /// topology constraints for the placement of the replicas
+    #[serde(default)]
+    pub topology: Option<Topology>,
+    /// only nodes with all of these labels can be used for the replicas
+    #[serde(default)]
+    pub node_selector: Option<NodeLabels>,
+    /// replicas must be placed on nodes which also hold replicas of each of
+    /// these volumes
+    #[serde(default)]
+    pub affinity: Option<Vec<VolumeId>>,
+    /// replicas must not be placed on nodes which hold replicas of any of
+    /// these volumes
+    #[serde(default)]
+    pub anti_affinity: Option<Vec<VolumeId>>,
 }
 impl From<CreateVolume> for CreateVolumeBody {
     fn from(create: CreateVolume) -> Self {
@@ -220,6 +245,10 @@ impl From<CreateVolume> for CreateVolumeBody {
             preferred_nodes: create.preferred_nodes.into(),
             allowed_nodes: create.allowed_nodes.into(),
             preferred_nexus_nodes: create.preferred_nexus_nodes.into(),
+            topology: create.topology,
+            node_selector: Some(create.node_selector),
+            affinity: create.affinity.into(),
+            anti_affinity: create.anti_affinity.into(),
         }
     }
 }
@@ -237,6 +266,10 @@ impl CreateVolumeBody {
                 .preferred_nexus_nodes
                 .clone()
                 .unwrap_or_default(),
+            topology: self.topology.clone(),
+            node_selector: self.node_selector.clone().unwrap_or_default(),
+            affinity: self.affinity.clone().unwrap_or_default(),
+            anti_affinity: self.anti_affinity.clone().unwrap_or_default(),
         }
     }
 }
@@ -252,6 +285,8 @@ pub trait RestClient {
     async fn create_pool(&self, args: CreatePool) -> anyhow::Result<Pool>;
     /// Destroy pool with arguments
     async fn destroy_pool(&self, args: DestroyPool) -> anyhow::Result<()>;
+    /// Get the live stats of a pool
+    async fn get_pool_stats(&self, filter: Filter) -> anyhow::Result<PoolStats>;
     /// Get all the known replicas
     async fn get_replicas(
         &self,
@@ -271,6 +306,11 @@ pub trait RestClient {
     /// Unshare replica with arguments
     async fn unshare_replica(&self, args: UnshareReplica)
         -> anyhow::Result<()>;
+    /// Get the live stats of a replica
+    async fn get_replica_stats(
+        &self,
+        filter: Filter,
+    ) -> anyhow::Result<ReplicaStats>;
     /// Get all the known nexuses
     async fn get_nexuses(&self, filter: Filter) -> anyhow::Result<Vec<Nexus>>;
     /// Create new nexus with arguments
@@ -420,6 +460,23 @@ impl RestClient for ActixRestClient {
         Ok(())
     }
 
+    async fn get_pool_stats(
+        &self,
+        filter: Filter,
+    ) -> anyhow::Result<PoolStats> {
+        let urn = match filter {
+            Filter::Pool(id) => format!("/v0/pools/{}/stats", id),
+            Filter::NodePool(n, p) => {
+                format!("/v0/nodes/{}/pools/{}/stats", n, p)
+            }
+            _ => {
+                return Err(anyhow::Error::msg("Invalid filter for pool stats"))
+            }
+        };
+        let stats = self.get(urn).await?;
+        Ok(stats)
+    }
+
     async fn get_replicas(
         &self,
         filter: Filter,
@@ -480,6 +537,26 @@ impl RestClient for ActixRestClient {
         Ok(())
     }
 
+    async fn get_replica_stats(
+        &self,
+        filter: Filter,
+    ) -> anyhow::Result<ReplicaStats> {
+        let urn = match filter {
+            Filter::Replica(id) => format!("/v0/replicas/{}/stats", id),
+            Filter::NodePoolReplica(n, p, r) => format!(
+                "/v0/nodes/{}/pools/{}/replicas/{}/stats",
+                n, p, r
+            ),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "Invalid filter for replica stats",
+                ))
+            }
+        };
+        let stats = self.get(urn).await?;
+        Ok(stats)
+    }
+
     async fn get_nexuses(&self, filter: Filter) -> anyhow::Result<Vec<Nexus>> {
         let nexuses = get_filter!(self, filter, GetNexuses).await?;
         Ok(nexuses)
@@ -623,9 +700,23 @@ impl RestError {
             BusError::MessageBusError {
                 source,
             } => {
-                let error = serde_json::json!({"error": source.as_ref(), "message": source.full_string() });
+                let kind = source.kind();
+                let error = serde_json::json!({"error": source.as_ref(), "kind": kind.as_ref(), "message": source.full_string() });
                 tracing::error!("Got error: {}", error);
-                HttpResponse::InternalServerError().json(error)
+                let mut response = match kind {
+                    ReplyErrorKind::NotFound => HttpResponse::NotFound(),
+                    ReplyErrorKind::AlreadyExists => HttpResponse::Conflict(),
+                    ReplyErrorKind::InvalidArgument => {
+                        HttpResponse::BadRequest()
+                    }
+                    ReplyErrorKind::Unavailable => {
+                        HttpResponse::ServiceUnavailable()
+                    }
+                    ReplyErrorKind::Internal => {
+                        HttpResponse::InternalServerError()
+                    }
+                };
+                response.json(error)
             }
         }
     }
@@ -106,6 +106,7 @@ async fn client_test(mayastor: &NodeId, test: &ComposeTest) {
             id: mayastor.clone(),
             grpc_endpoint: "10.1.0.7:10124".to_string(),
             state: NodeState::Online,
+            labels: Default::default(),
         }
     );
     info!("Nodes: {:#?}", nodes);
@@ -162,6 +163,36 @@ async fn client_test(mayastor: &NodeId, test: &ComposeTest) {
         Some(&replica),
         client.get_replicas(Filter::None).await.unwrap().first()
     );
+
+    let pool_stats = client
+        .get_pool_stats(Filter::NodePool(pool.node.clone(), pool.id.clone()))
+        .await
+        .unwrap();
+    info!("Pool stats: {:#?}", pool_stats);
+    assert_eq!(pool_stats.capacity, pool.capacity);
+    assert_eq!(pool_stats.used, replica.size);
+    assert_eq!(pool_stats.free, pool.capacity - replica.size);
+    let replica_stats = client
+        .get_replica_stats(Filter::Replica(replica.uuid.clone()))
+        .await
+        .unwrap();
+    info!("Replica stats: {:#?}", replica_stats);
+    assert_eq!(replica_stats.capacity, replica.size);
+    assert_eq!(replica_stats.used, Some(replica.size));
+    assert_eq!(replica_stats.free, Some(0));
+    assert_eq!(pool_stats.io, replica_stats.io);
+    // the stats of what does not exist are not found, rather than failing
+    let error = client
+        .get_pool_stats(Filter::Pool("nopool".into()))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("404"), "{}", error);
+    let error = client
+        .get_replica_stats(Filter::Replica("noreplica".into()))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("404"), "{}", error);
+
     client
         .destroy_replica(DestroyReplica {
             node: replica.node.clone(),
@@ -234,6 +265,10 @@ async fn client_test(mayastor: &NodeId, test: &ComposeTest) {
             allowed_nodes: vec![],
             preferred_nodes: vec![],
             preferred_nexus_nodes: vec![],
+            topology: None,
+            node_selector: Default::default(),
+            affinity: vec![],
+            anti_affinity: vec![],
         })
         .await
         .unwrap();
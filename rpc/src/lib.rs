@@ -5,11 +5,15 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 extern crate tonic;
+
+mod pool;
+
 #[allow(dead_code)]
 #[allow(clippy::type_complexity)]
 #[allow(clippy::unit_arg)]
 #[allow(clippy::redundant_closure)]
 pub mod mayastor {
+    pub use crate::pool::CreatePoolRequestBuilder;
 
     impl From<()> for Null {
         fn from(_: ()) -> Self {
@@ -0,0 +1,106 @@
+//! Builder for the request to create a pool, which checks that the request
+//! is sensible before it is sent.
+
+use crate::mayastor::{ChecksumAlgorithm, CreatePoolRequest, PoolWipe};
+
+impl CreatePoolRequest {
+    /// builder for a new request to create the named pool
+    pub fn builder() -> CreatePoolRequestBuilder {
+        CreatePoolRequestBuilder::default()
+    }
+}
+
+/// builder type to create a new CreatePoolRequest
+#[derive(Debug, Default, Clone)]
+pub struct CreatePoolRequestBuilder {
+    name: String,
+    disks: Vec<String>,
+    integrity_reserve: u32,
+    journal: bool,
+    dedup: bool,
+    dedup_hash: ChecksumAlgorithm,
+    wipe: PoolWipe,
+}
+
+impl CreatePoolRequestBuilder {
+    /// set the name of the pool
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// add a disk device path or URI to be claimed by the pool
+    pub fn disk<S: Into<String>>(mut self, disk: S) -> Self {
+        self.disks.push(disk.into());
+        self
+    }
+
+    /// add the disk device paths or URIs to be claimed by the pool
+    pub fn disks<I, S>(mut self, disks: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.disks.extend(disks.into_iter().map(Into::into));
+        self
+    }
+
+    /// set the percent of the pool reserved for integrity metadata
+    pub fn integrity_reserve(mut self, percent: u32) -> Self {
+        self.integrity_reserve = percent;
+        self
+    }
+
+    /// journal the metadata operations of the pool, so that they are
+    /// recovered when a crash interrupts them
+    pub fn journal(mut self, journal: bool) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// deduplicate the blocks of the replicas of the pool
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// set the hash the blocks of the pool are deduplicated by
+    pub fn dedup_hash(mut self, hash: ChecksumAlgorithm) -> Self {
+        self.dedup_hash = hash;
+        self
+    }
+
+    /// set what to clear of the disk before the pool is created on it
+    pub fn wipe(mut self, wipe: PoolWipe) -> Self {
+        self.wipe = wipe;
+        self
+    }
+
+    /// build the request, failing if it has no name or disks
+    pub fn build(self) -> Result<CreatePoolRequest, String> {
+        if self.name.is_empty() {
+            return Err("pool name must not be empty".into());
+        }
+        if self.disks.is_empty() {
+            return Err(format!("pool {} needs at least one disk", self.name));
+        }
+        if self.disks.iter().any(|d| d.is_empty()) {
+            return Err(format!("pool {} has a disk without a path", self.name));
+        }
+        if self.integrity_reserve >= 100 {
+            return Err(format!(
+                "pool {} can not reserve {}% for integrity metadata",
+                self.name, self.integrity_reserve
+            ));
+        }
+        Ok(CreatePoolRequest {
+            name: self.name,
+            disks: self.disks,
+            integrity_reserve: self.integrity_reserve,
+            journal: self.journal,
+            dedup: self.dedup,
+            dedup_hash: self.dedup_hash as i32,
+            wipe: self.wipe as i32,
+        })
+    }
+}
@@ -0,0 +1,89 @@
+use rpc::mayastor::{ChecksumAlgorithm, CreatePoolRequest, PoolWipe};
+
+#[test]
+fn create_pool_request_builder() {
+    let request = CreatePoolRequest::builder()
+        .name("pool")
+        .disk("malloc:///disk0?size_mb=64")
+        .disks(vec!["aio:///tmp/disk1.img", "/dev/sdb"])
+        .integrity_reserve(25)
+        .journal(true)
+        .dedup(true)
+        .dedup_hash(ChecksumAlgorithm::ChecksumSha256)
+        .wipe(PoolWipe::WipeFull)
+        .build()
+        .unwrap();
+    assert_eq!(
+        request,
+        CreatePoolRequest {
+            name: "pool".into(),
+            disks: vec![
+                "malloc:///disk0?size_mb=64".into(),
+                "aio:///tmp/disk1.img".into(),
+                "/dev/sdb".into(),
+            ],
+            integrity_reserve: 25,
+            journal: true,
+            dedup: true,
+            dedup_hash: ChecksumAlgorithm::ChecksumSha256 as i32,
+            wipe: PoolWipe::WipeFull as i32,
+        }
+    );
+
+    // the options default to those of the struct literal
+    let request = CreatePoolRequest::builder()
+        .name("pool")
+        .disk("/dev/sdb")
+        .build()
+        .unwrap();
+    assert_eq!(
+        request,
+        CreatePoolRequest {
+            name: "pool".into(),
+            disks: vec!["/dev/sdb".into()],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn create_pool_request_invalid() {
+    let err = CreatePoolRequest::builder()
+        .disk("/dev/sdb")
+        .build()
+        .unwrap_err();
+    assert!(err.contains("name"));
+
+    let err = CreatePoolRequest::builder()
+        .name("")
+        .disk("/dev/sdb")
+        .build()
+        .unwrap_err();
+    assert!(err.contains("name"));
+
+    let err = CreatePoolRequest::builder().name("pool").build().unwrap_err();
+    assert!(err.contains("at least one disk"));
+
+    let err = CreatePoolRequest::builder()
+        .name("pool")
+        .disks(Vec::<String>::new())
+        .build()
+        .unwrap_err();
+    assert!(err.contains("at least one disk"));
+
+    let err = CreatePoolRequest::builder()
+        .name("pool")
+        .disk("/dev/sdb")
+        .disk("")
+        .build()
+        .unwrap_err();
+    assert!(err.contains("without a path"));
+
+    let err = CreatePoolRequest::builder()
+        .name("pool")
+        .disk("/dev/sdb")
+        .integrity_reserve(100)
+        .build()
+        .unwrap_err();
+    assert!(err.contains("100%"));
+}
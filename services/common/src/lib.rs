@@ -19,6 +19,7 @@ use std::{
     ops::Deref,
 };
 use tracing::{debug, error};
+use tracing_futures::Instrument;
 
 #[derive(Debug, Snafu)]
 #[allow(missing_docs)]
@@ -112,6 +113,7 @@ impl<'a> Context<'a> {
                 );
                 error!("{}", error_msg);
                 Err(Error::ServiceError {
+                    kind: ReplyErrorKind::Internal,
                     message: error_msg,
                 })
             }
@@ -305,7 +307,23 @@ impl Service {
                 id: id.clone(),
             })?;
 
-        let result = subscription.handler(arguments.clone()).await;
+        // carry the correlation id of the request onto the handler's span
+        // and onto any messages the handler itself sends
+        let correlation_id = arguments
+            .request
+            .correlation_id()
+            .unwrap_or_else(|_| new_correlation_id());
+        let span = tracing::info_span!(
+            "handler",
+            id = %id.to_string(),
+            correlation_id = %correlation_id
+        );
+        let result = with_correlation_id(
+            correlation_id,
+            subscription.handler(arguments.clone()),
+        )
+        .instrument(span)
+        .await;
 
         Self::assess_handler_error(&result, &arguments).await;
 
@@ -320,27 +338,14 @@ impl Service {
         arguments: &Arguments<'_>,
     ) {
         if let Err(error) = result.as_ref() {
-            match error {
-                Error::DeserializeSend {
-                    ..
-                } => {
-                    arguments
-                        .request
-                        .respond::<(), _>(Err(ReplyError::DeserializeReq {
-                            message: error.full_string(),
-                        }))
-                        .await
-                }
-                _ => {
-                    arguments
-                        .request
-                        .respond::<(), _>(Err(ReplyError::Process {
-                            message: error.full_string(),
-                        }))
-                        .await
-                }
-            }
-            .ok();
+            arguments
+                .request
+                .respond::<(), _>(Err(ReplyError::new(
+                    error.kind(),
+                    error.full_string(),
+                )))
+                .await
+                .ok();
         }
     }
 
@@ -13,6 +13,7 @@ use dyn_clonable::clonable;
 use mbus_api::{
     message_bus::v0::{BusError, MessageBus, MessageBusTrait},
     v0::*,
+    ReplyErrorKind,
 };
 use rpc::mayastor::{mayastor_client::MayastorClient, Null};
 use snafu::{ResultExt, Snafu};
@@ -63,10 +64,58 @@ pub enum SvcError {
     GrpcShareReplica { source: tonic::Status },
     #[snafu(display("Failed to unshare replica via gRPC"))]
     GrpcUnshareReplica { source: tonic::Status },
+    #[snafu(display("Failed to get replica stats via gRPC"))]
+    GrpcStatReplicas { source: tonic::Status },
     #[snafu(display("Node not found"))]
     BusNodeNotFound { node_id: NodeId },
     #[snafu(display("Pool not found"))]
     BusPoolNotFound { pool_id: String },
+    #[snafu(display("Replica '{}' not found", replica_id))]
+    ReplicaNotFound { replica_id: String },
+    #[snafu(display("Nexus '{}' not found", nexus_id))]
+    NexusNotFound { nexus_id: String },
+    #[snafu(display("Volume '{}' not found", vol_id))]
+    VolumeNotFound { vol_id: String },
+    #[snafu(display(
+        "Timed out waiting for the children of nexus '{}' to rebuild",
+        nexus_id
+    ))]
+    RebuildTimeout { nexus_id: String },
+    #[snafu(display(
+        "Rebuild of child '{}' of nexus '{}' failed",
+        child,
+        nexus_id
+    ))]
+    RebuildFailed { nexus_id: String, child: String },
+    #[snafu(display(
+        "Volume '{}' has no replica on node '{}'",
+        vol_id,
+        node
+    ))]
+    VolumeReplicaNotFound { vol_id: String, node: NodeId },
+    #[snafu(display(
+        "Removing the replicas would leave volume '{}' without a healthy replica",
+        vol_id
+    ))]
+    LastHealthyReplica { vol_id: String },
+    #[snafu(display(
+        "Volume '{}' is already published on node '{}'",
+        vol_id,
+        node
+    ))]
+    VolumeAlreadyPublished { vol_id: String, node: NodeId },
+    #[snafu(display(
+        "Volume '{}' is not published on node '{}'",
+        vol_id,
+        node
+    ))]
+    VolumeNotPublished { vol_id: String, node: NodeId },
+    #[snafu(display(
+        "Affinity rules of volume '{}' cannot be satisfied: {}",
+        vol_id,
+        reason
+    ))]
+    AffinityConflict { vol_id: String, reason: String },
     #[snafu(display("Invalid filter for pools"))]
     InvalidFilter { filter: Filter },
     #[snafu(display("Failed to list nexuses via gRPC"))]
@@ -87,6 +136,137 @@ pub enum SvcError {
     NotImplemented {},
 }
 
+impl SvcError {
+    /// Kind of the error, as it should be returned over the bus
+    pub fn kind(&self) -> ReplyErrorKind {
+        match self {
+            SvcError::BusGetNodes {
+                source,
+            }
+            | SvcError::BusGetNode {
+                source,
+                ..
+            } => match source {
+                BusError::NotFound => ReplyErrorKind::NotFound,
+                BusError::NotUnique => ReplyErrorKind::Internal,
+                BusError::MessageBusError {
+                    source,
+                } => source.kind(),
+            },
+            SvcError::BusGetPools {
+                source,
+            }
+            | SvcError::BusCreatePool {
+                source,
+            }
+            | SvcError::BusDestroyPool {
+                source,
+            }
+            | SvcError::BusGetReplicas {
+                source,
+            } => source.kind(),
+            SvcError::GrpcListPools {
+                source,
+            }
+            | SvcError::GrpcCreatePool {
+                source,
+            }
+            | SvcError::GrpcDestroyPool {
+                source,
+            }
+            | SvcError::GrpcListReplicas {
+                source,
+            }
+            | SvcError::GrpcCreateReplica {
+                source,
+            }
+            | SvcError::GrpcDestroyReplica {
+                source,
+            }
+            | SvcError::GrpcShareReplica {
+                source,
+            }
+            | SvcError::GrpcUnshareReplica {
+                source,
+            }
+            | SvcError::GrpcStatReplicas {
+                source,
+            }
+            | SvcError::GrpcListNexuses {
+                source,
+            }
+            | SvcError::GrpcCreateNexus {
+                source,
+            }
+            | SvcError::GrpcDestroyNexus {
+                source,
+            }
+            | SvcError::GrpcShareNexus {
+                source,
+            }
+            | SvcError::GrpcUnshareNexus {
+                source,
+            } => match source.code() {
+                tonic::Code::NotFound => ReplyErrorKind::NotFound,
+                tonic::Code::AlreadyExists => ReplyErrorKind::AlreadyExists,
+                tonic::Code::InvalidArgument => ReplyErrorKind::InvalidArgument,
+                tonic::Code::Unavailable => ReplyErrorKind::Unavailable,
+                _ => ReplyErrorKind::Internal,
+            },
+            SvcError::BusNodeNotFound {
+                ..
+            }
+            | SvcError::BusPoolNotFound {
+                ..
+            }
+            | SvcError::ReplicaNotFound {
+                ..
+            }
+            | SvcError::NexusNotFound {
+                ..
+            }
+            | SvcError::VolumeNotFound {
+                ..
+            }
+            | SvcError::VolumeReplicaNotFound {
+                ..
+            } => ReplyErrorKind::NotFound,
+            SvcError::VolumeAlreadyPublished {
+                ..
+            } => ReplyErrorKind::AlreadyExists,
+            SvcError::VolumeNotPublished {
+                ..
+            }
+            | SvcError::LastHealthyReplica {
+                ..
+            }
+            | SvcError::AffinityConflict {
+                ..
+            }
+            | SvcError::InvalidFilter {
+                ..
+            }
+            | SvcError::InvalidArguments {} => ReplyErrorKind::InvalidArgument,
+            SvcError::NodeNotOnline {
+                ..
+            }
+            | SvcError::GrpcConnect {
+                ..
+            }
+            | SvcError::RebuildTimeout {
+                ..
+            }
+            | SvcError::NotEnoughResources {
+                ..
+            } => ReplyErrorKind::Unavailable,
+            SvcError::RebuildFailed {
+                ..
+            }
+            | SvcError::NotImplemented {} => ReplyErrorKind::Internal,
+        }
+    }
+}
+
 impl From<NotEnough> for SvcError {
     fn from(source: NotEnough) -> Self {
         Self::NotEnoughResources {
@@ -109,6 +289,13 @@ pub enum NotEnough {
     OfReplicas { have: u64, need: u64 },
     #[snafu(display("Not enough nexuses available, {}/{}", have, need))]
     OfNexuses { have: u64, need: u64 },
+    #[snafu(display(
+        "Not enough distinct '{}' topology domains available, {}/{}",
+        key,
+        have,
+        need
+    ))]
+    OfTopologyDomains { key: String, have: u64, need: u64 },
 }
 
 /// Implement default fake NodeNexusChildTrait for a type
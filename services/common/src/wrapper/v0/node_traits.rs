@@ -29,6 +29,11 @@ pub trait NodeReplicaTrait: Send + Sync + Debug + Clone {
     /// Fetch replicas on all pools via gRPC or MBUS
     async fn fetch_replicas(&self) -> Result<Vec<Replica>, SvcError>;
 
+    /// Fetch the stats of the replicas on all pools via gRPC or MBUS
+    async fn fetch_replica_stats(&self) -> Result<Vec<ReplicaStats>, SvcError> {
+        Err(SvcError::NotImplemented {})
+    }
+
     /// Create a replica on a pool via gRPC or MBUS
     async fn create_replica(
         &self,
@@ -83,6 +83,23 @@ impl NodeReplicaTrait for NodeWrapperPool {
         Ok(pools)
     }
 
+    /// Fetch the stats of all replicas from this node via gRPC
+    async fn fetch_replica_stats(&self) -> Result<Vec<ReplicaStats>, SvcError> {
+        let mut ctx = self.grpc_client().await?;
+        let rpc_stats = ctx
+            .client
+            .stat_replicas(Null {})
+            .await
+            .context(GrpcStatReplicas {})?;
+        let stats = rpc_stats
+            .get_ref()
+            .replicas
+            .iter()
+            .map(|s| rpc_replica_stats_to_bus(s, self.node.id.clone()))
+            .collect();
+        Ok(stats)
+    }
+
     /// Create a replica on the pool via gRPC
     async fn create_replica(
         &self,
@@ -309,6 +326,35 @@ fn rpc_replica_to_bus(
     }
 }
 
+/// convert rpc replica stats to message bus replica stats
+fn rpc_replica_stats_to_bus(
+    rpc_stats: &rpc::mayastor::ReplicaStats,
+    id: NodeId,
+) -> ReplicaStats {
+    let rpc_stats = rpc_stats.clone();
+    // the allocated bytes are not known for thin provisioned replicas
+    let used = if rpc_stats.allocated > 0 || rpc_stats.size == 0 {
+        Some(rpc_stats.allocated)
+    } else {
+        None
+    };
+    let io = rpc_stats.stats.unwrap_or_default();
+    ReplicaStats {
+        node: id,
+        uuid: rpc_stats.uuid.into(),
+        pool: rpc_stats.pool.into(),
+        capacity: rpc_stats.size,
+        used,
+        free: used.map(|used| rpc_stats.size.saturating_sub(used)),
+        io: IoStats {
+            num_read_ops: io.num_read_ops,
+            num_write_ops: io.num_write_ops,
+            bytes_read: io.bytes_read,
+            bytes_written: io.bytes_written,
+        },
+    }
+}
+
 /// convert a message bus replica to an rpc replica
 fn bus_replica_to_rpc(
     request: &CreateReplica,
@@ -320,6 +366,11 @@ fn bus_replica_to_rpc(
         thin: request.thin,
         size: request.size,
         share: request.share as i32,
+        protection: false,
+        checksum: false,
+        checksum_algorithm: 0,
+        compression: false,
+        compression_algorithm: 0,
     }
 }
 
@@ -351,6 +402,7 @@ fn bus_pool_to_rpc(request: &CreatePool) -> rpc::mayastor::CreatePoolRequest {
     rpc::mayastor::CreatePoolRequest {
         name: request.id.into(),
         disks: request.disks,
+        ..Default::default()
     }
 }
 
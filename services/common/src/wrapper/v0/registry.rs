@@ -145,6 +145,54 @@ impl<T: NodeWrapperTrait + Default + 'static + Clone> Registry<T> {
         Ok(pool)
     }
 
+    /// Fetch the live stats of the pool from its node
+    pub async fn fetch_pool_stats(
+        &self,
+        node: &NodeId,
+        pool: &PoolId,
+    ) -> Result<PoolStats, SvcError> {
+        let node = self.get_node(node).await?;
+        let found = node
+            .fetch_pools()
+            .await?
+            .into_iter()
+            .find(|p| &p.id == pool)
+            .ok_or_else(|| SvcError::BusPoolNotFound {
+                pool_id: pool.to_string(),
+            })?;
+        let mut io = IoStats::default();
+        for replica in node.fetch_replica_stats().await? {
+            if &replica.pool == pool {
+                io += replica.io;
+            }
+        }
+        Ok(PoolStats {
+            node: found.node,
+            id: found.id,
+            capacity: found.capacity,
+            used: found.used,
+            free: found.capacity.saturating_sub(found.used),
+            io,
+        })
+    }
+
+    /// Fetch the live stats of the replica from its node
+    pub async fn fetch_replica_stats(
+        &self,
+        node: &NodeId,
+        replica: &ReplicaId,
+    ) -> Result<ReplicaStats, SvcError> {
+        self.get_node(node)
+            .await?
+            .fetch_replica_stats()
+            .await?
+            .into_iter()
+            .find(|r| &r.uuid == replica)
+            .ok_or_else(|| SvcError::ReplicaNotFound {
+                replica_id: replica.to_string(),
+            })
+    }
+
     /// Get current list of known nodes
     async fn get_known_nodes(&self, node_id: &NodeId) -> Option<NodeWrapper> {
         let nodes = self.nodes.lock().await;
@@ -360,6 +408,26 @@ impl<T: NodeWrapperTrait + Default + 'static + Clone> Registry<T> {
         }
     }
 
+    /// Fetch the latest state of nexus `nexus` from node `node` and update
+    /// the registry with it
+    pub async fn fetch_nexus(
+        &self,
+        node: &NodeId,
+        nexus: &NexusId,
+    ) -> Result<Nexus, SvcError> {
+        let node = self.get_node(node).await?;
+        let nexuses = node.fetch_nexuses().await?;
+        match nexuses.into_iter().find(|n| &n.uuid == nexus) {
+            Some(nexus) => {
+                self.on_create_nexus(&nexus).await;
+                Ok(nexus)
+            }
+            None => Err(SvcError::NexusNotFound {
+                nexus_id: nexus.to_string(),
+            }),
+        }
+    }
+
     /// Create nexus
     pub async fn create_nexus(
         &self,
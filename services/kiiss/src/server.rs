@@ -85,17 +85,12 @@ impl ServiceSubscriber for ServiceHandler<ConfigGetCurrent> {
                     .await
                 }
                 None => {
-                    msg.reply(Err(ReplyError::WithMessage {
-                        message: "Config is missing".into(),
-                    }))
-                    .await
+                    msg.reply(Err(ReplyError::not_found("Config is missing")))
+                        .await
                 }
             },
             None => {
-                msg.reply(Err(ReplyError::WithMessage {
-                    message: "Config is missing".into(),
-                }))
-                .await
+                msg.reply(Err(ReplyError::not_found("Config is missing"))).await
             }
         }
     }
@@ -77,6 +77,9 @@ struct NodeStore {
 }
 struct NodeStoreInner {
     state: Mutex<HashMap<NodeId, (Node, Watchdog)>>,
+    /// labels are kept apart from the nodes so they persist across
+    /// deregistrations and reconnects
+    labels: Mutex<HashMap<NodeId, NodeLabels>>,
     deadline: std::time::Duration,
 }
 impl Default for NodeStoreInner {
@@ -84,6 +87,7 @@ impl Default for NodeStoreInner {
         Self {
             deadline: CliArgs::from_args().deadline.into(),
             state: Default::default(),
+            labels: Default::default(),
         }
     }
 }
@@ -106,10 +110,12 @@ impl NodeStore {
         });
 
         let id = registration.id.clone();
+        let labels = self.labels(&id).await;
         let node = Node {
             id: registration.id,
             grpc_endpoint: registration.grpc_endpoint,
             state: NodeState::Online,
+            labels,
         };
         state.insert(id, (node, watchdog));
     }
@@ -125,6 +131,20 @@ impl NodeStore {
             n.0.state = NodeState::Offline;
         }
     }
+    /// Set the labels of a node, which need not be registered yet
+    async fn set_labels(&self, request: SetNodeLabels) {
+        let mut state = self.inner.state.lock().await;
+        if let Some((node, _)) = state.get_mut(&request.node) {
+            node.labels = request.labels.clone();
+        }
+        let mut labels = self.inner.labels.lock().await;
+        labels.insert(request.node, request.labels);
+    }
+    /// Get the labels of a node
+    async fn labels(&self, id: &NodeId) -> NodeLabels {
+        let labels = self.inner.labels.lock().await;
+        labels.get(id).cloned().unwrap_or_default()
+    }
     /// Get the list of nodes which we know of
     async fn get_nodes(&self) -> Vec<Node> {
         let nodes = self.inner.state.lock().await;
@@ -176,6 +196,21 @@ impl ServiceSubscriber for ServiceHandler<GetNodes> {
     }
 }
 
+#[async_trait]
+impl ServiceSubscriber for ServiceHandler<SetNodeLabels> {
+    async fn handler(&self, args: Arguments<'_>) -> Result<(), Error> {
+        let request: ReceivedMessage<SetNodeLabels> =
+            args.request.try_into()?;
+
+        let store: &NodeStore = args.context.get_state()?;
+        store.set_labels(request.inner()).await;
+        request.reply(()).await
+    }
+    fn filter(&self) -> Vec<MessageId> {
+        vec![SetNodeLabels::default().id()]
+    }
+}
+
 fn init_tracing() {
     if let Ok(filter) = tracing_subscriber::EnvFilter::try_from_default_env() {
         tracing_subscriber::fmt().with_env_filter(filter).init();
@@ -202,6 +237,7 @@ async fn server(cli_args: CliArgs) {
         .with_channel(ChannelVs::Node)
         .with_default_liveness()
         .with_subscription(ServiceHandler::<GetNodes>::default())
+        .with_subscription(ServiceHandler::<SetNodeLabels>::default())
         .run()
         .await;
 }
@@ -286,6 +322,7 @@ mod tests {
                 id: maya_name.clone(),
                 grpc_endpoint: "0.0.0.0:10124".to_string(),
                 state: NodeState::Online,
+                labels: Default::default(),
             }
         );
         tokio::time::delay_for(std::time::Duration::from_secs(2)).await;
@@ -298,7 +335,41 @@ mod tests {
                 id: maya_name.clone(),
                 grpc_endpoint: "0.0.0.0:10124".to_string(),
                 state: NodeState::Offline,
+                labels: Default::default(),
             }
         );
+
+        let labels: NodeLabels = vec![("zone".to_string(), "a".to_string())]
+            .into_iter()
+            .collect();
+        SetNodeLabels {
+            node: maya_name.clone(),
+            labels: labels.clone(),
+        }
+        .request()
+        .await
+        .unwrap();
+        let nodes = GetNodes {}.request().await.unwrap();
+        assert_eq!(nodes.0.first().unwrap().labels, labels);
+
+        // labels persist across reconnects
+        Deregister {
+            id: maya_name.clone(),
+        }
+        .publish()
+        .await
+        .unwrap();
+        Register {
+            id: maya_name.clone(),
+            grpc_endpoint: "0.0.0.0:10124".to_string(),
+        }
+        .publish()
+        .await
+        .unwrap();
+        tokio::time::delay_for(std::time::Duration::from_millis(250)).await;
+        let nodes = GetNodes {}.request().await.unwrap();
+        tracing::info!("Nodes: {:?}", nodes);
+        assert_eq!(nodes.0.len(), 1);
+        assert_eq!(nodes.0.first().unwrap().labels, labels);
     }
 }
@@ -45,6 +45,7 @@ macro_rules! impl_service_handler {
                     .$ServiceFnName(&request.inner())
                     .await
                     .map_err(|error| Error::ServiceError {
+                        kind: error.kind(),
                         message: error.full_string(),
                     })?;
                 request.reply(reply).await
@@ -68,6 +69,9 @@ impl_service_handler!(CreateReplica, create_replica);
 impl_service_handler!(DestroyReplica, destroy_replica);
 impl_service_handler!(ShareReplica, share_replica);
 impl_service_handler!(UnshareReplica, unshare_replica);
+impl_service_handler!(SetPoolThreshold, set_pool_threshold);
+impl_service_handler!(GetPoolStats, get_pool_stats);
+impl_service_handler!(GetReplicaStats, get_replica_stats);
 
 fn init_tracing() {
     if let Ok(filter) = tracing_subscriber::EnvFilter::try_from_default_env() {
@@ -101,6 +105,9 @@ async fn server(cli_args: CliArgs) {
         .with_subscription(ServiceHandler::<DestroyReplica>::default())
         .with_subscription(ServiceHandler::<ShareReplica>::default())
         .with_subscription(ServiceHandler::<UnshareReplica>::default())
+        .with_subscription(ServiceHandler::<SetPoolThreshold>::default())
+        .with_subscription(ServiceHandler::<GetPoolStats>::default())
+        .with_subscription(ServiceHandler::<GetReplicaStats>::default())
         .run()
         .await;
 }
@@ -140,7 +147,12 @@ mod tests {
                 Binary::from_nix("nats-server").with_arg("-DV"),
             )
             .add_container_bin("node", Binary::from_dbg("node").with_nats("-n"))
-            .add_container_bin("pool", Binary::from_dbg("pool").with_nats("-n"))
+            .add_container_bin(
+                "pool",
+                Binary::from_dbg("pool")
+                    .with_nats("-n")
+                    .with_args(vec!["-p", "1s"]),
+            )
             .add_container_bin(
                 "mayastor",
                 Binary::from_dbg("mayastor")
@@ -228,6 +240,8 @@ mod tests {
 
         assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
 
+        pool_threshold(mayastor).await;
+
         DestroyPool {
             node: mayastor.into(),
             id: "pooloop".into(),
@@ -238,4 +252,73 @@ mod tests {
 
         assert!(GetPools::default().request().await.unwrap().0.is_empty());
     }
+
+    /// Wait for the next pool capacity event, if any
+    async fn next_event(
+        events: &mut BusSubscription,
+        timeout: std::time::Duration,
+    ) -> Option<PoolCapacityEvent> {
+        let message = tokio::time::timeout(timeout, events.next())
+            .await
+            .ok()??;
+        let message = ReceivedRawMessage::from(&message);
+        Some(message.inner::<PoolCapacityEvent>().unwrap())
+    }
+
+    async fn pool_threshold(mayastor: &str) {
+        let timeout = std::time::Duration::from_secs(5);
+        let quiet = std::time::Duration::from_secs(3);
+        let mut events =
+            bus().subscribe(ChannelVs::Event.into()).await.unwrap();
+
+        SetPoolThreshold {
+            node: mayastor.into(),
+            id: "pooloop".into(),
+            threshold: Some(50),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        // thick provisioned, so the pool usage goes above the threshold
+        CreateReplica {
+            node: mayastor.into(),
+            uuid: "replica2".into(),
+            pool: "pooloop".into(),
+            size: 67108864,
+            thin: false,
+            share: Protocol::Off,
+        }
+        .request()
+        .await
+        .unwrap();
+
+        let event = next_event(&mut events, timeout).await.unwrap();
+        assert_eq!(event.kind, PoolCapacityEventKind::Alert);
+        assert_eq!(event.threshold, 50);
+        // only one alert while the threshold remains exceeded
+        assert_eq!(next_event(&mut events, quiet).await, None);
+
+        DestroyReplica {
+            node: mayastor.into(),
+            uuid: "replica2".into(),
+            pool: "pooloop".into(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        let event = next_event(&mut events, timeout).await.unwrap();
+        assert_eq!(event.kind, PoolCapacityEventKind::Clear);
+        assert_eq!(next_event(&mut events, quiet).await, None);
+
+        SetPoolThreshold {
+            node: mayastor.into(),
+            id: "pooloop".into(),
+            threshold: None,
+        }
+        .request()
+        .await
+        .unwrap();
+    }
 }
@@ -3,11 +3,24 @@
 
 use super::*;
 use common::wrapper::v0::*;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+
+/// High watermark threshold of the used capacity of a pool
+#[derive(Clone, Debug)]
+struct PoolWatermark {
+    /// threshold as a percentage of the pool capacity
+    threshold: u8,
+    /// whether the threshold is currently exceeded (alert raised)
+    alerted: bool,
+}
 
 /// Pool service implementation methods
 #[derive(Clone, Debug, Default)]
 pub(super) struct PoolSvc {
     registry: Registry<NodeWrapperPool>,
+    watermarks: Arc<Mutex<HashMap<(NodeId, PoolId), PoolWatermark>>>,
+    period: std::time::Duration,
 }
 
 impl PoolSvc {
@@ -15,13 +28,70 @@ impl PoolSvc {
     pub fn new(period: std::time::Duration) -> Self {
         let obj = Self {
             registry: Registry::new(period),
+            period,
+            ..Default::default()
         };
         obj.start();
         obj
     }
-    /// Start registry poller
+    /// Start registry poller and the pool watermark poller
     fn start(&self) {
         self.registry.start();
+        let svc = self.clone();
+        tokio::spawn(async move {
+            svc.watermark_poller().await;
+        });
+    }
+
+    /// Periodically check the pools against their watermark threshold
+    async fn watermark_poller(&self) {
+        loop {
+            self.check_watermarks().await;
+            tokio::time::delay_for(self.period).await;
+        }
+    }
+
+    /// Check the cached pools against their watermark threshold and publish
+    /// an event whenever the threshold is crossed, in either direction
+    async fn check_watermarks(&self) {
+        let pools = self.registry.list_pools().await;
+        let mut events = vec![];
+        {
+            let mut watermarks = self.watermarks.lock().await;
+            for pool in pools {
+                let key = (pool.node.clone(), pool.id.clone());
+                if let Some(watermark) = watermarks.get_mut(&key) {
+                    let exceeded = pool.capacity > 0
+                        && pool.used * 100
+                            >= pool.capacity * watermark.threshold as u64;
+                    if exceeded == watermark.alerted {
+                        continue;
+                    }
+                    watermark.alerted = exceeded;
+                    events.push(PoolCapacityEvent {
+                        node: pool.node,
+                        id: pool.id,
+                        capacity: pool.capacity,
+                        used: pool.used,
+                        threshold: watermark.threshold,
+                        kind: if exceeded {
+                            PoolCapacityEventKind::Alert
+                        } else {
+                            PoolCapacityEventKind::Clear
+                        },
+                    });
+                }
+            }
+        }
+        for event in events {
+            tracing::warn!("Pool capacity event: {:?}", event);
+            if let Err(error) = event.publish().await {
+                tracing::error!(
+                    "Failed to publish pool capacity event: {}",
+                    error.full_string()
+                );
+            }
+        }
     }
 
     /// Get all pools from node or from all nodes
@@ -140,6 +210,85 @@ impl PoolSvc {
         }))
     }
 
+    /// Get the live stats of the pool matching the filter, the node of the
+    /// pool is looked up in the registry when not given
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn get_pool_stats(
+        &self,
+        request: &GetPoolStats,
+    ) -> Result<PoolStats, SvcError> {
+        let filter = request.filter.clone();
+        let (node_id, pool_id) = match filter {
+            Filter::NodePool(node_id, pool_id) => (node_id, pool_id),
+            Filter::Pool(pool_id) => {
+                let pools = self.get_node_pools(None).await?;
+                match pools.into_iter().find(|p| p.id == pool_id) {
+                    Some(pool) => (pool.node, pool_id),
+                    None => {
+                        return Err(SvcError::BusPoolNotFound {
+                            pool_id: pool_id.to_string(),
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Err(SvcError::InvalidFilter {
+                    filter,
+                })
+            }
+        };
+        self.registry.fetch_pool_stats(&node_id, &pool_id).await
+    }
+
+    /// Get the live stats of the replica matching the filter, the node of
+    /// the replica is looked up in the registry when not given
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn get_replica_stats(
+        &self,
+        request: &GetReplicaStats,
+    ) -> Result<ReplicaStats, SvcError> {
+        let filter = request.filter.clone();
+        let (node_id, replica_id) = match filter {
+            Filter::NodeReplica(node_id, replica_id)
+            | Filter::NodePoolReplica(node_id, _, replica_id) => {
+                (node_id, replica_id)
+            }
+            Filter::Replica(replica_id)
+            | Filter::PoolReplica(_, replica_id) => {
+                let replicas = self.get_node_replicas(None).await?;
+                match replicas.into_iter().find(|r| r.uuid == replica_id) {
+                    Some(replica) => (replica.node, replica_id),
+                    None => {
+                        return Err(SvcError::ReplicaNotFound {
+                            replica_id: replica_id.to_string(),
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Err(SvcError::InvalidFilter {
+                    filter,
+                })
+            }
+        };
+        let stats = self
+            .registry
+            .fetch_replica_stats(&node_id, &replica_id)
+            .await?;
+        // the replica must also be on the pool, when it is given
+        let pool_id = match &request.filter {
+            Filter::NodePoolReplica(_, pool_id, _)
+            | Filter::PoolReplica(pool_id, _) => Some(pool_id),
+            _ => None,
+        };
+        if pool_id.map_or(false, |pool_id| pool_id != &stats.pool) {
+            return Err(SvcError::ReplicaNotFound {
+                replica_id: replica_id.to_string(),
+            });
+        }
+        Ok(stats)
+    }
+
     /// Create replica
     #[tracing::instrument(level = "debug", err)]
     pub(super) async fn create_replica(
@@ -193,4 +342,39 @@ impl PoolSvc {
     ) -> Result<(), SvcError> {
         self.registry.destroy_pool(request).await
     }
+
+    /// Set the pool capacity threshold
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn set_pool_threshold(
+        &self,
+        request: &SetPoolThreshold,
+    ) -> Result<(), SvcError> {
+        let key = (request.node.clone(), request.id.clone());
+        {
+            let mut watermarks = self.watermarks.lock().await;
+            match request.threshold {
+                None => {
+                    watermarks.remove(&key);
+                }
+                Some(threshold) if threshold > 0 && threshold <= 100 => {
+                    // an existing alert stays raised, it's cleared only when
+                    // the used capacity drops below the new threshold
+                    let alerted = watermarks
+                        .get(&key)
+                        .map(|w| w.alerted)
+                        .unwrap_or_default();
+                    watermarks.insert(
+                        key,
+                        PoolWatermark {
+                            threshold,
+                            alerted,
+                        },
+                    );
+                }
+                Some(_) => return Err(SvcError::InvalidArguments {}),
+            }
+        }
+        self.check_watermarks().await;
+        Ok(())
+    }
 }
@@ -45,6 +45,7 @@ macro_rules! impl_service_handler {
                     .$ServiceFnName(&request.inner())
                     .await
                     .map_err(|error| Error::ServiceError {
+                        kind: error.kind(),
                         message: error.full_string(),
                     })?;
                 request.reply(reply).await
@@ -71,6 +72,11 @@ impl_service_handler!(RemoveNexusChild, remove_nexus_child);
 impl_service_handler!(GetVolumes, get_volumes);
 impl_service_handler!(CreateVolume, create_volume);
 impl_service_handler!(DestroyVolume, destroy_volume);
+impl_service_handler!(SetReplicaCount, set_replica_count);
+impl_service_handler!(PublishVolume, publish_volume);
+impl_service_handler!(UnpublishVolume, unpublish_volume);
+impl_service_handler!(MoveReplica, move_replica);
+impl_service_handler!(FailoverVolume, failover_volume);
 
 fn init_tracing() {
     if let Ok(filter) = tracing_subscriber::EnvFilter::try_from_default_env() {
@@ -99,6 +105,11 @@ async fn server(cli_args: CliArgs) {
         .with_subscription(ServiceHandler::<GetVolumes>::default())
         .with_subscription(ServiceHandler::<CreateVolume>::default())
         .with_subscription(ServiceHandler::<DestroyVolume>::default())
+        .with_subscription(ServiceHandler::<SetReplicaCount>::default())
+        .with_subscription(ServiceHandler::<PublishVolume>::default())
+        .with_subscription(ServiceHandler::<UnpublishVolume>::default())
+        .with_subscription(ServiceHandler::<MoveReplica>::default())
+        .with_subscription(ServiceHandler::<FailoverVolume>::default())
         .with_channel(ChannelVs::Nexus)
         .with_subscription(ServiceHandler::<GetNexuses>::default())
         .with_subscription(ServiceHandler::<CreateNexus>::default())
@@ -133,17 +144,21 @@ mod tests {
 
         test.start("mayastor").await.unwrap();
         test.start("mayastor2").await.unwrap();
+        test.start("mayastor3").await.unwrap();
 
         let mut hdl = test.grpc_handle("mayastor").await.unwrap();
         hdl.mayastor.list_nexus(Null {}).await.unwrap();
         let mut hdl = test.grpc_handle("mayastor2").await.unwrap();
         hdl.mayastor.list_nexus(Null {}).await.unwrap();
+        let mut hdl = test.grpc_handle("mayastor3").await.unwrap();
+        hdl.mayastor.list_nexus(Null {}).await.unwrap();
     }
 
     #[tokio::test]
     async fn volume() {
         let mayastor = "volume-test-name";
         let mayastor2 = "volume-test-name-replica";
+        let mayastor3 = "volume-test-name-replica2";
         let test = Builder::new()
             .name("volume")
             .add_container_bin("nats", Binary::from_nix("nats-server"))
@@ -167,6 +182,13 @@ mod tests {
                     .with_args(vec!["-N", mayastor2])
                     .with_args(vec!["-g", "10.1.0.7:10124"]),
             )
+            .add_container_bin(
+                "mayastor3",
+                Binary::from_dbg("mayastor")
+                    .with_nats("-n")
+                    .with_args(vec!["-N", mayastor3])
+                    .with_args(vec!["-g", "10.1.0.8:10124"]),
+            )
             .with_default_tracing()
             .autorun(false)
             .build()
@@ -180,6 +202,13 @@ mod tests {
         prepare_pools(mayastor, mayastor2).await;
         test_nexus(mayastor, mayastor2).await;
         test_volume().await;
+        test_volume_replica_count().await;
+        test_volume_publish(mayastor, mayastor2).await;
+        test_volume_failover(mayastor, mayastor2).await;
+        test_volume_topology(mayastor, mayastor2, mayastor3).await;
+        test_volume_node_selector(mayastor2).await;
+        test_volume_affinity().await;
+        test_volume_move_replica(mayastor, mayastor2, mayastor3).await;
 
         assert!(GetNexuses::default().request().await.unwrap().0.is_empty());
     }
@@ -276,6 +305,10 @@ mod tests {
             allowed_nodes: vec![],
             preferred_nodes: vec![],
             preferred_nexus_nodes: vec![],
+            topology: None,
+            node_selector: Default::default(),
+            affinity: vec![],
+            anti_affinity: vec![],
         };
 
         let volume = volume.request().await.unwrap();
@@ -295,4 +328,528 @@ mod tests {
         assert!(GetNexuses::default().request().await.unwrap().0.is_empty());
         assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
     }
+
+    async fn test_volume_replica_count() {
+        let uuid = "1e3cf927-80c2-47a8-adf0-95c486bdd7b7";
+        let volume = CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        assert_eq!(volume.children.first().unwrap().children.len(), 1);
+
+        let volume = SetReplicaCount {
+            uuid: uuid.into(),
+            replicas: 2,
+        }
+        .request()
+        .await
+        .unwrap();
+        let nexus = volume.children.first().unwrap();
+        tracing::info!("Scaled up volume: {:?}", volume);
+        assert_eq!(nexus.children.len(), 2);
+        assert!(nexus.children.iter().all(|c| c.state == ChildState::Online));
+        assert_eq!(GetReplicas::default().request().await.unwrap().0.len(), 2);
+
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        assert!(GetVolumes::default().request().await.unwrap().0.is_empty());
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_failover(mayastor: &str, mayastor2: &str) {
+        let uuid = "4b1e5a0c-9b1a-4a8e-8f5e-2d6c3f0b7a11";
+        let volume = CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 2,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let node = volume.children.first().unwrap().node.clone();
+        let other: NodeId = if node.as_str() == mayastor {
+            mayastor2.into()
+        } else {
+            mayastor.into()
+        };
+
+        // only a node the volume is published on can fail it over
+        FailoverVolume {
+            uuid: uuid.into(),
+            node: other.clone(),
+        }
+        .request()
+        .await
+        .expect_err("Not published on the other node");
+
+        let uri = PublishVolume {
+            uuid: uuid.into(),
+            node: node.clone(),
+            force: false,
+        }
+        .request()
+        .await
+        .unwrap();
+
+        // the volume is published on the other node as well, under the same
+        // NQN so that multipath initiators see a second path
+        let failover = FailoverVolume {
+            uuid: uuid.into(),
+            node: node.clone(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert_ne!(uri, failover);
+        let nqn = |uri: &str| uri.rsplit('/').next().unwrap().to_string();
+        assert_eq!(nqn(&uri), nqn(&failover));
+        let nexuses = GetNexuses::default().request().await.unwrap().0;
+        assert_eq!(nexuses.len(), 2);
+        assert!(nexuses.iter().any(|n| n.node == other));
+
+        // failing over again is idempotent
+        let failover2 = FailoverVolume {
+            uuid: uuid.into(),
+            node: node.clone(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert_eq!(failover, failover2);
+
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(GetNexuses::default().request().await.unwrap().0.is_empty());
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_publish(mayastor: &str, mayastor2: &str) {
+        let uuid = "c6ea1b29-9f58-4ba6-ae5a-1a8cd2c6ab4b";
+        let volume = CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 2,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let node = volume.children.first().unwrap().node.clone();
+        let other: NodeId = if node.as_str() == mayastor {
+            mayastor2.into()
+        } else {
+            mayastor.into()
+        };
+
+        let uri = PublishVolume {
+            uuid: uuid.into(),
+            node: node.clone(),
+            force: false,
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(uri.starts_with("nvmf://"));
+
+        // publishing on the same node is idempotent
+        let uri2 = PublishVolume {
+            uuid: uuid.into(),
+            node: node.clone(),
+            force: false,
+        }
+        .request()
+        .await
+        .unwrap();
+        assert_eq!(uri, uri2);
+
+        // but not on another node, unless forced
+        PublishVolume {
+            uuid: uuid.into(),
+            node: other.clone(),
+            force: false,
+        }
+        .request()
+        .await
+        .expect_err("Already published on another node");
+
+        UnpublishVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(GetNexuses::default().request().await.unwrap().0.is_empty());
+        assert_eq!(GetReplicas::default().request().await.unwrap().0.len(), 2);
+
+        let uri = PublishVolume {
+            uuid: uuid.into(),
+            node: other.clone(),
+            force: false,
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(uri.starts_with("nvmf://"));
+        let nexuses = GetNexuses::default().request().await.unwrap().0;
+        assert_eq!(nexuses.first().unwrap().node, other);
+
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        assert!(GetNexuses::default().request().await.unwrap().0.is_empty());
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_topology(
+        mayastor: &str,
+        mayastor2: &str,
+        mayastor3: &str,
+    ) {
+        CreatePool {
+            node: mayastor3.into(),
+            id: "pooloop".into(),
+            disks: vec!["malloc:///disk0?size_mb=100".into()],
+        }
+        .request()
+        .await
+        .unwrap();
+
+        // two nodes in zone a and one node in zone b
+        for (node, zone) in
+            &[(mayastor, "a"), (mayastor2, "a"), (mayastor3, "b")]
+        {
+            SetNodeLabels {
+                node: (*node).into(),
+                labels: vec![("zone".to_string(), zone.to_string())]
+                    .into_iter()
+                    .collect(),
+            }
+            .request()
+            .await
+            .unwrap();
+        }
+
+        let uuid = "0b8b4b4e-8f44-4c8b-8cd2-4e3b4e0dbd3c";
+        let spread = Topology {
+            key: "zone".into(),
+            spread: true,
+            value: None,
+        };
+        // only two zones, so we cannot spread 3 replicas
+        CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 3,
+            topology: Some(spread.clone()),
+            ..Default::default()
+        }
+        .request()
+        .await
+        .expect_err("Only 2 zones available");
+
+        CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 2,
+            topology: Some(spread),
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        assert_eq!(replicas.len(), 2);
+        // one of the replicas must be on the only node from zone b
+        assert_eq!(
+            replicas
+                .iter()
+                .filter(|r| r.node.as_str() == mayastor3)
+                .count(),
+            1
+        );
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        // pinned to zone b
+        CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            topology: Some(Topology {
+                key: "zone".into(),
+                spread: false,
+                value: Some("b".into()),
+            }),
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        assert_eq!(replicas.len(), 1);
+        assert_eq!(replicas.first().unwrap().node.as_str(), mayastor3);
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_node_selector(mayastor2: &str) {
+        let labels: NodeLabels = vec![("tier".to_string(), "fast".to_string())]
+            .into_iter()
+            .collect();
+        SetNodeLabels {
+            node: mayastor2.into(),
+            labels: labels.clone(),
+        }
+        .request()
+        .await
+        .unwrap();
+
+        let uuid = "3f3c1b4e-5c1a-4b36-9f0c-0f2b4f9c0a11";
+        CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            node_selector: labels.clone(),
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        assert_eq!(replicas.len(), 1);
+        assert_eq!(replicas.first().unwrap().node.as_str(), mayastor2);
+
+        // only one node has the labels
+        CreateVolume {
+            uuid: "7d7c6a62-8b1e-4a3a-9b2b-2f5b1d0e4c22".into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 2,
+            node_selector: labels,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .expect_err("Only one node is selected");
+
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_affinity() {
+        let volume_a = "5a0e6c1e-2f4b-4a5e-8c3b-1d2e3f4a5b6c";
+        CreateVolume {
+            uuid: volume_a.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        let node_a = replicas.first().unwrap().node.clone();
+
+        // must be co-located with volume a
+        let volume_b = "6b1f7d2f-3a5c-4b6f-9d4c-2e3f4a5b6c7d";
+        CreateVolume {
+            uuid: volume_b.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            affinity: vec![volume_a.into()],
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        let replica_b = replicas.iter().find(|r| r.uuid.as_str() == volume_b);
+        assert_eq!(replica_b.unwrap().node, node_a);
+
+        // must not be co-located with volume a
+        let volume_c = "7c2a8e3a-4b6d-4c7a-8e5d-3f4a5b6c7d8e";
+        CreateVolume {
+            uuid: volume_c.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            anti_affinity: vec![volume_a.into()],
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        let replica_c = replicas.iter().find(|r| r.uuid.as_str() == volume_c);
+        assert_ne!(replica_c.unwrap().node, node_a);
+
+        // conflicting rules
+        let volume_d = "8d3b9f4b-5c7e-4d8b-9f6e-4a5b6c7d8e9f";
+        CreateVolume {
+            uuid: volume_d.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            affinity: vec![volume_a.into()],
+            anti_affinity: vec![volume_b.into()],
+            ..Default::default()
+        }
+        .request()
+        .await
+        .expect_err("Volumes a and b share the same node");
+        CreateVolume {
+            uuid: volume_d.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 1,
+            affinity: vec![volume_a.into()],
+            anti_affinity: vec![volume_a.into()],
+            ..Default::default()
+        }
+        .request()
+        .await
+        .expect_err("Volume a is in both rules");
+
+        for uuid in &[volume_a, volume_b, volume_c] {
+            DestroyVolume {
+                uuid: (*uuid).into(),
+            }
+            .request()
+            .await
+            .unwrap();
+        }
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
+
+    async fn test_volume_move_replica(
+        mayastor: &str,
+        mayastor2: &str,
+        mayastor3: &str,
+    ) {
+        let uuid = "9e4c0a5c-6d8f-4e9c-8a7f-5b6c7d8e9f0a";
+        let volume = CreateVolume {
+            uuid: uuid.into(),
+            size: 5242880,
+            nexuses: 1,
+            replicas: 2,
+            ..Default::default()
+        }
+        .request()
+        .await
+        .unwrap();
+        let nexus = volume.children.first().unwrap().clone();
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        let used = replicas.iter().map(|r| r.node.clone()).collect::<Vec<_>>();
+        let to_node: NodeId = [mayastor, mayastor2, mayastor3]
+            .iter()
+            .map(|n| NodeId::from(*n))
+            .find(|n| !used.contains(n))
+            .unwrap();
+        // move the replica which is not local to the nexus
+        let from_node = used.iter().find(|n| *n != &nexus.node).unwrap();
+
+        // cannot move onto a node which already has a replica
+        MoveReplica {
+            uuid: uuid.into(),
+            from_node: from_node.clone(),
+            to_node: nexus.node.clone(),
+        }
+        .request()
+        .await
+        .expect_err("Destination already holds a replica");
+
+        // keep checking that the volume remains usable during the move
+        let moving = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(
+            true,
+        ));
+        let watcher = tokio::spawn({
+            let moving = moving.clone();
+            async move {
+                while moving.load(std::sync::atomic::Ordering::Relaxed) {
+                    let volumes =
+                        GetVolumes::default().request().await.unwrap().0;
+                    let volume = volumes
+                        .iter()
+                        .find(|v| v.uuid.as_str() == uuid)
+                        .expect("Volume must exist during the move");
+                    assert_ne!(volume.state, VolumeState::Faulted);
+                    tokio::time::delay_for(
+                        std::time::Duration::from_millis(100),
+                    )
+                    .await;
+                }
+            }
+        });
+
+        let volume = MoveReplica {
+            uuid: uuid.into(),
+            from_node: from_node.clone(),
+            to_node: to_node.clone(),
+        }
+        .request()
+        .await
+        .unwrap();
+        moving.store(false, std::sync::atomic::Ordering::Relaxed);
+        watcher.await.unwrap();
+        tracing::info!("Moved volume replica: {:?}", volume);
+
+        let nexus = volume.children.first().unwrap();
+        assert_eq!(nexus.children.len(), 2);
+        assert!(nexus.children.iter().all(|c| c.state == ChildState::Online));
+        let replicas = GetReplicas::default().request().await.unwrap().0;
+        assert_eq!(replicas.len(), 2);
+        assert!(replicas.iter().any(|r| r.node == to_node));
+        assert!(!replicas.iter().any(|r| &r.node == from_node));
+
+        DestroyVolume {
+            uuid: uuid.into(),
+        }
+        .request()
+        .await
+        .unwrap();
+        assert!(GetReplicas::default().request().await.unwrap().0.is_empty());
+    }
 }
@@ -3,6 +3,11 @@
 use super::*;
 use common::wrapper::v0::*;
 
+/// Maximum time to wait for new replicas to be rebuilt
+const REBUILD_TIMEOUT_SECS: u64 = 60;
+/// Period at which the rebuild progress is polled
+const REBUILD_POLL_MS: u64 = 250;
+
 /// Volume service implementation methods
 #[derive(Clone, Debug, Default)]
 pub(super) struct VolumeSvc {
@@ -169,12 +174,25 @@ impl VolumeSvc {
             );
         }
 
+        // node labels used by the node selector and topology constraints
+        let nodes = self.registry.list_nodes().await;
+        let topology = request.topology.clone().unwrap_or_default();
+        let spread = request.topology.is_some() && topology.spread;
+        // nodes required/excluded by the volume affinity rules
+        let affinity = self.affinity_nodes(request).await?;
+
         // filter pools according to the following criteria (any order):
         // 1. if allowed_nodes were specified then only pools from those nodes
         // can be used.
-        // 2. pools should have enough free space for the
+        // 2. if a node_selector was specified then only pools from nodes with
+        // all of the selector labels can be used.
+        // 3. if a topology constraint was specified then only pools from nodes
+        // with the topology label (and value, if pinned) can be used.
+        // 4. if affinity rules were specified then only pools from nodes
+        // which satisfy them can be used.
+        // 5. pools should have enough free space for the
         // volume (do we need to take into account metadata?)
-        // 3. ideally use only healthy(online) pools with degraded pools as a
+        // 6. ideally use only healthy(online) pools with degraded pools as a
         // fallback
         let mut pools = pools
             .iter()
@@ -182,6 +200,25 @@ impl VolumeSvc {
                 // required nodes, if any
                 allowed_nodes.is_empty() || allowed_nodes.contains(&p.node())
             })
+            .filter(|&p| {
+                // required node labels, if any
+                node_selected(&nodes, &p.node(), &request.node_selector)
+            })
+            .filter(|&p| {
+                // required topology, if any
+                if request.topology.is_none() {
+                    return true;
+                }
+                let domain = topology_domain(&nodes, &p.node(), &topology.key);
+                match &topology.value {
+                    Some(value) => domain.as_ref() == Some(value),
+                    None => domain.is_some(),
+                }
+            })
+            .filter(|&p| {
+                // volume affinity rules, if any
+                affinity.allows(&p.node())
+            })
             .filter(|&p| {
                 // enough free space
                 p.free_space() >= size
@@ -202,12 +239,37 @@ impl VolumeSvc {
             .into());
         }
 
+        if spread {
+            let mut domains = pools
+                .iter()
+                .filter_map(|p| {
+                    topology_domain(&nodes, &p.node(), &topology.key)
+                })
+                .collect::<Vec<_>>();
+            domains.sort();
+            domains.dedup();
+            // refuse to place two replicas in the same topology domain
+            if replicas > domains.len() as u64 {
+                return Err(NotEnough::OfTopologyDomains {
+                    key: topology.key,
+                    have: domains.len() as u64,
+                    need: replicas,
+                }
+                .into());
+            }
+        }
+
         // sort pools from least to most suitable
         // state and then number of replicas and then free space
         pools.sort();
 
         let mut replicas = vec![];
+        let mut used_domains = vec![];
         while let Some(pool) = pools.pop() {
+            let domain = topology_domain(&nodes, &pool.node(), &topology.key);
+            if spread && used_domains.contains(&domain) {
+                continue;
+            }
             let create_replica = CreateReplica {
                 node: pool.node(),
                 uuid: ReplicaId::from(request.uuid.as_str()),
@@ -227,6 +289,7 @@ impl VolumeSvc {
             let replica = self.registry.create_replica(&create_replica).await;
             if let Ok(replica) = replica {
                 replicas.push(replica);
+                used_domains.push(domain);
             } else {
                 tracing::error!(
                     "Failed to create replica: {:?}. Trying other pools (if any available)...",
@@ -299,6 +362,71 @@ impl VolumeSvc {
         }
     }
 
+    /// Get the nodes which hold replicas of the volume `uuid`
+    async fn volume_nodes(&self, uuid: &VolumeId) -> Vec<NodeId> {
+        let replicas = self.registry.list_replicas().await;
+        let mut nodes = replicas
+            .into_iter()
+            .filter(|r| r.uuid.as_str() == uuid.as_str())
+            .map(|r| r.node)
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        nodes.dedup();
+        nodes
+    }
+
+    /// Evaluate the affinity rules of the volume `request` against the
+    /// already placed volumes
+    async fn affinity_nodes(
+        &self,
+        request: &CreateVolume,
+    ) -> Result<AffinityNodes, SvcError> {
+        let conflict = |reason: String| SvcError::AffinityConflict {
+            vol_id: request.uuid.to_string(),
+            reason,
+        };
+        let mut affinity = AffinityNodes::default();
+
+        for volume in &request.affinity {
+            if request.anti_affinity.contains(volume) {
+                return Err(conflict(format!(
+                    "volume '{}' is both in the affinity and anti-affinity rules",
+                    volume
+                )));
+            }
+            let nodes = self.volume_nodes(volume).await;
+            if nodes.is_empty() {
+                return Err(SvcError::VolumeNotFound {
+                    vol_id: volume.to_string(),
+                });
+            }
+            affinity.required = Some(match affinity.required {
+                None => nodes,
+                Some(required) => required
+                    .into_iter()
+                    .filter(|n| nodes.contains(n))
+                    .collect(),
+            });
+        }
+        for volume in &request.anti_affinity {
+            affinity.excluded.extend(self.volume_nodes(volume).await);
+        }
+
+        if let Some(required) = &affinity.required {
+            let candidates = required
+                .iter()
+                .filter(|n| !affinity.excluded.contains(n))
+                .count() as u64;
+            if candidates < request.replicas {
+                return Err(conflict(format!(
+                    "only {} node(s) satisfy the affinity rules but {} replica(s) are required",
+                    candidates, request.replicas
+                )));
+            }
+        }
+        Ok(affinity)
+    }
+
     /// Destroy volume
     #[tracing::instrument(level = "debug", err)]
     pub(super) async fn destroy_volume(
@@ -333,6 +461,676 @@ impl VolumeSvc {
                 }
             }
         }
+        // the volume might not be published, in which case its replicas are
+        // not part of any nexus
+        let replicas = self.registry.list_replicas().await;
+        for replica in replicas
+            .iter()
+            .filter(|r| r.uuid.as_str() == request.uuid.as_str())
+        {
+            self.registry
+                .destroy_replica(&DestroyReplica {
+                    node: replica.node.clone(),
+                    pool: replica.pool.clone(),
+                    uuid: replica.uuid.clone(),
+                })
+                .await?;
+        }
         Ok(())
     }
+
+    /// Publish volume
+    /// Idempotent when the volume is already published on the same node.
+    /// If published on another node, the request fails unless forced, in
+    /// which case the existing nexus is torn down first.
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn publish_volume(
+        &self,
+        request: &PublishVolume,
+    ) -> Result<String, SvcError> {
+        let nexus = self.volume_nexus(&request.uuid).await.ok();
+        let nexus = match nexus {
+            Some(nexus) if nexus.node == request.node => nexus,
+            Some(nexus) => {
+                if !request.force {
+                    return Err(SvcError::VolumeAlreadyPublished {
+                        vol_id: request.uuid.to_string(),
+                        node: nexus.node,
+                    });
+                }
+                self.teardown_nexus(&nexus).await?;
+                self.create_volume_nexus(&request.uuid, &request.node)
+                    .await?
+            }
+            None => {
+                self.create_volume_nexus(&request.uuid, &request.node)
+                    .await?
+            }
+        };
+
+        if !nexus.device_uri.is_empty() {
+            return Ok(nexus.device_uri);
+        }
+        self.registry
+            .share_nexus(&ShareNexus {
+                node: nexus.node.clone(),
+                uuid: nexus.uuid.clone(),
+                key: None,
+                protocol: Protocol::Nvmf,
+            })
+            .await
+    }
+
+    /// Unpublish volume
+    /// The nexus is unshared and destroyed but the replicas are left intact
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn unpublish_volume(
+        &self,
+        request: &UnpublishVolume,
+    ) -> Result<(), SvcError> {
+        match self.volume_nexus(&request.uuid).await {
+            Ok(nexus) => self.teardown_nexus(&nexus).await,
+            // nothing to do, not published
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Unshare, if shared, and destroy the `nexus`
+    async fn teardown_nexus(&self, nexus: &Nexus) -> Result<(), SvcError> {
+        if !nexus.device_uri.is_empty() {
+            self.registry
+                .unshare_nexus(&UnshareNexus {
+                    node: nexus.node.clone(),
+                    uuid: nexus.uuid.clone(),
+                })
+                .await?;
+        }
+        self.registry
+            .destroy_nexus(&DestroyNexus {
+                node: nexus.node.clone(),
+                uuid: nexus.uuid.clone(),
+            })
+            .await
+    }
+
+    /// Create the nexus for the volume `uuid` on `node`, using the volume's
+    /// existing replicas as its children.
+    /// Replicas local to the node are accessed directly and remote replicas
+    /// are shared over nvmf.
+    async fn create_volume_nexus(
+        &self,
+        uuid: &VolumeId,
+        node: &NodeId,
+    ) -> Result<Nexus, SvcError> {
+        let replicas = self.registry.list_replicas().await;
+        let replicas = replicas
+            .into_iter()
+            .filter(|r| r.uuid.as_str() == uuid.as_str())
+            .collect::<Vec<_>>();
+        let size = match replicas.first() {
+            Some(replica) => replica.size,
+            None => {
+                return Err(SvcError::VolumeNotFound {
+                    vol_id: uuid.to_string(),
+                })
+            }
+        };
+
+        let mut children = vec![];
+        for replica in &replicas {
+            let uri = self.replica_child_uri(replica, node).await?;
+            children.push(ChildUri::from(uri));
+        }
+
+        self.registry
+            .create_nexus(&CreateNexus {
+                node: node.clone(),
+                uuid: NexusId::from(uuid.as_str()),
+                size,
+                children,
+            })
+            .await
+    }
+
+    /// The URI of the `replica` as a child of a nexus on `node`.
+    /// A replica local to the node is accessed directly and a remote replica
+    /// is shared over nvmf.
+    async fn replica_child_uri(
+        &self,
+        replica: &Replica,
+        node: &NodeId,
+    ) -> Result<String, SvcError> {
+        if &replica.node == node {
+            if replica.share != Protocol::Off {
+                self.registry
+                    .unshare_replica(&UnshareReplica {
+                        node: replica.node.clone(),
+                        pool: replica.pool.clone(),
+                        uuid: replica.uuid.clone(),
+                    })
+                    .await?;
+            }
+            Ok(format!("bdev:///{}", replica.uuid))
+        } else if replica.share == Protocol::Off {
+            self.registry
+                .share_replica(&ShareReplica {
+                    node: replica.node.clone(),
+                    pool: replica.pool.clone(),
+                    uuid: replica.uuid.clone(),
+                    protocol: Protocol::Nvmf,
+                })
+                .await
+        } else {
+            Ok(replica.uri.clone())
+        }
+    }
+
+    /// Failover volume
+    /// The node where the volume is published is shutting down, so the
+    /// volume is also published on another node holding one of its replicas.
+    /// Both nexuses share the NQN of the volume, so multipath initiators
+    /// switch over to the new path while the node shutting down tears down
+    /// its own nexus. Idempotent when the volume is already published on
+    /// another node.
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn failover_volume(
+        &self,
+        request: &FailoverVolume,
+    ) -> Result<String, SvcError> {
+        let nexuses = self.registry.list_nexuses().await;
+        let nexuses = nexuses
+            .into_iter()
+            .filter(|n| n.uuid.as_str() == request.uuid.as_str())
+            .collect::<Vec<_>>();
+        if !nexuses.iter().any(|n| n.node == request.node) {
+            return Err(SvcError::VolumeNotPublished {
+                vol_id: request.uuid.to_string(),
+                node: request.node.clone(),
+            });
+        }
+
+        let nexus = match nexuses.into_iter().find(|n| n.node != request.node)
+        {
+            Some(nexus) if !nexus.device_uri.is_empty() => {
+                return Ok(nexus.device_uri)
+            }
+            Some(nexus) => nexus,
+            None => {
+                // the replicas on the node shutting down go away with it
+                let replicas = self.registry.list_replicas().await;
+                let replicas = replicas
+                    .into_iter()
+                    .filter(|r| {
+                        r.uuid.as_str() == request.uuid.as_str()
+                            && r.node != request.node
+                    })
+                    .collect::<Vec<_>>();
+                let node = match replicas.first() {
+                    Some(replica) => replica.node.clone(),
+                    None => {
+                        return Err(NotEnough::OfReplicas {
+                            have: 0,
+                            need: 1,
+                        }
+                        .into())
+                    }
+                };
+
+                // the replicas stay shared as the nexus on the node shutting
+                // down may still be writing to them
+                let mut children = vec![];
+                for replica in &replicas {
+                    let uri = if replica.share != Protocol::Off {
+                        replica.uri.clone()
+                    } else {
+                        self.replica_child_uri(replica, &node).await?
+                    };
+                    children.push(ChildUri::from(uri));
+                }
+
+                self.registry
+                    .create_nexus(&CreateNexus {
+                        node,
+                        uuid: NexusId::from(request.uuid.as_str()),
+                        size: replicas[0].size,
+                        children,
+                    })
+                    .await?
+            }
+        };
+
+        self.registry
+            .share_nexus(&ShareNexus {
+                node: nexus.node.clone(),
+                uuid: nexus.uuid.clone(),
+                key: None,
+                protocol: Protocol::Nvmf,
+            })
+            .await
+    }
+
+    /// Set the replica count of a volume
+    /// Scaling up creates new replicas on pools from nodes which do not yet
+    /// hold a replica of the volume and adds them to the nexus, waiting for
+    /// them to be rebuilt. Scaling down removes replicas from the nexus,
+    /// preferring unhealthy ones, but never the last healthy replica.
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn set_replica_count(
+        &self,
+        request: &SetReplicaCount,
+    ) -> Result<Volume, SvcError> {
+        if request.replicas == 0 {
+            return Err(SvcError::InvalidArguments {});
+        }
+        let nexus = self.volume_nexus(&request.uuid).await?;
+        let nexus = self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await?;
+        let current = nexus.children.len() as u64;
+
+        let nexus = match request.replicas.cmp(&current) {
+            std::cmp::Ordering::Equal => nexus,
+            std::cmp::Ordering::Greater => {
+                self.add_volume_replicas(
+                    &request.uuid,
+                    &nexus,
+                    request.replicas - current,
+                )
+                .await?
+            }
+            std::cmp::Ordering::Less => {
+                self.remove_volume_replicas(
+                    &request.uuid,
+                    &nexus,
+                    current - request.replicas,
+                )
+                .await?
+            }
+        };
+
+        Ok(Volume {
+            uuid: request.uuid.clone(),
+            size: nexus.size,
+            state: nexus.state.clone(),
+            children: vec![nexus],
+        })
+    }
+
+    /// Get the nexus of the volume `uuid`
+    async fn volume_nexus(&self, uuid: &VolumeId) -> Result<Nexus, SvcError> {
+        let nexuses = self.registry.list_nexuses().await;
+        nexuses
+            .into_iter()
+            .find(|n| n.uuid.as_str() == uuid.as_str())
+            .ok_or(SvcError::VolumeNotFound {
+                vol_id: uuid.to_string(),
+            })
+    }
+
+    /// Create `count` new replicas for the volume `uuid` and add them as
+    /// children of the volume's `nexus`, waiting for the rebuilds to complete
+    async fn add_volume_replicas(
+        &self,
+        uuid: &VolumeId,
+        nexus: &Nexus,
+        count: u64,
+    ) -> Result<Nexus, SvcError> {
+        let replicas = self.registry.list_replicas().await;
+        let used_nodes = replicas
+            .iter()
+            .filter(|r| r.uuid.as_str() == uuid.as_str())
+            .map(|r| r.node.clone())
+            .collect::<Vec<_>>();
+
+        // one replica per node, on healthy pools with enough free space
+        let pools = self.registry.fetch_pools_wrapper().await;
+        let mut pools = pools
+            .iter()
+            .filter(|&p| !used_nodes.contains(&p.node()))
+            .filter(|&p| p.free_space() >= nexus.size)
+            .filter(|&p| {
+                p.state() != PoolState::Faulted
+                    && p.state() != PoolState::Unknown
+            })
+            .collect::<Vec<_>>();
+        pools.sort();
+
+        let mut added = 0;
+        let mut nodes = vec![];
+        while let Some(pool) = pools.pop() {
+            if nodes.contains(&pool.node()) {
+                continue;
+            }
+            let create_replica = CreateReplica {
+                node: pool.node(),
+                uuid: ReplicaId::from(uuid.as_str()),
+                pool: pool.uuid(),
+                size: nexus.size,
+                thin: true,
+                share: if pool.node() == nexus.node {
+                    Protocol::Off
+                } else {
+                    Protocol::Nvmf
+                },
+            };
+            let replica = match self
+                .registry
+                .create_replica(&create_replica)
+                .await
+            {
+                Ok(replica) => replica,
+                Err(error) => {
+                    tracing::error!(
+                        "Failed to create replica: {:?}, error: {}. Trying other pools (if any available)...",
+                        create_replica,
+                        error.full_string()
+                    );
+                    continue;
+                }
+            };
+            nodes.push(replica.node.clone());
+
+            let add_child = AddNexusChild {
+                node: nexus.node.clone(),
+                nexus: nexus.uuid.clone(),
+                uri: replica.uri.clone().into(),
+                auto_rebuild: true,
+            };
+            if let Err(error) = self.registry.add_nexus_child(&add_child).await
+            {
+                let _ = self
+                    .registry
+                    .destroy_replica(&DestroyReplica {
+                        node: replica.node,
+                        pool: replica.pool,
+                        uuid: replica.uuid,
+                    })
+                    .await;
+                return Err(error);
+            }
+
+            added += 1;
+            if added == count {
+                break;
+            }
+        }
+
+        if added < count {
+            return Err(NotEnough::OfReplicas {
+                have: added,
+                need: count,
+            }
+            .into());
+        }
+
+        self.wait_nexus_rebuilt(nexus).await
+    }
+
+    /// Wait until all children of the `nexus` are online
+    async fn wait_nexus_rebuilt(
+        &self,
+        nexus: &Nexus,
+    ) -> Result<Nexus, SvcError> {
+        let timeout = std::time::Duration::from_secs(REBUILD_TIMEOUT_SECS);
+        let period = std::time::Duration::from_millis(REBUILD_POLL_MS);
+        let start = std::time::Instant::now();
+        loop {
+            let nexus =
+                self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await?;
+            if nexus.children.iter().all(|c| c.state == ChildState::Online) {
+                return Ok(nexus);
+            }
+            if start.elapsed() > timeout {
+                return Err(SvcError::RebuildTimeout {
+                    nexus_id: nexus.uuid.to_string(),
+                });
+            }
+            tokio::time::delay_for(period).await;
+        }
+    }
+
+    /// Move the replica of a volume from one node to another
+    /// The new replica is added to the volume's nexus and rebuilt before the
+    /// source replica is removed. If the rebuild fails the new replica is
+    /// discarded and the source replica is kept
+    #[tracing::instrument(level = "debug", err)]
+    pub(super) async fn move_replica(
+        &self,
+        request: &MoveReplica,
+    ) -> Result<Volume, SvcError> {
+        if request.from_node == request.to_node {
+            return Err(SvcError::InvalidArguments {});
+        }
+        let nexus = self.volume_nexus(&request.uuid).await?;
+        let nexus = self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await?;
+
+        let replicas = self.registry.list_replicas().await;
+        let replicas = replicas
+            .into_iter()
+            .filter(|r| r.uuid.as_str() == request.uuid.as_str())
+            .collect::<Vec<_>>();
+        let source = replicas
+            .iter()
+            .find(|r| r.node == request.from_node)
+            .ok_or(SvcError::VolumeReplicaNotFound {
+                vol_id: request.uuid.to_string(),
+                node: request.from_node.clone(),
+            })?;
+        if replicas.iter().any(|r| r.node == request.to_node) {
+            // one replica per node only
+            return Err(SvcError::InvalidArguments {});
+        }
+
+        let pools = self.registry.fetch_pools_wrapper().await;
+        let mut pools = pools
+            .iter()
+            .filter(|&p| p.node() == request.to_node)
+            .filter(|&p| p.free_space() >= nexus.size)
+            .filter(|&p| {
+                p.state() != PoolState::Faulted
+                    && p.state() != PoolState::Unknown
+            })
+            .collect::<Vec<_>>();
+        pools.sort();
+        let pool = pools.pop().ok_or(NotEnough::OfPools {
+            have: 0,
+            need: 1,
+        })?;
+
+        let replica = self
+            .registry
+            .create_replica(&CreateReplica {
+                node: pool.node(),
+                uuid: source.uuid.clone(),
+                pool: pool.uuid(),
+                size: nexus.size,
+                thin: source.thin,
+                share: if pool.node() == nexus.node {
+                    Protocol::Off
+                } else {
+                    Protocol::Nvmf
+                },
+            })
+            .await?;
+        let destroy_replica = DestroyReplica {
+            node: replica.node.clone(),
+            pool: replica.pool.clone(),
+            uuid: replica.uuid.clone(),
+        };
+
+        let add_child = AddNexusChild {
+            node: nexus.node.clone(),
+            nexus: nexus.uuid.clone(),
+            uri: replica.uri.clone().into(),
+            auto_rebuild: true,
+        };
+        if let Err(error) = self.registry.add_nexus_child(&add_child).await {
+            let _ = self.registry.destroy_replica(&destroy_replica).await;
+            return Err(error);
+        }
+
+        if let Err(error) = self.wait_child_rebuilt(&nexus, &replica.uri).await
+        {
+            // keep the source replica, discard the new one
+            let _ = self
+                .registry
+                .remove_nexus_child(&RemoveNexusChild {
+                    node: nexus.node.clone(),
+                    nexus: nexus.uuid.clone(),
+                    uri: add_child.uri,
+                })
+                .await;
+            let _ = self.registry.destroy_replica(&destroy_replica).await;
+            return Err(error);
+        }
+
+        // the new replica is healthy, the source can now be removed
+        let source_child = nexus
+            .children
+            .iter()
+            .find(|c| c.uri.as_str() == source.uri.as_str());
+        if let Some(child) = source_child {
+            self.registry
+                .remove_nexus_child(&RemoveNexusChild {
+                    node: nexus.node.clone(),
+                    nexus: nexus.uuid.clone(),
+                    uri: child.uri.clone(),
+                })
+                .await?;
+        }
+        self.registry
+            .destroy_replica(&DestroyReplica {
+                node: source.node.clone(),
+                pool: source.pool.clone(),
+                uuid: source.uuid.clone(),
+            })
+            .await?;
+
+        let nexus = self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await?;
+        Ok(Volume {
+            uuid: request.uuid.clone(),
+            size: nexus.size,
+            state: nexus.state.clone(),
+            children: vec![nexus],
+        })
+    }
+
+    /// Wait until the child `uri` of the `nexus` is rebuilt
+    async fn wait_child_rebuilt(
+        &self,
+        nexus: &Nexus,
+        uri: &str,
+    ) -> Result<Nexus, SvcError> {
+        let timeout = std::time::Duration::from_secs(REBUILD_TIMEOUT_SECS);
+        let period = std::time::Duration::from_millis(REBUILD_POLL_MS);
+        let start = std::time::Instant::now();
+        loop {
+            let nexus =
+                self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await?;
+            let state = nexus
+                .children
+                .iter()
+                .find(|c| c.uri.as_str() == uri)
+                .map(|c| c.state.clone());
+            match state {
+                Some(ChildState::Online) => return Ok(nexus),
+                Some(ChildState::Faulted) | None => {
+                    return Err(SvcError::RebuildFailed {
+                        nexus_id: nexus.uuid.to_string(),
+                        child: uri.to_string(),
+                    })
+                }
+                _ => {}
+            }
+            if start.elapsed() > timeout {
+                return Err(SvcError::RebuildTimeout {
+                    nexus_id: nexus.uuid.to_string(),
+                });
+            }
+            tokio::time::delay_for(period).await;
+        }
+    }
+
+    /// Remove `count` replicas from the volume `uuid` and its `nexus`
+    /// Unhealthy replicas are removed first and at least one healthy replica
+    /// is always kept
+    async fn remove_volume_replicas(
+        &self,
+        uuid: &VolumeId,
+        nexus: &Nexus,
+        count: u64,
+    ) -> Result<Nexus, SvcError> {
+        let mut children = nexus.children.clone();
+        // healthy children last, so we pick the unhealthy ones first
+        children.sort_by_key(|c| c.state == ChildState::Online);
+        let (remove, keep) = children.split_at(count as usize);
+        if !keep.iter().any(|c| c.state == ChildState::Online) {
+            return Err(SvcError::LastHealthyReplica {
+                vol_id: uuid.to_string(),
+            });
+        }
+
+        let replicas = self.registry.list_replicas().await;
+        for child in remove {
+            self.registry
+                .remove_nexus_child(&RemoveNexusChild {
+                    node: nexus.node.clone(),
+                    nexus: nexus.uuid.clone(),
+                    uri: child.uri.clone(),
+                })
+                .await?;
+            let replica = replicas
+                .iter()
+                .find(|r| r.uri.as_str() == child.uri.as_str());
+            if let Some(replica) = replica {
+                self.registry
+                    .destroy_replica(&DestroyReplica {
+                        node: replica.node.clone(),
+                        pool: replica.pool.clone(),
+                        uuid: replica.uuid.clone(),
+                    })
+                    .await?;
+            }
+        }
+
+        self.registry.fetch_nexus(&nexus.node, &nexus.uuid).await
+    }
+}
+
+/// Nodes required and excluded by the affinity rules of a volume
+#[derive(Debug, Default)]
+struct AffinityNodes {
+    /// if set, replicas may only be placed on these nodes
+    required: Option<Vec<NodeId>>,
+    /// replicas may not be placed on these nodes
+    excluded: Vec<NodeId>,
+}
+
+impl AffinityNodes {
+    /// Check whether a replica may be placed on the node `node`
+    fn allows(&self, node: &NodeId) -> bool {
+        let required = match &self.required {
+            Some(required) => required.contains(node),
+            None => true,
+        };
+        required && !self.excluded.contains(node)
+    }
+}
+
+/// Get the value of the topology label `key` of the node `node`
+fn topology_domain(nodes: &[Node], node: &NodeId, key: &str) -> Option<String> {
+    nodes
+        .iter()
+        .find(|n| &n.id == node)
+        .and_then(|n| n.labels.get(key).cloned())
+}
+
+/// Check whether the node `node` has all the labels from the `selector`
+fn node_selected(nodes: &[Node], node: &NodeId, selector: &NodeLabels) -> bool {
+    if selector.is_empty() {
+        return true;
+    }
+    nodes
+        .iter()
+        .find(|n| &n.id == node)
+        .map(|n| selector.iter().all(|(k, v)| n.labels.get(k) == Some(v)))
+        .unwrap_or(false)
 }
@@ -77,7 +77,9 @@ fn main() {
         .whitelist_function("*.uring.*")
         .whitelist_function("^iscsi.*")
         .whitelist_function("^spdk.*")
+        .whitelist_function("create_delay_disk")
         .whitelist_function("create_malloc_disk")
+        .whitelist_function("delete_delay_disk")
         .whitelist_function("delete_malloc_disk")
         .whitelist_function("^bdev.*")
         .whitelist_function("^nbd_.*")
@@ -85,6 +87,7 @@ fn main() {
         .whitelist_function("^nvme_cmd_.*")
         .whitelist_function("^nvme_status_.*")
         .whitelist_function("^nvmf_tgt_accept")
+        .whitelist_function("^nvmf_subsystem_set_ana_state")
         .blacklist_type("^longfunc")
         .whitelist_var("^NVMF.*")
         .whitelist_var("^SPDK.*")
@@ -1,5 +1,6 @@
 #include <bdev/aio/bdev_aio.h>
 #include <bdev/crypto/vbdev_crypto.h>
+#include <bdev/delay/vbdev_delay.h>
 #include <bdev/error/vbdev_error.h>
 #include <bdev/iscsi/bdev_iscsi.h>
 #include <bdev/lvol/vbdev_lvol.h>
@@ -16,6 +17,8 @@
 #include <spdk/bdev_module.h>
 #include <spdk/conf.h>
 #include <spdk/cpuset.h>
+#include <spdk/crc32.h>
+#include <spdk/dif.h>
 #include <spdk/env.h>
 #include <spdk/env_dpdk.h>
 #include <spdk/event.h>
@@ -23,6 +26,7 @@
 #include <spdk/log.h>
 #include <spdk/lvol.h>
 #include <spdk/nbd.h>
+#include <spdk/notify.h>
 #include <spdk/nvme.h>
 #include <spdk/nvmf.h>
 #include <nvmf/nvmf_internal.h>
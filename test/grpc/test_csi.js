@@ -142,6 +142,43 @@ function getFsType (mp) {
   }
 }
 
+// Get number of nvmf controllers connected to the target with given URI,
+// or undefined if the URI is not an nvmf URI.
+function countNvmfSessions (uri) {
+  const url = new URL(uri);
+  if (url.protocol !== 'nvmf:') {
+    return undefined;
+  }
+  const nqn = url.pathname.split('/')[1];
+  const ctlDir = '/sys/class/nvme-fabrics/ctl';
+  if (!fs.existsSync(ctlDir)) {
+    return 0;
+  }
+  return fs.readdirSync(ctlDir).filter((ctl) => {
+    try {
+      const file = path.join(ctlDir, ctl, 'subsysnqn');
+      return fs.readFileSync(file).toString().trim() === nqn;
+    } catch (err) {
+      return false;
+    }
+  }).length;
+}
+
+// Get mount options for given mount point.
+function getMountOptions (mp) {
+  const lines = fs
+    .readFileSync('/proc/mounts')
+    .toString()
+    .trim()
+    .split('\n');
+  for (let i = 0; i < lines.length; i++) {
+    const cols = lines[i].split(' ');
+    if (mp === cols[1]) {
+      return cols[3].split(',');
+    }
+  }
+}
+
 describe('csi', function () {
   this.timeout(10000); // for network tests we need long timeouts
 
@@ -445,6 +482,22 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
         client.nodeStageVolume(getDefaultArgs(), done);
       });
 
+      it('staging the same volume repeatedly should not connect it again', (done) => {
+        async.timesSeries(
+          3,
+          (n, next) => client.nodeStageVolume(getDefaultArgs(), next),
+          (err) => {
+            if (err) return done(err);
+            assert.equal(getFsType(mountTarget), 'xfs');
+            const sessions = countNvmfSessions(publishedUris[UUID1].uri);
+            if (sessions !== undefined) {
+              assert.equal(sessions, 1);
+            }
+            done();
+          }
+        );
+      });
+
       it('staging a volume with the same staging path but with a different bdev should fail', (done) => {
         const args = getDefaultArgs();
         args.volume_id = UUID2;
@@ -523,6 +576,24 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
           }
         );
       });
+
+      it('unstaging the same volume again should return ok (idempotent)', (done) => {
+        client.nodeUnstageVolume(
+          {
+            volume_id: UUID1,
+            staging_target_path: mountTarget
+          },
+          (err) => {
+            if (err) return done(err);
+            assert.isUndefined(getFsType(mountTarget));
+            const sessions = countNvmfSessions(publishedUris[UUID1].uri);
+            if (sessions !== undefined) {
+              assert.equal(sessions, 0);
+            }
+            done();
+          }
+        );
+      });
     });
 
     describe('stage and unstage ext4 volume', function () {
@@ -583,6 +654,62 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
           }
         );
       });
+
+      it('should not reformat ext4 volume as xfs', (done) => {
+        client.nodeStageVolume(
+          {
+            volume_id: UUID2,
+            publish_context: publishedUris[UUID2],
+            staging_target_path: mountTarget,
+            volume_capability: {
+              access_mode: {
+                mode: 'MULTI_NODE_READER_ONLY'
+              },
+              mount: {
+                fs_type: 'xfs'
+              }
+            },
+            readonly: false,
+            secrets: {},
+            volume_context: {}
+          },
+          shouldFailWith(grpc.status.INTERNAL, (err) => {
+            if (err) return done(err);
+            assert.isUndefined(getFsType(mountTarget));
+            done();
+          })
+        );
+      });
+
+      it('should stage volume with fsType from volume context (ext4)', (done) => {
+        client.nodeStageVolume(
+          {
+            volume_id: UUID2,
+            publish_context: publishedUris[UUID2],
+            staging_target_path: mountTarget,
+            volume_capability: {
+              access_mode: {
+                mode: 'MULTI_NODE_READER_ONLY'
+              },
+              mount: {}
+            },
+            readonly: false,
+            secrets: {},
+            volume_context: { fsType: 'ext4' }
+          },
+          (err) => {
+            if (err) return done(err);
+            assert.equal(getFsType(mountTarget), 'ext4');
+            client.nodeUnstageVolume(
+              {
+                volume_id: UUID2,
+                staging_target_path: mountTarget
+              },
+              done
+            );
+          }
+        );
+      });
     });
 
     describe('stage misc', function () {
@@ -623,6 +750,57 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
           shouldFailWith(grpc.status.INVALID_ARGUMENT, done)
         );
       });
+
+      it('should fail to stage with mount option changing the mount', (done) => {
+        const args = {
+          volume_id: UUID3,
+          publish_context: publishedUris[UUID3],
+          staging_target_path: mountTarget,
+          volume_capability: {
+            access_mode: {
+              mode: 'MULTI_NODE_READER_ONLY'
+            },
+            mount: {
+              fs_type: 'xfs',
+              mount_flags: ['bind']
+            }
+          }
+        };
+        client.nodeStageVolume(
+          args,
+          shouldFailWith(grpc.status.INVALID_ARGUMENT, done)
+        );
+      });
+
+      it('should stage volume with mount options', (done) => {
+        const args = {
+          volume_id: UUID3,
+          publish_context: publishedUris[UUID3],
+          staging_target_path: mountTarget,
+          volume_capability: {
+            access_mode: {
+              mode: 'MULTI_NODE_READER_ONLY'
+            },
+            mount: {
+              fs_type: 'xfs',
+              mount_flags: ['noatime', 'discard']
+            }
+          }
+        };
+        client.nodeStageVolume(args, (err) => {
+          if (err) return done(err);
+          const options = getMountOptions(mountTarget);
+          assert.include(options, 'noatime');
+          assert.include(options, 'discard');
+          client.nodeUnstageVolume(
+            {
+              volume_id: UUID3,
+              staging_target_path: mountTarget
+            },
+            done
+          );
+        });
+      });
     });
 
     // The combinations of ro/rw and access mode flags are quite confusing.
@@ -791,6 +969,34 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
           );
         });
 
+        it('should fail to publish ephemeral volume without message bus', (done) => {
+          const args = {
+            volume_id: 'csi-ephemeral-test',
+            target_path: bindTarget2,
+            volume_capability: {
+              access_mode: {
+                mode: 'SINGLE_NODE_WRITER'
+              },
+              mount: {
+                fs_type: 'xfs',
+                mnt_flags: []
+              }
+            },
+            volume_context: {
+              'csi.storage.k8s.io/ephemeral': 'true'
+            }
+          };
+
+          client.nodePublishVolume(
+            args,
+            shouldFailWith(grpc.status.FAILED_PRECONDITION, (err) => {
+              if (err) return done(err);
+              assert.isUndefined(getFsType(bindTarget2));
+              done();
+            })
+          );
+        });
+
         it('should be able to unpublish ro volume', (done) => {
           client.nodeUnpublishVolume(
             {
@@ -1149,11 +1355,41 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
             if (err) return done(err);
             assert.equal(fs.existsSync(publishPath1), true);
             assert.equal(getFsType(publishPath1), 'devtmpfs');
+            assert.isTrue(fs.statSync(publishPath1).isBlockDevice());
             // re-publish should succeed (idempotent)
             client.nodePublishVolume(args, done);
           });
         });
 
+        it('should fail to publish a block volume on a directory', (done) => {
+          const dirPath = '/tmp/blockvol-dir';
+          const args = {
+            volume_id: UUID4,
+            publish_context: publishedUris[UUID4],
+            staging_target_path: stagingPath,
+            target_path: dirPath,
+            volume_capability: {
+              access_mode: {
+                mode: 'MULTI_NODE_READER_ONLY'
+              },
+              block: {
+              }
+            },
+            readonly: true
+          };
+
+          cleanPublishDir(dirPath, () => {
+            createPublishDir(dirPath);
+            client.nodePublishVolume(
+              args,
+              shouldFailWith(grpc.status.INVALID_ARGUMENT, (err) => {
+                assert.isUndefined(getFsType(dirPath));
+                cleanPublishDir(dirPath, () => done(err));
+              })
+            );
+          });
+        });
+
         it('should fail when publishing another volume on the same target path', (done) => {
           const args = {
             volume_id: UUID5,
@@ -1360,6 +1596,7 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
             if (err) return done(err);
             assert.equal(fs.existsSync(publishPath1), true);
             assert.equal(getFsType(publishPath1), 'devtmpfs');
+            assert.isTrue(fs.statSync(publishPath1).isBlockDevice());
             // re-publish should succeed (idempotent)
             client.nodePublishVolume(args, done);
           });
@@ -1384,6 +1621,7 @@ function csiProtocolTest (protoname, shareType, timeoutMillis) {
             if (err) return done(err);
             assert.equal(fs.existsSync(publishPath2), true);
             assert.equal(getFsType(publishPath2), 'devtmpfs');
+            assert.isTrue(fs.statSync(publishPath2).isBlockDevice());
             done();
           });
         });